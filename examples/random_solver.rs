@@ -0,0 +1,27 @@
+//! An external solver that wraps the built-in random solver.
+//!
+//! The command-line arguments are the same as `kurobako solver random`, so this program can be used as follows:
+//!
+//! ```console
+//! $ cargo build --example random_solver
+//! $ kurobako solver command target/debug/examples/random_solver
+//! ```
+#[macro_use]
+extern crate trackable;
+
+use kurobako::problem::KurobakoProblemRecipe;
+use kurobako::solver::KurobakoSolverRecipe;
+use kurobako_core::epi::solver::run_solver_server;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::solver::SolverRecipe as _;
+use kurobako_solvers::random::RandomSolverRecipe;
+use structopt::StructOpt as _;
+use trackable::result::TopLevelResult;
+
+fn main() -> TopLevelResult {
+    let recipe = RandomSolverRecipe::from_args();
+    let registry = FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
+    let factory = track!(recipe.create_factory(&registry))?;
+    track!(run_solver_server(factory))?;
+    Ok(())
+}
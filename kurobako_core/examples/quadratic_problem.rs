@@ -0,0 +1,65 @@
+//! An external problem that minimizes `(x - 2)^2 + (y + 1)^2`.
+//!
+//! This program can be used via `ExternalProgramProblemRecipe`:
+//!
+//! ```console
+//! $ cargo build --example quadratic_problem
+//! $ kurobako problem command target/debug/examples/quadratic_problem
+//! ```
+#[macro_use]
+extern crate trackable;
+
+use kurobako_core::domain::var;
+use kurobako_core::epi::problem::run_problem_server;
+use kurobako_core::problem::{Evaluator, Problem, ProblemFactory, ProblemSpec, ProblemSpecBuilder};
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use trackable::result::TopLevelResult;
+
+struct QuadraticProblemFactory;
+impl ProblemFactory for QuadraticProblemFactory {
+    type Problem = QuadraticProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        track!(ProblemSpecBuilder::new("Quadratic")
+            .param(var("x").continuous(-10.0, 10.0))
+            .param(var("y").continuous(-10.0, 10.0))
+            .value(var("Objective Value"))
+            .finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        Ok(QuadraticProblem)
+    }
+}
+
+struct QuadraticProblem;
+impl Problem for QuadraticProblem {
+    type Evaluator = QuadraticEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        track_assert_eq!(params.len(), 2, ErrorKind::InvalidInput);
+        track_assert!(
+            params.iter().all(|p| (-10.0..=10.0).contains(p)),
+            ErrorKind::UnevaluableParams,
+            "Out of range parameters: {:?}",
+            params
+        );
+        Ok(QuadraticEvaluator(params))
+    }
+}
+
+struct QuadraticEvaluator(Params);
+impl Evaluator for QuadraticEvaluator {
+    fn evaluate(&mut self, _next_step: u64) -> Result<(u64, Values)> {
+        let (x, y) = (self.0[0], self.0[1]);
+        let value = (x - 2.0).powi(2) + (y + 1.0).powi(2);
+        Ok((1, Values::new(vec![value])))
+    }
+}
+
+fn main() -> TopLevelResult {
+    track!(run_problem_server(QuadraticProblemFactory))?;
+    Ok(())
+}
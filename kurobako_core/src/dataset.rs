@@ -0,0 +1,215 @@
+//! Local cache of the datasets used by problems.
+//!
+//! Datasets are downloaded by `kurobako dataset get <DATASET>` to the cache directory.
+//! Its location is `$KUROBAKO_DATASET_DIR` if the environment variable is set,
+//! otherwise `$XDG_CACHE_HOME/kurobako` or `~/.cache/kurobako`.
+//!
+//! Problems never download datasets by themselves, and `kurobako dataset` commands
+//! don't access the network either if `$KUROBAKO_OFFLINE` is set to `1` (or `--offline` is specified).
+use crate::{Error, ErrorKind, Result};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the environment variable that overrides the default cache directory.
+pub const DATASET_DIR_ENV: &str = "KUROBAKO_DATASET_DIR";
+
+/// Name of the environment variable that enables the offline mode.
+pub const OFFLINE_ENV: &str = "KUROBAKO_OFFLINE";
+
+/// Returns `true` if the offline mode is enabled by the environment variable.
+pub fn is_offline() -> bool {
+    env::var(OFFLINE_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Dataset cache directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetCache {
+    dir: PathBuf,
+}
+impl DatasetCache {
+    /// Makes a new `DatasetCache` instance rooted at the given directory.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Makes a new `DatasetCache` instance rooted at the default directory.
+    pub fn open_default() -> Result<Self> {
+        if let Some(dir) = env::var_os(DATASET_DIR_ENV) {
+            return Ok(Self::new(dir));
+        }
+        if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+            return Ok(Self::new(Path::new(&dir).join("kurobako")));
+        }
+        let home = track_assert_some!(
+            env::var_os("HOME"),
+            ErrorKind::Other,
+            "Cannot determine the dataset cache directory (set ${})",
+            DATASET_DIR_ENV
+        );
+        Ok(Self::new(Path::new(&home).join(".cache/kurobako")))
+    }
+
+    /// Returns the root directory of this cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the directory of the given dataset.
+    pub fn dataset_dir(&self, dataset: &str) -> PathBuf {
+        self.dir.join(dataset)
+    }
+
+    /// Resolves `path_or_name` to a local file path.
+    ///
+    /// If `path_or_name` is an existing path, it is returned as-is.
+    /// Otherwise, if it is one of the names in `files` (pairs of a name and
+    /// a file path relative to the dataset directory), the cached file is returned.
+    ///
+    /// `ErrorKind::DatasetNotFound` is returned if the resolved file doesn't exist.
+    pub fn resolve(
+        &self,
+        path_or_name: &Path,
+        dataset: &str,
+        files: &[(&str, &str)],
+    ) -> Result<PathBuf> {
+        if path_or_name.exists() {
+            return Ok(path_or_name.to_path_buf());
+        }
+
+        let name = path_or_name.to_str();
+        if let Some((_, file)) = files.iter().find(|(n, _)| Some(*n) == name) {
+            let path = self.dataset_dir(dataset).join(file);
+            track_assert!(
+                path.exists(),
+                ErrorKind::DatasetNotFound,
+                "Dataset {:?} is not found in {:?} (run `kurobako dataset get {}`)",
+                path_or_name,
+                self.dir,
+                dataset
+            );
+            Ok(path)
+        } else {
+            track_panic!(
+                ErrorKind::DatasetNotFound,
+                "Dataset file {:?} is not found",
+                path_or_name
+            );
+        }
+    }
+}
+
+/// Resolves `path_or_name` to a local file path using the default cache directory.
+///
+/// See [`DatasetCache::resolve`] for details.
+pub fn resolve(path_or_name: &Path, dataset: &str, files: &[(&str, &str)]) -> Result<PathBuf> {
+    if path_or_name.exists() {
+        return Ok(path_or_name.to_path_buf());
+    }
+    let cache = track!(DatasetCache::open_default())?;
+    track!(cache.resolve(path_or_name, dataset, files))
+}
+
+/// Calculates the SHA-256 checksum of the given file and returns it as a lowercase hex string.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let file = track!(File::open(path).map_err(Error::from); path)?;
+    let mut hasher = Sha256::new();
+    track!(io::copy(&mut BufReader::new(file), &mut hasher).map_err(Error::from); path)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Writes the header of a versioned binary dataset file.
+///
+/// The header consists of the eight bytes magic number followed by the format version.
+pub fn write_header<W: Write>(mut writer: W, magic: &[u8; 8], version: u32) -> Result<()> {
+    track!(writer.write_all(magic).map_err(Error::from))?;
+    track!(writer
+        .write_all(&version.to_le_bytes())
+        .map_err(Error::from))?;
+    Ok(())
+}
+
+/// Reads the header written by [`write_header`] and returns the format version.
+///
+/// `None` is returned if the data does not start with the given magic number
+/// (i.e., it is not a binary dataset file of the expected kind).
+pub fn read_header<R: Read>(mut reader: R, magic: &[u8; 8]) -> Result<Option<u32>> {
+    let mut buf = [0; 12];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(track!(Error::from(e))),
+    }
+    if &buf[..8] != magic {
+        return Ok(None);
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&buf[8..]);
+    Ok(Some(u32::from_le_bytes(version)))
+}
+
+/// Reads the header of the given file.
+///
+/// See [`read_header`] for details.
+pub fn read_file_header<P: AsRef<Path>>(path: P, magic: &[u8; 8]) -> Result<Option<u32>> {
+    let path = path.as_ref();
+    let file = track!(File::open(path).map_err(Error::from); path)?;
+    track!(read_header(file, magic); path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn resolve_works() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let cache = DatasetCache::new(dir.path());
+        let files = &[("foo", "foo/data.bin")];
+
+        let e = cache.resolve(Path::new("foo"), "bar", files).err();
+        assert_eq!(e.map(|e| *e.kind()), Some(ErrorKind::DatasetNotFound));
+
+        track!(std::fs::create_dir_all(dir.path().join("bar/foo")).map_err(Error::from))?;
+        track!(File::create(dir.path().join("bar/foo/data.bin")).map_err(Error::from))?;
+        let path = track!(cache.resolve(Path::new("foo"), "bar", files))?;
+        assert_eq!(path, dir.path().join("bar/foo/data.bin"));
+
+        let explicit = dir.path().join("bar/foo/data.bin");
+        assert_eq!(track!(cache.resolve(&explicit, "bar", files))?, explicit);
+        assert!(cache.resolve(Path::new("unknown"), "bar", files).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn header_works() -> TopLevelResult {
+        let mut buf = Vec::new();
+        track!(write_header(&mut buf, b"KRBKTEST", 3))?;
+        buf.extend_from_slice(b"payload");
+        assert_eq!(track!(read_header(&buf[..], b"KRBKTEST"))?, Some(3));
+        assert_eq!(track!(read_header(&buf[..], b"KRBKXXXX"))?, None);
+        assert_eq!(track!(read_header(&buf[..4], b"KRBKTEST"))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_file_works() -> TopLevelResult {
+        let mut file = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(file.write_all(b"abc").map_err(Error::from))?;
+        assert_eq!(
+            track!(sha256_file(file.path()))?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        Ok(())
+    }
+}
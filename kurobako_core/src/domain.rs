@@ -1,14 +1,19 @@
 //! Domain of parameter and objective values.
+use crate::solver::{Capabilities, Capability};
+use crate::trial::Params;
 use crate::{Error, ErrorKind, Result};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use structopt::StructOpt;
 
 /// Domain.
 ///
 /// A `Domain` instance consists of a vector of `Variable`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Domain(Vec<Variable>);
 
 #[allow(clippy::len_without_is_empty)]
@@ -27,12 +32,112 @@ impl Domain {
                 "Duplicate name: {:?}",
                 v.name
             );
+            if let Some(condition) = &v.condition {
+                track!(condition.validate(&vars); v.name)?;
+            }
 
             vars.push(v);
         }
         Ok(Self(vars))
     }
 
+    /// Expands the given mutually exclusive variable groups into conditions of the member variables.
+    ///
+    /// Each member variable gets a condition that holds only if the selector of its group
+    /// is one of the choices the member belongs to (`Condition::Eq` or `Condition::In`).
+    /// If `variables` doesn't contain the selector, it is inserted before the first member of the group.
+    ///
+    /// The members must be unconditional and a variable can't belong to two groups,
+    /// but the selector of a group can be a member of a succeeding group (i.e., groups can be nested).
+    pub fn group(
+        mut variables: Vec<VariableBuilder>,
+        groups: &[Group],
+    ) -> Result<Vec<VariableBuilder>> {
+        let mut grouped = BTreeMap::new();
+        for group in groups {
+            track_assert!(
+                !group.choices.is_empty(),
+                ErrorKind::InvalidInput,
+                "Group without choices: {:?}",
+                group.selector
+            );
+            let names = group
+                .choices
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>();
+
+            let selector = variables.iter().position(|v| v.name == group.selector);
+            if let Some(i) = selector {
+                let choices = match &variables[i].range {
+                    Range::Categorical { choices, .. } => choices,
+                    _ => track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Non-categorical group selector: {:?}",
+                        group.selector
+                    ),
+                };
+                track_assert_eq!(choices, &names, ErrorKind::InvalidInput; group.selector);
+            }
+
+            let mut first_member = None;
+            for (i, v) in variables.iter_mut().enumerate() {
+                let values = group
+                    .choices
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| c.members.contains(&v.name))
+                    .map(|(j, _)| j as f64)
+                    .collect::<Vec<_>>();
+                if values.is_empty() {
+                    continue;
+                }
+                if let Some(other) = grouped.insert(v.name.clone(), group.selector.clone()) {
+                    track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Variable {:?} belongs to two groups: {:?} and {:?}",
+                        v.name,
+                        other,
+                        group.selector
+                    );
+                }
+                track_assert!(
+                    v.condition.is_none(),
+                    ErrorKind::InvalidInput,
+                    "Group member {:?} already has a condition",
+                    v.name
+                );
+                first_member.get_or_insert(i);
+                v.condition = Some(if values.len() == 1 {
+                    Condition::Eq {
+                        target: group.selector.clone(),
+                        value: values[0],
+                    }
+                } else {
+                    Condition::In {
+                        target: group.selector.clone(),
+                        values,
+                    }
+                });
+            }
+            for member in group.choices.iter().flat_map(|c| &c.members) {
+                track_assert!(
+                    variables.iter().any(|v| v.name == *member),
+                    ErrorKind::InvalidInput,
+                    "Unknown member {:?} of group {:?}",
+                    member,
+                    group.selector
+                );
+            }
+
+            if selector.is_none() {
+                let i = first_member.unwrap_or(variables.len());
+                variables.insert(i, group.selector_variable());
+            }
+        }
+        Ok(variables)
+    }
+
     /// Returns a reference to the variables in this domain.
     pub fn variables(&self) -> &[Variable] {
         &self.0
@@ -42,6 +147,184 @@ impl Domain {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// Returns the index of the variable that has the given name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.0.iter().position(|v| v.name == name)
+    }
+
+    /// Returns a copy of this domain in which all the variables are unconditional.
+    pub fn unconditional(&self) -> Self {
+        let mut vars = self.0.clone();
+        for v in &mut vars {
+            v.condition = None;
+        }
+        Self(vars)
+    }
+
+    /// Converts the given positional parameters to a map keyed by variable names.
+    pub fn to_map(&self, params: &Params) -> Result<BTreeMap<String, ParamValueView>> {
+        track_assert_eq!(self.0.len(), params.len(), ErrorKind::InvalidInput);
+
+        let mut map = BTreeMap::new();
+        for (var, &val) in self.0.iter().zip(params.iter()) {
+            let view = if val.is_nan() {
+                ParamValueView::Inactive
+            } else if let Range::Categorical { choices, .. } = &var.range {
+                let choice = track_assert_some!(
+                    choices.get(val as usize),
+                    ErrorKind::InvalidInput;
+                    var.name, val
+                );
+                ParamValueView::Categorical(choice.clone())
+            } else if let Range::Ordinal { values } = &var.range {
+                let value = track_assert_some!(
+                    values.get(val as usize),
+                    ErrorKind::InvalidInput;
+                    var.name, val
+                );
+                ParamValueView::Numerical(*value)
+            } else {
+                ParamValueView::Numerical(val)
+            };
+            map.insert(var.name.clone(), view);
+        }
+        Ok(map)
+    }
+
+    /// Converts the given map keyed by variable names to positional parameters.
+    ///
+    /// Conditional variables missing in the map are regarded as inactive.
+    pub fn params_from_map(&self, map: &BTreeMap<String, ParamValueView>) -> Result<Params> {
+        for name in map.keys() {
+            track_assert!(
+                self.index_of(name).is_some(),
+                ErrorKind::InvalidInput,
+                "Unknown variable: {:?}",
+                name
+            );
+        }
+
+        let mut params = Vec::with_capacity(self.0.len());
+        for var in &self.0 {
+            let val = match (map.get(&var.name), &var.range) {
+                (None, _) if var.condition.is_some() => f64::NAN,
+                (None, _) => {
+                    track_panic!(ErrorKind::InvalidInput, "Missing variable: {:?}", var.name)
+                }
+                (Some(ParamValueView::Inactive), _) => f64::NAN,
+                (Some(ParamValueView::Categorical(choice)), Range::Categorical { choices, .. }) => {
+                    let index = track_assert_some!(
+                        choices.iter().position(|c| c == choice),
+                        ErrorKind::InvalidInput;
+                        var.name, choice
+                    );
+                    index as f64
+                }
+                (Some(ParamValueView::Numerical(val)), Range::Ordinal { values }) => {
+                    let index = track_assert_some!(
+                        values.iter().position(|v| v == val),
+                        ErrorKind::InvalidInput;
+                        var.name, val
+                    );
+                    index as f64
+                }
+                (Some(ParamValueView::Numerical(val)), Range::Continuous { .. })
+                | (Some(ParamValueView::Numerical(val)), Range::Discrete { .. }) => *val,
+                (Some(view), _) => track_panic!(
+                    ErrorKind::InvalidInput,
+                    "Mismatched value type: variable={:?}, value={:?}",
+                    var.name,
+                    view
+                ),
+            };
+            params.push(val);
+        }
+        Ok(Params::new(params))
+    }
+}
+
+/// A parameter value that is decoded by its variable definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValueView {
+    /// Numerical value.
+    Numerical(f64),
+
+    /// Categorical choice.
+    Categorical(String),
+
+    /// Inactive conditional parameter (serialized as `null`).
+    Inactive,
+}
+
+/// Mutually exclusive groups of variables (e.g., the parameters specific to each optimizer).
+///
+/// A group consists of a categorical selector variable and the member variables of each choice of the selector.
+/// The members of a choice are active only if the selector is the choice.
+///
+/// Groups are expanded into `Condition`s by `Domain::group` (see also `ProblemSpecBuilder::group`).
+///
+/// # Examples
+///
+/// ```json
+/// {
+///   "selector": "kernel",
+///   "choices": [
+///     {"name": "linear"},
+///     {"name": "rbf", "members": ["gamma"]},
+///     {"name": "poly", "members": ["gamma", "degree"]}
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    /// Name of the selector variable.
+    pub selector: String,
+
+    /// Choices of the selector.
+    pub choices: Vec<GroupChoice>,
+}
+impl Group {
+    /// Makes a new `Group` instance that has no choices.
+    pub fn new(selector: &str) -> Self {
+        Self {
+            selector: selector.to_owned(),
+            choices: Vec::new(),
+        }
+    }
+
+    /// Adds a choice of the selector and the names of its member variables.
+    pub fn choice(mut self, name: &str, members: &[&str]) -> Self {
+        self.choices.push(GroupChoice {
+            name: name.to_owned(),
+            members: members.iter().map(|&m| m.to_owned()).collect(),
+        });
+        self
+    }
+
+    /// Returns the selector variable of this group.
+    pub fn selector_variable(&self) -> VariableBuilder {
+        var(&self.selector).categorical(self.choices.iter().map(|c| c.name.as_str()))
+    }
+}
+impl FromStr for Group {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        track!(serde_json::from_str(s).map_err(Error::from))
+    }
+}
+
+/// A choice of the selector of a `Group`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupChoice {
+    /// Name of the choice.
+    pub name: String,
+
+    /// Names of the variables that are active only if the choice is selected.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
 }
 
 /// Returns a `VariableBuilder` which was initialized with the given variable name.
@@ -57,7 +340,11 @@ pub struct VariableBuilder {
     name: String,
     range: Range,
     distribution: Distribution,
+    step: Option<f64>,
     constraint: Option<Constraint>,
+    condition: Option<Condition>,
+    description: Option<String>,
+    attrs: BTreeMap<String, String>,
 }
 impl VariableBuilder {
     /// Makes a new `VariableBuilder` with the given variable name.
@@ -67,9 +354,15 @@ impl VariableBuilder {
             range: Range::Continuous {
                 low: std::f64::NEG_INFINITY,
                 high: std::f64::INFINITY,
+                step: None,
+                inclusive_high: false,
             },
             distribution: Distribution::Uniform,
+            step: None,
             constraint: None,
+            condition: None,
+            description: None,
+            attrs: BTreeMap::new(),
         }
     }
 
@@ -95,13 +388,34 @@ impl VariableBuilder {
 
     /// Sets the range of this variable to the given continuous numerical range.
     pub fn continuous(mut self, low: f64, high: f64) -> Self {
-        self.range = Range::Continuous { low, high };
+        self.range = Range::Continuous {
+            low,
+            high,
+            step: None,
+            inclusive_high: false,
+        };
+        self
+    }
+
+    /// Sets the range of this variable to the given continuous numerical range that includes `high`.
+    pub fn continuous_inclusive(mut self, low: f64, high: f64) -> Self {
+        self.range = Range::Continuous {
+            low,
+            high,
+            step: None,
+            inclusive_high: true,
+        };
         self
     }
 
     /// Sets the range of this variable to the given discrete numerical range.
     pub fn discrete(mut self, low: i64, high: i64) -> Self {
-        self.range = Range::Discrete { low, high };
+        self.range = Range::Discrete {
+            low,
+            high,
+            step: 1,
+            inclusive_high: false,
+        };
         self
     }
 
@@ -113,6 +427,38 @@ impl VariableBuilder {
     {
         self.range = Range::Categorical {
             choices: choices.into_iter().map(|c| c.as_ref().to_owned()).collect(),
+            weights: Vec::new(),
+        };
+        self
+    }
+
+    /// Sets the range of this variable to the given categorical range whose choices are paired with
+    /// their prior weights.
+    ///
+    /// Random sampling picks a choice with the probability proportional to its weight.
+    /// The weights must be positive.
+    pub fn categorical_weighted<I, T>(mut self, choices: I) -> Self
+    where
+        I: IntoIterator<Item = (T, f64)>,
+        T: AsRef<str>,
+    {
+        let (choices, weights) = choices
+            .into_iter()
+            .map(|(c, w)| (c.as_ref().to_owned(), w))
+            .unzip();
+        self.range = Range::Categorical { choices, weights };
+        self
+    }
+
+    /// Sets the range of this variable to the given ordinal range.
+    ///
+    /// `values` must be sorted in strictly ascending order.
+    pub fn ordinal<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        self.range = Range::Ordinal {
+            values: values.into_iter().collect(),
         };
         self
     }
@@ -130,31 +476,98 @@ impl VariableBuilder {
         self
     }
 
+    /// Sets the quantization step of this variable.
+    ///
+    /// The resulting variable only takes the values `low + step * k` (`k` is a non-negative integer).
+    /// This is only applicable to numerical ranges, and the step of a discrete range must be an integer.
+    pub fn step(mut self, step: f64) -> Self {
+        self.step = Some(step);
+        self
+    }
+
     /// Sets the evaluation constraint to this variable.
     pub fn constraint(mut self, constraint: Constraint) -> Self {
         self.constraint = Some(constraint);
         self
     }
 
+    /// Sets the activation condition to this variable.
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Sets the human-readable description of this variable.
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    /// Sets an attribute of this variable.
+    pub fn attr(mut self, key: &str, value: &str) -> Self {
+        self.attrs.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
     /// Builds a `Variable` instance with the given settings.
-    pub fn finish(self) -> Result<Variable> {
+    pub fn finish(mut self) -> Result<Variable> {
+        if let Some(new_step) = self.step.take() {
+            match &mut self.range {
+                Range::Continuous { step, .. } => *step = Some(new_step),
+                Range::Discrete { step, .. } => {
+                    track_assert_eq!(new_step.fract(), 0.0, ErrorKind::InvalidInput; new_step);
+                    *step = new_step as i64;
+                }
+                Range::Categorical { .. } | Range::Ordinal { .. } => {
+                    track_panic!(ErrorKind::InvalidInput, "Non-numerical range cannot have a step"; self)
+                }
+            }
+        }
+
         match &self.range {
-            Range::Continuous { low, high } => {
-                track_assert!(low < high, ErrorKind::InvalidInput; self)
+            Range::Continuous {
+                low, high, step, ..
+            } => {
+                track_assert!(low < high, ErrorKind::InvalidInput; self);
+                if let Some(step) = *step {
+                    track_assert!(low.is_finite() && high.is_finite(), ErrorKind::InvalidInput; self);
+                    track_assert!(0.0 < step && step <= high - low, ErrorKind::InvalidInput; self);
+                }
             }
-            Range::Discrete { low, high } => {
-                track_assert!(low < high, ErrorKind::InvalidInput; self)
+            Range::Discrete {
+                low, high, step, ..
+            } => {
+                track_assert!(low < high, ErrorKind::InvalidInput; self);
+                track_assert!(0 < *step && *step <= high - low, ErrorKind::InvalidInput; self);
             }
-            Range::Categorical { choices } => {
-                track_assert!(!choices.is_empty(), ErrorKind::InvalidInput; self)
+            Range::Categorical { choices, weights } => {
+                track_assert!(!choices.is_empty(), ErrorKind::InvalidInput; self);
+                if !weights.is_empty() {
+                    track_assert_eq!(weights.len(), choices.len(), ErrorKind::InvalidInput; self);
+                    track_assert!(
+                        weights.iter().all(|&w| w.is_finite() && w > 0.0),
+                        ErrorKind::InvalidInput; self
+                    );
+                }
+            }
+            Range::Ordinal { values } => {
+                track_assert!(!values.is_empty(), ErrorKind::InvalidInput; self);
+                track_assert!(values.iter().all(|v| v.is_finite()), ErrorKind::InvalidInput; self);
+                track_assert!(values.windows(2).all(|w| w[0] < w[1]), ErrorKind::InvalidInput; self);
             }
         }
 
         if self.distribution == Distribution::LogUniform {
             match self.range {
-                Range::Continuous { low, .. } if 0.0 < low => {}
-                Range::Discrete { low, .. } if 0 < low => {}
-                _ => track_panic!(ErrorKind::InvalidInput; self),
+                Range::Continuous {
+                    low, step: None, ..
+                } if 0.0 < low => {}
+                Range::Discrete { low, step: 1, .. } if 0 < low => {}
+                _ => track_panic!(
+                    ErrorKind::InvalidInput,
+                    "Log-uniform distribution requires a numerical range with a positive lower bound and no step";
+                    self
+                ),
             }
         }
 
@@ -163,6 +576,9 @@ impl VariableBuilder {
             range: self.range,
             distribution: self.distribution,
             constraint: self.constraint,
+            condition: self.condition,
+            description: self.description,
+            attrs: self.attrs,
         })
     }
 }
@@ -172,19 +588,30 @@ impl From<Variable> for VariableBuilder {
             name: f.name,
             range: f.range,
             distribution: f.distribution,
+            step: None,
             constraint: f.constraint,
+            condition: f.condition,
+            description: f.description,
+            attrs: f.attrs,
         }
     }
 }
 
 /// A variable in a domain.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Variable {
     name: String,
     range: Range,
     distribution: Distribution,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     constraint: Option<Constraint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<Condition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    attrs: BTreeMap<String, String>,
 }
 impl Variable {
     /// Returns the name of this variable.
@@ -206,29 +633,69 @@ impl Variable {
     pub fn constraint(&self) -> Option<&Constraint> {
         self.constraint.as_ref()
     }
+
+    /// Returns the activation condition of this variable.
+    pub fn condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
+    }
+
+    /// Returns the human-readable description of this variable.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns the attributes of this variable.
+    pub fn attrs(&self) -> &BTreeMap<String, String> {
+        &self.attrs
+    }
+
+    /// Returns the solver capabilities required to handle this variable as a parameter.
+    pub fn requirements(&self) -> Capabilities {
+        let mut c = Capabilities::empty();
+        if self.constraint.is_some() || self.condition.is_some() {
+            c.add_capability(Capability::Conditional);
+        }
+
+        match (&self.range, self.distribution) {
+            (Range::Continuous { .. }, Distribution::Uniform) => {
+                c.add_capability(Capability::UniformContinuous);
+            }
+            (Range::Continuous { .. }, Distribution::LogUniform) => {
+                c.add_capability(Capability::LogUniformContinuous);
+            }
+            (Range::Discrete { .. }, Distribution::Uniform) => {
+                c.add_capability(Capability::UniformDiscrete);
+            }
+            (Range::Discrete { .. }, Distribution::LogUniform) => {
+                c.add_capability(Capability::LogUniformDiscrete);
+            }
+            (Range::Categorical { .. }, _) => {
+                c.add_capability(Capability::Categorical);
+            }
+            (Range::Ordinal { .. }, _) => {
+                c.add_capability(Capability::Ordinal);
+            }
+        }
+        c
+    }
+
+    /// Returns the label of this variable for display.
+    ///
+    /// This is the description if it exists, and the name otherwise.
+    pub fn label(&self) -> &str {
+        self.description.as_deref().unwrap_or(&self.name)
+    }
 }
 
 impl rand::distributions::Distribution<f64> for Variable {
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> f64 {
-        match &self.range {
-            Range::Continuous { low, high } => match self.distribution {
-                Distribution::Uniform => rng.gen_range(*low..*high),
-                Distribution::LogUniform => rng.gen_range(low.log2()..high.log2()).exp2(),
-            },
-            Range::Discrete { low, high } => match self.distribution {
-                Distribution::Uniform => rng.gen_range(*low..*high) as f64,
-                Distribution::LogUniform => rng
-                    .gen_range((*low as f64).log2()..(*high as f64).log2())
-                    .exp2()
-                    .floor(),
-            },
-            Range::Categorical { choices } => rng.gen_range(0..choices.len()) as f64,
-        }
+        self.range.sample(self.distribution, rng)
     }
 }
 
 /// Distribution.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[allow(missing_docs)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Distribution {
@@ -236,6 +703,11 @@ pub enum Distribution {
     LogUniform,
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn is_not_finite(x: &f64) -> bool {
     !x.is_finite()
@@ -249,36 +721,81 @@ fn infinity() -> f64 {
     std::f64::INFINITY
 }
 
+fn one() -> i64 {
+    1
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_one(x: &i64) -> bool {
+    *x == 1
+}
+
 /// Variable range.
 #[derive(Debug, Clone, Serialize, Deserialize, StructOpt)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[allow(missing_docs)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 #[structopt(rename_all = "kebab-case")]
 pub enum Range {
-    /// Continuous numerical range: `[low..high)`.
+    /// Continuous numerical range: `[low..high)` (or `[low..=high]` if `inclusive_high` is `true`).
     Continuous {
         /// Lower bound of this range (inclusive).
         #[serde(skip_serializing_if = "is_not_finite", default = "neg_infinity")]
         low: f64,
 
-        /// Upper bound of this range (exclusive).
+        /// Upper bound of this range (exclusive by default).
         #[serde(skip_serializing_if = "is_not_finite", default = "infinity")]
         high: f64,
+
+        /// Quantization step of this range.
+        #[structopt(long)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<f64>,
+
+        /// If `true`, the upper bound is included in this range.
+        #[structopt(long)]
+        #[serde(default, skip_serializing_if = "is_false")]
+        inclusive_high: bool,
     },
 
-    /// Discrete numerical range: `[low..high)`.
+    /// Discrete numerical range: `[low..high)` (or `[low..=high]` if `inclusive_high` is `true`).
     Discrete {
         /// Lower bound of this range (inclusive).
         low: i64,
 
-        /// Upper bound of this range (exclusive).
+        /// Upper bound of this range (exclusive by default).
         high: i64,
+
+        /// Quantization step of this range.
+        #[structopt(long, default_value = "1")]
+        #[serde(default = "one", skip_serializing_if = "is_one")]
+        step: i64,
+
+        /// If `true`, the upper bound is included in this range.
+        #[structopt(long)]
+        #[serde(default, skip_serializing_if = "is_false")]
+        inclusive_high: bool,
     },
 
     /// Categorical range.
     Categorical {
         /// Possible choices.
         choices: Vec<String>,
+
+        /// Prior weights of the choices (empty means the uniform weights).
+        ///
+        /// This is advisory: random sampling respects the weights, but solvers may ignore them.
+        #[structopt(long)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        weights: Vec<f64>,
+    },
+
+    /// Ordinal range.
+    ///
+    /// Like categorical ranges, a parameter value is represented by the index of a value in `values`.
+    Ordinal {
+        /// Possible values (sorted in strictly ascending order).
+        values: Vec<f64>,
     },
 }
 impl Range {
@@ -287,38 +804,208 @@ impl Range {
         match self {
             Self::Continuous { low, .. } => *low,
             Self::Discrete { low, .. } => *low as f64,
-            Self::Categorical { .. } => 0.0,
+            Self::Categorical { .. } | Self::Ordinal { .. } => 0.0,
         }
     }
 
-    /// Returns the exclusive upper bound of this range.
+    /// Returns the upper bound of this range.
+    ///
+    /// The bound is exclusive unless `inclusive_high` of the range is `true`.
     pub fn high(&self) -> f64 {
         match self {
             Self::Continuous { high, .. } => *high,
             Self::Discrete { high, .. } => *high as f64,
-            Self::Categorical { choices } => choices.len() as f64,
+            Self::Categorical { choices, .. } => choices.len() as f64,
+            Self::Ordinal { values } => values.len() as f64,
         }
     }
 
     /// Returns `true` if the given value is contained in this range.
+    ///
+    /// If this range has a quantization step, the value also needs to be on the grid.
     pub fn contains(&self, v: f64) -> bool {
         match self {
-            Self::Continuous { low, high } => *low <= v && v < *high,
-            Self::Discrete { low, high } => *low as f64 <= v && v < *high as f64,
-            Self::Categorical { choices } => 0.0 <= v && v < choices.len() as f64,
+            Self::Continuous {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
+                let below_high = if *inclusive_high {
+                    v <= *high
+                } else {
+                    v < *high
+                };
+                if !(*low <= v && below_high) {
+                    return false;
+                }
+                if let Some(step) = *step {
+                    let k = ((v - low) / step).round();
+                    (low + k * step - v).abs() <= step * 1e-6
+                } else {
+                    true
+                }
+            }
+            Self::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
+                let below_high = if *inclusive_high {
+                    v <= *high as f64
+                } else {
+                    v < *high as f64
+                };
+                *low as f64 <= v && below_high && (v as i64 - low) % step == 0
+            }
+            Self::Categorical { choices, .. } => 0.0 <= v && v < choices.len() as f64,
+            Self::Ordinal { values } => 0.0 <= v && v < values.len() as f64,
+        }
+    }
+
+    /// Rounds down the given value to the nearest grid point of this range.
+    ///
+    /// If this range has no quantization step, the value is returned as it is.
+    pub fn quantize(&self, v: f64) -> f64 {
+        match self {
+            Self::Continuous {
+                low,
+                step: Some(step),
+                ..
+            } => low + ((v - low) / step + 1e-9).floor() * step,
+            Self::Discrete { low, step, .. } if *step != 1 => {
+                (low + (v as i64 - low).div_euclid(*step) * step) as f64
+            }
+            _ => v,
+        }
+    }
+
+    /// Samples a value from this range in accordance with the given distribution.
+    pub fn sample<R: rand::Rng + ?Sized>(&self, distribution: Distribution, rng: &mut R) -> f64 {
+        match self {
+            Self::Continuous {
+                low,
+                high,
+                step: Some(step),
+                inclusive_high,
+            } => {
+                let n = ((high - low) / step + 1e-9).floor() as u64;
+                let count = if *inclusive_high || low + n as f64 * step < high - step * 1e-9 {
+                    n + 1
+                } else {
+                    n
+                };
+                low + rng.gen_range(0..count) as f64 * step
+            }
+            Self::Continuous {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } => match (distribution, inclusive_high) {
+                (Distribution::Uniform, false) => rng.gen_range(*low..*high),
+                (Distribution::Uniform, true) => rng.gen_range(*low..=*high),
+                (Distribution::LogUniform, false) => rng.gen_range(low.log2()..high.log2()).exp2(),
+                (Distribution::LogUniform, true) => {
+                    rng.gen_range(low.log2()..=high.log2()).exp2().min(*high)
+                }
+            },
+            Self::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
+                let high = if *inclusive_high { high + 1 } else { *high };
+                match distribution {
+                    Distribution::Uniform => {
+                        let count = (high - low + step - 1) / step;
+                        (low + rng.gen_range(0..count) * step) as f64
+                    }
+                    Distribution::LogUniform => rng
+                        .gen_range((*low as f64).log2()..(high as f64).log2())
+                        .exp2()
+                        .floor(),
+                }
+            }
+            Self::Categorical { choices, weights } if weights.is_empty() => {
+                rng.gen_range(0..choices.len()) as f64
+            }
+            Self::Categorical { weights, .. } => {
+                let mut r = rng.gen_range(0.0..weights.iter().sum::<f64>());
+                for (i, w) in weights.iter().enumerate() {
+                    if r < *w {
+                        return i as f64;
+                    }
+                    r -= w;
+                }
+                (weights.len() - 1) as f64
+            }
+            Self::Ordinal { values } => rng.gen_range(0..values.len()) as f64,
         }
     }
 }
 impl PartialEq for Range {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Self::Continuous { low: l0, high: h0 }, Self::Continuous { low: l1, high: h1 }) => {
-                OrderedFloat(*l0) == OrderedFloat(*l1) && OrderedFloat(*h0) == OrderedFloat(*h1)
+            (
+                Self::Continuous {
+                    low: l0,
+                    high: h0,
+                    step: s0,
+                    inclusive_high: i0,
+                },
+                Self::Continuous {
+                    low: l1,
+                    high: h1,
+                    step: s1,
+                    inclusive_high: i1,
+                },
+            ) => {
+                OrderedFloat(*l0) == OrderedFloat(*l1)
+                    && OrderedFloat(*h0) == OrderedFloat(*h1)
+                    && s0.map(OrderedFloat) == s1.map(OrderedFloat)
+                    && i0 == i1
             }
-            (Self::Discrete { low: l0, high: h0 }, Self::Discrete { low: l1, high: h1 }) => {
-                l0 == l1 && h0 == h1
+            (
+                Self::Discrete {
+                    low: l0,
+                    high: h0,
+                    step: s0,
+                    inclusive_high: i0,
+                },
+                Self::Discrete {
+                    low: l1,
+                    high: h1,
+                    step: s1,
+                    inclusive_high: i1,
+                },
+            ) => l0 == l1 && h0 == h1 && s0 == s1 && i0 == i1,
+            (
+                Self::Categorical {
+                    choices: c0,
+                    weights: w0,
+                },
+                Self::Categorical {
+                    choices: c1,
+                    weights: w1,
+                },
+            ) => {
+                c0 == c1
+                    && w0.len() == w1.len()
+                    && w0
+                        .iter()
+                        .zip(w1.iter())
+                        .all(|(a, b)| OrderedFloat(*a) == OrderedFloat(*b))
+            }
+            (Self::Ordinal { values: v0 }, Self::Ordinal { values: v1 }) => {
+                v0.len() == v1.len()
+                    && v0
+                        .iter()
+                        .zip(v1.iter())
+                        .all(|(a, b)| OrderedFloat(*a) == OrderedFloat(*b))
             }
-            (Self::Categorical { choices: c0 }, Self::Categorical { choices: c1 }) => c0 == c1,
             _ => false,
         }
     }
@@ -327,16 +1014,38 @@ impl Eq for Range {}
 impl Hash for Range {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
-            Self::Continuous { low, high } => {
+            Self::Continuous {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
                 OrderedFloat(*low).hash(state);
                 OrderedFloat(*high).hash(state);
+                step.map(OrderedFloat).hash(state);
+                inclusive_high.hash(state);
             }
-            Self::Discrete { low, high } => {
+            Self::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
                 low.hash(state);
                 high.hash(state);
+                step.hash(state);
+                inclusive_high.hash(state);
             }
-            Self::Categorical { choices } => {
+            Self::Categorical { choices, weights } => {
                 choices.hash(state);
+                for &w in weights {
+                    OrderedFloat(w).hash(state);
+                }
+            }
+            Self::Ordinal { values } => {
+                for &v in values {
+                    OrderedFloat(v).hash(state);
+                }
             }
         }
     }
@@ -344,6 +1053,7 @@ impl Hash for Range {
 
 /// Evaluation constraint.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Constraint {
     lua_script: String,
 }
@@ -373,9 +1083,12 @@ impl Constraint {
                     continue;
                 }
 
-                if let Range::Categorical { choices } = &var.range {
+                if let Range::Categorical { choices, .. } = &var.range {
                     let val = choices[val as usize].as_str();
                     track!(globals.set(var.name.as_str(), val).map_err(Error::from))?;
+                } else if let Range::Ordinal { values } = &var.range {
+                    let val = values[val as usize];
+                    track!(globals.set(var.name.as_str(), val).map_err(Error::from))?;
                 } else {
                     track!(globals.set(var.name.as_str(), val).map_err(Error::from))?;
                 }
@@ -386,11 +1099,463 @@ impl Constraint {
     }
 }
 
+/// Activation condition of a variable.
+///
+/// A conditional variable is active only if its condition holds for the values of the preceding variables.
+/// The value of an inactive variable is `NaN`.
+///
+/// Values are compared in the same representation as `Params`
+/// (i.e., categorical values are represented by the indices of the choices).
+///
+/// A condition can also be parsed from an expression such as `optimizer == 0` (see `Condition::from_str`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Condition {
+    /// The target variable is equal to the given value.
+    Eq {
+        /// Name of the target variable.
+        target: String,
+
+        /// Expected value.
+        value: f64,
+    },
+
+    /// The target variable is equal to one of the given values.
+    In {
+        /// Name of the target variable.
+        target: String,
+
+        /// Expected values.
+        values: Vec<f64>,
+    },
+
+    /// The target variable is greater than the given value.
+    Gt {
+        /// Name of the target variable.
+        target: String,
+
+        /// Threshold value (exclusive).
+        value: f64,
+    },
+
+    /// The target variable is less than the given value.
+    Lt {
+        /// Name of the target variable.
+        target: String,
+
+        /// Threshold value (exclusive).
+        value: f64,
+    },
+
+    /// The inner condition doesn't hold.
+    ///
+    /// Note that this condition is not satisfied if the target variable of the inner condition is inactive.
+    Not(Box<Condition>),
+}
+impl Condition {
+    /// Returns the name of the target variable of this condition.
+    pub fn target(&self) -> &str {
+        match self {
+            Self::Eq { target, .. }
+            | Self::In { target, .. }
+            | Self::Gt { target, .. }
+            | Self::Lt { target, .. } => target,
+            Self::Not(inner) => inner.target(),
+        }
+    }
+
+    /// Checks whether this condition is valid for a variable that follows the given ones.
+    ///
+    /// The target variable must be one of `preceding_vars` and
+    /// the values compared by equality must be contained in the range of the target.
+    pub fn validate(&self, preceding_vars: &[Variable]) -> Result<()> {
+        let target = track_assert_some!(
+            preceding_vars.iter().find(|v| v.name == self.target()),
+            ErrorKind::InvalidInput,
+            "Unknown or succeeding target variable: {:?}",
+            self.target()
+        );
+        match self {
+            Self::Eq { value, .. } => {
+                track_assert!(target.range.contains(*value), ErrorKind::InvalidInput; self, target);
+            }
+            Self::In { values, .. } => {
+                track_assert!(!values.is_empty(), ErrorKind::InvalidInput; self);
+                for &value in values {
+                    track_assert!(target.range.contains(value), ErrorKind::InvalidInput; self, target);
+                }
+            }
+            Self::Gt { value, .. } | Self::Lt { value, .. } => {
+                track_assert!(!value.is_nan(), ErrorKind::InvalidInput; self);
+            }
+            Self::Not(inner) => {
+                track!(inner.validate(preceding_vars))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if this condition is satisfied by the given variable values.
+    ///
+    /// If the target variable is inactive (i.e., its value is `NaN`) or missing, this returns `false`.
+    pub fn is_satisfied(&self, vars: &[Variable], vals: &[f64]) -> bool {
+        self.evaluate(vars, vals).unwrap_or(false)
+    }
+
+    fn evaluate(&self, vars: &[Variable], vals: &[f64]) -> Option<bool> {
+        if let Self::Not(inner) = self {
+            return inner.evaluate(vars, vals).map(|b| !b);
+        }
+
+        let actual = vars
+            .iter()
+            .zip(vals.iter())
+            .find(|(var, _)| var.name == self.target())
+            .map(|(_, &val)| val)
+            .filter(|val| !val.is_nan())?;
+        Some(match self {
+            Self::Eq { value, .. } => actual == *value,
+            Self::In { values, .. } => values.contains(&actual),
+            Self::Gt { value, .. } => actual > *value,
+            Self::Lt { value, .. } => actual < *value,
+            Self::Not(_) => unreachable!(),
+        })
+    }
+}
+impl PartialEq for Condition {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Eq {
+                    target: t0,
+                    value: v0,
+                },
+                Self::Eq {
+                    target: t1,
+                    value: v1,
+                },
+            )
+            | (
+                Self::Gt {
+                    target: t0,
+                    value: v0,
+                },
+                Self::Gt {
+                    target: t1,
+                    value: v1,
+                },
+            )
+            | (
+                Self::Lt {
+                    target: t0,
+                    value: v0,
+                },
+                Self::Lt {
+                    target: t1,
+                    value: v1,
+                },
+            ) => t0 == t1 && OrderedFloat(*v0) == OrderedFloat(*v1),
+            (
+                Self::In {
+                    target: t0,
+                    values: v0,
+                },
+                Self::In {
+                    target: t1,
+                    values: v1,
+                },
+            ) => {
+                t0 == t1
+                    && v0.len() == v1.len()
+                    && v0
+                        .iter()
+                        .zip(v1.iter())
+                        .all(|(a, b)| OrderedFloat(*a) == OrderedFloat(*b))
+            }
+            (Self::Not(c0), Self::Not(c1)) => c0 == c1,
+            _ => false,
+        }
+    }
+}
+impl Eq for Condition {}
+impl Hash for Condition {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Eq { target, value }
+            | Self::Gt { target, value }
+            | Self::Lt { target, value } => {
+                target.hash(state);
+                OrderedFloat(*value).hash(state);
+            }
+            Self::In { target, values } => {
+                target.hash(state);
+                for &v in values {
+                    OrderedFloat(v).hash(state);
+                }
+            }
+            Self::Not(inner) => {
+                inner.hash(state);
+            }
+        }
+    }
+}
+impl FromStr for Condition {
+    type Err = Error;
+
+    /// Parses a condition expression.
+    ///
+    /// The following forms are accepted:
+    ///
+    /// - `TARGET == VALUE`, `TARGET != VALUE`
+    /// - `TARGET > VALUE`, `TARGET >= VALUE`, `TARGET < VALUE`, `TARGET <= VALUE`
+    /// - `TARGET in [VALUE, ...]`, `TARGET not in [VALUE, ...]`
+    /// - `not EXPR`, `(EXPR)`
+    ///
+    /// `VALUE` must be a number (the index of a choice in the case of a categorical target).
+    /// Negated forms such as `!=` and `>=` are represented by `Condition::Not`.
+    fn from_str(s: &str) -> Result<Self> {
+        let expr = s.trim();
+        track_assert!(
+            !expr.is_empty(),
+            ErrorKind::InvalidInput,
+            "Empty condition expression"
+        );
+
+        if expr.starts_with('(') && expr.ends_with(')') {
+            return track!(expr[1..expr.len() - 1].parse());
+        }
+        if let Some(inner) = strip_keyword(expr, "not") {
+            let inner = track!(inner.parse())?;
+            return Ok(Self::Not(Box::new(inner)));
+        }
+
+        let end = expr
+            .find(|c: char| c.is_whitespace() || "=!<>[(".contains(c))
+            .unwrap_or(expr.len());
+        let target = expr[..end].to_owned();
+        track_assert!(
+            !target.is_empty(),
+            ErrorKind::InvalidInput,
+            "Missing target variable name: {:?}",
+            s
+        );
+
+        let rest = expr[end..].trim_start();
+        let (op, operand) = if let Some(operand) = strip_keyword(rest, "in") {
+            ("in", operand)
+        } else if let Some(rest) = strip_keyword(rest, "not") {
+            let operand = track_assert_some!(
+                strip_keyword(rest, "in"),
+                ErrorKind::InvalidInput,
+                "Expected `in` after `not`: {:?}",
+                s
+            );
+            ("not in", operand)
+        } else {
+            let op = track_assert_some!(
+                ["==", "!=", ">=", "<=", ">", "<"]
+                    .iter()
+                    .find(|op| rest.starts_with(*op)),
+                ErrorKind::InvalidInput,
+                "Unknown operator (expected one of `==`, `!=`, `>`, `>=`, `<`, `<=`, `in` or `not in`): {:?}",
+                s
+            );
+            (*op, &rest[op.len()..])
+        };
+
+        let not = |c| Self::Not(Box::new(c));
+        let condition = match op {
+            "in" | "not in" => {
+                let values = track!(parse_condition_values(operand); s)?;
+                let c = Self::In { target, values };
+                if op == "in" {
+                    c
+                } else {
+                    not(c)
+                }
+            }
+            _ => {
+                let value = track!(parse_condition_value(operand); s)?;
+                match op {
+                    "==" => Self::Eq { target, value },
+                    "!=" => not(Self::Eq { target, value }),
+                    ">" => Self::Gt { target, value },
+                    ">=" => not(Self::Lt { target, value }),
+                    "<" => Self::Lt { target, value },
+                    "<=" => not(Self::Gt { target, value }),
+                    _ => track_panic!(ErrorKind::Bug; op),
+                }
+            }
+        };
+        Ok(condition)
+    }
+}
+
+fn strip_keyword<'a>(s: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = s.strip_prefix(keyword)?;
+    if rest.starts_with(|c: char| c.is_whitespace() || c == '(' || c == '[') {
+        Some(rest.trim_start())
+    } else {
+        None
+    }
+}
+
+fn parse_condition_value(s: &str) -> Result<f64> {
+    let s = s.trim();
+    track_assert!(!s.is_empty(), ErrorKind::InvalidInput, "Missing value");
+    let value: f64 = track!(s.parse().map_err(Error::from); s)?;
+    track_assert!(
+        !value.is_nan(),
+        ErrorKind::InvalidInput,
+        "NaN is not allowed"
+    );
+    Ok(value)
+}
+
+fn parse_condition_values(s: &str) -> Result<Vec<f64>> {
+    let s = s.trim();
+    let items = track_assert_some!(
+        s.strip_prefix('[').and_then(|s| s.strip_suffix(']')),
+        ErrorKind::InvalidInput,
+        "Expected a list of values such as `[0, 1]`: {:?}",
+        s
+    );
+    let values = items
+        .split(',')
+        .map(|v| track!(parse_condition_value(v)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(values)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use trackable;
 
+    #[test]
+    fn group_works() -> trackable::result::TopLevelResult {
+        let group = Group::new("optimizer")
+            .choice("sgd", &["momentum"])
+            .choice("adam", &["beta1", "beta2"])
+            .choice("adamw", &["beta1", "beta2", "weight_decay"]);
+        let vars = track!(Domain::group(
+            vec![
+                var("lr").continuous(0.0, 1.0),
+                var("momentum").continuous(0.0, 1.0),
+                var("beta1").continuous(0.0, 1.0),
+                var("beta2").continuous(0.0, 1.0),
+                var("weight_decay").continuous(0.0, 1.0),
+            ],
+            std::slice::from_ref(&group)
+        ))?;
+        let domain = track!(Domain::new(vars))?;
+
+        let names = domain
+            .variables()
+            .iter()
+            .map(|v| v.name())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "lr",
+                "optimizer",
+                "momentum",
+                "beta1",
+                "beta2",
+                "weight_decay"
+            ]
+        );
+        assert_eq!(
+            domain.variables()[1].range(),
+            &Range::Categorical {
+                choices: vec!["sgd".to_owned(), "adam".to_owned(), "adamw".to_owned()],
+                weights: Vec::new(),
+            }
+        );
+
+        let conditions = domain
+            .variables()
+            .iter()
+            .map(|v| v.condition().cloned())
+            .collect::<Vec<_>>();
+        let optimizer = "optimizer".to_owned();
+        assert_eq!(
+            conditions,
+            [
+                None,
+                None,
+                Some(Condition::Eq {
+                    target: optimizer.clone(),
+                    value: 0.0
+                }),
+                Some(Condition::In {
+                    target: optimizer.clone(),
+                    values: vec![1.0, 2.0]
+                }),
+                Some(Condition::In {
+                    target: optimizer.clone(),
+                    values: vec![1.0, 2.0]
+                }),
+                Some(Condition::Eq {
+                    target: optimizer,
+                    value: 2.0
+                }),
+            ]
+        );
+
+        // Serialized representation.
+        let json = serde_json::json!({
+            "selector": "optimizer",
+            "choices": [
+                {"name": "sgd", "members": ["momentum"]},
+                {"name": "adam", "members": ["beta1", "beta2"]},
+                {"name": "adamw", "members": ["beta1", "beta2", "weight_decay"]}
+            ]
+        });
+        assert_eq!(track!(json.to_string().parse::<Group>())?, group);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_group_is_rejected() {
+        let vars = || vec![var("x").continuous(0.0, 1.0), var("y").continuous(0.0, 1.0)];
+        let a = Group::new("a").choice("p", &["x"]).choice("q", &["y"]);
+
+        // A variable can't belong to two groups.
+        let b = Group::new("b").choice("p", &["x"]).choice("q", &[]);
+        assert!(Domain::group(vars(), &[a.clone(), b]).is_err());
+
+        // Unknown member.
+        let b = Group::new("b").choice("p", &["z"]);
+        assert!(Domain::group(vars(), &[b]).is_err());
+
+        // Conditional member.
+        let vars_with_condition = vec![
+            var("c").boolean(),
+            var("x").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "c".to_owned(),
+                value: 1.0,
+            }),
+            var("y").continuous(0.0, 1.0),
+        ];
+        assert!(Domain::group(vars_with_condition, std::slice::from_ref(&a)).is_err());
+
+        // Mismatched selector.
+        let mut selector_vars = vars();
+        selector_vars.insert(0, var("a").categorical(["q", "p"]));
+        assert!(Domain::group(selector_vars, std::slice::from_ref(&a)).is_err());
+
+        // Nested groups.
+        let outer = Group::new("outer").choice("on", &["a"]).choice("off", &[]);
+        let vars = track_try_unwrap!(Domain::group(vars(), &[a, outer]));
+        let names = vars.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["outer", "a", "x", "y"]);
+        assert!(Domain::new(vars).is_ok());
+    }
+
     #[test]
     fn constraint_test() -> trackable::result::TopLevelResult {
         let vars = vec![
@@ -410,4 +1575,427 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn step_test() -> trackable::result::TopLevelResult {
+        use rand::SeedableRng;
+
+        let v = var("a").continuous(0.0, 1.0).step(0.25).finish()?;
+        assert!(v.range().contains(0.5));
+        assert!(!v.range().contains(0.3));
+        assert_eq!(v.range().quantize(0.3), 0.25);
+
+        let v = var("b").discrete(-3, 10).step(4.0).finish()?;
+        assert!(v.range().contains(5.0));
+        assert!(!v.range().contains(4.0));
+        assert_eq!(v.range().quantize(4.0), 1.0);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let x = rand::distributions::Distribution::sample(&v, &mut rng);
+            assert!(v.range().contains(x), "{}", x);
+        }
+
+        assert!(var("c").discrete(0, 10).step(0.5).finish().is_err());
+        assert!(var("d").continuous(0.0, 1.0).step(2.0).finish().is_err());
+        assert!(var("e").categorical(["x", "y"]).step(1.0).finish().is_err());
+        assert!(var("f")
+            .continuous(1.0, 10.0)
+            .log_uniform()
+            .step(1.0)
+            .finish()
+            .is_err());
+
+        let old: Range = track!(
+            serde_json::from_str(r#"{"type":"DISCRETE","low":0,"high":3}"#).map_err(Error::from)
+        )?;
+        assert_eq!(
+            old,
+            Range::Discrete {
+                low: 0,
+                high: 3,
+                step: 1,
+                inclusive_high: false,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn condition_validate_succeeds_for_preceding_target() -> trackable::result::TopLevelResult {
+        let domain = track!(Domain::new(vec![
+            var("kernel").categorical(["linear", "rbf"]),
+            var("gamma").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "kernel".to_owned(),
+                value: 1.0,
+            }),
+        ]))?;
+        assert!(domain.variables()[1].condition().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn condition_validate_fails_for_missing_target() {
+        let result = Domain::new(vec![
+            var("kernel").categorical(["linear", "rbf"]),
+            var("gamma").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "unknown".to_owned(),
+                value: 1.0,
+            }),
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn condition_validate_fails_for_succeeding_target() {
+        let result = Domain::new(vec![
+            var("gamma").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "kernel".to_owned(),
+                value: 1.0,
+            }),
+            var("kernel").categorical(["linear", "rbf"]),
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn condition_validate_fails_for_out_of_range_value() {
+        let result = Domain::new(vec![
+            var("kernel").categorical(["linear", "rbf"]),
+            var("gamma").continuous(0.0, 1.0).condition(Condition::In {
+                target: "kernel".to_owned(),
+                values: vec![0.0, 2.0],
+            }),
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn param_map_test() -> trackable::result::TopLevelResult {
+        let domain = track!(Domain::new(vec![
+            var("a").continuous(-1.0, 1.0),
+            var("b").discrete(0, 10),
+            var("c").categorical(["foo", "bar"]),
+            var("d").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "c".to_owned(),
+                value: 1.0,
+            }),
+        ]))?;
+        assert_eq!(domain.index_of("c"), Some(2));
+        assert_eq!(domain.index_of("e"), None);
+
+        let params = Params::new(vec![0.5, 3.0, 0.0, f64::NAN]);
+        let map = track!(domain.to_map(&params))?;
+        assert_eq!(map["a"], ParamValueView::Numerical(0.5));
+        assert_eq!(map["b"], ParamValueView::Numerical(3.0));
+        assert_eq!(map["c"], ParamValueView::Categorical("foo".to_owned()));
+        assert_eq!(map["d"], ParamValueView::Inactive);
+        assert_eq!(track!(domain.params_from_map(&map))?, params);
+
+        let mut map = map;
+        map.remove("d");
+        assert_eq!(track!(domain.params_from_map(&map))?, params);
+
+        map.insert(
+            "c".to_owned(),
+            ParamValueView::Categorical("baz".to_owned()),
+        );
+        assert!(domain.params_from_map(&map).is_err());
+
+        map.insert("c".to_owned(), ParamValueView::Numerical(1.0));
+        assert!(domain.params_from_map(&map).is_err());
+
+        map.insert(
+            "c".to_owned(),
+            ParamValueView::Categorical("bar".to_owned()),
+        );
+        map.insert("e".to_owned(), ParamValueView::Numerical(1.0));
+        assert!(domain.params_from_map(&map).is_err());
+
+        map.remove("e");
+        map.remove("a");
+        assert!(domain.params_from_map(&map).is_err());
+
+        assert!(domain.to_map(&Params::new(vec![0.5])).is_err());
+        assert!(domain
+            .to_map(&Params::new(vec![0.5, 3.0, 5.0, 0.1]))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ordinal_test() -> trackable::result::TopLevelResult {
+        let domain = track!(Domain::new(vec![
+            var("batch_size").ordinal(vec![32.0, 64.0, 128.0, 256.0])
+        ]))?;
+        let range = domain.variables()[0].range();
+        assert!(range.contains(3.0));
+        assert!(!range.contains(4.0));
+
+        let map = track!(domain.to_map(&Params::new(vec![2.0])))?;
+        assert_eq!(map["batch_size"], ParamValueView::Numerical(128.0));
+        assert_eq!(
+            track!(domain.params_from_map(&map))?,
+            Params::new(vec![2.0])
+        );
+
+        assert!(var("x").ordinal(vec![]).finish().is_err());
+        assert!(var("x").ordinal(vec![2.0, 1.0]).finish().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn inclusive_high_test() -> trackable::result::TopLevelResult {
+        use rand::SeedableRng;
+
+        let exclusive = var("a").continuous(0.0, 1.0).finish()?;
+        assert!(exclusive.range().contains(0.0));
+        assert!(!exclusive.range().contains(1.0));
+
+        let inclusive = var("a").continuous_inclusive(0.0, 1.0).finish()?;
+        assert!(inclusive.range().contains(0.0));
+        assert!(inclusive.range().contains(1.0));
+        assert!(!inclusive.range().contains(1.0 + 1e-9));
+
+        let exclusive = var("b").discrete(0, 3).finish()?;
+        assert!(!exclusive.range().contains(3.0));
+
+        let inclusive = var("b")
+            .range(Range::Discrete {
+                low: 0,
+                high: 3,
+                step: 1,
+                inclusive_high: true,
+            })
+            .finish()?;
+        assert!(inclusive.range().contains(3.0));
+        assert!(!inclusive.range().contains(4.0));
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let samples = (0..100)
+            .map(|_| rand::distributions::Distribution::sample(&inclusive, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(samples.iter().all(|&x| inclusive.range().contains(x)));
+        assert!(samples.contains(&3.0));
+
+        let grid = var("c")
+            .continuous_inclusive(0.0, 1.0)
+            .step(0.25)
+            .finish()?;
+        assert!(grid.range().contains(1.0));
+        let samples = (0..100)
+            .map(|_| rand::distributions::Distribution::sample(&grid, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(samples.iter().all(|&x| grid.range().contains(x)));
+        assert!(samples.contains(&1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn variable_metadata_serde_test() -> trackable::result::TopLevelResult {
+        let var = var("lr")
+            .continuous(1e-5, 1.0)
+            .log_uniform()
+            .description("learning rate")
+            .attr("unit", "unitless")
+            .finish()?;
+        assert_eq!(var.label(), "learning rate");
+        assert_eq!(
+            var.attrs().get("unit").map(|s| s.as_str()),
+            Some("unitless")
+        );
+
+        let json = track!(serde_json::to_string(&var).map_err(Error::from))?;
+        let restored: Variable = track!(serde_json::from_str(&json).map_err(Error::from))?;
+        assert_eq!(restored, var);
+        assert_eq!(restored.description(), Some("learning rate"));
+
+        let var = self::var("lr").continuous(1e-5, 1.0).finish()?;
+        let json = track!(serde_json::to_string(&var).map_err(Error::from))?;
+        assert!(!json.contains("description"));
+        assert!(!json.contains("attrs"));
+        assert_eq!(var.label(), "lr");
+        let restored: Variable = track!(serde_json::from_str(&json).map_err(Error::from))?;
+        assert_eq!(restored, var);
+        Ok(())
+    }
+
+    #[test]
+    fn condition_from_str_works() -> trackable::result::TopLevelResult {
+        let eq = |value| Condition::Eq {
+            target: "opt".to_owned(),
+            value,
+        };
+        let not = |c| Condition::Not(Box::new(c));
+
+        assert_eq!(track!("opt == 0".parse::<Condition>())?, eq(0.0));
+        assert_eq!(track!("  opt==1 ".parse::<Condition>())?, eq(1.0));
+        assert_eq!(track!("opt != 2".parse::<Condition>())?, not(eq(2.0)));
+        assert_eq!(
+            track!("x > -1.5".parse::<Condition>())?,
+            Condition::Gt {
+                target: "x".to_owned(),
+                value: -1.5
+            }
+        );
+        assert_eq!(
+            track!("x < 1e-3".parse::<Condition>())?,
+            Condition::Lt {
+                target: "x".to_owned(),
+                value: 1e-3
+            }
+        );
+        assert_eq!(
+            track!("x >= 3".parse::<Condition>())?,
+            not(Condition::Lt {
+                target: "x".to_owned(),
+                value: 3.0
+            })
+        );
+        assert_eq!(
+            track!("x <= 3".parse::<Condition>())?,
+            not(Condition::Gt {
+                target: "x".to_owned(),
+                value: 3.0
+            })
+        );
+
+        let in_ = Condition::In {
+            target: "opt".to_owned(),
+            values: vec![0.0, 2.0],
+        };
+        assert_eq!(track!("opt in [0, 2]".parse::<Condition>())?, in_);
+        assert_eq!(track!("opt in[0,2]".parse::<Condition>())?, in_);
+        assert_eq!(
+            track!("opt not in [0, 2]".parse::<Condition>())?,
+            not(in_.clone())
+        );
+        assert_eq!(
+            track!("not (opt in [0, 2])".parse::<Condition>())?,
+            not(in_)
+        );
+        assert_eq!(track!("(opt == 0)".parse::<Condition>())?, eq(0.0));
+
+        let c = track!("notes == 1".parse::<Condition>())?;
+        assert_eq!(c.target(), "notes");
+        let c = track!("model.depth > 2".parse::<Condition>())?;
+        assert_eq!(c.target(), "model.depth");
+        Ok(())
+    }
+
+    #[test]
+    fn condition_from_str_fails_for_invalid_expressions() {
+        for expr in &[
+            "",
+            "opt",
+            "== 0",
+            "opt = 0",
+            "opt == ",
+            "opt == foo",
+            "opt == NaN",
+            "opt in 0",
+            "opt in []",
+            "opt in [0, ]",
+            "opt not [0]",
+            "not",
+        ] {
+            let e = expr.parse::<Condition>().err();
+            assert_eq!(
+                e.map(|e| *e.kind()),
+                Some(ErrorKind::InvalidInput),
+                "{}",
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn range_and_distribution_combinations_work() {
+        let ranges = vec![
+            (var("a").continuous(1.0, 10.0), true),
+            (var("a").continuous(0.0, 10.0), false),
+            (var("a").continuous(-1.0, 10.0), false),
+            (var("a").continuous_inclusive(1.0, 10.0), true),
+            (var("a").continuous(1.0, 10.0).step(0.5), false),
+            (var("a").discrete(1, 10), true),
+            (var("a").discrete(0, 10), false),
+            (var("a").discrete(1, 10).step(2.0), false),
+            (var("a").categorical(["x", "y"]), false),
+            (var("a").boolean(), false),
+            (var("a").ordinal(vec![1.0, 2.0, 4.0]), false),
+        ];
+        for (builder, log_uniform_ok) in ranges {
+            let uniform = builder.uniform().finish().unwrap();
+            assert_eq!(uniform.distribution(), Distribution::Uniform);
+
+            let log_uniform = VariableBuilder::from(uniform.clone())
+                .log_uniform()
+                .finish();
+            assert_eq!(log_uniform.is_ok(), log_uniform_ok, "{:?}", uniform);
+            if let Ok(v) = log_uniform {
+                assert_eq!(v.distribution(), Distribution::LogUniform);
+                assert_eq!(v.range(), uniform.range());
+            }
+        }
+
+        assert!(var("a").continuous(1.0, 1.0).finish().is_err());
+        assert!(var("a").discrete(3, 2).finish().is_err());
+        assert!(var("a").categorical(Vec::<&str>::new()).finish().is_err());
+        assert!(var("a").ordinal(vec![]).finish().is_err());
+        assert!(var("a").ordinal(vec![2.0, 1.0]).finish().is_err());
+    }
+
+    #[test]
+    fn categorical_weights_work() -> trackable::result::TopLevelResult {
+        use rand::SeedableRng;
+
+        let v = var("c")
+            .categorical_weighted(vec![("a", 1.0), ("b", 2.0), ("c", 7.0)])
+            .finish()?;
+
+        // Chi-squared goodness-of-fit test (2 degrees of freedom, the critical value at p=0.001).
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let n = 10000;
+        let mut counts = [0.0; 3];
+        for _ in 0..n {
+            let x = rand::distributions::Distribution::sample(&v, &mut rng);
+            counts[x as usize] += 1.0;
+        }
+        let chi2 = counts
+            .iter()
+            .zip([0.1, 0.2, 0.7].iter())
+            .map(|(o, p)| (o - p * n as f64).powi(2) / (p * n as f64))
+            .sum::<f64>();
+        assert!(chi2 < 13.82, "{:?}", counts);
+
+        // Weights are omitted from JSON if they aren't specified.
+        let json = track!(serde_json::to_value(&v).map_err(Error::from))?;
+        assert_eq!(json["range"]["weights"], serde_json::json!([1.0, 2.0, 7.0]));
+        assert_eq!(
+            track!(serde_json::from_value::<Variable>(json).map_err(Error::from))?,
+            v
+        );
+        let unweighted = var("c").categorical(["a", "b"]).finish()?;
+        let json = track!(serde_json::to_value(&unweighted).map_err(Error::from))?;
+        assert!(json["range"].get("weights").is_none());
+        assert_ne!(
+            unweighted,
+            var("c")
+                .categorical_weighted([("a", 1.0), ("b", 2.0)])
+                .finish()?
+        );
+
+        assert!(var("c")
+            .categorical_weighted([("a", 1.0), ("b", 0.0)])
+            .finish()
+            .is_err());
+        assert!(var("c")
+            .range(Range::Categorical {
+                choices: vec!["a".to_owned(), "b".to_owned()],
+                weights: vec![1.0],
+            })
+            .finish()
+            .is_err());
+        Ok(())
+    }
 }
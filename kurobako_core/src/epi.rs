@@ -1,4 +1,32 @@
 //! **E**xternal **P**rogram **I**nterface.
+//!
+//! The problems and solvers implemented by external programs are only available if
+//! the `external-process` feature is enabled (default).
+//! The messages and the servers are always available so that they can be used over other transports.
 pub mod channel;
 pub mod problem;
 pub mod solver;
+
+#[cfg(feature = "external-process")]
+mod process;
+
+/// Default grace period in seconds for external programs to exit after `SHUTDOWN_CAST` is sent.
+pub const DEFAULT_SHUTDOWN_GRACE_PERIOD: u64 = 3;
+
+#[cfg(feature = "external-process")]
+pub(crate) fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Drops the external program factories cached in the thread-local storage of the calling thread.
+///
+/// The processes owned by the factories are terminated unless the factories are still referenced
+/// from elsewhere (e.g., a `FactoryRegistry`).
+/// Because thread-local storages are destroyed after a thread has been joined,
+/// a thread that creates external program factories should call this before it finishes,
+/// so that the processes have been reaped when the thread is joined.
+#[cfg(feature = "external-process")]
+pub fn clear_thread_local_factories() {
+    problem::clear_thread_local_factories();
+    solver::clear_thread_local_factories();
+}
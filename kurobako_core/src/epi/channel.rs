@@ -1,9 +1,15 @@
 //! The receiving and sending channels used to communicate with the external problems that support EPI.
-use crate::{Error, Result};
+use crate::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 use std::marker::PhantomData;
+use std::sync::atomic::{self, AtomicBool, AtomicU64};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Sending channel.
 pub struct MessageSender<T, W: Write> {
@@ -38,8 +44,11 @@ impl<T, W: Write> fmt::Debug for MessageSender<T, W> {
 }
 
 /// Receiving channel.
+///
+/// Lines that don't look like JSON objects (e.g., debug prints of the peer) are skipped.
 pub struct MessageReceiver<T, R: Read> {
-    reader: BufReader<R>,
+    lines: Lines<R>,
+    stderr: Option<StderrForwarder>,
     _message: PhantomData<T>,
 }
 impl<T, R> MessageReceiver<T, R>
@@ -50,17 +59,74 @@ where
     /// Makes a new `MessageReceiver` instance.
     pub fn new(reader: R) -> Self {
         Self {
-            reader: BufReader::new(reader),
+            lines: Lines::Blocking(BufReader::new(reader)),
+            stderr: None,
             _message: PhantomData,
         }
     }
 
+    /// Makes a new `MessageReceiver` instance that fails with `ErrorKind::Timeout`
+    /// if no message arrives within the given duration.
+    ///
+    /// The reader is moved to a background thread,
+    /// so the termination of the peer (i.e., end-of-stream) is detected while waiting.
+    pub fn with_timeout(reader: R, timeout: Duration) -> Self
+    where
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let mut line = String::new();
+                let result = reader
+                    .read_line(&mut line)
+                    .map(|n| Some(line).filter(|_| n > 0));
+                let is_last = !matches!(result, Ok(Some(_)));
+                if tx.send(result).is_err() || is_last {
+                    break;
+                }
+            }
+        });
+        Self {
+            lines: Lines::Threaded { rx, timeout },
+            stderr: None,
+            _message: PhantomData,
+        }
+    }
+
+    /// Sets the forwarder of the stderr of the peer.
+    ///
+    /// If set, the last lines of the stderr are included in the errors returned by `recv`.
+    pub fn stderr(mut self, stderr: StderrForwarder) -> Self {
+        self.stderr = Some(stderr);
+        self
+    }
+
     /// Receives a message.
     pub fn recv(&mut self) -> Result<T> {
-        let mut line = String::new();
-        track!(self.reader.read_line(&mut line).map_err(Error::from))?;
-        let message = track!(serde_json::from_str(&line).map_err(Error::from); line)?;
-        Ok(message)
+        let result = self.recv_message();
+        if let Some(stderr) = self.stderr.as_ref().filter(|_| result.is_err()) {
+            if result.as_ref().err().map(|e| *e.kind()) == Some(ErrorKind::UnexpectedEos) {
+                // The peer seems to have exited, so waits for the remaining stderr lines to be forwarded.
+                stderr.wait_closed(STDERR_CLOSE_WAIT);
+            }
+            let tail = stderr.tail().join("\n");
+            return track!(result, "Last stderr lines of the peer:\n{}", tail);
+        }
+        result
+    }
+
+    fn recv_message(&mut self) -> Result<T> {
+        loop {
+            let line = track!(self.lines.read_line())?;
+            let line = track_assert_some!(line, ErrorKind::UnexpectedEos);
+            if !line.trim_start().starts_with('{') {
+                continue;
+            }
+            let message = track!(serde_json::from_str(&line).map_err(Error::from); line)?;
+            return Ok(message);
+        }
     }
 }
 impl<T, R: Read> fmt::Debug for MessageReceiver<T, R> {
@@ -68,3 +134,431 @@ impl<T, R: Read> fmt::Debug for MessageReceiver<T, R> {
         write!(f, "MessageReceiver {{ .. }}")
     }
 }
+
+/// Message that can be correlated with a call by its request identifier.
+pub trait Correlated {
+    /// Returns the request identifier of this message.
+    fn request_id(&self) -> Option<u64>;
+}
+
+/// Bidirectional channel that multiplexes concurrent calls.
+///
+/// Each call is tagged with a unique request identifier,
+/// and the reply that echoes the identifier is delivered to the caller.
+/// Until the peer echoes an identifier, the calls are issued in lock-step
+/// (i.e., a call is sent only after the reply of the previous one has been received)
+/// because replies without identifiers can only be matched by order.
+pub struct MessageChannel<T, W: Write, R: Read> {
+    tx: Mutex<MessageSender<T, W>>,
+    rx: Mutex<(MessageReceiver<T, R>, HashMap<u64, T>)>,
+    lockstep: Mutex<()>,
+    multiplexed: AtomicBool,
+    next_request_id: AtomicU64,
+}
+impl<T, W, R> MessageChannel<T, W, R>
+where
+    T: Serialize + for<'a> Deserialize<'a> + Correlated,
+    W: Write,
+    R: Read,
+{
+    /// Makes a new `MessageChannel` instance.
+    pub fn new(tx: MessageSender<T, W>, rx: MessageReceiver<T, R>) -> Self {
+        Self {
+            tx: Mutex::new(tx),
+            rx: Mutex::new((rx, HashMap::new())),
+            lockstep: Mutex::new(()),
+            multiplexed: AtomicBool::new(false),
+            next_request_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Sends a message that doesn't have a reply.
+    pub fn cast(&self, message: &T) -> Result<()> {
+        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
+        track!(tx.send(message))
+    }
+
+    /// Sends the message made by `f` with a new request identifier and waits for the reply.
+    pub fn call<F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(Option<u64>) -> T,
+    {
+        let request_id = self.next_request_id.fetch_add(1, atomic::Ordering::SeqCst);
+        let _lockstep = if self.multiplexed.load(atomic::Ordering::SeqCst) {
+            None
+        } else {
+            let guard = track!(self.lockstep.lock().map_err(Error::from))?;
+            if self.multiplexed.load(atomic::Ordering::SeqCst) {
+                None
+            } else {
+                Some(guard)
+            }
+        };
+
+        track!(self.cast(&f(Some(request_id))))?;
+        loop {
+            let mut rx = track!(self.rx.lock().map_err(Error::from))?;
+            if let Some(reply) = rx.1.remove(&request_id) {
+                return Ok(reply);
+            }
+
+            let reply = track!(rx.0.recv())?;
+            match reply.request_id() {
+                None => return Ok(reply),
+                Some(id) if id == request_id => {
+                    self.multiplexed.store(true, atomic::Ordering::SeqCst);
+                    return Ok(reply);
+                }
+                Some(id) => {
+                    rx.1.insert(id, reply);
+                }
+            }
+        }
+    }
+
+    /// Receives a message that isn't a reply to a call (e.g., the first cast from the peer).
+    pub fn recv(&self) -> Result<T> {
+        let mut rx = track!(self.rx.lock().map_err(Error::from))?;
+        track!(rx.0.recv())
+    }
+}
+
+impl<T, W: Write, R: Read> fmt::Debug for MessageChannel<T, W, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MessageChannel {{ .. }}")
+    }
+}
+
+/// Period for waiting for the stderr of a peer to be closed after its stdout was closed.
+const STDERR_CLOSE_WAIT: Duration = Duration::from_secs(1);
+
+/// The number of the last stderr lines kept by `StderrForwarder`.
+pub const STDERR_TAIL_LINES: usize = 20;
+
+/// Forwarder of the stderr of an external process.
+///
+/// Each line is written to the stderr of `kurobako` with the given prefix
+/// (e.g., `external[foo.py pid=1234]: ...`).
+#[derive(Debug, Clone)]
+pub struct StderrForwarder {
+    tail: Arc<Mutex<VecDeque<String>>>,
+    closed: Arc<AtomicBool>,
+}
+impl StderrForwarder {
+    /// Spawns a thread that forwards the lines read from `reader`.
+    pub fn spawn<R>(reader: R, prefix: String) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Self::spawn_with_writer(reader, prefix, io::stderr())
+    }
+
+    fn spawn_with_writer<R, W>(reader: R, prefix: String, mut writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let tail = Arc::new(Mutex::new(VecDeque::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let this = Self {
+            tail: Arc::clone(&tail),
+            closed: Arc::clone(&closed),
+        };
+        thread::spawn(move || {
+            // Note that `lines()` also yields the last partial line at end-of-stream.
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                let _ = writeln!(writer, "{}: {}", prefix, line);
+
+                if let Ok(mut tail) = tail.lock() {
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            }
+            closed.store(true, atomic::Ordering::SeqCst);
+        });
+        this
+    }
+
+    /// Waits until the stderr is closed (e.g., the process exited) or the timeout expires.
+    ///
+    /// Returns `true` if the stderr has been closed.
+    pub fn wait_closed(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.closed.load(atomic::Ordering::SeqCst) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    /// Returns the last lines forwarded so far.
+    pub fn tail(&self) -> Vec<String> {
+        self.tail
+            .lock()
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+enum Lines<R> {
+    Blocking(BufReader<R>),
+    Threaded {
+        rx: mpsc::Receiver<io::Result<Option<String>>>,
+        timeout: Duration,
+    },
+}
+impl<R: Read> Lines<R> {
+    fn read_line(&mut self) -> Result<Option<String>> {
+        match self {
+            Self::Blocking(reader) => {
+                let mut line = String::new();
+                let n = track!(reader.read_line(&mut line).map_err(Error::from))?;
+                Ok(Some(line).filter(|_| n > 0))
+            }
+            Self::Threaded { rx, timeout } => match rx.recv_timeout(*timeout) {
+                Ok(result) => track!(result.map_err(Error::from)),
+                Err(RecvTimeoutError::Timeout) => track_panic!(
+                    ErrorKind::Timeout,
+                    "No message arrived within {:?}",
+                    timeout
+                ),
+                Err(RecvTimeoutError::Disconnected) => Ok(None),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn recv_skips_noise_lines() -> TopLevelResult {
+        let input = b"some noise\n\n{\"foo\": 1}\nnoise again\n{\"bar\": 2}\n";
+        let mut rx = MessageReceiver::<Value, _>::new(&input[..]);
+        assert_eq!(track!(rx.recv())?, serde_json::json!({"foo": 1}));
+        assert_eq!(track!(rx.recv())?, serde_json::json!({"bar": 2}));
+        assert_eq!(
+            rx.recv().err().map(|e| *e.kind()),
+            Some(ErrorKind::UnexpectedEos)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn recv_with_timeout_works() -> TopLevelResult {
+        let input = b"noise\n{\"foo\": 1}\n";
+        let mut rx = MessageReceiver::<Value, _>::with_timeout(&input[..], Duration::from_secs(10));
+        assert_eq!(track!(rx.recv())?, serde_json::json!({"foo": 1}));
+        assert_eq!(
+            rx.recv().err().map(|e| *e.kind()),
+            Some(ErrorKind::UnexpectedEos)
+        );
+
+        let (_tx, pipe) = mpsc::channel::<u8>();
+        let reader = BlockingReader(pipe);
+        let mut rx = MessageReceiver::<Value, _>::with_timeout(reader, Duration::from_millis(10));
+        assert_eq!(rx.recv().err().map(|e| *e.kind()), Some(ErrorKind::Timeout));
+        Ok(())
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Message {
+        request_id: Option<u64>,
+        value: u64,
+    }
+    impl Correlated for Message {
+        fn request_id(&self) -> Option<u64> {
+            self.request_id
+        }
+    }
+
+    struct ChannelWriter(mpsc::Sender<u8>);
+    impl Write for ChannelWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &b in buf {
+                self.0
+                    .send(b)
+                    .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn channel_demultiplexes_replies() -> TopLevelResult {
+        const CALLS: u64 = 16;
+
+        let (client_tx, peer_rx) = mpsc::channel();
+        let (peer_tx, client_rx) = mpsc::channel();
+        let peer = thread::spawn(move || -> Result<()> {
+            let mut tx = MessageSender::new(ChannelWriter(peer_tx));
+            let mut rx = MessageReceiver::<Message, _>::new(BlockingReader(peer_rx));
+
+            // The first call is answered immediately because the channel is in lock-step mode
+            // until it receives an identifier. The rest are answered in the reverse order.
+            let mut calls = Vec::new();
+            for i in 0..CALLS {
+                let m = track!(rx.recv())?;
+                if i == 0 {
+                    track!(tx.send(&m))?;
+                } else {
+                    calls.push(m);
+                }
+            }
+            for m in calls.into_iter().rev() {
+                track!(tx.send(&m))?;
+            }
+            Ok(())
+        });
+
+        let channel = Arc::new(MessageChannel::new(
+            MessageSender::new(ChannelWriter(client_tx)),
+            MessageReceiver::<Message, _>::new(BlockingReader(client_rx)),
+        ));
+        let callers = (0..CALLS)
+            .map(|value| {
+                let channel = Arc::clone(&channel);
+                thread::spawn(move || {
+                    channel
+                        .call(|request_id| Message { request_id, value })
+                        .map(|reply| reply.value == value)
+                })
+            })
+            .collect::<Vec<_>>();
+        for caller in callers {
+            assert!(track!(caller.join().expect("caller panicked"))?);
+        }
+        track!(peer.join().expect("peer panicked"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn channel_works_with_lockstep_peer() -> TopLevelResult {
+        let input = b"{\"request_id\": null, \"value\": 1}\n{\"value\": 2}\n";
+        let channel = MessageChannel::new(
+            MessageSender::new(Vec::new()),
+            MessageReceiver::<Message, _>::new(&input[..]),
+        );
+        let reply = track!(channel.call(|request_id| Message {
+            request_id,
+            value: 0
+        }))?;
+        assert_eq!(reply.value, 1);
+        let reply = track!(channel.call(|request_id| Message {
+            request_id,
+            value: 0
+        }))?;
+        assert_eq!(reply.value, 2);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+    impl SharedWriter {
+        fn lines(&self) -> Vec<String> {
+            let buf = self.0.lock().unwrap_or_else(|e| panic!("{}", e));
+            String::from_utf8_lossy(&buf)
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect()
+        }
+    }
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut inner = self.0.lock().unwrap_or_else(|e| panic!("{}", e));
+            inner.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stderr_forwarder_works() {
+        // The last line doesn't end with a newline.
+        let input = (0..25)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let writer = SharedWriter::default();
+        let stderr = StderrForwarder::spawn_with_writer(
+            io::Cursor::new(input),
+            "external[foo pid=1]".to_owned(),
+            writer.clone(),
+        );
+        assert!(stderr.wait_closed(Duration::from_secs(10)));
+
+        let forwarded = writer.lines();
+        assert_eq!(forwarded.len(), 25);
+        assert_eq!(forwarded[0], "external[foo pid=1]: line 0");
+        assert_eq!(forwarded[24], "external[foo pid=1]: line 24");
+
+        let tail = stderr.tail();
+        assert_eq!(tail.len(), STDERR_TAIL_LINES);
+        assert_eq!(tail[0], "line 5");
+        assert_eq!(tail[STDERR_TAIL_LINES - 1], "line 24");
+    }
+
+    #[test]
+    fn stderr_forwarder_does_not_wait_for_open_streams() {
+        let (_tx, pipe) = mpsc::channel::<u8>();
+        let stderr =
+            StderrForwarder::spawn_with_writer(BlockingReader(pipe), "p".to_owned(), io::sink());
+        assert!(!stderr.wait_closed(Duration::from_millis(10)));
+        assert!(stderr.tail().is_empty());
+    }
+
+    #[test]
+    fn recv_errors_contain_stderr_tail() -> TopLevelResult {
+        let stderr = StderrForwarder::spawn_with_writer(
+            &b"Traceback (most recent call last):\nValueError: oops"[..],
+            "p".to_owned(),
+            io::sink(),
+        );
+        let input = b"{\"foo\": 1}\n";
+        let mut rx = MessageReceiver::<Value, _>::new(&input[..]).stderr(stderr);
+        assert_eq!(track!(rx.recv())?, serde_json::json!({"foo": 1}));
+
+        // The stderr is awaited on end-of-stream, so the tail contains the last partial line.
+        let e = track_assert_some!(rx.recv().err(), ErrorKind::Bug);
+        assert_eq!(*e.kind(), ErrorKind::UnexpectedEos);
+        let message = e.to_string();
+        assert!(
+            message.contains("Last stderr lines of the peer:"),
+            "{}",
+            message
+        );
+        assert!(message.contains("ValueError: oops"), "{}", message);
+        Ok(())
+    }
+
+    struct BlockingReader(mpsc::Receiver<u8>);
+    impl Read for BlockingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.recv() {
+                Ok(b) => {
+                    buf[0] = b;
+                    Ok(1)
+                }
+                Err(_) => Ok(0),
+            }
+        }
+    }
+}
@@ -1,14 +1,26 @@
 //! EPI components for `problem`.
+#[cfg(feature = "external-process")]
 pub use self::embedded_script::{
     EmbeddedScriptEvaluator, EmbeddedScriptProblem, EmbeddedScriptProblemFactory,
     EmbeddedScriptProblemRecipe,
 };
+#[cfg(feature = "external-process")]
+pub(crate) use self::external_program::clear_thread_local_factories;
+#[cfg(feature = "external-process")]
 pub use self::external_program::{
     ExternalProgramEvaluator, ExternalProgramProblem, ExternalProgramProblemFactory,
-    ExternalProgramProblemRecipe,
+    ExternalProgramProblemRecipe, Isolation,
 };
 pub use self::message::ProblemMessage;
+pub use self::server::run_problem_server;
+#[cfg(all(unix, feature = "external-process"))]
+pub use self::unix_socket::UnixSocketProblemRecipe;
 
+#[cfg(feature = "external-process")]
 mod embedded_script;
+#[cfg(feature = "external-process")]
 mod external_program;
 mod message;
+mod server;
+#[cfg(all(unix, feature = "external-process"))]
+mod unix_socket;
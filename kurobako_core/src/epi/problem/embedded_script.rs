@@ -1,6 +1,6 @@
 use crate::epi::problem::{
     ExternalProgramEvaluator, ExternalProgramProblem, ExternalProgramProblemFactory,
-    ExternalProgramProblemRecipe,
+    ExternalProgramProblemRecipe, Isolation,
 };
 use crate::problem::{Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec};
 use crate::registry::FactoryRegistry;
@@ -9,7 +9,7 @@ use crate::trial::{Params, Values};
 use crate::{Error, Result};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write as _;
 use std::sync::Mutex;
 use structopt::StructOpt;
@@ -60,7 +60,15 @@ impl ProblemRecipe for EmbeddedScriptProblemRecipe {
         };
 
         let args = self.args.clone();
-        let eppr = ExternalProgramProblemRecipe { path, args };
+        let eppr = ExternalProgramProblemRecipe {
+            path,
+            args,
+            timeout: None,
+            shutdown_grace_period: None,
+            passthrough_stderr: false,
+            isolation: Isolation::Shared,
+            max_processes: None,
+        };
         let inner = track!(eppr.create_factory(registry))?;
 
         Ok(EmbeddedScriptProblemFactory { inner })
@@ -108,4 +116,16 @@ impl Evaluator for EmbeddedScriptEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
         track!(self.inner.evaluate(next_step))
     }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        track!(self.inner.evaluate_with_fidelity(next_step, fidelity))
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        self.inner.take_attrs()
+    }
 }
@@ -1,5 +1,6 @@
-use crate::epi::channel::{MessageReceiver, MessageSender};
+use crate::epi::channel::{MessageChannel, MessageReceiver, MessageSender, StderrForwarder};
 use crate::epi::problem::ProblemMessage;
+use crate::epi::{process, DEFAULT_SHUTDOWN_GRACE_PERIOD};
 use crate::problem::{Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec};
 use crate::registry::FactoryRegistry;
 use crate::rng::{ArcRng, Rng as _};
@@ -8,18 +9,35 @@ use crate::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
 use std::sync::atomic::{self, AtomicU64};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
 use std::thread_local;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+pub(crate) type BoxWrite = Box<dyn Write + Send>;
+pub(crate) type BoxRead = Box<dyn Read + Send>;
+type Channel = MessageChannel<ProblemMessage, BoxWrite, BoxRead>;
+
 thread_local! {
     static FACTORY_CACHE : RefCell<Option<(Vec<u8>, ExternalProgramProblemFactory)>> =
         RefCell::new(None);
 }
 
+pub(crate) fn clear_thread_local_factories() {
+    // The factory is dropped after the borrow is released.
+    let factory = FACTORY_CACHE.with(|f| f.borrow_mut().take());
+    std::mem::drop(factory);
+}
+
 /// Recipe for the problem implemented by an external program.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, StructOpt, Serialize, Deserialize)]
 #[structopt(rename_all = "kebab-case")]
@@ -29,39 +47,115 @@ pub struct ExternalProgramProblemRecipe {
 
     /// The command line arguments that are passed to the program.
     pub args: Vec<String>,
+
+    /// Timeout in seconds for waiting for a message from the program.
+    ///
+    /// If omitted, `kurobako` waits until the program replies or exits.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// Grace period in seconds for waiting for the program to exit after sending `SHUTDOWN_CAST`.
+    ///
+    /// If the program is still running after the period, it is killed.
+    /// If omitted, `DEFAULT_SHUTDOWN_GRACE_PERIOD` is used.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutdown_grace_period: Option<u64>,
+
+    /// If specified, the stderr of the program is inherited as is
+    /// instead of being forwarded line by line with a prefix.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "crate::epi::is_false")]
+    pub passthrough_stderr: bool,
+
+    /// Process isolation mode (`shared` or `per-trial`).
+    ///
+    /// See `Isolation` for the tradeoff between the modes.
+    #[structopt(long, default_value = "shared")]
+    #[serde(default, skip_serializing_if = "Isolation::is_shared")]
+    pub isolation: Isolation,
+
+    /// Maximum number of the processes that run concurrently in the `per-trial` isolation mode.
+    ///
+    /// Creating an evaluator blocks while the limit is reached
+    /// (and fails if `timeout` is specified and elapsed).
+    /// If omitted, the number of the available CPUs is used.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_processes: Option<NonZeroUsize>,
 }
 impl ExternalProgramProblemRecipe {
     fn create_new_factory(
         &self,
         _registry: &FactoryRegistry,
     ) -> Result<ExternalProgramProblemFactory> {
+        let factory = track!(self.spawn())?;
+        if self.isolation == Isolation::Shared {
+            return Ok(factory);
+        }
+
+        let max_processes = self
+            .max_processes
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+
+        // The process spawned above is only used to obtain the problem specification.
+        let spawner = ProcessSpawner {
+            recipe: self.clone(),
+            spec: track!(factory.specification())?,
+            max_processes,
+            running: Mutex::new(0),
+            released: Condvar::new(),
+        };
+        Ok(ExternalProgramProblemFactory(FactoryKind::PerTrial(
+            Arc::new(spawner),
+        )))
+    }
+
+    /// Spawns the program and makes a factory that shares the process.
+    fn spawn(&self) -> Result<ExternalProgramProblemFactory> {
         let mut child = track!(Command::new(&self.path)
             .args(&self.args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(if self.passthrough_stderr {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            })
             .spawn()
             .map_err(Error::from))?;
 
         let stdin = track_assert_some!(child.stdin.take(), ErrorKind::IoError);
         let stdout = track_assert_some!(child.stdout.take(), ErrorKind::IoError);
+        let stderr = child.stderr.take().map(|stderr| {
+            let name = self
+                .path
+                .file_name()
+                .unwrap_or_else(|| self.path.as_os_str());
+            let prefix = format!("external[{} pid={}]", name.to_string_lossy(), child.id());
+            StderrForwarder::spawn(stderr, prefix)
+        });
 
-        let tx = MessageSender::new(stdin);
-        let mut rx = MessageReceiver::new(stdout);
-        let spec = match track!(rx.recv())? {
-            ProblemMessage::ProblemSpecCast { spec } => spec,
-            m => track_panic!(ErrorKind::InvalidInput, "Unexpected message: {:?}", m),
+        let tx = MessageSender::new(Box::new(stdin) as BoxWrite);
+        let stdout = Box::new(stdout) as BoxRead;
+        let mut rx = if let Some(timeout) = self.timeout {
+            MessageReceiver::with_timeout(stdout, Duration::from_secs(timeout))
+        } else {
+            MessageReceiver::new(stdout)
         };
-
-        Ok(ExternalProgramProblemFactory(Arc::new(
-            ExternalProgramProblemFactoryInner {
-                spec,
-                child,
-                tx: Arc::new(Mutex::new(tx)),
-                rx: Arc::new(Mutex::new(rx)),
-                next_problem_id: AtomicU64::new(0),
-                next_evaluator_id: Arc::new(AtomicU64::new(0)),
-            },
-        )))
+        if let Some(stderr) = stderr {
+            rx = rx.stderr(stderr);
+        }
+        let grace_period = self
+            .shutdown_grace_period
+            .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD);
+        track!(ExternalProgramProblemFactory::new(
+            Some((child, Duration::from_secs(grace_period))),
+            tx,
+            rx
+        ))
     }
 
     fn cache_key(&self) -> Vec<u8> {
@@ -70,6 +164,7 @@ impl ExternalProgramProblemRecipe {
         for arg in &self.args {
             hasher.update(arg.as_bytes());
         }
+        hasher.update(self.isolation.to_string());
         hasher.finalize().to_vec()
     }
 }
@@ -93,89 +188,268 @@ impl ProblemRecipe for ExternalProgramProblemRecipe {
     }
 }
 
+/// Process isolation mode of external program problems.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Isolation {
+    /// All the problems and evaluators created by a factory share a single long-lived process.
+    ///
+    /// This is the cheapest mode, but state leaked by the program
+    /// (e.g., memory or global variables) may affect subsequent evaluations.
+    #[default]
+    Shared,
+
+    /// A fresh process is spawned for each evaluator (i.e., trial) and terminated when the evaluator is dropped.
+    ///
+    /// This prevents evaluations from interfering with each other at the cost of
+    /// a process startup and handshake per trial, which can dominate the run time of cheap problems.
+    /// Note that the process of a paused trial keeps running until the trial is resumed and finished.
+    PerTrial,
+}
+impl Isolation {
+    fn is_shared(&self) -> bool {
+        *self == Isolation::Shared
+    }
+}
+impl FromStr for Isolation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "shared" => Ok(Isolation::Shared),
+            "per-trial" => Ok(Isolation::PerTrial),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown isolation mode: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for Isolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Isolation::Shared => write!(f, "shared"),
+            Isolation::PerTrial => write!(f, "per-trial"),
+        }
+    }
+}
+
+/// Spawner of the per-trial processes, which limits the number of the running processes.
+#[derive(Debug)]
+struct ProcessSpawner {
+    recipe: ExternalProgramProblemRecipe,
+    spec: ProblemSpec,
+    max_processes: usize,
+    running: Mutex<usize>,
+    released: Condvar,
+}
+impl ProcessSpawner {
+    fn spawn(self: &Arc<Self>) -> Result<(ExternalProgramProblemFactory, ProcessSlot)> {
+        let slot = track!(self.acquire())?;
+        let factory = track!(self.recipe.spawn())?;
+        Ok((factory, slot))
+    }
+
+    fn acquire(self: &Arc<Self>) -> Result<ProcessSlot> {
+        let deadline = self
+            .recipe
+            .timeout
+            .map(|t| Instant::now() + Duration::from_secs(t));
+        let mut running = track!(self.running.lock().map_err(Error::from))?;
+        while *running >= self.max_processes {
+            running = if let Some(deadline) = deadline {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                track_assert!(
+                    !timeout.is_zero(),
+                    ErrorKind::Other,
+                    "Timed out waiting for one of the {} running processes to exit",
+                    self.max_processes
+                );
+                let (running, _) = track!(self
+                    .released
+                    .wait_timeout(running, timeout)
+                    .map_err(Error::from))?;
+                running
+            } else {
+                track!(self.released.wait(running).map_err(Error::from))?
+            };
+        }
+        *running += 1;
+        Ok(ProcessSlot(Arc::clone(self)))
+    }
+}
+
+#[derive(Debug)]
+struct ProcessSlot(Arc<ProcessSpawner>);
+impl Drop for ProcessSlot {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.0.running.lock() {
+            *running -= 1;
+            self.0.released.notify_one();
+        }
+    }
+}
+
 /// Factory for the problem implemented by an external program.
 #[derive(Debug, Clone)]
-pub struct ExternalProgramProblemFactory(Arc<ExternalProgramProblemFactoryInner>);
+pub struct ExternalProgramProblemFactory(FactoryKind);
+impl ExternalProgramProblemFactory {
+    /// Makes a new factory that communicates with the peer via the given channels.
+    ///
+    /// The first message from the peer must be `ProblemSpecCast`.
+    /// If `child` is given, it is terminated when the factory is dropped
+    /// (`SHUTDOWN_CAST` is sent and the process is killed if it doesn't exit within the grace period).
+    pub(crate) fn new(
+        child: Option<(Child, Duration)>,
+        tx: MessageSender<ProblemMessage, BoxWrite>,
+        rx: MessageReceiver<ProblemMessage, BoxRead>,
+    ) -> Result<Self> {
+        let channel = MessageChannel::new(tx, rx);
+        let spec = match track!(channel.recv())? {
+            ProblemMessage::ProblemSpecCast { spec } => spec,
+            m => track_panic!(ErrorKind::InvalidInput, "Unexpected message: {:?}", m),
+        };
+
+        Ok(Self(FactoryKind::Shared(Arc::new(
+            ExternalProgramProblemFactoryInner {
+                spec,
+                child,
+                channel: Arc::new(channel),
+                next_problem_id: AtomicU64::new(0),
+                next_evaluator_id: Arc::new(AtomicU64::new(0)),
+            },
+        ))))
+    }
+
+    fn shared(&self) -> Result<&ExternalProgramProblemFactoryInner> {
+        match &self.0 {
+            FactoryKind::Shared(inner) => Ok(inner),
+            FactoryKind::PerTrial(_) => track_panic!(ErrorKind::Bug),
+        }
+    }
+}
 impl ProblemFactory for ExternalProgramProblemFactory {
     type Problem = ExternalProgramProblem;
 
     fn specification(&self) -> Result<ProblemSpec> {
-        track!(self.0.specification())
+        match &self.0 {
+            FactoryKind::Shared(inner) => Ok(inner.spec.clone()),
+            FactoryKind::PerTrial(spawner) => Ok(spawner.spec.clone()),
+        }
     }
 
-    fn create_problem(&self, rng: ArcRng) -> Result<Self::Problem> {
-        track!(self.0.create_problem(rng))
+    fn create_problem(&self, mut rng: ArcRng) -> Result<Self::Problem> {
+        let kind = match &self.0 {
+            FactoryKind::Shared(inner) => {
+                ProblemKind::Shared(track!(inner.create_problem(rng.gen()))?)
+            }
+            FactoryKind::PerTrial(spawner) => ProblemKind::PerTrial {
+                spawner: Arc::clone(spawner),
+                random_seed: rng.gen(),
+            },
+        };
+        Ok(ExternalProgramProblem(kind))
     }
 }
 
+#[derive(Debug, Clone)]
+enum FactoryKind {
+    Shared(Arc<ExternalProgramProblemFactoryInner>),
+    PerTrial(Arc<ProcessSpawner>),
+}
+
 #[derive(Debug)]
 struct ExternalProgramProblemFactoryInner {
     spec: ProblemSpec,
-    child: Child,
-    tx: Arc<Mutex<MessageSender<ProblemMessage, ChildStdin>>>,
-    rx: Arc<Mutex<MessageReceiver<ProblemMessage, ChildStdout>>>,
+    child: Option<(Child, Duration)>,
+    channel: Arc<Channel>,
     next_problem_id: AtomicU64,
     next_evaluator_id: Arc<AtomicU64>,
 }
-impl ProblemFactory for ExternalProgramProblemFactoryInner {
-    type Problem = ExternalProgramProblem;
-
-    fn specification(&self) -> Result<ProblemSpec> {
-        Ok(self.spec.clone())
-    }
-
-    fn create_problem(&self, mut rng: ArcRng) -> Result<Self::Problem> {
+impl ExternalProgramProblemFactoryInner {
+    fn create_problem(&self, random_seed: u64) -> Result<SharedProblem> {
         let problem_id = self.next_problem_id.fetch_add(1, atomic::Ordering::SeqCst);
         let m = ProblemMessage::CreateProblemCast {
             problem_id,
-            random_seed: rng.gen(),
+            random_seed,
         };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
+        track!(self.channel.cast(&m))?;
 
-        Ok(ExternalProgramProblem {
+        Ok(SharedProblem {
             problem_id,
-            tx: Arc::clone(&self.tx),
-            rx: Arc::clone(&self.rx),
+            channel: Arc::clone(&self.channel),
             next_evaluator_id: Arc::clone(&self.next_evaluator_id),
         })
     }
 }
 impl Drop for ExternalProgramProblemFactoryInner {
     fn drop(&mut self) {
-        if self.child.kill().is_ok() {
-            let _ = self.child.wait(); // for preventing the child process becomes a zombie.
+        if let Some((child, grace_period)) = &mut self.child {
+            let _ = self.channel.cast(&ProblemMessage::ShutdownCast);
+            process::terminate_child(child, *grace_period);
         }
     }
 }
 
 /// Problem that is implemented by an external program.
 #[derive(Debug)]
-pub struct ExternalProgramProblem {
-    problem_id: u64,
-    tx: Arc<Mutex<MessageSender<ProblemMessage, ChildStdin>>>,
-    rx: Arc<Mutex<MessageReceiver<ProblemMessage, ChildStdout>>>,
-    next_evaluator_id: Arc<AtomicU64>,
-}
+pub struct ExternalProgramProblem(ProblemKind);
 impl Problem for ExternalProgramProblem {
     type Evaluator = ExternalProgramEvaluator;
 
     fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        let kind = match &self.0 {
+            ProblemKind::Shared(problem) => {
+                EvaluatorKind::Shared(track!(problem.create_evaluator(params))?)
+            }
+            ProblemKind::PerTrial {
+                spawner,
+                random_seed,
+            } => {
+                let (factory, slot) = track!(spawner.spawn())?;
+                let problem = track!(track!(factory.shared())?.create_problem(*random_seed))?;
+                let evaluator = track!(problem.create_evaluator(params))?;
+                EvaluatorKind::PerTrial {
+                    evaluator,
+                    _problem: problem,
+                    _factory: factory,
+                    _slot: slot,
+                }
+            }
+        };
+        Ok(ExternalProgramEvaluator(kind))
+    }
+}
+
+#[derive(Debug)]
+enum ProblemKind {
+    Shared(SharedProblem),
+    PerTrial {
+        spawner: Arc<ProcessSpawner>,
+        random_seed: u64,
+    },
+}
+
+/// Problem that lives in a (possibly shared) process.
+#[derive(Debug)]
+struct SharedProblem {
+    problem_id: u64,
+    channel: Arc<Channel>,
+    next_evaluator_id: Arc<AtomicU64>,
+}
+impl SharedProblem {
+    fn create_evaluator(&self, params: Params) -> Result<SharedEvaluator> {
         let evaluator_id = self
             .next_evaluator_id
             .fetch_add(1, atomic::Ordering::SeqCst);
-        let m = ProblemMessage::CreateEvaluatorCall {
-            problem_id: self.problem_id,
-            evaluator_id,
-            params,
-        };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
-
-        let mut rx = track!(self.rx.lock().map_err(Error::from))?;
-        match track!(rx.recv())? {
-            ProblemMessage::CreateEvaluatorReply => {}
-            ProblemMessage::ErrorReply { kind, message } => {
+        let reply = track!(self
+            .channel
+            .call(|request_id| ProblemMessage::CreateEvaluatorCall {
+                problem_id: self.problem_id,
+                evaluator_id,
+                params,
+                request_id,
+            }))?;
+        match reply {
+            ProblemMessage::CreateEvaluatorReply { .. } => {}
+            ProblemMessage::ErrorReply { kind, message, .. } => {
                 if let Some(message) = message {
                     track_panic!(kind, "{}", message);
                 } else {
@@ -187,47 +461,91 @@ impl Problem for ExternalProgramProblem {
             }
         }
 
-        Ok(ExternalProgramEvaluator {
+        Ok(SharedEvaluator {
             evaluator_id,
-            tx: Arc::clone(&self.tx),
-            rx: Arc::clone(&self.rx),
+            channel: Arc::clone(&self.channel),
+            attrs: BTreeMap::new(),
         })
     }
 }
-impl Drop for ExternalProgramProblem {
+impl Drop for SharedProblem {
     fn drop(&mut self) {
         let problem_id = self.problem_id;
         let m = ProblemMessage::DropProblemCast { problem_id };
-        if let Ok(mut tx) = self.tx.lock() {
-            let _ = tx.send(&m);
-        }
+        let _ = self.channel.cast(&m);
     }
 }
 
 /// Evaluator that is implemented by an external program.
 #[derive(Debug)]
-pub struct ExternalProgramEvaluator {
-    evaluator_id: u64,
-    tx: Arc<Mutex<MessageSender<ProblemMessage, ChildStdin>>>,
-    rx: Arc<Mutex<MessageReceiver<ProblemMessage, ChildStdout>>>,
+pub struct ExternalProgramEvaluator(EvaluatorKind);
+impl ExternalProgramEvaluator {
+    fn inner(&mut self) -> &mut SharedEvaluator {
+        match &mut self.0 {
+            EvaluatorKind::Shared(evaluator) => evaluator,
+            EvaluatorKind::PerTrial { evaluator, .. } => evaluator,
+        }
+    }
 }
 impl Evaluator for ExternalProgramEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
-        let evaluator_id = self.evaluator_id;
-        let m = ProblemMessage::EvaluateCall {
-            evaluator_id,
-            next_step,
-        };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
+        track!(self.evaluate_with_fidelity(next_step, &Params::default()))
+    }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        track!(self.inner().evaluate(next_step, fidelity))
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        std::mem::take(&mut self.inner().attrs)
+    }
+}
 
-        let mut rx = track!(self.rx.lock().map_err(Error::from))?;
-        match track!(rx.recv())? {
+// The fields are dropped in the declaration order, so the process is shut down
+// after the evaluator and the problem are dropped, and then the slot is released.
+#[derive(Debug)]
+enum EvaluatorKind {
+    Shared(SharedEvaluator),
+    PerTrial {
+        evaluator: SharedEvaluator,
+        _problem: SharedProblem,
+        _factory: ExternalProgramProblemFactory,
+        _slot: ProcessSlot,
+    },
+}
+
+#[derive(Debug)]
+struct SharedEvaluator {
+    evaluator_id: u64,
+    channel: Arc<Channel>,
+    attrs: BTreeMap<String, serde_json::Value>,
+}
+impl SharedEvaluator {
+    fn evaluate(&mut self, next_step: u64, fidelity: &Params) -> Result<(u64, Values)> {
+        let evaluator_id = self.evaluator_id;
+        let reply = track!(self
+            .channel
+            .call(|request_id| ProblemMessage::EvaluateCall {
+                evaluator_id,
+                next_step,
+                fidelity: fidelity.clone(),
+                request_id,
+            }))?;
+        match reply {
             ProblemMessage::EvaluateReply {
                 current_step,
                 values,
-            } => Ok((current_step, values)),
-            ProblemMessage::ErrorReply { kind, message } => {
+                attrs,
+                ..
+            } => {
+                self.attrs = attrs;
+                Ok((current_step, values))
+            }
+            ProblemMessage::ErrorReply { kind, message, .. } => {
                 if let Some(message) = message {
                     track_panic!(kind, "{}", message);
                 } else {
@@ -240,13 +558,135 @@ impl Evaluator for ExternalProgramEvaluator {
         }
     }
 }
-impl Drop for ExternalProgramEvaluator {
+impl Drop for SharedEvaluator {
     fn drop(&mut self) {
         let m = ProblemMessage::DropEvaluatorCast {
             evaluator_id: self.evaluator_id,
         };
-        if let Ok(mut tx) = self.tx.lock() {
-            let _ = tx.send(&m);
-        }
+        let _ = self.channel.cast(&m);
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::epi::solver::ExternalProgramSolverRecipe;
+    use crate::problem::ProblemSpecBuilder;
+    use std::path::Path;
+    use trackable::result::TopLevelResult;
+
+    fn recipe(script: &str, arg: &Path, grace_period: u64) -> Result<ExternalProgramProblemRecipe> {
+        let spec = track!(ProblemSpecBuilder::new("shutdown")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .finish())?;
+        let m = ProblemMessage::ProblemSpecCast { spec };
+        let m = track!(serde_json::to_string(&m).map_err(Error::from))?;
+        Ok(ExternalProgramProblemRecipe {
+            path: PathBuf::from("sh"),
+            args: vec![
+                "-c".to_owned(),
+                script.to_owned(),
+                "sh".to_owned(),
+                m,
+                arg.to_string_lossy().into_owned(),
+            ],
+            timeout: None,
+            shutdown_grace_period: Some(grace_period),
+            passthrough_stderr: false,
+            isolation: Isolation::Shared,
+            max_processes: None,
+        })
+    }
+
+    fn terminate(recipe: &ExternalProgramProblemRecipe) -> Result<u32> {
+        let registry =
+            FactoryRegistry::new::<ExternalProgramProblemRecipe, ExternalProgramSolverRecipe>();
+        let factory = track!(recipe.create_new_factory(&registry))?;
+        let pid = track!(factory.shared())?.child.as_ref().map(|c| c.0.id());
+        let pid = track_assert_some!(pid, ErrorKind::Bug);
+        std::mem::drop(factory);
+        Ok(pid)
+    }
+
+    #[test]
+    fn external_program_terminates_on_drop() -> TopLevelResult {
+        let marker = std::env::temp_dir().join(format!("kurobako_shutdown_{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+
+        // A well-behaved program exits by itself on `SHUTDOWN_CAST`.
+        let script = r#"printf '%s\n' "$1"
+while read -r line; do
+  case "$line" in *SHUTDOWN_CAST*) touch "$2"; exit 0;; esac
+done"#;
+        let pid = track!(terminate(&track!(recipe(script, &marker, 10))?))?;
+        assert!(marker.exists());
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+        track!(std::fs::remove_file(&marker).map_err(Error::from))?;
+
+        // A program that ignores `SHUTDOWN_CAST` is killed after the grace period.
+        let script = r#"printf '%s\n' "$1"
+while true; do sleep 1; done"#;
+        let pid = track!(terminate(&track!(recipe(script, &marker, 0))?))?;
+        assert!(!marker.exists());
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn isolation_modes_work() -> TopLevelResult {
+        // Reports the number of the evaluators created by the process as an attribute.
+        let script = r#"printf '%s\n' "$1"
+n=0
+while read -r line; do
+  case "$line" in
+    *CREATE_EVALUATOR_CALL*)
+      n=$((n+1))
+      x=$(printf '%s\n' "$line" | sed 's/.*"params":\[\([^]]*\)\].*/\1/')
+      echo '{"type":"CREATE_EVALUATOR_REPLY"}';;
+    *EVALUATE_CALL*)
+      printf '{"type":"EVALUATE_REPLY","current_step":1,"values":[%s],"attrs":{"n":%d}}\n' "$x" "$n";;
+    *SHUTDOWN_CAST*) exit 0;;
+  esac
+done"#;
+        let registry =
+            FactoryRegistry::new::<ExternalProgramProblemRecipe, ExternalProgramSolverRecipe>();
+        let run = |recipe: &ExternalProgramProblemRecipe| -> Result<Vec<(f64, Option<u64>)>> {
+            let factory = track!(recipe.create_new_factory(&registry))?;
+            let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+            [0.25, 0.5, 0.75]
+                .iter()
+                .map(|&x| {
+                    let mut evaluator = track!(problem.create_evaluator(Params::new(vec![x])))?;
+                    let (_, values) = track!(evaluator.evaluate(1))?;
+                    let n = evaluator.take_attrs().remove("n").and_then(|n| n.as_u64());
+                    Ok((values[0], n))
+                })
+                .collect()
+        };
+
+        let mut recipe = track!(recipe(script, Path::new("unused"), 10))?;
+        let shared = track!(run(&recipe))?;
+        recipe.isolation = Isolation::PerTrial;
+        let per_trial = track!(run(&recipe))?;
+
+        let values = |xs: &[(f64, Option<u64>)]| xs.iter().map(|x| x.0).collect::<Vec<_>>();
+        let counts = |xs: &[(f64, Option<u64>)]| xs.iter().map(|x| x.1).collect::<Vec<_>>();
+        assert_eq!(values(&shared), vec![0.25, 0.5, 0.75]);
+        assert_eq!(values(&per_trial), values(&shared));
+        assert_eq!(counts(&shared), vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(counts(&per_trial), vec![Some(1); 3]);
+
+        // The number of the concurrent processes is limited.
+        recipe.max_processes = NonZeroUsize::new(1);
+        recipe.timeout = Some(1);
+        let factory = track!(recipe.create_new_factory(&registry))?;
+        let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+        let evaluator = track!(problem.create_evaluator(Params::new(vec![0.5])))?;
+        assert!(problem.create_evaluator(Params::new(vec![0.5])).is_err());
+        std::mem::drop(evaluator);
+        track!(problem.create_evaluator(Params::new(vec![0.5])))?;
+        Ok(())
     }
 }
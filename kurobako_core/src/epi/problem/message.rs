@@ -1,10 +1,16 @@
+use crate::epi::channel::Correlated;
 use crate::problem::ProblemSpec;
 use crate::trial::{Params, Values};
 use crate::ErrorKind;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Messages that are used to communicate with external problems.
+///
+/// `request_id` of a reply message is the same as the one of the corresponding call message.
+/// Peers that don't echo the identifiers are also supported (the calls are issued in lock-step).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[allow(missing_docs)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ProblemMessage {
@@ -22,22 +28,50 @@ pub enum ProblemMessage {
         problem_id: u64,
         evaluator_id: u64,
         params: Params,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    CreateEvaluatorReply {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
-    CreateEvaluatorReply,
     DropEvaluatorCast {
         evaluator_id: u64,
     },
     EvaluateCall {
         evaluator_id: u64,
         next_step: u64,
+        #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+        fidelity: Params,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
     EvaluateReply {
         current_step: u64,
         values: Values,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: BTreeMap<String, serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
+    ShutdownCast,
     ErrorReply {
         kind: ErrorKind,
         #[serde(default)]
         message: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
 }
+impl Correlated for ProblemMessage {
+    fn request_id(&self) -> Option<u64> {
+        match self {
+            Self::CreateEvaluatorCall { request_id, .. }
+            | Self::CreateEvaluatorReply { request_id }
+            | Self::EvaluateCall { request_id, .. }
+            | Self::EvaluateReply { request_id, .. }
+            | Self::ErrorReply { request_id, .. } => *request_id,
+            _ => None,
+        }
+    }
+}
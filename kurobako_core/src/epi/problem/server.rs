@@ -0,0 +1,177 @@
+use crate::epi::channel::{Correlated as _, MessageReceiver, MessageSender};
+use crate::epi::problem::ProblemMessage;
+use crate::problem::{Evaluator as _, Problem, ProblemFactory};
+use crate::rng::ArcRng;
+use crate::trial::{Params, Values};
+use crate::{ErrorKind, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+
+/// Runs the server-side EPI loop of the problem made by the given factory.
+///
+/// This function sends `PROBLEM_SPEC_CAST` to the standard output, and then handles
+/// the messages read from the standard input until `SHUTDOWN_CAST` arrives or the input is closed.
+/// So, a program that just calls this function can be used via `ExternalProgramProblemRecipe`.
+///
+/// Errors raised by the problems or evaluators are replied as `ERROR_REPLY` messages
+/// instead of terminating the loop.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use kurobako_core::problem::ProblemFactory;
+/// # use kurobako_core::epi::problem::run_problem_server;
+/// # fn example<F: ProblemFactory>(factory: F) -> kurobako_core::Result<()> {
+/// run_problem_server(factory)
+/// # }
+/// ```
+pub fn run_problem_server<F: ProblemFactory>(factory: F) -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    track!(serve(factory, stdin.lock(), stdout.lock()))
+}
+
+fn serve<F, R, W>(factory: F, reader: R, writer: W) -> Result<()>
+where
+    F: ProblemFactory,
+    R: Read,
+    W: Write,
+{
+    let mut tx = MessageSender::new(writer);
+    let mut rx = MessageReceiver::<ProblemMessage, _>::new(reader);
+    let mut server = ProblemServer {
+        factory,
+        problems: HashMap::new(),
+        evaluators: HashMap::new(),
+    };
+
+    let spec = track!(server.factory.specification())?;
+    track!(tx.send(&ProblemMessage::ProblemSpecCast { spec }))?;
+    loop {
+        let message = match rx.recv() {
+            Ok(m) => m,
+            Err(e) if *e.kind() == ErrorKind::UnexpectedEos => return Ok(()),
+            Err(e) => return Err(track!(e)),
+        };
+        if let ProblemMessage::ShutdownCast = message {
+            return Ok(());
+        }
+        if let Some(reply) = server.handle(message) {
+            track!(tx.send(&reply))?;
+        }
+    }
+}
+
+struct ProblemServer<F: ProblemFactory> {
+    factory: F,
+    problems: HashMap<u64, Result<F::Problem>>,
+    evaluators: HashMap<u64, <F::Problem as Problem>::Evaluator>,
+}
+impl<F: ProblemFactory> ProblemServer<F> {
+    fn handle(&mut self, message: ProblemMessage) -> Option<ProblemMessage> {
+        match message {
+            ProblemMessage::CreateProblemCast {
+                problem_id,
+                random_seed,
+            } => {
+                // Failures are reported when evaluators of the problem are requested.
+                let problem = track!(self.factory.create_problem(ArcRng::new(random_seed)));
+                self.problems.insert(problem_id, problem);
+                None
+            }
+            ProblemMessage::DropProblemCast { problem_id } => {
+                self.problems.remove(&problem_id);
+                None
+            }
+            ProblemMessage::CreateEvaluatorCall {
+                problem_id,
+                evaluator_id,
+                params,
+                request_id,
+            } => {
+                let result = self.create_evaluator(problem_id, evaluator_id, params);
+                Some(reply(result, request_id, |()| {
+                    ProblemMessage::CreateEvaluatorReply { request_id }
+                }))
+            }
+            ProblemMessage::DropEvaluatorCast { evaluator_id } => {
+                self.evaluators.remove(&evaluator_id);
+                None
+            }
+            ProblemMessage::EvaluateCall {
+                evaluator_id,
+                next_step,
+                fidelity,
+                request_id,
+            } => {
+                let result = self.evaluate(evaluator_id, next_step, &fidelity);
+                Some(reply(
+                    result,
+                    request_id,
+                    |(current_step, values, attrs)| ProblemMessage::EvaluateReply {
+                        current_step,
+                        values,
+                        attrs,
+                        request_id,
+                    },
+                ))
+            }
+            m => Some(ProblemMessage::ErrorReply {
+                kind: ErrorKind::InvalidInput,
+                message: Some(format!("Unexpected message: {:?}", m)),
+                request_id: m.request_id(),
+            }),
+        }
+    }
+
+    fn create_evaluator(
+        &mut self,
+        problem_id: u64,
+        evaluator_id: u64,
+        params: Params,
+    ) -> Result<()> {
+        let problem = track_assert_some!(
+            self.problems.get(&problem_id),
+            ErrorKind::InvalidInput,
+            "Unknown problem: {}",
+            problem_id
+        );
+        let problem = match problem {
+            Ok(problem) => problem,
+            Err(e) => return Err(track!(e.clone())),
+        };
+        let evaluator = track!(problem.create_evaluator(params))?;
+        self.evaluators.insert(evaluator_id, evaluator);
+        Ok(())
+    }
+
+    fn evaluate(
+        &mut self,
+        evaluator_id: u64,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values, BTreeMap<String, serde_json::Value>)> {
+        let evaluator = track_assert_some!(
+            self.evaluators.get_mut(&evaluator_id),
+            ErrorKind::InvalidInput,
+            "Unknown evaluator: {}",
+            evaluator_id
+        );
+        let (current_step, values) = track!(evaluator.evaluate_with_fidelity(next_step, fidelity))?;
+        Ok((current_step, values, evaluator.take_attrs()))
+    }
+}
+
+fn reply<T, F>(result: Result<T>, request_id: Option<u64>, f: F) -> ProblemMessage
+where
+    F: FnOnce(T) -> ProblemMessage,
+{
+    match result {
+        Ok(x) => f(x),
+        Err(e) => ProblemMessage::ErrorReply {
+            kind: *e.kind(),
+            message: Some(e.to_string()),
+            request_id,
+        },
+    }
+}
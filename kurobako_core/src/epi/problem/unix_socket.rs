@@ -0,0 +1,212 @@
+use super::external_program::{BoxRead, BoxWrite};
+use crate::epi::channel::{MessageReceiver, MessageSender};
+use crate::epi::problem::ExternalProgramProblemFactory;
+use crate::problem::ProblemRecipe;
+use crate::registry::FactoryRegistry;
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+/// Recipe for the problem served by an external process listening on a Unix domain socket.
+///
+/// The messages exchanged over the socket are the same as `ExternalProgramProblemRecipe`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct UnixSocketProblemRecipe {
+    /// The path of the socket.
+    pub path: PathBuf,
+
+    /// Period in seconds for retrying to connect to the socket.
+    #[structopt(long, default_value = "10")]
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Timeout in seconds for waiting for a message from the peer.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+impl UnixSocketProblemRecipe {
+    fn connect(&self) -> Result<UnixStream> {
+        let deadline = Instant::now() + Duration::from_secs(self.connect_timeout);
+        let mut backoff = Duration::from_millis(10);
+        loop {
+            match UnixStream::connect(&self.path) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    track_assert!(
+                        Instant::now() + backoff < deadline,
+                        ErrorKind::IoError,
+                        "Cannot connect to the socket {:?}: {}",
+                        self.path,
+                        e
+                    );
+                    thread::sleep(backoff);
+                    backoff = std::cmp::min(backoff * 2, Duration::from_secs(1));
+                }
+            }
+        }
+    }
+}
+impl ProblemRecipe for UnixSocketProblemRecipe {
+    type Factory = ExternalProgramProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        let stream = track!(self.connect())?;
+        let reader = track!(stream.try_clone().map_err(crate::Error::from); self.path)?;
+
+        let tx = MessageSender::new(Box::new(SocketWriter(stream)) as BoxWrite);
+        let reader = Box::new(reader) as BoxRead;
+        let rx = if let Some(timeout) = self.timeout {
+            MessageReceiver::with_timeout(reader, Duration::from_secs(timeout))
+        } else {
+            MessageReceiver::new(reader)
+        };
+        track!(ExternalProgramProblemFactory::new(None, tx, rx); self.path)
+    }
+}
+
+/// Writing half of a socket that shuts the connection down when dropped.
+///
+/// This is needed to notify the peer of the end of the session
+/// because the reading half may be still owned by a background thread.
+struct SocketWriter(UnixStream);
+impl Write for SocketWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+impl Drop for SocketWriter {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::epi::channel::Correlated as _;
+    use crate::epi::problem::ProblemMessage;
+    use crate::epi::solver::ExternalProgramSolverRecipe;
+    use crate::problem::{Evaluator as _, Problem as _, ProblemFactory as _, ProblemSpecBuilder};
+    use crate::rng::ArcRng;
+    use crate::trial::{Params, Values};
+    use std::collections::BTreeMap;
+    use std::os::unix::net::UnixListener;
+    use trackable::result::TopLevelResult;
+
+    fn serve(listener: UnixListener) -> Result<Vec<ProblemMessage>> {
+        let (stream, _) = track!(listener.accept().map_err(crate::Error::from))?;
+        let reader = track!(stream.try_clone().map_err(crate::Error::from))?;
+        let mut tx = MessageSender::new(stream);
+        let mut rx = MessageReceiver::new(reader);
+
+        let spec = track!(ProblemSpecBuilder::new("socket")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .finish())?;
+        track!(tx.send(&ProblemMessage::ProblemSpecCast { spec }))?;
+
+        let mut received = Vec::new();
+        loop {
+            let m = match rx.recv() {
+                Ok(m) => m,
+                Err(e) if *e.kind() == ErrorKind::UnexpectedEos => break,
+                Err(e) => return Err(track!(e)),
+            };
+            match &m {
+                ProblemMessage::CreateEvaluatorCall { .. } => {
+                    track!(tx.send(&ProblemMessage::CreateEvaluatorReply {
+                        request_id: m.request_id(),
+                    }))?;
+                }
+                ProblemMessage::EvaluateCall { next_step, .. } => {
+                    track!(tx.send(&ProblemMessage::EvaluateReply {
+                        current_step: *next_step,
+                        values: Values::new(vec![1.5]),
+                        attrs: BTreeMap::new(),
+                        request_id: m.request_id(),
+                    }))?;
+                }
+                _ => {}
+            }
+            received.push(m);
+        }
+        Ok(received)
+    }
+
+    #[test]
+    fn unix_socket_problem_works() -> TopLevelResult {
+        let path = std::env::temp_dir().join(format!("kurobako_test_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = track!(UnixListener::bind(&path).map_err(crate::Error::from))?;
+        let server = thread::spawn(move || serve(listener));
+
+        let recipe = UnixSocketProblemRecipe {
+            path: path.clone(),
+            connect_timeout: 1,
+            timeout: Some(10),
+        };
+        let registry =
+            FactoryRegistry::new::<UnixSocketProblemRecipe, ExternalProgramSolverRecipe>();
+        {
+            let factory = track!(recipe.create_factory(&registry))?;
+            assert_eq!(track!(factory.specification())?.name, "socket");
+
+            let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+            let mut evaluator = track!(problem.create_evaluator(Params::new(vec![0.5])))?;
+            let (step, values) = track!(evaluator.evaluate(1))?;
+            assert_eq!(step, 1);
+            assert_eq!(&values[..], &[1.5]);
+        }
+
+        let received = track!(server.join().expect("server panicked"))?;
+        let types = received
+            .iter()
+            .map(|m| match m {
+                ProblemMessage::CreateProblemCast { .. } => "create_problem",
+                ProblemMessage::CreateEvaluatorCall { .. } => "create_evaluator",
+                ProblemMessage::EvaluateCall { .. } => "evaluate",
+                ProblemMessage::DropEvaluatorCast { .. } => "drop_evaluator",
+                ProblemMessage::DropProblemCast { .. } => "drop_problem",
+                _ => "other",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                "create_problem",
+                "create_evaluator",
+                "evaluate",
+                "drop_evaluator",
+                "drop_problem"
+            ]
+        );
+
+        let _ = std::fs::remove_file(&path);
+
+        let recipe = UnixSocketProblemRecipe {
+            path: path.clone(),
+            connect_timeout: 0,
+            timeout: None,
+        };
+        let e = recipe.create_factory(&registry).expect_err("must fail");
+        assert!(e.to_string().contains(&*path.to_string_lossy()));
+        Ok(())
+    }
+}
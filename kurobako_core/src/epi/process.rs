@@ -0,0 +1,93 @@
+//! Helpers to spawn and terminate external programs.
+use crate::epi::channel::StderrForwarder;
+use crate::{ErrorKind, Result};
+use std::env;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+use trackable::error::ErrorKindExt as _;
+
+/// Period for waiting for an external program to exit after it failed the handshake.
+const HANDSHAKE_FAILURE_WAIT: Duration = Duration::from_secs(1);
+
+/// Makes a command that executes `program`.
+///
+/// If `python` is specified, `program` is executed as a script by the interpreter.
+/// If `venv` is specified, the virtual environment is activated in the same way as its `bin/activate` does
+/// (i.e., `$VIRTUAL_ENV` is set, `$VIRTUAL_ENV/bin` is prepended to `$PATH` and `$PYTHONHOME` is unset),
+/// so that the `python` found via `$PATH` (including the one in a `#!/usr/bin/env python3` line) is the one in the environment.
+pub(crate) fn command(
+    program: &Path,
+    python: Option<&Path>,
+    venv: Option<&Path>,
+) -> Result<Command> {
+    let mut command = if let Some(python) = python {
+        let mut command = Command::new(python);
+        command.arg(program);
+        command
+    } else {
+        Command::new(program)
+    };
+    if let Some(venv) = venv {
+        let mut paths = vec![venv.join("bin")];
+        if let Some(path) = env::var_os("PATH") {
+            paths.extend(env::split_paths(&path));
+        }
+        let path = track!(env::join_paths(paths).map_err(|e| ErrorKind::InvalidInput.cause(e)))?;
+        command
+            .env("VIRTUAL_ENV", venv)
+            .env("PATH", path)
+            .env_remove("PYTHONHOME");
+    }
+    Ok(command)
+}
+
+/// Describes the state of a child process that failed the handshake
+/// (e.g., because the interpreter is not the expected one).
+///
+/// The child is given a short period to exit so that its exit status and the last lines of its stderr are available.
+pub(crate) fn describe_handshake_failure(
+    child: &mut Child,
+    stderr: Option<&StderrForwarder>,
+) -> String {
+    let deadline = Instant::now() + HANDSHAKE_FAILURE_WAIT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(status) => break status,
+            Err(_) => break None,
+        }
+    };
+    let mut message = match status {
+        Some(status) => format!("The program exited before the handshake ({})", status),
+        None => "The program is running but didn't complete the handshake".to_owned(),
+    };
+    if let Some(stderr) = stderr {
+        stderr.wait_closed(deadline.saturating_duration_since(Instant::now()));
+        let tail = stderr.tail();
+        if tail.is_empty() {
+            message.push_str("; no stderr output");
+        } else {
+            message.push_str("; last stderr lines:\n");
+            message.push_str(&tail.join("\n"));
+        }
+    }
+    message
+}
+
+/// Waits for the child process to exit within the grace period, and then kills it if it's still alive.
+///
+/// In any case, the child is reaped so that it doesn't become a zombie.
+pub(crate) fn terminate_child(child: &mut Child, grace_period: Duration) {
+    let deadline = Instant::now() + grace_period;
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(_)) => return,
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(_) => break,
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
@@ -1,12 +1,20 @@
 //! EPI components for `solver`.
+#[cfg(feature = "external-process")]
 pub use self::embedded_script::{
     EmbeddedScriptSolver, EmbeddedScriptSolverFactory, EmbeddedScriptSolverRecipe,
 };
+#[cfg(feature = "external-process")]
+pub(crate) use self::external_program::clear_thread_local_factories;
+#[cfg(feature = "external-process")]
 pub use self::external_program::{
     ExternalProgramSolver, ExternalProgramSolverFactory, ExternalProgramSolverRecipe,
 };
 pub use self::message::SolverMessage;
+pub use self::server::run_solver_server;
 
+#[cfg(feature = "external-process")]
 mod embedded_script;
+#[cfg(feature = "external-process")]
 mod external_program;
 mod message;
+mod server;
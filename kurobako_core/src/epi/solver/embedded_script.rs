@@ -9,8 +9,9 @@ use crate::trial::{EvaluatedTrial, IdGen, NextTrial};
 use crate::{Error, Result};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write as _;
+use std::path::PathBuf;
 use std::sync::Mutex;
 use structopt::StructOpt;
 use tempfile::{NamedTempFile, TempPath};
@@ -28,6 +29,18 @@ pub struct EmbeddedScriptSolverRecipe {
 
     /// Command line arguments that are passed to the script.
     pub args: Vec<String>,
+
+    /// Python interpreter that executes the script.
+    ///
+    /// If omitted, the interpreter specified by the shebang line of the script is used.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PathBuf>,
+
+    /// Python virtual environment directory activated before the script is spawned.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venv: Option<PathBuf>,
 }
 impl SolverRecipe for EmbeddedScriptSolverRecipe {
     type Factory = EmbeddedScriptSolverFactory;
@@ -60,7 +73,15 @@ impl SolverRecipe for EmbeddedScriptSolverRecipe {
         };
 
         let args = self.args.clone();
-        let eppr = ExternalProgramSolverRecipe { path, args };
+        let eppr = ExternalProgramSolverRecipe {
+            path,
+            args,
+            timeout: None,
+            shutdown_grace_period: None,
+            passthrough_stderr: false,
+            python: self.python.clone(),
+            venv: self.venv.clone(),
+        };
         let inner = track!(eppr.create_factory(registry))?;
         Ok(EmbeddedScriptSolverFactory { inner })
     }
@@ -97,4 +118,8 @@ impl Solver for EmbeddedScriptSolver {
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
         track!(self.inner.tell(trial))
     }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        track!(self.inner.finalize())
+    }
 }
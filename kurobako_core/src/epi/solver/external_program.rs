@@ -1,27 +1,37 @@
-use crate::epi::channel::{MessageReceiver, MessageSender};
+use crate::epi::channel::{MessageChannel, MessageReceiver, MessageSender, StderrForwarder};
 use crate::epi::solver::SolverMessage;
+use crate::epi::{process, DEFAULT_SHUTDOWN_GRACE_PERIOD};
 use crate::problem::ProblemSpec;
 use crate::registry::FactoryRegistry;
 use crate::rng::{ArcRng, Rng as _};
-use crate::solver::{Solver, SolverFactory, SolverRecipe, SolverSpec};
+use crate::solver::{Capability, Solver, SolverFactory, SolverRecipe, SolverSpec};
 use crate::trial::{EvaluatedTrial, IdGen, NextTrial};
 use crate::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
 use std::sync::atomic::{self, AtomicU64};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread_local;
+use std::time::Duration;
 use structopt::StructOpt;
 
+type Channel = MessageChannel<SolverMessage, ChildStdin, ChildStdout>;
+
 thread_local! {
     static FACTORIES: RefCell<HashMap<Vec<u8>, ExternalProgramSolverFactory>> =
         RefCell::new(HashMap::new());
 }
 
+pub(crate) fn clear_thread_local_factories() {
+    // The factories are dropped after the borrow is released.
+    let factories = FACTORIES.with(|f| std::mem::take(&mut *f.borrow_mut()));
+    std::mem::drop(factories);
+}
+
 /// Recipe for the solver that is implemented by an external program.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, StructOpt, Serialize, Deserialize)]
 #[structopt(rename_all = "kebab-case")]
@@ -31,35 +41,102 @@ pub struct ExternalProgramSolverRecipe {
 
     /// The command line arguments that are passed to the program.
     pub args: Vec<String>,
+
+    /// Timeout in seconds for waiting for a message from the program.
+    ///
+    /// If omitted, `kurobako` waits until the program replies or exits.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// Grace period in seconds for waiting for the program to exit after sending `SHUTDOWN_CAST`.
+    ///
+    /// If the program is still running after the period, it is killed.
+    /// If omitted, `DEFAULT_SHUTDOWN_GRACE_PERIOD` is used.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutdown_grace_period: Option<u64>,
+
+    /// If specified, the stderr of the program is inherited as is
+    /// instead of being forwarded line by line with a prefix.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "crate::epi::is_false")]
+    pub passthrough_stderr: bool,
+
+    /// Python interpreter that executes the program as a script (i.e., `PYTHON PATH ARGS...`).
+    ///
+    /// If omitted, the program is executed directly.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PathBuf>,
+
+    /// Python virtual environment directory activated before the program is spawned.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venv: Option<PathBuf>,
 }
 impl ExternalProgramSolverRecipe {
     fn create_new_factory(
         &self,
         _registry: &FactoryRegistry,
     ) -> Result<ExternalProgramSolverFactory> {
-        let mut child = track!(Command::new(&self.path)
+        let mut command = track!(process::command(
+            &self.path,
+            self.python.as_deref(),
+            self.venv.as_deref()
+        ))?;
+        let mut child = track!(command
             .args(&self.args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(if self.passthrough_stderr {
+                Stdio::inherit()
+            } else {
+                Stdio::piped()
+            })
             .spawn()
             .map_err(Error::from))?;
 
         let stdin = track_assert_some!(child.stdin.take(), ErrorKind::IoError);
         let stdout = track_assert_some!(child.stdout.take(), ErrorKind::IoError);
+        let stderr = child.stderr.take().map(|stderr| {
+            let name = self
+                .path
+                .file_name()
+                .unwrap_or_else(|| self.path.as_os_str());
+            let prefix = format!("external[{} pid={}]", name.to_string_lossy(), child.id());
+            StderrForwarder::spawn(stderr, prefix)
+        });
 
         let tx = MessageSender::new(stdin);
-        let mut rx = MessageReceiver::new(stdout);
-        let spec = match track!(rx.recv())? {
-            SolverMessage::SolverSpecCast { spec } => spec,
-            m => track_panic!(ErrorKind::InvalidInput, "Unexpected message: {:?}", m),
+        let mut rx = if let Some(timeout) = self.timeout {
+            MessageReceiver::with_timeout(stdout, Duration::from_secs(timeout))
+        } else {
+            MessageReceiver::new(stdout)
         };
+        let spec = match rx.recv() {
+            Ok(SolverMessage::SolverSpecCast { spec }) => spec,
+            Ok(m) => track_panic!(ErrorKind::InvalidInput, "Unexpected message: {:?}", m),
+            Err(e) => {
+                let description = process::describe_handshake_failure(&mut child, stderr.as_ref());
+                process::terminate_child(&mut child, Duration::from_secs(0));
+                return Err(track!(e, "{:?}: {}", self.path, description));
+            }
+        };
+        if let Some(stderr) = stderr {
+            rx = rx.stderr(stderr);
+        }
+        let channel = MessageChannel::new(tx, rx);
 
         Ok(ExternalProgramSolverFactory(Arc::new(
             ExternalProgramSolverFactoryInner {
                 spec,
                 child,
-                tx: Arc::new(Mutex::new(tx)),
-                rx: Arc::new(Mutex::new(rx)),
+                shutdown_grace_period: Duration::from_secs(
+                    self.shutdown_grace_period
+                        .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD),
+                ),
+                channel: Arc::new(channel),
                 next_solver_id: AtomicU64::new(0),
             },
         )))
@@ -71,6 +148,14 @@ impl ExternalProgramSolverRecipe {
         for arg in &self.args {
             hasher.update(arg.as_bytes());
         }
+        if let Some(python) = &self.python {
+            hasher.update(b"--python");
+            hasher.update(&*python.to_string_lossy());
+        }
+        if let Some(venv) = &self.venv {
+            hasher.update(b"--venv");
+            hasher.update(&*venv.to_string_lossy());
+        }
         hasher.finalize().to_vec()
     }
 }
@@ -108,8 +193,8 @@ impl SolverFactory for ExternalProgramSolverFactory {
 struct ExternalProgramSolverFactoryInner {
     spec: SolverSpec,
     child: Child,
-    tx: Arc<Mutex<MessageSender<SolverMessage, ChildStdin>>>,
-    rx: Arc<Mutex<MessageReceiver<SolverMessage, ChildStdout>>>,
+    shutdown_grace_period: Duration,
+    channel: Arc<Channel>,
     next_solver_id: AtomicU64,
 }
 impl SolverFactory for ExternalProgramSolverFactoryInner {
@@ -126,21 +211,19 @@ impl SolverFactory for ExternalProgramSolverFactoryInner {
             random_seed: rng.gen(),
             problem: problem.clone(),
         };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
+        track!(self.channel.cast(&m))?;
 
         Ok(ExternalProgramSolver {
             solver_id,
-            tx: Arc::clone(&self.tx),
-            rx: Arc::clone(&self.rx),
+            channel: Arc::clone(&self.channel),
+            finalize: self.spec.capabilities.is_capable(Capability::Finalize),
         })
     }
 }
 impl Drop for ExternalProgramSolverFactoryInner {
     fn drop(&mut self) {
-        if self.child.kill().is_ok() {
-            let _ = self.child.wait(); // for preventing the child process becomes a zombie.
-        }
+        let _ = self.channel.cast(&SolverMessage::ShutdownCast);
+        process::terminate_child(&mut self.child, self.shutdown_grace_period);
     }
 }
 
@@ -148,23 +231,21 @@ impl Drop for ExternalProgramSolverFactoryInner {
 #[derive(Debug)]
 pub struct ExternalProgramSolver {
     solver_id: u64,
-    tx: Arc<Mutex<MessageSender<SolverMessage, ChildStdin>>>,
-    rx: Arc<Mutex<MessageReceiver<SolverMessage, ChildStdout>>>,
+    channel: Arc<Channel>,
+    finalize: bool,
 }
 impl Solver for ExternalProgramSolver {
     fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
-        let m = SolverMessage::AskCall {
+        let reply = track!(self.channel.call(|request_id| SolverMessage::AskCall {
             solver_id: self.solver_id,
             next_trial_id: idg.peek_id().get(),
-        };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
-
-        let mut rx = track!(self.rx.lock().map_err(Error::from))?;
-        match track!(rx.recv())? {
+            request_id,
+        }))?;
+        match reply {
             SolverMessage::AskReply {
                 trial,
                 next_trial_id,
+                ..
             } => {
                 track_assert!(
                     idg.peek_id().get() <= next_trial_id,
@@ -176,7 +257,7 @@ impl Solver for ExternalProgramSolver {
 
                 Ok(trial)
             }
-            SolverMessage::ErrorReply { kind, message } => {
+            SolverMessage::ErrorReply { kind, message, .. } => {
                 if let Some(message) = message {
                     track_panic!(kind, "{}", message);
                 } else {
@@ -190,17 +271,41 @@ impl Solver for ExternalProgramSolver {
     }
 
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
-        let m = SolverMessage::TellCall {
-            solver_id: self.solver_id,
+        let solver_id = self.solver_id;
+        let reply = track!(self.channel.call(|request_id| SolverMessage::TellCall {
+            solver_id,
             trial,
-        };
-        let mut tx = track!(self.tx.lock().map_err(Error::from))?;
-        track!(tx.send(&m))?;
+            request_id,
+        }))?;
+        match reply {
+            SolverMessage::TellReply { .. } => Ok(()),
+            SolverMessage::ErrorReply { kind, message, .. } => {
+                if let Some(message) = message {
+                    track_panic!(kind, "{}", message);
+                } else {
+                    track_panic!(kind);
+                }
+            }
+            m => {
+                track_panic!(ErrorKind::Other, "Unexpected message: {:?}", m);
+            }
+        }
+    }
 
-        let mut rx = track!(self.rx.lock().map_err(Error::from))?;
-        match track!(rx.recv())? {
-            SolverMessage::TellReply => Ok(()),
-            SolverMessage::ErrorReply { kind, message } => {
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        if !self.finalize {
+            // The program doesn't know `FINALIZE_CALL`.
+            return Ok(BTreeMap::new());
+        }
+
+        let solver_id = self.solver_id;
+        let reply = track!(self.channel.call(|request_id| SolverMessage::FinalizeCall {
+            solver_id,
+            request_id,
+        }))?;
+        match reply {
+            SolverMessage::FinalizeReply { attrs, .. } => Ok(attrs),
+            SolverMessage::ErrorReply { kind, message, .. } => {
                 if let Some(message) = message {
                     track_panic!(kind, "{}", message);
                 } else {
@@ -217,8 +322,54 @@ impl Drop for ExternalProgramSolver {
     fn drop(&mut self) {
         let solver_id = self.solver_id;
         let m = SolverMessage::DropSolverCast { solver_id };
-        if let Ok(mut tx) = self.tx.lock() {
-            let _ = tx.send(&m);
+        let _ = self.channel.cast(&m);
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::epi::problem::ExternalProgramProblemRecipe;
+    use std::io::Write as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn handshake_failure_is_diagnosed() -> TopLevelResult {
+        // An "interpreter" that cannot run the script.
+        let mut python = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(writeln!(
+            python,
+            "#!/bin/sh\necho \"venv=$VIRTUAL_ENV\" >&2\necho 'SyntaxError: invalid syntax' >&2\nexit 3"
+        )
+        .map_err(Error::from))?;
+        let python = python.into_temp_path();
+        {
+            use std::fs;
+            use std::os::unix::fs::PermissionsExt as _;
+            track!(
+                fs::set_permissions(&python, fs::Permissions::from_mode(0o755))
+                    .map_err(Error::from)
+            )?;
         }
+
+        let recipe = ExternalProgramSolverRecipe {
+            path: PathBuf::from("solver.py"),
+            args: Vec::new(),
+            timeout: Some(10),
+            shutdown_grace_period: None,
+            passthrough_stderr: false,
+            python: Some(python.to_path_buf()),
+            venv: Some(PathBuf::from("/path/to/venv")),
+        };
+        let registry =
+            FactoryRegistry::new::<ExternalProgramProblemRecipe, ExternalProgramSolverRecipe>();
+        let e = track_assert_some!(recipe.create_new_factory(&registry).err(), ErrorKind::Bug);
+        assert_eq!(*e.kind(), ErrorKind::UnexpectedEos);
+
+        let message = e.to_string();
+        assert!(message.contains("exit status: 3"), "{}", message);
+        assert!(message.contains("venv=/path/to/venv"), "{}", message);
+        assert!(message.contains("SyntaxError"), "{}", message);
+        Ok(())
     }
 }
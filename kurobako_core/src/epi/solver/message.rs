@@ -1,11 +1,17 @@
+use crate::epi::channel::Correlated;
 use crate::problem::ProblemSpec;
 use crate::solver::SolverSpec;
 use crate::trial::{EvaluatedTrial, NextTrial};
 use crate::ErrorKind;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Messages that are used to communicate with external solvers.
+///
+/// `request_id` of a reply message is the same as the one of the corresponding call message.
+/// Peers that don't echo the identifiers are also supported (the calls are issued in lock-step).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[allow(missing_docs)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum SolverMessage {
@@ -23,19 +29,56 @@ pub enum SolverMessage {
     AskCall {
         solver_id: u64,
         next_trial_id: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
     AskReply {
         trial: NextTrial,
         next_trial_id: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
     TellCall {
         solver_id: u64,
         trial: EvaluatedTrial,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
-    TellReply,
+    TellReply {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    FinalizeCall {
+        solver_id: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    FinalizeReply {
+        #[serde(default)]
+        attrs: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    ShutdownCast,
     ErrorReply {
         kind: ErrorKind,
         #[serde(default)]
         message: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
     },
 }
+impl Correlated for SolverMessage {
+    fn request_id(&self) -> Option<u64> {
+        match self {
+            Self::AskCall { request_id, .. }
+            | Self::AskReply { request_id, .. }
+            | Self::TellCall { request_id, .. }
+            | Self::TellReply { request_id }
+            | Self::FinalizeCall { request_id, .. }
+            | Self::FinalizeReply { request_id, .. }
+            | Self::ErrorReply { request_id, .. } => *request_id,
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::epi::channel::{Correlated as _, MessageReceiver, MessageSender};
+use crate::epi::solver::SolverMessage;
+use crate::rng::ArcRng;
+use crate::solver::{Solver as _, SolverFactory};
+use crate::trial::{EvaluatedTrial, IdGen, NextTrial};
+use crate::{ErrorKind, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+
+/// Runs the server-side EPI loop of the solver made by the given factory.
+///
+/// This function sends `SOLVER_SPEC_CAST` to the standard output, and then handles
+/// the messages read from the standard input until `SHUTDOWN_CAST` arrives or the input is closed.
+/// So, a program that just calls this function can be used via `ExternalProgramSolverRecipe`.
+///
+/// Errors raised by the solvers are replied as `ERROR_REPLY` messages
+/// instead of terminating the loop.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use kurobako_core::solver::SolverFactory;
+/// # use kurobako_core::epi::solver::run_solver_server;
+/// # fn example<F: SolverFactory>(factory: F) -> kurobako_core::Result<()> {
+/// run_solver_server(factory)
+/// # }
+/// ```
+pub fn run_solver_server<F: SolverFactory>(factory: F) -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    track!(serve(factory, stdin.lock(), stdout.lock()))
+}
+
+fn serve<F, R, W>(factory: F, reader: R, writer: W) -> Result<()>
+where
+    F: SolverFactory,
+    R: Read,
+    W: Write,
+{
+    let mut tx = MessageSender::new(writer);
+    let mut rx = MessageReceiver::<SolverMessage, _>::new(reader);
+    let mut server = SolverServer {
+        factory,
+        solvers: HashMap::new(),
+    };
+
+    let spec = track!(server.factory.specification())?;
+    track!(tx.send(&SolverMessage::SolverSpecCast { spec }))?;
+    loop {
+        let message = match rx.recv() {
+            Ok(m) => m,
+            Err(e) if *e.kind() == ErrorKind::UnexpectedEos => return Ok(()),
+            Err(e) => return Err(track!(e)),
+        };
+        if let SolverMessage::ShutdownCast = message {
+            return Ok(());
+        }
+        if let Some(reply) = server.handle(message) {
+            track!(tx.send(&reply))?;
+        }
+    }
+}
+
+struct SolverServer<F: SolverFactory> {
+    factory: F,
+    solvers: HashMap<u64, Result<F::Solver>>,
+}
+impl<F: SolverFactory> SolverServer<F> {
+    fn handle(&mut self, message: SolverMessage) -> Option<SolverMessage> {
+        match message {
+            SolverMessage::CreateSolverCast {
+                solver_id,
+                random_seed,
+                problem,
+            } => {
+                // Failures are reported when the solver is called.
+                let solver = track!(self
+                    .factory
+                    .create_solver(ArcRng::new(random_seed), &problem));
+                self.solvers.insert(solver_id, solver);
+                None
+            }
+            SolverMessage::DropSolverCast { solver_id } => {
+                self.solvers.remove(&solver_id);
+                None
+            }
+            SolverMessage::AskCall {
+                solver_id,
+                next_trial_id,
+                request_id,
+            } => {
+                let result = self.ask(solver_id, next_trial_id);
+                Some(reply(result, request_id, |(trial, next_trial_id)| {
+                    SolverMessage::AskReply {
+                        trial,
+                        next_trial_id,
+                        request_id,
+                    }
+                }))
+            }
+            SolverMessage::TellCall {
+                solver_id,
+                trial,
+                request_id,
+            } => {
+                let result = self.tell(solver_id, trial);
+                Some(reply(result, request_id, |()| SolverMessage::TellReply {
+                    request_id,
+                }))
+            }
+            SolverMessage::FinalizeCall {
+                solver_id,
+                request_id,
+            } => {
+                let result = self.finalize(solver_id);
+                Some(reply(result, request_id, |attrs| {
+                    SolverMessage::FinalizeReply { attrs, request_id }
+                }))
+            }
+            m => Some(SolverMessage::ErrorReply {
+                kind: ErrorKind::InvalidInput,
+                message: Some(format!("Unexpected message: {:?}", m)),
+                request_id: m.request_id(),
+            }),
+        }
+    }
+
+    fn solver(&mut self, solver_id: u64) -> Result<&mut F::Solver> {
+        let solver = track_assert_some!(
+            self.solvers.get_mut(&solver_id),
+            ErrorKind::InvalidInput,
+            "Unknown solver: {}",
+            solver_id
+        );
+        match solver {
+            Ok(solver) => Ok(solver),
+            Err(e) => Err(track!(e.clone())),
+        }
+    }
+
+    fn ask(&mut self, solver_id: u64, next_trial_id: u64) -> Result<(NextTrial, u64)> {
+        let solver = track!(self.solver(solver_id))?;
+        let mut idg = IdGen::from_next_id(next_trial_id);
+        let trial = track!(solver.ask(&mut idg))?;
+        Ok((trial, idg.peek_id().get()))
+    }
+
+    fn tell(&mut self, solver_id: u64, trial: EvaluatedTrial) -> Result<()> {
+        let solver = track!(self.solver(solver_id))?;
+        track!(solver.tell(trial))
+    }
+
+    fn finalize(&mut self, solver_id: u64) -> Result<BTreeMap<String, String>> {
+        let solver = track!(self.solver(solver_id))?;
+        track!(solver.finalize())
+    }
+}
+
+fn reply<T, F>(result: Result<T>, request_id: Option<u64>, f: F) -> SolverMessage
+where
+    F: FnOnce(T) -> SolverMessage,
+{
+    match result {
+        Ok(x) => f(x),
+        Err(e) => SolverMessage::ErrorReply {
+            kind: *e.kind(),
+            message: Some(e.to_string()),
+            request_id,
+        },
+    }
+}
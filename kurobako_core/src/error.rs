@@ -25,6 +25,11 @@ impl From<std::num::ParseIntError> for Error {
         ErrorKind::InvalidInput.cause(f).into()
     }
 }
+impl From<std::num::ParseFloatError> for Error {
+    fn from(f: std::num::ParseFloatError) -> Self {
+        ErrorKind::InvalidInput.cause(f).into()
+    }
+}
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(f: std::sync::PoisonError<T>) -> Self {
         ErrorKind::Other.cause(f.to_string()).into()
@@ -39,6 +44,12 @@ impl From<serde_json::error::Error> for Error {
         }
     }
 }
+#[cfg(feature = "yaml")]
+impl From<serde_yaml::Error> for Error {
+    fn from(f: serde_yaml::Error) -> Self {
+        ErrorKind::InvalidInput.cause(f).into()
+    }
+}
 impl From<rlua::Error> for Error {
     fn from(f: rlua::Error) -> Self {
         ErrorKind::Other.cause(f).into()
@@ -47,6 +58,7 @@ impl From<rlua::Error> for Error {
 
 /// Possible error kinds.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ErrorKind {
     /// Invalid input was given.
@@ -58,12 +70,21 @@ pub enum ErrorKind {
     /// Unexpected end-of-stream.
     UnexpectedEos,
 
+    /// An operation timed out.
+    Timeout,
+
     /// Incapable feature was required.
     Incapable,
 
     /// Unevaluable parameter set was passed.
     UnevaluableParams,
 
+    /// A required dataset has not been downloaded.
+    DatasetNotFound,
+
+    /// A dataset is broken or has been converted by an incompatible version.
+    CorruptDataset,
+
     /// Implementation bug.
     Bug,
 
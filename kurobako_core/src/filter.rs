@@ -0,0 +1,243 @@
+//! Filter.
+//!
+//! A filter sits between a solver and a problem.
+//! It can rewrite the problem specification seen by the solver,
+//! the trials asked by the solver, and the evaluation results told to the solver.
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::solver::Solver;
+use crate::trial::{EvaluatedTrial, IdGen, NextTrial};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use structopt::StructOpt;
+
+pub use self::clip_values::{ClipValuesFilter, ClipValuesFilterRecipe};
+pub use self::discrete_to_continuous::{
+    DiscreteToContinuousFilter, DiscreteToContinuousFilterRecipe,
+};
+pub use self::gaussian_noise::{GaussianNoiseFilter, GaussianNoiseFilterRecipe};
+pub use self::log_value::{LogValueFilter, LogValueFilterRecipe};
+pub use self::negate_values::{NegateValuesFilter, NegateValuesFilterRecipe};
+pub use self::normalize_params::{NormalizeParamsFilter, NormalizeParamsFilterRecipe};
+pub use self::one_hot::{OneHotFilter, OneHotFilterRecipe};
+pub use self::param_noise::{ParamNoiseFilter, ParamNoiseFilterRecipe};
+pub use self::rescale_steps::{RescaleStepsFilter, RescaleStepsFilterRecipe};
+
+mod clip_values;
+mod discrete_to_continuous;
+mod gaussian_noise;
+mod log_value;
+mod negate_values;
+mod normalize_params;
+mod one_hot;
+mod param_noise;
+mod rescale_steps;
+
+/// Recipe of a filter.
+pub trait FilterRecipe: Clone + Send + StructOpt + Serialize + for<'a> Deserialize<'a> {
+    /// The type of the filter instantiated by this recipe.
+    type Filter: Filter;
+
+    /// Creates a filter instance.
+    fn create_filter(&self, rng: ArcRng) -> Result<Self::Filter>;
+}
+
+/// Filter.
+pub trait Filter: Send {
+    /// Rewrites the problem specification that will be passed to the solver.
+    ///
+    /// This method is invoked once before the solver is created.
+    fn filter_problem_spec(&mut self, _spec: &mut ProblemSpec) -> Result<()> {
+        Ok(())
+    }
+
+    /// Converts a trial asked by the solver into the one evaluated by the problem.
+    fn filter_ask(&mut self, _trial: &mut NextTrial) -> Result<()> {
+        Ok(())
+    }
+
+    /// Converts an evaluated trial into the one told to the solver.
+    fn filter_tell(&mut self, _trial: &mut EvaluatedTrial) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Boxed filter.
+pub struct BoxFilter(Box<dyn Filter>);
+impl BoxFilter {
+    /// Makes a new `BoxFilter` instance.
+    pub fn new<T>(filter: T) -> Self
+    where
+        T: 'static + Filter,
+    {
+        Self(Box::new(filter))
+    }
+}
+impl Filter for BoxFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        track!(self.0.filter_problem_spec(spec))
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        track!(self.0.filter_ask(trial))
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        track!(self.0.filter_tell(trial))
+    }
+}
+impl fmt::Debug for BoxFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BoxFilter {{ .. }}")
+    }
+}
+
+/// Applies a chain of filters to a problem specification.
+///
+/// The first filter is the closest to the problem.
+pub fn filter_problem_spec(filters: &mut [BoxFilter], spec: &mut ProblemSpec) -> Result<()> {
+    for filter in filters {
+        track!(filter.filter_problem_spec(spec))?;
+    }
+    Ok(())
+}
+
+/// Solver wrapped by a chain of filters.
+///
+/// The first filter is the closest to the problem.
+#[derive(Debug)]
+pub struct FilteredSolver<S> {
+    inner: S,
+    filters: Vec<BoxFilter>,
+}
+impl<S: Solver> FilteredSolver<S> {
+    /// Makes a new `FilteredSolver` instance.
+    ///
+    /// Note that `inner` should be created with the specification rewritten by `filter_problem_spec`.
+    pub fn new(inner: S, filters: Vec<BoxFilter>) -> Self {
+        Self { inner, filters }
+    }
+}
+impl<S: Solver> Solver for FilteredSolver<S> {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        let mut trial = track!(self.inner.ask(idg))?;
+        for filter in self.filters.iter_mut().rev() {
+            track!(filter.filter_ask(&mut trial))?;
+        }
+        Ok(trial)
+    }
+
+    fn tell(&mut self, mut trial: EvaluatedTrial) -> Result<()> {
+        for filter in &mut self.filters {
+            track!(filter.filter_tell(&mut trial))?;
+        }
+        track!(self.inner.tell(trial))
+    }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        track!(self.inner.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::solver::BoxSolver;
+    use crate::trial::{TrialId, Values};
+    use crate::ErrorKind;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use trackable::result::TopLevelResult;
+
+    #[derive(Debug, Default)]
+    struct RecordingSolver {
+        told: Arc<Mutex<Vec<Values>>>,
+    }
+    impl Solver for RecordingSolver {
+        fn ask(&mut self, _idg: &mut IdGen) -> Result<NextTrial> {
+            track_panic!(ErrorKind::Other, "Unused");
+        }
+
+        fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+            self.told
+                .lock()
+                .unwrap_or_else(|e| panic!("{}", e))
+                .push(trial.values);
+            Ok(())
+        }
+
+        fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+            let told = self.told.lock().unwrap_or_else(|e| panic!("{}", e)).len();
+            Ok(vec![("told".to_owned(), told.to_string())]
+                .into_iter()
+                .collect())
+        }
+    }
+
+    fn told_value(recipes: &[&dyn Fn() -> Result<BoxFilter>]) -> Result<f64> {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y").continuous(0.0, 100.0))
+            .finish())?;
+        let mut filters = recipes.iter().map(|f| f()).collect::<Result<Vec<_>>>()?;
+        track!(filter_problem_spec(&mut filters, &mut spec))?;
+
+        let solver = RecordingSolver::default();
+        let told = Arc::clone(&solver.told);
+        let mut solver = FilteredSolver::new(solver, filters);
+        for &v in &[10.0, 20.0] {
+            track!(solver.tell(EvaluatedTrial {
+                id: TrialId::new(0),
+                values: Values::new(vec![v]),
+                current_step: 1,
+                status: Default::default(),
+                attrs: BTreeMap::new(),
+            }))?;
+        }
+        let told = told.lock().unwrap_or_else(|e| panic!("{}", e));
+        Ok(told[1][0])
+    }
+
+    #[test]
+    fn filters_compose_in_declaration_order() -> TopLevelResult {
+        let negate = || {
+            let recipe = NegateValuesFilterRecipe {
+                objectives: vec![0],
+            };
+            track!(recipe.create_filter(ArcRng::new(0)).map(BoxFilter::new))
+        };
+        let log = || {
+            let recipe = LogValueFilterRecipe {
+                epsilon: 1.0,
+                objectives: None,
+            };
+            track!(recipe.create_filter(ArcRng::new(0)).map(BoxFilter::new))
+        };
+
+        // ln(-20 - (-20) + 1)
+        assert_eq!(track!(told_value(&[&negate, &log]))?, 0.0);
+
+        // -ln(20 - 10 + 1)
+        assert_eq!(track!(told_value(&[&log, &negate]))?, -11.0f64.ln());
+        Ok(())
+    }
+
+    #[test]
+    fn finalize_is_forwarded_to_inner_solver() -> TopLevelResult {
+        let mut solver = BoxSolver::new(FilteredSolver::new(RecordingSolver::default(), vec![]));
+        track!(solver.tell(EvaluatedTrial {
+            id: TrialId::new(0),
+            values: Values::new(vec![1.0]),
+            current_step: 1,
+            status: Default::default(),
+            attrs: BTreeMap::new(),
+        }))?;
+        let attrs = track!(solver.finalize())?;
+        assert_eq!(attrs.get("told").map(|s| s.as_str()), Some("1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use crate::filter::{Filter, FilterRecipe};
+use crate::num::OrderedFloat;
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{EvaluatedTrial, Values};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `ClipValuesFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ClipValuesFilterRecipe {
+    /// Absolute upper bound of the objective values told to solvers.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    /// Quantile (in `(0, 1]`) of the values observed so far that is used as the upper bound.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantile: Option<f64>,
+}
+impl FilterRecipe for ClipValuesFilterRecipe {
+    type Filter = ClipValuesFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        track_assert!(
+            self.max.is_some() || self.quantile.is_some(),
+            ErrorKind::InvalidInput,
+            "Either `max` or `quantile` must be specified"
+        );
+        if let Some(max) = self.max {
+            track_assert!(!max.is_nan(), ErrorKind::InvalidInput; max);
+        }
+        if let Some(q) = self.quantile {
+            track_assert!(0.0 < q && q <= 1.0, ErrorKind::InvalidInput; q);
+        }
+        Ok(ClipValuesFilter {
+            max: self.max,
+            quantile: self.quantile,
+            observed: Vec::new(),
+        })
+    }
+}
+
+/// Filter that clips the objective values told to solvers.
+///
+/// The upper bound is the smaller of the absolute cap (`max`) and
+/// the running quantile of the values observed so far (`quantile`).
+#[derive(Debug)]
+pub struct ClipValuesFilter {
+    max: Option<f64>,
+    quantile: Option<f64>,
+    observed: Vec<RunningQuantile>,
+}
+impl Filter for ClipValuesFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        self.observed = vec![RunningQuantile::new(); spec.values_domain.len()];
+        Ok(())
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        if trial.status.is_failed() {
+            return Ok(());
+        }
+        track_assert_eq!(
+            trial.values.len(),
+            self.observed.len(),
+            ErrorKind::InvalidInput
+        );
+
+        let mut values = Vec::with_capacity(trial.values.len());
+        for (&v, observed) in trial.values.iter().zip(self.observed.iter_mut()) {
+            let mut cap = self.max.unwrap_or(f64::INFINITY);
+            if let Some(q) = self.quantile {
+                observed.insert(v);
+                if let Some(x) = observed.quantile(q) {
+                    cap = cap.min(x);
+                }
+            }
+            values.push(if v > cap { cap } else { v });
+        }
+        trial.values = Values::new(values);
+        Ok(())
+    }
+}
+
+/// Exact running quantile estimator backed by a sorted reservoir of all observed values.
+#[derive(Debug, Clone, Default)]
+struct RunningQuantile {
+    sorted: Vec<OrderedFloat<f64>>,
+}
+impl RunningQuantile {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, v: f64) {
+        if v.is_nan() {
+            return;
+        }
+        let v = OrderedFloat(v);
+        let i = match self.sorted.binary_search(&v) {
+            Ok(i) | Err(i) => i,
+        };
+        self.sorted.insert(i, v);
+    }
+
+    /// Returns the `q`-quantile of the observed values (linearly interpolated).
+    fn quantile(&self, q: f64) -> Option<f64> {
+        if self.sorted.is_empty() {
+            return None;
+        }
+        let pos = q * (self.sorted.len() - 1) as f64;
+        let lower = pos.floor() as usize;
+        let upper = pos.ceil() as usize;
+        let (a, b) = (self.sorted[lower].0, self.sorted[upper].0);
+        if lower == upper {
+            Some(a)
+        } else {
+            Some(a + (b - a) * (pos - lower as f64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::trial::TrialId;
+    use std::collections::BTreeMap;
+    use trackable::result::TopLevelResult;
+
+    fn trial(value: f64) -> EvaluatedTrial {
+        EvaluatedTrial {
+            id: TrialId::new(0),
+            values: Values::new(vec![value]),
+            current_step: 1,
+            status: Default::default(),
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn running_quantile_works() {
+        let mut q = RunningQuantile::new();
+        assert_eq!(q.quantile(0.5), None);
+
+        q.insert(3.0);
+        assert_eq!(q.quantile(0.5), Some(3.0));
+
+        for &v in &[5.0, 1.0, f64::NAN, 4.0, 2.0] {
+            q.insert(v);
+        }
+        assert_eq!(q.quantile(0.0), Some(1.0));
+        assert_eq!(q.quantile(0.5), Some(3.0));
+        assert_eq!(q.quantile(0.9), Some(4.6));
+        assert_eq!(q.quantile(1.0), Some(5.0));
+    }
+
+    #[test]
+    fn clip_values_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .finish())?;
+
+        let recipe = ClipValuesFilterRecipe {
+            max: Some(10.0),
+            quantile: None,
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        for &(v, expected) in &[(1.0, 1.0), (1e30, 10.0), (-5.0, -5.0)] {
+            let mut t = trial(v);
+            track!(filter.filter_tell(&mut t))?;
+            assert_eq!(t.values[0], expected);
+        }
+
+        let recipe = ClipValuesFilterRecipe {
+            max: Some(100.0),
+            quantile: Some(0.5),
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        for &(v, expected) in &[(1.0, 1.0), (3.0, 2.0), (1e30, 3.0), (1e30, 100.0)] {
+            let mut t = trial(v);
+            track!(filter.filter_tell(&mut t))?;
+            assert_eq!(t.values[0], expected);
+        }
+
+        let recipe = ClipValuesFilterRecipe {
+            max: None,
+            quantile: None,
+        };
+        assert!(recipe.create_filter(ArcRng::new(0)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::domain::{Domain, Range, VariableBuilder};
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{NextTrial, Params};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `DiscreteToContinuousFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DiscreteToContinuousFilterRecipe {}
+impl FilterRecipe for DiscreteToContinuousFilterRecipe {
+    type Filter = DiscreteToContinuousFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        Ok(DiscreteToContinuousFilter {
+            discretes: Vec::new(),
+        })
+    }
+}
+
+/// Filter that relaxes discrete variables into continuous ones.
+///
+/// A discrete variable `[low..high)` with step `s` is shown to solvers as
+/// a continuous variable `[low..last+s)` where `last` is the largest valid value.
+/// The values proposed by solvers are rounded down to the nearest valid values.
+#[derive(Debug)]
+pub struct DiscreteToContinuousFilter {
+    // The `(low, step, last)` of each discrete variable.
+    discretes: Vec<Option<(i64, i64, i64)>>,
+}
+impl DiscreteToContinuousFilter {
+    fn to_discrete(&self, params: &Params) -> Result<Params> {
+        track_assert_eq!(params.len(), self.discretes.len(), ErrorKind::InvalidInput);
+
+        let params = params
+            .iter()
+            .zip(self.discretes.iter())
+            .map(|(&v, d)| match d {
+                Some((low, step, last)) if !v.is_nan() => {
+                    let i = ((v - *low as f64) / *step as f64).floor() as i64;
+                    (low + i * step).max(*low).min(*last) as f64
+                }
+                _ => v,
+            })
+            .collect();
+        Ok(Params::new(params))
+    }
+}
+impl Filter for DiscreteToContinuousFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        let mut vars = Vec::new();
+        self.discretes.clear();
+        for var in spec.params_domain.variables() {
+            let builder = VariableBuilder::from(var.clone());
+            if let Range::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } = *var.range()
+            {
+                let max = if inclusive_high { high } else { high - 1 };
+                let last = low + (max - low) / step * step;
+                vars.push(builder.range(Range::Continuous {
+                    low: low as f64,
+                    high: (last + step) as f64,
+                    step: None,
+                    inclusive_high: false,
+                }));
+                self.discretes.push(Some((low, step, last)));
+            } else {
+                vars.push(builder);
+                self.discretes.push(None);
+            }
+        }
+        spec.params_domain = track!(Domain::new(vars))?;
+        Ok(())
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        trial.params = track!(self.to_discrete(&trial.params))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn discrete_to_continuous_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("a").discrete(0, 5).step(2.0))
+            .param(var("b").continuous(0.0, 1.0))
+            .param(var("c").discrete(1, 10).log_uniform())
+            .value(var("y"))
+            .finish())?;
+        let original = spec.clone();
+
+        let mut filter = track!(DiscreteToContinuousFilterRecipe {}.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        assert!(spec
+            .params_domain
+            .variables()
+            .iter()
+            .all(|v| matches!(v.range(), Range::Continuous { .. })));
+        assert_eq!(spec.params_domain.variables()[0].range().high(), 6.0);
+
+        // Valid discrete parameters are unchanged by the round-trip.
+        for &(a, c) in &[(0.0, 1.0), (2.0, 5.0), (4.0, 9.0)] {
+            let params = Params::new(vec![a, 0.5, c]);
+            let filtered = track!(filter.to_discrete(&params))?;
+            assert_eq!(filtered, params);
+        }
+
+        // Relaxed values are rounded down to valid ones.
+        let params = track!(filter.to_discrete(&Params::new(vec![5.9, 0.5, 9.9])))?;
+        assert_eq!(params, Params::new(vec![4.0, 0.5, 9.0]));
+        track!(original.validate_params(&params))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::{standard_normal, ArcRng};
+use crate::trial::{EvaluatedTrial, Values};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `GaussianNoiseFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct GaussianNoiseFilterRecipe {
+    /// Noise level.
+    ///
+    /// The standard deviation of the noise added to an objective value is
+    /// `level * (the width of the observed range of the objective)`.
+    #[structopt(long, default_value = "0.1")]
+    pub level: f64,
+}
+impl FilterRecipe for GaussianNoiseFilterRecipe {
+    type Filter = GaussianNoiseFilter;
+
+    fn create_filter(&self, rng: ArcRng) -> Result<Self::Filter> {
+        track_assert!(
+            self.level.is_finite() && self.level >= 0.0,
+            ErrorKind::InvalidInput; self.level
+        );
+        Ok(GaussianNoiseFilter {
+            level: self.level,
+            rng,
+            observed: Vec::new(),
+        })
+    }
+}
+
+/// Filter that adds Gaussian noise to the objective values told to solvers.
+#[derive(Debug)]
+pub struct GaussianNoiseFilter {
+    level: f64,
+    rng: ArcRng,
+
+    // The observed `(min, max)` of each objective.
+    observed: Vec<(f64, f64)>,
+}
+impl Filter for GaussianNoiseFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        self.observed = vec![(f64::INFINITY, f64::NEG_INFINITY); spec.values_domain.len()];
+        Ok(())
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        if trial.status.is_failed() {
+            return Ok(());
+        }
+        track_assert_eq!(
+            trial.values.len(),
+            self.observed.len(),
+            ErrorKind::InvalidInput
+        );
+
+        let mut values = Vec::with_capacity(trial.values.len());
+        for (&v, (min, max)) in trial.values.iter().zip(self.observed.iter_mut()) {
+            *min = min.min(v);
+            *max = max.max(v);
+
+            let sd = self.level * (*max - *min);
+            values.push(v + sd * standard_normal(&mut self.rng));
+        }
+        trial.values = Values::new(values);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::trial::TrialId;
+    use std::collections::BTreeMap;
+    use trackable::result::TopLevelResult;
+
+    fn trial(value: f64) -> EvaluatedTrial {
+        EvaluatedTrial {
+            id: TrialId::new(0),
+            values: Values::new(vec![value]),
+            current_step: 1,
+            status: Default::default(),
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn gaussian_noise_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .finish())?;
+
+        // A zero noise level doesn't change the values.
+        let recipe = GaussianNoiseFilterRecipe { level: 0.0 };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        for &v in &[1.0, 3.0, 2.0] {
+            let mut t = trial(v);
+            track!(filter.filter_tell(&mut t))?;
+            assert_eq!(t.values[0], v);
+        }
+
+        // The noise is proportional to the observed range.
+        let recipe = GaussianNoiseFilterRecipe { level: 0.1 };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        let mut t = trial(1.0);
+        track!(filter.filter_tell(&mut t))?;
+        assert_eq!(t.values[0], 1.0);
+
+        let mut t = trial(100.0);
+        track!(filter.filter_tell(&mut t))?;
+        assert_ne!(t.values[0], 100.0);
+        assert!((t.values[0] - 100.0).abs() < 99.0 * 0.1 * 6.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use crate::domain::{Domain, VariableBuilder};
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{EvaluatedTrial, Values};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `LogValueFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct LogValueFilterRecipe {
+    /// Offset added to the shifted values before taking their logarithms.
+    #[structopt(long, default_value = "1e-6")]
+    pub epsilon: f64,
+
+    /// Indices of the objectives to be transformed.
+    ///
+    /// If omitted, all objectives are transformed.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objectives: Option<Vec<usize>>,
+}
+impl FilterRecipe for LogValueFilterRecipe {
+    type Filter = LogValueFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        track_assert!(
+            self.epsilon.is_finite() && self.epsilon > 0.0,
+            ErrorKind::InvalidInput; self.epsilon
+        );
+        Ok(LogValueFilter {
+            epsilon: self.epsilon,
+            objectives: self.objectives.clone(),
+            observed_mins: Vec::new(),
+        })
+    }
+}
+
+/// Filter that tells solvers the logarithms of objective values.
+///
+/// Each selected objective value `v` is replaced with `ln(v - min + epsilon)`,
+/// where `min` is the minimum value observed so far.
+#[derive(Debug)]
+pub struct LogValueFilter {
+    epsilon: f64,
+    objectives: Option<Vec<usize>>,
+
+    // The observed minimum of each objective (`None` means that the objective is not transformed).
+    observed_mins: Vec<Option<f64>>,
+}
+impl Filter for LogValueFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        let n = spec.values_domain.len();
+        self.observed_mins = match &self.objectives {
+            None => vec![Some(f64::INFINITY); n],
+            Some(objectives) => {
+                let mut mins = vec![None; n];
+                for &i in objectives {
+                    track_assert!(i < n, ErrorKind::InvalidInput; i, n);
+                    mins[i] = Some(f64::INFINITY);
+                }
+                mins
+            }
+        };
+
+        let vars = spec
+            .values_domain
+            .variables()
+            .iter()
+            .zip(self.observed_mins.iter())
+            .map(|(var, min)| {
+                let builder = VariableBuilder::from(var.clone());
+                if min.is_some() {
+                    builder
+                        .uniform()
+                        .continuous(f64::NEG_INFINITY, f64::INFINITY)
+                } else {
+                    builder
+                }
+            })
+            .collect();
+        spec.values_domain = track!(Domain::new(vars))?;
+        Ok(())
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        if trial.status.is_failed() {
+            return Ok(());
+        }
+        track_assert_eq!(
+            trial.values.len(),
+            self.observed_mins.len(),
+            ErrorKind::InvalidInput
+        );
+
+        let mut values = Vec::with_capacity(trial.values.len());
+        for (&v, min) in trial.values.iter().zip(self.observed_mins.iter_mut()) {
+            if let Some(min) = min {
+                *min = min.min(v);
+                values.push((v - *min + self.epsilon).ln());
+            } else {
+                values.push(v);
+            }
+        }
+        trial.values = Values::new(values);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::trial::TrialId;
+    use std::collections::BTreeMap;
+    use trackable::result::TopLevelResult;
+
+    fn trial(values: Vec<f64>) -> EvaluatedTrial {
+        EvaluatedTrial {
+            id: TrialId::new(0),
+            values: Values::new(values),
+            current_step: 1,
+            status: Default::default(),
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn log_value_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y").continuous(0.0, 100.0))
+            .value(var("z").continuous(0.0, 100.0))
+            .finish())?;
+
+        let recipe = LogValueFilterRecipe {
+            epsilon: 1.0,
+            objectives: Some(vec![0]),
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        let vars = spec.values_domain.variables();
+        assert_eq!(vars[0].range().low(), f64::NEG_INFINITY);
+        assert_eq!(vars[0].range().high(), f64::INFINITY);
+        assert_eq!(vars[1].range().high(), 100.0);
+
+        let mut t = trial(vec![10.0, 10.0]);
+        track!(filter.filter_tell(&mut t))?;
+        assert_eq!(&t.values[..], &[0.0, 10.0]);
+
+        let mut t = trial(vec![20.0, 20.0]);
+        track!(filter.filter_tell(&mut t))?;
+        assert_eq!(&t.values[..], &[11.0f64.ln(), 20.0]);
+
+        let recipe = LogValueFilterRecipe {
+            epsilon: 1.0,
+            objectives: Some(vec![2]),
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        assert!(filter.filter_problem_spec(&mut spec).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use crate::domain::{Domain, Range, VariableBuilder};
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{EvaluatedTrial, Values};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `NegateValuesFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct NegateValuesFilterRecipe {
+    /// Indices of the objectives to be negated.
+    #[structopt(long)]
+    pub objectives: Vec<usize>,
+}
+impl FilterRecipe for NegateValuesFilterRecipe {
+    type Filter = NegateValuesFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        Ok(NegateValuesFilter {
+            objectives: self.objectives.clone(),
+            negate: Vec::new(),
+        })
+    }
+}
+
+/// Filter that negates the selected objective values told to solvers.
+///
+/// This makes it possible to benchmark problems that should be maximized
+/// (`kurobako` solvers always minimize objectives).
+#[derive(Debug)]
+pub struct NegateValuesFilter {
+    objectives: Vec<usize>,
+    negate: Vec<bool>,
+}
+impl Filter for NegateValuesFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        let n = spec.values_domain.len();
+        self.negate = vec![false; n];
+        for &i in &self.objectives {
+            track_assert!(i < n, ErrorKind::InvalidInput; i, n);
+            self.negate[i] = true;
+        }
+
+        let vars = spec
+            .values_domain
+            .variables()
+            .iter()
+            .zip(self.negate.iter())
+            .map(|(var, &negate)| {
+                let builder = VariableBuilder::from(var.clone());
+                if !negate {
+                    return Ok(builder);
+                }
+
+                let (low, high) = (var.range().low(), var.range().high());
+                let inclusive_high = match *var.range() {
+                    Range::Continuous { .. } | Range::Discrete { .. } => low.is_finite(),
+                    _ => track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Non-numerical objective: {:?}",
+                        var
+                    ),
+                };
+                let builder = builder.uniform();
+                if inclusive_high {
+                    Ok(builder.continuous_inclusive(-high, -low))
+                } else {
+                    Ok(builder.continuous(-high, -low))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        spec.values_domain = track!(Domain::new(vars))?;
+        Ok(())
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        if trial.status.is_failed() {
+            return Ok(());
+        }
+        track_assert_eq!(
+            trial.values.len(),
+            self.negate.len(),
+            ErrorKind::InvalidInput
+        );
+
+        let values = trial
+            .values
+            .iter()
+            .zip(self.negate.iter())
+            .map(|(&v, &negate)| if negate { -v } else { v })
+            .collect();
+        trial.values = Values::new(values);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::trial::TrialId;
+    use std::collections::BTreeMap;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn negate_values_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("accuracy").continuous_inclusive(0.0, 1.0))
+            .value(var("loss"))
+            .finish())?;
+
+        let recipe = NegateValuesFilterRecipe {
+            objectives: vec![0],
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        let vars = spec.values_domain.variables();
+        assert_eq!(vars[0].range().low(), -1.0);
+        assert_eq!(vars[0].range().high(), 0.0);
+        assert_eq!(vars[1].range().low(), f64::NEG_INFINITY);
+
+        let mut t = EvaluatedTrial {
+            id: TrialId::new(0),
+            values: Values::new(vec![0.8, 2.0]),
+            current_step: 1,
+            status: Default::default(),
+            attrs: BTreeMap::new(),
+        };
+        track!(filter.filter_tell(&mut t))?;
+        assert_eq!(&t.values[..], &[-0.8, 2.0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+use crate::domain::{Distribution, Domain, Range, Variable, VariableBuilder};
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{NextTrial, Params};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `NormalizeParamsFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct NormalizeParamsFilterRecipe {}
+impl FilterRecipe for NormalizeParamsFilterRecipe {
+    type Filter = NormalizeParamsFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        Ok(NormalizeParamsFilter { scales: Vec::new() })
+    }
+}
+
+/// Filter that shows solvers the numerical variables normalized into `[0, 1)`.
+///
+/// Log-uniform variables are normalized in the log space.
+/// Categorical and ordinal variables, unbounded variables,
+/// and variables that have evaluation constraints or are referred from conditions are left as is,
+/// because normalizing them would change their meaning.
+#[derive(Debug)]
+pub struct NormalizeParamsFilter {
+    scales: Vec<Scale>,
+}
+impl NormalizeParamsFilter {
+    /// Converts parameters in the original scales into the normalized ones.
+    pub fn normalize(&self, params: &Params) -> Result<Params> {
+        track_assert_eq!(params.len(), self.scales.len(), ErrorKind::InvalidInput);
+        Ok(Params::new(
+            self.scales
+                .iter()
+                .zip(params.iter())
+                .map(|(s, &v)| if v.is_nan() { v } else { s.normalize(v) })
+                .collect(),
+        ))
+    }
+
+    /// Converts normalized parameters into the ones in the original scales.
+    pub fn denormalize(&self, params: &Params) -> Result<Params> {
+        track_assert_eq!(params.len(), self.scales.len(), ErrorKind::InvalidInput);
+        Ok(Params::new(
+            self.scales
+                .iter()
+                .zip(params.iter())
+                .map(|(s, &v)| if v.is_nan() { v } else { s.denormalize(v) })
+                .collect(),
+        ))
+    }
+}
+impl NormalizeParamsFilter {
+    /// Returns `true` if the `i`-th variable is normalized (i.e., not left as is).
+    pub(crate) fn normalizes(&self, i: usize) -> bool {
+        self.scales
+            .get(i)
+            .is_some_and(|s| !matches!(s, Scale::Keep))
+    }
+}
+impl Filter for NormalizeParamsFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        let variables = spec.params_domain.variables();
+        self.scales = variables
+            .iter()
+            .map(|var| {
+                let is_condition_target = variables
+                    .iter()
+                    .filter_map(|v| v.condition())
+                    .any(|c| c.target() == var.name());
+                if is_condition_target || var.constraint().is_some() {
+                    Scale::Keep
+                } else {
+                    Scale::new(var)
+                }
+            })
+            .collect();
+
+        let vars = variables
+            .iter()
+            .zip(self.scales.iter())
+            .map(|(var, scale)| {
+                let builder = VariableBuilder::from(var.clone());
+                match scale {
+                    Scale::Keep => builder,
+                    Scale::Linear {
+                        inclusive_high: true,
+                        ..
+                    }
+                    | Scale::Log {
+                        inclusive_high: true,
+                        ..
+                    } => builder.uniform().continuous_inclusive(0.0, 1.0),
+                    _ => builder.uniform().continuous(0.0, 1.0),
+                }
+            })
+            .collect();
+        spec.params_domain = track!(Domain::new(vars))?;
+        Ok(())
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        trial.params = track!(self.denormalize(&trial.params))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scale {
+    Keep,
+    Linear {
+        low: f64,
+        high: f64,
+        inclusive_high: bool,
+    },
+    Log {
+        low: f64,
+        high: f64,
+        inclusive_high: bool,
+    },
+    Grid {
+        low: f64,
+        step: f64,
+        count: u64,
+    },
+    LogGrid {
+        low: f64,
+        high: f64,
+    },
+}
+impl Scale {
+    fn new(var: &Variable) -> Self {
+        let log = var.distribution() == Distribution::LogUniform;
+        match *var.range() {
+            Range::Continuous { low, high, .. } if !(low.is_finite() && high.is_finite()) => {
+                Scale::Keep
+            }
+            Range::Continuous {
+                low,
+                high,
+                step: Some(step),
+                inclusive_high,
+            } => {
+                let n = ((high - low) / step + 1e-9).floor() as u64;
+                let count = if inclusive_high || low + n as f64 * step < high - step * 1e-9 {
+                    n + 1
+                } else {
+                    n
+                };
+                Scale::Grid { low, step, count }
+            }
+            Range::Continuous {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } if log => Scale::Log {
+                low,
+                high,
+                inclusive_high,
+            },
+            Range::Continuous {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } => Scale::Linear {
+                low,
+                high,
+                inclusive_high,
+            },
+            Range::Discrete {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } if log => Scale::LogGrid {
+                low: low as f64,
+                high: if inclusive_high { high + 1 } else { high } as f64,
+            },
+            Range::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } => {
+                let high = if inclusive_high { high + 1 } else { high };
+                Scale::Grid {
+                    low: low as f64,
+                    step: step as f64,
+                    count: ((high - low + step - 1) / step) as u64,
+                }
+            }
+            Range::Categorical { .. } | Range::Ordinal { .. } => Scale::Keep,
+        }
+    }
+
+    fn normalize(self, v: f64) -> f64 {
+        match self {
+            Scale::Keep => v,
+            Scale::Linear { low, high, .. } => (v - low) / (high - low),
+            Scale::Log { low, high, .. } => (v.ln() - low.ln()) / (high.ln() - low.ln()),
+            Scale::Grid { low, step, count } => {
+                let i = ((v - low) / step).round();
+                (i + 0.5) / count as f64
+            }
+            Scale::LogGrid { low, high } => ((v + 0.5).ln() - low.ln()) / (high.ln() - low.ln()),
+        }
+    }
+
+    fn denormalize(self, u: f64) -> f64 {
+        match self {
+            Scale::Keep => u,
+            Scale::Linear {
+                low,
+                high,
+                inclusive_high,
+            } => clamp(low + u * (high - low), low, high, inclusive_high),
+            Scale::Log {
+                low,
+                high,
+                inclusive_high,
+            } => {
+                let v = (low.ln() + u * (high.ln() - low.ln())).exp();
+                clamp(v, low, high, inclusive_high)
+            }
+            Scale::Grid { low, step, count } => {
+                let i = (u * count as f64).floor().max(0.0).min(count as f64 - 1.0);
+                low + i * step
+            }
+            Scale::LogGrid { low, high } => {
+                let v = (low.ln() + u * (high.ln() - low.ln())).exp().floor();
+                v.max(low).min(high - 1.0)
+            }
+        }
+    }
+}
+
+fn clamp(v: f64, low: f64, high: f64, inclusive_high: bool) -> f64 {
+    if v < low {
+        low
+    } else if inclusive_high && v > high {
+        high
+    } else if !inclusive_high && v >= high {
+        low + (high - low) * (1.0 - f64::EPSILON)
+    } else {
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use crate::rng::Rng;
+    use trackable::result::TopLevelResult;
+
+    fn random_var<R: Rng>(name: &str, rng: &mut R) -> VariableBuilder {
+        let var = var(name);
+        match rng.gen_range(0..6) {
+            0 => {
+                let low = rng.gen_range(-100.0..100.0);
+                var.continuous(low, low + rng.gen_range(0.001..100.0))
+            }
+            1 => {
+                let low = rng.gen_range(-100.0..100.0);
+                var.continuous_inclusive(low, low + rng.gen_range(0.001..100.0))
+            }
+            2 => {
+                let low = rng.gen_range(1e-5..10.0);
+                var.continuous(low, low * rng.gen_range(1.1..1e5))
+                    .log_uniform()
+            }
+            3 => {
+                let low = rng.gen_range(-100.0..100.0);
+                var.continuous(low, low + rng.gen_range(1.0..100.0))
+                    .step(rng.gen_range(0.01..1.0))
+            }
+            4 => {
+                let low = rng.gen_range(-100..100);
+                let width = rng.gen_range(1..100);
+                var.discrete(low, low + width)
+                    .step(rng.gen_range(1..=width.min(4)) as f64)
+            }
+            _ => {
+                let low = rng.gen_range(1..100);
+                var.discrete(low, low + rng.gen_range(1..1000))
+                    .log_uniform()
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_params_filter_works() -> TopLevelResult {
+        let mut rng = ArcRng::new(0);
+        for _ in 0..100 {
+            let mut builder = ProblemSpecBuilder::new("foo").value(var("y"));
+            for i in 0..5 {
+                builder = builder.param(random_var(&format!("x{}", i), &mut rng));
+            }
+            builder = builder.param(var("c").categorical(["a", "b"]));
+            let original = track!(builder.finish())?;
+
+            let mut spec = original.clone();
+            let mut filter = track!(NormalizeParamsFilterRecipe {}.create_filter(rng.clone()))?;
+            track!(filter.filter_problem_spec(&mut spec))?;
+
+            for _ in 0..10 {
+                let params = Params::new(
+                    original
+                        .params_domain
+                        .variables()
+                        .iter()
+                        .map(|v| v.range().sample(v.distribution(), &mut rng))
+                        .collect(),
+                );
+                let normalized = track!(filter.normalize(&params))?;
+                track!(spec.validate_params(&normalized))?;
+
+                let denormalized = track!(filter.denormalize(&normalized))?;
+                track!(original.validate_params(&denormalized))?;
+                for (a, b) in params.iter().zip(denormalized.iter()) {
+                    assert!((a - b).abs() <= 1e-9 * a.abs().max(1.0), "{} != {}", a, b);
+                }
+
+                let random = Params::new(
+                    spec.params_domain
+                        .variables()
+                        .iter()
+                        .map(|v| v.range().sample(v.distribution(), &mut rng))
+                        .collect(),
+                );
+                let denormalized = track!(filter.denormalize(&random))?;
+                track!(original.validate_params(&denormalized))?;
+            }
+        }
+        Ok(())
+    }
+}
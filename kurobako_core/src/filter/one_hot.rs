@@ -0,0 +1,153 @@
+use crate::domain::{self, Domain, Range, VariableBuilder};
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::ArcRng;
+use crate::trial::{NextTrial, Params};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `OneHotFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct OneHotFilterRecipe {}
+impl FilterRecipe for OneHotFilterRecipe {
+    type Filter = OneHotFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        Ok(OneHotFilter { widths: Vec::new() })
+    }
+}
+
+/// Filter that encodes categorical variables into one-hot continuous variables.
+///
+/// A categorical variable `foo` that has `n` choices is shown to solvers as
+/// `n` continuous variables `foo[CHOICE]` in `[0, 1)`,
+/// and the choice having the largest value is passed to the problem.
+///
+/// Categorical variables that are referred from the conditions of other variables are left as is.
+#[derive(Debug)]
+pub struct OneHotFilter {
+    // The number of the solver-side variables corresponding to each problem-side variable
+    // (`None` means that the variable is left as is).
+    widths: Vec<Option<usize>>,
+}
+impl OneHotFilter {
+    fn decode(&self, params: &Params) -> Result<Params> {
+        let expected = self.widths.iter().map(|w| w.unwrap_or(1)).sum::<usize>();
+        track_assert_eq!(params.len(), expected, ErrorKind::InvalidInput);
+
+        let mut decoded = Vec::with_capacity(self.widths.len());
+        let mut params = &params[..];
+        for width in &self.widths {
+            let n = width.unwrap_or(1);
+            let (head, tail) = params.split_at(n);
+            params = tail;
+
+            if width.is_none() {
+                decoded.push(head[0]);
+            } else if head.iter().any(|v| v.is_nan()) {
+                decoded.push(f64::NAN);
+            } else {
+                let (i, _) = head.iter().enumerate().fold((0, head[0]), |acc, (i, &v)| {
+                    if v > acc.1 {
+                        (i, v)
+                    } else {
+                        acc
+                    }
+                });
+                decoded.push(i as f64);
+            }
+        }
+        Ok(Params::new(decoded))
+    }
+}
+impl Filter for OneHotFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        let variables = spec.params_domain.variables();
+        let mut vars = Vec::new();
+        self.widths.clear();
+        for var in variables {
+            let is_condition_target = variables
+                .iter()
+                .filter_map(|v| v.condition())
+                .any(|c| c.target() == var.name());
+            match var.range() {
+                Range::Categorical { choices, .. } if !is_condition_target => {
+                    for choice in choices {
+                        let mut builder = domain::var(&format!("{}[{}]", var.name(), choice))
+                            .continuous(0.0, 1.0);
+                        if let Some(condition) = var.condition() {
+                            builder = builder.condition(condition.clone());
+                        }
+                        vars.push(builder);
+                    }
+                    self.widths.push(Some(choices.len()));
+                }
+                _ => {
+                    vars.push(VariableBuilder::from(var.clone()));
+                    self.widths.push(None);
+                }
+            }
+        }
+        spec.params_domain = track!(Domain::new(vars))?;
+        Ok(())
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        trial.params = track!(self.decode(&trial.params))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    fn encode(filter: &OneHotFilter, params: &Params) -> Result<Params> {
+        track_assert_eq!(params.len(), filter.widths.len(), ErrorKind::InvalidInput);
+
+        let mut encoded = Vec::new();
+        for (&v, width) in params.iter().zip(filter.widths.iter()) {
+            match width {
+                None => encoded.push(v),
+                Some(n) if v.is_nan() => encoded.extend((0..*n).map(|_| f64::NAN)),
+                Some(n) => encoded.extend((0..*n).map(|i| if i == v as usize { 1.0 } else { 0.0 })),
+            }
+        }
+        Ok(Params::new(encoded))
+    }
+
+    #[test]
+    fn one_hot_filter_works() -> TopLevelResult {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("a").categorical(["x", "y", "z"]))
+            .param(var("b").continuous(0.0, 1.0))
+            .param(var("c").categorical(["p", "q"]))
+            .value(var("v"))
+            .finish())?;
+
+        let mut filter = track!(OneHotFilterRecipe {}.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        let names = spec
+            .params_domain
+            .variables()
+            .iter()
+            .map(|v| v.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a[x]", "a[y]", "a[z]", "b", "c[p]", "c[q]"]);
+
+        for &(a, c) in &[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)] {
+            let params = Params::new(vec![a, 0.3, c]);
+            let encoded = track!(encode(&filter, &params))?;
+            assert_eq!(track!(filter.decode(&encoded))?, params);
+        }
+
+        let decoded = track!(filter.decode(&Params::new(vec![0.2, 0.9, 0.5, 0.3, 0.6, 0.1])))?;
+        assert_eq!(decoded, Params::new(vec![1.0, 0.3, 0.0]));
+        Ok(())
+    }
+}
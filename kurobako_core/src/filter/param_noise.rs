@@ -0,0 +1,189 @@
+use crate::filter::{Filter, FilterRecipe, NormalizeParamsFilter, NormalizeParamsFilterRecipe};
+use crate::problem::ProblemSpec;
+use crate::rng::{standard_normal, ArcRng};
+use crate::trial::{NextTrial, Params, TrialId};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use structopt::StructOpt;
+
+/// Recipe of `ParamNoiseFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ParamNoiseFilterRecipe {
+    /// Standard deviation of the noise added to a parameter, relative to the width of its range.
+    #[structopt(long, default_value = "0.01")]
+    pub sigma_fraction: f64,
+
+    /// If specified, each parameter is snapped to the nearest of the given number of equally spaced
+    /// intervals of its range (e.g., `10` means the grid points at every 10% of the range).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantize: Option<u64>,
+}
+impl FilterRecipe for ParamNoiseFilterRecipe {
+    type Filter = ParamNoiseFilter;
+
+    fn create_filter(&self, rng: ArcRng) -> Result<Self::Filter> {
+        track_assert!(
+            self.sigma_fraction.is_finite() && self.sigma_fraction >= 0.0,
+            ErrorKind::InvalidInput; self.sigma_fraction
+        );
+        track_assert_ne!(self.quantize, Some(0), ErrorKind::InvalidInput);
+        let normalizer = track!(NormalizeParamsFilterRecipe {}.create_filter(rng.clone()))?;
+        Ok(ParamNoiseFilter {
+            sigma_fraction: self.sigma_fraction,
+            quantize: self.quantize,
+            rng,
+            normalizer,
+            perturbed: HashMap::new(),
+        })
+    }
+}
+
+/// Filter that perturbs the parameters asked by solvers before they are evaluated
+/// (e.g., to simulate imprecise actuators).
+///
+/// The noise is added in the normalized space of each variable (the log space for log-uniform ones),
+/// and the perturbed parameters are clamped to the domain.
+/// Categorical and ordinal variables, unbounded variables, and variables that have evaluation constraints
+/// or are referred from conditions are left untouched (see `NormalizeParamsFilter`).
+///
+/// The parameters originally asked by the solver are kept in `NextTrial::asked_params`.
+/// This filter should be the closest to the problem (i.e., the first filter of a study),
+/// because the filters between it and the problem don't update `NextTrial::asked_params`.
+#[derive(Debug)]
+pub struct ParamNoiseFilter {
+    sigma_fraction: f64,
+    quantize: Option<u64>,
+    rng: ArcRng,
+    normalizer: NormalizeParamsFilter,
+
+    // Resumed trials are evaluated with the same perturbed parameters.
+    perturbed: HashMap<TrialId, Params>,
+}
+impl ParamNoiseFilter {
+    fn perturb(&mut self, params: &Params) -> Result<Params> {
+        let normalized = track!(self.normalizer.normalize(params))?;
+        let mut perturbed = Vec::with_capacity(normalized.len());
+        for (i, &u) in normalized.iter().enumerate() {
+            if u.is_nan() || !self.normalizer.normalizes(i) {
+                perturbed.push(u);
+                continue;
+            }
+
+            let mut u = u + self.sigma_fraction * standard_normal(&mut self.rng);
+            if let Some(n) = self.quantize {
+                u = (u * n as f64).round() / n as f64;
+            }
+            perturbed.push(u.clamp(0.0, 1.0));
+        }
+        track!(self.normalizer.denormalize(&Params::new(perturbed)))
+    }
+}
+impl Filter for ParamNoiseFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        // The specification seen by the solver is not changed.
+        track!(self.normalizer.filter_problem_spec(&mut spec.clone()))
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        let perturbed = if let Some(perturbed) = self.perturbed.get(&trial.id) {
+            perturbed.clone()
+        } else {
+            let perturbed = track!(self.perturb(&trial.params))?;
+            self.perturbed.insert(trial.id, perturbed.clone());
+            perturbed
+        };
+        if trial.next_step.is_none() {
+            self.perturbed.remove(&trial.id);
+        }
+
+        let asked = std::mem::replace(&mut trial.params, perturbed);
+        if trial.asked_params.is_none() {
+            trial.asked_params = Some(asked);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    fn trial(id: u64, params: Vec<f64>) -> NextTrial {
+        NextTrial {
+            id: TrialId::new(id),
+            params: Params::new(params),
+            next_step: Some(1),
+            fidelity: Params::new(Vec::new()),
+            asked_params: None,
+        }
+    }
+
+    fn filter(
+        sigma_fraction: f64,
+        quantize: Option<u64>,
+    ) -> Result<(ParamNoiseFilter, ProblemSpec)> {
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 10.0))
+            .param(var("y").discrete(0, 5))
+            .param(var("c").categorical(["a", "b", "c"]))
+            .value(var("v"))
+            .finish())?;
+        let original = spec.clone();
+        let recipe = ParamNoiseFilterRecipe {
+            sigma_fraction,
+            quantize,
+        };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        track!(filter.filter_problem_spec(&mut spec))?;
+        assert_eq!(spec, original);
+        Ok((filter, spec))
+    }
+
+    #[test]
+    fn param_noise_filter_works() -> TopLevelResult {
+        let (mut filter, spec) = track!(filter(0.1, None))?;
+        let mut changed = false;
+        for i in 0..100 {
+            let mut t = trial(i, vec![9.9, 0.0, 2.0]);
+            track!(filter.filter_ask(&mut t))?;
+            assert_eq!(t.asked_params, Some(Params::new(vec![9.9, 0.0, 2.0])));
+
+            // The perturbed parameters are clamped to the domain, and categorical ones are untouched.
+            track!(spec.validate_params(&t.params))?;
+            assert_eq!(t.params[2], 2.0);
+            changed |= t.params[0] != 9.9;
+        }
+        assert!(changed);
+
+        // A resumed trial is evaluated with the same parameters.
+        let mut t0 = trial(0, vec![5.0, 2.0, 1.0]);
+        track!(filter.filter_ask(&mut t0))?;
+        let mut t1 = trial(0, vec![5.0, 2.0, 1.0]);
+        track!(filter.filter_ask(&mut t1))?;
+        assert_eq!(t0.params, t1.params);
+        Ok(())
+    }
+
+    #[test]
+    fn quantize_works() -> TopLevelResult {
+        let (mut filter, _) = track!(filter(0.0, Some(4)))?;
+        let mut t = trial(0, vec![3.3, 3.0, 0.0]);
+        track!(filter.filter_ask(&mut t))?;
+        assert_eq!(t.params[0], 2.5);
+        assert_eq!(t.params[1], 3.0);
+
+        assert!(ParamNoiseFilterRecipe {
+            sigma_fraction: 0.1,
+            quantize: Some(0)
+        }
+        .create_filter(ArcRng::new(0))
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::filter::{Filter, FilterRecipe};
+use crate::problem::{EvaluableSteps, ProblemSpec};
+use crate::rng::ArcRng;
+use crate::trial::{EvaluatedTrial, NextTrial};
+use crate::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Recipe of `RescaleStepsFilter`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct RescaleStepsFilterRecipe {
+    /// The number of the steps shown to solvers.
+    #[structopt(long)]
+    pub steps: u64,
+}
+impl FilterRecipe for RescaleStepsFilterRecipe {
+    type Filter = RescaleStepsFilter;
+
+    fn create_filter(&self, _rng: ArcRng) -> Result<Self::Filter> {
+        track_assert!(self.steps > 0, ErrorKind::InvalidInput);
+        Ok(RescaleStepsFilter {
+            solver_steps: self.steps,
+            problem_steps: 0,
+        })
+    }
+}
+
+/// Filter that shows solvers a problem as if it had the given number of evaluation steps.
+///
+/// A solver step `s` corresponds to the problem step `ceil(s * L / S)`
+/// and a problem step `p` is reported as the solver step `floor(p * S / L)`,
+/// where `S` is the number of the solver steps and `L` is the last step of the problem.
+/// Thus, the last steps of both sides always correspond to each other.
+#[derive(Debug)]
+pub struct RescaleStepsFilter {
+    solver_steps: u64,
+    problem_steps: u64,
+}
+impl RescaleStepsFilter {
+    fn to_problem_step(&self, step: u64) -> u64 {
+        let (s, l) = (
+            u128::from(self.solver_steps),
+            u128::from(self.problem_steps),
+        );
+        (u128::from(step) * l).div_ceil(s) as u64
+    }
+
+    fn to_solver_step(&self, step: u64) -> u64 {
+        let (s, l) = (
+            u128::from(self.solver_steps),
+            u128::from(self.problem_steps),
+        );
+        (u128::from(step) * s / l) as u64
+    }
+}
+impl Filter for RescaleStepsFilter {
+    fn filter_problem_spec(&mut self, spec: &mut ProblemSpec) -> Result<()> {
+        self.problem_steps = spec.steps.last();
+        track_assert!(
+            self.solver_steps <= self.problem_steps,
+            ErrorKind::InvalidInput,
+            "The number of the rescaled steps ({}) exceeds the last step of the problem ({})",
+            self.solver_steps,
+            self.problem_steps
+        );
+        spec.steps = track!(EvaluableSteps::new((1..=self.solver_steps).collect()))?;
+        Ok(())
+    }
+
+    fn filter_ask(&mut self, trial: &mut NextTrial) -> Result<()> {
+        trial.next_step = trial.next_step.map(|s| self.to_problem_step(s));
+        Ok(())
+    }
+
+    fn filter_tell(&mut self, trial: &mut EvaluatedTrial) -> Result<()> {
+        trial.current_step = self.to_solver_step(trial.current_step);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn rescale_steps_filter_works() -> TopLevelResult {
+        for &(problem_steps, solver_steps) in &[(1000, 10), (7, 3), (5, 5), (1, 1)] {
+            let mut spec = track!(ProblemSpecBuilder::new("foo")
+                .param(var("x").continuous(0.0, 1.0))
+                .value(var("y"))
+                .steps(1..=problem_steps)
+                .finish())?;
+
+            let recipe = RescaleStepsFilterRecipe {
+                steps: solver_steps,
+            };
+            let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+            track!(filter.filter_problem_spec(&mut spec))?;
+            assert_eq!(spec.steps.last(), solver_steps);
+
+            assert_eq!(filter.to_problem_step(solver_steps), problem_steps);
+            assert_eq!(filter.to_solver_step(problem_steps), solver_steps);
+            for s in 1..=solver_steps {
+                let p = filter.to_problem_step(s);
+                assert!(p <= problem_steps);
+                assert_eq!(filter.to_solver_step(p), s);
+            }
+        }
+
+        let mut spec = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .steps(1..=3)
+            .finish())?;
+        let recipe = RescaleStepsFilterRecipe { steps: 10 };
+        let mut filter = track!(recipe.create_filter(ArcRng::new(0)))?;
+        assert!(filter.filter_problem_spec(&mut spec).is_err());
+        Ok(())
+    }
+}
@@ -1,20 +1,327 @@
 //! JSON.
-use crate::{Error, Result};
+use crate::{Error, ErrorKind, Result};
+use serde::de::{Deserializer as _, SeqAccess, Visitor};
 use serde::Deserialize;
-use std::io::Read;
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use trackable::error::ErrorKindExt;
+
+/// The key used to refer to a JSON file from within a recipe (e.g., `{"$file": "foo.json"}`).
+pub const FILE_REF_KEY: &str = "$file";
+
+/// The maximum nesting depth of file references.
+pub const MAX_FILE_REF_DEPTH: usize = 32;
 
 /// JSON representation of a recipe.
 pub type JsonRecipe = serde_json::Value;
 
+/// Format of input files.
+///
+/// Whatever the input format is, outputs are always JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// JSON (newline-delimited or pretty-printed documents, or an array of entries).
+    #[default]
+    Json,
+
+    /// YAML (a stream of documents separated by `---`).
+    ///
+    /// Loading YAML requires the `yaml` feature (see the `yaml` module).
+    Yaml,
+}
+impl Format {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["json", "yaml"];
+
+    /// Guesses the format from the extension of the given path (`.yaml` or `.yml` for YAML).
+    ///
+    /// The extensions of compressed files (`.gz` and `.zst`) are ignored.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut path = path;
+        while let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            match ext {
+                "json" => return Some(Self::Json),
+                "yaml" | "yml" => return Some(Self::Yaml),
+                "gz" | "zst" => path = Path::new(path.file_stem().unwrap_or_default()),
+                _ => break,
+            }
+        }
+        None
+    }
+}
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown format: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
 /// Parses the given JSON string.
+///
+/// File references (`{"$file": "path/to/recipe.json"}`) contained in the JSON are resolved
+/// relative to the current directory.
 pub fn parse_json<T>(json: &str) -> Result<T>
 where
     T: for<'a> Deserialize<'a>,
 {
     let v = track!(serde_json::from_str(json).map_err(Error::from))?;
+    let v = track!(resolve_file_refs(v, Path::new(".")))?;
+    let v = track!(serde_json::from_value(v).map_err(Error::from))?;
     Ok(v)
 }
 
+/// Replaces every file reference (`{"$file": "path/to/recipe.json"}`) in the given JSON
+/// with the content of the referred file.
+///
+/// Files whose extensions are `.yaml` or `.yml` are parsed as YAML, and the others as JSON.
+///
+/// Relative paths are resolved against `base_dir` for top-level references and
+/// against the directory of the including file for nested ones.
+pub fn resolve_file_refs(json: JsonRecipe, base_dir: &Path) -> Result<JsonRecipe> {
+    track!(resolve_file_refs_rec(json, base_dir, &mut Vec::new()))
+}
+
+fn resolve_file_refs_rec(
+    json: JsonRecipe,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<JsonRecipe> {
+    match json {
+        JsonRecipe::Object(map) => {
+            if map.len() == 1 {
+                if let Some(path) = map.get(FILE_REF_KEY) {
+                    let path = track_assert_some!(path.as_str(), ErrorKind::InvalidInput; path);
+                    return track!(load_file_ref(&base_dir.join(path), stack));
+                }
+            }
+            map.into_iter()
+                .map(|(k, v)| Ok((k, track!(resolve_file_refs_rec(v, base_dir, stack))?)))
+                .collect::<Result<_>>()
+                .map(JsonRecipe::Object)
+        }
+        JsonRecipe::Array(array) => array
+            .into_iter()
+            .map(|v| track!(resolve_file_refs_rec(v, base_dir, stack)))
+            .collect::<Result<_>>()
+            .map(JsonRecipe::Array),
+        _ => Ok(json),
+    }
+}
+
+fn load_file_ref(path: &Path, stack: &mut Vec<PathBuf>) -> Result<JsonRecipe> {
+    let path = track!(path.canonicalize().map_err(Error::from); path)?;
+    track_assert!(
+        !stack.contains(&path),
+        ErrorKind::InvalidInput,
+        "Cyclic file reference: {:?}",
+        path
+    );
+    track_assert!(
+        stack.len() < MAX_FILE_REF_DEPTH,
+        ErrorKind::InvalidInput,
+        "Too deep file references: {:?}",
+        path
+    );
+
+    let file = track!(File::open(&path).map_err(Error::from); path)?;
+    let json: JsonRecipe = if Format::from_path(&path) == Some(Format::Yaml) {
+        track!(load_yaml_file(file); path)?
+    } else {
+        track!(serde_json::from_reader(file).map_err(Error::from); path)?
+    };
+    let base_dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+
+    stack.push(path);
+    let result = track!(resolve_file_refs_rec(json, &base_dir, stack));
+    stack.pop();
+    result
+}
+
+#[cfg(feature = "yaml")]
+fn load_yaml_file(file: File) -> Result<JsonRecipe> {
+    track!(crate::yaml::from_reader(file))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn load_yaml_file(_file: File) -> Result<JsonRecipe> {
+    track_panic!(
+        ErrorKind::InvalidInput,
+        "YAML files are not supported (the `yaml` feature is disabled)"
+    )
+}
+
+/// Returns the names of the placeholders (e.g., `${dim}`) contained in the string values of the given JSON.
+pub fn placeholders(json: &JsonRecipe) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    track!(visit_placeholders(json, &mut String::new(), &mut |name| {
+        names.insert(name.to_owned());
+    }))?;
+    Ok(names)
+}
+
+/// Replaces the placeholders (e.g., `${dim}`) in the string values of the given JSON with the values in `vars`.
+///
+/// A string that consists of a single placeholder is replaced by the value itself (e.g., a number).
+/// Otherwise, each placeholder is replaced by the string representation of the value.
+///
+/// `ErrorKind::InvalidInput` is returned if a placeholder is not defined in `vars`.
+pub fn substitute_placeholders(
+    json: &JsonRecipe,
+    vars: &BTreeMap<String, JsonRecipe>,
+) -> Result<JsonRecipe> {
+    track!(substitute_rec(json, vars, &mut String::new()))
+}
+
+fn substitute_rec(
+    json: &JsonRecipe,
+    vars: &BTreeMap<String, JsonRecipe>,
+    pointer: &mut String,
+) -> Result<JsonRecipe> {
+    match json {
+        JsonRecipe::String(s) => {
+            let segments = track!(parse_placeholders(s, pointer))?;
+            let lookup = |name: &str| {
+                let value = track_assert_some!(
+                    vars.get(name),
+                    ErrorKind::InvalidInput,
+                    "Undefined placeholder `${{{}}}` at {:?}",
+                    name,
+                    pointer
+                );
+                Ok(value)
+            };
+            if let [Segment::Placeholder(name)] = segments[..] {
+                return track!(lookup(name)).map(|v| v.clone());
+            }
+
+            let mut substituted = String::new();
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => substituted.push_str(text),
+                    Segment::Placeholder(name) => match track!(lookup(name))? {
+                        JsonRecipe::String(v) => substituted.push_str(v),
+                        v => substituted.push_str(&v.to_string()),
+                    },
+                }
+            }
+            Ok(JsonRecipe::String(substituted))
+        }
+        JsonRecipe::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| with_pointer(pointer, &i.to_string(), |p| substitute_rec(v, vars, p)))
+            .collect::<Result<_>>()
+            .map(JsonRecipe::Array),
+        JsonRecipe::Object(map) => map
+            .iter()
+            .map(|(k, v)| {
+                let v = track!(with_pointer(pointer, k, |p| substitute_rec(v, vars, p)))?;
+                Ok((k.clone(), v))
+            })
+            .collect::<Result<_>>()
+            .map(JsonRecipe::Object),
+        _ => Ok(json.clone()),
+    }
+}
+
+fn visit_placeholders<F>(json: &JsonRecipe, pointer: &mut String, f: &mut F) -> Result<()>
+where
+    F: FnMut(&str),
+{
+    match json {
+        JsonRecipe::String(s) => {
+            for segment in track!(parse_placeholders(s, pointer))? {
+                if let Segment::Placeholder(name) = segment {
+                    f(name);
+                }
+            }
+        }
+        JsonRecipe::Array(array) => {
+            for (i, v) in array.iter().enumerate() {
+                track!(with_pointer(pointer, &i.to_string(), |p| {
+                    visit_placeholders(v, p, f)
+                }))?;
+            }
+        }
+        JsonRecipe::Object(map) => {
+            for (k, v) in map {
+                track!(with_pointer(pointer, k, |p| visit_placeholders(v, p, f)))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Calls `f` with the JSON pointer extended by the given token.
+fn with_pointer<F, T>(pointer: &mut String, token: &str, f: F) -> Result<T>
+where
+    F: FnOnce(&mut String) -> Result<T>,
+{
+    let len = pointer.len();
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    let result = f(pointer);
+    pointer.truncate(len);
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse_placeholders<'a>(mut s: &'a str, pointer: &str) -> Result<Vec<Segment<'a>>> {
+    let mut segments = Vec::new();
+    while let Some(start) = s.find("${") {
+        if start > 0 {
+            segments.push(Segment::Text(&s[..start]));
+        }
+        let end = track_assert_some!(
+            s[start..].find('}'),
+            ErrorKind::InvalidInput,
+            "Unclosed placeholder at {:?}: {:?}",
+            pointer,
+            s
+        );
+        let name = &s[start + 2..start + end];
+        track_assert!(
+            !name.is_empty(),
+            ErrorKind::InvalidInput,
+            "Empty placeholder at {:?}",
+            pointer
+        );
+        segments.push(Segment::Placeholder(name));
+        s = &s[start + end + 1..];
+    }
+    if !s.is_empty() {
+        segments.push(Segment::Text(s));
+    }
+    Ok(segments)
+}
+
 /// Loads entries from the given reader.
 pub fn load<R, T>(reader: R) -> Result<Vec<T>>
 where
@@ -26,3 +333,352 @@ where
         .map(|json| track!(json.map_err(Error::from)))
         .collect()
 }
+
+/// Loads entries from the given reader, tolerating malformed ones.
+///
+/// The input is either a JSON array of entries or a sequence of JSON documents
+/// (e.g., newline-delimited or concatenated pretty-printed documents).
+/// The format is detected from the first non-whitespace byte.
+///
+/// `f` is called for every entry in order with the parsed entry or the parse error.
+/// The error messages contain the byte offset at which the error was detected.
+/// After a syntax error in a sequence, the input is skipped to the next line starting with `{`
+/// (i.e., the start of a newline-delimited or pretty-printed document).
+/// A syntax error in an array ends the input.
+///
+/// Only I/O errors and the errors returned by `f` abort the loading.
+pub fn load_lenient<R, T, F>(reader: R, mut f: F) -> Result<()>
+where
+    R: BufRead,
+    T: for<'a> Deserialize<'a>,
+    F: FnMut(Result<T>) -> Result<()>,
+{
+    let mut reader = CountingReader {
+        inner: reader,
+        count: 0,
+    };
+    loop {
+        let buf = track!(reader.fill_buf().map_err(Error::from))?;
+        let whitespaces = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        let done = buf.is_empty() || whitespaces < buf.len();
+        let is_array = buf.get(whitespaces) == Some(&b'[');
+        reader.consume(whitespaces);
+        if done {
+            if is_array {
+                return track!(load_array(&mut reader, f));
+            }
+            break;
+        }
+    }
+
+    // Each chunk starts with a line beginning with `{` (i.e., the start of a top-level document),
+    // so a syntax error only affects the chunk containing it.
+    let mut chunk = Vec::new();
+    let mut chunk_offset = reader.count;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let size = track!(reader.read_until(b'\n', &mut line).map_err(Error::from))?;
+        if size == 0 || (line.starts_with(b"{") && !chunk.is_empty()) {
+            track!(load_chunk(&chunk, chunk_offset, &mut f))?;
+            chunk.clear();
+            chunk_offset = reader.count - size as u64;
+        }
+        if size == 0 {
+            return Ok(());
+        }
+        chunk.extend_from_slice(&line);
+    }
+}
+
+fn load_chunk<T, F>(chunk: &[u8], offset: u64, f: &mut F) -> Result<()>
+where
+    T: for<'a> Deserialize<'a>,
+    F: FnMut(Result<T>) -> Result<()>,
+{
+    for entry in serde_json::Deserializer::from_slice(chunk).into_iter() {
+        match entry {
+            Ok(entry) => track!(f(from_value(entry)))?,
+            Err(e) => {
+                let position = chunk
+                    .split(|&b| b == b'\n')
+                    .take(e.line().saturating_sub(1))
+                    .map(|line| line.len() + 1)
+                    .sum::<usize>()
+                    + e.column();
+                return track!(f(Err(syntax_error(e, offset + position as u64))));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_array<R, T, F>(reader: &mut CountingReader<R>, f: F) -> Result<()>
+where
+    R: BufRead,
+    T: for<'a> Deserialize<'a>,
+    F: FnMut(Result<T>) -> Result<()>,
+{
+    struct EntriesVisitor<T, F> {
+        f: F,
+        error: Option<Error>,
+        _entry: std::marker::PhantomData<T>,
+    }
+    impl<'de, T, F> Visitor<'de> for &mut EntriesVisitor<T, F>
+    where
+        T: for<'a> Deserialize<'a>,
+        F: FnMut(Result<T>) -> Result<()>,
+    {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "an array")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<(), A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            while let Some(entry) = seq.next_element()? {
+                if let Err(e) = (self.f)(from_value(entry)) {
+                    self.error = Some(e);
+                    return Err(serde::de::Error::custom("aborted"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut visitor = EntriesVisitor {
+        f,
+        error: None,
+        _entry: std::marker::PhantomData,
+    };
+    let mut de = serde_json::Deserializer::from_reader(&mut *reader);
+    let result = de.deserialize_seq(&mut visitor).and_then(|()| de.end());
+    if let Some(e) = visitor.error {
+        return Err(track!(e));
+    }
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.is_io() => Err(track!(Error::from(e))),
+        Err(e) => track!((visitor.f)(Err(syntax_error(e, reader.count)))),
+    }
+}
+
+fn from_value<T>(value: JsonValue) -> Result<T>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    track!(serde_json::from_value(value).map_err(Error::from))
+}
+
+fn syntax_error(e: serde_json::Error, byte_offset: u64) -> Error {
+    // Strips the position suffix (e.g., " at line 1 column 2") in favor of the byte offset.
+    let message = e.to_string();
+    let message = message
+        .rfind(" at line ")
+        .map_or(&message[..], |i| &message[..i]);
+    ErrorKind::InvalidInput
+        .cause(format!("{} at byte offset {}", message, byte_offset))
+        .into()
+}
+
+#[derive(Debug)]
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.count += size as u64;
+        Ok(size)
+    }
+}
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn resolve_file_refs_works() -> TopLevelResult {
+        let dir = std::env::temp_dir().join(format!("kurobako_json_test_{}", std::process::id()));
+        track!(fs::create_dir_all(dir.join("sub")).map_err(Error::from))?;
+        track!(fs::write(dir.join("sub/inner.json"), r#"{"x": 1}"#).map_err(Error::from))?;
+        track!(fs::write(
+            dir.join("sub/outer.json"),
+            r#"{"inner": {"$file": "inner.json"}}"#
+        )
+        .map_err(Error::from))?;
+        track!(
+            fs::write(dir.join("cycle.json"), r#"[{"$file": "cycle.json"}]"#).map_err(Error::from)
+        )?;
+
+        let json = serde_json::json!({"a": [{"$file": "sub/outer.json"}], "b": 2});
+        let resolved = track!(resolve_file_refs(json, &dir))?;
+        assert_eq!(
+            resolved,
+            serde_json::json!({"a": [{"inner": {"x": 1}}], "b": 2})
+        );
+
+        let json = serde_json::json!({"$file": "cycle.json"});
+        assert!(resolve_file_refs(json, &dir).is_err());
+
+        track!(fs::remove_dir_all(&dir).map_err(Error::from))?;
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Entry {
+        x: u32,
+    }
+
+    fn load_entries(input: &str) -> Result<(Vec<u32>, Vec<Error>)> {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        track!(load_lenient(input.as_bytes(), |entry: Result<Entry>| {
+            match entry {
+                Ok(entry) => entries.push(entry.x),
+                Err(e) => errors.push(e),
+            }
+            Ok(())
+        }))?;
+        Ok((entries, errors))
+    }
+
+    #[test]
+    fn load_lenient_works_with_arrays() -> TopLevelResult {
+        let (entries, errors) = track!(load_entries("  [{\"x\": 1},\n {\"x\": 2}, {\"y\": 3}]\n"))?;
+        assert_eq!(entries, vec![1, 2]);
+        assert_eq!(errors.len(), 1);
+
+        let (entries, errors) = track!(load_entries("[]"))?;
+        assert!(entries.is_empty());
+        assert!(errors.is_empty());
+
+        let (entries, errors) = track!(load_entries("[{\"x\": 1}, {\"x\": "))?;
+        assert_eq!(entries, vec![1]);
+        assert_eq!(errors.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn load_lenient_works_with_sequences() -> TopLevelResult {
+        let (entries, errors) = track!(load_entries("{\"x\": 1}\n{\"x\": 2}\n"))?;
+        assert_eq!(entries, vec![1, 2]);
+        assert!(errors.is_empty());
+
+        let (entries, errors) = track!(load_entries("{\n  \"x\": 1\n}\n{\n  \"x\": 2\n}"))?;
+        assert_eq!(entries, vec![1, 2]);
+        assert!(errors.is_empty());
+
+        let (entries, errors) = track!(load_entries(""))?;
+        assert!(entries.is_empty());
+        assert!(errors.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn load_lenient_skips_corrupt_entries() -> TopLevelResult {
+        let input = "{\"x\": 1}\n{\"x\": 2, \"z\n{\"x\": 3}\n{\"y\": 4}\n{\"x\": 5}\n";
+        let (entries, errors) = track!(load_entries(input))?;
+        assert_eq!(entries, vec![1, 3, 5]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].to_string().contains("byte offset"));
+
+        let input = "{\n  \"x\": 1\n}\n{\n  \"x\": \n}\n{\n  \"x\": 3\n}\n";
+        let (entries, errors) = track!(load_entries(input))?;
+        assert_eq!(entries, vec![1, 3]);
+        assert_eq!(errors.len(), 1);
+
+        let result = load_lenient("{\"x\": 1}{\"x\": 2}".as_bytes(), |_: Result<Entry>| {
+            track_panic!(ErrorKind::Other)
+        });
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn format_from_path_works() {
+        let format = |path: &str| Format::from_path(Path::new(path));
+        assert_eq!(format("studies.yaml"), Some(Format::Yaml));
+        assert_eq!(format("dir.json/studies.yml"), Some(Format::Yaml));
+        assert_eq!(format("studies.yaml.gz"), Some(Format::Yaml));
+        assert_eq!(format("studies.json.zst"), Some(Format::Json));
+        assert_eq!(format("studies.txt"), None);
+        assert_eq!(format("studies"), None);
+        assert_eq!("yaml".parse::<Format>().ok(), Some(Format::Yaml));
+        assert!("toml".parse::<Format>().is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_files_can_be_referred() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        track!(fs::write(
+            dir.path().join("solver.yaml"),
+            "# Random search.\nrandom: {}\n"
+        )
+        .map_err(Error::from))?;
+
+        let json = serde_json::json!({"solver": {"$file": "solver.yaml"}});
+        let resolved = track!(resolve_file_refs(json, dir.path()))?;
+        assert_eq!(resolved, serde_json::json!({"solver": {"random": {}}}));
+        Ok(())
+    }
+
+    #[test]
+    fn substitute_placeholders_works() -> TopLevelResult {
+        let json = serde_json::json!({
+            "sigopt": {"name": "ACKLEY", "dim": "${dim}"},
+            "tags": ["dim-${dim}", "${name}/${dim}", "plain"],
+            "n": 1
+        });
+        let names = track!(placeholders(&json))?;
+        assert_eq!(
+            names.into_iter().collect::<Vec<_>>(),
+            vec!["dim".to_owned(), "name".to_owned()]
+        );
+
+        let mut vars = BTreeMap::new();
+        vars.insert("dim".to_owned(), serde_json::json!(8));
+        vars.insert("name".to_owned(), serde_json::json!("foo"));
+        let substituted = track!(substitute_placeholders(&json, &vars))?;
+        assert_eq!(
+            substituted,
+            serde_json::json!({
+                "sigopt": {"name": "ACKLEY", "dim": 8},
+                "tags": ["dim-8", "foo/8", "plain"],
+                "n": 1
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn substitute_placeholders_fails_for_undefined_placeholders() {
+        let json = serde_json::json!({"sigopt": {"dims": [1, "${dim}"]}});
+        let e = substitute_placeholders(&json, &BTreeMap::new()).err();
+        let e = e.map(|e| e.to_string()).unwrap_or_default();
+        assert!(e.contains("${dim}"), "{}", e);
+        assert!(e.contains("/sigopt/dims/1"), "{}", e);
+
+        let json = serde_json::json!({"a": "${dim"});
+        assert!(placeholders(&json).is_err());
+        let json = serde_json::json!({"a": "${}"});
+        assert!(placeholders(&json).is_err());
+    }
+}
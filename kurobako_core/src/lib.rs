@@ -6,18 +6,30 @@ extern crate trackable;
 
 pub use error::{Error, ErrorKind};
 
+pub mod dataset;
 pub mod domain;
 pub mod epi;
+pub mod filter;
 pub mod hypervolume;
 pub mod json;
 pub mod num;
+pub mod parallel;
 pub mod problem;
 pub mod registry;
 pub mod rng;
 pub mod solver;
+pub mod stats;
+pub mod time;
 pub mod trial;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
 mod error;
 
+/// Version of this crate.
+///
+/// Problems and solvers built against different versions of this crate may not be compatible.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// This crate specific `Result` type.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
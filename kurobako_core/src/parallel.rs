@@ -0,0 +1,222 @@
+//! Parallel execution of independent jobs.
+use crate::{ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Runs the given jobs on `parallelism` worker threads.
+///
+/// Each worker is created by `create_worker` (with the worker index) in its own thread,
+/// so thread-local resources (e.g., the processes of external program problems) are not shared
+/// between workers. If `parallelism` is `1`, the only worker runs on the calling thread.
+///
+/// `output` is called with the index of each job in `jobs` and its result.
+/// The results are passed in the input order if `ordered` is `true`,
+/// otherwise in the completion order.
+///
+/// Jobs are consumed lazily. The first error (from `jobs`, a worker or `output`) aborts the execution.
+pub fn run_jobs<I, J, R, C, W, O>(
+    jobs: I,
+    parallelism: NonZeroUsize,
+    ordered: bool,
+    create_worker: C,
+    mut output: O,
+) -> Result<()>
+where
+    I: IntoIterator<Item = Result<J>>,
+    J: Send,
+    R: Send,
+    C: Fn(usize) -> Result<W> + Sync,
+    W: FnMut(J) -> Result<R>,
+    O: FnMut(usize, R) -> Result<()>,
+{
+    if parallelism.get() == 1 {
+        let mut worker = track!(create_worker(0))?;
+        for (i, job) in jobs.into_iter().enumerate() {
+            let result = track!(worker(track!(job)?))?;
+            track!(output(i, result))?;
+        }
+        return Ok(());
+    }
+
+    let (job_tx, job_rx) = mpsc::sync_channel::<(usize, J)>(parallelism.get());
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (result_tx, result_rx) = mpsc::channel::<(usize, Result<R>)>();
+    let create_worker = &create_worker;
+
+    thread::scope(|scope| {
+        for worker_id in 0..parallelism.get() {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            scope.spawn(move || {
+                let mut worker = match track!(create_worker(worker_id)) {
+                    Ok(worker) => worker,
+                    Err(e) => {
+                        let _ = result_tx.send((usize::MAX, Err(e)));
+                        return;
+                    }
+                };
+                loop {
+                    let job = match job_rx.lock() {
+                        Ok(rx) => rx.recv(),
+                        Err(_) => return,
+                    };
+                    let (i, job) = match job {
+                        Ok(x) => x,
+                        Err(_) => return,
+                    };
+                    let result = track!(worker(job));
+                    let failed = result.is_err();
+                    if result_tx.send((i, result)).is_err() || failed {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        let mut outputs = Outputs::new(ordered);
+        let mut sent = 0;
+        for (i, job) in jobs.into_iter().enumerate() {
+            let job = track!(job)?;
+            if job_tx.send((i, job)).is_err() {
+                // All workers have exited (a worker error will be reported below).
+                break;
+            }
+            sent += 1;
+            while let Ok((i, result)) = result_rx.try_recv() {
+                track!(outputs.push(i, result, &mut output))?;
+            }
+        }
+        drop(job_tx);
+
+        for (i, result) in result_rx.iter() {
+            track!(outputs.push(i, result, &mut output))?;
+        }
+        track_assert_eq!(outputs.received, sent, ErrorKind::Bug);
+        Ok(())
+    })
+}
+
+#[derive(Debug)]
+struct Outputs<R> {
+    ordered: bool,
+    next: usize,
+    pending: BTreeMap<usize, R>,
+    received: usize,
+}
+impl<R> Outputs<R> {
+    fn new(ordered: bool) -> Self {
+        Self {
+            ordered,
+            next: 0,
+            pending: BTreeMap::new(),
+            received: 0,
+        }
+    }
+
+    fn push<O>(&mut self, i: usize, result: Result<R>, output: &mut O) -> Result<()>
+    where
+        O: FnMut(usize, R) -> Result<()>,
+    {
+        let result = track!(result)?;
+        self.received += 1;
+        if !self.ordered {
+            return track!(output(i, result));
+        }
+
+        self.pending.insert(i, result);
+        while let Some(result) = self.pending.remove(&self.next) {
+            track!(output(self.next, result))?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::{Evaluator, Problem};
+    use crate::trial::{Params, Values};
+    use std::time::{Duration, Instant};
+    use trackable::result::TopLevelResult;
+
+    struct SleepProblem;
+    impl Problem for SleepProblem {
+        type Evaluator = SleepEvaluator;
+
+        fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+            Ok(SleepEvaluator(params[0]))
+        }
+    }
+
+    struct SleepEvaluator(f64);
+    impl Evaluator for SleepEvaluator {
+        fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+            thread::sleep(Duration::from_millis(100));
+            Ok((next_step, Values::new(vec![self.0 * 2.0])))
+        }
+    }
+
+    fn run(parallelism: usize, ordered: bool) -> Result<(Vec<(usize, f64)>, Duration)> {
+        let jobs = (0..8).map(|i| Ok(Params::new(vec![i as f64])));
+        let mut results = Vec::new();
+        let start = Instant::now();
+        track!(run_jobs(
+            jobs,
+            NonZeroUsize::new(parallelism).unwrap(),
+            ordered,
+            |_| {
+                let problem = SleepProblem;
+                Ok(move |params: Params| {
+                    let mut evaluator = track!(problem.create_evaluator(params))?;
+                    track!(evaluator.evaluate(1)).map(|(_, values)| values[0])
+                })
+            },
+            |i, value| {
+                results.push((i, value));
+                Ok(())
+            }
+        ))?;
+        Ok((results, start.elapsed()))
+    }
+
+    #[test]
+    fn parallel_jobs_work() -> TopLevelResult {
+        let (sequential, sequential_elapsed) = track!(run(1, false))?;
+        assert_eq!(sequential.len(), 8);
+        assert!(sequential_elapsed >= Duration::from_millis(800));
+
+        let (parallel, parallel_elapsed) = track!(run(4, false))?;
+        assert_eq!(parallel.len(), 8);
+        assert!(parallel_elapsed * 2 < sequential_elapsed);
+        for &(i, value) in &parallel {
+            assert_eq!(value, i as f64 * 2.0);
+        }
+
+        let (ordered, _) = track!(run(4, true))?;
+        assert_eq!(ordered, sequential);
+        Ok(())
+    }
+
+    #[test]
+    fn worker_errors_are_reported() {
+        let jobs = (0..8).map(Ok);
+        let result = run_jobs(
+            jobs,
+            NonZeroUsize::new(3).unwrap(),
+            false,
+            |_| {
+                Ok(|i: usize| {
+                    track_assert_ne!(i, 5, ErrorKind::InvalidInput);
+                    Ok(i)
+                })
+            },
+            |_, _| Ok(()),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -1,14 +1,15 @@
 //! The interface of the problem for black-box optimization.
-use crate::domain::{Distribution, Domain, Range, VariableBuilder};
+use crate::domain::{Domain, Group, Range, VariableBuilder};
 use crate::registry::FactoryRegistry;
 use crate::rng::ArcRng;
-use crate::solver::{Capabilities, Capability};
+use crate::solver::{Capabilities, Capability, IncapableError, SolverSpec};
 use crate::trial::{Params, Values};
-use crate::{ErrorKind, Result};
+use crate::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt;
 use structopt::StructOpt;
+use trackable::error::ErrorKindExt;
 
 /// `ProblemSpec` builder.
 #[derive(Debug)]
@@ -16,7 +17,9 @@ pub struct ProblemSpecBuilder {
     name: String,
     attrs: BTreeMap<String, String>,
     params: Vec<VariableBuilder>,
+    groups: Vec<Group>,
     values: Vec<VariableBuilder>,
+    fidelities: Vec<VariableBuilder>,
     steps: Vec<u64>,
     reference_point: Option<Params>,
 }
@@ -27,7 +30,9 @@ impl ProblemSpecBuilder {
             name: problem_name.to_owned(),
             attrs: BTreeMap::new(),
             params: Vec::new(),
+            groups: Vec::new(),
             values: Vec::new(),
+            fidelities: Vec::new(),
             steps: vec![1],
             reference_point: None,
         }
@@ -45,6 +50,16 @@ impl ProblemSpecBuilder {
         self
     }
 
+    /// Adds the selector variable of a group of mutually exclusive variables to the parameter domain of this problem.
+    ///
+    /// The member variables are added by `param` and they get conditions on the selector
+    /// when the specification is built (see `Domain::group`).
+    pub fn group(mut self, group: Group) -> Self {
+        self.params.push(group.selector_variable());
+        self.groups.push(group);
+        self
+    }
+
     /// Sets variables of the parameter domain of this problem.
     pub fn params(mut self, vars: Vec<VariableBuilder>) -> Self {
         self.params = vars;
@@ -63,6 +78,12 @@ impl ProblemSpecBuilder {
         self
     }
 
+    /// Adds a variable to the fidelity domain of this problem.
+    pub fn fidelity(mut self, var: VariableBuilder) -> Self {
+        self.fidelities.push(var);
+        self
+    }
+
     /// Sets the evaluable steps of this problem.
     pub fn steps<I>(mut self, steps: I) -> Self
     where
@@ -82,8 +103,14 @@ impl ProblemSpecBuilder {
     pub fn finish(self) -> Result<ProblemSpec> {
         track!(self.validate())?;
 
-        let params_domain = track!(Domain::new(self.params))?;
+        let params = track!(Domain::group(self.params, &self.groups))?;
+        let params_domain = track!(Domain::new(params))?;
         let values_domain = track!(Domain::new(self.values))?;
+        let fidelity_domain = if self.fidelities.is_empty() {
+            None
+        } else {
+            Some(track!(Domain::new(self.fidelities))?)
+        };
         let steps = track!(EvaluableSteps::new(self.steps))?;
 
         Ok(ProblemSpec {
@@ -91,6 +118,7 @@ impl ProblemSpecBuilder {
             attrs: self.attrs,
             params_domain,
             values_domain,
+            fidelity_domain,
             steps,
             reference_point: self.reference_point,
         })
@@ -113,6 +141,7 @@ impl ProblemSpecBuilder {
 
 /// Problem specification.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ProblemSpec {
     /// Problem name.
     pub name: String,
@@ -127,6 +156,13 @@ pub struct ProblemSpec {
     /// Domain of the objective values.
     pub values_domain: Domain,
 
+    /// Domain of the fidelity (resource) variables (e.g., the number of epochs or the dataset fraction).
+    ///
+    /// Solvers can choose the fidelity values via `NextTrial::fidelity`.
+    /// If they are omitted, the parameters are evaluated at the highest fidelity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fidelity_domain: Option<Domain>,
+
     /// List of steps.
     ///
     /// This problem can evaluate a given parameter set at a step in this list.
@@ -145,31 +181,168 @@ impl ProblemSpec {
             c.add_capability(Capability::MultiObjective);
         }
 
+        if self.steps.iter().count() > 1 {
+            c.add_capability(Capability::MultiStep);
+        }
+
         for v in self.params_domain.variables() {
-            if v.constraint().is_some() {
-                c.add_capability(Capability::Conditional);
+            for r in v.requirements().iter() {
+                c.add_capability(r);
             }
+        }
 
-            match (v.range(), v.distribution()) {
-                (Range::Continuous { .. }, Distribution::Uniform) => {
-                    c.add_capability(Capability::UniformContinuous);
-                }
-                (Range::Continuous { .. }, Distribution::LogUniform) => {
-                    c.add_capability(Capability::LogUniformContinuous);
-                }
-                (Range::Discrete { .. }, Distribution::Uniform) => {
-                    c.add_capability(Capability::UniformDiscrete);
-                }
-                (Range::Discrete { .. }, Distribution::LogUniform) => {
-                    c.add_capability(Capability::LogUniformDiscrete);
-                }
-                (Range::Categorical { .. }, _) => {
-                    c.add_capability(Capability::Categorical);
+        c
+    }
+
+    /// Checks whether the given solver has all the capabilities required by this problem.
+    ///
+    /// If not, this returns an `ErrorKind::Incapable` error.
+    /// The cause of the error is an `IncapableError` that holds the missing capabilities,
+    /// and its message describes which variables require them.
+    pub fn check_capabilities(&self, solver: &SolverSpec) -> Result<()> {
+        let missing = Capabilities::new(solver.capabilities.incapables(&self.requirements()));
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let reasons = missing
+            .iter()
+            .map(|c| {
+                let vars = self
+                    .params_domain
+                    .variables()
+                    .iter()
+                    .filter(|v| v.requirements().is_capable(c))
+                    .map(|v| format!("'{}'", v.name()))
+                    .collect::<Vec<_>>();
+                match vars.len() {
+                    0 if c == Capability::MultiObjective => {
+                        format!("{} required by {} objectives", c, self.values_domain.len())
+                    }
+                    0 if c == Capability::MultiStep => format!(
+                        "{} required by {} evaluable steps",
+                        c,
+                        self.steps.iter().count()
+                    ),
+                    0 => c.to_string(),
+                    1 => format!("{} required by variable {}", c, vars[0]),
+                    _ => format!("{} required by variables {}", c, vars.join(", ")),
                 }
+            })
+            .collect::<Vec<_>>();
+        let message = format!(
+            "solver '{}' lacks {} of problem '{}'",
+            solver.name,
+            reasons.join(", "),
+            self.name
+        );
+        let e: Error = ErrorKind::Incapable
+            .cause(IncapableError::new(missing, message))
+            .into();
+        Err(track!(e))
+    }
+
+    /// Checks whether the given parameters are valid for this problem.
+    ///
+    /// This checks the number of the parameters, the range (and the integrality) of each parameter,
+    /// and the consistency with the activation conditions
+    /// (i.e., inactive parameters must be `NaN` and active ones must not be).
+    pub fn validate_params(&self, params: &Params) -> Result<()> {
+        let vars = self.params_domain.variables();
+        track_assert_eq!(
+            params.len(),
+            vars.len(),
+            ErrorKind::InvalidInput,
+            "Wrong number of parameters"
+        );
+
+        for (i, (var, &val)) in vars.iter().zip(params.iter()).enumerate() {
+            let active = if let Some(condition) = var.condition() {
+                condition.is_satisfied(vars, &params[..i])
+            } else {
+                true
+            };
+            if !active {
+                track_assert!(
+                    val.is_nan(),
+                    ErrorKind::InvalidInput,
+                    "Inactive parameter {:?} must be NaN: value={}",
+                    var.name(),
+                    val
+                );
+                continue;
+            }
+
+            if val.is_nan() && var.constraint().is_some() {
+                continue;
+            }
+            track_assert!(
+                !val.is_nan(),
+                ErrorKind::InvalidInput,
+                "Active parameter {:?} must not be NaN",
+                var.name()
+            );
+            if let Range::Discrete { .. } | Range::Categorical { .. } | Range::Ordinal { .. } =
+                var.range()
+            {
+                track_assert_eq!(
+                    val.fract(),
+                    0.0,
+                    ErrorKind::InvalidInput,
+                    "Parameter {:?} must be an integer: value={}",
+                    var.name(),
+                    val
+                );
             }
+            // The upper bound of a continuous range is tolerated because some solvers
+            // (e.g., Optuna) regard it as inclusive and rounding errors can produce it.
+            let contained = match var.range() {
+                Range::Continuous { high, .. } if val == *high => true,
+                range => range.contains(val),
+            };
+            track_assert!(
+                contained,
+                ErrorKind::InvalidInput,
+                "Parameter {:?} is out of range: value={}, range={:?}",
+                var.name(),
+                val,
+                var.range()
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks whether the given fidelity values are valid for this problem.
+    ///
+    /// Empty values are always valid because they mean the highest fidelity.
+    pub fn validate_fidelity(&self, fidelity: &Params) -> Result<()> {
+        if fidelity.is_empty() {
+            return Ok(());
         }
 
-        c
+        let domain = track_assert_some!(
+            self.fidelity_domain.as_ref(),
+            ErrorKind::InvalidInput,
+            "Problem {:?} has no fidelity domain",
+            self.name
+        );
+        track_assert_eq!(
+            fidelity.len(),
+            domain.len(),
+            ErrorKind::InvalidInput,
+            "Wrong number of fidelity values"
+        );
+        for (var, &val) in domain.variables().iter().zip(fidelity.iter()) {
+            track_assert!(
+                var.range().contains(val),
+                ErrorKind::InvalidInput,
+                "Fidelity {:?} is out of range: value={}, range={:?}",
+                var.name(),
+                val,
+                var.range()
+            );
+        }
+        Ok(())
     }
 }
 
@@ -295,11 +468,46 @@ pub trait Evaluator: Send {
     /// Although it's desirable that the current step matches to `next_step`,
     /// it's allowed to exceed `next_step`.
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)>;
+
+    /// Procedes the evaluation at the given fidelity.
+    ///
+    /// The order of `fidelity` follows the variables of `ProblemSpec::fidelity_domain`.
+    /// If `fidelity` is empty, the evaluation is conducted at the highest fidelity.
+    ///
+    /// The default implementation ignores `fidelity` and just calls `evaluate` method.
+    /// Evaluators of problems that have a fidelity domain need to override this.
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        let _ = fidelity;
+        self.evaluate(next_step)
+    }
+
+    /// Takes the user attributes (e.g., auxiliary diagnostics) reported by the last evaluation.
+    ///
+    /// The default implementation returns an empty map.
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        BTreeMap::new()
+    }
 }
 impl<T: Evaluator + ?Sized> Evaluator for Box<T> {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
         (**self).evaluate(next_step)
     }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        (**self).evaluate_with_fidelity(next_step, fidelity)
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        (**self).take_attrs()
+    }
 }
 
 /// Boxed evaluator.
@@ -317,6 +525,18 @@ impl Evaluator for BoxEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
         self.0.evaluate(next_step)
     }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        self.0.evaluate_with_fidelity(next_step, fidelity)
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        self.0.take_attrs()
+    }
 }
 impl fmt::Debug for BoxEvaluator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -326,6 +546,7 @@ impl fmt::Debug for BoxEvaluator {
 
 /// Evaluable steps of a problem.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct EvaluableSteps(EvaluableStepsInner);
 impl EvaluableSteps {
     /// Makes a new `EvaluableSteps` instance.
@@ -345,6 +566,7 @@ impl EvaluableSteps {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 enum EvaluableStepsInner {
     Max(u64),
@@ -380,3 +602,114 @@ impl EvaluableStepsInner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{var, Condition};
+    use crate::solver::SolverSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    fn spec() -> Result<ProblemSpec> {
+        ProblemSpecBuilder::new("test")
+            .param(var("a").continuous(0.0, 1.0))
+            .param(var("b").discrete(0, 10))
+            .param(var("c").categorical(["foo", "bar"]))
+            .param(var("d").continuous(0.0, 1.0).condition(Condition::Eq {
+                target: "c".to_owned(),
+                value: 1.0,
+            }))
+            .value(var("v"))
+            .finish()
+    }
+
+    #[test]
+    fn validate_params_works() -> TopLevelResult {
+        let spec = track!(spec())?;
+        track!(spec.validate_params(&Params::new(vec![0.5, 3.0, 1.0, 0.2])))?;
+        track!(spec.validate_params(&Params::new(vec![0.5, 3.0, 0.0, f64::NAN])))?;
+
+        // Wrong length.
+        assert!(spec
+            .validate_params(&Params::new(vec![0.5, 3.0, 1.0]))
+            .is_err());
+
+        // Out of range.
+        assert!(spec
+            .validate_params(&Params::new(vec![1.5, 3.0, 1.0, 0.2]))
+            .is_err());
+        assert!(spec
+            .validate_params(&Params::new(vec![0.5, 3.0, 2.0, 0.2]))
+            .is_err());
+
+        // Not integer.
+        assert!(spec
+            .validate_params(&Params::new(vec![0.5, 3.5, 1.0, 0.2]))
+            .is_err());
+
+        // Inconsistent with the condition.
+        assert!(spec
+            .validate_params(&Params::new(vec![0.5, 3.0, 0.0, 0.2]))
+            .is_err());
+        assert!(spec
+            .validate_params(&Params::new(vec![0.5, 3.0, 1.0, f64::NAN]))
+            .is_err());
+        assert!(spec
+            .validate_params(&Params::new(vec![f64::NAN, 3.0, 1.0, 0.2]))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn check_capabilities_works() -> TopLevelResult {
+        let spec = track!(spec())?;
+        let solver = SolverSpecBuilder::new("all")
+            .capabilities(Capabilities::all())
+            .finish();
+        track!(spec.check_capabilities(&solver))?;
+
+        let solver = SolverSpecBuilder::new("foo")
+            .capable(Capability::UniformContinuous)
+            .capable(Capability::UniformDiscrete)
+            .finish();
+        let e = spec.check_capabilities(&solver).expect_err("should fail");
+        assert_eq!(*e.kind(), ErrorKind::Incapable);
+
+        let cause = e
+            .concrete_cause::<IncapableError>()
+            .expect("should have the cause");
+        assert!(cause.missing().is_capable(Capability::Categorical));
+        assert!(cause.missing().is_capable(Capability::Conditional));
+        assert_eq!(
+            cause.to_string(),
+            "solver 'foo' lacks CATEGORICAL required by variable 'c', \
+             CONDITIONAL required by variable 'd' of problem 'test'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_step_requirement_works() -> TopLevelResult {
+        let spec = track!(spec())?;
+        assert!(!spec.requirements().is_capable(Capability::MultiStep));
+
+        let spec = track!(ProblemSpecBuilder::new("test")
+            .param(var("a").continuous(0.0, 1.0))
+            .value(var("v"))
+            .steps(1..=10)
+            .finish())?;
+        assert!(spec.requirements().is_capable(Capability::MultiStep));
+
+        let solver = SolverSpecBuilder::new("foo")
+            .capable(Capability::UniformContinuous)
+            .finish();
+        let e = spec.check_capabilities(&solver).expect_err("should fail");
+        assert_eq!(
+            e.concrete_cause::<IncapableError>()
+                .expect("should have the cause")
+                .to_string(),
+            "solver 'foo' lacks MULTI_STEP required by 10 evaluable steps of problem 'test'"
+        );
+        Ok(())
+    }
+}
@@ -1,19 +1,34 @@
 //! Registry of problem and solver factories.
 // FIXME: Rename this module and structs.
 use crate::json::JsonRecipe;
-use crate::problem::{BoxProblemFactory, ProblemRecipe};
-use crate::solver::{BoxSolverFactory, SolverRecipe};
+use crate::problem::{BoxProblem, BoxProblemFactory, ProblemFactory, ProblemRecipe, ProblemSpec};
+use crate::rng::ArcRng;
+use crate::solver::{BoxSolver, BoxSolverFactory, SolverFactory, SolverRecipe, SolverSpec};
 use crate::{Error, Result};
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::{Arc, Mutex};
 
 type CreateProblemFactory =
-    dyn Fn(&JsonRecipe, &FactoryRegistry) -> Result<BoxProblemFactory> + Send;
-type CreateSolverFactory = dyn Fn(&JsonRecipe, &FactoryRegistry) -> Result<BoxSolverFactory> + Send;
+    dyn Fn(&JsonRecipe, &FactoryRegistry) -> Result<BoxProblemFactory> + Send + Sync;
+type CreateSolverFactory =
+    dyn Fn(&JsonRecipe, &FactoryRegistry) -> Result<BoxSolverFactory> + Send + Sync;
+
+/// A cache entry that is filled by the first caller that creates the factory.
+type Slot<T> = Arc<Mutex<Option<Arc<Mutex<T>>>>>;
 
 /// Factory registry.
+///
+/// The factories created by this registry are cached and keyed by their canonical recipe JSONs,
+/// so identical recipes share the same factory instance.
+/// Each recipe is built exactly once even if it is requested from multiple threads at the same time.
+/// The cached factories (and the external processes owned by them) are dropped
+/// when this registry is dropped unless they are still referenced from elsewhere.
 pub struct FactoryRegistry {
     create_problem_factory: Box<CreateProblemFactory>,
     create_solver_factory: Box<CreateSolverFactory>,
+    problem_factories: Mutex<HashMap<String, Slot<BoxProblemFactory>>>,
+    solver_factories: Mutex<HashMap<String, Slot<BoxSolverFactory>>>,
 }
 impl FactoryRegistry {
     /// Makes a new `FactoryRegistry` instance.
@@ -35,17 +50,49 @@ impl FactoryRegistry {
         Self {
             create_problem_factory,
             create_solver_factory,
+            problem_factories: Mutex::new(HashMap::new()),
+            solver_factories: Mutex::new(HashMap::new()),
         }
     }
 
     /// Creates a problem factory associated with the given recipe JSON.
+    ///
+    /// If a factory for the same recipe has already been created, it is shared.
     pub fn create_problem_factory_from_json(&self, json: &JsonRecipe) -> Result<BoxProblemFactory> {
-        track!((self.create_problem_factory)(json, self); json)
+        let factory = track!(get_or_create(&self.problem_factories, json, || {
+            track!((self.create_problem_factory)(json, self); json)
+        }))?;
+        Ok(BoxProblemFactory::new(SharedProblemFactory(factory)))
     }
 
     /// Creates a solver factory associated with the given recipe JSON.
+    ///
+    /// If a factory for the same recipe has already been created, it is shared.
     pub fn create_solver_factory_from_json(&self, json: &JsonRecipe) -> Result<BoxSolverFactory> {
-        track!((self.create_solver_factory)(json, self); json)
+        let factory = track!(get_or_create(&self.solver_factories, json, || {
+            track!((self.create_solver_factory)(json, self); json)
+        }))?;
+        Ok(BoxSolverFactory::new(SharedSolverFactory(factory)))
+    }
+
+    /// Removes the cached factories associated with the given recipe JSON.
+    ///
+    /// Subsequent calls of `create_*_factory_from_json` with the recipe create new factories.
+    pub fn invalidate(&self, json: &JsonRecipe) -> Result<()> {
+        let key = json.to_string();
+        track!(self.problem_factories.lock().map_err(Error::from))?.remove(&key);
+        track!(self.solver_factories.lock().map_err(Error::from))?.remove(&key);
+        Ok(())
+    }
+
+    /// Drops all the cached factories.
+    ///
+    /// External processes owned by the factories are terminated
+    /// unless the factories are still referenced from elsewhere.
+    pub fn shutdown(&self) -> Result<()> {
+        track!(self.problem_factories.lock().map_err(Error::from))?.clear();
+        track!(self.solver_factories.lock().map_err(Error::from))?.clear();
+        Ok(())
     }
 }
 impl fmt::Debug for FactoryRegistry {
@@ -53,3 +100,226 @@ impl fmt::Debug for FactoryRegistry {
         write!(f, "FactoryRegistry {{ .. }}")
     }
 }
+
+fn get_or_create<T, F>(
+    slots: &Mutex<HashMap<String, Slot<T>>>,
+    json: &JsonRecipe,
+    create: F,
+) -> Result<Arc<Mutex<T>>>
+where
+    F: FnOnce() -> Result<T>,
+{
+    // Only the slot of the recipe is locked while creating the factory,
+    // because the recipe may create nested factories (with different recipes).
+    let slot = Arc::clone(
+        track!(slots.lock().map_err(Error::from))?
+            .entry(json.to_string())
+            .or_default(),
+    );
+    let mut slot = track!(slot.lock().map_err(Error::from))?;
+    if let Some(factory) = &*slot {
+        return Ok(Arc::clone(factory));
+    }
+
+    // If the creation fails, the slot is left empty and the next caller retries it.
+    let factory = Arc::new(Mutex::new(track!(create())?));
+    *slot = Some(Arc::clone(&factory));
+    Ok(factory)
+}
+
+#[derive(Debug)]
+struct SharedProblemFactory(Arc<Mutex<BoxProblemFactory>>);
+impl ProblemFactory for SharedProblemFactory {
+    type Problem = BoxProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let factory = track!(self.0.lock().map_err(Error::from))?;
+        track!(factory.specification())
+    }
+
+    fn create_problem(&self, rng: ArcRng) -> Result<Self::Problem> {
+        let factory = track!(self.0.lock().map_err(Error::from))?;
+        track!(factory.create_problem(rng))
+    }
+}
+
+#[derive(Debug)]
+struct SharedSolverFactory(Arc<Mutex<BoxSolverFactory>>);
+impl SolverFactory for SharedSolverFactory {
+    type Solver = BoxSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let factory = track!(self.0.lock().map_err(Error::from))?;
+        track!(factory.specification())
+    }
+
+    fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let factory = track!(self.0.lock().map_err(Error::from))?;
+        track!(factory.create_solver(rng, problem))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::var;
+    use crate::problem::{Evaluator, Problem, ProblemSpecBuilder};
+    use crate::solver::{Solver, SolverSpecBuilder};
+    use crate::trial::{EvaluatedTrial, IdGen, NextTrial, Params, Values};
+    use serde::{Deserialize, Serialize};
+    use std::thread;
+    use std::time::Duration;
+    use structopt::StructOpt;
+    use trackable::result::TopLevelResult;
+
+    lazy_static::lazy_static! {
+        // The number of the created factories for each problem name.
+        static ref CREATED_FACTORIES: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+    }
+
+    fn created_factories(name: &str) -> usize {
+        let created = CREATED_FACTORIES.lock().unwrap_or_else(|e| panic!("{}", e));
+        created.get(name).copied().unwrap_or(0)
+    }
+
+    #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+    struct TestProblemRecipe {
+        #[structopt(long)]
+        name: String,
+    }
+    impl ProblemRecipe for TestProblemRecipe {
+        type Factory = TestProblemFactory;
+
+        fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+            // Widens the window for concurrent creations.
+            thread::sleep(Duration::from_millis(10));
+            *track!(CREATED_FACTORIES.lock().map_err(Error::from))?
+                .entry(self.name.clone())
+                .or_default() += 1;
+            Ok(TestProblemFactory(self.name.clone()))
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestProblemFactory(String);
+    impl ProblemFactory for TestProblemFactory {
+        type Problem = TestProblem;
+
+        fn specification(&self) -> Result<ProblemSpec> {
+            track!(ProblemSpecBuilder::new(&self.0)
+                .param(var("x").continuous(0.0, 1.0))
+                .value(var("y"))
+                .finish())
+        }
+
+        fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+            Ok(TestProblem)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestProblem;
+    impl Problem for TestProblem {
+        type Evaluator = TestEvaluator;
+
+        fn create_evaluator(&self, _params: Params) -> Result<Self::Evaluator> {
+            Ok(TestEvaluator)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestEvaluator;
+    impl Evaluator for TestEvaluator {
+        fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+            Ok((next_step, Values::new(vec![0.0])))
+        }
+    }
+
+    #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+    struct TestSolverRecipe {}
+    impl SolverRecipe for TestSolverRecipe {
+        type Factory = TestSolverFactory;
+
+        fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+            Ok(TestSolverFactory)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestSolverFactory;
+    impl SolverFactory for TestSolverFactory {
+        type Solver = TestSolver;
+
+        fn specification(&self) -> Result<SolverSpec> {
+            Ok(SolverSpecBuilder::new("test").finish())
+        }
+
+        fn create_solver(&self, _rng: ArcRng, _problem: &ProblemSpec) -> Result<Self::Solver> {
+            Ok(TestSolver)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestSolver;
+    impl Solver for TestSolver {
+        fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+            Ok(NextTrial {
+                id: idg.generate(),
+                params: Params::new(vec![0.5]),
+                next_step: Some(1),
+                fidelity: Params::new(Vec::new()),
+                asked_params: None,
+            })
+        }
+
+        fn tell(&mut self, _trial: EvaluatedTrial) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registry_caches_factories() -> TopLevelResult {
+        let registry = FactoryRegistry::new::<TestProblemRecipe, TestSolverRecipe>();
+        let recipe = serde_json::json!({"name": "foo"});
+
+        let f0 = track!(registry.create_problem_factory_from_json(&recipe))?;
+        let f1 = track!(registry.create_problem_factory_from_json(&recipe))?;
+        assert_eq!(created_factories("foo"), 1);
+        assert_eq!(track!(f0.specification())?, track!(f1.specification())?);
+
+        track!(registry.invalidate(&recipe))?;
+        track!(registry.create_problem_factory_from_json(&recipe))?;
+        assert_eq!(created_factories("foo"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn registry_creates_each_factory_once_under_contention() -> TopLevelResult {
+        let registry = FactoryRegistry::new::<TestProblemRecipe, TestSolverRecipe>();
+        let recipe = serde_json::json!({"name": "bar"});
+
+        let specs = thread::scope(|scope| {
+            let handles = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let factory = track!(registry.create_problem_factory_from_json(&recipe))?;
+                        track!(factory.specification())
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Result<Vec<_>>>()
+        });
+        let specs = track!(specs)?;
+        assert_eq!(specs.len(), 8);
+        assert_eq!(created_factories("bar"), 1);
+
+        let solver = track!(registry.create_solver_factory_from_json(&serde_json::json!({})))?;
+        let mut solver = track!(solver.create_solver(ArcRng::new(0), &specs[0]))?;
+        let trial = track!(solver.ask(&mut IdGen::new()))?;
+        assert_eq!(trial.params.get(), &[0.5][..]);
+        Ok(())
+    }
+}
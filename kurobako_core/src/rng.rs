@@ -2,13 +2,17 @@
 use crate::Result;
 use rand::rngs::StdRng;
 use rand::{Error, RngCore, SeedableRng};
+use std::f64::consts::PI;
 use std::sync::{Arc, Mutex};
 
 pub use rand::Rng;
 
 /// The random number generator for `kurobako`.
 #[derive(Debug, Clone)]
-pub struct ArcRng(Arc<Mutex<StdRng>>);
+pub struct ArcRng {
+    inner: Arc<Mutex<StdRng>>,
+    seed: u64,
+}
 impl ArcRng {
     /// Makes a new `ArcRng` with the given random seed.
     pub fn new(seed: u64) -> Self {
@@ -16,7 +20,25 @@ impl ArcRng {
         seed256[0..8].copy_from_slice(&seed.to_be_bytes());
 
         let inner = StdRng::from_seed(seed256);
-        Self(Arc::new(Mutex::new(inner)))
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+            seed,
+        }
+    }
+
+    /// Returns the seed of this instance.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Makes a child RNG associated with the given stream identifier.
+    ///
+    /// The resulting RNG only depends on the seed of this instance and `stream_id`
+    /// (i.e., it is independent of how many random numbers have been generated by this instance).
+    /// Hence, it can be used to give deterministic RNGs to components running concurrently.
+    pub fn split(&self, stream_id: u64) -> Self {
+        let seed = splitmix64(self.seed ^ splitmix64(stream_id.wrapping_add(1)));
+        Self::new(seed)
     }
 
     /// Acquires the lock of this instance and invokes `f` with the internal RNG.
@@ -24,30 +46,94 @@ impl ArcRng {
     where
         F: FnOnce(&mut StdRng) -> T,
     {
-        let mut rng = track!(self.0.lock().map_err(crate::Error::from))?;
+        let mut rng = track!(self.inner.lock().map_err(crate::Error::from))?;
         Ok(f(&mut rng))
     }
 }
 impl RngCore for ArcRng {
     fn next_u32(&mut self) -> u32 {
-        self.0.lock().unwrap_or_else(|e| panic!("{}", e)).next_u32()
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| panic!("{}", e))
+            .next_u32()
     }
 
     fn next_u64(&mut self) -> u64 {
-        self.0.lock().unwrap_or_else(|e| panic!("{}", e)).next_u64()
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| panic!("{}", e))
+            .next_u64()
     }
 
     fn fill_bytes(&mut self, dest: &mut [u8]) {
-        self.0
+        self.inner
             .lock()
             .unwrap_or_else(|e| panic!("{}", e))
             .fill_bytes(dest)
     }
 
     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
-        self.0
+        self.inner
             .lock()
             .unwrap_or_else(|e| panic!("{}", e))
             .try_fill_bytes(dest)
     }
 }
+
+/// Draws a sample from the standard normal distribution by using the Box-Muller transform.
+pub fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u0: f64 = 1.0 - rng.gen::<f64>();
+    let u1: f64 = rng.gen();
+    (-2.0 * u0.ln()).sqrt() * (2.0 * PI * u1).cos()
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(mut rng: ArcRng) -> Vec<u64> {
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn split_works() {
+        let mut rng = ArcRng::new(10);
+        for stream_id in 0..100 {
+            assert_eq!(sample(rng.split(stream_id)), sample(rng.split(stream_id)));
+            assert_ne!(
+                sample(rng.split(stream_id)),
+                sample(rng.split(stream_id + 1))
+            );
+            assert_ne!(sample(rng.split(stream_id)), sample(rng.clone()));
+        }
+
+        // Children don't depend on the state of their parent.
+        let child = sample(rng.split(3));
+        let _: u64 = rng.gen();
+        assert_eq!(child, sample(rng.split(3)));
+
+        // Children of different parents are different.
+        assert_ne!(child, sample(ArcRng::new(11).split(3)));
+    }
+
+    #[test]
+    fn standard_normal_works() {
+        let mut rng = ArcRng::new(0);
+        let xs = (0..10000)
+            .map(|_| standard_normal(&mut rng))
+            .collect::<Vec<_>>();
+        assert!(xs.iter().all(|x| x.is_finite()));
+
+        let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+        let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / xs.len() as f64;
+        assert!(mean.abs() < 0.05, "{}", mean);
+        assert!((var - 1.0).abs() < 0.05, "{}", var);
+    }
+}
@@ -9,7 +9,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use structopt::StructOpt;
 
-pub use self::capability::{Capabilities, Capability};
+pub use self::capability::{Capabilities, Capability, IncapableError};
 
 mod capability;
 
@@ -19,6 +19,7 @@ pub struct SolverSpecBuilder {
     name: String,
     attrs: BTreeMap<String, String>,
     capabilities: BTreeSet<Capability>,
+    max_params: Option<usize>,
 }
 impl SolverSpecBuilder {
     /// Makes a new `SolverSpecBuilder` instance.
@@ -27,6 +28,7 @@ impl SolverSpecBuilder {
             name: solver_name.to_owned(),
             attrs: BTreeMap::new(),
             capabilities: BTreeSet::new(),
+            max_params: None,
         }
     }
 
@@ -48,18 +50,26 @@ impl SolverSpecBuilder {
         self
     }
 
+    /// Sets the maximum number of parameters (i.e., dimensionality) that this solver supports.
+    pub fn max_params(mut self, n: usize) -> Self {
+        self.max_params = Some(n);
+        self
+    }
+
     /// Builds a `SolverSpec` instance with the given settings.
     pub fn finish(self) -> SolverSpec {
         SolverSpec {
             name: self.name,
             attrs: self.attrs,
             capabilities: Capabilities::new(self.capabilities.into_iter()),
+            max_params: self.max_params,
         }
     }
 }
 
 /// Solver specification.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SolverSpec {
     /// The name of this solver.
     pub name: String,
@@ -71,6 +81,10 @@ pub struct SolverSpec {
     /// The capability of this solver.
     #[serde(default)]
     pub capabilities: Capabilities,
+
+    /// The maximum number of parameters that this solver supports (`None` means unlimited).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_params: Option<usize>,
 }
 
 /// Recipe of a solver.
@@ -158,6 +172,16 @@ pub trait Solver: Send {
 
     /// Tells the evaluation result of a trial.
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()>;
+
+    /// Finalizes this solver at the end of a study.
+    ///
+    /// The returned key-value pairs are diagnostics of the solver (e.g., the number of model refits)
+    /// and are stored in the study record. They never affect the evaluation of the solver.
+    ///
+    /// The default implementation returns an empty map.
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        Ok(BTreeMap::new())
+    }
 }
 
 /// Boxed solver.
@@ -179,6 +203,10 @@ impl Solver for BoxSolver {
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
         track!(self.0.tell(trial))
     }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        track!(self.0.finalize())
+    }
 }
 impl fmt::Debug for BoxSolver {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
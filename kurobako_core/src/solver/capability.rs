@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
+use std::fmt;
 
 /// Solver capabilities.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Capabilities(BTreeSet<Capability>);
 impl Capabilities {
     /// Makes a `Capabilities` instance that has the given capabilities.
@@ -21,9 +23,12 @@ impl Capabilities {
             Capability::LogUniformContinuous,
             Capability::LogUniformDiscrete,
             Capability::Categorical,
+            Capability::Ordinal,
             Capability::Conditional,
             Capability::MultiObjective,
             Capability::Concurrent,
+            Capability::Finalize,
+            Capability::MultiStep,
         ]
         .iter()
         .copied()
@@ -71,6 +76,7 @@ impl Capabilities {
 
 /// Solver capability.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[allow(missing_docs)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Capability {
@@ -80,6 +86,11 @@ pub enum Capability {
     LogUniformDiscrete,
     Categorical,
 
+    /// Ordinal range.
+    ///
+    /// Solvers lacking this capability can handle ordinal parameters as discrete indices instead.
+    Ordinal,
+
     /// Conditional search space.
     ///
     /// If a problem has one or more constrainted parameters, the search space of the problem is conditional.
@@ -87,4 +98,60 @@ pub enum Capability {
 
     MultiObjective,
     Concurrent,
+
+    /// End-of-study diagnostics.
+    ///
+    /// External solvers having this capability are sent `FINALIZE_CALL` at the end of each study.
+    Finalize,
+
+    /// Multi-step evaluation (i.e., pruning).
+    ///
+    /// Solvers having this capability decide the steps at which trials are evaluated
+    /// (via `NextTrial::next_step`), so that they can stop unpromising trials before the last step.
+    /// Problems that have more than one evaluable step require this capability,
+    /// but it's only enforced if `kurobako run --require-multi-step` is specified.
+    MultiStep,
+}
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UniformContinuous => write!(f, "UNIFORM_CONTINUOUS"),
+            Self::UniformDiscrete => write!(f, "UNIFORM_DISCRETE"),
+            Self::LogUniformContinuous => write!(f, "LOG_UNIFORM_CONTINUOUS"),
+            Self::LogUniformDiscrete => write!(f, "LOG_UNIFORM_DISCRETE"),
+            Self::Categorical => write!(f, "CATEGORICAL"),
+            Self::Ordinal => write!(f, "ORDINAL"),
+            Self::Conditional => write!(f, "CONDITIONAL"),
+            Self::MultiObjective => write!(f, "MULTI_OBJECTIVE"),
+            Self::Concurrent => write!(f, "CONCURRENT"),
+            Self::Finalize => write!(f, "FINALIZE"),
+            Self::MultiStep => write!(f, "MULTI_STEP"),
+        }
+    }
+}
+
+/// The cause of an `ErrorKind::Incapable` error.
+///
+/// This holds the capabilities that a solver lacks.
+#[derive(Debug, Clone)]
+pub struct IncapableError {
+    missing: Capabilities,
+    message: String,
+}
+impl IncapableError {
+    /// Makes a new `IncapableError` instance.
+    pub fn new(missing: Capabilities, message: String) -> Self {
+        Self { missing, message }
+    }
+
+    /// Returns the capabilities that the solver lacks.
+    pub fn missing(&self) -> &Capabilities {
+        &self.missing
+    }
+}
+impl fmt::Display for IncapableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
+impl std::error::Error for IncapableError {}
@@ -0,0 +1,260 @@
+//! Helpers for computing basic statistics.
+use crate::num::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+/// Basic statistics of a set of values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasicStats {
+    /// The number of the values.
+    pub n: usize,
+
+    /// Mean.
+    pub mean: f64,
+
+    /// Standard deviation (population).
+    pub sd: f64,
+
+    /// Minimum value.
+    pub min: f64,
+
+    /// Maximum value.
+    pub max: f64,
+
+    /// Median.
+    pub median: f64,
+
+    /// Pairs of a requested quantile and its value.
+    pub quantiles: Vec<(f64, f64)>,
+}
+impl BasicStats {
+    /// Default quantiles computed in addition to the median.
+    pub const DEFAULT_QUANTILES: &'static [f64] = &[0.25, 0.75];
+
+    /// Computes the statistics of the given values.
+    ///
+    /// Quantiles are linearly interpolated between the closest ranks.
+    /// `NaN`s are ignored, and `None` is returned if there are no values.
+    pub fn new<I>(values: I, quantiles: &[f64]) -> Option<Self>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        let mut sorted = values
+            .into_iter()
+            .filter(|v| !v.is_nan())
+            .map(OrderedFloat)
+            .collect::<Vec<_>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort();
+        let sorted = sorted.into_iter().map(|v| v.0).collect::<Vec<_>>();
+
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let sd = (sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64).sqrt();
+        Some(Self {
+            n,
+            mean,
+            sd,
+            min: sorted[0],
+            max: sorted[n - 1],
+            median: quantile(&sorted, 0.5),
+            quantiles: quantiles
+                .iter()
+                .map(|&q| (q, quantile(&sorted, q)))
+                .collect(),
+        })
+    }
+
+    /// Returns the value of the given quantile if it has been computed.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.quantiles
+            .iter()
+            .find(|x| (x.0 - q).abs() < 1e-12)
+            .map(|x| x.1)
+    }
+}
+
+/// Outcomes of the paired comparisons between two competitors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeadToHead {
+    /// The number of the pairs won by the first competitor.
+    pub wins: usize,
+
+    /// The number of the pairs lost by the first competitor.
+    pub losses: usize,
+
+    /// The number of the tied pairs.
+    pub ties: usize,
+}
+impl HeadToHead {
+    /// Compares the paired results of two competitors (lower values are better).
+    ///
+    /// Each result is a pair of a random seed and a value.
+    /// Results having the same seed are paired first, and the remaining ones are
+    /// paired in the order of their values (i.e., the best with the best).
+    /// Missing values (`None` or `NaN`) lose against any value and tie with each other.
+    /// Results left unpaired (if the numbers of the results differ) are ignored.
+    pub fn compare(a: &[(u64, Option<f64>)], b: &[(u64, Option<f64>)]) -> Self {
+        let mut unpaired_b = BTreeMap::<_, Vec<_>>::new();
+        for &(seed, v) in b {
+            unpaired_b.entry(seed).or_default().push(v);
+        }
+
+        let mut this = Self::default();
+        let mut unpaired_a = Vec::new();
+        for &(seed, v) in a {
+            if let Some(w) = unpaired_b.get_mut(&seed).and_then(|w| w.pop()) {
+                this.add(v, w);
+            } else {
+                unpaired_a.push(v);
+            }
+        }
+
+        let mut unpaired_b = unpaired_b.into_iter().flat_map(|x| x.1).collect::<Vec<_>>();
+        unpaired_a.sort_by_key(|&v| sort_key(v));
+        unpaired_b.sort_by_key(|&v| sort_key(v));
+        for (v, w) in unpaired_a.into_iter().zip(unpaired_b) {
+            this.add(v, w);
+        }
+        this
+    }
+
+    /// Returns the number of the compared pairs.
+    pub fn pairs(&self) -> usize {
+        self.wins + self.losses + self.ties
+    }
+
+    /// Returns the fraction of the pairs won by the first competitor (ties count as half a win).
+    ///
+    /// `None` is returned if there are no pairs.
+    pub fn win_rate(&self) -> Option<f64> {
+        if self.pairs() == 0 {
+            None
+        } else {
+            Some((self.wins as f64 + self.ties as f64 * 0.5) / self.pairs() as f64)
+        }
+    }
+
+    /// Returns the outcomes from the viewpoint of the second competitor.
+    pub fn reversed(&self) -> Self {
+        Self {
+            wins: self.losses,
+            losses: self.wins,
+            ties: self.ties,
+        }
+    }
+
+    fn add(&mut self, a: Option<f64>, b: Option<f64>) {
+        match sort_key(a).cmp(&sort_key(b)) {
+            Ordering::Less => self.wins += 1,
+            Ordering::Greater => self.losses += 1,
+            Ordering::Equal => self.ties += 1,
+        }
+    }
+}
+impl AddAssign for HeadToHead {
+    fn add_assign(&mut self, other: Self) {
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.ties += other.ties;
+    }
+}
+
+fn sort_key(v: Option<f64>) -> (bool, OrderedFloat<f64>) {
+    match v.filter(|v| !v.is_nan()) {
+        Some(v) => (false, OrderedFloat(v)),
+        None => (true, OrderedFloat(0.0)),
+    }
+}
+
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_value_works() {
+        let stats = BasicStats::new(vec![3.0], BasicStats::DEFAULT_QUANTILES).unwrap();
+        assert_eq!(stats.n, 1);
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.sd, 0.0);
+        assert_eq!(stats.min, 3.0);
+        assert_eq!(stats.max, 3.0);
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.quantile(0.25), Some(3.0));
+        assert_eq!(stats.quantile(0.75), Some(3.0));
+
+        assert_eq!(BasicStats::new(vec![], &[]), None);
+        assert_eq!(BasicStats::new(vec![f64::NAN], &[]), None);
+    }
+
+    #[test]
+    fn odd_number_of_values_works() {
+        let stats =
+            BasicStats::new(vec![5.0, 1.0, 4.0, 2.0, 3.0], BasicStats::DEFAULT_QUANTILES).unwrap();
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.sd, 2.0f64.sqrt());
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 5.0);
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.quantile(0.25), Some(2.0));
+        assert_eq!(stats.quantile(0.75), Some(4.0));
+        assert_eq!(stats.quantile(0.9), None);
+    }
+
+    #[test]
+    fn even_number_of_values_works() {
+        let stats = BasicStats::new(vec![4.0, 1.0, 3.0, 2.0], &[0.25, 0.5]).unwrap();
+        assert_eq!(stats.median, 2.5);
+        assert_eq!(stats.quantile(0.25), Some(1.75));
+        assert_eq!(stats.quantile(0.5), Some(2.5));
+    }
+
+    #[test]
+    fn head_to_head_works() {
+        // Results with the same seeds are paired.
+        let a = [(0, Some(1.0)), (1, Some(5.0)), (2, Some(3.0))];
+        let b = [(2, Some(2.0)), (1, Some(4.0)), (0, Some(1.0))];
+        let h = HeadToHead::compare(&a, &b);
+        assert_eq!(
+            h,
+            HeadToHead {
+                wins: 0,
+                losses: 2,
+                ties: 1
+            }
+        );
+        assert_eq!(h.win_rate(), Some(0.5 / 3.0));
+        assert_eq!(HeadToHead::compare(&b, &a), h.reversed());
+
+        // The others are paired in the order of their values.
+        let a = [(0, Some(1.0)), (1, None), (2, Some(3.0))];
+        let b = [
+            (3, Some(2.0)),
+            (4, Some(0.0)),
+            (5, Some(f64::NAN)),
+            (6, None),
+        ];
+        let h = HeadToHead::compare(&a, &b);
+        assert_eq!(
+            h,
+            HeadToHead {
+                wins: 0,
+                losses: 2,
+                ties: 1
+            }
+        );
+
+        assert_eq!(HeadToHead::compare(&a, &[]).win_rate(), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Time related components.
+//!
+//! Durations within a study are always measured by the monotonic clock (`Instant`),
+//! so they are not affected by adjustments of the system clock (e.g., NTP steps).
+//! Absolute times are derived from a wall-clock anchor captured once per study (see [`StudyClock`]).
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Datetime.
+pub type DateTime = chrono::DateTime<chrono::Local>;
+
+/// Elapsed seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ElapsedSeconds(f64);
+impl ElapsedSeconds {
+    /// Upper bound of plausible elapsed seconds (one year).
+    ///
+    /// Larger values are regarded as corrupted measurements.
+    pub const MAX: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+    /// Makes a new `ElapsedSeconds` instance.
+    pub fn new(seconds: f64) -> Self {
+        Self(seconds)
+    }
+
+    /// Makes a new `ElapsedSeconds` instance clamped to the range `[0.0, ElapsedSeconds::MAX]`.
+    ///
+    /// `NaN` is converted to zero.
+    /// The second item of the result is `true` if the given value was out of the range.
+    pub fn clamped(seconds: f64) -> (Self, bool) {
+        if seconds.is_nan() || seconds < 0.0 {
+            (Self::zero(), true)
+        } else if seconds > Self::MAX {
+            (Self(Self::MAX), true)
+        } else {
+            (Self(seconds), false)
+        }
+    }
+
+    /// Makes a `ElapsedSeconds` instance that represents the zero elapsed seconds.
+    pub const fn zero() -> Self {
+        Self(0.0)
+    }
+
+    /// Returns the elapsed seconds value.
+    pub const fn get(self) -> f64 {
+        self.0
+    }
+
+    /// Converts the elapsed seconds to `Duration`.
+    ///
+    /// Negative and non-finite values are converted to zero.
+    pub fn to_duration(self) -> Duration {
+        Duration::try_from_secs_f64(self.0).unwrap_or_default()
+    }
+
+    /// Executes the given function, and returns the result and elapsed time.
+    pub fn time<F, T>(f: F) -> (T, Self)
+    where
+        F: FnOnce() -> T,
+    {
+        let now = Instant::now();
+        let result = f();
+        (result, Self::from(now.elapsed()))
+    }
+
+    /// Executes the given function that may fail, and returns the result and elapsed time.
+    pub fn try_time<F, T>(f: F) -> Result<(T, Self)>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        let now = Instant::now();
+        let value = f()?;
+        Ok((value, Self::from(now.elapsed())))
+    }
+}
+impl From<Duration> for ElapsedSeconds {
+    fn from(f: Duration) -> Self {
+        Self(f.as_secs_f64())
+    }
+}
+
+/// Clock of a study.
+///
+/// This pairs a wall-clock anchor with a monotonic instant captured at the same moment.
+/// Later absolute times are computed by adding the monotonic elapsed time to the anchor,
+/// so they never go backwards even if the system clock is changed during the study.
+#[derive(Debug, Clone, Copy)]
+pub struct StudyClock {
+    anchor: DateTime,
+    start: Instant,
+}
+impl StudyClock {
+    /// Makes a new `StudyClock` instance anchored at the current time.
+    pub fn start() -> Self {
+        Self::with_anchor(chrono::Local::now(), Instant::now())
+    }
+
+    /// Makes a new `StudyClock` instance that regards `start` as the instant of `anchor`.
+    pub fn with_anchor(anchor: DateTime, start: Instant) -> Self {
+        Self { anchor, start }
+    }
+
+    /// Returns the wall-clock time at which this clock was started.
+    pub fn anchor(&self) -> DateTime {
+        self.anchor
+    }
+
+    /// Returns the elapsed time since this clock was started.
+    pub fn elapsed(&self) -> ElapsedSeconds {
+        self.elapsed_at(Instant::now())
+    }
+
+    /// Returns the elapsed time from the start of this clock to the given instant.
+    ///
+    /// Instants before the start are regarded as the start itself.
+    pub fn elapsed_at(&self, instant: Instant) -> ElapsedSeconds {
+        ElapsedSeconds::from(instant.saturating_duration_since(self.start))
+    }
+
+    /// Returns the current absolute time derived from the monotonic clock.
+    pub fn now(&self) -> DateTime {
+        self.datetime_at(Instant::now())
+    }
+
+    /// Converts the given instant to an absolute time.
+    pub fn datetime_at(&self, instant: Instant) -> DateTime {
+        let elapsed = instant.saturating_duration_since(self.start);
+        let elapsed =
+            chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero());
+        self.anchor + elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn clamped_works() {
+        assert_eq!(
+            ElapsedSeconds::clamped(1.5),
+            (ElapsedSeconds::new(1.5), false)
+        );
+        assert_eq!(
+            ElapsedSeconds::clamped(0.0),
+            (ElapsedSeconds::zero(), false)
+        );
+        assert_eq!(
+            ElapsedSeconds::clamped(-0.1),
+            (ElapsedSeconds::zero(), true)
+        );
+        assert_eq!(
+            ElapsedSeconds::clamped(f64::NAN),
+            (ElapsedSeconds::zero(), true)
+        );
+        assert_eq!(
+            ElapsedSeconds::clamped(f64::INFINITY),
+            (ElapsedSeconds::new(ElapsedSeconds::MAX), true)
+        );
+        assert_eq!(ElapsedSeconds::new(-1.0).to_duration(), Duration::default());
+    }
+
+    #[test]
+    fn study_clock_conversion_works() {
+        let anchor = chrono::Local.timestamp_opt(1_600_000_000, 0).unwrap();
+        let start = Instant::now();
+        let clock = StudyClock::with_anchor(anchor, start);
+
+        let later = start + Duration::from_millis(2500);
+        assert_eq!(clock.elapsed_at(later), ElapsedSeconds::new(2.5));
+        assert_eq!(
+            clock.datetime_at(later),
+            anchor + chrono::Duration::milliseconds(2500)
+        );
+
+        // Instants before the start are never converted to negative durations.
+        if let Some(earlier) = start.checked_sub(Duration::from_secs(1)) {
+            assert_eq!(clock.elapsed_at(earlier), ElapsedSeconds::zero());
+            assert_eq!(clock.datetime_at(earlier), anchor);
+        }
+        assert!(clock.now() >= anchor);
+    }
+}
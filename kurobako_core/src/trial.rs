@@ -2,11 +2,13 @@
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
 /// Trial Identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrialId(u64);
 impl TrialId {
     /// Makes a new trial identifier.
@@ -22,6 +24,7 @@ impl TrialId {
 
 /// A trial that has a parameter set to be evaluated.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct NextTrial {
     /// The identifier of this trial.
     pub id: TrialId,
@@ -34,6 +37,19 @@ pub struct NextTrial {
     /// The evaluator needs to evaluate the parameters until this step reaches.
     /// If this is `None`, it means that this trial doesn't need to be evaluated anymore.
     pub next_step: Option<u64>,
+
+    /// The fidelity values at which the parameters are evaluated.
+    ///
+    /// The order of the values follows the variables of `ProblemSpec::fidelity_domain`.
+    /// If this is empty, the parameters are evaluated at the highest fidelity.
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    pub fidelity: Params,
+
+    /// The parameters asked by the solver if a filter perturbed `params` (see `ParamNoiseFilter`).
+    ///
+    /// This is only used to record trials and is never sent to solvers or problems.
+    #[serde(skip)]
+    pub asked_params: Option<Params>,
 }
 impl NextTrial {
     /// Makes an `EvaluatedTrial` instance with the given values and step.
@@ -42,17 +58,26 @@ impl NextTrial {
             id: self.id,
             values,
             current_step,
+            status: TrialStatus::Ok,
+            attrs: BTreeMap::new(),
         }
     }
 
     /// Makes an `EvaluatedTrial` instance that indicates this trial couldn't be evaluated.
     pub fn unevaluable(&self) -> EvaluatedTrial {
-        self.evaluated(Values::new(Vec::new()), self.next_step.unwrap_or(0))
+        EvaluatedTrial {
+            id: self.id,
+            values: Values::new(Vec::new()),
+            current_step: self.next_step.unwrap_or(0),
+            status: TrialStatus::Failed,
+            attrs: BTreeMap::new(),
+        }
     }
 }
 
 /// A trial that has an evaluated values.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct EvaluatedTrial {
     /// The identifier of this trial.
     pub id: TrialId,
@@ -64,6 +89,42 @@ pub struct EvaluatedTrial {
 
     /// The current evaluation step.
     pub current_step: u64,
+
+    /// The status of the evaluation.
+    ///
+    /// If this is `TrialStatus::Failed`, `values` is empty.
+    #[serde(default, skip_serializing_if = "TrialStatus::is_ok")]
+    pub status: TrialStatus,
+
+    /// The user attributes reported by the evaluator (e.g., auxiliary diagnostics).
+    ///
+    /// Solvers can ignore this.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: BTreeMap<String, serde_json::Value>,
+}
+
+/// Status of an evaluated trial.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TrialStatus {
+    /// The parameters have been evaluated successfully.
+    #[default]
+    Ok,
+
+    /// The evaluation of the parameters failed (e.g., the evaluator crashed in the region).
+    Failed,
+}
+impl TrialStatus {
+    /// Returns `true` if this is `TrialStatus::Ok`, otherwise `false`.
+    pub fn is_ok(&self) -> bool {
+        *self == TrialStatus::Ok
+    }
+
+    /// Returns `true` if this is `TrialStatus::Failed`, otherwise `false`.
+    pub fn is_failed(&self) -> bool {
+        *self == TrialStatus::Failed
+    }
 }
 
 /// Trial ID generator.
@@ -100,7 +161,12 @@ impl IdGen {
 /// Note that if a parameter is conditional and the condition didn't hold,
 /// the value of the parameter is set to NaN.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct Params(#[serde(with = "nullable_f64_vec")] Vec<f64>);
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Params(
+    #[serde(with = "nullable_f64_vec")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Vec<Option<f64>>"))]
+    Vec<f64>,
+);
 impl Params {
     /// Makes a new `Params` instance.
     pub const fn new(params: Vec<f64>) -> Self {
@@ -144,6 +210,7 @@ impl Deref for Params {
 
 /// Evaluated values (a.k.a. objective values).
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Values(Vec<f64>);
 impl Values {
     /// Makes a new `Values` instance.
@@ -224,3 +291,30 @@ mod nullable_f64_vec {
         v.serialize(serializer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Result};
+
+    #[test]
+    fn evaluated_trial_serde_works() -> Result<()> {
+        let json = r#"{"id":0,"values":[1.0],"current_step":1}"#;
+        let trial: EvaluatedTrial = track!(serde_json::from_str(json).map_err(Error::from))?;
+        assert_eq!(trial.status, TrialStatus::Ok);
+        assert!(trial.attrs.is_empty());
+        assert_eq!(
+            track!(serde_json::to_string(&trial).map_err(Error::from))?,
+            json
+        );
+
+        let mut trial = trial;
+        trial
+            .attrs
+            .insert("elapsed".to_owned(), serde_json::json!(1.5));
+        let json = track!(serde_json::to_string(&trial).map_err(Error::from))?;
+        let restored: EvaluatedTrial = track!(serde_json::from_str(&json).map_err(Error::from))?;
+        assert_eq!(restored.attrs, trial.attrs);
+        Ok(())
+    }
+}
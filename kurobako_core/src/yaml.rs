@@ -0,0 +1,98 @@
+//! YAML as an alternative input format (see [`json::Format`](crate::json::Format)).
+//!
+//! The loaded documents are converted to JSON values, so that they are handled in the same way as JSON inputs.
+use crate::json::JsonRecipe;
+use crate::{Error, Result};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::io::Read;
+
+/// Reads a single YAML document from the given reader.
+pub fn from_reader<R: Read>(reader: R) -> Result<JsonRecipe> {
+    track!(serde_yaml::from_reader(reader).map_err(Error::from))
+}
+
+/// Loads entries from the given YAML stream.
+///
+/// Each document of the stream is an entry, like each line of newline-delimited JSON.
+pub fn load<R, T>(reader: R) -> Result<Vec<T>>
+where
+    R: Read,
+    T: for<'a> Deserialize<'a>,
+{
+    let mut entries = Vec::new();
+    track!(load_lenient(reader, |entry| {
+        entries.push(track!(entry)?);
+        Ok(())
+    }))?;
+    Ok(entries)
+}
+
+/// Loads entries from the given YAML stream, tolerating malformed ones.
+///
+/// This is the YAML counterpart of [`json::load_lenient`](crate::json::load_lenient):
+/// `f` is called for every document in order with the parsed entry or the error.
+/// An entry that doesn't match `T` is passed to `f` as an error, and the loading continues.
+/// A syntax error ends the input, because the following documents can't be located reliably.
+///
+/// Empty documents (e.g., a trailing `---`) are ignored.
+pub fn load_lenient<R, T, F>(reader: R, mut f: F) -> Result<()>
+where
+    R: Read,
+    T: for<'a> Deserialize<'a>,
+    F: FnMut(Result<T>) -> Result<()>,
+{
+    for document in serde_yaml::Deserializer::from_reader(reader) {
+        let entry = match JsonValue::deserialize(document) {
+            Ok(JsonValue::Null) => continue,
+            Ok(entry) => entry,
+            Err(e) => return track!(f(Err(Error::from(e)))),
+        };
+        track!(f(serde_json::from_value(entry).map_err(Error::from)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use trackable::result::TopLevelResult;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Entry {
+        x: u32,
+    }
+
+    #[test]
+    fn load_works() -> TopLevelResult {
+        let yaml = "# A comment.\nx: 1\n---\nx: 2 # Another comment.\n---\n{x: 3}\n---\n";
+        let entries: Vec<Entry> = track!(load(yaml.as_bytes()))?;
+        assert_eq!(
+            entries,
+            vec![Entry { x: 1 }, Entry { x: 2 }, Entry { x: 3 }]
+        );
+
+        let json = "{\"x\": 1}\n{\"x\": 2}\n{\"x\": 3}\n";
+        let yaml_values: Vec<JsonValue> = track!(load(yaml.as_bytes()))?;
+        let json_values: Vec<JsonValue> = track!(json::load(json.as_bytes()))?;
+        assert_eq!(yaml_values, json_values);
+
+        // Entries that don't match the type are skipped, but a syntax error ends the input.
+        let mut entries = Vec::new();
+        let mut errors = 0;
+        track!(load_lenient(
+            "x: 1\n---\ny: 2\n---\nx: 3\n---\nx: [4\n---\nx: 5\n".as_bytes(),
+            |entry: Result<Entry>| {
+                match entry {
+                    Ok(entry) => entries.push(entry.x),
+                    Err(_) => errors += 1,
+                }
+                Ok(())
+            }
+        ))?;
+        assert_eq!(entries, vec![1, 3]);
+        assert_eq!(errors, 2);
+        Ok(())
+    }
+}
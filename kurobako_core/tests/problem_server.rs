@@ -0,0 +1,72 @@
+#[macro_use]
+extern crate trackable;
+
+use kurobako_core::epi::problem::{ExternalProgramProblemRecipe, Isolation};
+use kurobako_core::epi::solver::ExternalProgramSolverRecipe;
+use kurobako_core::problem::{Evaluator as _, Problem as _, ProblemFactory as _, ProblemRecipe};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::Params;
+use kurobako_core::ErrorKind;
+use std::path::PathBuf;
+use trackable::result::TopLevelResult;
+
+/// Returns the path of the `quadratic_problem` example (built by `cargo test` together with this test).
+fn example_path() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap_or_else(|e| panic!("{}", e));
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push("examples");
+    path.push(format!("quadratic_problem{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{:?} is not found (run `cargo build --examples`)",
+        path
+    );
+    path
+}
+
+#[test]
+fn quadratic_problem_server_works() -> TopLevelResult {
+    let recipe = ExternalProgramProblemRecipe {
+        path: example_path(),
+        args: Vec::new(),
+        timeout: Some(10),
+        shutdown_grace_period: None,
+        passthrough_stderr: false,
+        isolation: Isolation::Shared,
+        max_processes: None,
+    };
+    let registry =
+        FactoryRegistry::new::<ExternalProgramProblemRecipe, ExternalProgramSolverRecipe>();
+    let factory = track!(recipe.create_factory(&registry))?;
+
+    let spec = track!(factory.specification())?;
+    assert_eq!(spec.name, "Quadratic");
+    assert_eq!(spec.params_domain.variables().len(), 2);
+
+    let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+    let mut evaluator = track!(problem.create_evaluator(Params::new(vec![3.0, 1.0])))?;
+    let (step, values) = track!(evaluator.evaluate(1))?;
+    assert_eq!(step, 1);
+    assert_eq!(values.to_vec(), vec![5.0]);
+
+    // Errors are replied without terminating the server.
+    let e = problem
+        .create_evaluator(Params::new(vec![30.0, 1.0]))
+        .err()
+        .map(|e| *e.kind());
+    assert_eq!(e, Some(ErrorKind::UnevaluableParams));
+    let e = problem
+        .create_evaluator(Params::new(vec![1.0]))
+        .err()
+        .map(|e| *e.kind());
+    assert_eq!(e, Some(ErrorKind::InvalidInput));
+
+    let mut evaluator = track!(problem.create_evaluator(Params::new(vec![2.0, -1.0])))?;
+    let (_, values) = track!(evaluator.evaluate(1))?;
+    assert_eq!(values.to_vec(), vec![0.0]);
+    Ok(())
+}
@@ -0,0 +1,333 @@
+//! Standard test functions used in Bayesian optimization papers (Branin, Hartmann and Shekel).
+//!
+//! Each problem has a fixed dimension and the known global minimum is set to the `optimum` attribute of its spec.
+//!
+//! # References
+//!
+//! - [Virtual Library of Simulation Experiments: Optimization Test Problems](https://www.sfu.ca/~ssurjano/optimization.html)
+#![allow(clippy::unreadable_literal)]
+use kurobako_core::domain;
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use structopt::StructOpt;
+
+/// Recipe of `BoStandardProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct BoStandardProblemRecipe {
+    /// Test function name.
+    #[structopt(subcommand)]
+    pub function: BoStandardFunction,
+}
+impl ProblemRecipe for BoStandardProblemRecipe {
+    type Factory = BoStandardProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        Ok(BoStandardProblemFactory {
+            function: self.function,
+        })
+    }
+}
+
+/// Factory of `BoStandardProblem`.
+#[derive(Debug)]
+pub struct BoStandardProblemFactory {
+    function: BoStandardFunction,
+}
+impl ProblemFactory for BoStandardProblemFactory {
+    type Problem = BoStandardProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let mut spec = ProblemSpecBuilder::new(&format!("{:?}", self.function))
+            .attr(
+                "version",
+                &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("optimum", &self.function.optimum().to_string());
+        for (i, (low, high)) in self.function.bounds().into_iter().enumerate() {
+            spec = spec.param(domain::var(&format!("x{}", i + 1)).continuous_inclusive(low, high));
+        }
+        track!(spec.value(domain::var("Objective Value")).finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        Ok(BoStandardProblem {
+            function: self.function,
+        })
+    }
+}
+
+/// Problem that uses one of the standard test functions.
+#[derive(Debug)]
+pub struct BoStandardProblem {
+    function: BoStandardFunction,
+}
+impl Problem for BoStandardProblem {
+    type Evaluator = BoStandardEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        track_assert_eq!(
+            params.len(),
+            self.function.dimension(),
+            ErrorKind::InvalidInput
+        );
+        Ok(BoStandardEvaluator {
+            function: self.function,
+            params,
+        })
+    }
+}
+
+/// Evaluator of `BoStandardProblem`.
+#[derive(Debug)]
+pub struct BoStandardEvaluator {
+    function: BoStandardFunction,
+    params: Params,
+}
+impl Evaluator for BoStandardEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        track_assert_eq!(next_step, 1, ErrorKind::Bug);
+        let value = self.function.evaluate(self.params.get());
+        Ok((1, Values::new(vec![value])))
+    }
+}
+
+/// Standard test function.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, StructOpt, Serialize, Deserialize,
+)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BoStandardFunction {
+    /// Branin function (2-dimensional).
+    Branin,
+
+    /// Hartmann function (3-dimensional).
+    Hartmann3,
+
+    /// Hartmann function (6-dimensional).
+    Hartmann6,
+
+    /// Shekel function with `m=5` (4-dimensional).
+    Shekel5,
+
+    /// Shekel function with `m=7` (4-dimensional).
+    Shekel7,
+
+    /// Shekel function with `m=10` (4-dimensional).
+    Shekel10,
+}
+impl BoStandardFunction {
+    /// Returns all the functions.
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::Branin,
+            Self::Hartmann3,
+            Self::Hartmann6,
+            Self::Shekel5,
+            Self::Shekel7,
+            Self::Shekel10,
+        ]
+    }
+
+    /// Returns the dimension of this function.
+    pub fn dimension(self) -> usize {
+        match self {
+            Self::Branin => 2,
+            Self::Hartmann3 => 3,
+            Self::Hartmann6 => 6,
+            Self::Shekel5 | Self::Shekel7 | Self::Shekel10 => 4,
+        }
+    }
+
+    /// Returns the known global minimum of this function.
+    pub fn optimum(self) -> f64 {
+        match self {
+            Self::Branin => 0.397887357729739,
+            Self::Hartmann3 => -3.86278214782076,
+            Self::Hartmann6 => -3.32236801141551,
+            Self::Shekel5 => -10.1531996790582,
+            Self::Shekel7 => -10.4029405668187,
+            Self::Shekel10 => -10.5364098166920,
+        }
+    }
+
+    fn bounds(self) -> Vec<(f64, f64)> {
+        match self {
+            Self::Branin => vec![(-5.0, 10.0), (0.0, 15.0)],
+            Self::Hartmann3 | Self::Hartmann6 => vec![(0.0, 1.0); self.dimension()],
+            Self::Shekel5 | Self::Shekel7 | Self::Shekel10 => vec![(0.0, 10.0); 4],
+        }
+    }
+
+    fn evaluate(self, xs: &[f64]) -> f64 {
+        match self {
+            Self::Branin => branin(xs),
+            Self::Hartmann3 => hartmann3(xs),
+            Self::Hartmann6 => hartmann6(xs),
+            Self::Shekel5 => shekel(xs, 5),
+            Self::Shekel7 => shekel(xs, 7),
+            Self::Shekel10 => shekel(xs, 10),
+        }
+    }
+}
+
+fn branin(xs: &[f64]) -> f64 {
+    let (x1, x2) = (xs[0], xs[1]);
+    let b = 5.1 / (4.0 * PI * PI);
+    let c = 5.0 / PI;
+    let t = 1.0 / (8.0 * PI);
+    (x2 - b * x1 * x1 + c * x1 - 6.0).powi(2) + 10.0 * (1.0 - t) * x1.cos() + 10.0
+}
+
+const HARTMANN_ALPHA: [f64; 4] = [1.0, 1.2, 3.0, 3.2];
+
+fn hartmann<const N: usize>(xs: &[f64], a: &[[f64; N]; 4], p: &[[f64; N]; 4]) -> f64 {
+    -HARTMANN_ALPHA
+        .iter()
+        .zip(a.iter().zip(p.iter()))
+        .map(|(alpha, (a, p))| {
+            let d = (0..N).map(|j| a[j] * (xs[j] - p[j]).powi(2)).sum::<f64>();
+            alpha * (-d).exp()
+        })
+        .sum::<f64>()
+}
+
+fn hartmann3(xs: &[f64]) -> f64 {
+    let a = [
+        [3.0, 10.0, 30.0],
+        [0.1, 10.0, 35.0],
+        [3.0, 10.0, 30.0],
+        [0.1, 10.0, 35.0],
+    ];
+    let p = [
+        [0.3689, 0.1170, 0.2673],
+        [0.4699, 0.4387, 0.7470],
+        [0.1091, 0.8732, 0.5547],
+        [0.0381, 0.5743, 0.8828],
+    ];
+    hartmann(xs, &a, &p)
+}
+
+fn hartmann6(xs: &[f64]) -> f64 {
+    let a = [
+        [10.0, 3.0, 17.0, 3.5, 1.7, 8.0],
+        [0.05, 10.0, 17.0, 0.1, 8.0, 14.0],
+        [3.0, 3.5, 1.7, 10.0, 17.0, 8.0],
+        [17.0, 8.0, 0.05, 10.0, 0.1, 14.0],
+    ];
+    let p = [
+        [0.1312, 0.1696, 0.5569, 0.0124, 0.8283, 0.5886],
+        [0.2329, 0.4135, 0.8307, 0.3736, 0.1004, 0.9991],
+        [0.2348, 0.1451, 0.3522, 0.2883, 0.3047, 0.6650],
+        [0.4047, 0.8828, 0.8732, 0.5743, 0.1091, 0.0381],
+    ];
+    hartmann(xs, &a, &p)
+}
+
+fn shekel(xs: &[f64], m: usize) -> f64 {
+    let c = [0.1, 0.2, 0.2, 0.4, 0.4, 0.6, 0.3, 0.7, 0.5, 0.5];
+    let a = [
+        [4.0, 4.0, 4.0, 4.0],
+        [1.0, 1.0, 1.0, 1.0],
+        [8.0, 8.0, 8.0, 8.0],
+        [6.0, 6.0, 6.0, 6.0],
+        [3.0, 7.0, 3.0, 7.0],
+        [2.0, 9.0, 2.0, 9.0],
+        [5.0, 5.0, 3.0, 3.0],
+        [8.0, 1.0, 8.0, 1.0],
+        [6.0, 2.0, 6.0, 2.0],
+        [7.0, 3.6, 7.0, 3.6],
+    ];
+    -a.iter()
+        .zip(c.iter())
+        .take(m)
+        .map(|(a, c)| {
+            let d = xs
+                .iter()
+                .zip(a.iter())
+                .map(|(x, a)| (x - a).powi(2))
+                .sum::<f64>();
+            1.0 / (d + c)
+        })
+        .sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_optimum(f: BoStandardFunction, xs: &[f64]) {
+        let value = f.evaluate(xs);
+        assert!(
+            (value - f.optimum()).abs() < 1e-4,
+            "{:?}: {} != {}",
+            f,
+            value,
+            f.optimum()
+        );
+    }
+
+    #[test]
+    fn optima_work() {
+        assert_optimum(BoStandardFunction::Branin, &[-PI, 12.275]);
+        assert_optimum(BoStandardFunction::Branin, &[PI, 2.275]);
+        assert_optimum(BoStandardFunction::Branin, &[9.42478, 2.475]);
+        assert_optimum(
+            BoStandardFunction::Hartmann3,
+            &[0.114614, 0.555649, 0.852547],
+        );
+        assert_optimum(
+            BoStandardFunction::Hartmann6,
+            &[0.20169, 0.150011, 0.476874, 0.275332, 0.311652, 0.6573],
+        );
+        assert_optimum(
+            BoStandardFunction::Shekel5,
+            &[4.00003715, 4.00013327, 4.00003715, 4.00013327],
+        );
+        assert_optimum(
+            BoStandardFunction::Shekel7,
+            &[4.00057291, 4.00068972, 3.99948971, 3.99960616],
+        );
+        assert_optimum(
+            BoStandardFunction::Shekel10,
+            &[4.00074671, 4.00059326, 3.99966290, 3.99950981],
+        );
+    }
+
+    #[test]
+    fn values_work() {
+        use BoStandardFunction::*;
+
+        assert_eq!(Branin.evaluate(&[0.0, 0.0]), 55.602112642270264);
+        assert_eq!(Hartmann3.evaluate(&[0.12, 0.34, 0.56]), -0.5775714394889235);
+        assert_eq!(Hartmann6.evaluate(&[0.5; 6]), -0.5053149917022333);
+        assert_eq!(Shekel5.evaluate(&[1.0, 2.0, 3.0, 4.0]), -0.1936924709041272);
+        assert_eq!(Shekel7.evaluate(&[1.0, 2.0, 3.0, 4.0]), -0.2447701148795464);
+        assert_eq!(
+            Shekel10.evaluate(&[1.0, 2.0, 3.0, 4.0]),
+            -0.3006598969554929
+        );
+    }
+
+    #[test]
+    fn specs_work() -> trackable::result::TopLevelResult {
+        for &f in BoStandardFunction::all() {
+            let factory = BoStandardProblemFactory { function: f };
+            let spec = track!(factory.specification())?;
+            assert_eq!(spec.params_domain.variables().len(), f.dimension());
+            assert_eq!(
+                spec.attrs.get("optimum").map(|v| v.parse::<f64>().ok()),
+                Some(Some(f.optimum()))
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,547 @@
+//! A problem that executes an external command once per evaluation.
+//!
+//! Unlike the problems implemented by external programs (see `kurobako_core::epi`),
+//! the command doesn't need to speak the EPI protocol: the parameters are passed via its command line
+//! arguments (or environment variables), and the objective value is parsed from its standard output.
+//! As there is no handshake, the parameters are declared in the recipe.
+//!
+//! # Templates
+//!
+//! In an argument or environment variable template, `{NAME}` is replaced with the value of the parameter `NAME`
+//! (`{{` and `}}` are the escaped braces).
+//! The value of a categorical parameter is its choice, the value of a discrete one is an integer,
+//! and the value of an inactive conditional parameter is an empty string.
+//!
+//! # Failures
+//!
+//! If the command exits with a non-zero status, times out or doesn't output an objective value,
+//! the trial is regarded as failed (and a warning is shown with the last stderr lines of the exited command).
+//!
+//! # Examples
+//!
+//! ```json
+//! {
+//!   "path": "./train.sh",
+//!   "args": ["--lr={lr}", "--optimizer={optimizer}"],
+//!   "params": [
+//!     {"name": "lr", "type": "CONTINUOUS", "low": 0.0001, "high": 0.1, "log_uniform": true},
+//!     {"name": "optimizer", "type": "CATEGORICAL", "choices": ["sgd", "adam"]}
+//!   ],
+//!   "parse": {"json-pointer": "/metrics/loss"},
+//!   "timeout": 3600
+//! }
+//! ```
+use crate::param::ParamSpec;
+use kurobako_core::domain::{self, Range};
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use trackable::error::ErrorKindExt as _;
+
+/// The number of the last stderr lines of a failed command shown in a warning.
+const STDERR_TAIL_LINES: usize = 10;
+
+/// Recipe of `CommandProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct CommandProblemRecipe {
+    /// The path of the command.
+    pub path: PathBuf,
+
+    /// Templates of the command line arguments (e.g., `--lr={lr}`).
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Templates of the environment variables passed to the command (`KEY=TEMPLATE`).
+    ///
+    /// This option can be specified multiple times.
+    #[structopt(long = "env", number_of_values = 1)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub envs: Vec<String>,
+
+    /// Parameter of the problem (e.g., `{"name": "x", "type": "CONTINUOUS", "low": 0.0, "high": 1.0}`).
+    ///
+    /// This option can be specified multiple times.
+    #[structopt(long = "param", number_of_values = 1)]
+    pub params: Vec<ParamSpec>,
+
+    /// How to parse the objective value from the standard output of the command
+    /// (`last-float` or `json-pointer=POINTER`).
+    ///
+    /// `last-float` takes the last number in the output.
+    /// `json-pointer=POINTER` takes the number at `POINTER` (e.g., `/metrics/loss`) in the output JSON
+    /// (the whole output or its last non-empty line).
+    #[structopt(long, default_value = "last-float")]
+    #[serde(default)]
+    pub parse: OutputParser,
+
+    /// Timeout in seconds of an evaluation.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+impl ProblemRecipe for CommandProblemRecipe {
+    type Factory = CommandProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        let names = self
+            .params
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        let args = track!(self
+            .args
+            .iter()
+            .map(|a| Template::parse(a, &names))
+            .collect::<Result<Vec<_>>>())?;
+        let mut envs = Vec::new();
+        for env in &self.envs {
+            let mut tokens = env.splitn(2, '=');
+            let key = tokens.next().unwrap_or_else(|| unreachable!());
+            let template = track_assert_some!(
+                tokens.next(),
+                ErrorKind::InvalidInput,
+                "Expected `KEY=TEMPLATE`: {:?}",
+                env
+            );
+            track_assert!(!key.is_empty(), ErrorKind::InvalidInput; env);
+            envs.push((key.to_owned(), track!(Template::parse(template, &names))?));
+        }
+
+        Ok(CommandProblemFactory {
+            recipe: self.clone(),
+            command: Arc::new(CommandLine {
+                path: self.path.clone(),
+                args,
+                envs,
+                ranges: self.params.iter().map(|p| p.range.clone()).collect(),
+                parse: self.parse.clone(),
+                timeout: self.timeout.map(Duration::from_secs),
+            }),
+        })
+    }
+}
+
+/// How to parse the objective value from the output of a command.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputParser {
+    /// Takes the last number in the output.
+    #[default]
+    LastFloat,
+
+    /// Takes the number at the given JSON pointer in the output JSON.
+    JsonPointer(String),
+}
+impl OutputParser {
+    fn parse(&self, output: &str) -> Option<f64> {
+        match self {
+            Self::LastFloat => output
+                .rsplit(|c: char| c.is_whitespace() || ",;:=()[]{}\"'".contains(c))
+                .find_map(|token| token.parse::<f64>().ok()),
+            Self::JsonPointer(pointer) => {
+                let last_line = output.lines().rev().find(|l| !l.trim().is_empty());
+                std::iter::once(output)
+                    .chain(last_line)
+                    .filter_map(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+                    .find_map(|json| json.pointer(pointer).and_then(|v| v.as_f64()))
+            }
+        }
+    }
+}
+impl FromStr for OutputParser {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "last-float" {
+            Ok(Self::LastFloat)
+        } else if let Some(pointer) = s.strip_prefix("json-pointer=") {
+            Ok(Self::JsonPointer(pointer.to_owned()))
+        } else {
+            track_panic!(ErrorKind::InvalidInput, "Unknown output parser: {:?}", s);
+        }
+    }
+}
+impl fmt::Display for OutputParser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LastFloat => write!(f, "last-float"),
+            Self::JsonPointer(pointer) => write!(f, "json-pointer={}", pointer),
+        }
+    }
+}
+
+/// Factory of `CommandProblem`.
+#[derive(Debug)]
+pub struct CommandProblemFactory {
+    recipe: CommandProblemRecipe,
+    command: Arc<CommandLine>,
+}
+impl ProblemFactory for CommandProblemFactory {
+    type Problem = CommandProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let name = self
+            .recipe
+            .path
+            .file_name()
+            .unwrap_or_else(|| self.recipe.path.as_os_str())
+            .to_string_lossy();
+        let mut spec = ProblemSpecBuilder::new(&name)
+            .attr(
+                "version",
+                &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("args", &self.recipe.args.join(" "))
+            .attr("parse", &self.recipe.parse.to_string())
+            .value(domain::var("Objective"));
+        for p in &self.recipe.params {
+            spec = spec.param(p.to_variable());
+        }
+        track!(spec.finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        Ok(CommandProblem {
+            command: Arc::clone(&self.command),
+        })
+    }
+}
+
+/// Problem that executes an external command once per evaluation.
+#[derive(Debug)]
+pub struct CommandProblem {
+    command: Arc<CommandLine>,
+}
+impl Problem for CommandProblem {
+    type Evaluator = CommandEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        Ok(CommandEvaluator {
+            command: Arc::clone(&self.command),
+            params,
+        })
+    }
+}
+
+/// Evaluator of `CommandProblem`.
+#[derive(Debug)]
+pub struct CommandEvaluator {
+    command: Arc<CommandLine>,
+    params: Params,
+}
+impl Evaluator for CommandEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        match self.command.execute(&self.params) {
+            Ok(value) => Ok((next_step, Values::new(vec![value]))),
+            Err(e) if *e.kind() == ErrorKind::UnevaluableParams => {
+                eprintln!("Warning: evaluation failed: {}", e);
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CommandLine {
+    path: PathBuf,
+    args: Vec<Template>,
+    envs: Vec<(String, Template)>,
+    ranges: Vec<Range>,
+    parse: OutputParser,
+    timeout: Option<Duration>,
+}
+impl CommandLine {
+    /// Executes the command and returns the objective value.
+    ///
+    /// The failures of the command are reported as `ErrorKind::UnevaluableParams` errors.
+    fn execute(&self, params: &Params) -> Result<f64> {
+        let values = self
+            .ranges
+            .iter()
+            .zip(params.iter())
+            .map(|(range, &v)| format_param(range, v))
+            .collect::<Vec<_>>();
+        let mut command = Command::new(&self.path);
+        for arg in &self.args {
+            command.arg(arg.render(&values));
+        }
+        for (key, template) in &self.envs {
+            command.env(key, template.render(&values));
+        }
+        let mut child = track!(command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(Error::from); self.path)?;
+
+        let stdout = track_assert_some!(child.stdout.take(), ErrorKind::Bug);
+        let stderr = track_assert_some!(child.stderr.take(), ErrorKind::Bug);
+        let stdout = thread::spawn(move || read_to_string(stdout));
+        let stderr = thread::spawn(move || read_to_string(stderr));
+
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let status = loop {
+            if let Some(status) = track!(child.try_wait().map_err(Error::from))? {
+                break status;
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                // The output pipes may be kept open by the descendants of the command,
+                // so the reader threads are detached instead of being joined.
+                track_panic!(
+                    ErrorKind::UnevaluableParams,
+                    "{:?} timed out (params={:?})",
+                    self.path,
+                    values
+                );
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+
+        let failure = |reason: String| {
+            let lines = stderr.lines().collect::<Vec<_>>();
+            let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+            Error::from(ErrorKind::UnevaluableParams.cause(format!(
+                "{:?} {} (params={:?}); last stderr lines:\n{}",
+                self.path, reason, values, tail
+            )))
+        };
+        if !status.success() {
+            return Err(track!(failure(format!("exited with {}", status))));
+        }
+        let value = self
+            .parse
+            .parse(&stdout)
+            .ok_or_else(|| failure(format!("didn't output an objective value ({})", self.parse)));
+        track!(value)
+    }
+}
+
+fn read_to_string<R: Read>(mut reader: R) -> String {
+    let mut buf = Vec::new();
+    let _ = reader.read_to_end(&mut buf);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+fn format_param(range: &Range, value: f64) -> String {
+    if value.is_nan() {
+        return String::new();
+    }
+    match range {
+        Range::Continuous { .. } => value.to_string(),
+        Range::Discrete { .. } => (value as i64).to_string(),
+        Range::Categorical { choices, .. } => choices
+            .get(value as usize)
+            .cloned()
+            .unwrap_or_else(|| value.to_string()),
+        Range::Ordinal { values } => values
+            .get(value as usize)
+            .copied()
+            .unwrap_or(value)
+            .to_string(),
+    }
+}
+
+/// Argument template (parameter names are resolved to their indices).
+#[derive(Debug, Clone, PartialEq)]
+struct Template(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Param(usize),
+}
+impl Template {
+    fn parse(s: &str, names: &[&str]) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => track_panic!(ErrorKind::InvalidInput, "Unclosed `{{`: {:?}", s),
+                        }
+                    }
+                    let i = track_assert_some!(
+                        names.iter().position(|n| *n == name),
+                        ErrorKind::InvalidInput,
+                        "Undeclared parameter {:?} in template {:?}",
+                        name,
+                        s
+                    );
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Param(i));
+                }
+                '}' => track_panic!(ErrorKind::InvalidInput, "Unmatched `}}`: {:?}", s),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Self(segments))
+    }
+
+    fn render(&self, values: &[String]) -> String {
+        self.0
+            .iter()
+            .map(|s| match s {
+                Segment::Text(t) => t.as_str(),
+                Segment::Param(i) => values[*i].as_str(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use kurobako_core::epi::solver::ExternalProgramSolverRecipe;
+    use std::io::Write as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn template_works() -> TopLevelResult {
+        let names = ["lr", "opt"];
+        let values = ["0.1".to_owned(), "adam".to_owned()];
+        let t = track!(Template::parse("--lr={lr}", &names))?;
+        assert_eq!(t.render(&values), "--lr=0.1");
+        let t = track!(Template::parse("{opt}-{lr}{{x}}", &names))?;
+        assert_eq!(t.render(&values), "adam-0.1{x}");
+
+        assert!(Template::parse("{foo}", &names).is_err());
+        assert!(Template::parse("{lr", &names).is_err());
+        assert!(Template::parse("lr}", &names).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn output_parser_works() -> TopLevelResult {
+        let parser = OutputParser::LastFloat;
+        assert_eq!(
+            parser.parse("epoch 1: 0.5\nloss=0.25, acc=1e-2\n"),
+            Some(0.01)
+        );
+        assert_eq!(parser.parse("done\n"), None);
+
+        let parser: OutputParser = track!("json-pointer=/metrics/loss".parse())?;
+        assert_eq!(parser.to_string(), "json-pointer=/metrics/loss");
+        assert_eq!(
+            parser.parse("log\n{\"metrics\": {\"loss\": 1.5}}\n"),
+            Some(1.5)
+        );
+        assert_eq!(
+            parser.parse("{\n  \"metrics\": {\"loss\": 2}\n}"),
+            Some(2.0)
+        );
+        assert_eq!(parser.parse("{\"metrics\": {\"loss\": \"x\"}}"), None);
+
+        let json = serde_json::json!({"json-pointer": "/a"});
+        let parser: OutputParser = track!(serde_json::from_value(json).map_err(Error::from))?;
+        assert_eq!(parser, OutputParser::JsonPointer("/a".to_owned()));
+        assert!("foo".parse::<OutputParser>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn command_problem_works() -> TopLevelResult {
+        let mut script = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(writeln!(
+            script,
+            "#!/bin/sh\n\
+             echo \"args: $@\"\n\
+             if [ \"$2\" = \"--c=fail\" ]; then echo 'Traceback' >&2; exit 2; fi\n\
+             if [ \"$2\" = \"--c=hang\" ]; then sleep 10; fi\n\
+             echo \"loss: $1 $FOO\""
+        )
+        .map_err(Error::from))?;
+        let script = script.into_temp_path();
+        {
+            use std::fs;
+            use std::os::unix::fs::PermissionsExt as _;
+            track!(
+                fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+                    .map_err(Error::from)
+            )?;
+        }
+
+        let recipe = serde_json::json!({
+            "path": script.to_path_buf(),
+            "args": ["{x}", "--c={c}"],
+            "envs": ["FOO={n}"],
+            "params": [
+                {"name": "x", "type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                {"name": "c", "type": "CATEGORICAL", "choices": ["ok", "fail", "hang"]},
+                {"name": "n", "type": "DISCRETE", "low": 0, "high": 10}
+            ],
+            "timeout": 1
+        });
+        let recipe: CommandProblemRecipe =
+            track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let registry = FactoryRegistry::new::<CommandProblemRecipe, ExternalProgramSolverRecipe>();
+        let factory = track!(recipe.create_factory(&registry))?;
+        let spec = track!(factory.specification())?;
+        assert_eq!(spec.params_domain.variables().len(), 3);
+        let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+
+        let evaluate = |params: Vec<f64>| {
+            let mut evaluator = track!(problem.create_evaluator(Params::new(params)))?;
+            track!(evaluator.evaluate(1))
+        };
+
+        // The last number in the output is the objective value.
+        let (_, values) = track!(evaluate(vec![0.5, 0.0, 3.0]))?;
+        assert_eq!(values[0], 3.0);
+
+        for c in &[1.0, 2.0] {
+            let e = track_assert_some!(evaluate(vec![0.5, *c, 3.0]).err(), ErrorKind::Bug);
+            assert_eq!(*e.kind(), ErrorKind::UnevaluableParams);
+            if *c == 1.0 {
+                assert!(e.to_string().contains("Traceback"), "{}", e);
+            } else {
+                assert!(e.to_string().contains("timed out"), "{}", e);
+            }
+        }
+
+        let mut recipe = recipe;
+        recipe.args = vec!["{y}".to_owned()];
+        assert!(recipe.create_factory(&registry).is_err());
+        Ok(())
+    }
+}
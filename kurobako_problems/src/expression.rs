@@ -0,0 +1,499 @@
+//! A problem whose objective is an arithmetic expression given in its recipe.
+//!
+//! This is handy to define a tiny problem inline (e.g., in bug reports or documentation examples)
+//! without writing an external program.
+//!
+//! # Expressions
+//!
+//! An expression consists of the following elements:
+//!
+//! - numbers (e.g., `1`, `0.5`, `1e-3`)
+//! - parameter names (identifiers made of ASCII alphanumerics and `_`)
+//! - binary operators `+`, `-`, `*`, `/` and `^` (power; right-associative)
+//! - unary minus
+//! - parentheses
+//! - functions `sin`, `cos`, `tan`, `exp`, `ln`, `sqrt` and `abs` (e.g., `sin(x)`)
+//!
+//! The value of a categorical parameter is the index of its choice,
+//! and the value of an ordinal parameter is the selected value.
+//!
+//! # Examples
+//!
+//! ```json
+//! {
+//!   "params": [
+//!     {"name": "x", "type": "CONTINUOUS", "low": -5.0, "high": 5.0},
+//!     {"name": "y", "type": "DISCRETE", "low": 0, "high": 10}
+//!   ],
+//!   "expression": "(x - 1)^2 + abs(y - 3)"
+//! }
+//! ```
+use crate::param::ParamSpec;
+use kurobako_core::domain::{self, Range};
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use structopt::StructOpt;
+
+/// Recipe of `ExpressionProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ExpressionProblemRecipe {
+    /// Parameter of the problem (e.g., `{"name": "x", "type": "CONTINUOUS", "low": 0.0, "high": 1.0}`).
+    ///
+    /// This option can be specified multiple times.
+    #[structopt(long = "param", number_of_values = 1)]
+    pub params: Vec<ParamSpec>,
+
+    /// Arithmetic expression to be minimized (e.g., `(x - 1)^2 + sin(y)`).
+    pub expression: String,
+}
+impl ProblemRecipe for ExpressionProblemRecipe {
+    type Factory = ExpressionProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        let names = self
+            .params
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        let expr = track!(Expr::parse(&self.expression, &names))?;
+        Ok(ExpressionProblemFactory {
+            params: self.params.clone(),
+            expression: self.expression.clone(),
+            expr: Arc::new(expr),
+        })
+    }
+}
+
+/// Factory of `ExpressionProblem`.
+#[derive(Debug)]
+pub struct ExpressionProblemFactory {
+    params: Vec<ParamSpec>,
+    expression: String,
+    expr: Arc<Expr>,
+}
+impl ProblemFactory for ExpressionProblemFactory {
+    type Problem = ExpressionProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let mut spec = ProblemSpecBuilder::new(&format!("Expression: {}", self.expression))
+            .attr(
+                "version",
+                &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("expression", &self.expression)
+            .value(domain::var("Objective"));
+        for p in &self.params {
+            spec = spec.param(p.to_variable());
+        }
+        track!(spec.finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        let ordinals = self
+            .params
+            .iter()
+            .map(|p| match &p.range {
+                Range::Ordinal { values } => Some(values.clone()),
+                _ => None,
+            })
+            .collect();
+        Ok(ExpressionProblem {
+            expr: Arc::clone(&self.expr),
+            ordinals,
+        })
+    }
+}
+
+/// Problem that evaluates an arithmetic expression.
+#[derive(Debug)]
+pub struct ExpressionProblem {
+    expr: Arc<Expr>,
+    ordinals: Vec<Option<Vec<f64>>>,
+}
+impl Problem for ExpressionProblem {
+    type Evaluator = ExpressionEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        let mut vars = params.into_vec();
+        for (v, ordinal) in vars.iter_mut().zip(self.ordinals.iter()) {
+            if let Some(values) = ordinal {
+                *v = *track_assert_some!(values.get(*v as usize), ErrorKind::InvalidInput);
+            }
+        }
+        Ok(ExpressionEvaluator {
+            expr: Arc::clone(&self.expr),
+            vars,
+        })
+    }
+}
+
+/// Evaluator of `ExpressionProblem`.
+#[derive(Debug)]
+pub struct ExpressionEvaluator {
+    expr: Arc<Expr>,
+    vars: Vec<f64>,
+}
+impl Evaluator for ExpressionEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        let value = self.expr.evaluate(&self.vars);
+        Ok((next_step, Values::new(vec![value])))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+}
+impl Func {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sin" => Some(Self::Sin),
+            "cos" => Some(Self::Cos),
+            "tan" => Some(Self::Tan),
+            "exp" => Some(Self::Exp),
+            "ln" => Some(Self::Ln),
+            "sqrt" => Some(Self::Sqrt),
+            "abs" => Some(Self::Abs),
+            _ => None,
+        }
+    }
+
+    fn apply(self, x: f64) -> f64 {
+        match self {
+            Self::Sin => x.sin(),
+            Self::Cos => x.cos(),
+            Self::Tan => x.tan(),
+            Self::Exp => x.exp(),
+            Self::Ln => x.ln(),
+            Self::Sqrt => x.sqrt(),
+            Self::Abs => x.abs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// Parsed expression (variables are resolved to their indices).
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(f64),
+    Var(usize),
+    Neg(Box<Expr>),
+    Call(Func, Box<Expr>),
+    Bin(BinOp, Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    fn parse(s: &str, vars: &[&str]) -> Result<Self> {
+        let tokens = track!(tokenize(s))?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            vars,
+        };
+        let expr = track!(parser.parse_expr())?;
+        if let Some(token) = parser.peek() {
+            track_panic!(ErrorKind::InvalidInput, "Unexpected token: {:?}", token);
+        }
+        Ok(expr)
+    }
+
+    fn evaluate(&self, vars: &[f64]) -> f64 {
+        match self {
+            Self::Num(x) => *x,
+            Self::Var(i) => vars[*i],
+            Self::Neg(x) => -x.evaluate(vars),
+            Self::Call(f, x) => f.apply(x.evaluate(vars)),
+            Self::Bin(op, l, r) => {
+                let l = l.evaluate(vars);
+                let r = r.evaluate(vars);
+                match op {
+                    BinOp::Add => l + r,
+                    BinOp::Sub => l - r,
+                    BinOp::Mul => l * r,
+                    BinOp::Div => l / r,
+                    BinOp::Pow => l.powf(r),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut prev = c;
+            while let Some(&(i, c)) = chars.peek() {
+                let is_exp_sign = (c == '+' || c == '-') && (prev == 'e' || prev == 'E');
+                if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_exp_sign) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                prev = c;
+                chars.next();
+            }
+            let n = &s[start..end];
+            let n = track_assert_some!(n.parse().ok(), ErrorKind::InvalidInput; n);
+            tokens.push(Token::Num(n));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Ident(s[start..end].to_owned()));
+        } else {
+            let token = match c {
+                '+' | '-' | '*' | '/' | '^' => Token::Op(c),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => track_panic!(ErrorKind::InvalidInput, "Unexpected character: {:?}", c),
+            };
+            tokens.push(token);
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser of the following grammar:
+///
+/// ```text
+/// expr    := term (("+" | "-") term)*
+/// term    := unary (("*" | "/") unary)*
+/// unary   := "-" unary | power
+/// power   := primary ("^" unary)?
+/// primary := NUMBER | IDENT | IDENT "(" expr ")" | "(" expr ")"
+/// ```
+#[derive(Debug)]
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    vars: &'a [&'a str],
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<&'a Token> {
+        let token = track_assert_some!(
+            self.tokens.get(self.pos),
+            ErrorKind::InvalidInput,
+            "Unexpected end of expression"
+        );
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat_op(&mut self, ops: &[char]) -> Option<char> {
+        match self.peek() {
+            Some(Token::Op(c)) if ops.contains(c) => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        let mut expr = track!(self.parse_term())?;
+        while let Some(c) = self.eat_op(&['+', '-']) {
+            let op = if c == '+' { BinOp::Add } else { BinOp::Sub };
+            let rhs = track!(self.parse_term())?;
+            expr = Expr::Bin(op, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr> {
+        let mut expr = track!(self.parse_unary())?;
+        while let Some(c) = self.eat_op(&['*', '/']) {
+            let op = if c == '*' { BinOp::Mul } else { BinOp::Div };
+            let rhs = track!(self.parse_unary())?;
+            expr = Expr::Bin(op, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.eat_op(&['-']).is_some() {
+            let expr = track!(self.parse_unary())?;
+            Ok(Expr::Neg(Box::new(expr)))
+        } else {
+            track!(self.parse_power())
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Expr> {
+        let base = track!(self.parse_primary())?;
+        if self.eat_op(&['^']).is_some() {
+            let exponent = track!(self.parse_unary())?;
+            Ok(Expr::Bin(BinOp::Pow, Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match track!(self.next())? {
+            Token::Num(n) => Ok(Expr::Num(*n)),
+            Token::Ident(name) if self.peek() == Some(&Token::Open) => {
+                let f = track_assert_some!(
+                    Func::from_name(name),
+                    ErrorKind::InvalidInput,
+                    "Unknown function: {:?}",
+                    name
+                );
+                self.pos += 1;
+                let arg = track!(self.parse_expr())?;
+                track!(self.expect_close())?;
+                Ok(Expr::Call(f, Box::new(arg)))
+            }
+            Token::Ident(name) => {
+                let i = track_assert_some!(
+                    self.vars.iter().position(|v| v == name),
+                    ErrorKind::InvalidInput,
+                    "Undeclared variable: {:?}",
+                    name
+                );
+                Ok(Expr::Var(i))
+            }
+            Token::Open => {
+                let expr = track!(self.parse_expr())?;
+                track!(self.expect_close())?;
+                Ok(expr)
+            }
+            token => track_panic!(ErrorKind::InvalidInput, "Unexpected token: {:?}", token),
+        }
+    }
+
+    fn expect_close(&mut self) -> Result<()> {
+        let token = track!(self.next())?;
+        track_assert_eq!(token, &Token::Close, ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "external-process"))]
+mod tests {
+    use super::*;
+    use kurobako_core::epi::solver::ExternalProgramSolverRecipe;
+    use kurobako_core::Error;
+    use trackable::result::TopLevelResult;
+
+    fn eval(s: &str, vars: &[(&str, f64)]) -> Result<f64> {
+        let names = vars.iter().map(|v| v.0).collect::<Vec<_>>();
+        let values = vars.iter().map(|v| v.1).collect::<Vec<_>>();
+        let expr = track!(Expr::parse(s, &names))?;
+        Ok(expr.evaluate(&values))
+    }
+
+    #[test]
+    fn parser_works() -> TopLevelResult {
+        assert_eq!(track!(eval("1 + 2 * 3", &[]))?, 7.0);
+        assert_eq!(track!(eval("(1 + 2) * 3", &[]))?, 9.0);
+        assert_eq!(track!(eval("8 - 2 - 1", &[]))?, 5.0);
+        assert_eq!(track!(eval("8 / 2 / 2", &[]))?, 2.0);
+        assert_eq!(track!(eval("2 ^ 3 ^ 2", &[]))?, 512.0);
+        assert_eq!(track!(eval("-2 ^ 2", &[]))?, -4.0);
+        assert_eq!(track!(eval("2 ^ -1", &[]))?, 0.5);
+        assert_eq!(track!(eval("--3", &[]))?, 3.0);
+        assert_eq!(track!(eval("1.5e1 + 2E-1", &[]))?, 15.2);
+        assert_eq!(
+            track!(eval("abs(x - 10) * y", &[("x", 3.0), ("y", 2.0)]))?,
+            14.0
+        );
+        assert_eq!(track!(eval("sqrt(abs(-16))", &[]))?, 4.0);
+        assert!((track!(eval("exp(ln(16))", &[]))? - 16.0).abs() < 1e-9);
+        assert_eq!(track!(eval("sin(0) + cos(0) + tan(0)", &[]))?, 1.0);
+        assert_eq!(track!(eval("x_1*x_1", &[("x_1", -3.0)]))?, 9.0);
+        Ok(())
+    }
+
+    #[test]
+    fn parser_rejects_invalid_expressions() {
+        for s in &[
+            "", "1 +", "(1 + 2", "1 + 2)", "1 2", "y + 1", "foo(1)", "x(1)", "1 % 2", "1..2", "*1",
+        ] {
+            let e = eval(s, &[("x", 1.0)]).err().map(|e| *e.kind());
+            assert_eq!(e, Some(ErrorKind::InvalidInput), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn expression_problem_works() -> TopLevelResult {
+        let recipe: ExpressionProblemRecipe = track!(serde_json::from_value(serde_json::json!({
+            "params": [
+                {"name": "x", "type": "CONTINUOUS", "low": -5.0, "high": 5.0},
+                {"name": "c", "type": "CATEGORICAL", "choices": ["a", "b", "c"]},
+                {"name": "o", "type": "ORDINAL", "values": [0.5, 2.0]},
+                {"name": "lr", "type": "CONTINUOUS", "low": 0.001, "high": 1.0, "log_uniform": true}
+            ],
+            "expression": "(x - 1)^2 + c * o"
+        }))
+        .map_err(Error::from))?;
+        let registry =
+            FactoryRegistry::new::<ExpressionProblemRecipe, ExternalProgramSolverRecipe>();
+        let factory = track!(recipe.create_factory(&registry))?;
+        let spec = track!(factory.specification())?;
+        assert_eq!(spec.params_domain.variables().len(), 4);
+        assert_eq!(
+            spec.params_domain.variables()[3].distribution(),
+            domain::Distribution::LogUniform
+        );
+
+        let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+        let mut evaluator =
+            track!(problem.create_evaluator(Params::new(vec![3.0, 2.0, 1.0, 0.1])))?;
+        let (_, values) = track!(evaluator.evaluate(1))?;
+        assert_eq!(values[0], 8.0);
+
+        let param: ParamSpec =
+            track!(r#"{"name": "y", "type": "DISCRETE", "low": 0, "high": 3}"#.parse())?;
+        assert_eq!(param.name, "y");
+
+        // Expressions can refer to declared variables only.
+        let mut recipe = recipe;
+        recipe.expression = "x + y".to_owned();
+        assert!(recipe.create_factory(&registry).is_err());
+        Ok(())
+    }
+}
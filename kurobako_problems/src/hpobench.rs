@@ -2,7 +2,8 @@
 //!
 //! [paper]: https://arxiv.org/abs/1905.04970
 use hdf5file::{self, DataObject, Hdf5File};
-use kurobako_core::domain;
+use kurobako_core::dataset;
+use kurobako_core::domain::{self, Domain, ParamValueView};
 use kurobako_core::problem::{
     Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
 };
@@ -11,8 +12,11 @@ use kurobako_core::rng::{ArcRng, Rng};
 use kurobako_core::trial::{Params, Values};
 use kurobako_core::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::f64;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use structopt::StructOpt;
 use trackable::error::ErrorKindExt as _;
@@ -22,16 +26,52 @@ use trackable::error::ErrorKindExt as _;
 #[structopt(rename_all = "kebab-case")]
 pub struct HpobenchProblemRecipe {
     /// Path of the FC-Net dataset.
+    ///
+    /// Instead of a path, one of the dataset names (`naval`, `parkinson`, `protein` or `slice`)
+    /// can be specified to use the dataset downloaded by `kurobako dataset get hpobench`.
     pub dataset: PathBuf,
 }
+impl HpobenchProblemRecipe {
+    /// Pairs of a dataset name and its file path relative to the cache directory of HPOBench.
+    ///
+    /// The files are the ones converted by `convert_to_binary`.
+    pub const DATASET_FILES: &'static [(&'static str, &'static str)] = &[
+        (
+            "naval",
+            "fcnet_tabular_benchmarks/fcnet_naval_propulsion_data.bin",
+        ),
+        (
+            "parkinson",
+            "fcnet_tabular_benchmarks/fcnet_parkinsons_telemonitoring_data.bin",
+        ),
+        (
+            "protein",
+            "fcnet_tabular_benchmarks/fcnet_protein_structure_data.bin",
+        ),
+        (
+            "slice",
+            "fcnet_tabular_benchmarks/fcnet_slice_localization_data.bin",
+        ),
+    ];
+}
 impl ProblemRecipe for HpobenchProblemRecipe {
     type Factory = HpobenchProblemFactory;
 
     fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
-        let file = track!(Hdf5File::open_file(&self.dataset).map_err(into_error))?;
+        let path = track!(dataset::resolve(
+            &self.dataset,
+            "hpobench",
+            Self::DATASET_FILES
+        ))?;
+        let table = if track!(dataset::read_file_header(&path, BINARY_MAGIC))?.is_some() {
+            Table::Binary(track!(BinaryTable::open(&path).map_err(corrupt_dataset))?)
+        } else {
+            let file = track!(Hdf5File::open_file(&path).map_err(into_error))?;
+            Table::Hdf5(Mutex::new(file))
+        };
         Ok(HpobenchProblemFactory {
-            file: Arc::new(Mutex::new(file)),
-            path: self.dataset.clone(),
+            table: Arc::new(table),
+            path,
         })
     }
 }
@@ -39,7 +79,7 @@ impl ProblemRecipe for HpobenchProblemRecipe {
 /// Factory of `HpobenchProblem`.
 #[derive(Debug)]
 pub struct HpobenchProblemFactory {
-    file: Arc<Mutex<Hdf5File>>,
+    table: Arc<Table>,
     path: PathBuf,
 }
 impl ProblemFactory for HpobenchProblemFactory {
@@ -86,8 +126,10 @@ impl ProblemFactory for HpobenchProblemFactory {
     }
 
     fn create_problem(&self, rng: ArcRng) -> Result<Self::Problem> {
+        let spec = track!(self.specification())?;
         Ok(HpobenchProblem {
-            file: Arc::clone(&self.file),
+            table: Arc::clone(&self.table),
+            params_domain: spec.params_domain,
             rng,
         })
     }
@@ -96,7 +138,8 @@ impl ProblemFactory for HpobenchProblemFactory {
 /// FC-Net problem.
 #[derive(Debug)]
 pub struct HpobenchProblem {
-    file: Arc<Mutex<Hdf5File>>,
+    table: Arc<Table>,
+    params_domain: Domain,
     rng: ArcRng,
 }
 impl Problem for HpobenchProblem {
@@ -106,23 +149,37 @@ impl Problem for HpobenchProblem {
         const UNITS: [usize; 6] = [16, 32, 64, 128, 256, 512];
         const DROPOUTS: [&str; 3] = ["0.0", "0.3", "0.6"];
 
+        let params = track!(self.params_domain.to_map(&params))?;
+        let choice = |name: &str| -> Result<String> {
+            match &params[name] {
+                ParamValueView::Categorical(c) => Ok(c.clone()),
+                v => track_panic!(ErrorKind::InvalidInput; name, v),
+            }
+        };
+        let index = |name: &str| -> Result<usize> {
+            match params[name] {
+                ParamValueView::Numerical(v) => Ok(v as usize),
+                ref v => track_panic!(ErrorKind::InvalidInput; name, v),
+            }
+        };
+
         let key = format!(
             r#"{{"activation_fn_1": {:?}, "activation_fn_2": {:?}, "batch_size": {}, "dropout_1": {}, "dropout_2": {}, "init_lr": {}, "lr_schedule": {:?}, "n_units_1": {}, "n_units_2": {}}}"#,
-            (["tanh", "relu"])[params[0] as usize],
-            (["tanh", "relu"])[params[1] as usize],
-            ([8, 16, 32, 64])[params[2] as usize],
-            DROPOUTS[params[3] as usize],
-            DROPOUTS[params[4] as usize],
-            ([5.0 * 1e-4, 1e-3, 5.0 * 1e-3, 1e-2, 5.0 * 1e-2, 1e-1])[params[5] as usize],
-            (["cosine", "const"])[params[6] as usize],
-            UNITS[params[7] as usize],
-            UNITS[params[8] as usize]
+            track!(choice("activation_fn_1"))?,
+            track!(choice("activation_fn_2"))?,
+            ([8, 16, 32, 64])[track!(index("batch_size"))?],
+            DROPOUTS[track!(index("dropout_1"))?],
+            DROPOUTS[track!(index("dropout_2"))?],
+            ([5.0 * 1e-4, 1e-3, 5.0 * 1e-3, 1e-2, 5.0 * 1e-2, 1e-1])[track!(index("init_lr"))?],
+            track!(choice("lr_schedule"))?,
+            UNITS[track!(index("n_units_1"))?],
+            UNITS[track!(index("n_units_2"))?]
         );
 
         let sample_index = track!(self.rng.with_lock(|rng| rng.gen::<usize>() % 4))?;
         Ok(HpobenchEvaluator {
-            file: Arc::clone(&self.file),
-            key: format!("/{}/valid_mse", key),
+            table: Arc::clone(&self.table),
+            key,
             sample_index,
         })
     }
@@ -131,21 +188,233 @@ impl Problem for HpobenchProblem {
 /// Evaluator of `HpobenchProblem`.
 #[derive(Debug)]
 pub struct HpobenchEvaluator {
-    file: Arc<Mutex<Hdf5File>>,
+    table: Arc<Table>,
     key: String,
     sample_index: usize,
 }
 impl Evaluator for HpobenchEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
-        let mut file = track!(self.file.lock().map_err(Error::from))?;
-        let data = track!(file.get_object(&self.key).map_err(into_error))?;
-        let DataObject::Float(data) = track_assert_some!(data, ErrorKind::InvalidInput; self.key);
-
-        let value = data[[self.sample_index, next_step as usize - 1]];
+        let value = track!(self
+            .table
+            .value(&self.key, self.sample_index, next_step as usize - 1))?;
         Ok((next_step, Values::new(vec![value])))
     }
 }
 
+const BINARY_MAGIC: &[u8; 8] = b"KRBKHPOB";
+
+/// Version of the binary format written by `convert_to_binary`.
+pub const BINARY_FORMAT_VERSION: u32 = 1;
+
+/// Returns `true` if the given file has been converted by the current version of `convert_to_binary`.
+pub fn is_converted<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let version = track!(dataset::read_file_header(path, BINARY_MAGIC))?;
+    Ok(version == Some(BINARY_FORMAT_VERSION))
+}
+
+/// Converts an FC-Net dataset in HDF5 format into the binary format of `kurobako`.
+///
+/// The binary format consists of a header, an index of the hyperparameter configurations and
+/// a table of the validation MSEs.
+/// Only the index is loaded when creating a factory, and each evaluation reads a value from the file directly.
+pub fn convert_to_binary<P: AsRef<Path>, Q: AsRef<Path>>(
+    hdf5_path: P,
+    binary_path: Q,
+) -> Result<()> {
+    let mut file = track!(Hdf5File::open_file(hdf5_path).map_err(into_error))?;
+    let paths = track!(file.object_paths().map_err(into_error))?
+        .collect::<std::result::Result<Vec<_>, _>>();
+    let keys = track!(paths.map_err(into_error))?
+        .into_iter()
+        .filter(|path| path.file_name().is_some_and(|n| n == "valid_mse"))
+        .map(|path| {
+            let key = track_assert_some!(
+                path.parent().and_then(|p| p.to_str()),
+                ErrorKind::InvalidInput; path
+            );
+            Ok(key.trim_start_matches('/').to_owned())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut shape = None;
+    let binary_path = binary_path.as_ref();
+    let out = track!(File::create(binary_path).map_err(Error::from); binary_path)?;
+    track!(write_binary(BufWriter::new(out), &keys, |key| {
+        let data = track!(file
+            .get_object(format!("/{}/valid_mse", key))
+            .map_err(into_error))?;
+        let DataObject::Float(data) = track_assert_some!(data, ErrorKind::InvalidInput; key);
+        track_assert_eq!(data.ndim(), 2, ErrorKind::InvalidInput; key);
+        let (samples, steps) = (data.shape()[0], data.shape()[1]);
+        track_assert_eq!(*shape.get_or_insert((samples, steps)), (samples, steps), ErrorKind::InvalidInput; key);
+        Ok((samples, steps, data.iter().copied().collect()))
+    }))
+}
+
+fn write_binary<W, F>(mut writer: W, keys: &[String], mut values: F) -> Result<()>
+where
+    W: Write,
+    F: FnMut(&str) -> Result<(usize, usize, Vec<f64>)>,
+{
+    track_assert!(!keys.is_empty(), ErrorKind::InvalidInput);
+    let (samples, steps, first) = track!(values(&keys[0]))?;
+
+    track!(dataset::write_header(
+        &mut writer,
+        BINARY_MAGIC,
+        BINARY_FORMAT_VERSION
+    ))?;
+    for n in &[keys.len(), samples, steps] {
+        track!(writer
+            .write_all(&(*n as u64).to_le_bytes())
+            .map_err(Error::from))?;
+    }
+    for key in keys {
+        track!(writer
+            .write_all(&(key.len() as u32).to_le_bytes())
+            .map_err(Error::from))?;
+        track!(writer.write_all(key.as_bytes()).map_err(Error::from))?;
+    }
+    for (i, key) in keys.iter().enumerate() {
+        let data = if i == 0 {
+            first.clone()
+        } else {
+            track!(values(key))?.2
+        };
+        track_assert_eq!(data.len(), samples * steps, ErrorKind::InvalidInput; key);
+        for v in data {
+            track!(writer.write_all(&v.to_le_bytes()).map_err(Error::from))?;
+        }
+    }
+    track!(writer.flush().map_err(Error::from))?;
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Table {
+    Hdf5(Mutex<Hdf5File>),
+    Binary(BinaryTable),
+}
+impl Table {
+    fn value(&self, key: &str, sample: usize, step: usize) -> Result<f64> {
+        match self {
+            Self::Hdf5(file) => {
+                let mut file = track!(file.lock().map_err(Error::from))?;
+                let path = format!("/{}/valid_mse", key);
+                let data = track!(file.get_object(&path).map_err(into_error))?;
+                let DataObject::Float(data) =
+                    track_assert_some!(data, ErrorKind::InvalidInput; path);
+                Ok(data[[sample, step]])
+            }
+            Self::Binary(table) => track!(table.value(key, sample, step)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BinaryTable {
+    file: Mutex<File>,
+    index: HashMap<String, usize>,
+    samples: usize,
+    steps: usize,
+    data_offset: u64,
+}
+impl BinaryTable {
+    fn open(path: &Path) -> Result<Self> {
+        let file = track!(File::open(path).map_err(Error::from); path)?;
+        let mut reader = BufReader::new(file);
+        let version = track!(dataset::read_header(&mut reader, BINARY_MAGIC))?;
+        track_assert_eq!(
+            version,
+            Some(BINARY_FORMAT_VERSION),
+            ErrorKind::CorruptDataset,
+            "Stale conversion {:?} (run `kurobako dataset convert hpobench` or `kurobako dataset get hpobench` again)",
+            path
+        );
+
+        let entries = track!(read_u64(&mut reader))? as usize;
+        let samples = track!(read_u64(&mut reader))? as usize;
+        let steps = track!(read_u64(&mut reader))? as usize;
+        let mut data_offset = 12 + 8 * 3;
+        let mut index = HashMap::with_capacity(entries);
+        for i in 0..entries {
+            let mut len = [0; 4];
+            track!(reader.read_exact(&mut len).map_err(Error::from))?;
+            let mut key = vec![0; u32::from_le_bytes(len) as usize];
+            track!(reader.read_exact(&mut key).map_err(Error::from))?;
+            data_offset += 4 + key.len() as u64;
+            let key = track!(String::from_utf8(key).map_err(|e| ErrorKind::InvalidInput.cause(e)))?;
+            index.insert(key, i);
+        }
+
+        Ok(Self {
+            file: Mutex::new(reader.into_inner()),
+            index,
+            samples,
+            steps,
+            data_offset,
+        })
+    }
+
+    fn value(&self, key: &str, sample: usize, step: usize) -> Result<f64> {
+        let i = *track_assert_some!(self.index.get(key), ErrorKind::InvalidInput; key);
+        track_assert!(sample < self.samples, ErrorKind::InvalidInput; sample, self.samples);
+        track_assert!(step < self.steps, ErrorKind::InvalidInput; step, self.steps);
+
+        let position = ((i * self.samples + sample) * self.steps + step) as u64 * 8;
+        let mut file = track!(self.file.lock().map_err(Error::from))?;
+        track!(file
+            .seek(SeekFrom::Start(self.data_offset + position))
+            .map_err(Error::from))?;
+        let mut buf = [0; 8];
+        track!(file.read_exact(&mut buf).map_err(Error::from))?;
+        Ok(f64::from_le_bytes(buf))
+    }
+}
+
+fn read_u64<R: Read>(mut reader: R) -> Result<u64> {
+    let mut buf = [0; 8];
+    track!(reader.read_exact(&mut buf).map_err(Error::from))?;
+    Ok(u64::from_le_bytes(buf))
+}
+
 fn into_error(e: hdf5file::Error) -> Error {
-    ErrorKind::Other.takes_over(e).into()
+    ErrorKind::CorruptDataset.takes_over(e).into()
+}
+
+fn corrupt_dataset(e: Error) -> Error {
+    if *e.kind() == ErrorKind::CorruptDataset {
+        e
+    } else {
+        ErrorKind::CorruptDataset.takes_over(e).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn binary_table_works() -> TopLevelResult {
+        let keys = vec!["foo".to_owned(), "barbaz".to_owned()];
+        let file = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(write_binary(file.as_file(), &keys, |key| {
+            let base = if key == "foo" { 0.0 } else { 100.0 };
+            Ok((2, 3, (0..6).map(|i| base + i as f64).collect()))
+        }))?;
+
+        assert!(track!(is_converted(file.path()))?);
+        let table = track!(BinaryTable::open(file.path()))?;
+        assert_eq!(track!(table.value("foo", 0, 0))?, 0.0);
+        assert_eq!(track!(table.value("foo", 1, 2))?, 5.0);
+        assert_eq!(track!(table.value("barbaz", 1, 0))?, 103.0);
+        assert!(table.value("qux", 0, 0).is_err());
+        assert!(table.value("foo", 2, 0).is_err());
+        Ok(())
+    }
 }
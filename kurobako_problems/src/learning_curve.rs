@@ -0,0 +1,325 @@
+//! A synthetic problem whose intermediate values follow learning curves.
+//!
+//! Each configuration (i.e., a point in `[0.0, 1.0]^dim`) has an asymptote and a learning rate.
+//! The asymptote is the squared distance to a hidden optimum, and
+//! the value at a step decays from `asymptote + 1.0` towards the asymptote
+//! following the shape specified by `--curve`.
+//!
+//! By default, better configurations learn faster, so the curves never cross and
+//! early stopping is always safe. `--crossing-probability` makes the given fraction of
+//! configurations learn at the opposite rate, which is the hard case for pruners.
+//!
+//! All the hidden structures and the noise are deterministic given the random seed passed to the factory,
+//! and the value of a configuration at a step doesn't depend on the evaluation order.
+use kurobako_core::domain;
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::{standard_normal, ArcRng, Rng};
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+const MIN_RATE: f64 = 1.0;
+const MAX_RATE: f64 = 20.0;
+
+/// Recipe of `LearningCurveProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct LearningCurveProblemRecipe {
+    /// Dimension of the search space.
+    #[structopt(long, default_value = "2")]
+    pub dim: usize,
+
+    /// Shape of the learning curves (`pow`, `exp` or `log`).
+    #[structopt(long, default_value = "pow")]
+    pub curve: Curve,
+
+    /// Standard deviation of the Gaussian noise added to each value.
+    #[structopt(long, default_value = "0.0")]
+    pub noise: f64,
+
+    /// Probability that a configuration learns at the opposite rate (i.e., its curve crosses the others).
+    #[structopt(long, default_value = "0.0")]
+    pub crossing_probability: f64,
+
+    /// Number of the evaluation steps.
+    #[structopt(long, default_value = "100")]
+    pub steps: u64,
+}
+impl ProblemRecipe for LearningCurveProblemRecipe {
+    type Factory = LearningCurveProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(self.dim > 0, ErrorKind::InvalidInput; self.dim);
+        track_assert!(self.steps > 0, ErrorKind::InvalidInput; self.steps);
+        track_assert!(self.noise >= 0.0, ErrorKind::InvalidInput; self.noise);
+        track_assert!(
+            (0.0..=1.0).contains(&self.crossing_probability),
+            ErrorKind::InvalidInput; self.crossing_probability
+        );
+        Ok(LearningCurveProblemFactory {
+            recipe: self.clone(),
+        })
+    }
+}
+
+/// Shape of learning curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    /// Power law decay (`1 / (1 + rate * t)`).
+    Pow,
+
+    /// Exponential decay (`exp(-rate * t)`).
+    Exp,
+
+    /// Logarithmic decay (`1 - ln(1 + rate * t) / ln(1 + rate)`), which reaches the asymptote at the last step.
+    Log,
+}
+impl Curve {
+    /// Returns the remaining fraction of the initial gap at the relative step `t` (in `[0.0, 1.0]`).
+    fn decay(self, rate: f64, t: f64) -> f64 {
+        match self {
+            Curve::Pow => 1.0 / (1.0 + rate * t),
+            Curve::Exp => (-rate * t).exp(),
+            Curve::Log => 1.0 - (1.0 + rate * t).ln() / (1.0 + rate).ln(),
+        }
+    }
+}
+impl FromStr for Curve {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pow" => Ok(Curve::Pow),
+            "exp" => Ok(Curve::Exp),
+            "log" => Ok(Curve::Log),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown curve: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::Pow => write!(f, "pow"),
+            Curve::Exp => write!(f, "exp"),
+            Curve::Log => write!(f, "log"),
+        }
+    }
+}
+
+/// Factory of `LearningCurveProblem`.
+#[derive(Debug)]
+pub struct LearningCurveProblemFactory {
+    recipe: LearningCurveProblemRecipe,
+}
+impl ProblemFactory for LearningCurveProblemFactory {
+    type Problem = LearningCurveProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let mut spec = ProblemSpecBuilder::new(&format!(
+            "Learning Curve ({}, dim={})",
+            self.recipe.curve, self.recipe.dim
+        ))
+        .attr(
+            "version",
+            &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+        )
+        .attr("noise", &self.recipe.noise.to_string())
+        .attr(
+            "crossing_probability",
+            &self.recipe.crossing_probability.to_string(),
+        )
+        .value(domain::var("Loss"))
+        .steps(1..=self.recipe.steps);
+        for i in 0..self.recipe.dim {
+            spec = spec.param(domain::var(&format!("x{}", i)).continuous(0.0, 1.0));
+        }
+        track!(spec.finish())
+    }
+
+    fn create_problem(&self, mut rng: ArcRng) -> Result<Self::Problem> {
+        let optimum = (0..self.recipe.dim).map(|_| rng.gen()).collect();
+        Ok(LearningCurveProblem {
+            recipe: self.recipe.clone(),
+            optimum,
+            rng: ArcRng::new(rng.gen()),
+        })
+    }
+}
+
+/// Synthetic problem whose intermediate values follow learning curves.
+#[derive(Debug)]
+pub struct LearningCurveProblem {
+    recipe: LearningCurveProblemRecipe,
+    optimum: Vec<f64>,
+    rng: ArcRng,
+}
+impl LearningCurveProblem {
+    fn asymptote(&self, params: &Params) -> f64 {
+        let d = params
+            .iter()
+            .zip(self.optimum.iter())
+            .map(|(x, o)| (x - o).powi(2))
+            .sum::<f64>();
+        d / params.len() as f64
+    }
+}
+impl Problem for LearningCurveProblem {
+    type Evaluator = LearningCurveEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        track_assert_eq!(params.len(), self.recipe.dim, ErrorKind::InvalidInput);
+
+        // Random numbers of a configuration only depend on its parameters.
+        let stream_id = params.iter().fold(0u64, |acc, x| {
+            acc.rotate_left(17) ^ x.to_bits().wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        });
+        let mut rng = self.rng.split(stream_id);
+
+        let asymptote = self.asymptote(&params);
+        let crossing = rng.gen::<f64>() < self.recipe.crossing_probability;
+        let goodness = if crossing { asymptote } else { 1.0 - asymptote };
+        Ok(LearningCurveEvaluator {
+            curve: self.recipe.curve,
+            noise: self.recipe.noise,
+            steps: self.recipe.steps,
+            asymptote,
+            rate: MIN_RATE + (MAX_RATE - MIN_RATE) * goodness,
+            rng,
+        })
+    }
+}
+
+/// Evaluator of `LearningCurveProblem`.
+#[derive(Debug)]
+pub struct LearningCurveEvaluator {
+    curve: Curve,
+    noise: f64,
+    steps: u64,
+    asymptote: f64,
+    rate: f64,
+    rng: ArcRng,
+}
+impl LearningCurveEvaluator {
+    fn value(&self, step: u64) -> f64 {
+        let t = step as f64 / self.steps as f64;
+        let mut value = self.asymptote + self.curve.decay(self.rate, t);
+        if self.noise > 0.0 {
+            let mut rng = self.rng.split(step);
+            value += self.noise * standard_normal(&mut rng);
+        }
+        value
+    }
+}
+impl Evaluator for LearningCurveEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        let step = next_step.min(self.steps);
+        Ok((step, Values::new(vec![self.value(step)])))
+    }
+}
+
+#[cfg(all(test, feature = "external-process"))]
+mod tests {
+    use super::*;
+    use kurobako_core::epi::solver::ExternalProgramSolverRecipe;
+    use trackable::result::TopLevelResult;
+
+    fn problem(
+        curve: Curve,
+        noise: f64,
+        crossing_probability: f64,
+    ) -> Result<LearningCurveProblem> {
+        let recipe = LearningCurveProblemRecipe {
+            dim: 3,
+            curve,
+            noise,
+            crossing_probability,
+            steps: 100,
+        };
+        let registry =
+            FactoryRegistry::new::<LearningCurveProblemRecipe, ExternalProgramSolverRecipe>();
+        let factory = track!(recipe.create_factory(&registry))?;
+        track!(factory.create_problem(ArcRng::new(7)))
+    }
+
+    fn curve(problem: &LearningCurveProblem, params: &[f64]) -> Result<Vec<f64>> {
+        let mut evaluator = track!(problem.create_evaluator(Params::new(params.to_vec())))?;
+        (1..=100)
+            .map(|step| track!(evaluator.evaluate(step)).map(|(_, v)| v[0]))
+            .collect()
+    }
+
+    fn configs() -> Vec<Vec<f64>> {
+        (0..30)
+            .map(|i| {
+                let i = i as f64;
+                vec![(i * 0.37) % 1.0, (i * 0.61) % 1.0, (i * 0.23) % 1.0]
+            })
+            .collect()
+    }
+
+    fn crossings(problem: &LearningCurveProblem) -> Result<usize> {
+        let curves = configs()
+            .iter()
+            .map(|c| track!(curve(problem, c)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut count = 0;
+        for a in &curves {
+            for b in &curves {
+                if a[9] < b[9] && a[99] > b[99] {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    #[test]
+    fn curves_decrease_towards_asymptotes() -> TopLevelResult {
+        for &c in &[Curve::Pow, Curve::Exp, Curve::Log] {
+            let problem = track!(problem(c, 0.0, 0.0))?;
+            let params = [0.2, 0.5, 0.8];
+            let values = track!(curve(&problem, &params))?;
+            assert!(values.windows(2).all(|w| w[0] > w[1]), "{:?}", c);
+
+            let asymptote = problem.asymptote(&Params::new(params.to_vec()));
+            assert!(values[99] >= asymptote);
+            assert!(values[99] - asymptote < 0.5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn crossing_probability_works() -> TopLevelResult {
+        for &c in &[Curve::Pow, Curve::Exp, Curve::Log] {
+            assert_eq!(track!(crossings(&track!(problem(c, 0.0, 0.0))?))?, 0);
+            assert!(track!(crossings(&track!(problem(c, 0.0, 0.5))?))? > 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn evaluation_is_deterministic() -> TopLevelResult {
+        let params = [0.1, 0.2, 0.3];
+        let a = track!(problem(Curve::Exp, 0.1, 0.5))?;
+        let b = track!(problem(Curve::Exp, 0.1, 0.5))?;
+        let values = track!(curve(&a, &params))?;
+        assert_eq!(values, track!(curve(&b, &params))?);
+
+        // Values don't depend on the evaluation order.
+        let mut evaluator = track!(a.create_evaluator(Params::new(params.to_vec())))?;
+        let (_, v) = track!(evaluator.evaluate(50))?;
+        assert_eq!(v[0], values[49]);
+
+        // Noise is actually added.
+        let noiseless = track!(curve(&track!(problem(Curve::Exp, 0.0, 0.5))?, &params))?;
+        assert_ne!(values, noiseless);
+        Ok(())
+    }
+}
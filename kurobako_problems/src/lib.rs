@@ -4,9 +4,17 @@
 #[macro_use]
 extern crate trackable;
 
+pub mod bo_standard;
+#[cfg(feature = "external-process")]
+pub mod command;
+pub mod expression;
 pub mod hpobench;
+pub mod learning_curve;
+pub mod mf_branin;
 pub mod nasbench;
+pub mod param;
 pub mod sigopt;
 pub mod surrogate;
+pub mod svm_like;
 pub mod warm_starting;
 pub mod zdt;
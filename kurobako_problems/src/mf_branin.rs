@@ -0,0 +1,144 @@
+//! A synthetic multi-fidelity problem based on the Branin function.
+//!
+//! This problem has two fidelity variables `z1` and `z2` in `[0.0, 1.0]`.
+//! The function coincides with the original Branin function when both of them are `1.0`.
+//!
+//! The cost of an evaluation is modeled by its steps:
+//! solvers can stop evaluations earlier than the last step (e.g., at low fidelities) to save the budget.
+//!
+//! # References
+//!
+//! - [Multi-fidelity Bayesian Optimisation with Continuous Approximations](https://arxiv.org/abs/1703.06240)
+use kurobako_core::domain;
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use structopt::StructOpt;
+
+/// Recipe of `MfBraninProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct MfBraninProblemRecipe {
+    /// Number of the evaluation steps.
+    #[structopt(long, default_value = "1")]
+    #[serde(default = "default_steps")]
+    pub steps: u64,
+}
+
+impl ProblemRecipe for MfBraninProblemRecipe {
+    type Factory = MfBraninProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(self.steps > 0, ErrorKind::InvalidInput; self.steps);
+        Ok(MfBraninProblemFactory { steps: self.steps })
+    }
+}
+
+fn default_steps() -> u64 {
+    1
+}
+
+/// Factory of `MfBraninProblem`.
+#[derive(Debug)]
+pub struct MfBraninProblemFactory {
+    steps: u64,
+}
+
+impl ProblemFactory for MfBraninProblemFactory {
+    type Problem = MfBraninProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let spec = ProblemSpecBuilder::new("Multi-Fidelity Branin")
+            .attr(
+                "version",
+                &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr(
+                "paper",
+                "Kandasamy, Kirthevasan, et al. \"Multi-fidelity Bayesian optimisation with \
+                 continuous approximations.\" Proceedings of the 34th International Conference \
+                 on Machine Learning-Volume 70. JMLR. org, 2017.",
+            )
+            .param(domain::var("x1").continuous(-5.0, 10.0))
+            .param(domain::var("x2").continuous(0.0, 15.0))
+            .fidelity(domain::var("z1").continuous_inclusive(0.0, 1.0))
+            .fidelity(domain::var("z2").continuous_inclusive(0.0, 1.0))
+            .value(domain::var("Branin"))
+            .steps(1..=self.steps);
+        track!(spec.finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        Ok(MfBraninProblem {})
+    }
+}
+
+/// Multi-fidelity Branin problem.
+#[derive(Debug)]
+pub struct MfBraninProblem {}
+
+impl Problem for MfBraninProblem {
+    type Evaluator = MfBraninEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        Ok(MfBraninEvaluator { params })
+    }
+}
+
+/// Evaluator of `MfBraninProblem`.
+#[derive(Debug)]
+pub struct MfBraninEvaluator {
+    params: Params,
+}
+impl Evaluator for MfBraninEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        track!(self.evaluate_with_fidelity(next_step, &Params::default()))
+    }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        let (z1, z2) = match fidelity.get() {
+            [] => (1.0, 1.0),
+            [z1, z2] => (*z1, *z2),
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Wrong number of fidelity values: {:?}",
+                fidelity
+            ),
+        };
+        let value = branin(self.params[0], self.params[1], z1, z2);
+        Ok((next_step, Values::new(vec![value])))
+    }
+}
+
+fn branin(x1: f64, x2: f64, z1: f64, z2: f64) -> f64 {
+    let a = 1.0;
+    let b = 5.1 / (4.0 * PI * PI) - 0.01 * (1.0 - z1);
+    let c = 5.0 / PI;
+    let r = 6.0;
+    let s = 10.0;
+    let t = 1.0 / (8.0 * PI) + 0.1 * (1.0 - z2);
+    a * (x2 - b * x1 * x1 + c * x1 - r).powi(2) + s * (1.0 - t) * x1.cos() + s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branin_works() {
+        let optimum = 0.397_887_357_729_738_2;
+        assert!((branin(-PI, 12.275, 1.0, 1.0) - optimum).abs() < 1e-9);
+        assert!((branin(PI, 2.275, 1.0, 1.0) - optimum).abs() < 1e-9);
+        assert!((branin(PI, 2.275, 0.5, 0.5) - optimum).abs() > 1e-3);
+    }
+}
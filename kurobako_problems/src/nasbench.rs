@@ -1,6 +1,7 @@
 //! A problem based on the benchmark described in [NAS-Bench-101: Towards Reproducible Neural Architecture Search][nasbench].
 //!
 //! [nasbench]: https://arxiv.org/abs/1902.09635
+use kurobako_core::dataset;
 use kurobako_core::domain::{self, VariableBuilder};
 use kurobako_core::num::OrderedFloat;
 use kurobako_core::problem::{
@@ -15,11 +16,14 @@ use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::{Bound, HashSet};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::thread_local;
 use structopt::StructOpt;
+use trackable::error::ErrorKindExt as _;
 
 thread_local! {
     static NASBENCHES: RefCell<HashMap<PathBuf, Arc<NasBench>>> = RefCell::new(HashMap::new());
@@ -34,6 +38,9 @@ const EDGE_KINDS: usize = VERTICES * (VERTICES - 1) / 2;
 #[structopt(rename_all = "kebab-case")]
 pub struct NasbenchProblemRecipe {
     /// Path of the NASBench dataset.
+    ///
+    /// If `full` is specified instead of a path, the dataset downloaded by
+    /// `kurobako dataset get nasbench` is used.
     pub dataset: PathBuf,
 
     /// Encoding type of the NASBench search space.
@@ -53,22 +60,29 @@ pub struct NasbenchProblemRecipe {
     #[serde(default = "default_metrics")]
     pub metrics: Vec<Metric>,
 }
+impl NasbenchProblemRecipe {
+    /// Pairs of a dataset name and its file path relative to the cache directory of NASBench.
+    pub const DATASET_FILES: &'static [(&'static str, &'static str)] =
+        &[("full", "nasbench_full.bin")];
+}
 impl ProblemRecipe for NasbenchProblemRecipe {
     type Factory = NasbenchProblemFactory;
 
     fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
         track_assert!(!self.metrics.is_empty(), ErrorKind::InvalidInput);
 
+        let path = track!(dataset::resolve(
+            &self.dataset,
+            "nasbench",
+            Self::DATASET_FILES
+        ))?;
         NASBENCHES.with(|map| {
             let mut map = map.borrow_mut();
-            if !map.contains_key(&self.dataset) {
-                map.insert(
-                    self.dataset.clone(),
-                    Arc::new(track!(NasBench::new(&self.dataset))?),
-                );
+            if !map.contains_key(&path) {
+                map.insert(path.clone(), Arc::new(track!(load_binary(&path))?));
             }
             Ok(NasbenchProblemFactory {
-                nasbench: Arc::clone(&map[&self.dataset]),
+                nasbench: Arc::clone(&map[&path]),
                 encoding: self.encoding,
                 metrics: self.metrics.clone(),
             })
@@ -76,6 +90,67 @@ impl ProblemRecipe for NasbenchProblemRecipe {
     }
 }
 
+const BINARY_MAGIC: &[u8; 8] = b"KRBKNASB";
+
+/// Version of the binary format written by `convert_to_binary`.
+pub const BINARY_FORMAT_VERSION: u32 = 1;
+
+/// Returns `true` if the given file has been converted by the current version of `convert_to_binary`.
+pub fn is_converted<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let version = track!(dataset::read_file_header(path, BINARY_MAGIC))?;
+    Ok(version == Some(BINARY_FORMAT_VERSION))
+}
+
+/// Converts a NASBench dataset in TFRecord format (`nasbench_*.tfrecord`) into the binary format of `kurobako`.
+pub fn convert_to_binary<P: AsRef<Path>, Q: AsRef<Path>>(
+    tfrecord_path: P,
+    binary_path: Q,
+) -> Result<()> {
+    let tfrecord_path = tfrecord_path.as_ref();
+    let file = track!(File::open(tfrecord_path).map_err(Error::from); tfrecord_path)?;
+    let nasbench = track!(NasBench::from_tfrecord_reader(BufReader::new(file), false))?;
+
+    let binary_path = binary_path.as_ref();
+    let file = track!(File::create(binary_path).map_err(Error::from); binary_path)?;
+    let mut writer = BufWriter::new(file);
+    track!(dataset::write_header(
+        &mut writer,
+        BINARY_MAGIC,
+        BINARY_FORMAT_VERSION
+    ))?;
+    track!(nasbench.to_writer(writer))?;
+    Ok(())
+}
+
+/// Loads a binary dataset.
+///
+/// Files written by older versions of `kurobako` (i.e., without the header) are also accepted.
+fn load_binary(path: &Path) -> Result<NasBench> {
+    let file = track!(File::open(path).map_err(Error::from); path)?;
+    let mut reader = BufReader::new(file);
+    match track!(dataset::read_header(&mut reader, BINARY_MAGIC))? {
+        None => track!(NasBench::new(path).map_err(corrupt_dataset)),
+        Some(version) => {
+            track_assert_eq!(
+                version,
+                BINARY_FORMAT_VERSION,
+                ErrorKind::CorruptDataset,
+                "Stale conversion {:?} (run `kurobako dataset convert nasbench` or `kurobako dataset get nasbench` again)",
+                path
+            );
+            track!(NasBench::from_reader(reader).map_err(corrupt_dataset))
+        }
+    }
+}
+
+fn corrupt_dataset(e: trackable::error::Failure) -> Error {
+    ErrorKind::CorruptDataset.takes_over(e).into()
+}
+
 /// Factory of `NasbenchProblem`.
 #[derive(Debug)]
 pub struct NasbenchProblemFactory {
@@ -0,0 +1,43 @@
+//! Parameters declared inline in problem recipes.
+use kurobako_core::domain::{self, Range, VariableBuilder};
+use kurobako_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Parameter declared in a problem recipe (e.g., `{"name": "x", "type": "CONTINUOUS", "low": 0.0, "high": 1.0}`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamSpec {
+    /// Name of the parameter.
+    pub name: String,
+
+    /// Range of the parameter.
+    #[serde(flatten)]
+    pub range: Range,
+
+    /// If `true`, the prior distribution of the parameter is log-uniform.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub log_uniform: bool,
+}
+impl ParamSpec {
+    /// Returns the domain variable of this parameter.
+    pub fn to_variable(&self) -> VariableBuilder {
+        let var = domain::var(&self.name).range(self.range.clone());
+        if self.log_uniform {
+            var.log_uniform()
+        } else {
+            var
+        }
+    }
+}
+impl FromStr for ParamSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        track!(serde_json::from_str(s).map_err(Error::from))
+    }
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(b: &bool) -> bool {
+    !b
+}
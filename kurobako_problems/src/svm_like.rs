@@ -0,0 +1,186 @@
+//! A synthetic problem that has a conditional search space like SVM hyperparameter tuning.
+//!
+//! The search space is as follows:
+//!
+//! - `kernel`: one of `linear`, `rbf` and `poly`
+//! - `C`: regularization parameter (log-uniform)
+//! - `gamma`: kernel coefficient (log-uniform, active only if `kernel` is `rbf` or `poly`)
+//! - `degree`: degree of the polynomial kernel (active only if `kernel` is `poly`)
+//!
+//! The objective is an analytic "validation error" that only depends on the active parameters.
+//! Its global minimum is `0.05` at `kernel=rbf`, `C=10` and `gamma=0.01`.
+use kurobako_core::domain::{self, Group};
+use kurobako_core::problem::{
+    Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+const LINEAR: f64 = 0.0;
+const RBF: f64 = 1.0;
+const POLY: f64 = 2.0;
+
+/// Recipe of `SvmLikeProblem`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SvmLikeProblemRecipe {}
+impl ProblemRecipe for SvmLikeProblemRecipe {
+    type Factory = SvmLikeProblemFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        Ok(SvmLikeProblemFactory {})
+    }
+}
+
+/// Factory of `SvmLikeProblem`.
+#[derive(Debug)]
+pub struct SvmLikeProblemFactory {}
+impl ProblemFactory for SvmLikeProblemFactory {
+    type Problem = SvmLikeProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let spec = ProblemSpecBuilder::new("SVM-like")
+            .attr(
+                "version",
+                &format!("kurobako_problems={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("optimum", "0.05")
+            .group(
+                Group::new("kernel")
+                    .choice("linear", &[])
+                    .choice("rbf", &["gamma"])
+                    .choice("poly", &["gamma", "degree"]),
+            )
+            .param(domain::var("C").continuous(1e-3, 1e3).log_uniform())
+            .param(domain::var("gamma").continuous(1e-4, 10.0).log_uniform())
+            .param(domain::var("degree").discrete(2, 6))
+            .value(domain::var("Validation Error").continuous_inclusive(0.0, 1.0));
+        track!(spec.finish())
+    }
+
+    fn create_problem(&self, _rng: ArcRng) -> Result<Self::Problem> {
+        Ok(SvmLikeProblem {})
+    }
+}
+
+/// SVM-like problem.
+#[derive(Debug)]
+pub struct SvmLikeProblem {}
+impl Problem for SvmLikeProblem {
+    type Evaluator = SvmLikeEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        track_assert_eq!(params.len(), 4, ErrorKind::InvalidInput);
+        let kernel = params[0];
+        track_assert!(
+            kernel == LINEAR || kernel == RBF || kernel == POLY,
+            ErrorKind::InvalidInput; params
+        );
+        track_assert!(params[1].is_finite(), ErrorKind::InvalidInput; params);
+        if kernel != LINEAR {
+            track_assert!(
+                params[2].is_finite(),
+                ErrorKind::InvalidInput,
+                "Inactive `gamma`: {:?}",
+                params
+            );
+        }
+        if kernel == POLY {
+            track_assert!(
+                params[3].is_finite(),
+                ErrorKind::InvalidInput,
+                "Inactive `degree`: {:?}",
+                params
+            );
+        }
+        Ok(SvmLikeEvaluator { params })
+    }
+}
+
+/// Evaluator of `SvmLikeProblem`.
+#[derive(Debug)]
+pub struct SvmLikeEvaluator {
+    params: Params,
+}
+impl Evaluator for SvmLikeEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        track_assert_eq!(next_step, 1, ErrorKind::Bug);
+        let p = &self.params;
+        let value = validation_error(p[0], p[1], p[2], p[3]);
+        Ok((1, Values::new(vec![value])))
+    }
+}
+
+/// Computes the objective value (parameters that are inactive for `kernel` are ignored).
+fn validation_error(kernel: f64, c: f64, gamma: f64, degree: f64) -> f64 {
+    let c = c.log10();
+    let error = if kernel == LINEAR {
+        0.2 + 0.02 * c.powi(2)
+    } else if kernel == RBF {
+        0.05 + 0.02 * (c - 1.0).powi(2) + 0.05 * (gamma.log10() + 2.0).powi(2)
+    } else {
+        0.08 + 0.02 * (c - 1.0).powi(2)
+            + 0.03 * (gamma.log10() + 1.0).powi(2)
+            + 0.02 * (degree - 3.0).powi(2)
+    };
+    error.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::solver::Capability;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn spec_works() -> TopLevelResult {
+        let spec = track!(SvmLikeProblemFactory {}.specification())?;
+        assert!(spec.requirements().is_capable(Capability::Conditional));
+
+        let vars = spec.params_domain.variables();
+        let is_active = |i: usize, vals: &[f64]| {
+            vars[i]
+                .condition()
+                .is_none_or(|c| c.is_satisfied(vars, vals))
+        };
+        assert!(is_active(2, &[RBF, 1.0, 0.1, f64::NAN]));
+        assert!(is_active(2, &[POLY, 1.0, 0.1, 3.0]));
+        assert!(!is_active(2, &[LINEAR, 1.0, f64::NAN, f64::NAN]));
+        assert!(is_active(3, &[POLY, 1.0, 0.1, 3.0]));
+        assert!(!is_active(3, &[RBF, 1.0, 0.1, f64::NAN]));
+        Ok(())
+    }
+
+    #[test]
+    fn evaluate_works() -> TopLevelResult {
+        let problem = track!(SvmLikeProblemFactory {}.create_problem(ArcRng::new(0)))?;
+        let evaluate = |params: Vec<f64>| -> Result<f64> {
+            let mut evaluator = track!(problem.create_evaluator(Params::new(params)))?;
+            Ok(track!(evaluator.evaluate(1))?.1[0])
+        };
+
+        assert_eq!(track!(evaluate(vec![RBF, 10.0, 0.01, f64::NAN]))?, 0.05);
+        assert_eq!(
+            track!(evaluate(vec![LINEAR, 1.0, f64::NAN, f64::NAN]))?,
+            0.2
+        );
+        assert_eq!(
+            track!(evaluate(vec![LINEAR, 100.0, f64::NAN, f64::NAN]))?,
+            0.28
+        );
+        assert_eq!(track!(evaluate(vec![POLY, 10.0, 0.1, 3.0]))?, 0.08);
+        assert_eq!(track!(evaluate(vec![POLY, 10.0, 0.1, 5.0]))?, 0.16);
+
+        // Inactive parameters don't affect the value.
+        assert_eq!(track!(evaluate(vec![LINEAR, 1.0, 0.5, 4.0]))?, 0.2);
+
+        // Active parameters must be specified.
+        assert!(evaluate(vec![RBF, 10.0, f64::NAN, f64::NAN]).is_err());
+        assert!(evaluate(vec![POLY, 10.0, 0.1, f64::NAN]).is_err());
+        Ok(())
+    }
+}
@@ -157,20 +157,34 @@ impl Zdt {
                 .map(|_| Range::Continuous {
                     low: 0.0,
                     high: 1.0,
+                    step: None,
+                    inclusive_high: false,
                 })
                 .collect(),
             Self::Function4 => std::iter::once((0.0, 1.0))
                 .chain(std::iter::repeat((-5.0, 5.0)).take(9))
-                .map(|(low, high)| Range::Continuous { low, high })
+                .map(|(low, high)| Range::Continuous {
+                    low,
+                    high,
+                    step: None,
+                    inclusive_high: false,
+                })
                 .collect(),
             Self::Function5 => std::iter::once((0, ((1 << 30) - 1)))
                 .chain(std::iter::repeat((0, ((1 << 5) - 1))).take(10))
-                .map(|(low, high)| Range::Discrete { low, high })
+                .map(|(low, high)| Range::Discrete {
+                    low,
+                    high,
+                    step: 1,
+                    inclusive_high: false,
+                })
                 .collect(),
             Self::Function6 => (0..10)
                 .map(|_| Range::Continuous {
                     low: 0.0,
                     high: 1.0,
+                    step: None,
+                    inclusive_high: false,
                 })
                 .collect(),
         }
@@ -20,6 +20,11 @@ parser.add_argument("--pruner-kwargs", type=str, default="{}")
 parser.add_argument("--loglevel", choices=["debug", "info", "warning", "error"])
 parser.add_argument("--direction", choices=["minimize", "maximize"], default="minimize")
 parser.add_argument("--use-discrete-uniform", action="store_true")
+parser.add_argument("--tpe-n-startup-trials", type=int)
+parser.add_argument("--tpe-n-ei-candidates", type=int)
+parser.add_argument("--tpe-multivariate", action="store_true")
+parser.add_argument("--tpe-group", action="store_true")
+parser.add_argument("--tpe-constant-liar", action="store_true")
 
 args = parser.parse_args()
 
@@ -45,6 +50,16 @@ def create_study(seed):
         raise ValueError("Unknown sampler: {}.".format(args.sampler))
 
     sampler_kwargs = json.loads(args.sampler_kwargs)
+    if args.tpe_n_startup_trials is not None:
+        sampler_kwargs["n_startup_trials"] = args.tpe_n_startup_trials
+    if args.tpe_n_ei_candidates is not None:
+        sampler_kwargs["n_ei_candidates"] = args.tpe_n_ei_candidates
+    if args.tpe_multivariate:
+        sampler_kwargs["multivariate"] = True
+    if args.tpe_group:
+        sampler_kwargs["group"] = True
+    if args.tpe_constant_liar:
+        sampler_kwargs["constant_liar"] = True
     try:
         sampler_kwargs["seed"] = seed
         sampler = sampler_cls(**sampler_kwargs)
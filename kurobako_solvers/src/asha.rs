@@ -3,6 +3,7 @@
 //! [ASHA]: https://arxiv.org/abs/1810.05934
 use crate::error::{from_yamakan, into_yamakan};
 use crate::yamakan_utils::YamakanIdGen;
+use kurobako_core::domain::{Domain, Range};
 use kurobako_core::json::JsonRecipe;
 use kurobako_core::num::OrderedFloat;
 use kurobako_core::problem::ProblemSpec;
@@ -12,10 +13,12 @@ use kurobako_core::solver::{
     BoxSolver, BoxSolverFactory, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec,
     SolverSpecBuilder,
 };
-use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, TrialId, Values};
-use kurobako_core::{ErrorKind, Result};
+use kurobako_core::trial::{
+    EvaluatedTrial, IdGen, NextTrial, Params, TrialId, TrialStatus, Values,
+};
+use kurobako_core::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f64;
 use structopt::StructOpt;
 use yamakan::optimizers::asha::{AshaOptimizer, AshaOptimizerBuilder};
@@ -44,6 +47,26 @@ pub struct AshaSolverRecipe {
     #[structopt(long)]
     pub without_checkpoint: bool,
 
+    /// Name of the fidelity variable used as the resource of ASHA.
+    ///
+    /// If this is specified, ASHA allocates the resource by choosing a value of the variable
+    /// (the range of which is divided into `fidelity_max_budget` units) instead of evaluation steps.
+    /// The other fidelity variables are fixed to their highest values.
+    ///
+    /// A trial evaluated at a fidelity rate `r` (i.e., `r` of the highest one) is evaluated
+    /// until `r` of the last step of the problem, so it consumes the budget proportionally.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fidelity: Option<String>,
+
+    /// Number of the units into which the range of the fidelity variable is divided.
+    ///
+    /// This is the maximum resource parameter of ASHA when `fidelity` is specified.
+    /// For discrete fidelity variables, the number of their values is a good choice.
+    #[structopt(long, default_value = "100")]
+    #[serde(default = "default_fidelity_max_budget")]
+    pub fidelity_max_budget: u64,
+
     /// Recipe of the base solver.
     pub base_solver: JsonRecipe,
 }
@@ -51,17 +74,28 @@ impl SolverRecipe for AshaSolverRecipe {
     type Factory = AshaSolverFactory;
 
     fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(
+            self.fidelity_max_budget > 0,
+            ErrorKind::InvalidInput,
+            "`--fidelity-max-budget` must be positive"
+        );
         let base = track!(registry.create_solver_factory_from_json(&self.base_solver))?;
         Ok(AshaSolverFactory {
             min_step_rate: self.min_step_rate,
             min_step: self.min_step,
             reduction_factor: self.reduction_factor,
             without_checkpoint: self.without_checkpoint,
+            fidelity: self.fidelity.clone(),
+            fidelity_max_budget: self.fidelity_max_budget,
             base,
         })
     }
 }
 
+fn default_fidelity_max_budget() -> u64 {
+    100
+}
+
 /// Factory of `AshaSolver`.
 #[derive(Debug)]
 pub struct AshaSolverFactory {
@@ -69,6 +103,8 @@ pub struct AshaSolverFactory {
     min_step: Option<u64>,
     reduction_factor: usize,
     without_checkpoint: bool,
+    fidelity: Option<String>,
+    fidelity_max_budget: u64,
     base: BoxSolverFactory,
 }
 impl SolverFactory for AshaSolverFactory {
@@ -78,6 +114,9 @@ impl SolverFactory for AshaSolverFactory {
         let mut base = track!(self.base.specification())?;
         base.capabilities
             .remove_capability(Capability::MultiObjective);
+        if self.fidelity.is_none() {
+            base.capabilities.add_capability(Capability::MultiStep);
+        }
 
         let spec = SolverSpecBuilder::new(&format!("ASHA with {}", base.name))
             .attr(
@@ -94,7 +133,23 @@ impl SolverFactory for AshaSolverFactory {
     }
 
     fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
-        let max_budget = problem.steps.last();
+        let fidelity = if let Some(name) = &self.fidelity {
+            let domain = track_assert_some!(
+                problem.fidelity_domain.as_ref(),
+                ErrorKind::InvalidInput,
+                "Problem {:?} has no fidelity domain",
+                problem.name
+            );
+            Some(track!(FidelityResource::new(domain, name))?)
+        } else {
+            None
+        };
+
+        let max_budget = if fidelity.is_some() {
+            self.fidelity_max_budget
+        } else {
+            problem.steps.last()
+        };
         let min_budget = if let Some(v) = self.min_step {
             v
         } else {
@@ -113,12 +168,16 @@ impl SolverFactory for AshaSolverFactory {
         let optimizer = track!(builder
             .finish(BaseOptimizer::new(max_budget, base), min_budget, max_budget)
             .map_err(from_yamakan))?;
+        let log = PromotionLog::new(min_budget, max_budget, self.reduction_factor);
 
         Ok(AshaSolver {
             optimizer,
             rng,
             trials: HashMap::new(),
             max_budget,
+            last_step: problem.steps.last(),
+            fidelity,
+            log,
         })
     }
 }
@@ -130,8 +189,19 @@ impl SolverFactory for AshaSolverFactory {
 pub struct AshaSolver {
     optimizer: AshaOptimizer<OrderedFloat<f64>, BaseOptimizer>,
     rng: ArcRng,
-    trials: HashMap<TrialId, NextTrial>,
+    trials: HashMap<TrialId, (NextTrial, u64)>,
     max_budget: u64,
+    last_step: u64,
+    fidelity: Option<FidelityResource>,
+    log: PromotionLog,
+}
+impl AshaSolver {
+    /// Returns the promotion decisions made so far.
+    ///
+    /// Trials that have not been promoted from a non-top rung yet are reported as stopped ones.
+    pub fn promotion_log(&self) -> Vec<PromotionDecision> {
+        self.log.decisions()
+    }
 }
 impl Solver for AshaSolver {
     fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
@@ -141,32 +211,230 @@ impl Solver for AshaSolver {
             .ask(&mut self.rng, &mut idg)
             .map_err(from_yamakan))?;
 
+        self.log.ask(obs.param.id);
+
         let mut trial = obs.param.clone();
         trial.id = TrialId::new(obs.id.get());
+        if let Some(fidelity) = &self.fidelity {
+            let amount = obs.budget.amount;
+            trial.fidelity = track!(fidelity.values(amount, self.max_budget))?;
 
-        self.trials.insert(trial.id, obs.param);
+            // The runner rounds this up to the nearest step of the problem.
+            let step = (self.last_step * amount).div_ceil(self.max_budget);
+            trial.next_step = Some(step.max(1));
+        }
+
+        self.trials.insert(trial.id, (obs.param, obs.budget.amount));
         Ok(trial)
     }
 
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
-        let param = track_assert_some!(self.trials.remove(&trial.id), ErrorKind::Bug);
+        let (param, amount) = track_assert_some!(self.trials.remove(&trial.id), ErrorKind::Bug);
         let value = if trial.values.is_empty() {
             OrderedFloat(f64::NAN)
         } else {
             OrderedFloat(trial.values[0])
         };
 
-        let obs = MfObs {
-            id: ObsId::new(trial.id.get()),
-            budget: Budget {
+        let budget = if self.fidelity.is_some() {
+            Budget {
+                amount,
+                consumption: amount,
+            }
+        } else {
+            Budget {
                 amount: self.max_budget,
                 consumption: trial.current_step,
-            },
+            }
+        };
+        self.log.tell(param.id, trial.id, budget, value.0);
+        let obs = MfObs {
+            id: ObsId::new(trial.id.get()),
+            budget,
             param,
             value,
         };
         track!(self.optimizer.tell(obs).map_err(from_yamakan))
     }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        let mut attrs = track!(self.log.summary())?;
+        let base = track!(self.optimizer.inner_mut().solver.finalize())?;
+        attrs.extend(base.into_iter().map(|(k, v)| (format!("base.{}", k), v)));
+        Ok(attrs)
+    }
+}
+
+/// Promotion decision made by ASHA.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromotionDecision {
+    /// Identifier of the trial evaluated at the rung.
+    pub trial_id: TrialId,
+
+    /// Index of the rung (`0` is the lowest one).
+    pub rung: usize,
+
+    /// `true` if the trial was promoted to the next rung, `false` if it was stopped at the rung.
+    pub promoted: bool,
+
+    /// Value of the trial at the time of the decision.
+    pub value: f64,
+}
+
+/// Recorder of the promotion decisions.
+///
+/// Configurations are identified by the trial identifiers of the base solver,
+/// which are kept even if ASHA assigns new identifiers to promoted trials (i.e., `--without-checkpoint`).
+#[derive(Debug)]
+struct PromotionLog {
+    rung_budgets: Vec<u64>,
+    pending: HashMap<TrialId, (TrialId, usize, f64)>,
+    promotions: Vec<PromotionDecision>,
+}
+impl PromotionLog {
+    fn new(min_budget: u64, max_budget: u64, reduction_factor: usize) -> Self {
+        // The same rungs as the ones of `yamakan::optimizers::asha::AshaOptimizer`.
+        let mut rung_budgets = Vec::new();
+        let mut budget = min_budget;
+        while budget < max_budget {
+            rung_budgets.push(budget);
+            budget = max_budget.min(budget.saturating_mul(reduction_factor as u64));
+        }
+        rung_budgets.push(max_budget);
+        Self {
+            rung_budgets,
+            pending: HashMap::new(),
+            promotions: Vec::new(),
+        }
+    }
+
+    fn ask(&mut self, base_id: TrialId) {
+        if let Some((trial_id, rung, value)) = self.pending.remove(&base_id) {
+            self.promotions.push(PromotionDecision {
+                trial_id,
+                rung,
+                promoted: true,
+                value,
+            });
+        }
+    }
+
+    fn tell(&mut self, base_id: TrialId, trial_id: TrialId, budget: Budget, value: f64) {
+        if budget.consumption < budget.amount {
+            // Canceled evaluations are not placed in any rungs.
+            return;
+        }
+        let rung = self
+            .rung_budgets
+            .iter()
+            .rposition(|&b| b <= budget.consumption)
+            .unwrap_or(0);
+        if rung + 1 < self.rung_budgets.len() {
+            self.pending.insert(base_id, (trial_id, rung, value));
+        }
+    }
+
+    fn decisions(&self) -> Vec<PromotionDecision> {
+        let mut stopped = self
+            .pending
+            .values()
+            .map(|&(trial_id, rung, value)| PromotionDecision {
+                trial_id,
+                rung,
+                promoted: false,
+                value,
+            })
+            .collect::<Vec<_>>();
+        stopped.sort_by_key(|d| d.trial_id);
+        self.promotions.iter().cloned().chain(stopped).collect()
+    }
+
+    fn summary(&self) -> Result<BTreeMap<String, String>> {
+        let decisions = self.decisions();
+        let mut attrs = BTreeMap::new();
+        for rung in 0..self.rung_budgets.len() - 1 {
+            let count = |promoted| {
+                decisions
+                    .iter()
+                    .filter(|d| d.rung == rung && d.promoted == promoted)
+                    .count()
+            };
+            attrs.insert(format!("rung_{}.promoted", rung), count(true).to_string());
+            attrs.insert(format!("rung_{}.stopped", rung), count(false).to_string());
+        }
+        let log = track!(serde_json::to_string(&decisions).map_err(Error::from))?;
+        attrs.insert("promotion_log".to_owned(), log);
+        Ok(attrs)
+    }
+}
+
+#[derive(Debug)]
+struct FidelityResource {
+    index: usize,
+    range: Range,
+    highest: Vec<f64>,
+}
+impl FidelityResource {
+    fn new(domain: &Domain, name: &str) -> Result<Self> {
+        let index = track_assert_some!(
+            domain.index_of(name),
+            ErrorKind::InvalidInput,
+            "Unknown fidelity variable: {:?}",
+            name
+        );
+        let highest = domain
+            .variables()
+            .iter()
+            .map(|v| track!(Self::value(v.range(), 1.0); v.name()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            index,
+            range: domain.variables()[index].range().clone(),
+            highest,
+        })
+    }
+
+    fn values(&self, budget: u64, max_budget: u64) -> Result<Params> {
+        let rate = budget as f64 / max_budget as f64;
+        let mut values = self.highest.clone();
+        values[self.index] = track!(Self::value(&self.range, rate))?;
+        Ok(Params::new(values))
+    }
+
+    fn value(range: &Range, rate: f64) -> Result<f64> {
+        let v = match range {
+            Range::Continuous {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } => {
+                track_assert!(
+                    *inclusive_high,
+                    ErrorKind::InvalidInput,
+                    "A continuous fidelity variable must have an inclusive upper bound"
+                );
+                low + (high - low) * rate
+            }
+            Range::Discrete {
+                low,
+                high,
+                inclusive_high,
+                ..
+            } => {
+                let max = if *inclusive_high { *high } else { high - 1 };
+                (*low as f64 + ((max - low) as f64 * rate).round()).max(*low as f64)
+            }
+            Range::Ordinal { values } => ((values.len() - 1) as f64 * rate).round(),
+            Range::Categorical { .. } => {
+                track_panic!(
+                    ErrorKind::InvalidInput,
+                    "A categorical variable cannot be used as a fidelity"
+                );
+            }
+        };
+        Ok(range.quantize(v))
+    }
 }
 
 #[derive(Debug)]
@@ -209,19 +477,197 @@ impl Optimizer for BaseOptimizer {
 
     fn tell(&mut self, obs: Obs<Self::Param, Self::Value>) -> Result<(), yamakan::Error> {
         let value = obs.value.value.0;
-        let values = if value.is_nan() {
-            Values::new(Vec::new())
+        let (values, status) = if value.is_nan() {
+            (Values::new(Vec::new()), TrialStatus::Failed)
         } else {
-            Values::new(vec![value])
+            (Values::new(vec![value]), TrialStatus::Ok)
         };
 
         let trial = EvaluatedTrial {
             id: obs.param.id,
             values,
             current_step: self.max_budget - obs.value.rank,
+            status,
+            attrs: Default::default(),
         };
         track!(self.solver.tell(trial).map_err(into_yamakan))?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    /// Solver that proposes a fixed sequence of distinct parameters.
+    ///
+    /// Ties would make the decisions of ASHA depend on the iteration order of hash maps.
+    struct SequenceSolverFactory;
+    impl SolverFactory for SequenceSolverFactory {
+        type Solver = SequenceSolver;
+
+        fn specification(&self) -> Result<SolverSpec> {
+            Ok(SolverSpecBuilder::new("Sequence").finish())
+        }
+
+        fn create_solver(&self, _rng: ArcRng, _problem: &ProblemSpec) -> Result<Self::Solver> {
+            Ok(SequenceSolver)
+        }
+    }
+
+    struct SequenceSolver;
+    impl Solver for SequenceSolver {
+        fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+            let id = idg.generate();
+            let x = (id.get() * 37 % 101) as f64 / 100.0;
+            Ok(NextTrial {
+                id,
+                params: Params::new(vec![x]),
+                next_step: Some(1),
+                fidelity: Params::new(vec![]),
+                asked_params: None,
+            })
+        }
+
+        fn tell(&mut self, _trial: EvaluatedTrial) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs ASHA against a synthetic learning curve (`x + 1 - epochs`) that converges to `x`.
+    fn run_asha(trials: usize) -> Result<(Vec<PromotionDecision>, BTreeMap<String, String>)> {
+        let problem = track!(ProblemSpecBuilder::new("curve")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .fidelity(var("epochs").continuous_inclusive(0.0, 1.0))
+            .finish())?;
+        let factory = AshaSolverFactory {
+            min_step_rate: 0.01,
+            min_step: Some(25),
+            reduction_factor: 2,
+            without_checkpoint: false,
+            fidelity: Some("epochs".to_owned()),
+            fidelity_max_budget: 100,
+            base: BoxSolverFactory::new(SequenceSolverFactory),
+        };
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &problem))?;
+
+        let mut idg = IdGen::new();
+        for _ in 0..trials {
+            let trial = track!(solver.ask(&mut idg))?;
+            let value = trial.params[0] + 1.0 - trial.fidelity[0];
+            track!(solver.tell(EvaluatedTrial {
+                id: trial.id,
+                values: Values::new(vec![value]),
+                current_step: 1,
+                status: TrialStatus::Ok,
+                attrs: BTreeMap::new(),
+            }))?;
+        }
+        let log = solver.promotion_log();
+        let attrs = track!(solver.finalize())?;
+        Ok((log, attrs))
+    }
+
+    #[test]
+    fn promotion_log_works() -> TopLevelResult {
+        let (log, attrs) = track!(run_asha(40))?;
+
+        // Rungs: 25 -> 50 -> 100.
+        assert!(log.iter().all(|d| d.rung < 2));
+        let count = |rung, promoted| {
+            log.iter()
+                .filter(|d| d.rung == rung && d.promoted == promoted)
+                .count()
+        };
+        assert!(count(0, true) > 0);
+        assert!(count(0, false) > 0);
+        assert!(count(1, true) > 0);
+        assert!(count(1, true) <= count(0, true));
+        assert_eq!(attrs["rung_0.promoted"], count(0, true).to_string());
+        assert_eq!(attrs["rung_1.stopped"], count(1, false).to_string());
+
+        // The curve preserves the order of configurations, so the promoted ones are better than the stopped ones.
+        let values = |promoted| {
+            log.iter()
+                .filter(move |d| d.rung == 0 && d.promoted == promoted)
+                .map(|d| d.value)
+        };
+        let worst_promoted = values(true).fold(f64::NEG_INFINITY, f64::max);
+        let best_stopped = values(false).fold(f64::INFINITY, f64::min);
+        assert!(worst_promoted < best_stopped);
+
+        let logged: Vec<PromotionDecision> =
+            track!(serde_json::from_str(&attrs["promotion_log"]).map_err(Error::from))?;
+        let key = |d: &PromotionDecision| (d.trial_id, d.rung, d.promoted);
+        assert_eq!(
+            logged.iter().map(key).collect::<Vec<_>>(),
+            log.iter().map(key).collect::<Vec<_>>()
+        );
+
+        // The decisions are deterministic.
+        assert_eq!(track!(run_asha(40))?.0, log);
+        Ok(())
+    }
+
+    #[test]
+    fn fidelity_budget_works() -> TopLevelResult {
+        let problem = track!(ProblemSpecBuilder::new("curve")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .fidelity(var("epochs").continuous_inclusive(0.0, 1.0))
+            .steps(vec![10, 20, 30, 40])
+            .finish())?;
+        let factory = AshaSolverFactory {
+            min_step_rate: 0.01,
+            min_step: Some(1),
+            reduction_factor: 2,
+            without_checkpoint: true,
+            fidelity: Some("epochs".to_owned()),
+            fidelity_max_budget: 4,
+            base: BoxSolverFactory::new(SequenceSolverFactory),
+        };
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &problem))?;
+
+        // Rungs: 1 -> 2 -> 4 (i.e., the fidelity rates 0.25, 0.5 and 1.0).
+        let mut idg = IdGen::new();
+        let mut steps = Vec::new();
+        for _ in 0..40 {
+            let trial = track!(solver.ask(&mut idg))?;
+            let rate = trial.fidelity[0];
+            assert!([0.25, 0.5, 1.0].contains(&rate), "{}", rate);
+
+            // The trial is evaluated until the step proportional to the fidelity.
+            let step = track_assert_some!(trial.next_step, ErrorKind::Other);
+            assert_eq!(step, (rate * 40.0) as u64);
+            steps.push(step);
+
+            let value = trial.params[0] + 1.0 - rate;
+            track!(solver.tell(EvaluatedTrial {
+                id: trial.id,
+                values: Values::new(vec![value]),
+                current_step: step,
+                status: TrialStatus::Ok,
+                attrs: BTreeMap::new(),
+            }))?;
+        }
+        assert!(steps.contains(&10));
+        assert!(steps.contains(&40));
+
+        // Recipes without the number of units use the default one.
+        let recipe: AshaSolverRecipe = track!(serde_json::from_value(serde_json::json!({
+            "fidelity": "epochs",
+            "min_step_rate": 0.01,
+            "reduction_factor": 2,
+            "without_checkpoint": false,
+            "base_solver": {"random": {}}
+        }))
+        .map_err(Error::from))?;
+        assert_eq!(recipe.fidelity_max_budget, 100);
+        Ok(())
+    }
+}
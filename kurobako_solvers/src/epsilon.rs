@@ -0,0 +1,179 @@
+//! A solver that mixes random search into its inner solver (i.e., epsilon-greedy exploration).
+use crate::random::sample_params;
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    BoxSolver, BoxSolverFactory, Solver, SolverFactory, SolverRecipe, SolverSpec, SolverSpecBuilder,
+};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params};
+use kurobako_core::{ErrorKind, Result};
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use structopt::StructOpt;
+
+/// Recipe of `EpsilonSolver`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct EpsilonSolverRecipe {
+    /// Probability that a trial is sampled at random instead of being asked to the inner solver.
+    #[structopt(long)]
+    pub epsilon: f64,
+
+    /// Recipe of the inner solver.
+    pub inner: JsonRecipe,
+}
+impl SolverRecipe for EpsilonSolverRecipe {
+    type Factory = EpsilonSolverFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(
+            (0.0..=1.0).contains(&self.epsilon),
+            ErrorKind::InvalidInput; self.epsilon
+        );
+        let inner = track!(registry.create_solver_factory_from_json(&self.inner))?;
+        Ok(EpsilonSolverFactory {
+            epsilon: self.epsilon,
+            inner,
+        })
+    }
+}
+
+/// Factory of `EpsilonSolver`.
+#[derive(Debug)]
+pub struct EpsilonSolverFactory {
+    epsilon: f64,
+    inner: BoxSolverFactory,
+}
+impl SolverFactory for EpsilonSolverFactory {
+    type Solver = EpsilonSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let inner = track!(self.inner.specification())?;
+        let spec = SolverSpecBuilder::new(&format!("{} with Epsilon-Random", inner.name))
+            .attr(
+                "version",
+                &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("epsilon", &self.epsilon.to_string())
+            .capabilities(inner.capabilities);
+        Ok(spec.finish())
+    }
+
+    fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let inner = track!(self.inner.create_solver(rng.clone(), problem))?;
+        Ok(EpsilonSolver {
+            epsilon: self.epsilon,
+            rng,
+            problem: problem.clone(),
+            inner,
+            asks: 0,
+            random_asks: 0,
+        })
+    }
+}
+
+/// A solver that answers each `ask` with random parameters with probability `epsilon`
+/// and otherwise delegates it to the inner solver.
+///
+/// Random parameters are sampled in the same way as `RandomSolver`,
+/// and evaluated at the last step and the highest fidelity.
+/// The results of all the trials (including the random ones) are told to the inner solver,
+/// so the inner solver has to accept the results of trials that it didn't ask.
+#[derive(Debug)]
+pub struct EpsilonSolver {
+    epsilon: f64,
+    rng: ArcRng,
+    problem: ProblemSpec,
+    inner: BoxSolver,
+    asks: u64,
+    random_asks: u64,
+}
+impl EpsilonSolver {
+    /// Returns the fraction of the trials sampled at random so far.
+    pub fn random_fraction(&self) -> f64 {
+        if self.asks == 0 {
+            0.0
+        } else {
+            self.random_asks as f64 / self.asks as f64
+        }
+    }
+}
+impl Solver for EpsilonSolver {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        self.asks += 1;
+        if self.rng.gen::<f64>() < self.epsilon {
+            self.random_asks += 1;
+            return Ok(NextTrial {
+                id: idg.generate(),
+                params: sample_params(&self.problem, &mut self.rng),
+                next_step: Some(self.problem.steps.last()),
+                fidelity: Params::default(),
+                asked_params: None,
+            });
+        }
+        track!(self.inner.ask(idg))
+    }
+
+    fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+        track!(self.inner.tell(trial))
+    }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        let mut attrs = track!(self.inner.finalize())?;
+        attrs.insert(
+            "random_fraction".to_owned(),
+            self.random_fraction().to_string(),
+        );
+        Ok(attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::RandomSolverRecipe;
+    use kurobako_core::domain::var;
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use kurobako_core::trial::Values;
+    use trackable::result::TopLevelResult;
+
+    fn recipe(epsilon: f64) -> EpsilonSolverRecipe {
+        EpsilonSolverRecipe {
+            epsilon,
+            inner: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn random_fraction_converges_to_epsilon() -> TopLevelResult {
+        let registry = FactoryRegistry::new::<ExternalProgramProblemRecipe, RandomSolverRecipe>();
+        let factory = track!(recipe(0.3).create_factory(&registry))?;
+        let problem = track!(ProblemSpecBuilder::new("Foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .param(var("c").categorical(["a", "b"]))
+            .value(var("y"))
+            .finish())?;
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &problem))?;
+        let mut idg = IdGen::new();
+        for _ in 0..5000 {
+            let trial = track!(solver.ask(&mut idg))?;
+            track!(problem.validate_params(&trial.params))?;
+            track!(solver.tell(trial.evaluated(Values::new(vec![0.0]), 1)))?;
+        }
+        assert!((solver.random_fraction() - 0.3).abs() < 0.02);
+
+        let attrs = track!(solver.finalize())?;
+        assert_eq!(
+            attrs.get("random_fraction"),
+            Some(&solver.random_fraction().to_string())
+        );
+
+        assert!(recipe(1.5).create_factory(&registry).is_err());
+        assert!(recipe(f64::NAN).create_factory(&registry).is_err());
+        Ok(())
+    }
+}
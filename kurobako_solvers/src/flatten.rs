@@ -0,0 +1,228 @@
+//! A solver that lets an inner solver without the `CONDITIONAL` capability tackle conditional search spaces.
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    BoxSolver, BoxSolverFactory, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec,
+    SolverSpecBuilder,
+};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params};
+use kurobako_core::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use structopt::StructOpt;
+
+/// Recipe of `FlattenSolver`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct FlattenSolverRecipe {
+    /// Recipe of the inner solver.
+    pub inner: JsonRecipe,
+}
+impl SolverRecipe for FlattenSolverRecipe {
+    type Factory = FlattenSolverFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        let inner = track!(registry.create_solver_factory_from_json(&self.inner))?;
+        Ok(FlattenSolverFactory { inner })
+    }
+}
+
+/// Factory of `FlattenSolver`.
+#[derive(Debug)]
+pub struct FlattenSolverFactory {
+    inner: BoxSolverFactory,
+}
+impl SolverFactory for FlattenSolverFactory {
+    type Solver = FlattenSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let mut inner = track!(self.inner.specification())?;
+        inner.capabilities.add_capability(Capability::Conditional);
+
+        let spec = SolverSpecBuilder::new(&format!("{} with Flattened Conditions", inner.name))
+            .attr(
+                "version",
+                &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("conditions", "flattened")
+            .capabilities(inner.capabilities);
+        Ok(spec.finish())
+    }
+
+    fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let flattened = flattened_problem_spec(problem);
+
+        // Constraints also require the `CONDITIONAL` capability, and they are kept as is.
+        let inner_spec = track!(self.inner.specification())?;
+        track!(flattened.check_capabilities(&inner_spec))?;
+
+        let inner = track!(self.inner.create_solver(rng, &flattened))?;
+        Ok(FlattenSolver {
+            problem: problem.clone(),
+            inner,
+            masked_params: 0,
+        })
+    }
+}
+
+/// Returns the specification of the given problem with the conditions of its parameters removed.
+fn flattened_problem_spec(problem: &ProblemSpec) -> ProblemSpec {
+    let mut flattened = problem.clone();
+    flattened.params_domain = problem.params_domain.unconditional();
+    flattened
+}
+
+/// A solver that presents a conditional search space to its inner solver as an unconditional one.
+///
+/// The inner solver proposes the values of all the parameters,
+/// and then the parameters that are inactive under the conditions evaluated against the proposed values
+/// are set to `NaN` before the evaluation.
+/// The evaluation results are told to the inner solver as they are.
+#[derive(Debug)]
+pub struct FlattenSolver {
+    problem: ProblemSpec,
+    inner: BoxSolver,
+    masked_params: u64,
+}
+impl FlattenSolver {
+    /// Returns the number of the parameters set to `NaN` by this solver so far.
+    pub fn masked_params(&self) -> u64 {
+        self.masked_params
+    }
+
+    fn mask_inactive_params(&mut self, params: Params) -> Params {
+        let vars = self.problem.params_domain.variables();
+        let mut params = params.into_vec();
+        for i in 0..params.len().min(vars.len()) {
+            let active = vars[i]
+                .condition()
+                .is_none_or(|c| c.is_satisfied(vars, &params[..i]));
+            if !active && !params[i].is_nan() {
+                params[i] = f64::NAN;
+                self.masked_params += 1;
+            }
+        }
+        Params::new(params)
+    }
+}
+impl Solver for FlattenSolver {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        let mut trial = track!(self.inner.ask(idg))?;
+        trial.params = self.mask_inactive_params(trial.params);
+        Ok(trial)
+    }
+
+    fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+        track!(self.inner.tell(trial))
+    }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        let mut attrs = track!(self.inner.finalize())?;
+        attrs.insert("masked_params".to_owned(), self.masked_params.to_string());
+        Ok(attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nsga2::Nsga2SolverRecipe;
+    use crate::random::RandomSolverRecipe;
+    use kurobako_core::domain::{var, Group};
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use kurobako_core::trial::Values;
+    use trackable::result::TopLevelResult;
+
+    fn svm_like_spec() -> Result<ProblemSpec> {
+        track!(ProblemSpecBuilder::new("SVM-like")
+            .group(
+                Group::new("kernel")
+                    .choice("linear", &[])
+                    .choice("rbf", &["gamma"])
+                    .choice("poly", &["gamma", "degree"]),
+            )
+            .param(var("C").continuous(1e-3, 1e3).log_uniform())
+            .param(var("gamma").continuous(1e-4, 10.0).log_uniform())
+            .param(var("degree").discrete(2, 6))
+            .value(var("Validation Error").continuous_inclusive(0.0, 1.0))
+            .finish())
+    }
+
+    /// Asks the given solver `n` times and returns the number of the active parameters by each kernel.
+    fn run(solver: &mut dyn Solver, problem: &ProblemSpec, n: usize) -> Result<Vec<Vec<usize>>> {
+        let mut actives = vec![vec![0; 4]; 3];
+        let mut idg = IdGen::new();
+        for _ in 0..n {
+            let trial = track!(solver.ask(&mut idg))?;
+            track!(problem.validate_params(&trial.params))?;
+
+            let kernel = trial.params[0] as usize;
+            for (i, v) in trial.params.iter().enumerate() {
+                if !v.is_nan() {
+                    actives[kernel][i] += 1;
+                }
+            }
+            let value = trial.params[1].ln().abs() / 10.0;
+            track!(solver.tell(trial.evaluated(Values::new(vec![value]), 1)))?;
+        }
+        Ok(actives)
+    }
+
+    /// Returns the sets of the variables that are active at least once by each kernel.
+    fn active_sets(actives: &[Vec<usize>]) -> Vec<Vec<bool>> {
+        actives
+            .iter()
+            .map(|a| a.iter().map(|&n| n > 0).collect())
+            .collect()
+    }
+
+    #[test]
+    fn flatten_solver_works() -> TopLevelResult {
+        let problem = track!(svm_like_spec())?;
+
+        let registry = FactoryRegistry::new::<ExternalProgramProblemRecipe, Nsga2SolverRecipe>();
+        let inner = serde_json::json!({
+            "population": 20, "tournament": 2, "crossover": 0.5, "mutation": 0.3
+        });
+        let inner_factory = track!(registry.create_solver_factory_from_json(&inner))?;
+        assert!(problem
+            .check_capabilities(&track!(inner_factory.specification())?)
+            .is_err());
+
+        let factory = track!(FlattenSolverRecipe { inner }.create_factory(&registry))?;
+        let spec = track!(factory.specification())?;
+        assert_eq!(spec.name, "NSGA-II with Flattened Conditions");
+        assert_eq!(
+            spec.attrs.get("conditions").map(|s| s.as_str()),
+            Some("flattened")
+        );
+        track!(problem.check_capabilities(&spec))?;
+
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &problem))?;
+        let flattened = track!(run(&mut solver, &problem, 300))?;
+        assert!(solver.masked_params() > 0);
+        let attrs = track!(solver.finalize())?;
+        assert_eq!(
+            attrs.get("masked_params"),
+            Some(&solver.masked_params().to_string())
+        );
+
+        // The active variables of each kernel are the same as those of a natively conditional solver.
+        let random = track!(RandomSolverRecipe::from_iter(&["random"]).create_factory(&registry))?;
+        let mut random = track!(random.create_solver(ArcRng::new(0), &problem))?;
+        let native = track!(run(&mut random, &problem, 300))?;
+        assert_eq!(active_sets(&flattened), active_sets(&native));
+        assert_eq!(
+            active_sets(&native),
+            [
+                [true, true, false, false],
+                [true, true, true, false],
+                [true, true, true, true]
+            ]
+        );
+        Ok(())
+    }
+}
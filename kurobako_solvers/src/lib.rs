@@ -5,9 +5,15 @@
 extern crate trackable;
 
 pub mod asha;
+pub mod epsilon;
+pub mod flatten;
+pub mod nelder_mead;
 pub mod nsga2;
+#[cfg(feature = "external-process")]
 pub mod optuna;
 pub mod random;
+pub mod restart;
+pub mod scalarize;
 
 mod error;
 mod yamakan_utils;
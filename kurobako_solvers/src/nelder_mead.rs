@@ -0,0 +1,431 @@
+//! A solver based on the (adaptive) Nelder-Mead simplex algorithm.
+//!
+//! Only continuous numerical parameters are supported and the search is performed in the raw parameter space.
+//!
+//! # References
+//!
+//! - [Implementing the Nelder-Mead simplex algorithm with adaptive parameters](https://link.springer.com/article/10.1007/s10589-010-9329-3)
+use crate::error::from_yamakan;
+use crate::yamakan_utils::YamakanIdGen;
+use kurobako_core::num::OrderedFloat;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    Capabilities, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec, SolverSpecBuilder,
+};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params, TrialId};
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use structopt::StructOpt;
+use yamakan::domains::ContinuousDomain;
+use yamakan::optimizers::nelder_mead;
+use yamakan::{Obs, ObsId, Optimizer};
+
+type NelderMeadOptimizer = nelder_mead::NelderMeadOptimizer<OrderedFloat<f64>>;
+
+/// Recipe of `NelderMeadSolver`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct NelderMeadSolverRecipe {
+    /// How to place the initial simplex.
+    #[structopt(
+        long,
+        default_value = "random",
+        possible_values = NelderMeadInit::POSSIBLE_VALUES
+    )]
+    #[serde(default)]
+    init: NelderMeadInit,
+
+    /// Coordinates of the first vertex of the initial simplex (`NAME=VALUE`; used if `--init given`).
+    ///
+    /// This needs to be specified for every parameter of the problem.
+    #[structopt(long = "init-point", number_of_values = 1)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    init_point: Vec<InitCoordinate>,
+
+    /// Spread of the initial simplex as a fraction of the range of each parameter.
+    #[structopt(long, default_value = "0.1")]
+    #[serde(default = "default_init_scale")]
+    init_scale: f64,
+}
+impl SolverRecipe for NelderMeadSolverRecipe {
+    type Factory = NelderMeadSolverFactory;
+
+    fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(
+            self.init_scale > 0.0 && self.init_scale <= 1.0,
+            ErrorKind::InvalidInput,
+            "`--init-scale` must be in (0, 1]: {}",
+            self.init_scale
+        );
+        if self.init == NelderMeadInit::Given {
+            track_assert!(
+                !self.init_point.is_empty(),
+                ErrorKind::InvalidInput,
+                "`--init given` requires `--init-point`"
+            );
+        } else {
+            track_assert!(
+                self.init_point.is_empty(),
+                ErrorKind::InvalidInput,
+                "`--init-point` is only available with `--init given`"
+            );
+        }
+        for (i, c) in self.init_point.iter().enumerate() {
+            track_assert!(
+                self.init_point[..i].iter().all(|d| d.name != c.name),
+                ErrorKind::InvalidInput,
+                "Duplicate `--init-point` variable: {:?}",
+                c.name
+            );
+        }
+
+        Ok(NelderMeadSolverFactory {
+            init: self.init,
+            init_point: self.init_point.clone(),
+            init_scale: self.init_scale,
+        })
+    }
+}
+
+fn default_init_scale() -> f64 {
+    0.1
+}
+
+/// Initialization method of the simplex of `NelderMeadSolver`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NelderMeadInit {
+    /// The first vertex is sampled uniformly at random from the search space.
+    #[default]
+    Random,
+
+    /// The first vertex is the center of the search space.
+    CentroidPlusAxis,
+
+    /// The first vertex is the point given by `--init-point`.
+    Given,
+}
+impl NelderMeadInit {
+    /// Possible values of this enum.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["random", "centroid-plus-axis", "given"];
+}
+impl FromStr for NelderMeadInit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "random" => Ok(Self::Random),
+            "centroid-plus-axis" => Ok(Self::CentroidPlusAxis),
+            "given" => Ok(Self::Given),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown initialization: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for NelderMeadInit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Random => write!(f, "random"),
+            Self::CentroidPlusAxis => write!(f, "centroid-plus-axis"),
+            Self::Given => write!(f, "given"),
+        }
+    }
+}
+
+/// Coordinate of the initial point of `NelderMeadSolver`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InitCoordinate {
+    /// Variable name.
+    pub name: String,
+
+    /// Coordinate value.
+    pub value: f64,
+}
+impl FromStr for InitCoordinate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = s.splitn(2, '=');
+        let name = tokens.next().unwrap_or_else(|| unreachable!());
+        let value = track_assert_some!(
+            tokens.next(),
+            ErrorKind::InvalidInput,
+            "Expected `NAME=VALUE`: {:?}",
+            s
+        );
+        track_assert!(
+            !name.is_empty(),
+            ErrorKind::InvalidInput,
+            "Empty variable name: {:?}",
+            s
+        );
+        let value: f64 = track!(value.parse().map_err(Error::from); s)?;
+        track_assert!(
+            value.is_finite(),
+            ErrorKind::InvalidInput,
+            "Non-finite coordinate: {:?}",
+            s
+        );
+        Ok(Self {
+            name: name.to_owned(),
+            value,
+        })
+    }
+}
+
+/// Factory of `NelderMeadSolver`.
+#[derive(Debug)]
+pub struct NelderMeadSolverFactory {
+    init: NelderMeadInit,
+    init_point: Vec<InitCoordinate>,
+    init_scale: f64,
+}
+impl NelderMeadSolverFactory {
+    fn first_vertex(&self, rng: &mut ArcRng, problem: &ProblemSpec) -> Result<Vec<f64>> {
+        let vars = problem.params_domain.variables();
+        match self.init {
+            NelderMeadInit::Random => Ok(vars
+                .iter()
+                .map(|v| v.range().sample(v.distribution(), rng))
+                .collect()),
+            NelderMeadInit::CentroidPlusAxis => Ok(vars
+                .iter()
+                .map(|v| (v.range().low() + v.range().high()) / 2.0)
+                .collect()),
+            NelderMeadInit::Given => {
+                for c in &self.init_point {
+                    track_assert!(
+                        problem.params_domain.index_of(&c.name).is_some(),
+                        ErrorKind::InvalidInput,
+                        "Unknown variable in `--init-point`: {:?}",
+                        c.name
+                    );
+                }
+                vars.iter()
+                    .map(|v| {
+                        let c = self.init_point.iter().find(|c| c.name == v.name());
+                        let c = track_assert_some!(
+                            c,
+                            ErrorKind::InvalidInput,
+                            "`--init-point` lacks the variable {:?}",
+                            v.name()
+                        );
+                        track_assert!(
+                            v.range().contains(c.value),
+                            ErrorKind::InvalidInput,
+                            "`--init-point` is out of the range of {:?}: value={}, range={:?}",
+                            v.name(),
+                            c.value,
+                            v.range()
+                        );
+                        Ok(c.value)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+impl SolverFactory for NelderMeadSolverFactory {
+    type Solver = NelderMeadSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let spec = SolverSpecBuilder::new("Nelder-Mead")
+            .attr(
+                "version",
+                &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("init", &self.init.to_string())
+            .attr("init_scale", &self.init_scale.to_string())
+            .capabilities(Capabilities::new(
+                [
+                    Capability::UniformContinuous,
+                    Capability::LogUniformContinuous,
+                ]
+                .iter()
+                .copied(),
+            ));
+        Ok(spec.finish())
+    }
+
+    fn create_solver(&self, mut rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let vars = problem.params_domain.variables();
+        let params_domain = vars
+            .iter()
+            .map(|v| {
+                track!(ContinuousDomain::new(v.range().low(), v.range().high())
+                    .map_err(from_yamakan); v.name())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let first = track!(self.first_vertex(&mut rng, problem))?;
+
+        // The optimizer asks the vertices from the last one.
+        let mut simplex = Vec::with_capacity(vars.len() + 1);
+        for (i, d) in params_domain.iter().enumerate().rev() {
+            let mut x = first.clone();
+            let delta = d.size() * self.init_scale;
+            x[i] = if x[i] + delta < d.high() {
+                x[i] + delta
+            } else {
+                x[i] - delta
+            };
+            simplex.push(x);
+        }
+        simplex.push(first);
+
+        let optimizer = track!(
+            NelderMeadOptimizer::with_initial_simplex(params_domain, simplex).map_err(from_yamakan)
+        )?;
+        Ok(NelderMeadSolver {
+            rng,
+            problem: problem.clone(),
+            optimizer,
+            evaluating: None,
+        })
+    }
+}
+
+/// Solver based on the Nelder-Mead simplex algorithm.
+#[derive(Debug)]
+pub struct NelderMeadSolver {
+    rng: ArcRng,
+    problem: ProblemSpec,
+    optimizer: NelderMeadOptimizer,
+    evaluating: Option<(TrialId, Vec<f64>)>,
+}
+impl Solver for NelderMeadSolver {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        let mut idg = YamakanIdGen(idg);
+        let obs = track!(self
+            .optimizer
+            .ask(&mut self.rng, &mut idg)
+            .map_err(from_yamakan))?;
+
+        let trial = NextTrial {
+            id: TrialId::new(obs.id.get()),
+            params: Params::new(obs.param.clone()),
+            next_step: Some(self.problem.steps.last()),
+            fidelity: Params::default(),
+            asked_params: None,
+        };
+        self.evaluating = Some((trial.id, obs.param));
+
+        Ok(trial)
+    }
+
+    fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+        let (id, param) = track_assert_some!(self.evaluating.take(), ErrorKind::InvalidInput);
+        track_assert_eq!(id, trial.id, ErrorKind::InvalidInput);
+
+        // Unevaluable params are regarded as the worst ones.
+        let value = trial
+            .values
+            .into_vec()
+            .first()
+            .copied()
+            .unwrap_or(f64::INFINITY);
+        let obs = Obs {
+            id: ObsId::new(id.get()),
+            param,
+            value: OrderedFloat(value),
+        };
+        track!(self.optimizer.tell(obs).map_err(from_yamakan))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use kurobako_core::trial::Values;
+    use trackable::result::TopLevelResult;
+
+    fn problem() -> Result<ProblemSpec> {
+        let spec = ProblemSpecBuilder::new("Foo")
+            .param(var("x").continuous(0.0, 10.0))
+            .param(var("y").continuous(-1.0, 1.0))
+            .value(var("v"));
+        track!(spec.finish())
+    }
+
+    fn registry() -> FactoryRegistry {
+        FactoryRegistry::new::<ExternalProgramProblemRecipe, NelderMeadSolverRecipe>()
+    }
+
+    fn recipe(init: NelderMeadInit, init_point: &[&str]) -> Result<NelderMeadSolverRecipe> {
+        Ok(NelderMeadSolverRecipe {
+            init,
+            init_point: init_point
+                .iter()
+                .map(|s| track!(s.parse()))
+                .collect::<Result<_>>()?,
+            init_scale: 0.1,
+        })
+    }
+
+    #[test]
+    fn given_init_works() -> TopLevelResult {
+        let recipe = track!(recipe(NelderMeadInit::Given, &["y=0.5", "x=2.0"]))?;
+        let factory = track!(recipe.create_factory(&registry()))?;
+        let spec = track!(factory.specification())?;
+        assert_eq!(spec.attrs.get("init").map(|s| s.as_str()), Some("given"));
+        assert_eq!(
+            spec.attrs.get("init_scale").map(|s| s.as_str()),
+            Some("0.1")
+        );
+
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &track!(problem())?))?;
+        let mut idg = IdGen::new();
+        let trial = track!(solver.ask(&mut idg))?;
+        assert_eq!(trial.params.get(), [2.0, 0.5]);
+
+        // The other vertices are shifted along each axis by 10% of the range.
+        track!(solver.tell(trial.evaluated(Values::new(vec![1.0]), 1)))?;
+        let trial = track!(solver.ask(&mut idg))?;
+        assert_eq!(trial.params.get(), [3.0, 0.5]);
+        track!(solver.tell(trial.evaluated(Values::new(vec![1.0]), 1)))?;
+        let trial = track!(solver.ask(&mut idg))?;
+        assert_eq!(trial.params.get(), [2.0, 0.7]);
+        Ok(())
+    }
+
+    #[test]
+    fn centroid_plus_axis_init_works() -> TopLevelResult {
+        let recipe = track!(recipe(NelderMeadInit::CentroidPlusAxis, &[]))?;
+        let factory = track!(recipe.create_factory(&registry()))?;
+        let mut solver = track!(factory.create_solver(ArcRng::new(0), &track!(problem())?))?;
+        let trial = track!(solver.ask(&mut IdGen::new()))?;
+        assert_eq!(trial.params.get(), [5.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_init_point_is_rejected() -> TopLevelResult {
+        let problem = track!(problem())?;
+        let registry = registry();
+        for point in &[
+            &["x=10.0", "y=0.0"][..],
+            &["x=-0.5", "y=0.0"][..],
+            &["x=1.0"][..],
+            &["x=1.0", "y=0.0", "z=0.0"][..],
+        ] {
+            let recipe = track!(recipe(NelderMeadInit::Given, point))?;
+            let factory = track!(recipe.create_factory(&registry))?;
+            assert!(factory.create_solver(ArcRng::new(0), &problem).is_err());
+        }
+
+        assert!(track!(recipe(NelderMeadInit::Given, &[]))?
+            .create_factory(&registry)
+            .is_err());
+        assert!(track!(recipe(NelderMeadInit::Random, &["x=1.0"]))?
+            .create_factory(&registry)
+            .is_err());
+        assert!("x".parse::<InitCoordinate>().is_err());
+        assert!("x=inf".parse::<InitCoordinate>().is_err());
+        Ok(())
+    }
+}
@@ -79,6 +79,7 @@ impl SolverFactory for Nsga2SolverFactory {
             .capabilities(
                 Capabilities::all()
                     .remove_capability(Capability::Conditional)
+                    .remove_capability(Capability::MultiStep)
                     .clone(),
             );
         Ok(spec.finish())
@@ -130,6 +131,8 @@ impl Solver for Nsga2Solver {
             id: TrialId::new(obs.id.get()),
             params: Params::new(obs.param.clone()),
             next_step: Some(self.problem.steps.last()),
+            fidelity: Params::default(),
+            asked_params: None,
         };
         self.evaluatings.insert(trial.id, obs.param);
 
@@ -5,10 +5,12 @@ use kurobako_core::epi::solver::{
 use kurobako_core::problem::ProblemSpec;
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
-use kurobako_core::solver::{Solver, SolverFactory, SolverRecipe, SolverSpec};
+use kurobako_core::solver::{Capability, Solver, SolverFactory, SolverRecipe, SolverSpec};
 use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial};
-use kurobako_core::Result;
+use kurobako_core::{ErrorKind, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 fn add_arg(args: &mut Vec<String>, key: &str, val: &str) {
@@ -84,10 +86,47 @@ pub struct OptunaSolverRecipe {
     #[serde(default, skip_serializing_if = "is_false")]
     pub maximize: bool,
 
+    /// `n_startup_trials` of `TPESampler` (the default is 10).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpe_n_startup_trials: Option<usize>,
+
+    /// `n_ei_candidates` of `TPESampler` (the default is 24).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tpe_n_ei_candidates: Option<usize>,
+
+    /// Enables `multivariate` of `TPESampler`.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tpe_multivariate: bool,
+
+    /// Enables `group` of `TPESampler` (this requires `--tpe-multivariate`).
+    #[structopt(long, requires = "tpe-multivariate")]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tpe_group: bool,
+
+    /// Enables `constant_liar` of `TPESampler`.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tpe_constant_liar: bool,
+
     /// If this is `true`, `Trial.suggest_discrete_uniform()` is used for sampling discrete parameters instead of `Trial.suggest_int()`.
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "is_false")]
     pub use_discrete_uniform: bool,
+
+    /// Python interpreter that executes the Optuna script.
+    ///
+    /// If omitted, `python3` found via `$PATH` is used.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PathBuf>,
+
+    /// Python virtual environment directory (that has Optuna installed) activated before the script is spawned.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venv: Option<PathBuf>,
 }
 impl OptunaSolverRecipe {
     fn build_args(&self) -> Vec<String> {
@@ -112,21 +151,91 @@ impl OptunaSolverRecipe {
         if self.use_discrete_uniform {
             args.push("--use-discrete-uniform".to_owned());
         }
+        if let Some(v) = self.tpe_n_startup_trials {
+            add_arg(&mut args, "--tpe-n-startup-trials", &v.to_string());
+        }
+        if let Some(v) = self.tpe_n_ei_candidates {
+            add_arg(&mut args, "--tpe-n-ei-candidates", &v.to_string());
+        }
+        if self.tpe_multivariate {
+            args.push("--tpe-multivariate".to_owned());
+        }
+        if self.tpe_group {
+            args.push("--tpe-group".to_owned());
+        }
+        if self.tpe_constant_liar {
+            args.push("--tpe-constant-liar".to_owned());
+        }
         args
     }
+
+    fn uses_tpe(&self) -> bool {
+        self.sampler.as_deref().is_none_or(|s| s == "TPESampler")
+    }
+
+    fn has_tpe_options(&self) -> bool {
+        self.tpe_n_startup_trials.is_some()
+            || self.tpe_n_ei_candidates.is_some()
+            || self.tpe_multivariate
+            || self.tpe_group
+            || self.tpe_constant_liar
+    }
+
+    /// Returns the `TPESampler` options (with Optuna's defaults for the unspecified ones).
+    fn tpe_attrs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "tpe_n_startup_trials",
+                self.tpe_n_startup_trials.unwrap_or(10).to_string(),
+            ),
+            (
+                "tpe_n_ei_candidates",
+                self.tpe_n_ei_candidates.unwrap_or(24).to_string(),
+            ),
+            ("tpe_multivariate", self.tpe_multivariate.to_string()),
+            ("tpe_group", self.tpe_group.to_string()),
+            ("tpe_constant_liar", self.tpe_constant_liar.to_string()),
+        ]
+    }
 }
 impl SolverRecipe for OptunaSolverRecipe {
     type Factory = OptunaSolverFactory;
 
     fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(
+            self.uses_tpe() || !self.has_tpe_options(),
+            ErrorKind::InvalidInput,
+            "`--tpe-*` options are only available with `TPESampler`: sampler={:?}",
+            self.sampler
+        );
+        track_assert!(
+            self.tpe_multivariate || !self.tpe_group,
+            ErrorKind::InvalidInput,
+            "`--tpe-group` requires `--tpe-multivariate`"
+        );
+
         let script = include_str!("../scripts/optuna_solver.py");
         let args = self.build_args();
         let recipe = EmbeddedScriptSolverRecipe {
             script: script.to_owned(),
             args,
+            python: self.python.clone(),
+            venv: self.venv.clone(),
         };
         let inner = track!(recipe.create_factory(registry))?;
-        Ok(OptunaSolverFactory { inner })
+
+        // The script uses `MedianPruner` by default.
+        let prunes = self.pruner.as_deref() != Some("NopPruner");
+        let attrs = if self.uses_tpe() {
+            self.tpe_attrs()
+        } else {
+            Vec::new()
+        };
+        Ok(OptunaSolverFactory {
+            inner,
+            prunes,
+            attrs,
+        })
     }
 }
 
@@ -134,12 +243,21 @@ impl SolverRecipe for OptunaSolverRecipe {
 #[derive(Debug)]
 pub struct OptunaSolverFactory {
     inner: EmbeddedScriptSolverFactory,
+    prunes: bool,
+    attrs: Vec<(&'static str, String)>,
 }
 impl SolverFactory for OptunaSolverFactory {
     type Solver = OptunaSolver;
 
     fn specification(&self) -> Result<SolverSpec> {
-        track!(self.inner.specification())
+        let mut spec = track!(self.inner.specification())?;
+        if self.prunes {
+            spec.capabilities.add_capability(Capability::MultiStep);
+        }
+        for (k, v) in &self.attrs {
+            spec.attrs.insert((*k).to_owned(), v.clone());
+        }
+        Ok(spec)
     }
 
     fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
@@ -161,4 +279,57 @@ impl Solver for OptunaSolver {
     fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
         track!(self.inner.tell(trial))
     }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        track!(self.inner.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+
+    #[test]
+    fn tpe_options_work() {
+        let recipe = OptunaSolverRecipe::from_iter([
+            "optuna",
+            "--tpe-n-startup-trials",
+            "5",
+            "--tpe-multivariate",
+            "--tpe-group",
+        ]);
+        let args = recipe.build_args();
+        assert!(args.ends_with(&[
+            "--tpe-n-startup-trials".to_owned(),
+            "5".to_owned(),
+            "--tpe-multivariate".to_owned(),
+            "--tpe-group".to_owned()
+        ]));
+        assert_eq!(
+            recipe.tpe_attrs()[..2],
+            [
+                ("tpe_n_startup_trials", "5".to_owned()),
+                ("tpe_n_ei_candidates", "24".to_owned())
+            ]
+        );
+
+        // Unspecified options are omitted from the serialized recipe.
+        let json = serde_json::to_value(OptunaSolverRecipe::from_iter(["optuna"])).unwrap();
+        assert_eq!(json, serde_json::json!({}));
+
+        // The options are validated before the script is spawned.
+        let registry = FactoryRegistry::new::<ExternalProgramProblemRecipe, OptunaSolverRecipe>();
+        let recipe = OptunaSolverRecipe::from_iter([
+            "optuna",
+            "--sampler",
+            "RandomSampler",
+            "--tpe-constant-liar",
+        ]);
+        assert!(recipe.create_factory(&registry).is_err());
+
+        let json = serde_json::json!({"tpe_group": true});
+        let recipe: OptunaSolverRecipe = serde_json::from_value(json).unwrap();
+        assert!(recipe.create_factory(&registry).is_err());
+    }
 }
@@ -3,11 +3,12 @@ use kurobako_core::problem::ProblemSpec;
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
 use kurobako_core::solver::{
-    Capabilities, Solver, SolverFactory, SolverRecipe, SolverSpec, SolverSpecBuilder,
+    Capabilities, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec, SolverSpecBuilder,
 };
 use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params};
 use kurobako_core::{ErrorKind, Result};
 use rand::distributions::Distribution as _;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
@@ -23,6 +24,13 @@ pub struct RandomSolverRecipe {
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "is_false")]
     ask_all_steps: bool,
+
+    /// If this flag is set, this solver also samples fidelity values from the fidelity domain of the problem.
+    ///
+    /// Otherwise, parameters are always evaluated at the highest fidelity.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    sample_fidelity: bool,
 }
 impl SolverRecipe for RandomSolverRecipe {
     type Factory = RandomSolverFactory;
@@ -30,6 +38,7 @@ impl SolverRecipe for RandomSolverRecipe {
     fn create_factory(&self, _registry: &FactoryRegistry) -> Result<Self::Factory> {
         Ok(RandomSolverFactory {
             ask_all_steps: self.ask_all_steps,
+            sample_fidelity: self.sample_fidelity,
         })
     }
 }
@@ -38,17 +47,23 @@ impl SolverRecipe for RandomSolverRecipe {
 #[derive(Debug)]
 pub struct RandomSolverFactory {
     ask_all_steps: bool,
+    sample_fidelity: bool,
 }
 impl SolverFactory for RandomSolverFactory {
     type Solver = RandomSolver;
 
     fn specification(&self) -> Result<SolverSpec> {
+        let mut capabilities = Capabilities::all();
+        if !self.ask_all_steps {
+            // Trials are always evaluated at the last step.
+            capabilities.remove_capability(Capability::MultiStep);
+        }
         let spec = SolverSpecBuilder::new("Random")
             .attr(
                 "version",
                 &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
             )
-            .capabilities(Capabilities::all());
+            .capabilities(capabilities);
         Ok(spec.finish())
     }
 
@@ -57,6 +72,7 @@ impl SolverFactory for RandomSolverFactory {
             problem: problem.clone(),
             rng,
             current_step: if self.ask_all_steps { Some(0) } else { None },
+            sample_fidelity: self.sample_fidelity,
         })
     }
 }
@@ -67,14 +83,11 @@ pub struct RandomSolver {
     rng: ArcRng,
     problem: ProblemSpec,
     current_step: Option<u64>,
+    sample_fidelity: bool,
 }
 impl Solver for RandomSolver {
     fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
-        let mut params = Vec::new();
-        for p in self.problem.params_domain.variables() {
-            let param = p.sample(&mut self.rng);
-            params.push(param);
-        }
+        let params = sample_params(&self.problem, &mut self.rng);
 
         let next_step = if let Some(current_step) = self.current_step {
             let step = self.problem.steps.iter().find(|&s| s > current_step);
@@ -82,10 +95,21 @@ impl Solver for RandomSolver {
         } else {
             self.problem.steps.last()
         };
+
+        let rng = &mut self.rng;
+        let fidelity = match &self.problem.fidelity_domain {
+            Some(domain) if self.sample_fidelity => {
+                domain.variables().iter().map(|v| v.sample(rng)).collect()
+            }
+            _ => Vec::new(),
+        };
+
         Ok(NextTrial {
             id: idg.generate(),
-            params: Params::new(params),
+            params,
             next_step: Some(next_step),
+            fidelity: Params::new(fidelity),
+            asked_params: None,
         })
     }
 
@@ -96,3 +120,21 @@ impl Solver for RandomSolver {
         Ok(())
     }
 }
+
+/// Samples parameters uniformly at random in accordance with the distributions of the variables.
+///
+/// Variables whose conditions are not satisfied are set to `NaN`.
+pub(crate) fn sample_params<R: Rng + ?Sized>(problem: &ProblemSpec, rng: &mut R) -> Params {
+    let vars = problem.params_domain.variables();
+    let mut params = Vec::new();
+    for p in vars {
+        let active = if let Some(condition) = p.condition() {
+            condition.is_satisfied(vars, &params)
+        } else {
+            true
+        };
+        let param = if active { p.sample(rng) } else { f64::NAN };
+        params.push(param);
+    }
+    Params::new(params)
+}
@@ -0,0 +1,286 @@
+//! A solver that restarts its inner solver periodically (i.e., multi-start).
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::num::OrderedFloat;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    BoxSolver, BoxSolverFactory, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec,
+    SolverSpecBuilder,
+};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params, TrialId};
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Recipe of `RestartSolver`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct RestartSolverRecipe {
+    /// Number of the completed trials after which the inner solver is recreated.
+    #[structopt(long)]
+    pub every: u64,
+
+    /// If this flag is set, the parameters of the best trial so far are proposed again
+    /// as the first trial of each restarted solver.
+    ///
+    /// As the result of a trial can only be told to the solver that asked it,
+    /// the re-proposed trial is evaluated but its result is not told to the inner solver.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub keep_best: bool,
+
+    /// Recipe of the inner solver.
+    pub inner: JsonRecipe,
+}
+impl SolverRecipe for RestartSolverRecipe {
+    type Factory = RestartSolverFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(self.every > 0, ErrorKind::InvalidInput; self.every);
+        let inner = track!(registry.create_solver_factory_from_json(&self.inner))?;
+        Ok(RestartSolverFactory {
+            every: self.every,
+            keep_best: self.keep_best,
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+}
+
+/// Factory of `RestartSolver`.
+#[derive(Debug)]
+pub struct RestartSolverFactory {
+    every: u64,
+    keep_best: bool,
+    inner: Arc<Mutex<BoxSolverFactory>>,
+}
+impl SolverFactory for RestartSolverFactory {
+    type Solver = RestartSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let mut inner = track!(track!(self.inner.lock().map_err(Error::from))?.specification())?;
+        if self.keep_best {
+            // The best trial is determined by the first objective.
+            inner
+                .capabilities
+                .remove_capability(Capability::MultiObjective);
+        }
+
+        let spec = SolverSpecBuilder::new(&format!("{} with Restarts", inner.name))
+            .attr(
+                "version",
+                &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("every", &self.every.to_string())
+            .attr("keep_best", &self.keep_best.to_string())
+            .capabilities(inner.capabilities);
+        Ok(spec.finish())
+    }
+
+    fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let inner = track!(
+            track!(self.inner.lock().map_err(Error::from))?.create_solver(rng.clone(), problem)
+        )?;
+        Ok(RestartSolver {
+            factory: Arc::clone(&self.inner),
+            rng,
+            problem: problem.clone(),
+            every: self.every,
+            keep_best: self.keep_best,
+            inner,
+            generation: 0,
+            completed: 0,
+            trials: HashMap::new(),
+            best: None,
+            pending_best: None,
+        })
+    }
+}
+
+/// A solver that restarts its inner solver every time the given number of trials are completed.
+///
+/// Trials asked by the previous generations of the inner solver may be told after a restart
+/// (e.g., if they were being evaluated concurrently); their results are only used to
+/// count the completed trials and to update the best trial.
+#[derive(Debug)]
+pub struct RestartSolver {
+    factory: Arc<Mutex<BoxSolverFactory>>,
+    rng: ArcRng,
+    problem: ProblemSpec,
+    every: u64,
+    keep_best: bool,
+    inner: BoxSolver,
+    generation: u64,
+    completed: u64,
+    trials: HashMap<TrialId, (Option<u64>, Params)>,
+    best: Option<(OrderedFloat<f64>, Params)>,
+    pending_best: Option<Params>,
+}
+impl RestartSolver {
+    /// Returns the number of the restarts made so far.
+    pub fn restarts(&self) -> u64 {
+        self.generation
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        let factory = track!(self.factory.lock().map_err(Error::from))?;
+        self.inner = track!(factory.create_solver(self.rng.clone(), &self.problem))?;
+        drop(factory);
+        self.generation += 1;
+        self.completed = 0;
+        if self.keep_best {
+            self.pending_best = self.best.as_ref().map(|(_, params)| params.clone());
+        }
+        Ok(())
+    }
+}
+impl Solver for RestartSolver {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        if let Some(params) = self.pending_best.take() {
+            let trial = NextTrial {
+                id: idg.generate(),
+                params,
+                next_step: Some(self.problem.steps.last()),
+                fidelity: Params::default(),
+                asked_params: None,
+            };
+            self.trials.insert(trial.id, (None, trial.params.clone()));
+            return Ok(trial);
+        }
+
+        let trial = track!(self.inner.ask(idg))?;
+        if trial.next_step.is_some() {
+            self.trials
+                .insert(trial.id, (Some(self.generation), trial.params.clone()));
+        } else {
+            // Pruned trials are never told.
+            self.trials.remove(&trial.id);
+        }
+        Ok(trial)
+    }
+
+    fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+        let (generation, params) = track_assert_some!(
+            self.trials.get(&trial.id).cloned(),
+            ErrorKind::InvalidInput,
+            "Unknown trial: {:?}",
+            trial.id
+        );
+        let is_completed =
+            trial.values.is_empty() || trial.current_step >= self.problem.steps.last();
+        if is_completed {
+            self.trials.remove(&trial.id);
+            if let Some(&value) = trial.values.first() {
+                let value = OrderedFloat(value);
+                if self.best.as_ref().is_none_or(|(best, _)| value < *best) {
+                    self.best = Some((value, params));
+                }
+            }
+        }
+
+        if generation == Some(self.generation) {
+            track!(self.inner.tell(trial))?;
+        }
+
+        if is_completed {
+            self.completed += 1;
+            if self.completed >= self.every {
+                track!(self.restart())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        let mut attrs = track!(self.inner.finalize())?;
+        attrs.insert("restarts".to_owned(), self.restarts().to_string());
+        Ok(attrs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nsga2::Nsga2SolverRecipe;
+    use kurobako_core::domain::var;
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use kurobako_core::trial::Values;
+    use trackable::result::TopLevelResult;
+
+    fn solver(keep_best: bool) -> Result<RestartSolver> {
+        let registry = FactoryRegistry::new::<ExternalProgramProblemRecipe, Nsga2SolverRecipe>();
+        let recipe = RestartSolverRecipe {
+            every: 2,
+            keep_best,
+            // NSGA-II rejects the results of trials that it didn't ask.
+            inner: serde_json::json!({
+                "population": 2, "tournament": 2, "crossover": 0.5, "mutation": 0.3
+            }),
+        };
+        let factory = track!(recipe.create_factory(&registry))?;
+        let problem = track!(ProblemSpecBuilder::new("Foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"))
+            .finish())?;
+        track!(factory.create_solver(ArcRng::new(0), &problem))
+    }
+
+    fn tell(solver: &mut RestartSolver, trial: &NextTrial, value: f64) -> Result<()> {
+        track!(solver.tell(trial.evaluated(Values::new(vec![value]), 1)))
+    }
+
+    #[test]
+    fn restart_works() -> TopLevelResult {
+        let mut solver = track!(solver(false))?;
+        let mut idg = IdGen::new();
+
+        // The trials asked before a restart can be told after it.
+        let t0 = track!(solver.ask(&mut idg))?;
+        let t1 = track!(solver.ask(&mut idg))?;
+        let t2 = track!(solver.ask(&mut idg))?;
+        track!(tell(&mut solver, &t0, 3.0))?;
+        track!(tell(&mut solver, &t1, 2.0))?;
+        assert_eq!(solver.restarts(), 1);
+        let t3 = track!(solver.ask(&mut idg))?;
+        track!(tell(&mut solver, &t2, 1.0))?;
+        track!(tell(&mut solver, &t3, 4.0))?;
+        assert_eq!(solver.restarts(), 2);
+
+        assert!(solver
+            .tell(t0.evaluated(Values::new(vec![0.0]), 1))
+            .is_err());
+        let attrs = track!(solver.finalize())?;
+        assert_eq!(attrs.get("restarts").map(|s| s.as_str()), Some("2"));
+        Ok(())
+    }
+
+    #[test]
+    fn keep_best_works() -> TopLevelResult {
+        let mut solver = track!(solver(true))?;
+        let mut idg = IdGen::new();
+
+        let t0 = track!(solver.ask(&mut idg))?;
+        let t1 = track!(solver.ask(&mut idg))?;
+        track!(tell(&mut solver, &t1, 1.0))?;
+        track!(tell(&mut solver, &t0, 2.0))?;
+        assert_eq!(solver.restarts(), 1);
+
+        // The best trial is proposed again by the restarted solver.
+        let t2 = track!(solver.ask(&mut idg))?;
+        assert_ne!(t2.id, t1.id);
+        assert_eq!(t2.params, t1.params);
+        track!(tell(&mut solver, &t2, 1.0))?;
+        let t3 = track!(solver.ask(&mut idg))?;
+        track!(tell(&mut solver, &t3, 5.0))?;
+        assert_eq!(solver.restarts(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+//! A solver that tackles multi-objective problems with a single-objective inner solver via scalarization.
+use kurobako_core::domain::{var, Domain};
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    BoxSolver, BoxSolverFactory, Capability, Solver, SolverFactory, SolverRecipe, SolverSpec,
+    SolverSpecBuilder,
+};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, TrialId, Values};
+use kurobako_core::{Error, ErrorKind, Result};
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Recipe of `ScalarizeSolver`.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ScalarizeSolverRecipe {
+    /// Scalarization method.
+    #[structopt(
+        long,
+        default_value = "weighted-sum",
+        possible_values = ScalarizeMethod::POSSIBLE_VALUES
+    )]
+    #[serde(default)]
+    pub method: ScalarizeMethod,
+
+    /// Weights of the objectives (e.g., `--weights 0.3,0.7`).
+    ///
+    /// If omitted, all the objectives are weighted equally.
+    #[structopt(long, use_delimiter = true, require_delimiter = true)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<f64>>,
+
+    /// If this flag is set, the weights are sampled uniformly from the simplex for each trial
+    /// (this approximates the pareto front rather than a single point on it).
+    #[structopt(long, conflicts_with = "weights")]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub random_weights: bool,
+
+    /// Recipe of the inner solver.
+    pub inner: JsonRecipe,
+}
+impl SolverRecipe for ScalarizeSolverRecipe {
+    type Factory = ScalarizeSolverFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        if let Some(weights) = &self.weights {
+            track_assert!(!self.random_weights, ErrorKind::InvalidInput);
+            track_assert!(
+                weights.iter().all(|w| w.is_finite() && *w >= 0.0)
+                    && weights.iter().any(|w| *w > 0.0),
+                ErrorKind::InvalidInput; weights
+            );
+        }
+        let inner = track!(registry.create_solver_factory_from_json(&self.inner))?;
+        Ok(ScalarizeSolverFactory {
+            method: self.method,
+            weights: self.weights.clone(),
+            random_weights: self.random_weights,
+            inner,
+        })
+    }
+}
+
+/// Scalarization method of `ScalarizeSolver`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScalarizeMethod {
+    /// `sum_i w_i * f_i`.
+    #[default]
+    WeightedSum,
+
+    /// `max_i w_i * (f_i - z_i)` where `z` is the best value of each objective observed so far.
+    Tchebycheff,
+}
+impl ScalarizeMethod {
+    /// Possible values of this enum.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["weighted-sum", "tchebycheff"];
+
+    /// Returns the scalarized value of `values` under the given weights and ideal point.
+    pub fn scalarize(self, weights: &[f64], values: &[f64], ideal: &[f64]) -> f64 {
+        let terms = weights.iter().zip(values.iter()).zip(ideal.iter());
+        match self {
+            Self::WeightedSum => terms.map(|((w, f), _)| w * f).sum(),
+            Self::Tchebycheff => terms
+                .map(|((w, f), z)| w * (f - z))
+                .fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+impl FromStr for ScalarizeMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "weighted-sum" => Ok(Self::WeightedSum),
+            "tchebycheff" => Ok(Self::Tchebycheff),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown method: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for ScalarizeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WeightedSum => write!(f, "weighted-sum"),
+            Self::Tchebycheff => write!(f, "tchebycheff"),
+        }
+    }
+}
+
+/// Factory of `ScalarizeSolver`.
+#[derive(Debug)]
+pub struct ScalarizeSolverFactory {
+    method: ScalarizeMethod,
+    weights: Option<Vec<f64>>,
+    random_weights: bool,
+    inner: BoxSolverFactory,
+}
+impl SolverFactory for ScalarizeSolverFactory {
+    type Solver = ScalarizeSolver;
+
+    fn specification(&self) -> Result<SolverSpec> {
+        let mut inner = track!(self.inner.specification())?;
+        inner
+            .capabilities
+            .add_capability(Capability::MultiObjective);
+
+        let mut spec = SolverSpecBuilder::new(&format!("{} with Scalarization", inner.name))
+            .attr(
+                "version",
+                &format!("kurobako_solvers={}", env!("CARGO_PKG_VERSION")),
+            )
+            .attr("method", &self.method.to_string())
+            .capabilities(inner.capabilities);
+        if self.random_weights {
+            spec = spec.attr("weights", "random");
+        } else if let Some(weights) = &self.weights {
+            let weights = weights.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+            spec = spec.attr("weights", &weights.join(","));
+        }
+        Ok(spec.finish())
+    }
+
+    fn create_solver(&self, rng: ArcRng, problem: &ProblemSpec) -> Result<Self::Solver> {
+        let objectives = problem.values_domain.len();
+        let weights = if let Some(weights) = &self.weights {
+            track_assert_eq!(weights.len(), objectives, ErrorKind::InvalidInput);
+            weights.clone()
+        } else {
+            vec![1.0 / objectives as f64; objectives]
+        };
+
+        let scalarized = track!(scalarized_problem_spec(problem))?;
+        let inner = track!(self.inner.create_solver(rng.clone(), &scalarized))?;
+        Ok(ScalarizeSolver {
+            method: self.method,
+            weights,
+            random_weights: self.random_weights,
+            rng,
+            inner,
+            ideal: vec![f64::INFINITY; objectives],
+            trial_weights: HashMap::new(),
+        })
+    }
+}
+
+/// Returns the specification of the given problem with its objectives replaced by a single scalarized one.
+fn scalarized_problem_spec(problem: &ProblemSpec) -> Result<ProblemSpec> {
+    let mut scalarized = problem.clone();
+    scalarized.values_domain = track!(Domain::new(vec![var("Scalarized")]))?;
+    scalarized.reference_point = None;
+    Ok(scalarized)
+}
+
+/// A solver that presents a multi-objective problem to its inner solver as a single-objective one.
+///
+/// The objective values told to this solver are converted into the scalarized value before
+/// being told to the inner solver, while the study records keep the original values.
+/// Note that the ideal point used by `ScalarizeMethod::Tchebycheff` is updated as trials are told,
+/// so the scalarized values of earlier trials are not comparable with later ones in the strict sense.
+#[derive(Debug)]
+pub struct ScalarizeSolver {
+    method: ScalarizeMethod,
+    weights: Vec<f64>,
+    random_weights: bool,
+    rng: ArcRng,
+    inner: BoxSolver,
+    ideal: Vec<f64>,
+
+    // The weights of each trial (only used if `random_weights` is `true`).
+    trial_weights: HashMap<TrialId, Vec<f64>>,
+}
+impl ScalarizeSolver {
+    fn sample_weights(&mut self) -> Vec<f64> {
+        // Normalized exponential variates are uniformly distributed on the simplex.
+        let mut weights = (0..self.weights.len())
+            .map(|_| -(1.0 - self.rng.gen::<f64>()).ln())
+            .collect::<Vec<_>>();
+        let sum: f64 = weights.iter().sum();
+        if sum > 0.0 {
+            weights.iter_mut().for_each(|w| *w /= sum);
+            weights
+        } else {
+            self.weights.clone()
+        }
+    }
+}
+impl Solver for ScalarizeSolver {
+    fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+        let trial = track!(self.inner.ask(idg))?;
+        if self.random_weights && !self.trial_weights.contains_key(&trial.id) {
+            let weights = self.sample_weights();
+            self.trial_weights.insert(trial.id, weights);
+        }
+        Ok(trial)
+    }
+
+    fn tell(&mut self, mut trial: EvaluatedTrial) -> Result<()> {
+        if !trial.values.is_empty() {
+            track_assert_eq!(
+                trial.values.len(),
+                self.weights.len(),
+                ErrorKind::InvalidInput
+            );
+            for (z, v) in self.ideal.iter_mut().zip(trial.values.iter()) {
+                *z = z.min(*v);
+            }
+            let weights = self.trial_weights.get(&trial.id).unwrap_or(&self.weights);
+            let value = self.method.scalarize(weights, &trial.values, &self.ideal);
+            trial.values = Values::new(vec![value]);
+        }
+        track!(self.inner.tell(trial))
+    }
+
+    fn finalize(&mut self) -> Result<BTreeMap<String, String>> {
+        track!(self.inner.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nelder_mead::NelderMeadSolverRecipe;
+    use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn scalarize_works() {
+        let weights = [0.25, 0.75];
+        let ideal = [1.0, -1.0];
+        assert_eq!(
+            ScalarizeMethod::WeightedSum.scalarize(&weights, &[2.0, 4.0], &ideal),
+            3.5
+        );
+        assert_eq!(
+            ScalarizeMethod::Tchebycheff.scalarize(&weights, &[5.0, 0.0], &ideal),
+            1.0
+        );
+        assert_eq!(
+            ScalarizeMethod::Tchebycheff.scalarize(&weights, &[1.0, 1.0], &ideal),
+            1.5
+        );
+    }
+
+    #[test]
+    fn scalarize_solver_works() -> TopLevelResult {
+        let registry =
+            FactoryRegistry::new::<ExternalProgramProblemRecipe, NelderMeadSolverRecipe>();
+        let problem = track!(ProblemSpecBuilder::new("Foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .param(var("y").continuous(0.0, 1.0))
+            .value(var("f1"))
+            .value(var("f2"))
+            .finish())?;
+        let scalarized = track!(scalarized_problem_spec(&problem))?;
+        assert_eq!(scalarized.values_domain.len(), 1);
+        assert_eq!(scalarized.params_domain, problem.params_domain);
+
+        for (method, weights, random_weights) in [
+            (ScalarizeMethod::WeightedSum, None, false),
+            (ScalarizeMethod::Tchebycheff, Some(vec![0.2, 0.8]), false),
+            (ScalarizeMethod::Tchebycheff, None, true),
+        ] {
+            let recipe = ScalarizeSolverRecipe {
+                method,
+                weights,
+                random_weights,
+                inner: serde_json::json!({}),
+            };
+            let factory = track!(recipe.create_factory(&registry))?;
+            let spec = track!(factory.specification())?;
+            assert!(spec.capabilities.is_capable(Capability::MultiObjective));
+            track!(problem.check_capabilities(&spec))?;
+
+            let mut solver = track!(factory.create_solver(ArcRng::new(0), &problem))?;
+            let mut idg = IdGen::new();
+            for _ in 0..20 {
+                let trial = track!(solver.ask(&mut idg))?;
+                let (x, y) = (trial.params[0], trial.params[1]);
+                let values = Values::new(vec![x * x + y, (1.0 - x).powi(2) + y]);
+                track!(solver.tell(trial.evaluated(values, 1)))?;
+            }
+            assert!(solver.ideal.iter().all(|z| z.is_finite()));
+            if random_weights {
+                assert_eq!(solver.trial_weights.len(), 20);
+                for weights in solver.trial_weights.values() {
+                    assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+                }
+            }
+        }
+
+        // The number of the weights should be the same as the number of the objectives.
+        let recipe = ScalarizeSolverRecipe {
+            method: ScalarizeMethod::WeightedSum,
+            weights: Some(vec![1.0, 2.0, 3.0]),
+            random_weights: false,
+            inner: serde_json::json!({}),
+        };
+        let factory = track!(recipe.create_factory(&registry))?;
+        assert!(factory.create_solver(ArcRng::new(0), &problem).is_err());
+        Ok(())
+    }
+}
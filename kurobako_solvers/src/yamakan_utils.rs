@@ -32,19 +32,6 @@ impl yamakan::Domain for KurobakoDomain {
 
 impl rand::distributions::Distribution<f64> for KurobakoDomain {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
-        match &self.range {
-            Range::Continuous { low, high } => match self.distribution {
-                Distribution::Uniform => rng.gen_range(*low..*high),
-                Distribution::LogUniform => rng.gen_range(low.log2()..high.log2()).exp2(),
-            },
-            Range::Discrete { low, high } => match self.distribution {
-                Distribution::Uniform => rng.gen_range(*low..*high) as f64,
-                Distribution::LogUniform => rng
-                    .gen_range((*low as f64).log2()..(*high as f64).log2())
-                    .exp2()
-                    .floor(),
-            },
-            Range::Categorical { choices } => rng.gen_range(0..choices.len()) as f64,
-        }
+        self.range.sample(self.distribution, rng)
     }
 }
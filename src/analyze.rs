@@ -0,0 +1,424 @@
+//! `kurobako analyze` command.
+use crate::load::LoadOpt;
+use crate::markdown::{Align, ColumnHeader, MarkdownWriter, Table};
+use crate::record::StudyRecord;
+use crate::report::csv_field;
+use kurobako_core::domain::{Distribution, Domain, Range, Variable};
+use kurobako_core::trial::Params;
+use kurobako_core::{Error, ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Options of the `kurobako analyze` command.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub enum AnalyzeOpt {
+    /// Estimates the importance of each parameter for the objective value of each problem.
+    Importance(ImportanceOpt),
+}
+impl AnalyzeOpt {
+    /// Runs the specified analysis and writes the result to the standard output.
+    pub fn run(&self) -> Result<()> {
+        match self {
+            Self::Importance(opt) => {
+                let studies = track!(opt.load.load_inputs())?;
+                let stdout = io::stdout();
+                track!(opt.write(&studies, stdout.lock()))
+            }
+        }
+    }
+}
+
+/// Options of the `kurobako analyze importance` command.
+///
+/// The importance of a variable is estimated by the variance of the mean objective values
+/// in the bins of the variable (i.e., its first-order effect in the functional ANOVA decomposition),
+/// and normalized so that the importances of all the variables of a problem sum to one.
+/// Numerical variables are divided into bins of the same width over their ranges
+/// (in the log space for log-uniform variables), and each choice of a categorical (or ordinal) variable is a bin.
+/// The parameters of inactive conditional variables fall into their own bin.
+///
+/// The objective values are those at the last step of the problems,
+/// so unfinished (e.g., pruned) trials are ignored. Multi-objective problems are not supported.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ImportanceOpt {
+    /// If specified, only the trials of the solver that has the given name are used.
+    #[structopt(long)]
+    pub solver: Option<String>,
+
+    /// Number of the bins of a numerical variable.
+    #[structopt(long, default_value = "10")]
+    pub bins: NonZeroUsize,
+
+    /// Output format.
+    #[structopt(long, default_value = "markdown", possible_values = OutputFormat::POSSIBLE_VALUES)]
+    pub format: OutputFormat,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
+}
+impl ImportanceOpt {
+    /// Writes the importances of the parameters of the problems of the given studies.
+    pub fn write<W: Write>(&self, studies: &[StudyRecord], mut writer: W) -> Result<()> {
+        track!(self.load.require_all_trials("`analyze importance`"))?;
+        let problems = track!(self.problem_importances(studies))?;
+        match self.format {
+            OutputFormat::Markdown => {
+                let mut writer = MarkdownWriter::new(&mut writer);
+                let mut writer = track!(writer.heading("Parameter Importance"))?;
+                for p in problems {
+                    let mut writer = track!(
+                        writer.heading(&format!("Problem: {} (trials: {})", p.name, p.trials))
+                    )?;
+                    let mut table = Table::new(
+                        vec![
+                            ColumnHeader::new("Variable", Align::Left),
+                            ColumnHeader::new("Importance", Align::Right),
+                        ]
+                        .into_iter(),
+                    );
+                    for v in &p.variables {
+                        table
+                            .row()
+                            .item(&v.name)
+                            .item(format!("{:.3}", v.importance));
+                    }
+                    track!(writer.write_table(&table))?;
+                    track!(writer.newline())?;
+                }
+            }
+            OutputFormat::Csv => {
+                track_writeln!(writer, "problem,variable,importance,trials")?;
+                for p in problems {
+                    for v in &p.variables {
+                        track_writeln!(
+                            writer,
+                            "{},{},{},{}",
+                            csv_field(&p.name),
+                            csv_field(&v.name),
+                            v.importance,
+                            p.trials
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn problem_importances(&self, studies: &[StudyRecord]) -> Result<Vec<ProblemImportance>> {
+        let mut problems = BTreeMap::new();
+        for study in studies {
+            if let Some(solver) = &self.solver {
+                if study.solver.spec.name != *solver {
+                    continue;
+                }
+            }
+            let id = track!(study.problem.id())?;
+            problems
+                .entry((study.problem.spec.name.clone(), id))
+                .or_insert_with(Vec::new)
+                .push(study);
+        }
+
+        let mut importances = Vec::new();
+        for ((name, _), studies) in problems {
+            let spec = &studies[0].problem.spec;
+            if spec.values_domain.len() != 1 {
+                eprintln!(
+                    "Warning: skipped the multi-objective problem {:?} (not supported)",
+                    name
+                );
+                continue;
+            }
+
+            let last_step = spec.steps.last();
+            let domain = &spec.params_domain;
+            let samples = studies
+                .iter()
+                .flat_map(|s| &s.trials)
+                .filter(|t| t.params.len() == domain.len())
+                .filter_map(|t| t.value(last_step).map(|v| (&t.params, v)))
+                .filter(|(_, v)| v.is_finite())
+                .collect::<Vec<_>>();
+            if samples.len() < 2 {
+                eprintln!(
+                    "Warning: skipped the problem {:?} (too few completed trials)",
+                    name
+                );
+                continue;
+            }
+
+            importances.push(ProblemImportance {
+                name,
+                trials: samples.len(),
+                variables: variable_importances(domain, &samples, self.bins.get()),
+            });
+        }
+        Ok(importances)
+    }
+}
+
+/// Output format of an analysis.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// Markdown tables.
+    #[default]
+    Markdown,
+
+    /// CSV.
+    Csv,
+}
+impl OutputFormat {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["markdown", "csv"];
+}
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown" => Ok(Self::Markdown),
+            "csv" => Ok(Self::Csv),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown output format: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Markdown => write!(f, "markdown"),
+            Self::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProblemImportance {
+    name: String,
+    trials: usize,
+    variables: Vec<VariableImportance>,
+}
+
+/// Importance of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableImportance {
+    /// Variable name.
+    pub name: String,
+
+    /// Share of the importance (the importances of all the variables sum to one unless all of them are zero).
+    pub importance: f64,
+}
+
+/// Estimates the importances of the variables of `domain` from the given pairs of parameters and objective values.
+///
+/// See `ImportanceOpt` for the estimation method.
+pub fn variable_importances(
+    domain: &Domain,
+    samples: &[(&Params, f64)],
+    bins: usize,
+) -> Vec<VariableImportance> {
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let variances = domain
+        .variables()
+        .iter()
+        .enumerate()
+        .map(|(i, var)| {
+            let binning = Binning::new(var, samples.iter().map(|(p, _)| p[i]), bins);
+            let mut groups = BTreeMap::<_, (f64, f64)>::new();
+            for (params, y) in samples {
+                let group = groups.entry(binning.bin(params[i])).or_default();
+                group.0 += y;
+                group.1 += 1.0;
+            }
+            groups
+                .values()
+                .map(|(sum, count)| count * (sum / count - mean).powi(2))
+                .sum::<f64>()
+                / n
+        })
+        .collect::<Vec<_>>();
+
+    let total = variances.iter().sum::<f64>();
+    domain
+        .variables()
+        .iter()
+        .zip(variances)
+        .map(|(var, v)| VariableImportance {
+            name: var.name().to_owned(),
+            importance: if total > 0.0 { v / total } else { 0.0 },
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+enum Binning {
+    Choices,
+    Numerical {
+        low: f64,
+        high: f64,
+        log: bool,
+        bins: usize,
+    },
+}
+impl Binning {
+    fn new(var: &Variable, values: impl Iterator<Item = f64>, bins: usize) -> Self {
+        let values = values.filter(|v| v.is_finite()).collect::<Vec<_>>();
+        match var.range() {
+            Range::Categorical { .. } | Range::Ordinal { .. } => Self::Choices,
+            Range::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } if levels(*low, *high, *step, *inclusive_high) <= bins as i64 => Self::Choices,
+            range => {
+                let (mut low, mut high) = (range.low(), range.high());
+                if !low.is_finite() {
+                    low = values.iter().copied().fold(f64::INFINITY, f64::min);
+                }
+                if !high.is_finite() {
+                    high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                }
+                let log = var.distribution() == Distribution::LogUniform && low > 0.0;
+                if log {
+                    low = low.ln();
+                    high = high.ln();
+                }
+                Self::Numerical {
+                    low,
+                    high,
+                    log,
+                    bins,
+                }
+            }
+        }
+    }
+
+    /// Returns the bin of the given value (`None` for the parameters of inactive variables).
+    fn bin(&self, v: f64) -> Option<i64> {
+        if !v.is_finite() {
+            return None;
+        }
+        match *self {
+            Self::Choices => Some(v.round() as i64),
+            Self::Numerical {
+                low,
+                high,
+                log,
+                bins,
+            } => {
+                let v = if log { v.ln() } else { v };
+                let i = if high > low {
+                    ((v - low) / (high - low) * bins as f64).floor()
+                } else {
+                    0.0
+                };
+                Some((i.max(0.0) as i64).min(bins as i64 - 1))
+            }
+        }
+    }
+}
+
+fn levels(low: i64, high: i64, step: i64, inclusive_high: bool) -> i64 {
+    let high = if inclusive_high { high + 1 } else { high };
+    (high - low + step - 1) / step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::rng::{ArcRng, Rng};
+    use trackable::result::TopLevelResult;
+
+    fn importance(importances: &[VariableImportance], name: &str) -> f64 {
+        importances
+            .iter()
+            .find(|v| v.name == name)
+            .map_or(f64::NAN, |v| v.importance)
+    }
+
+    #[test]
+    fn additive_function_works() -> TopLevelResult {
+        let domain = track!(Domain::new(vec![
+            var("x1").continuous(0.0, 1.0),
+            var("x2").continuous(1.0, 100.0).log_uniform(),
+            var("x3").discrete(0, 50),
+            var("c").categorical(["a", "b", "c"]),
+        ]))?;
+
+        // f = 3 * x1 + ln(x2) / ln(100) + 0 * x3 + [0.0, 0.5, 1.0][c]
+        //
+        // The variances of the terms are 9/12, 1/12, 0 and 1/6 (the total is 1).
+        let mut rng = ArcRng::new(0);
+        let params = (0..5000)
+            .map(|_| {
+                Params::new(vec![
+                    rng.gen_range(0.0..1.0),
+                    rng.gen_range(0.0f64..100.0f64.ln()).exp(),
+                    rng.gen_range(0..50) as f64,
+                    rng.gen_range(0..3) as f64,
+                ])
+            })
+            .collect::<Vec<_>>();
+        let samples = params
+            .iter()
+            .map(|p| (p, 3.0 * p[0] + p[1].ln() / 100.0f64.ln() + 0.5 * p[3]))
+            .collect::<Vec<_>>();
+
+        let importances = variable_importances(&domain, &samples, 10);
+        assert!((importance(&importances, "x1") - 0.75).abs() < 0.02);
+        assert!((importance(&importances, "x2") - 1.0 / 12.0).abs() < 0.02);
+        assert!(importance(&importances, "x3") < 0.02);
+        assert!((importance(&importances, "c") - 1.0 / 6.0).abs() < 0.02);
+        let total = importances.iter().map(|v| v.importance).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn inactive_parameters_form_own_bin() -> TopLevelResult {
+        let domain = track!(Domain::new(vec![
+            var("x").continuous(0.0, 1.0),
+            var("y").continuous(0.0, 1.0)
+        ]))?;
+
+        // `y` is inactive in half of the samples, and the objective value only depends on that.
+        let params = (0..100)
+            .map(|i| {
+                let y = if i % 2 == 0 { f64::NAN } else { 0.5 };
+                Params::new(vec![((i / 2) % 10) as f64 / 10.0, y])
+            })
+            .collect::<Vec<_>>();
+        let samples = params
+            .iter()
+            .map(|p| (p, if p[1].is_nan() { 1.0 } else { 0.0 }))
+            .collect::<Vec<_>>();
+
+        let importances = variable_importances(&domain, &samples, 10);
+        assert!(importance(&importances, "x") < 1e-9);
+        assert!((importance(&importances, "y") - 1.0).abs() < 1e-9);
+
+        // Constant objective values.
+        let samples = params.iter().map(|p| (p, 1.0)).collect::<Vec<_>>();
+        let importances = variable_importances(&domain, &samples, 10);
+        assert!(importances.iter().all(|v| v.importance == 0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn output_formats_work() {
+        assert_eq!("csv".parse::<OutputFormat>().ok(), Some(OutputFormat::Csv));
+        assert_eq!(OutputFormat::default().to_string(), "markdown");
+        assert!("json".parse::<OutputFormat>().is_err());
+    }
+}
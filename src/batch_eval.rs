@@ -1,21 +1,37 @@
-//! `kurobako run` command.
+//! `kurobako batch-evaluate` command.
 use crate::problem::KurobakoProblemRecipe;
 use crate::solver::KurobakoSolverRecipe;
+use crate::time::ElapsedSeconds;
+use kurobako_core::domain::ParamValueView;
 use kurobako_core::json;
+use kurobako_core::parallel;
 use kurobako_core::problem::ProblemRecipe as _;
-use kurobako_core::problem::{Evaluator as _, Problem as _, ProblemFactory as _};
+use kurobako_core::problem::{
+    BoxProblem, Evaluator as _, Problem as _, ProblemFactory as _, ProblemSpec,
+};
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
 use kurobako_core::trial::{Params, Values};
-use kurobako_core::{ErrorKind, Result};
-use serde::Deserialize;
-use serde::Serialize;
-use std::io;
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use structopt::StructOpt;
-use serde_json::Error;
-use std::io::Write;
 
 /// Options of the `kurobako batch-evaluate` command.
+///
+/// Each input line is a JSON object that maps variable names to parameter values
+/// (e.g., `{"x": 0.5, "kernel": "rbf"}`).
+/// An object of the form `{"params": ..., "step": ...}` is also accepted,
+/// where `params` is a map keyed by variable names or a positional array.
+///
+/// Each evaluation result is printed as a JSON line `{"line", "params", "step", "values", "elapsed"}`,
+/// where `line` is the (1-based) line number of the input.
+/// Invalid input lines (e.g., out-of-domain parameters) produce `{"line", "error"}` records
+/// instead of aborting the whole batch.
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct BatchEvaluateOpt {
@@ -23,20 +39,83 @@ pub struct BatchEvaluateOpt {
     #[structopt(long, parse(try_from_str = json::parse_json))]
     pub problem: KurobakoProblemRecipe,
 
+    /// Newline-delimited JSON file of the parameters to be evaluated.
+    ///
+    /// If omitted, the parameters are read from the standard input.
+    #[structopt(long)]
+    pub params: Option<PathBuf>,
+
+    /// Evaluation step. If omitted, the maximum step of the problem is used.
+    ///
+    /// A `step` field in an input line takes precedence over this.
+    #[structopt(long)]
+    pub step: Option<u64>,
+
     /// Random seed.
     #[structopt(long)]
     pub seed: Option<u64>,
+
+    /// Number of parameter sets evaluated concurrently.
+    ///
+    /// Each worker thread has its own problem instance
+    /// (and its own process in the case of external program problems).
+    #[structopt(long, default_value = "1")]
+    pub parallelism: NonZeroUsize,
+
+    /// Prints the results in the input order.
+    ///
+    /// If not specified, the results are printed as soon as they are available.
+    #[structopt(long)]
+    pub ordered: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum EvalCall {
+    WithStep {
+        params: ParamsInput,
+        #[serde(default)]
+        step: Option<u64>,
+    },
+    Named(BTreeMap<String, ParamValueView>),
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct EvalCall {
-    params: Params,
-    step: Option<u64>
+#[serde(untagged)]
+enum ParamsInput {
+    Positional(Params),
+    Named(BTreeMap<String, ParamValueView>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+enum EvalReply {
+    Evaluated {
+        line: usize,
+        params: BTreeMap<String, ParamValueView>,
+        step: u64,
+        values: Values,
+        elapsed: ElapsedSeconds,
+    },
+    Error {
+        line: usize,
+        error: EvalError,
+    },
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct EvalReply {
-    values: Values,
+struct EvalError {
+    kind: ErrorKind,
+    message: String,
+}
+impl From<Error> for EvalError {
+    fn from(e: Error) -> Self {
+        let message = e.to_string().lines().next().unwrap_or_default().to_owned();
+        Self {
+            kind: *e.kind(),
+            message,
+        }
+    }
 }
 
 impl BatchEvaluateOpt {
@@ -48,43 +127,215 @@ impl BatchEvaluateOpt {
         let problem_factory = track!(self.problem.create_factory(&registry))?;
         let problem_spec = track!(problem_factory.specification())?;
 
-        let problem = track!(problem_factory.create_problem(rng))?;
-        let mut writer = io::stdout();
-        loop{
-            let mut line = String::new();
-            let n = io::stdin().read_line(&mut line)?;
-            if n == 0 {
-                break;
+        let reader: Box<dyn BufRead> = if let Some(path) = &self.params {
+            let file = track!(File::open(path).map_err(Error::from); path)?;
+            Box::new(BufReader::new(file))
+        } else {
+            Box::new(BufReader::new(io::stdin()))
+        };
+        let jobs = reader
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| match line {
+                Err(e) => Some(Err(track!(Error::from(e)))),
+                Ok(line) if line.trim().is_empty() => None,
+                Ok(line) => Some(Ok((i + 1, self.parse_line(&problem_spec, &line)))),
+            });
+
+        let stdout = io::stdout();
+        let mut writer = stdout.lock();
+        track!(parallel::run_jobs(
+            jobs,
+            self.parallelism,
+            self.ordered,
+            |worker_id| {
+                let rng = if worker_id == 0 {
+                    rng.clone()
+                } else {
+                    rng.split(worker_id as u64)
+                };
+                let registry =
+                    FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
+                let factory = track!(self.problem.create_factory(&registry))?;
+                let problem = track!(factory.create_problem(rng))?;
+                let problem_spec = &problem_spec;
+                Ok(move |(line, parsed)| track!(evaluate(&problem, problem_spec, line, parsed)))
+            },
+            |_, reply| {
+                track!(serde_json::to_writer(&mut writer, &reply).map_err(Error::from))?;
+                track_writeln!(writer)?;
+                track!(writer.flush().map_err(Error::from))
             }
-            let EvalCall { params, step } = serde_json::from_str(&line).map_err(Error::from)?;
-
-            track_assert_eq!(
-                params.len(),
-                problem_spec.params_domain.variables().len(),
-                ErrorKind::InvalidInput
-            );
-
-            
-            let evaluator_or_error = track!(problem.create_evaluator(params.clone()));
-
-            let values = match evaluator_or_error {
-                Ok(mut evaluator) => {
-                    let s = step.unwrap_or_else(|| problem_spec.steps.last());
-                    let (_, values) = track!(evaluator.evaluate(s))?;
-                    values
-                },
-                Err(e) => {
-                    if *e.kind() != ErrorKind::UnevaluableParams {
-                        return Err(e);
-                    } else {
-                        Values::new(vec![])
-                    }
-                }
-            };
-
-            serde_json::to_writer(&mut writer, &EvalReply{values}).map_err(Error::from)?;
-            writer.write("\n".as_bytes())?;
-            writer.flush()?;
+        ))
+    }
+
+    fn parse_line(&self, spec: &ProblemSpec, line: &str) -> Result<(Params, u64)> {
+        let call: EvalCall = track!(serde_json::from_str(line).map_err(Error::from))?;
+        let (params, step) = match call {
+            EvalCall::WithStep { params, step } => (params, step),
+            EvalCall::Named(map) => (ParamsInput::Named(map), None),
+        };
+        let params = match params {
+            ParamsInput::Positional(params) => params,
+            ParamsInput::Named(map) => track!(spec.params_domain.params_from_map(&map))?,
+        };
+        track!(spec.validate_params(&params))?;
+
+        let step = step.or(self.step).unwrap_or_else(|| spec.steps.last());
+        track_assert!(
+            0 < step && step <= spec.steps.last(),
+            ErrorKind::InvalidInput,
+            "Out of range step: {}",
+            step
+        );
+        Ok((params, step))
+    }
+}
+
+fn evaluate(
+    problem: &BoxProblem,
+    spec: &ProblemSpec,
+    line: usize,
+    parsed: Result<(Params, u64)>,
+) -> Result<EvalReply> {
+    let (params, step) = match parsed {
+        Ok(x) => x,
+        Err(e) => {
+            return Ok(EvalReply::Error {
+                line,
+                error: EvalError::from(e),
+            })
+        }
+    };
+
+    let ((step, values), elapsed) = track!(ElapsedSeconds::try_time(|| {
+        match problem.create_evaluator(params.clone()) {
+            Ok(mut evaluator) => track!(evaluator.evaluate(step)),
+            Err(e) if *e.kind() == ErrorKind::UnevaluableParams => Ok((step, Values::new(vec![]))),
+            Err(e) => Err(track!(e)),
+        }
+    }))?;
+    Ok(EvalReply::Evaluated {
+        line,
+        params: track!(spec.params_domain.to_map(&params))?,
+        step,
+        values,
+        elapsed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    fn opt(step: Option<u64>) -> Result<BatchEvaluateOpt> {
+        let problem = serde_json::json!({"learning_curve": {
+            "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 3
+        }});
+        Ok(BatchEvaluateOpt {
+            problem: track!(serde_json::from_value(problem).map_err(Error::from))?,
+            params: None,
+            step,
+            seed: Some(0),
+            parallelism: NonZeroUsize::new(1).unwrap_or_else(|| unreachable!()),
+            ordered: true,
+        })
+    }
+
+    fn spec() -> Result<ProblemSpec> {
+        track!(ProblemSpecBuilder::new("test")
+            .param(var("x").continuous(0.0, 1.0))
+            .param(var("kernel").categorical(["linear", "rbf"]))
+            .value(var("y"))
+            .steps(1..=10)
+            .finish())
+    }
+
+    #[test]
+    fn names_are_mapped_to_positions() -> TopLevelResult {
+        let opt = track!(opt(None))?;
+        let spec = track!(spec())?;
+        let expected = (Params::new(vec![0.5, 1.0]), 10);
+
+        let lines = [
+            r#"{"kernel": "rbf", "x": 0.5}"#,
+            r#"{"params": {"x": 0.5, "kernel": "rbf"}}"#,
+            r#"{"params": [0.5, 1.0]}"#,
+        ];
+        for line in &lines {
+            assert_eq!(track!(opt.parse_line(&spec, line))?, expected, "{}", line);
+        }
+
+        assert!(opt.parse_line(&spec, r#"{"x": 0.5}"#).is_err());
+        assert!(opt
+            .parse_line(&spec, r#"{"x": 0.5, "kernel": "poly"}"#)
+            .is_err());
+        assert!(opt
+            .parse_line(&spec, r#"{"x": 0.5, "kernel": "rbf", "y": 1}"#)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn step_in_line_takes_precedence() -> TopLevelResult {
+        let spec = track!(spec())?;
+        let step = |opt: &BatchEvaluateOpt, line: &str| -> Result<u64> {
+            track!(opt.parse_line(&spec, line)).map(|(_, step)| step)
+        };
+        let without_step = r#"{"params": {"x": 0.5, "kernel": "rbf"}}"#;
+        let with_step = r#"{"params": {"x": 0.5, "kernel": "rbf"}, "step": 3}"#;
+
+        // The maximum step of the problem is used by default.
+        let opt_none = track!(opt(None))?;
+        assert_eq!(track!(step(&opt_none, without_step))?, 10);
+        assert_eq!(track!(step(&opt_none, with_step))?, 3);
+
+        let opt_five = track!(opt(Some(5)))?;
+        assert_eq!(track!(step(&opt_five, without_step))?, 5);
+        assert_eq!(track!(step(&opt_five, with_step))?, 3);
+
+        let out_of_range = r#"{"params": {"x": 0.5, "kernel": "rbf"}, "step": 11}"#;
+        assert!(step(&opt_five, out_of_range).is_err());
+        assert!(step(&track!(opt(Some(0)))?, without_step).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_lines_produce_error_records() -> TopLevelResult {
+        let opt = track!(opt(None))?;
+        let registry = FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
+        let factory = track!(opt.problem.create_factory(&registry))?;
+        let spec = track!(factory.specification())?;
+        let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+        let eval = |line: usize, input: &str| -> Result<serde_json::Value> {
+            let reply = track!(evaluate(
+                &problem,
+                &spec,
+                line,
+                opt.parse_line(&spec, input)
+            ))?;
+            track!(serde_json::to_value(reply).map_err(Error::from))
+        };
+
+        let reply = track!(eval(1, r#"{"x0": 0.5}"#))?;
+        assert_eq!(reply["line"], 1);
+        assert_eq!(reply["params"], serde_json::json!({"x0": 0.5}));
+        assert_eq!(reply["step"], 3);
+        assert_eq!(reply["values"].as_array().map(|v| v.len()), Some(1));
+        assert!(reply.get("error").is_none());
+
+        for (line, input) in [(2, r#"{"x0": 2.0}"#), (3, r#"{"x0": "#)].iter() {
+            let reply = track!(eval(*line, input))?;
+            let keys = reply
+                .as_object()
+                .map(|o| o.keys().cloned().collect::<Vec<_>>());
+            assert_eq!(keys, Some(vec!["error".to_owned(), "line".to_owned()]));
+            assert_eq!(reply["line"], *line);
+            assert_eq!(reply["error"]["kind"], "INVALID_INPUT");
+            assert!(reply["error"]["message"].is_string());
         }
         Ok(())
     }
@@ -1,6 +1,12 @@
 //! Datasets management.
-use kurobako_core::{Error, Result};
-use std::path::PathBuf;
+use kurobako_core::dataset::{self, DatasetCache};
+use kurobako_core::{Error, ErrorKind, Result};
+use kurobako_problems::hpobench::{self, HpobenchProblemRecipe};
+use kurobako_problems::nasbench::{self, NasbenchProblemRecipe};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
 use structopt::StructOpt;
 
 pub mod surrogate;
@@ -9,6 +15,15 @@ pub mod surrogate;
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub enum DatasetOpt {
+    /// Downloads a dataset to the cache directory and prints its local paths as JSON.
+    Get(GetOpt),
+
+    /// Converts a dataset into the binary format that can be loaded quickly by problems.
+    Convert(ConvertOpt),
+
+    /// Re-verifies the integrity of the files in the cache directory.
+    Verify(VerifyOpt),
+
     /// Dataset management for `kurobako problem nasbench`.
     Nasbench(NasbenchOpt),
 
@@ -23,6 +38,9 @@ impl DatasetOpt {
     /// Runs the specified dataset management command.
     pub fn run(&self) -> Result<()> {
         match self {
+            Self::Get(opt) => track!(opt.run()),
+            Self::Convert(opt) => track!(opt.run()),
+            Self::Verify(opt) => track!(opt.run()),
             Self::Nasbench(opt) => track!(opt.run()),
             Self::Hpobench(opt) => {
                 opt.run();
@@ -43,6 +61,8 @@ pub enum NasbenchOpt {
     Url,
 
     /// Converts TFRecord (nasbench_*.tfrecord) file to the binary file for kurobako.
+    ///
+    /// This is equivalent to `kurobako dataset convert nasbench`.
     Convert {
         /// Input file path.
         tfrecord_format_dataset_path: PathBuf,
@@ -56,38 +76,54 @@ impl NasbenchOpt {
     fn run(&self) -> Result<()> {
         match self {
             Self::Url => {
-                println!("https://storage.googleapis.com/nasbench/nasbench_full.tfrecord");
+                println!("{}", NASBENCH_URL);
                 Ok(())
             }
             Self::Convert {
                 tfrecord_format_dataset_path,
                 binary_format_dataset_path,
-            } => {
-                eprintln!(
-                    "Converting {:?}. It may take several minutes.",
-                    tfrecord_format_dataset_path
-                );
-
-                let file = track!(
-                    std::fs::File::open(&tfrecord_format_dataset_path).map_err(Error::from);
-                    tfrecord_format_dataset_path
-                )?;
-                let nasbench = track!(nasbench::NasBench::from_tfrecord_reader(
-                    std::io::BufReader::new(file),
-                    false
-                ))?;
-
-                let file =
-                    track!(std::fs::File::create(binary_format_dataset_path).map_err(Error::from))?;
-                track!(nasbench.to_writer(std::io::BufWriter::new(file)))?;
-
-                eprintln!("Done!");
-                Ok(())
-            }
+            } => track!(convert(
+                DatasetName::Nasbench,
+                tfrecord_format_dataset_path,
+                binary_format_dataset_path
+            )),
         }
     }
 }
 
+fn convert(dataset: DatasetName, input: &Path, output: &Path) -> Result<()> {
+    eprintln!("Converting {:?}. It may take several minutes.", input);
+    match dataset {
+        DatasetName::Hpobench => track!(hpobench::convert_to_binary(input, output))?,
+        DatasetName::Nasbench => track!(nasbench::convert_to_binary(input, output))?,
+    }
+    eprintln!("Done!");
+    Ok(())
+}
+
+/// Options of the `kurobako dataset convert` command.
+///
+/// The output file embeds the format version, and `kurobako dataset get` converts
+/// the dataset again if its cached conversion has been made by an incompatible version.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ConvertOpt {
+    /// Dataset name.
+    #[structopt(possible_values = DatasetName::POSSIBLE_VALUES)]
+    pub dataset: DatasetName,
+
+    /// Input file path (HDF5 file for `hpobench` and TFRecord file for `nasbench`).
+    pub input: PathBuf,
+
+    /// Output file path.
+    pub output: PathBuf,
+}
+impl ConvertOpt {
+    fn run(&self) -> Result<()> {
+        track!(convert(self.dataset, &self.input, &self.output))
+    }
+}
+
 /// Options of the `kurobako dataset hpobench` command.
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
@@ -98,6 +134,330 @@ pub enum HpobenchOpt {
 
 impl HpobenchOpt {
     fn run(&self) {
-        println!("http://ml4aad.org/wp-content/uploads/2019/01/fcnet_tabular_benchmarks.tar.gz");
+        println!("{}", HPOBENCH_URL);
+    }
+}
+
+const NASBENCH_URL: &str = "https://storage.googleapis.com/nasbench/nasbench_full.tfrecord";
+const HPOBENCH_URL: &str =
+    "http://ml4aad.org/wp-content/uploads/2019/01/fcnet_tabular_benchmarks.tar.gz";
+
+/// Options of the `kurobako dataset get` command.
+///
+/// Artifacts are downloaded with `curl` (interrupted downloads are resumed on the next run).
+/// The SHA-256 checksum of a downloaded artifact is recorded in the `*.sha256` file next to it,
+/// and the cached artifact is verified against the recorded checksum on later runs.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct GetOpt {
+    /// Dataset name.
+    #[structopt(possible_values = DatasetName::POSSIBLE_VALUES)]
+    pub dataset: DatasetName,
+
+    /// Cache directory.
+    ///
+    /// If omitted, `$KUROBAKO_DATASET_DIR` (or `~/.cache/kurobako`) is used.
+    #[structopt(long)]
+    pub dir: Option<PathBuf>,
+
+    /// Expected SHA-256 checksum of the downloaded artifact.
+    #[structopt(long)]
+    pub sha256: Option<String>,
+
+    /// Fails instead of downloading artifacts that are not in the cache.
+    ///
+    /// This is also enabled by setting `$KUROBAKO_OFFLINE` to `1`.
+    #[structopt(long)]
+    pub offline: bool,
+}
+impl GetOpt {
+    fn run(&self) -> Result<()> {
+        let cache = if let Some(dir) = &self.dir {
+            DatasetCache::new(dir)
+        } else {
+            track!(DatasetCache::open_default())?
+        };
+        let dir = cache.dataset_dir(self.dataset.as_str());
+        track!(fs::create_dir_all(&dir).map_err(Error::from); dir)?;
+
+        let files = match self.dataset {
+            DatasetName::Hpobench => {
+                let archive = track!(self.download(HPOBENCH_URL, &dir))?;
+                let files = HpobenchProblemRecipe::DATASET_FILES;
+                let hdf5 = |f: &str| dir.join(f).with_extension("hdf5");
+                if !files.iter().all(|(_, f)| hdf5(f).exists()) {
+                    eprintln!("Extracting {:?}", archive);
+                    let status = track!(Command::new("tar")
+                        .arg("xzf")
+                        .arg(&archive)
+                        .arg("-C")
+                        .arg(&dir)
+                        .status()
+                        .map_err(Error::from))?;
+                    track_assert!(status.success(), ErrorKind::Other; status);
+                }
+                for (_, f) in files {
+                    if !track!(hpobench::is_converted(dir.join(f)))? {
+                        track!(convert(self.dataset, &hdf5(f), &dir.join(f)))?;
+                    }
+                }
+                files
+            }
+            DatasetName::Nasbench => {
+                let tfrecord = track!(self.download(NASBENCH_URL, &dir))?;
+                let files = NasbenchProblemRecipe::DATASET_FILES;
+                let binary = dir.join(files[0].1);
+                if !track!(nasbench::is_converted(&binary))? {
+                    track!(convert(self.dataset, &tfrecord, &binary))?;
+                }
+                files
+            }
+        };
+
+        let json = serde_json::json!({
+            "dataset": self.dataset.as_str(),
+            "path": dir,
+            "files": files
+                .iter()
+                .map(|(name, file)| (name.to_string(), dir.join(file)))
+                .collect::<std::collections::BTreeMap<_, _>>()
+        });
+        println!("{}", json);
+        Ok(())
+    }
+
+    fn download(&self, url: &str, dir: &Path) -> Result<PathBuf> {
+        let name = track_assert_some!(url.rsplit('/').next(), ErrorKind::Bug);
+        let path = dir.join(name);
+        let checksum_path = dir.join(format!("{}.sha256", name));
+        let expected = if let Some(expected) = &self.sha256 {
+            Some(expected.clone())
+        } else if checksum_path.exists() {
+            let recorded = track!(fs::read_to_string(&checksum_path).map_err(Error::from))?;
+            Some(recorded.trim().to_owned())
+        } else {
+            None
+        };
+
+        if !path.exists() {
+            track_assert!(
+                !(self.offline || dataset::is_offline()),
+                ErrorKind::DatasetNotFound,
+                "{:?} is not in the cache and cannot be downloaded in the offline mode: url={}, sha256={}",
+                path,
+                url,
+                expected.as_deref().unwrap_or("unknown")
+            );
+
+            let partial = dir.join(format!("{}.part", name));
+            eprintln!("Downloading {} to {:?}", url, path);
+            let status = track!(Command::new("curl")
+                .arg("--fail")
+                .arg("--location")
+                .arg("--continue-at")
+                .arg("-")
+                .arg("--output")
+                .arg(&partial)
+                .arg(url)
+                .status()
+                .map_err(Error::from); url)?;
+            track_assert!(
+                status.success(),
+                ErrorKind::Other,
+                "Failed to download {} ({}); rerun the command to resume",
+                url,
+                status
+            );
+
+            let checksum = track!(dataset::sha256_file(&partial))?;
+            if let Some(expected) = &expected {
+                if !checksum.eq_ignore_ascii_case(expected) {
+                    track!(fs::remove_file(&partial).map_err(Error::from))?;
+                    track_panic!(
+                        ErrorKind::CorruptDataset,
+                        "Checksum mismatch: url={}, expected={}, actual={}",
+                        url,
+                        expected,
+                        checksum
+                    );
+                }
+            }
+            track!(fs::write(&checksum_path, &checksum).map_err(Error::from))?;
+            track!(fs::rename(&partial, &path).map_err(Error::from))?;
+            return Ok(path);
+        }
+
+        eprintln!("Verifying {:?}", path);
+        let checksum = track!(dataset::sha256_file(&path))?;
+        if let Some(expected) = expected {
+            track_assert!(
+                checksum.eq_ignore_ascii_case(&expected),
+                ErrorKind::CorruptDataset,
+                "Checksum mismatch (remove {:?} to download it again): expected={}, actual={}",
+                path,
+                expected,
+                checksum
+            );
+        } else {
+            track!(fs::write(&checksum_path, &checksum).map_err(Error::from))?;
+        }
+        Ok(path)
+    }
+}
+
+/// Options of the `kurobako dataset verify` command.
+///
+/// Every downloaded artifact in the cache is re-hashed and compared with its recorded checksum,
+/// and every converted file is checked for its format version.
+/// The result of each file is printed as a JSON line.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct VerifyOpt {
+    /// Cache directory.
+    ///
+    /// If omitted, `$KUROBAKO_DATASET_DIR` (or `~/.cache/kurobako`) is used.
+    #[structopt(long)]
+    pub dir: Option<PathBuf>,
+
+    /// Deletes corrupt and partial files.
+    #[structopt(long)]
+    pub delete: bool,
+}
+impl VerifyOpt {
+    fn run(&self) -> Result<()> {
+        let cache = if let Some(dir) = &self.dir {
+            DatasetCache::new(dir)
+        } else {
+            track!(DatasetCache::open_default())?
+        };
+
+        let mut files = Vec::new();
+        if cache.dir().exists() {
+            track!(list_files(cache.dir(), &mut files))?;
+        }
+        files.sort();
+
+        let mut broken = 0;
+        for path in files {
+            let status = track!(self.verify_file(&cache, &path))?;
+            let deleted = self.delete && status.is_broken();
+            if status.is_broken() {
+                broken += 1;
+            }
+            if deleted {
+                track!(fs::remove_file(&path).map_err(Error::from); path)?;
+            }
+            let json = serde_json::json!({
+                "path": path,
+                "status": status,
+                "deleted": deleted,
+            });
+            println!("{}", json);
+        }
+
+        track_assert!(
+            broken == 0 || self.delete,
+            ErrorKind::CorruptDataset,
+            "Found {} corrupt or partial files (use `--delete` to remove them)",
+            broken
+        );
+        Ok(())
+    }
+
+    fn verify_file(&self, cache: &DatasetCache, path: &Path) -> Result<FileStatus> {
+        if path.extension().is_some_and(|x| x == "part") {
+            return Ok(FileStatus::Partial);
+        }
+
+        let mut checksum_path = path.as_os_str().to_owned();
+        checksum_path.push(".sha256");
+        let checksum_path = PathBuf::from(checksum_path);
+        if checksum_path.exists() {
+            eprintln!("Verifying {:?}", path);
+            let expected = track!(fs::read_to_string(&checksum_path).map_err(Error::from))?;
+            let checksum = track!(dataset::sha256_file(path))?;
+            return if checksum.eq_ignore_ascii_case(expected.trim()) {
+                Ok(FileStatus::Ok)
+            } else {
+                Ok(FileStatus::Corrupt)
+            };
+        }
+
+        if path.extension().is_some_and(|x| x == "bin") {
+            let dataset = path
+                .strip_prefix(cache.dir())
+                .ok()
+                .and_then(|p| p.iter().next())
+                .and_then(|p| p.to_str());
+            let converted = match dataset.map(DatasetName::from_str) {
+                Some(Ok(DatasetName::Hpobench)) => track!(hpobench::is_converted(path))?,
+                Some(Ok(DatasetName::Nasbench)) => track!(nasbench::is_converted(path))?,
+                _ => return Ok(FileStatus::Unchecked),
+            };
+            return if converted {
+                Ok(FileStatus::Ok)
+            } else {
+                Ok(FileStatus::Corrupt)
+            };
+        }
+
+        Ok(FileStatus::Unchecked)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FileStatus {
+    Ok,
+    Corrupt,
+    Partial,
+    Unchecked,
+}
+impl FileStatus {
+    fn is_broken(self) -> bool {
+        matches!(self, Self::Corrupt | Self::Partial)
+    }
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in track!(fs::read_dir(dir).map_err(Error::from); dir)? {
+        let path = track!(entry.map_err(Error::from))?.path();
+        if path.is_dir() {
+            track!(list_files(&path, files))?;
+        } else if path.extension().is_none_or(|x| x != "sha256") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Dataset that can be downloaded by `kurobako dataset get`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum DatasetName {
+    Hpobench,
+    Nasbench,
+}
+impl DatasetName {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["hpobench", "nasbench"];
+
+    /// Returns the name of this dataset.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hpobench => "hpobench",
+            Self::Nasbench => "nasbench",
+        }
+    }
+}
+impl FromStr for DatasetName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hpobench" => Ok(Self::Hpobench),
+            "nasbench" => Ok(Self::Nasbench),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown dataset: {:?}", s),
+        }
     }
 }
@@ -0,0 +1,45 @@
+//! Filters that are applied between solvers and problems.
+use kurobako_core::filter::{
+    BoxFilter, ClipValuesFilterRecipe, DiscreteToContinuousFilterRecipe, FilterRecipe,
+    GaussianNoiseFilterRecipe, LogValueFilterRecipe, NegateValuesFilterRecipe,
+    NormalizeParamsFilterRecipe, OneHotFilterRecipe, ParamNoiseFilterRecipe,
+    RescaleStepsFilterRecipe,
+};
+use kurobako_core::rng::ArcRng;
+use kurobako_core::Result;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Filter recipe.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum KurobakoFilterRecipe {
+    GaussianNoise(GaussianNoiseFilterRecipe),
+    DiscreteToContinuous(DiscreteToContinuousFilterRecipe),
+    OneHot(OneHotFilterRecipe),
+    LogValue(LogValueFilterRecipe),
+    NormalizeParams(NormalizeParamsFilterRecipe),
+    ClipValues(ClipValuesFilterRecipe),
+    NegateValues(NegateValuesFilterRecipe),
+    RescaleSteps(RescaleStepsFilterRecipe),
+    ParamNoise(ParamNoiseFilterRecipe),
+}
+impl FilterRecipe for KurobakoFilterRecipe {
+    type Filter = BoxFilter;
+
+    fn create_filter(&self, rng: ArcRng) -> Result<Self::Filter> {
+        match self {
+            Self::GaussianNoise(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::DiscreteToContinuous(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::OneHot(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::LogValue(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::NormalizeParams(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::ClipValues(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::NegateValues(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::RescaleSteps(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+            Self::ParamNoise(r) => track!(r.create_filter(rng).map(BoxFilter::new)),
+        }
+    }
+}
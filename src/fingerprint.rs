@@ -0,0 +1,225 @@
+//! `kurobako compare-fingerprints` command.
+use crate::load::LoadOpt;
+use crate::record::StudyRecord;
+use kurobako_core::{ErrorKind, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Options of the `kurobako compare-fingerprints` command.
+///
+/// The studies in the two files are paired by their configurations (i.e., solver, problem, budget and so on)
+/// and seeds, so the order of the studies doesn't matter (studies having the same configuration and seed
+/// are paired in the order of appearance). If a study has no recorded fingerprint
+/// (i.e., it was run without `kurobako run --fingerprint`), it is computed from the trials.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct CompareFingerprintsOpt {
+    /// Benchmark result file (JSON) to be compared.
+    pub a: PathBuf,
+
+    /// Benchmark result file (JSON) to be compared with `a`.
+    pub b: PathBuf,
+}
+impl CompareFingerprintsOpt {
+    /// Compares the fingerprints of the studies in the two files.
+    ///
+    /// Mismatches are printed to the standard output, and then an error is returned
+    /// (i.e., the command exits with a non-zero status).
+    pub fn compare(&self) -> Result<()> {
+        let a = track!(load(&self.a))?;
+        let b = track!(load(&self.b))?;
+
+        let mut mismatches = 0;
+        let ids = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+        for id in &ids {
+            match (a.get(*id), b.get(*id)) {
+                (Some(x), Some(y)) => {
+                    if let Some(mismatch) = Mismatch::find(x, y) {
+                        println!("{}:", name(x));
+                        mismatch.print();
+                        mismatches += 1;
+                    }
+                }
+                (Some(x), None) => {
+                    println!("{}: only in {:?}", name(x), self.a);
+                    mismatches += 1;
+                }
+                (None, Some(y)) => {
+                    println!("{}: only in {:?}", name(y), self.b);
+                    mismatches += 1;
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        track_assert_eq!(
+            mismatches,
+            0,
+            ErrorKind::Other,
+            "{} of {} studies have different fingerprints",
+            mismatches,
+            ids.len()
+        );
+        eprintln!("All {} studies have the same fingerprints", ids.len());
+        Ok(())
+    }
+}
+
+fn load(path: &Path) -> Result<BTreeMap<(String, u64, usize), StudyRecord>> {
+    let opt = LoadOpt {
+        files: vec![path.to_path_buf()],
+        ..LoadOpt::default()
+    };
+    let mut studies = BTreeMap::new();
+    for study in track!(opt.load_inputs())? {
+        let id = track!(study.id())?;
+        let n = (0..)
+            .find(|&n| !studies.contains_key(&(id.clone(), study.seed, n)))
+            .unwrap_or_else(|| unreachable!());
+        studies.insert((id, study.seed, n), study);
+    }
+    Ok(studies)
+}
+
+fn name(study: &StudyRecord) -> String {
+    format!(
+        "solver={:?}, problem={:?}, seed={}",
+        study.solver.spec.name, study.problem.spec.name, study.seed
+    )
+}
+
+fn fingerprint(study: &StudyRecord) -> String {
+    study
+        .fingerprint
+        .clone()
+        .unwrap_or_else(|| study.compute_fingerprint())
+}
+
+#[derive(Debug, PartialEq)]
+struct Mismatch {
+    fingerprints: (String, String),
+    diverging_trial: Option<DivergingTrial>,
+}
+impl Mismatch {
+    fn find(a: &StudyRecord, b: &StudyRecord) -> Option<Self> {
+        let fingerprints = (fingerprint(a), fingerprint(b));
+        if fingerprints.0 == fingerprints.1 {
+            return None;
+        }
+
+        let len = a.trials.len().max(b.trials.len());
+        let diverging_trial = (0..len)
+            .find(|&i| {
+                a.trials.get(i).map(|t| t.fingerprint_bytes())
+                    != b.trials.get(i).map(|t| t.fingerprint_bytes())
+            })
+            .map(|i| {
+                let params = |s: &StudyRecord| s.trials.get(i).map(|t| t.params.to_vec());
+                DivergingTrial {
+                    index: i,
+                    a: params(a),
+                    b: params(b),
+                }
+            });
+        Some(Self {
+            fingerprints,
+            diverging_trial,
+        })
+    }
+
+    fn print(&self) {
+        println!(
+            "  fingerprints: {} != {}",
+            self.fingerprints.0, self.fingerprints.1
+        );
+        match &self.diverging_trial {
+            Some(t) => {
+                println!("  first diverging trial: #{}", t.index);
+                println!("    a: {}", params_to_string(&t.a));
+                println!("    b: {}", params_to_string(&t.b));
+            }
+            None => {
+                // The recorded fingerprints differ from the trials (e.g., edited records).
+                println!("  no diverging trial (the recorded fingerprints are inconsistent with the trials)");
+            }
+        }
+    }
+}
+
+/// The first trial that differs between two studies.
+#[derive(Debug, PartialEq)]
+struct DivergingTrial {
+    index: usize,
+
+    /// Parameters of the trial in the both studies (`None` if a study doesn't have the trial).
+    a: Option<Vec<f64>>,
+    b: Option<Vec<f64>>,
+}
+
+fn params_to_string(params: &Option<Vec<f64>>) -> String {
+    params
+        .as_ref()
+        .map_or_else(|| "(missing)".to_owned(), |p| format!("{:?}", p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{Runner, RunnerOpt};
+    use crate::study::StudyRecipe;
+    use kurobako_core::trial::Params;
+    use kurobako_core::Error;
+    use trackable::result::TopLevelResult;
+
+    fn run(seed: u64) -> Result<StudyRecord> {
+        let study: StudyRecipe = track!(serde_json::from_str(
+            r#"{
+                "solver": {"random": {}},
+                "problem": {"sigopt": {"name": "ACKLEY"}},
+                "budget": 10,
+                "concurrency": 1,
+                "scheduling": "RANDOM"
+            }"#
+        )
+        .map_err(Error::from))?;
+        let runner = Runner::new(RunnerOpt {
+            quiet: true,
+            fingerprint: true,
+            ..RunnerOpt::default()
+        });
+        track!(runner.run_study(&study, seed))
+    }
+
+    #[test]
+    fn fingerprints_work() -> TopLevelResult {
+        let a = track!(run(0))?;
+        let b = track!(run(0))?;
+        let fingerprint = a.fingerprint.clone().unwrap_or_default();
+        assert!(fingerprint.starts_with("v1:"));
+        assert_eq!(fingerprint, a.compute_fingerprint());
+        assert_eq!(Mismatch::find(&a, &b), None);
+
+        // Fingerprints survive JSON round-trips.
+        let json = track!(serde_json::to_string(&a).map_err(Error::from))?;
+        let loaded: StudyRecord = track!(serde_json::from_str(&json).map_err(Error::from))?;
+        assert_eq!(loaded.compute_fingerprint(), fingerprint);
+
+        let mut c = b.clone();
+        c.trials[3].params = Params::new(c.trials[3].params.iter().map(|x| x + 1.0).collect());
+        c.fingerprint = None;
+        let mismatch = Mismatch::find(&a, &c).unwrap_or_else(|| panic!());
+        assert_eq!(
+            mismatch.diverging_trial,
+            Some(DivergingTrial {
+                index: 3,
+                a: Some(a.trials[3].params.to_vec()),
+                b: Some(c.trials[3].params.to_vec()),
+            })
+        );
+
+        let d = track!(run(1))?;
+        assert_ne!(d.fingerprint, a.fingerprint);
+        Ok(())
+    }
+}
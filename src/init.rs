@@ -0,0 +1,247 @@
+//! `kurobako init` command.
+use crate::problem::KurobakoProblemRecipe;
+use crate::solver::KurobakoSolverRecipe;
+use kurobako_core::domain::var;
+use kurobako_core::problem::ProblemSpecBuilder;
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use trackable::error::ErrorKindExt as _;
+
+/// Options of the `kurobako init` command.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct InitOpt {
+    /// Directory where the scaffold is generated (created if it doesn't exist).
+    #[structopt(default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Also generates a Python problem script that speaks the EPI protocol (and its recipe).
+    #[structopt(long)]
+    pub with_external_problem: bool,
+
+    /// Overwrites existing files.
+    #[structopt(long)]
+    pub force: bool,
+}
+impl InitOpt {
+    /// Generates an example benchmark scaffold in `self.dir`.
+    ///
+    /// The scaffold consists of solver and problem recipes (JSONs) and `run.sh`,
+    /// which generates studies from the recipes, runs them, and then makes a report and plots.
+    pub fn init(&self) -> Result<()> {
+        let files = track!(self.files())?;
+        if !self.force {
+            for (path, _) in &files {
+                let path = self.dir.join(path);
+                track_assert!(
+                    !path.exists(),
+                    ErrorKind::InvalidInput,
+                    "{:?} already exists (use `--force` to overwrite it)",
+                    path
+                );
+            }
+        }
+
+        for (path, content) in &files {
+            let path = self.dir.join(path);
+            if let Some(dir) = path.parent() {
+                track!(fs::create_dir_all(dir).map_err(Error::from); dir)?;
+            }
+            track!(fs::write(&path, content).map_err(Error::from); path)?;
+        }
+        track!(make_executable(&self.dir.join("run.sh")))?;
+
+        eprintln!(
+            "Generated an example benchmark in {:?}: run `{}` to start it",
+            self.dir,
+            self.dir.join("run.sh").display()
+        );
+        Ok(())
+    }
+
+    /// Returns the paths (relative to `self.dir`) and the contents of the files of the scaffold.
+    fn files(&self) -> Result<Vec<(String, String)>> {
+        let solvers = [
+            ("random", vec!["random"]),
+            ("optuna", vec!["optuna", "--pruner", "MedianPruner"]),
+        ];
+        let mut problems = vec![
+            ("ackley", vec!["sigopt", "ackley"]),
+            ("learning_curve", vec!["learning-curve", "--steps", "10"]),
+        ];
+        if self.with_external_problem {
+            problems.push((
+                "quadratic",
+                vec!["command", "python3", "problems/quadratic.py"],
+            ));
+        }
+
+        // Recipes are made by the same parsers as `kurobako solver` and `kurobako problem` commands,
+        // so that they always follow the current schema.
+        let mut files = Vec::new();
+        for (name, args) in &solvers {
+            let args = ["solver"].iter().chain(args.iter());
+            let recipe = track!(KurobakoSolverRecipe::from_iter_safe(args)
+                .map_err(|e| ErrorKind::InvalidInput.cause(e.to_string())))?;
+            files.push((format!("solvers/{}.json", name), track!(to_json(&recipe))?));
+        }
+        for (name, args) in &problems {
+            let args = ["problem"].iter().chain(args.iter());
+            let recipe = track!(KurobakoProblemRecipe::from_iter_safe(args)
+                .map_err(|e| ErrorKind::InvalidInput.cause(e.to_string())))?;
+            files.push((format!("problems/{}.json", name), track!(to_json(&recipe))?));
+        }
+        if self.with_external_problem {
+            files.push((
+                "problems/quadratic.py".to_owned(),
+                track!(external_problem_script())?,
+            ));
+        }
+
+        let solver_names = solvers.iter().map(|s| s.0).collect::<Vec<_>>();
+        let problem_names = problems.iter().map(|p| p.0).collect::<Vec<_>>();
+        files.push((
+            "run.sh".to_owned(),
+            run_script(&solver_names.join(" "), &problem_names.join(" ")),
+        ));
+        Ok(files)
+    }
+}
+
+fn to_json<T: Serialize>(x: &T) -> Result<String> {
+    let json = track!(serde_json::to_string_pretty(x).map_err(Error::from))?;
+    Ok(json + "\n")
+}
+
+#[cfg(unix)]
+fn make_executable(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let mut permissions = track!(fs::metadata(path).map_err(Error::from); path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    track!(fs::set_permissions(path, permissions).map_err(Error::from); path)
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &std::path::Path) -> Result<()> {
+    Ok(())
+}
+
+fn run_script(solvers: &str, problems: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+#
+# Runs the example benchmark generated by `kurobako init`, and then makes a report and plots.
+#
+# Environment variables:
+#   KUROBAKO: path of the kurobako command (default: kurobako)
+#   SOLVERS:  names of the solver recipes in `solvers/` (default: "{solvers}")
+#   PROBLEMS: names of the problem recipes in `problems/` (default: "{problems}")
+#   BUDGET:   budget of each study (default: 20)
+#   REPEATS:  number of the executions of each study (default: 5)
+set -eu
+cd "$(dirname "$0")"
+
+KUROBAKO="${{KUROBAKO:-kurobako}}"
+SOLVERS="${{SOLVERS:-{solvers}}}"
+PROBLEMS="${{PROBLEMS:-{problems}}}"
+BUDGET="${{BUDGET:-20}}"
+REPEATS="${{REPEATS:-5}}"
+
+set --
+for name in $SOLVERS; do
+    set -- "$@" --solvers "{{\"\$file\": \"solvers/$name.json\"}}"
+done
+for name in $PROBLEMS; do
+    set -- "$@" --problems "{{\"\$file\": \"problems/$name.json\"}}"
+done
+
+# (1) Generates the studies (i.e., the cross product of the solvers and problems), and then runs them.
+"$KUROBAKO" studies "$@" --budget "$BUDGET" --repeats "$REPEATS" | "$KUROBAKO" run | tee results.json
+
+# (2) Makes a markdown report.
+"$KUROBAKO" report results.json > report.md
+echo "Wrote report.md" >&2
+
+# (3) Plots the optimization curves (requires gnuplot).
+if command -v gnuplot > /dev/null; then
+    "$KUROBAKO" plot curve results.json --output-dir plots
+    echo "Wrote the plots in plots/" >&2
+else
+    echo "Skipped the plots because gnuplot is not found" >&2
+fi
+"#,
+        solvers = solvers,
+        problems = problems
+    )
+}
+
+fn external_problem_script() -> Result<String> {
+    let spec = track!(ProblemSpecBuilder::new("Quadratic")
+        .param(var("x").continuous(-10.0, 10.0))
+        .param(var("y").continuous(-10.0, 10.0))
+        .value(var("Objective Value"))
+        .finish())?;
+    let spec = track!(serde_json::to_string_pretty(&spec).map_err(Error::from))?;
+    Ok(format!(
+        r#"#!/usr/bin/env python3
+"""A minimal problem that speaks the EPI protocol of kurobako (generated by `kurobako init`).
+
+This problem minimizes `(x - 2)^2 + (y + 1)^2`.
+The messages are JSON lines exchanged via the standard input and output,
+and this script doesn't depend on any third-party packages.
+"""
+import json
+import sys
+
+SPEC = json.loads(
+    r"""{spec}"""
+)
+
+
+def evaluate(params):
+    x, y = params
+    return [(x - 2.0) ** 2 + (y + 1.0) ** 2]
+
+
+def send(message):
+    sys.stdout.write(json.dumps(message) + "\n")
+    sys.stdout.flush()
+
+
+def main():
+    send({{"type": "PROBLEM_SPEC_CAST", "spec": SPEC}})
+
+    evaluators = {{}}
+    for line in sys.stdin:
+        message = json.loads(line)
+        kind = message["type"]
+        if kind == "CREATE_EVALUATOR_CALL":
+            evaluators[message["evaluator_id"]] = message["params"]
+            reply = {{"type": "CREATE_EVALUATOR_REPLY"}}
+        elif kind == "EVALUATE_CALL":
+            params = evaluators[message["evaluator_id"]]
+            reply = {{"type": "EVALUATE_REPLY", "current_step": 1, "values": evaluate(params)}}
+        elif kind == "DROP_EVALUATOR_CAST":
+            evaluators.pop(message["evaluator_id"], None)
+            continue
+        elif kind == "SHUTDOWN_CAST":
+            break
+        else:
+            # `CREATE_PROBLEM_CAST` and `DROP_PROBLEM_CAST` (this problem is stateless).
+            continue
+
+        if "request_id" in message:
+            reply["request_id"] = message["request_id"]
+        send(reply)
+
+
+if __name__ == "__main__":
+    main()
+"#,
+        spec = spec
+    ))
+}
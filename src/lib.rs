@@ -19,9 +19,15 @@ macro_rules! track_writeln {
     }
 }
 
+pub mod analyze;
 pub mod batch_eval;
 pub mod dataset;
 pub mod evaluate;
+pub mod filters;
+pub mod fingerprint;
+pub mod init;
+pub mod load;
+pub mod merge;
 pub mod plot;
 pub mod problem;
 pub mod problem_suites;
@@ -29,7 +35,9 @@ pub mod report;
 pub mod runner;
 pub mod solver;
 pub mod spec;
+pub mod stats;
 pub mod study;
+pub mod target;
 pub mod time;
 pub mod variable;
 
@@ -0,0 +1,452 @@
+//! Loading of benchmark results.
+use crate::record::StudyRecord;
+use flate2::bufread::MultiGzDecoder;
+use kurobako_core::json::{self, Format};
+use kurobako_core::yaml;
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Options for loading benchmark results (JSONs).
+#[derive(Debug, Clone, Default, StructOpt, Serialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct LoadOpt {
+    /// Benchmark result files (JSONs or YAMLs).
+    ///
+    /// If omitted or `-` is given, the results are read from the standard input.
+    /// Gzip and zstd compressed files are decompressed transparently.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+
+    /// Format of the benchmark result files.
+    ///
+    /// If omitted, the format is guessed from the extension of each file (`.yaml` or `.yml` for YAML),
+    /// and JSON is assumed for the other files and the standard input.
+    /// A YAML file is a stream of documents (separated by `---`), each of which is a study record.
+    #[structopt(long, possible_values = Format::POSSIBLE_VALUES)]
+    #[serde(skip)]
+    pub input_format: Option<Format>,
+
+    /// Truncates each study to the trials completed within the given budget.
+    ///
+    /// Studies that have no completed trials after the truncation are discarded,
+    /// and studies whose budgets are smaller than the given one are kept as they are (with warnings).
+    #[structopt(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_budget: Option<u64>,
+
+    /// Reduces memory usage by discarding the trials that don't affect the best values,
+    /// AUCs and hypervolumes while loading.
+    ///
+    /// The best values, AUCs, hypervolumes, consumed budgets, numbers of trials (and failures),
+    /// budget breakdowns and total elapsed times are the same as without this option,
+    /// but elapsed time curves only have points at the end steps of the retained trials.
+    /// Outputs that show individual trials (i.e., `report --param-coverage`,
+    /// `plot slice`, `plot pareto-front`, `plot trials` and `analyze importance`) are refused.
+    #[structopt(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub low_memory: bool,
+
+    /// Treats each study as a single-objective one that only has the given objective (0-origin index).
+    ///
+    /// This is useful to look at an objective of multi-objective studies as if it were the only one.
+    /// It's an error if some studies have fewer objectives.
+    #[structopt(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub objective: Option<usize>,
+}
+impl LoadOpt {
+    /// Returns an error if `--low-memory` is specified, because `what` needs all the trials of the studies.
+    pub fn require_all_trials(&self, what: &str) -> Result<()> {
+        track_assert!(
+            !self.low_memory,
+            ErrorKind::InvalidInput,
+            "{} needs all the trials and can't be used with `--low-memory`",
+            what
+        );
+        Ok(())
+    }
+
+    /// Loads study records from the files specified by `self.files` (or the standard input).
+    ///
+    /// The records of all the files are concatenated in the given order.
+    /// See [`LoadOpt::load`] for the accepted formats.
+    pub fn load_inputs(&self) -> Result<Vec<StudyRecord>> {
+        let stdin = [PathBuf::from("-")];
+        let files = if self.files.is_empty() {
+            &stdin[..]
+        } else {
+            &self.files[..]
+        };
+
+        let mut studies = Vec::new();
+        let mut skipped = 0;
+        for path in files {
+            let reader = track!(open_input(path); path)?;
+            let format = self
+                .input_format
+                .or_else(|| Format::from_path(path))
+                .unwrap_or_default();
+            skipped += track!(self.load_into(reader, path, format, &mut studies))?;
+        }
+        warn_skipped(skipped);
+        Ok(studies)
+    }
+
+    /// Loads study records from the given reader.
+    ///
+    /// The input is either newline-delimited (or pretty-printed) JSONs or a JSON array of records
+    /// (or a YAML stream if `self.input_format` is `Format::Yaml`).
+    /// Malformed records are skipped with warnings.
+    ///
+    /// Records are processed one at a time, so that only the retained part of each study is kept in memory.
+    pub fn load<R: Read>(&self, reader: R) -> Result<Vec<StudyRecord>> {
+        let mut studies = Vec::new();
+        let format = self.input_format.unwrap_or_default();
+        let skipped = track!(self.load_into(reader, Path::new("-"), format, &mut studies))?;
+        warn_skipped(skipped);
+        Ok(studies)
+    }
+
+    fn load_into<R: Read>(
+        &self,
+        reader: R,
+        path: &Path,
+        format: Format,
+        studies: &mut Vec<StudyRecord>,
+    ) -> Result<usize> {
+        let mut skipped = 0;
+        let mut lacking = Vec::new();
+        let mut handle = |study: Result<StudyRecord>| {
+            let mut study = match study {
+                Ok(study) => study,
+                Err(e) => {
+                    let message = e.to_string();
+                    eprintln!(
+                        "Warning: skipped a malformed record in {:?}: {}",
+                        path,
+                        message.lines().next().unwrap_or_default()
+                    );
+                    skipped += 1;
+                    return Ok(());
+                }
+            };
+            if let Some(objective) = self.objective {
+                if !track!(study.select_objective(objective))? {
+                    lacking.push(format!(
+                        "solver={:?}, problem={:?}, seed={} ({} objectives)",
+                        study.solver.spec.name,
+                        study.problem.spec.name,
+                        study.seed,
+                        study.problem.spec.values_domain.len()
+                    ));
+                    return Ok(());
+                }
+            }
+            if let Some(max_budget) = self.max_budget {
+                if study.budget < max_budget {
+                    eprintln!(
+                        "Warning: kept a study whose budget {} is smaller than the budget {}: \
+                         solver={:?}, problem={:?}, seed={}",
+                        study.budget,
+                        max_budget,
+                        study.solver.spec.name,
+                        study.problem.spec.name,
+                        study.seed
+                    );
+                }
+                if !study.truncate_budget(max_budget) {
+                    eprintln!(
+                        "Warning: discarded a study that has no completed trials \
+                         within the budget {}: solver={:?}, problem={:?}, seed={}",
+                        max_budget, study.solver.spec.name, study.problem.spec.name, study.seed
+                    );
+                    return Ok(());
+                }
+            }
+            study.backfill();
+            if self.low_memory {
+                study.compact();
+                study.trials.shrink_to_fit();
+            }
+            studies.push(study);
+            Ok(())
+        };
+        match format {
+            Format::Json => track!(json::load_lenient(BufReader::new(reader), &mut handle); path)?,
+            Format::Yaml => track!(yaml::load_lenient(reader, &mut handle); path)?,
+        }
+        if let Some(objective) = self.objective {
+            track_assert!(
+                lacking.is_empty(),
+                ErrorKind::InvalidInput,
+                "The following studies in {:?} don't have the objective {}:\n{}",
+                path,
+                objective,
+                lacking.join("\n")
+            );
+        }
+        Ok(skipped)
+    }
+}
+
+fn warn_skipped(skipped: usize) {
+    if skipped > 0 {
+        eprintln!("Warning: skipped {} malformed records in total", skipped);
+    }
+}
+
+/// Opens the given file (`-` means the standard input), decompressing it if needed.
+fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+    let mut reader: Box<dyn BufRead + Send> = if path == Path::new("-") {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let file = track!(File::open(path).map_err(Error::from))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let magic = track!(reader.fill_buf().map_err(Error::from))?;
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        let decoder = track!(ZstdDecoder::with_buffer(reader).map_err(Error::from))?;
+        Ok(Box::new(decoder))
+    } else {
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{Compression, StudyRunner};
+    use crate::study::StudyRecipe;
+    use flate2::write::GzEncoder;
+    use std::io::Write as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn objective_works() -> TopLevelResult {
+        let recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"zdt": {"zdt": "1"}},
+            "budget": 5,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 0
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let study = track!(StudyRunner::new(&recipe).and_then(|r| r.run()))?;
+        let json = track!(serde_json::to_string(&study).map_err(Error::from))?;
+
+        let opt = LoadOpt {
+            objective: Some(1),
+            ..LoadOpt::default()
+        };
+        let loaded = track!(opt.load(json.as_bytes()))?;
+        let spec = &loaded[0].problem.spec;
+        assert_eq!(spec.name, "ZDT1 (Objective 1: f2)");
+        assert_eq!(spec.values_domain.len(), 1);
+        for (t0, t1) in study.trials.iter().zip(loaded[0].trials.iter()) {
+            assert_eq!(t1.value(1), t0.values(1).map(|v| v[1]));
+        }
+
+        let opt = LoadOpt {
+            objective: Some(2),
+            ..LoadOpt::default()
+        };
+        let e = track_assert_some!(opt.load(json.as_bytes()).err(), ErrorKind::Bug);
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("seed=0 (2 objectives)"), "{}", e);
+        Ok(())
+    }
+
+    /// Returns the JSON of a study that has a single trial.
+    fn study_json(seed: u64) -> String {
+        serde_json::json!({
+            "start_time": "2020-01-01T00:00:00+00:00",
+            "end_time": "2020-01-01T00:00:01+00:00",
+            "seed": seed,
+            "budget": 1,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+                }},
+                "spec": {
+                    "name": "Foo",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": 1
+                }
+            },
+            "trials": [{
+                "thread_id": 0,
+                "params": [0.5],
+                "evaluations": [{
+                    "values": [1.0],
+                    "start_step": 0,
+                    "end_step": 1,
+                    "ask_elapsed": 0.0,
+                    "tell_elapsed": 0.0,
+                    "evaluate_elapsed": 0.0
+                }]
+            }]
+        })
+        .to_string()
+    }
+
+    /// Writes the given content to `path` compressed by gzip or zstd.
+    fn write_compressed(path: &Path, compression: Compression, content: &str) -> Result<()> {
+        let file = track!(File::create(path).map_err(Error::from))?;
+        let bytes = content.as_bytes();
+        match compression {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(file, flate2::Compression::default());
+                track!(encoder.write_all(bytes).map_err(Error::from))?;
+                track!(encoder.finish().map_err(Error::from))?;
+            }
+            Compression::Zstd => {
+                track!(zstd::stream::copy_encode(bytes, file, 0).map_err(Error::from))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn load_inputs_concatenates_files() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let plain = dir.path().join("plain.json");
+        let gzip = dir.path().join("gzip.json.gz");
+        let zstd = dir.path().join("zstd.json.zst");
+        track!(
+            std::fs::write(&plain, format!("{}\n{}\n", study_json(0), study_json(1)))
+                .map_err(Error::from)
+        )?;
+        track!(write_compressed(&gzip, Compression::Gzip, &study_json(2)))?;
+        track!(write_compressed(
+            &zstd,
+            Compression::Zstd,
+            &format!("[{}, {}]", study_json(3), study_json(4))
+        ))?;
+
+        let opt = LoadOpt {
+            files: vec![zstd.clone(), plain.clone(), gzip.clone()],
+            ..LoadOpt::default()
+        };
+        let studies = track!(opt.load_inputs())?;
+        let seeds = studies.iter().map(|s| s.seed).collect::<Vec<_>>();
+        assert_eq!(seeds, [3, 4, 0, 1, 2]);
+
+        // The same file can be given more than once.
+        let opt = LoadOpt {
+            files: vec![gzip.clone(), gzip],
+            ..LoadOpt::default()
+        };
+        assert_eq!(track!(opt.load_inputs())?.len(), 2);
+
+        // Missing files are reported with their names.
+        let missing = dir.path().join("missing.json");
+        let opt = LoadOpt {
+            files: vec![plain, missing],
+            ..LoadOpt::default()
+        };
+        let e = track_assert_some!(opt.load_inputs().err(), ErrorKind::Bug);
+        assert!(e.to_string().contains("missing.json"), "{}", e);
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_inputs_work() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let json = dir.path().join("studies.json");
+        let yaml = dir.path().join("studies.yaml");
+        let gzip = dir.path().join("studies.yml.gz");
+        track!(
+            std::fs::write(&json, format!("{}\n{}\n", study_json(0), study_json(1)))
+                .map_err(Error::from)
+        )?;
+
+        // A JSON is also a YAML document, so the records are written as they are.
+        // The last document is malformed and skipped.
+        let documents = format!(
+            "# Results.\n{}\n---\n{}\n---\nseed: 2\n",
+            study_json(0),
+            study_json(1)
+        );
+        track!(std::fs::write(&yaml, &documents).map_err(Error::from))?;
+        track!(write_compressed(&gzip, Compression::Gzip, &documents))?;
+
+        let load = |files: Vec<PathBuf>| -> Result<Vec<serde_json::Value>> {
+            let opt = LoadOpt {
+                files,
+                ..LoadOpt::default()
+            };
+            let studies = track!(opt.load_inputs())?;
+            track!(studies
+                .iter()
+                .map(|s| serde_json::to_value(s).map_err(Error::from))
+                .collect::<Result<_>>())
+        };
+        let expected = track!(load(vec![json]))?;
+        assert_eq!(expected.len(), 2);
+        assert_eq!(track!(load(vec![yaml]))?, expected);
+        assert_eq!(track!(load(vec![gzip]))?, expected);
+
+        // The format option takes precedence over the extensions.
+        let misnamed = dir.path().join("documents.json");
+        track!(std::fs::write(&misnamed, &documents).map_err(Error::from))?;
+        let opt = LoadOpt {
+            files: vec![misnamed],
+            input_format: Some(Format::Yaml),
+            ..LoadOpt::default()
+        };
+        assert_eq!(track!(opt.load_inputs())?.len(), 2);
+
+        let opt = LoadOpt {
+            input_format: Some(Format::Yaml),
+            ..LoadOpt::default()
+        };
+        assert_eq!(track!(opt.load(documents.as_bytes()))?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn broken_compressed_input_is_an_error() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let path = dir.path().join("broken.json.gz");
+        let mut bytes = GZIP_MAGIC.to_vec();
+        bytes.extend_from_slice(b"not a gzip stream");
+        track!(std::fs::write(&path, bytes).map_err(Error::from))?;
+
+        let opt = LoadOpt {
+            files: vec![path],
+            ..LoadOpt::default()
+        };
+        let e = track_assert_some!(opt.load_inputs().err(), ErrorKind::Bug);
+        assert!(e.to_string().contains("invalid gzip header"), "{}", e);
+        assert!(e.to_string().contains("broken.json.gz"), "{}", e);
+        Ok(())
+    }
+}
@@ -1,19 +1,23 @@
 #[macro_use]
 extern crate trackable;
 
+use kurobako::analyze::AnalyzeOpt;
 use kurobako::batch_eval::BatchEvaluateOpt;
 use kurobako::dataset::DatasetOpt;
 use kurobako::evaluate::EvaluateOpt;
+use kurobako::filters::KurobakoFilterRecipe;
+use kurobako::fingerprint::CompareFingerprintsOpt;
+use kurobako::init::InitOpt;
+use kurobako::merge::MergeOpt;
 use kurobako::plot::PlotOpt;
 use kurobako::problem::KurobakoProblemRecipe;
 use kurobako::problem_suites::ProblemSuite;
 use kurobako::report::{ReportOpt, Reporter};
 use kurobako::runner::{Runner, RunnerOpt};
 use kurobako::solver::KurobakoSolverRecipe;
-use kurobako::spec::SpecOpt;
+use kurobako::spec::{Spec, SpecOpt};
 use kurobako::study::StudiesRecipe;
-use kurobako::variable::Var;
-use kurobako_core::json;
+use kurobako::variable::VariableOpt;
 use kurobako_core::Error;
 use std::io;
 use structopt::StructOpt;
@@ -33,17 +37,23 @@ macro_rules! print_json {
 #[structopt(rename_all = "kebab-case")]
 #[allow(clippy::large_enum_variant)]
 enum Opt {
+    /// Generates an example benchmark (recipes and a script that runs, reports and plots it).
+    Init(InitOpt),
+
     /// Generates a solver recipe (JSON).
     Solver(KurobakoSolverRecipe),
 
     /// Generates a problem recipe (JSON).
     Problem(KurobakoProblemRecipe),
 
+    /// Generates a filter recipe (JSON).
+    Filter(KurobakoFilterRecipe),
+
     /// Generates problem recipes (JSONs) belong to the specified suite.
     ProblemSuite(ProblemSuite),
 
-    /// Generates a variable recipe (JSON).
-    Var(Var),
+    /// Generates a domain variable (JSON).
+    Var(VariableOpt),
 
     /// Generates study recipes (JSONs).
     Studies(StudiesRecipe),
@@ -57,6 +67,17 @@ enum Opt {
     /// Generates visualization images from benchmark results (JSONs).
     Plot(PlotOpt),
 
+    /// Analyzes benchmark results (JSONs).
+    Analyze(AnalyzeOpt),
+
+    /// Merges benchmark result files (JSONs) while removing duplicate studies.
+    Merge(MergeOpt),
+
+    /// Compares the fingerprints of the studies in two benchmark result files (JSONs).
+    ///
+    /// Exits with a non-zero status if any fingerprint differs.
+    CompareFingerprints(CompareFingerprintsOpt),
+
     /// Dataset management.
     Dataset(DatasetOpt),
 
@@ -74,39 +95,59 @@ fn main() -> trackable::result::TopLevelResult {
     let opt = Opt::from_args();
 
     match opt {
+        Opt::Init(opt) => {
+            track!(opt.init())?;
+        }
         Opt::Solver(x) => {
             print_json!(x);
         }
         Opt::Problem(x) => {
             print_json!(x);
         }
+        Opt::Filter(x) => {
+            print_json!(x);
+        }
         Opt::ProblemSuite(p) => {
             for p in p.recipes() {
                 print_json!(p);
             }
         }
         Opt::Studies(x) => {
-            for y in x.studies() {
+            for y in track!(x.studies())? {
                 print_json!(y);
             }
         }
-        Opt::Var(x) => {
-            print_json!(x);
+        Opt::Var(opt) => {
+            let var = track!(opt.to_variable())?;
+            print_json!(var);
         }
         Opt::Run(opt) => {
             track!(Runner::new(opt).run())?;
         }
         Opt::Report(opt) => {
-            let studies = track!(json::load(io::stdin().lock()))?;
+            let studies = track!(opt.load.load_inputs())?;
             let reporter = Reporter::new(studies, opt);
             let stdout = io::stdout();
             let stdout = stdout.lock();
             track!(reporter.report_all(stdout))?;
+            track!(reporter.export_win_rates())?;
+            track!(reporter.export_expected_running_times())?;
+            track!(reporter.export_per_study_results())?;
+            track!(reporter.export_normalizations())?;
         }
         Opt::Plot(opt) => {
-            let studies = track!(json::load(io::stdin().lock()))?;
+            let studies = track!(opt.load_opt().load_inputs())?;
             track!(opt.plot(&studies))?;
         }
+        Opt::Analyze(opt) => {
+            track!(opt.run())?;
+        }
+        Opt::Merge(opt) => {
+            track!(opt.merge())?;
+        }
+        Opt::CompareFingerprints(opt) => {
+            track!(opt.compare())?;
+        }
         Opt::Dataset(opt) => {
             track!(opt.run())?;
         }
@@ -116,7 +157,18 @@ fn main() -> trackable::result::TopLevelResult {
         }
         Opt::Spec(opt) => {
             let spec = track!(opt.get_spec())?;
-            print_json!(spec);
+            let stdout = std::io::stdout();
+            if let Spec::Schemas(schemas) = spec {
+                for schema in schemas {
+                    track!(
+                        serde_json::to_writer_pretty(stdout.lock(), &schema).map_err(Error::from)
+                    )?;
+                    println!();
+                }
+            } else {
+                track!(serde_json::to_writer_pretty(stdout.lock(), &spec).map_err(Error::from))?;
+                println!();
+            }
         }
         Opt::BatchEvaluate(opt) => {
             track!(opt.run())?;
@@ -51,14 +51,14 @@ impl<'a, W: Write> MarkdownWriter<'a, W> {
         let mut widthes = table
             .headers
             .iter()
-            .map(|h| h.name.len())
+            .map(|h| h.name.chars().count())
             .collect::<Vec<_>>();
 
         #[allow(clippy::needless_range_loop)]
         for col in 0..table.headers.len() {
             for row in &table.rows {
                 if let Some(item) = row.items.get(col) {
-                    widthes[col] = cmp::max(widthes[col], item.len());
+                    widthes[col] = cmp::max(widthes[col], item.chars().count());
                 }
             }
         }
@@ -0,0 +1,389 @@
+//! `kurobako merge` command.
+use crate::record::StudyRecord;
+use crate::study::BudgetUnit;
+use kurobako_core::{Error, ErrorKind, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Options of the `kurobako merge` command.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct MergeOpt {
+    /// Benchmark result files (JSONs) to be merged.
+    pub inputs: Vec<PathBuf>,
+
+    /// Output file path.
+    ///
+    /// If omitted, the merged results are written to the standard output.
+    #[structopt(long)]
+    pub out: Option<PathBuf>,
+
+    /// Which study is kept when duplicate studies have different trials (`newest` or `oldest`).
+    ///
+    /// Studies are compared by their end times.
+    #[structopt(long, default_value = "newest")]
+    pub prefer: Prefer,
+
+    /// Maximum difference in seconds between the start times of duplicate studies.
+    #[structopt(long, default_value = "1.0")]
+    pub start_time_tolerance: f64,
+}
+impl MergeOpt {
+    /// Merges the input files.
+    pub fn merge(&self) -> Result<()> {
+        track_assert!(
+            self.start_time_tolerance >= 0.0,
+            ErrorKind::InvalidInput; self.start_time_tolerance
+        );
+
+        let mut merger = Merger::new(self);
+        for path in &self.inputs {
+            let file = track!(File::open(path).map_err(Error::from); path)?;
+            let reader = BufReader::new(file);
+            for study in serde_json::Deserializer::from_reader(reader).into_iter() {
+                let study: StudyRecord = track!(study.map_err(Error::from); path)?;
+                track!(merger.add(study))?;
+            }
+        }
+
+        if let Some(path) = &self.out {
+            let file = track!(File::create(path).map_err(Error::from); path)?;
+            track!(merger.write_to(BufWriter::new(file)))?;
+        } else {
+            let stdout = io::stdout();
+            track!(merger.write_to(stdout.lock()))?;
+        }
+
+        eprintln!(
+            "Merged {} studies: kept {}, dropped {} ({} conflicts resolved by preferring {} ones)",
+            merger.kept.len() + merger.dropped,
+            merger.kept.len(),
+            merger.dropped,
+            merger.conflicts,
+            self.prefer
+        );
+        Ok(())
+    }
+}
+
+/// Policy for resolving conflicts between duplicate studies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Prefer {
+    Newest,
+    Oldest,
+}
+impl FromStr for Prefer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "newest" => Ok(Self::Newest),
+            "oldest" => Ok(Self::Oldest),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown preference: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for Prefer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Newest => write!(f, "newest"),
+            Self::Oldest => write!(f, "oldest"),
+        }
+    }
+}
+
+/// Identity of a study.
+///
+/// Two studies are regarded as duplicates if they have the same identity and
+/// their start times differ by at most `--start-time-tolerance` seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StudyIdentity {
+    solver_recipe: String,
+    problem_recipe: String,
+    seed: u64,
+    budget: u64,
+    budget_unit: BudgetUnit,
+}
+impl StudyIdentity {
+    fn new(study: &StudyRecord) -> Result<Self> {
+        Ok(Self {
+            solver_recipe: track!(serde_json::to_string(&study.solver.recipe).map_err(Error::from))?,
+            problem_recipe: track!(
+                serde_json::to_string(&study.problem.recipe).map_err(Error::from)
+            )?,
+            seed: study.seed,
+            budget: study.budget,
+            budget_unit: study.budget_unit,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Merger {
+    prefer: Prefer,
+    tolerance: f64,
+    kept: Vec<StudyRecord>,
+    index: HashMap<StudyIdentity, Vec<usize>>,
+    dropped: usize,
+    conflicts: usize,
+}
+impl Merger {
+    fn new(opt: &MergeOpt) -> Self {
+        Self {
+            prefer: opt.prefer,
+            tolerance: opt.start_time_tolerance,
+            kept: Vec::new(),
+            index: HashMap::new(),
+            dropped: 0,
+            conflicts: 0,
+        }
+    }
+
+    fn add(&mut self, study: StudyRecord) -> Result<()> {
+        let identity = track!(StudyIdentity::new(&study))?;
+        let (kept, tolerance) = (&self.kept, self.tolerance);
+        let candidates = self.index.entry(identity).or_default();
+        let duplicate = candidates.iter().copied().find(|&i| {
+            let diff = study.start_time.signed_duration_since(kept[i].start_time);
+            (diff.num_milliseconds() as f64 / 1000.0).abs() <= tolerance
+        });
+
+        let i = if let Some(i) = duplicate {
+            i
+        } else {
+            candidates.push(self.kept.len());
+            self.kept.push(study);
+            return Ok(());
+        };
+
+        self.dropped += 1;
+        let trials = track!(serde_json::to_string(&study.trials).map_err(Error::from))?;
+        let other_trials =
+            track!(serde_json::to_string(&self.kept[i].trials).map_err(Error::from))?;
+        if trials != other_trials {
+            self.conflicts += 1;
+            let replace = match self.prefer {
+                Prefer::Newest => study.end_time > self.kept[i].end_time,
+                Prefer::Oldest => study.end_time < self.kept[i].end_time,
+            };
+            if replace {
+                self.kept[i] = study;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        for study in &self.kept {
+            track!(serde_json::to_writer(&mut writer, study).map_err(Error::from))?;
+            track_writeln!(writer)?;
+        }
+        track!(writer.flush().map_err(Error::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    fn opt(prefer: Prefer, start_time_tolerance: f64) -> MergeOpt {
+        MergeOpt {
+            inputs: Vec::new(),
+            out: None,
+            prefer,
+            start_time_tolerance,
+        }
+    }
+
+    /// Makes a study that starts and ends at the given milliseconds after `2020-01-01T00:00:00`.
+    fn study(seed: u64, start_ms: u32, end_ms: u32, value: f64) -> Result<StudyRecord> {
+        let time = |s: u32| {
+            format!(
+                "2020-01-01T00:{:02}:{:02}.{:03}+00:00",
+                s / 1000 / 60,
+                s / 1000 % 60,
+                s % 1000
+            )
+        };
+        let json = serde_json::json!({
+            "start_time": time(start_ms),
+            "end_time": time(end_ms),
+            "seed": seed,
+            "budget": 1,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+                }},
+                "spec": {
+                    "name": "Foo",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": 1
+                }
+            },
+            "trials": [{
+                "thread_id": 0,
+                "params": [value],
+                "evaluations": [{
+                    "values": [value],
+                    "start_step": 0,
+                    "end_step": 1,
+                    "ask_elapsed": 0.1,
+                    "tell_elapsed": 0.2,
+                    "evaluate_elapsed": 0.3
+                }]
+            }]
+        });
+        track!(serde_json::from_value(json).map_err(Error::from))
+    }
+
+    fn merge(opt: &MergeOpt, studies: Vec<StudyRecord>) -> Result<Merger> {
+        let mut merger = Merger::new(opt);
+        for study in studies {
+            track!(merger.add(study))?;
+        }
+        Ok(merger)
+    }
+
+    fn values(merger: &Merger) -> Vec<f64> {
+        merger
+            .kept
+            .iter()
+            .map(|s| s.trials[0].evaluations[0].values[0])
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_studies_are_dropped() -> TopLevelResult {
+        let opt = opt(Prefer::Newest, 1.0);
+        let studies = vec![
+            track!(study(0, 0, 10_000, 1.0))?,
+            track!(study(1, 0, 10_000, 2.0))?,
+            track!(study(0, 0, 10_000, 1.0))?,
+        ];
+        let merger = track!(merge(&opt, studies))?;
+        assert_eq!(values(&merger), vec![1.0, 2.0]);
+        assert_eq!(merger.dropped, 1);
+        assert_eq!(merger.conflicts, 0);
+
+        // Studies that differ in their recipes are not duplicates.
+        let mut other = track!(study(0, 0, 10_000, 3.0))?;
+        other.budget = 2;
+        let merger = track!(merge(&opt, vec![track!(study(0, 0, 10_000, 1.0))?, other]))?;
+        assert_eq!(values(&merger), vec![1.0, 3.0]);
+        assert_eq!(merger.dropped, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn start_time_tolerance_works() -> TopLevelResult {
+        let studies = || -> Result<Vec<StudyRecord>> {
+            Ok(vec![
+                track!(study(0, 0, 10_000, 1.0))?,
+                track!(study(0, 500, 10_000, 1.0))?,
+                track!(study(0, 2_000, 10_000, 1.0))?,
+            ])
+        };
+
+        let merger = track!(merge(&opt(Prefer::Newest, 1.0), track!(studies())?))?;
+        assert_eq!(merger.kept.len(), 2);
+        assert_eq!(merger.dropped, 1);
+
+        let merger = track!(merge(&opt(Prefer::Newest, 2.0), track!(studies())?))?;
+        assert_eq!(merger.kept.len(), 1);
+        assert_eq!(merger.dropped, 2);
+
+        let merger = track!(merge(&opt(Prefer::Newest, 0.0), track!(studies())?))?;
+        assert_eq!(merger.kept.len(), 3);
+        assert_eq!(merger.dropped, 0);
+
+        assert!(opt(Prefer::Newest, -1.0).merge().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_are_resolved_by_preference() -> TopLevelResult {
+        let old = || study(0, 0, 10_000, 1.0);
+        let new = || study(0, 0, 20_000, 2.0);
+
+        for &(prefer, expected) in &[(Prefer::Newest, 2.0), (Prefer::Oldest, 1.0)] {
+            let opt = opt(prefer, 1.0);
+            // The result doesn't depend on the input order.
+            let inputs = vec![
+                vec![track!(old())?, track!(new())?],
+                vec![track!(new())?, track!(old())?],
+            ];
+            for studies in inputs {
+                let merger = track!(merge(&opt, studies))?;
+                assert_eq!(values(&merger), vec![expected], "prefer={}", prefer);
+                assert_eq!(merger.dropped, 1);
+                assert_eq!(merger.conflicts, 1);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn merge_works() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let mut inputs = Vec::new();
+        let files = vec![
+            vec![track!(study(0, 0, 10_000, 1.0))?],
+            vec![
+                track!(study(0, 0, 20_000, 2.0))?,
+                track!(study(1, 0, 10_000, 3.0))?,
+            ],
+        ];
+        for (i, studies) in files.into_iter().enumerate() {
+            let path = dir.path().join(format!("{}.json", i));
+            let mut file = track!(File::create(&path).map_err(Error::from))?;
+            for study in studies {
+                track!(serde_json::to_writer(&mut file, &study).map_err(Error::from))?;
+                track_writeln!(file)?;
+            }
+            inputs.push(path);
+        }
+
+        let out = dir.path().join("merged.json");
+        let opt = MergeOpt {
+            inputs,
+            out: Some(out.clone()),
+            ..opt(Prefer::Oldest, 1.0)
+        };
+        track!(opt.merge())?;
+
+        let merged = track!(std::fs::read_to_string(&out).map_err(Error::from))?;
+        let merged = track!(merged
+            .lines()
+            .map(|line| track!(serde_json::from_str(line).map_err(Error::from)))
+            .collect::<Result<Vec<StudyRecord>>>())?;
+        let merged = merged
+            .iter()
+            .map(|s| (s.seed, s.trials[0].evaluations[0].values[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(merged, vec![(0, 1.0), (1, 3.0)]);
+        Ok(())
+    }
+}
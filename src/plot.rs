@@ -1,12 +1,17 @@
 //! `kurobako plot` command.
+use crate::load::LoadOpt;
 use crate::record::StudyRecord;
 use kurobako_core::{Error, ErrorKind, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use structopt::StructOpt;
 
 pub mod curve;
 pub mod pareto_front;
 pub mod slice;
+pub mod trials;
 
 /// Options of the `kurobako plot` command.
 #[derive(Debug, StructOpt)]
@@ -20,16 +25,143 @@ pub enum PlotOpt {
 
     /// Generates 2D pareto front plots.
     ParetoFront(self::pareto_front::PlotParetoFrontOpt),
+
+    /// Generates scatter plots of the objective values of all trials.
+    Trials(self::trials::PlotTrialsOpt),
 }
 impl PlotOpt {
+    /// Returns the options for loading benchmark results.
+    pub fn load_opt(&self) -> &LoadOpt {
+        match self {
+            Self::Curve(opt) => &opt.load,
+            Self::Slice(opt) => &opt.load,
+            Self::ParetoFront(opt) => &opt.load,
+            Self::Trials(opt) => &opt.load,
+        }
+    }
+
     /// Plots a graph.
     pub fn plot(&self, studies: &[StudyRecord]) -> Result<()> {
         match self {
             Self::Curve(opt) => track!(opt.plot(studies)),
             Self::Slice(opt) => track!(opt.plot(studies)),
             Self::ParetoFront(opt) => track!(opt.plot(studies)),
+            Self::Trials(opt) => track!(opt.plot(studies)),
+        }
+    }
+}
+
+/// Options that control the names and the layout of generated image files.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct OutputFileOpt {
+    /// Template of output file names.
+    ///
+    /// Available placeholders depend on the plot kind: `{problem}`, `{problem_id}`, `{solver}`,
+    /// `{study_id}`, `{param}`, `{metric}` and `{format}`.
+    /// If omitted, the default template of each plot kind is used.
+    #[structopt(long)]
+    pub filename_template: Option<String>,
+
+    /// Creates a subdirectory for each problem under the output directory.
+    #[structopt(long)]
+    pub subdir_per_problem: bool,
+
+    /// Overwrites existing files.
+    #[structopt(long, conflicts_with = "skip-existing")]
+    pub force: bool,
+
+    /// Skips plots whose output file already exists.
+    #[structopt(long)]
+    pub skip_existing: bool,
+
+    /// Also writes the data plotted in each image to a CSV file named after the image
+    /// (e.g., `foo.csv` for `foo.png`).
+    #[structopt(long)]
+    pub dump_data: bool,
+}
+impl OutputFileOpt {
+    /// Returns the path of an output file.
+    ///
+    /// `None` means that the file already exists and `--skip-existing` is specified.
+    fn output_path(
+        &self,
+        output_dir: &Path,
+        default_template: &str,
+        placeholders: &[(&str, &str)],
+    ) -> Result<Option<PathBuf>> {
+        let template = self
+            .filename_template
+            .as_deref()
+            .unwrap_or(default_template);
+        let filename = track!(expand_filename_template(template, placeholders))?;
+
+        let mut path = output_dir.to_path_buf();
+        if self.subdir_per_problem {
+            if let Some((_, problem)) = placeholders.iter().find(|(k, _)| *k == "problem") {
+                path.push(normalize_filename(problem));
+            }
+        }
+        path.push(filename);
+
+        if path.exists() {
+            if self.skip_existing {
+                return Ok(None);
+            }
+            track_assert!(
+                self.force,
+                ErrorKind::InvalidInput,
+                "Output file {:?} already exists (use `--force` or `--skip-existing`)",
+                path
+            );
+        }
+        if let Some(dir) = path.parent() {
+            track!(fs::create_dir_all(dir).map_err(Error::from); dir)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// Writes the data of the given image by using `f` if `--dump-data` is specified.
+    fn dump_data<F>(&self, image: &Path, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>,
+    {
+        if !self.dump_data {
+            return Ok(());
         }
+
+        let path = image.with_extension("csv");
+        let file = track!(File::create(&path).map_err(Error::from); path)?;
+        let mut writer = BufWriter::new(file);
+        track!(f(&mut writer); path)?;
+        track!(writer.flush().map_err(Error::from); path)
+    }
+}
+
+fn expand_filename_template(template: &str, placeholders: &[(&str, &str)]) -> Result<String> {
+    let mut filename = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filename.push_str(&rest[..start]);
+        let end = track_assert_some!(
+            rest[start..].find('}'),
+            ErrorKind::InvalidInput,
+            "Unclosed placeholder in filename template: {:?}",
+            template
+        );
+        let key = &rest[start + 1..start + end];
+        let (_, value) = track_assert_some!(
+            placeholders.iter().find(|(k, _)| *k == key),
+            ErrorKind::InvalidInput,
+            "Unknown placeholder {{{}}} in filename template (available: {:?})",
+            key,
+            placeholders.iter().map(|(k, _)| *k).collect::<Vec<_>>()
+        );
+        filename.push_str(&normalize_filename(value));
+        rest = &rest[start + end + 1..];
     }
+    filename.push_str(rest);
+    Ok(filename)
 }
 
 fn execute_gnuplot(script: &str) -> Result<()> {
@@ -45,6 +177,17 @@ fn execute_gnuplot(script: &str) -> Result<()> {
     Ok(())
 }
 
+/// Truncates `s` to `max_len` characters (including the trailing ellipsis).
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        s.to_owned()
+    } else {
+        let mut t = s.chars().take(max_len - 1).collect::<String>();
+        t.push('…');
+        t
+    }
+}
+
 fn normalize_filename(s: &str) -> String {
     let mut t = String::new();
     let mut replaced = false;
@@ -59,3 +202,65 @@ fn normalize_filename(s: &str) -> String {
     }
     t.trim_matches('-').to_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    const PLACEHOLDERS: &[(&str, &str)] = &[("problem", "Sphere (dim=2)"), ("index", "3")];
+
+    #[test]
+    fn expand_filename_template_works() -> TopLevelResult {
+        assert_eq!(
+            track!(expand_filename_template(
+                "{problem}-{index}.png",
+                PLACEHOLDERS
+            ))?,
+            "sphere-dim-2-3.png"
+        );
+        assert_eq!(
+            track!(expand_filename_template("plain.png", PLACEHOLDERS))?,
+            "plain.png"
+        );
+        assert_eq!(
+            track!(expand_filename_template("{index}{index}", PLACEHOLDERS))?,
+            "33"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn expand_filename_template_rejects_broken_placeholders() {
+        let e = expand_filename_template("{solver}.png", PLACEHOLDERS).expect_err("unknown");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("Unknown placeholder {solver}"));
+
+        let e = expand_filename_template("{problem.png", PLACEHOLDERS).expect_err("unclosed");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("Unclosed placeholder"));
+
+        let e = expand_filename_template("{}.png", PLACEHOLDERS).expect_err("empty");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn normalize_filename_works() -> TopLevelResult {
+        assert_eq!(normalize_filename("Sphere (dim=2)"), "sphere-dim-2");
+        assert_eq!(normalize_filename("../etc/passwd"), "etc-passwd");
+        assert_eq!(normalize_filename(r"C:\foo\bar"), "c-foo-bar");
+        assert_eq!(normalize_filename("a  b\t\nc"), "a-b-c");
+        assert_eq!(normalize_filename("*?<>|\"'"), "");
+        assert_eq!(normalize_filename("日本語 x"), "x");
+
+        // Values substituted into templates can not introduce path separators.
+        let placeholders = [("problem", "../../tmp/x"), ("index", "/")];
+        let filename = track!(expand_filename_template(
+            "{problem}{index}.png",
+            &placeholders
+        ))?;
+        assert_eq!(filename, "tmp-x.png");
+        assert!(!filename.contains('/'));
+        Ok(())
+    }
+}
@@ -1,15 +1,22 @@
 //! `kurobako plot curve` command.
 #![allow(clippy::format_push_string)]
-use super::{execute_gnuplot, normalize_filename};
+use super::{execute_gnuplot, truncate, OutputFileOpt};
+use crate::load::LoadOpt;
 use crate::record::{ProblemRecord, StudyRecord};
+use crate::report::{csv_field, Stat};
+use crate::study::BudgetUnit;
+use crate::target::{self, TargetOpt, Targets};
 use indicatif::{ProgressBar, ProgressStyle};
 use kurobako_core::num::OrderedFloat;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::stats::BasicStats;
 use kurobako_core::{Error, ErrorKind, Result};
 use rustats::fundamental::{average, stddev};
 use std::collections::BTreeMap;
 use std::fs;
-use std::io::Write as _;
-use std::path::PathBuf;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use structopt::StructOpt;
 use tempfile::{NamedTempFile, TempPath};
@@ -31,6 +38,15 @@ impl Metric {
         "elapsed-time",
         "solver-elapsed-time",
     ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::BestValue => "best-value",
+            Metric::Hypervolume => "hypervolume",
+            Metric::ElapsedTime => "elapsed-time",
+            Metric::SolverElapsedTime => "solver-elapsed-time",
+        }
+    }
 }
 impl FromStr for Metric {
     type Err = Error;
@@ -54,14 +70,28 @@ pub struct PlotCurveOpt {
     #[structopt(long, short = "o", default_value = "images/curve/")]
     pub output_dir: PathBuf,
 
-    /// Image width in pixels.
+    /// Image width in pixels (the width of each panel if `--combined` is specified).
     #[structopt(long, default_value = "800")]
     pub width: usize,
 
-    /// Image height in pixels.
+    /// Image height in pixels (the height of each panel if `--combined` is specified).
     #[structopt(long, default_value = "600")]
     pub height: usize,
 
+    /// Lays out the plots of all the problems as the panels of a single image.
+    ///
+    /// The panels share one legend, and each solver has the same color in every panel.
+    #[structopt(long)]
+    pub combined: bool,
+
+    /// Number of the panel columns of the combined image.
+    #[structopt(long, default_value = "4")]
+    pub columns: NonZeroUsize,
+
+    /// Makes all the panels of the combined image share the same Y-axis range.
+    #[structopt(long, requires = "combined")]
+    pub share_y: bool,
+
     /// Minimum value of Y axis.
     #[structopt(long)]
     pub ymin: Option<f64>,
@@ -78,14 +108,27 @@ pub struct PlotCurveOpt {
     #[structopt(long)]
     pub xmax: Option<f64>,
 
+    /// Study label used to split the curves of each solver into groups (e.g., `hardware`).
+    ///
+    /// Studies without the label fall into the `(unlabeled)` group.
+    #[structopt(long)]
+    pub group_by: Option<String>,
+
     /// Makes Y axis log scale.
     #[structopt(long)]
     pub ylogscale: bool,
 
-    /// Displays errorbar showing standard deviation of optimization curve.
+    /// Displays errorbar around the optimization curve.
+    ///
+    /// The band shows the standard deviation if `--aggregate mean` is specified,
+    /// and the interquartile range (i.e., from the 25% quantile to the 75% quantile) otherwise.
     #[structopt(long)]
     pub errorbar: bool,
 
+    /// Statistic used to aggregate the studies of each solver into the curve.
+    #[structopt(long, default_value = "mean", possible_values = Stat::POSSIBLE_VALUES)]
+    pub aggregate: Stat,
+
     /// Metric of X axis.
     #[structopt(
         long,
@@ -93,6 +136,20 @@ pub struct PlotCurveOpt {
         possible_values = Metric::POSSIBLE_VALUES
     )]
     pub metric: Metric,
+
+    /// Target values drawn as horizontal lines on the best value curves.
+    ///
+    /// The known optimum of a problem (the `optimum` attribute of its spec) is always drawn if it exists.
+    #[structopt(flatten)]
+    pub target: TargetOpt,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub output_file: OutputFileOpt,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
 }
 impl PlotCurveOpt {
     pub(crate) fn plot(&self, studies: &[StudyRecord]) -> Result<()> {
@@ -110,9 +167,22 @@ impl PlotCurveOpt {
         pb.set_style(ProgressStyle::default_bar().template(template));
 
         track!(fs::create_dir_all(&self.output_dir).map_err(Error::from); self.output_dir)?;
+        let targets = track!(self.target.load())?;
+
+        if self.combined {
+            let problems = problems
+                .into_iter()
+                .map(|(problem_id, studies)| {
+                    track!(Problem::new(problem_id, studies, &targets, self))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            track!(self.plot_combined(&problems))?;
+            pb.finish_with_message(&format!("done (dir={:?})", self.output_dir));
+            return Ok(());
+        }
 
         for (problem_id, studies) in problems {
-            let problem = track!(Problem::new(problem_id, studies, self))?;
+            let problem = track!(Problem::new(problem_id, studies, &targets, self))?;
             track!(problem.plot())?;
             pb.inc(1);
         }
@@ -120,127 +190,303 @@ impl PlotCurveOpt {
 
         Ok(())
     }
+
+    fn plot_combined(&self, problems: &[Problem]) -> Result<()> {
+        let problems = problems
+            .iter()
+            .filter(|p| p.is_plottable())
+            .collect::<Vec<_>>();
+        track_assert!(
+            !problems.is_empty(),
+            ErrorKind::InvalidInput,
+            "No problems to be plotted"
+        );
+
+        let output = track!(self.output_file.output_path(
+            &self.output_dir,
+            "combined-{metric}.{format}",
+            &[("metric", self.metric.name()), ("format", "png")]
+        ))?;
+        let output = if let Some(output) = output {
+            output
+        } else {
+            return Ok(());
+        };
+
+        // Colors are assigned to solvers across all the problems.
+        let mut colors = BTreeMap::new();
+        for problem in &problems {
+            for (name, solver) in problem.solvers.iter().map(|(k, v)| (&k.0, v)) {
+                colors.insert((name.as_str(), solver.solver_id.as_str()), 0);
+            }
+        }
+        for (i, color) in colors.values_mut().enumerate() {
+            *color = i + 1;
+        }
+
+        let yrange = if self.share_y {
+            let ranges = problems.iter().filter_map(|p| p.value_range());
+            let ymin = ranges.clone().map(|r| OrderedFloat(r.0)).min();
+            let ymax = ranges.map(|r| OrderedFloat(r.1)).max();
+            Some((
+                self.ymin.or(ymin.map(|y| y.0)),
+                self.ymax.or(ymax.map(|y| y.0)),
+            ))
+        } else {
+            None
+        };
+
+        let columns = self.columns.get().min(problems.len() + 1);
+        let rows = (problems.len() + 1).div_ceil(columns);
+        let mut s = format!(
+            "set terminal pngcairo size {},{} noenhanced; set output {:?};",
+            self.width * columns,
+            self.height * rows,
+            output
+        );
+        s += &format!("set multiplot layout {},{};", rows, columns);
+
+        // Approximate number of the characters that fit in the width of a panel.
+        let max_title_len = (self.width / 10).max(4);
+        let mut data_paths = Vec::new();
+        for &problem in &problems {
+            let data_path = track!(problem.generate_data())?;
+            let title = truncate(&problem.problem.spec.name, max_title_len);
+            s += &problem.panel_script(&data_path, &title, &colors, yrange, false);
+            s += ";";
+            data_paths.push(data_path);
+        }
+
+        // The last panel only shows the legend.
+        s += "unset title; unset xlabel; unset ylabel; unset grid; unset border; unset tics;";
+        s += "unset logscale y; set key center center;";
+        s += "plot [0:1] [0:1]";
+        for (i, ((name, _), color)) in colors.iter().enumerate() {
+            if i > 0 {
+                s += ",";
+            }
+            s += &format!(" NaN w l t {:?} lc {}", name, color);
+        }
+        s += ";unset multiplot;";
+
+        track!(execute_gnuplot(&s))?;
+        std::mem::drop(data_paths);
+
+        track!(self.output_file.dump_data(&output, |w| {
+            track_writeln!(w, "{}", DATA_HEADER)?;
+            for problem in &problems {
+                track!(problem.write_data(w))?;
+            }
+            Ok(())
+        }))?;
+        Ok(())
+    }
 }
 
+/// Header of the CSV files written by `--dump-data`.
+///
+/// `lower` and `upper` are the bounds of the band drawn by `--errorbar`
+/// (i.e., `mean -/+ sd` for `--aggregate mean` and the 25% and 75% quantiles for `--aggregate median`).
+const DATA_HEADER: &str = "problem,solver,budget,mean,median,lower,upper,n";
+
 #[derive(Debug)]
 struct Problem<'a> {
     problem_id: String,
     problem: &'a ProblemRecord,
-    solvers: BTreeMap<(&'a str, String), Solver>,
+    solvers: BTreeMap<(String, String), Solver>,
+    budget_unit: Option<BudgetUnit>,
+    reference_lines: Vec<ReferenceLine>,
     opt: &'a PlotCurveOpt,
 }
 impl<'a> Problem<'a> {
     fn new(
         problem_id: String,
         studies: Vec<&'a StudyRecord>,
+        targets: &Targets,
         opt: &'a PlotCurveOpt,
     ) -> Result<Self> {
         let problem = &studies[0].problem;
+        let budget_unit =
+            Some(studies[0].budget_unit).filter(|&u| studies.iter().all(|s| s.budget_unit == u));
         let mut solvers = BTreeMap::<_, Vec<_>>::new();
         for study in studies {
-            let study_id = track!(study.id())?;
-            solvers
-                .entry((study.solver.spec.name.as_str(), study_id))
-                .or_default()
-                .push(study);
+            let mut name = study.solver.spec.name.clone();
+            let mut study_id = track!(study.id())?;
+            if let Some(key) = &opt.group_by {
+                let label = study.label(key);
+                name = format!("{} ({}={})", name, key, label);
+                study_id = format!("{}/{}", study_id, label);
+            }
+            solvers.entry((name, study_id)).or_default().push(study);
         }
         Ok(Self {
             problem_id,
             problem,
             solvers: solvers
                 .into_iter()
-                .map(|(k, v)| (k, Solver::new(v, opt)))
-                .collect(),
+                .map(|(k, v)| Ok((k, track!(Solver::new(v, opt))?)))
+                .collect::<Result<_>>()?,
+            budget_unit,
+            reference_lines: if opt.metric == Metric::BestValue {
+                ReferenceLine::new(&problem.spec, &targets.get(&problem.spec))
+            } else {
+                Vec::new()
+            },
             opt,
         })
     }
 
+    fn is_plottable(&self) -> bool {
+        // This plot doesn't support multi-objective problems.
+        !(self.opt.metric == Metric::BestValue
+            && self.problem.spec.values_domain.variables().len() != 1)
+    }
+
     fn plot(&self) -> Result<bool> {
-        if self.opt.metric == Metric::BestValue
-            && self.problem.spec.values_domain.variables().len() != 1
-        {
-            // This plot doesn't support multi-objective problems.
+        if !self.is_plottable() {
             return Ok(false);
         }
 
+        let output = track!(self.opt.output_file.output_path(
+            &self.opt.output_dir,
+            "{problem}-{problem_id}.{format}",
+            &[
+                ("problem", &self.problem.spec.name),
+                ("problem_id", &self.problem_id),
+                ("metric", self.opt.metric.name()),
+                ("format", "png"),
+            ]
+        ))?;
+        let output = if let Some(output) = output {
+            output
+        } else {
+            return Ok(false);
+        };
+
         let data_path = track!(self.generate_data())?;
-        let script = self.make_gnuplot_script(&data_path);
+        let script = self.make_gnuplot_script(&data_path, &output);
         track!(execute_gnuplot(&script))?;
         std::mem::drop(data_path);
 
+        track!(self.opt.output_file.dump_data(&output, |w| {
+            track_writeln!(w, "{}", DATA_HEADER)?;
+            track!(self.write_data(w))
+        }))?;
         Ok(true)
     }
 
-    fn make_gnuplot_script(&self, data_path: &TempPath) -> String {
-        let ylabel = match self.opt.metric {
-            Metric::BestValue => self.problem.spec.values_domain.variables()[0].name(),
+    fn make_gnuplot_script(&self, data_path: &TempPath, output: &Path) -> String {
+        let mut s = format!(
+            "set terminal pngcairo size {},{} noenhanced; set output {:?};",
+            self.opt.width, self.opt.height, output
+        );
+        let colors = self
+            .solvers
+            .iter()
+            .enumerate()
+            .map(|(i, ((name, _), solver))| ((name.as_str(), solver.solver_id.as_str()), i + 1))
+            .collect();
+        s += &self.panel_script(data_path, &self.problem.spec.name, &colors, None, true);
+        s
+    }
+
+    /// Makes the gnuplot commands that plot the curves of this problem.
+    ///
+    /// `colors` maps pairs of a solver name and its identifier to line colors.
+    /// If `yrange` is given, it's used instead of the Y-axis range of this problem.
+    fn panel_script(
+        &self,
+        data_path: &TempPath,
+        title: &str,
+        colors: &BTreeMap<(&str, &str), usize>,
+        yrange: Option<(Option<f64>, Option<f64>)>,
+        key: bool,
+    ) -> String {
+        let metric = match self.opt.metric {
+            Metric::BestValue => self.problem.spec.values_domain.variables()[0].label(),
             Metric::Hypervolume => "Hypervolume",
             Metric::ElapsedTime => "Cumulative Elapsed Seconds (Ask + Evaluate + Tell)",
             Metric::SolverElapsedTime => "Cumulative Elapsed Seconds (Ask + Tell)",
         };
+        let ylabel = format!("{} [{}]", metric, self.aggregation_label());
 
+        let xlabel = match self.budget_unit {
+            Some(BudgetUnit::Trials) => "Budget (Trials)",
+            _ => "Budget",
+        };
         let mut s = format!(
-            "set title {:?}; set ylabel {:?}; set xlabel \"Budget\"; set grid;",
-            self.problem.spec.name, ylabel
+            "set title {:?}; set ylabel {:?}; set xlabel {:?}; set grid;",
+            title, ylabel, xlabel
         );
         s += "set datafile missing \"NaN\";";
 
         if self.opt.ylogscale {
             s += "set logscale y;"
         }
-
-        let output = self.opt.output_dir.join(format!(
-            "{}-{}.png",
-            normalize_filename(&self.problem.spec.name),
-            self.problem_id
-        ));
-        s += &format!(
-            "set terminal pngcairo size {},{} noenhanced; set output {:?};",
-            self.opt.width, self.opt.height, output
-        );
+        if !key {
+            s += "unset key;";
+        }
 
         if self.opt.errorbar {
             s += "set style fill transparent solid 0.2;";
             s += "set style fill noborder;";
         }
 
-        s += &format!(
-            "plot [{}:{}] [{}:{}]",
-            self.xmin(),
-            self.xmax(),
-            self.ymin(),
-            self.ymax()
-        );
+        let (ymin, ymax) = match yrange {
+            Some((ymin, ymax)) => (
+                ymin.map_or_else(String::new, |y| y.to_string()),
+                ymax.map_or_else(String::new, |y| y.to_string()),
+            ),
+            None => (self.ymin(), self.ymax()),
+        };
+        s += &format!("plot [{}:{}] [{}:{}]", self.xmin(), self.xmax(), ymin, ymax);
 
         let problem_steps = self.problem.spec.steps.last();
-        for i in 0..self.solvers.len() {
+        for (i, ((name, _), solver)) in self.solvers.iter().enumerate() {
             if i == 0 {
                 s += &format!(" {:?}", data_path);
             } else {
                 s += ", \"\"";
             }
+            let color = colors
+                .get(&(name.as_str(), solver.solver_id.as_str()))
+                .copied()
+                .unwrap_or(i + 1);
             s += &format!(
                 " u ($0/{}):{} w l t columnhead lc {}",
                 problem_steps,
-                (i * 2) + 1,
-                i + 1
+                (i * 3) + 1,
+                color
             );
             if self.opt.errorbar {
                 s += &format!(
-                    ", \"\" u ($0/{}):(${}-${}):(${}+${}) with filledcurves notitle lc {}",
+                    ", \"\" u ($0/{}):{}:{} with filledcurves notitle lc {}",
                     problem_steps,
-                    (i * 2) + 1,
-                    (i * 2) + 1 + 1,
-                    (i * 2) + 1,
-                    (i * 2) + 1 + 1,
-                    i + 1
+                    (i * 3) + 2,
+                    (i * 3) + 3,
+                    color
                 );
             }
         }
+        for line in &self.reference_lines {
+            if self.opt.ylogscale && line.value <= 0.0 {
+                continue;
+            }
+            s += &line.script();
+        }
 
         s
     }
 
+    /// Returns the label that states how the studies are aggregated into the curves.
+    fn aggregation_label(&self) -> String {
+        match (self.opt.aggregate, self.opt.errorbar) {
+            (Stat::Mean, true) => "mean +- sd".to_owned(),
+            (Stat::Median, true) => "median, 25%-75% quantiles".to_owned(),
+            (stat, false) => stat.name().to_owned(),
+        }
+    }
+
     fn ymax(&self) -> String {
         if let Some(y) = self.opt.ymax {
             y.to_string()
@@ -255,7 +501,10 @@ impl<'a> Problem<'a> {
             if let Some(y) = self
                 .solvers
                 .values()
-                .filter_map(|s| s.y(step).map(|v| OrderedFloat(v.avg)))
+                .filter_map(|s| {
+                    s.y(step)
+                        .map(|v| OrderedFloat(v.center(self.opt.aggregate)))
+                })
                 .max()
             {
                 y.0.to_string()
@@ -267,6 +516,33 @@ impl<'a> Problem<'a> {
         }
     }
 
+    /// Returns the minimum and maximum of the Y-axis range this problem needs.
+    ///
+    /// The maximum is determined in the same way as `ymax()` if `--ymax` is not specified.
+    fn value_range(&self) -> Option<(f64, f64)> {
+        let values = self
+            .solvers
+            .values()
+            .flat_map(|s| s.ys.iter().flatten())
+            .map(|v| {
+                if self.opt.errorbar {
+                    v.band(self.opt.aggregate)
+                } else {
+                    let center = v.center(self.opt.aggregate);
+                    (center, center)
+                }
+            })
+            .filter(|v| v.0.is_finite() && v.1.is_finite())
+            .collect::<Vec<_>>();
+        let ymin = values.iter().map(|v| OrderedFloat(v.0)).min()?.0;
+        let ymax = self
+            .ymax()
+            .parse()
+            .ok()
+            .or_else(|| values.iter().map(|v| OrderedFloat(v.1)).max().map(|y| y.0))?;
+        Some((ymin, ymax))
+    }
+
     fn ymin(&self) -> String {
         if let Some(y) = self.opt.ymin {
             y.to_string()
@@ -293,7 +569,7 @@ impl<'a> Problem<'a> {
         let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
 
         for (name, _) in self.solvers.keys() {
-            track_write!(temp_file, "{:?} {:?} ", name, name)?;
+            track_write!(temp_file, "{:?} {:?} {:?} ", name, name, name)?;
         }
         track_writeln!(temp_file)?;
 
@@ -306,9 +582,11 @@ impl<'a> Problem<'a> {
         for step in 0..max_step {
             for s in self.solvers.values() {
                 if let Some(v) = s.y(step) {
-                    track_write!(temp_file, "{} {} ", v.avg, v.sd)?;
+                    let (lower, upper) = v.band(self.opt.aggregate);
+                    let center = v.center(self.opt.aggregate);
+                    track_write!(temp_file, "{} {} {} ", center, lower, upper)?;
                 } else {
-                    track_write!(temp_file, "NaN NaN ")?;
+                    track_write!(temp_file, "NaN NaN NaN ")?;
                 }
             }
             track_writeln!(temp_file)?;
@@ -316,14 +594,41 @@ impl<'a> Problem<'a> {
 
         Ok(temp_file.into_temp_path())
     }
+
+    /// Writes the plotted series as CSV rows (see `DATA_HEADER`).
+    fn write_data(&self, writer: &mut dyn Write) -> Result<()> {
+        let problem_steps = self.problem.spec.steps.last() as f64;
+        for ((name, _), solver) in &self.solvers {
+            for (step, v) in solver.ys.iter().enumerate() {
+                if let Some(v) = v {
+                    let (lower, upper) = v.band(self.opt.aggregate);
+                    track_writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{},{}",
+                        csv_field(&self.problem.spec.name),
+                        csv_field(name),
+                        step as f64 / problem_steps,
+                        v.avg,
+                        v.median,
+                        lower,
+                        upper,
+                        v.n
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 struct Solver {
+    solver_id: String,
     ys: Vec<Option<Value>>,
 }
 impl Solver {
-    fn new(studies: Vec<&StudyRecord>, opt: &PlotCurveOpt) -> Self {
+    fn new(studies: Vec<&StudyRecord>, opt: &PlotCurveOpt) -> Result<Self> {
+        let solver_id = track!(studies[0].solver.id())?;
         let study_metrics = studies
             .iter()
             .map(|study| match opt.metric {
@@ -333,21 +638,8 @@ impl Solver {
                 Metric::SolverElapsedTime => study.elapsed_times(false),
             })
             .collect::<Vec<_>>();
-        let mut ys = vec![None];
-        for step in 1..studies[0].study_steps() {
-            let values = study_metrics
-                .iter()
-                .filter_map(|x| x.range(..=step).last().map(|v| *v.1))
-                .collect::<Vec<_>>();
-            if values.is_empty() {
-                ys.push(None);
-            } else {
-                let avg = average(values.iter().copied());
-                let sd = stddev(values.into_iter());
-                ys.push(Some(Value { avg, sd }));
-            }
-        }
-        Self { ys }
+        let ys = aggregate(&study_metrics, studies[0].study_steps());
+        Ok(Self { solver_id, ys })
     }
 
     fn y(&self, step: usize) -> Option<&Value> {
@@ -355,11 +647,288 @@ impl Solver {
     }
 }
 
-#[derive(Debug)]
+/// Horizontal line drawn on the best value curves.
+#[derive(Debug, Clone, PartialEq)]
+struct ReferenceLine {
+    value: f64,
+    title: String,
+    is_optimum: bool,
+}
+impl ReferenceLine {
+    /// Makes the lines of the known optimum of the given problem (if any) and the target values.
+    fn new(spec: &ProblemSpec, targets: &[f64]) -> Vec<Self> {
+        let optimum = target::optimum(spec).map(|value| Self {
+            value,
+            title: format!("optimum ({})", value),
+            is_optimum: true,
+        });
+        optimum
+            .into_iter()
+            .chain(targets.iter().map(|&value| Self {
+                value,
+                title: format!("target ({})", value),
+                is_optimum: false,
+            }))
+            .collect()
+    }
+
+    fn script(&self) -> String {
+        let (dash_type, color) = if self.is_optimum {
+            (2, "black")
+        } else {
+            (3, "gray40")
+        };
+        format!(
+            ", {} w l dt {} lc rgb {:?} t {:?}",
+            self.value, dash_type, color, self.title
+        )
+    }
+}
+
+/// Aggregates the metric curves of studies into the values at each step in `0..steps`.
+///
+/// The value of a curve at a step is the last one recorded until the step.
+fn aggregate(curves: &[BTreeMap<u64, f64>], steps: u64) -> Vec<Option<Value>> {
+    let mut ys = vec![None];
+    for step in 1..steps {
+        let values = curves
+            .iter()
+            .filter_map(|x| x.range(..=step).last().map(|v| *v.1))
+            .collect::<Vec<_>>();
+        ys.push(Value::new(&values));
+    }
+    ys
+}
+
+/// Statistics of the values of studies at a step.
+///
+/// Both the plotted curves and the data written by `--dump-data` are derived from this.
+#[derive(Debug, PartialEq)]
 struct Value {
     avg: f64,
     sd: f64,
+    median: f64,
+    quartiles: (f64, f64),
+    n: usize,
+}
+impl Value {
+    fn new(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let (median, quartiles) = BasicStats::new(values.iter().copied(), &[0.25, 0.75])
+            .map_or((f64::NAN, (f64::NAN, f64::NAN)), |s| {
+                (s.median, (s.quantiles[0].1, s.quantiles[1].1))
+            });
+        Some(Self {
+            avg: average(values.iter().copied()),
+            sd: stddev(values.iter().copied()),
+            median,
+            quartiles,
+            n: values.len(),
+        })
+    }
+
+    /// Returns the value of the central line.
+    fn center(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Mean => self.avg,
+            Stat::Median => self.median,
+        }
+    }
+
+    /// Returns the lower and upper bounds of the band around the central line.
+    fn band(&self, stat: Stat) -> (f64, f64) {
+        match stat {
+            Stat::Mean => (self.avg - self.sd, self.avg + self.sd),
+            Stat::Median => self.quartiles,
+        }
+    }
 }
 
 #[derive(Debug)]
 struct BestValues {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn reference_lines_work() -> TopLevelResult {
+        let spec = ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"));
+        let without_optimum = track!(spec.finish())?;
+        assert_eq!(ReferenceLine::new(&without_optimum, &[]), []);
+
+        let spec = ProblemSpecBuilder::new("foo")
+            .attr("optimum", "0.5")
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"));
+        let with_optimum = track!(spec.finish())?;
+        let lines = ReferenceLine::new(&with_optimum, &[2.0, 1.0]);
+        let titles = lines.iter().map(|l| l.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, ["optimum (0.5)", "target (2)", "target (1)"]);
+        assert_eq!(
+            lines[0].script(),
+            r#", 0.5 w l dt 2 lc rgb "black" t "optimum (0.5)""#
+        );
+
+        let lines = ReferenceLine::new(&without_optimum, &[1.5]);
+        assert_eq!(
+            lines[0].script(),
+            r#", 1.5 w l dt 3 lc rgb "gray40" t "target (1.5)""#
+        );
+        Ok(())
+    }
+
+    /// Makes a study whose `i`-th trial ends at `steps[i]` with `values[i]`.
+    fn study(seed: u64, steps: &[u64], values: &[f64]) -> Result<StudyRecord> {
+        let trials = steps
+            .iter()
+            .zip(values.iter())
+            .map(|(&step, &value)| {
+                serde_json::json!({
+                    "thread_id": 0,
+                    "params": [0.5],
+                    "evaluations": [{
+                        "values": [value],
+                        "start_step": step - 1,
+                        "end_step": step,
+                        "ask_elapsed": 0.0,
+                        "tell_elapsed": 0.0,
+                        "evaluate_elapsed": 0.0
+                    }]
+                })
+            })
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "start_time": "2020-01-01T00:00:00+00:00",
+            "end_time": "2020-01-01T00:00:01+00:00",
+            "seed": seed,
+            "budget": 3,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+                }},
+                "spec": {
+                    "name": "Foo, Bar",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": 1
+                }
+            },
+            "trials": trials
+        });
+        track!(serde_json::from_value(json).map_err(Error::from))
+    }
+
+    #[test]
+    fn write_data_works() -> TopLevelResult {
+        // The second study has no trials ending at the first step.
+        let studies = [
+            track!(study(0, &[1, 2, 3], &[3.0, 1.0, 2.0]))?,
+            track!(study(1, &[2, 3], &[4.0, 0.5]))?,
+        ];
+        let opt = PlotCurveOpt::from_iter(&["curve"]);
+        let problem = track!(Problem::new(
+            track!(studies[0].problem.id())?,
+            studies.iter().collect(),
+            &Targets::default(),
+            &opt
+        ))?;
+
+        // step 1: [3.0]      -> mean=3.0, median=3.0, sd=0.0, n=1
+        // step 2: [1.0, 4.0] -> mean=2.5, median=2.5, sd=1.5, n=2
+        let mut buf = Vec::new();
+        track!(problem.write_data(&mut buf))?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "\"Foo, Bar\",Random,1,3,3,3,3,1\n\
+             \"Foo, Bar\",Random,2,2.5,2.5,1,4,2\n"
+        );
+        assert_eq!(problem.aggregation_label(), "mean");
+
+        // The band is the interquartile range if the curves are aggregated by median.
+        let opt = PlotCurveOpt::from_iter(&["curve", "--aggregate", "median", "--errorbar"]);
+        let problem = track!(Problem::new(
+            track!(studies[0].problem.id())?,
+            studies.iter().collect(),
+            &Targets::default(),
+            &opt
+        ))?;
+        let mut buf = Vec::new();
+        track!(problem.write_data(&mut buf))?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "\"Foo, Bar\",Random,1,3,3,3,3,1\n\
+             \"Foo, Bar\",Random,2,2.5,2.5,1.75,3.25,2\n"
+        );
+        assert_eq!(problem.aggregation_label(), "median, 25%-75% quantiles");
+        Ok(())
+    }
+
+    #[test]
+    fn aggregate_works() {
+        let curves = vec![
+            vec![(1, 3.0), (2, 1.0)].into_iter().collect(),
+            vec![(2, 4.0), (3, 0.5)].into_iter().collect(),
+            vec![(1, 7.0)].into_iter().collect(),
+        ];
+        let ys = aggregate(&curves, 4);
+        assert_eq!(ys.len(), 4);
+        assert_eq!(ys[0], None);
+
+        // [3.0, 7.0]
+        assert_eq!(
+            ys[1],
+            Some(Value {
+                avg: 5.0,
+                sd: 2.0,
+                median: 5.0,
+                quartiles: (4.0, 6.0),
+                n: 2
+            })
+        );
+
+        // [1.0, 4.0, 7.0]
+        assert_eq!(
+            ys[2],
+            Some(Value {
+                avg: 4.0,
+                sd: 6f64.sqrt(),
+                median: 4.0,
+                quartiles: (2.5, 5.5),
+                n: 3
+            })
+        );
+
+        // [1.0, 0.5, 7.0]
+        let v = ys[3].as_ref().unwrap();
+        assert_eq!((v.median, v.n), (1.0, 3));
+        assert_eq!(v.center(Stat::Mean), 8.5 / 3.0);
+        assert_eq!(v.band(Stat::Mean), (v.avg - v.sd, v.avg + v.sd));
+        assert_eq!(v.center(Stat::Median), 1.0);
+        assert_eq!(v.band(Stat::Median), (0.75, 4.0));
+
+        assert_eq!(aggregate(&[], 2), [None, None]);
+    }
+}
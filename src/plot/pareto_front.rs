@@ -1,13 +1,15 @@
 //! `kurobako plot pareto-front` command.
 #![allow(clippy::format_push_string)]
-use super::{execute_gnuplot, normalize_filename};
+use super::{execute_gnuplot, OutputFileOpt};
+use crate::load::LoadOpt;
 use crate::record::StudyRecord;
+use crate::report::csv_field;
 use indicatif::{ProgressBar, ProgressStyle};
 use kurobako_core::{Error, ErrorKind, Result};
 use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write as _;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tempfile::{NamedTempFile, TempPath};
 
@@ -42,9 +44,18 @@ pub struct PlotParetoFrontOpt {
     /// Maximum value of X axis.
     #[structopt(long)]
     pub xmax: Option<f64>,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub output_file: OutputFileOpt,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
 }
 impl PlotParetoFrontOpt {
     pub(crate) fn plot(&self, study_records: &[StudyRecord]) -> Result<()> {
+        track!(self.load.require_all_trials("`plot pareto-front`"))?;
         let mut studies = BTreeMap::new();
         for record in study_records {
             track_assert_eq!(
@@ -91,19 +102,53 @@ impl<'a> Study<'a> {
     }
 
     fn plot(&self, opt: &PlotParetoFrontOpt) -> Result<()> {
-        let data_path = track!(self.generate_data())?;
-        let script = track!(self.make_gnuplot_script(&data_path, opt))?;
+        let problem = &self.instances[0].problem;
+        let solver = &self.instances[0].solver;
+        let study_id = track!(self.instances[0].id())?;
+        let output = track!(opt.output_file.output_path(
+            &opt.output_dir,
+            "{problem}-{solver}-{study_id}.{format}",
+            &[
+                ("problem", &problem.spec.name),
+                ("solver", &solver.spec.name),
+                ("study_id", &study_id),
+                ("format", "png"),
+            ]
+        ))?;
+        let output = if let Some(output) = output {
+            output
+        } else {
+            return Ok(());
+        };
+
+        let points = self.points();
+        let data_path = track!(generate_data(&points))?;
+        let script = self.make_gnuplot_script(&data_path, &output, opt);
         track!(execute_gnuplot(&script))?;
         std::mem::drop(data_path);
 
+        track!(opt.output_file.dump_data(&output, |w| {
+            let values = problem.spec.values_domain.variables();
+            track_writeln!(
+                w,
+                "budget,{},{}",
+                csv_field(values[0].name()),
+                csv_field(values[1].name())
+            )?;
+            for (budget, v0, v1) in &points {
+                track_writeln!(w, "{},{},{}", budget, v0, v1)?;
+            }
+            Ok(())
+        }))?;
         Ok(())
     }
 
     fn make_gnuplot_script(
         &self,
         data_path: &TempPath,
+        output: &Path,
         opt: &PlotParetoFrontOpt,
-    ) -> Result<String> {
+    ) -> String {
         let problem = &self.instances[0].problem;
         let solver = &self.instances[0].solver;
         let title = format!(
@@ -116,17 +161,10 @@ impl<'a> Study<'a> {
              set xlabel {:?}; \
              set grid;",
             title,
-            problem.spec.values_domain.variables()[1].name(),
-            problem.spec.values_domain.variables()[0].name(),
+            problem.spec.values_domain.variables()[1].label(),
+            problem.spec.values_domain.variables()[0].label(),
         );
 
-        let output = opt.output_dir.join(format!(
-            "{}-{}-{}.png",
-            normalize_filename(&problem.spec.name),
-            normalize_filename(&solver.spec.name),
-            track!(self.instances[0].id())?
-        ));
-
         s += &format!(
             "set terminal pngcairo size {},{} noenhanced; set output {:?};",
             opt.width, opt.height, output
@@ -142,28 +180,29 @@ impl<'a> Study<'a> {
             data_path
         );
 
-        Ok(s)
+        s
     }
 
-    fn generate_data(&self) -> Result<TempPath> {
-        let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
-
+    /// Returns the `(budget, first objective value, second objective value)` points of the trials.
+    fn points(&self) -> Vec<(f64, f64, f64)> {
+        let mut points = Vec::new();
         let problem_steps = self.instances[0].problem.spec.steps.last();
         for study in &self.instances {
-            eprintln!("trials: {:?}", study.trials.len());
-            dbg!(problem_steps);
-            let mut c = 0;
-            for trial in &study.trials {
+            for (trial, end_step) in study.trial_end_steps() {
                 if let Some(vs) = trial.values(problem_steps) {
-                    c += 1;
-                    let end_step = trial.end_step().unwrap_or_else(|| unreachable!());
                     let budget = end_step as f64 / problem_steps as f64;
-                    track_writeln!(temp_file, "{} {} {}", budget, vs[1], vs[0])?;
+                    points.push((budget, vs[0], vs[1]));
                 }
             }
-            dbg!(c);
         }
+        points
+    }
+}
 
-        Ok(temp_file.into_temp_path())
+fn generate_data(points: &[(f64, f64, f64)]) -> Result<TempPath> {
+    let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
+    for (budget, v0, v1) in points {
+        track_writeln!(temp_file, "{} {} {}", budget, v1, v0)?;
     }
+    Ok(temp_file.into_temp_path())
 }
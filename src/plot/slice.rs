@@ -1,14 +1,16 @@
 //! `kurobako plot slice` command.
 #![allow(clippy::format_push_string)]
-use super::{execute_gnuplot, normalize_filename};
+use super::{execute_gnuplot, OutputFileOpt};
+use crate::load::LoadOpt;
 use crate::record::StudyRecord;
+use crate::report::csv_field;
 use indicatif::{ProgressBar, ProgressStyle};
 use kurobako_core::domain::Variable;
 use kurobako_core::{Error, Result};
 use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write as _;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tempfile::{NamedTempFile, TempPath};
 
@@ -43,9 +45,18 @@ pub struct PlotSliceOpt {
     /// Maximum value of X axis.
     #[structopt(long)]
     pub xmax: Option<f64>,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub output_file: OutputFileOpt,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
 }
 impl PlotSliceOpt {
     pub(crate) fn plot(&self, study_records: &[StudyRecord]) -> Result<()> {
+        track!(self.load.require_all_trials("`plot slice`"))?;
         let mut studies = BTreeMap::new();
         for record in study_records {
             let id = track!(record.id())?;
@@ -104,10 +115,44 @@ impl<'a> Study<'a> {
             .iter()
             .enumerate()
         {
-            let data_path = track!(self.generate_data(param_index))?;
-            let script = track!(self.make_gnuplot_script(param, &data_path, opt))?;
+            let problem = &self.instances[0].problem;
+            let solver = &self.instances[0].solver;
+            let study_id = track!(self.instances[0].id())?;
+            let output = track!(opt.output_file.output_path(
+                &opt.output_dir,
+                "{problem}-{solver}-{param}-{study_id}.{format}",
+                &[
+                    ("problem", &problem.spec.name),
+                    ("solver", &solver.spec.name),
+                    ("param", param.name()),
+                    ("study_id", &study_id),
+                    ("format", "png"),
+                ]
+            ))?;
+            let output = if let Some(output) = output {
+                output
+            } else {
+                continue;
+            };
+
+            let points = self.points(param_index);
+            let data_path = track!(generate_data(&points))?;
+            let script = self.make_gnuplot_script(param, &data_path, &output, opt);
             track!(execute_gnuplot(&script))?;
             std::mem::drop(data_path);
+
+            track!(opt.output_file.dump_data(&output, |w| {
+                track_writeln!(
+                    w,
+                    "budget,{},{}",
+                    csv_field(problem.spec.values_domain.variables()[0].name()),
+                    csv_field(param.name())
+                )?;
+                for (budget, value, param) in &points {
+                    track_writeln!(w, "{},{},{}", budget, value, param)?;
+                }
+                Ok(())
+            }))?;
         }
         Ok(())
     }
@@ -116,8 +161,9 @@ impl<'a> Study<'a> {
         &self,
         param: &Variable,
         data_path: &TempPath,
+        output: &Path,
         opt: &PlotSliceOpt,
-    ) -> Result<String> {
+    ) -> String {
         let problem = &self.instances[0].problem;
         let solver = &self.instances[0].solver;
         let title = format!(
@@ -130,18 +176,10 @@ impl<'a> Study<'a> {
              set xlabel \"Parameter: {}\"; \
              set grid;",
             title,
-            problem.spec.values_domain.variables()[0].name(),
-            param.name()
+            problem.spec.values_domain.variables()[0].label(),
+            param.label()
         );
 
-        let output = opt.output_dir.join(format!(
-            "{}-{}-{}-{}.png",
-            normalize_filename(&problem.spec.name),
-            normalize_filename(&solver.spec.name),
-            normalize_filename(param.name()),
-            track!(self.instances[0].id())?
-        ));
-
         s += &format!(
             "set terminal pngcairo size {},{} noenhanced; set output {:?};",
             opt.width, opt.height, output
@@ -157,26 +195,32 @@ impl<'a> Study<'a> {
             data_path
         );
 
-        Ok(s)
+        s
     }
 
-    fn generate_data(&self, param_index: usize) -> Result<TempPath> {
-        let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
-
+    /// Returns the `(budget, value, parameter)` points of the trials.
+    fn points(&self, param_index: usize) -> Vec<(f64, f64, f64)> {
+        let mut points = Vec::new();
         let problem_steps = self.instances[0].problem.spec.steps.last();
         for study in &self.instances {
-            for trial in &study.trials {
+            for (trial, end_step) in study.trial_end_steps() {
                 if let Some(v) = trial.value(problem_steps) {
-                    let end_step = trial.end_step().unwrap_or_else(|| unreachable!());
                     let budget = end_step as f64 / problem_steps as f64;
                     let p = trial.params[param_index];
                     if p.is_finite() {
-                        track_writeln!(temp_file, "{} {} {}", budget, v, p)?;
+                        points.push((budget, v, p));
                     }
                 }
             }
         }
+        points
+    }
+}
 
-        Ok(temp_file.into_temp_path())
+fn generate_data(points: &[(f64, f64, f64)]) -> Result<TempPath> {
+    let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
+    for (budget, value, param) in points {
+        track_writeln!(temp_file, "{} {} {}", budget, value, param)?;
     }
+    Ok(temp_file.into_temp_path())
 }
@@ -0,0 +1,522 @@
+//! `kurobako plot trials` command.
+#![allow(clippy::format_push_string)]
+use super::{execute_gnuplot, truncate, OutputFileOpt};
+use crate::load::LoadOpt;
+use crate::record::{ProblemRecord, StudyRecord};
+use crate::report::csv_field;
+use indicatif::{ProgressBar, ProgressStyle};
+use kurobako_core::{Error, ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+use tempfile::{NamedTempFile, TempPath};
+
+/// Quantity of the X-axis of trial plots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum XAxis {
+    /// Index of the completed trials (in the order of their completion).
+    #[default]
+    Index,
+
+    /// Cumulative elapsed seconds (ask + evaluate + tell) at the completion of each trial.
+    ElapsedTime,
+}
+impl XAxis {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["index", "elapsed-time"];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Index => "Completed Trials",
+            Self::ElapsedTime => "Cumulative Elapsed Seconds (Ask + Evaluate + Tell)",
+        }
+    }
+}
+impl FromStr for XAxis {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "index" => Ok(Self::Index),
+            "elapsed-time" => Ok(Self::ElapsedTime),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown X-axis: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for XAxis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Index => write!(f, "index"),
+            Self::ElapsedTime => write!(f, "elapsed-time"),
+        }
+    }
+}
+
+/// Options of the `kurobako plot trials` command.
+///
+/// The final objective value of every completed trial is plotted against its completion,
+/// with the best value so far overlaid as a line.
+/// Each problem is plotted as an image that has a panel for each solver.
+/// Multi-objective problems are not supported.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct PlotTrialsOpt {
+    /// Output directory where generated images are stored.
+    #[structopt(long, short = "o", default_value = "images/trials/")]
+    pub output_dir: PathBuf,
+
+    /// Width of each panel in pixels.
+    #[structopt(long, default_value = "800")]
+    pub width: usize,
+
+    /// Height of each panel in pixels.
+    #[structopt(long, default_value = "600")]
+    pub height: usize,
+
+    /// Number of the panel columns of an image.
+    #[structopt(long, default_value = "4")]
+    pub columns: NonZeroUsize,
+
+    /// Quantity of X axis.
+    #[structopt(long, default_value = "index", possible_values = XAxis::POSSIBLE_VALUES)]
+    pub x_axis: XAxis,
+
+    /// Minimum value of Y axis.
+    #[structopt(long)]
+    pub ymin: Option<f64>,
+
+    /// Maximum value of Y axis.
+    #[structopt(long)]
+    pub ymax: Option<f64>,
+
+    /// Makes Y axis log scale.
+    #[structopt(long)]
+    pub ylogscale: bool,
+
+    /// Maximum number of the trials plotted in a panel.
+    ///
+    /// If a panel has more trials than this, they are uniformly thinned out
+    /// (the best value lines are drawn from all the trials).
+    #[structopt(long, default_value = "10000")]
+    pub max_points: NonZeroUsize,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub output_file: OutputFileOpt,
+
+    #[structopt(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
+}
+impl PlotTrialsOpt {
+    pub(crate) fn plot(&self, studies: &[StudyRecord]) -> Result<()> {
+        track!(self.load.require_all_trials("`plot trials`"))?;
+        let mut problems = BTreeMap::<_, Vec<_>>::new();
+        for study in studies {
+            problems
+                .entry(track!(study.problem.id())?)
+                .or_default()
+                .push(study);
+        }
+
+        let pb = ProgressBar::new(problems.len() as u64);
+        let template =
+            "(PLOT) [{elapsed_precise}] [{pos}/{len} {percent:>3}%] [ETA {eta:>3}] {msg}";
+        pb.set_style(ProgressStyle::default_bar().template(template));
+
+        track!(fs::create_dir_all(&self.output_dir).map_err(Error::from); self.output_dir)?;
+
+        for (problem_id, studies) in problems {
+            let problem = &studies[0].problem;
+            if problem.spec.values_domain.variables().len() != 1 {
+                eprintln!(
+                    "Warning: skipped the multi-objective problem {:?}",
+                    problem.spec.name
+                );
+            } else {
+                let problem = track!(Problem::new(problem_id, studies, self))?;
+                track!(problem.plot())?;
+            }
+            pb.inc(1);
+        }
+        pb.finish_with_message(&format!("done (dir={:?})", self.output_dir));
+
+        Ok(())
+    }
+}
+
+/// Header of the CSV files written by `--dump-data`.
+const DATA_HEADER: &str = "problem,solver,seed,x,value,best";
+
+#[derive(Debug)]
+struct Problem<'a> {
+    problem_id: String,
+    problem: &'a ProblemRecord,
+    panels: Vec<Panel>,
+    opt: &'a PlotTrialsOpt,
+}
+impl<'a> Problem<'a> {
+    fn new(
+        problem_id: String,
+        studies: Vec<&'a StudyRecord>,
+        opt: &'a PlotTrialsOpt,
+    ) -> Result<Self> {
+        let problem = &studies[0].problem;
+        let mut solvers = BTreeMap::<_, Vec<_>>::new();
+        for study in studies {
+            let key = (study.solver.spec.name.clone(), track!(study.id())?);
+            solvers.entry(key).or_default().push(study);
+        }
+        let panels = solvers
+            .into_iter()
+            .map(|((name, _), studies)| Panel::new(name, &studies, opt))
+            .filter(|panel| !panel.points.is_empty())
+            .collect();
+        Ok(Self {
+            problem_id,
+            problem,
+            panels,
+            opt,
+        })
+    }
+
+    fn plot(&self) -> Result<()> {
+        if self.panels.is_empty() {
+            eprintln!(
+                "Warning: no completed trials of the problem {:?}",
+                self.problem.spec.name
+            );
+            return Ok(());
+        }
+
+        let output = track!(self.opt.output_file.output_path(
+            &self.opt.output_dir,
+            "{problem}-{problem_id}.{format}",
+            &[
+                ("problem", &self.problem.spec.name),
+                ("problem_id", &self.problem_id),
+                ("format", "png"),
+            ]
+        ))?;
+        let output = if let Some(output) = output {
+            output
+        } else {
+            return Ok(());
+        };
+
+        let columns = self.opt.columns.get().min(self.panels.len());
+        let rows = self.panels.len().div_ceil(columns);
+        let mut s = format!(
+            "set terminal pngcairo size {},{} noenhanced; set output {:?};",
+            self.opt.width * columns,
+            self.opt.height * rows,
+            output
+        );
+        s += &format!(
+            "set multiplot layout {},{} title {:?};",
+            rows, columns, self.problem.spec.name
+        );
+
+        // Approximate number of the characters that fit in the width of a panel.
+        let max_title_len = (self.opt.width / 10).max(4);
+        let mut data_paths = Vec::new();
+        for (i, panel) in self.panels.iter().enumerate() {
+            let data_path = track!(panel.generate_data())?;
+            let title = truncate(&panel.solver, max_title_len);
+            s += &self.panel_script(&data_path, &title, i + 1);
+            s += ";";
+            data_paths.push(data_path);
+        }
+        s += "unset multiplot;";
+
+        track!(execute_gnuplot(&s))?;
+        std::mem::drop(data_paths);
+
+        track!(self.opt.output_file.dump_data(&output, |w| {
+            track_writeln!(w, "{}", DATA_HEADER)?;
+            track!(self.write_data(w))
+        }))?;
+        Ok(())
+    }
+
+    /// Makes the gnuplot commands that plot a panel.
+    fn panel_script(&self, data_path: &TempPath, title: &str, color: usize) -> String {
+        let mut s = format!(
+            "set title {:?}; set ylabel {:?}; set xlabel {:?}; set grid;",
+            title,
+            self.problem.spec.values_domain.variables()[0].label(),
+            self.opt.x_axis.label()
+        );
+        if self.opt.ylogscale {
+            s += "set logscale y;";
+        }
+        s += &format!(
+            "plot [:] [{}:{}] {:?} index 0 u 1:2 w p pt 7 ps 0.5 lc {} t \"trials\", \
+             \"\" index 1 u 1:2 w steps lw 2 lc rgb \"black\" t \"best\"",
+            self.opt.ymin.map(|v| v.to_string()).unwrap_or_default(),
+            self.opt.ymax.map(|v| v.to_string()).unwrap_or_default(),
+            data_path,
+            color
+        );
+        s
+    }
+
+    /// Writes the plotted points as CSV rows (see `DATA_HEADER`).
+    fn write_data(&self, writer: &mut dyn Write) -> Result<()> {
+        for panel in &self.panels {
+            for p in &panel.points {
+                track_writeln!(
+                    writer,
+                    "{},{},{},{},{},{}",
+                    csv_field(&self.problem.spec.name),
+                    csv_field(&panel.solver),
+                    p.seed,
+                    p.x,
+                    p.value,
+                    p.best
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Point {
+    seed: u64,
+    x: f64,
+    value: f64,
+    best: f64,
+}
+
+/// Points of the studies of a solver.
+#[derive(Debug)]
+struct Panel {
+    solver: String,
+
+    // Thinned out if there are more than `--max-points`.
+    points: Vec<Point>,
+
+    // The `(x, best value)` points where the best value of each study is updated,
+    // followed by the last point of the study.
+    best_lines: Vec<Vec<(f64, f64)>>,
+}
+impl Panel {
+    fn new(solver: String, studies: &[&StudyRecord], opt: &PlotTrialsOpt) -> Self {
+        let mut points = Vec::new();
+        let mut best_lines = Vec::new();
+        for study in studies {
+            let mut line = Vec::new();
+            let mut best = f64::INFINITY;
+            let mut last_x = None;
+            for (x, value) in trial_points(study, opt.x_axis) {
+                if value < best {
+                    best = value;
+                    line.push((x, best));
+                }
+                points.push(Point {
+                    seed: study.seed,
+                    x,
+                    value,
+                    best,
+                });
+                last_x = Some(x);
+            }
+            if let Some(last_x) = last_x {
+                if line.last().is_some_and(|&(x, _)| x != last_x) {
+                    line.push((last_x, best));
+                }
+            }
+            if !line.is_empty() {
+                best_lines.push(line);
+            }
+        }
+        Self {
+            solver,
+            points: thin_out(points, opt.max_points.get()),
+            best_lines,
+        }
+    }
+
+    /// Writes the points (the first data block) and the best value lines (the second one).
+    fn generate_data(&self) -> Result<TempPath> {
+        let mut temp_file = track!(NamedTempFile::new().map_err(Error::from))?;
+        for p in &self.points {
+            track_writeln!(temp_file, "{} {}", p.x, p.value)?;
+        }
+        track_writeln!(temp_file)?;
+        track_writeln!(temp_file)?;
+        for line in &self.best_lines {
+            for (x, best) in line {
+                track_writeln!(temp_file, "{} {}", x, best)?;
+            }
+            track_writeln!(temp_file)?;
+        }
+        Ok(temp_file.into_temp_path())
+    }
+}
+
+/// Returns the `(x, value)` points of the completed trials of a study in the order of their completion.
+fn trial_points(study: &StudyRecord, x_axis: XAxis) -> Vec<(f64, f64)> {
+    let problem_steps = study.problem.spec.steps.last();
+    let mut trials = study
+        .trial_end_steps()
+        .into_iter()
+        .filter_map(|(t, step)| t.value(problem_steps).map(|value| (step, value)))
+        .collect::<Vec<_>>();
+    trials.sort_by_key(|t| t.0);
+
+    let elapsed_times = match x_axis {
+        XAxis::Index => BTreeMap::new(),
+        XAxis::ElapsedTime => study.elapsed_times(true),
+    };
+    trials
+        .into_iter()
+        .enumerate()
+        .map(|(i, (step, value))| {
+            let x = match x_axis {
+                XAxis::Index => (i + 1) as f64,
+                XAxis::ElapsedTime => elapsed_times.range(..=step).last().map_or(0.0, |(_, &t)| t),
+            };
+            (x, value)
+        })
+        .collect()
+}
+
+/// Uniformly thins `items` out so that at most `max` items remain.
+fn thin_out<T>(items: Vec<T>, max: usize) -> Vec<T> {
+    let n = items.len();
+    if n <= max {
+        return items;
+    }
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| (i + 1) * max / n > i * max / n)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    /// Makes a study whose `i`-th trial ends at the `i + 1`-th step with `values[i]`.
+    fn study(seed: u64, values: &[f64]) -> Result<StudyRecord> {
+        let trials = values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                serde_json::json!({
+                    "thread_id": 0,
+                    "params": [0.5],
+                    "evaluations": [{
+                        "values": [value],
+                        "start_step": i,
+                        "end_step": i + 1,
+                        "ask_elapsed": 0.5,
+                        "tell_elapsed": 0.0,
+                        "evaluate_elapsed": 1.0
+                    }]
+                })
+            })
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "start_time": "2020-01-01T00:00:00+00:00",
+            "end_time": "2020-01-01T00:00:01+00:00",
+            "seed": seed,
+            "budget": values.len(),
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+                }},
+                "spec": {
+                    "name": "Foo",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": 1
+                }
+            },
+            "trials": trials
+        });
+        track!(serde_json::from_value(json).map_err(Error::from))
+    }
+
+    #[test]
+    fn panel_works() -> TopLevelResult {
+        let studies = [
+            track!(study(0, &[3.0, 1.0, 2.0]))?,
+            track!(study(1, &[4.0, 5.0]))?,
+        ];
+        let opt = PlotTrialsOpt::from_iter(&["trials"]);
+        let panel = Panel::new(
+            "Random".to_owned(),
+            &studies.iter().collect::<Vec<_>>(),
+            &opt,
+        );
+        let points = panel
+            .points
+            .iter()
+            .map(|p| (p.seed, p.x, p.value, p.best))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            points,
+            [
+                (0, 1.0, 3.0, 3.0),
+                (0, 2.0, 1.0, 1.0),
+                (0, 3.0, 2.0, 1.0),
+                (1, 1.0, 4.0, 4.0),
+                (1, 2.0, 5.0, 4.0)
+            ]
+        );
+        assert_eq!(
+            panel.best_lines,
+            [
+                vec![(1.0, 3.0), (2.0, 1.0), (3.0, 1.0)],
+                vec![(1.0, 4.0), (2.0, 4.0)]
+            ]
+        );
+
+        // The best value lines are drawn from all the trials even if the points are thinned out.
+        let opt =
+            PlotTrialsOpt::from_iter(&["trials", "--max-points", "2", "--x-axis", "elapsed-time"]);
+        let panel = Panel::new(
+            "Random".to_owned(),
+            &studies.iter().collect::<Vec<_>>(),
+            &opt,
+        );
+        let points = panel.points.iter().map(|p| p.x).collect::<Vec<_>>();
+        assert_eq!(points, [4.5, 3.0]);
+        assert_eq!(panel.best_lines[0], [(1.5, 3.0), (3.0, 1.0), (4.5, 1.0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn thin_out_works() {
+        assert_eq!(thin_out(vec![0, 1, 2], 5), [0, 1, 2]);
+        assert_eq!(thin_out((0..10).collect(), 5), [1, 3, 5, 7, 9]);
+        assert_eq!(thin_out((0..10).collect(), 3), [3, 6, 9]);
+        assert_eq!(thin_out((0..10).collect(), 1), [9]);
+    }
+}
@@ -1,20 +1,32 @@
 //! The problem for `kurobako`.
+#[cfg(feature = "external-process")]
 use kurobako_core::epi::problem::ExternalProgramProblemRecipe;
+#[cfg(all(unix, feature = "external-process"))]
+use kurobako_core::epi::problem::UnixSocketProblemRecipe;
 use kurobako_core::problem::{
     BoxProblem, BoxProblemFactory, ProblemFactory, ProblemRecipe, ProblemSpec,
 };
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
-use kurobako_core::Result;
-use kurobako_problems::{hpobench, nasbench, sigopt, surrogate, warm_starting, zdt};
+use kurobako_core::{Error, Result};
+#[cfg(feature = "external-process")]
+use kurobako_problems::command;
+use kurobako_problems::{
+    bo_standard, expression, hpobench, learning_curve, mf_branin, nasbench, sigopt, surrogate,
+    svm_like, warm_starting, zdt,
+};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
 mod average;
+mod cache;
 mod ln;
+mod noise;
 mod rank;
 mod study;
 
+pub use self::cache::CACHE_HITS_ATTR;
+
 /// Problem recipe.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 #[structopt(rename_all = "kebab-case")]
@@ -38,6 +50,26 @@ impl ProblemRecipe for KurobakoProblemRecipe {
         })
     }
 }
+impl KurobakoProblemRecipe {
+    /// Makes a recipe of the problem that adds Gaussian noise to the objective values of this problem.
+    ///
+    /// `level` is the standard deviation of the noise.
+    pub fn with_noise(self, level: f64) -> Result<Self> {
+        let problem = track!(serde_json::to_value(&self).map_err(Error::from))?;
+        Ok(Self {
+            name: None,
+            inner: InnerRecipe::Noise(noise::NoiseProblemRecipe { problem, level }),
+        })
+    }
+}
+impl From<bo_standard::BoStandardProblemRecipe> for KurobakoProblemRecipe {
+    fn from(f: bo_standard::BoStandardProblemRecipe) -> Self {
+        Self {
+            name: None,
+            inner: InnerRecipe::BoStandard(f),
+        }
+    }
+}
 impl From<hpobench::HpobenchProblemRecipe> for KurobakoProblemRecipe {
     fn from(f: hpobench::HpobenchProblemRecipe) -> Self {
         Self {
@@ -75,35 +107,61 @@ impl From<surrogate::SurrogateProblemRecipe> for KurobakoProblemRecipe {
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "snake_case")]
 enum InnerRecipe {
+    #[cfg(feature = "external-process")]
     Command(ExternalProgramProblemRecipe),
+    #[cfg(all(unix, feature = "external-process"))]
+    UnixSocket(UnixSocketProblemRecipe),
     /// Recipe of `SigoptProblem`.
     Sigopt(sigopt::SigoptProblemRecipe),
     Nasbench(nasbench::NasbenchProblemRecipe),
     Hpobench(hpobench::HpobenchProblemRecipe),
     Zdt(zdt::ZdtProblemRecipe),
+    MfBranin(mf_branin::MfBraninProblemRecipe),
+    BoStandard(bo_standard::BoStandardProblemRecipe),
+    SvmLike(svm_like::SvmLikeProblemRecipe),
+    LearningCurve(learning_curve::LearningCurveProblemRecipe),
     Surrogate(surrogate::SurrogateProblemRecipe),
     Study(self::study::StudyProblemRecipe),
     Rank(self::rank::RankProblemRecipe),
     Average(self::average::AverageProblemRecipe),
     Ln(self::ln::LnProblemRecipe),
+    Noise(self::noise::NoiseProblemRecipe),
+    Cache(self::cache::CacheProblemRecipe),
     WarmStarting(warm_starting::WarmStartingProblemRecipe),
+    Expression(expression::ExpressionProblemRecipe),
+    #[cfg(feature = "external-process")]
+    Exec(command::CommandProblemRecipe),
 }
 impl ProblemRecipe for InnerRecipe {
     type Factory = BoxProblemFactory;
 
     fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
         match self {
+            #[cfg(feature = "external-process")]
             Self::Command(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            #[cfg(all(unix, feature = "external-process"))]
+            Self::UnixSocket(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Sigopt(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Nasbench(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Hpobench(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Zdt(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::MfBranin(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::BoStandard(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::SvmLike(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::LearningCurve(p) => {
+                track!(p.create_factory(registry).map(BoxProblemFactory::new))
+            }
             Self::Surrogate(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Study(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Rank(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Average(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::Ln(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::Noise(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::Cache(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
             Self::WarmStarting(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            Self::Expression(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
+            #[cfg(feature = "external-process")]
+            Self::Exec(p) => track!(p.create_factory(registry).map(BoxProblemFactory::new)),
         }
     }
 }
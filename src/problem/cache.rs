@@ -0,0 +1,347 @@
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::problem::{
+    BoxEvaluator, BoxProblem, BoxProblemFactory, Evaluator, Problem, ProblemFactory, ProblemRecipe,
+    ProblemSpec,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{Error, ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead as _, BufReader, BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+/// Key of the trial attribute that holds the number of the evaluations served from the cache.
+pub const CACHE_HITS_ATTR: &str = "cache_hits";
+
+/// Recipe to cache the evaluation results of a problem.
+///
+/// The results are keyed by the parameters, the fidelity and the requested step,
+/// so this is only appropriate for deterministic problems.
+/// The number of the cached evaluations of each trial is recorded in the `cache_hits` trial attribute.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct CacheProblemRecipe {
+    /// Problem recipe JSON.
+    pub problem: JsonRecipe,
+
+    /// File to persist the cached results across runs.
+    ///
+    /// The results in the file are loaded when the problem is created,
+    /// and new results are appended to it (one JSON object per line).
+    /// A file must not be shared by different problems.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_file: Option<PathBuf>,
+}
+impl ProblemRecipe for CacheProblemRecipe {
+    type Factory = CacheProblemFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        let problem = track!(registry.create_problem_factory_from_json(&self.problem))?;
+        let cache = if let Some(path) = &self.cache_file {
+            track!(Cache::open(path))?
+        } else {
+            Cache::default()
+        };
+        Ok(CacheProblemFactory {
+            problem,
+            cache: Arc::new(Mutex::new(cache)),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CacheProblemFactory {
+    problem: BoxProblemFactory,
+    cache: Arc<Mutex<Cache>>,
+}
+impl ProblemFactory for CacheProblemFactory {
+    type Problem = CacheProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        track!(self.problem.specification())
+    }
+
+    fn create_problem(&self, rng: ArcRng) -> Result<Self::Problem> {
+        let problem = track!(self.problem.create_problem(rng))?;
+        Ok(CacheProblem {
+            problem,
+            cache: Arc::clone(&self.cache),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CacheProblem {
+    problem: BoxProblem,
+    cache: Arc<Mutex<Cache>>,
+}
+impl Problem for CacheProblem {
+    type Evaluator = CacheEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        let evaluator = track!(self.problem.create_evaluator(params.clone()))?;
+        Ok(CacheEvaluator {
+            evaluator,
+            params,
+            cache: Arc::clone(&self.cache),
+            hits: 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CacheEvaluator {
+    evaluator: BoxEvaluator,
+    params: Params,
+    cache: Arc<Mutex<Cache>>,
+    hits: u64,
+}
+impl Evaluator for CacheEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        track!(self.evaluate_with_fidelity(next_step, &Params::default()))
+    }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        let key = CacheKey {
+            params: self.params.clone(),
+            fidelity: fidelity.clone(),
+            step: next_step,
+        };
+        if let Some(result) = track!(self.cache.lock().map_err(Error::from))?.get(&key) {
+            self.hits += 1;
+            return Ok(result);
+        }
+
+        let (current_step, values) =
+            track!(self.evaluator.evaluate_with_fidelity(next_step, fidelity))?;
+        track!(track!(self.cache.lock().map_err(Error::from))?.insert(
+            key,
+            current_step,
+            values.clone()
+        ))?;
+        Ok((current_step, values))
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        let mut attrs = self.evaluator.take_attrs();
+        if self.hits > 0 {
+            attrs.insert(CACHE_HITS_ATTR.to_owned(), self.hits.into());
+        }
+        attrs
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    params: Params,
+    fidelity: Params,
+    step: u64,
+}
+
+/// Line of a cache file.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    params: Params,
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    fidelity: Params,
+    step: u64,
+    current_step: u64,
+    values: Values,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    results: HashMap<CacheKey, (u64, Values)>,
+    file: Option<CacheFile>,
+}
+impl Cache {
+    fn open(path: &Path) -> Result<Self> {
+        let lock = track!(CacheLock::acquire(path))?;
+
+        let mut results = HashMap::new();
+        match File::open(path) {
+            Ok(file) => {
+                for (i, line) in BufReader::new(file).lines().enumerate() {
+                    let line = track!(line.map_err(Error::from); path)?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<CacheEntry>(&line) {
+                        Ok(e) => {
+                            let key = CacheKey {
+                                params: e.params,
+                                fidelity: e.fidelity,
+                                step: e.step,
+                            };
+                            results.insert(key, (e.current_step, e.values));
+                        }
+                        Err(e) => {
+                            // e.g., the last line of a file written by an interrupted run.
+                            eprintln!(
+                                "Warning: skipped a broken line of the cache file {:?} (line {}): {}",
+                                path,
+                                i + 1,
+                                e
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(track!(Error::from(e); path)),
+        }
+
+        let file = track!(OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(Error::from); path)?;
+        Ok(Self {
+            results,
+            file: Some(CacheFile {
+                writer: BufWriter::new(file),
+                _lock: lock,
+            }),
+        })
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<(u64, Values)> {
+        self.results.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: CacheKey, current_step: u64, values: Values) -> Result<()> {
+        // Non-finite values can't be represented in JSON.
+        if values.is_empty() || !values.iter().all(|v| v.is_finite()) {
+            return Ok(());
+        }
+        if let Some(file) = &mut self.file {
+            let entry = CacheEntry {
+                params: key.params.clone(),
+                fidelity: key.fidelity.clone(),
+                step: key.step,
+                current_step,
+                values: values.clone(),
+            };
+            track!(serde_json::to_writer(&mut file.writer, &entry).map_err(Error::from))?;
+            track!(writeln!(file.writer).map_err(Error::from))?;
+        }
+        self.results.insert(key, (current_step, values));
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct CacheFile {
+    writer: BufWriter<File>,
+
+    // Declared after `writer` so that the lock is released after the results are flushed.
+    _lock: CacheLock,
+}
+impl Drop for CacheFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            eprintln!("Warning: cannot flush the cache file: {}", e);
+        }
+    }
+}
+
+/// Lock file (`${CACHE_FILE}.lock`) that prevents concurrent runs from writing the same cache file.
+#[derive(Debug)]
+struct CacheLock {
+    path: PathBuf,
+}
+impl CacheLock {
+    fn acquire(cache_file: &Path) -> Result<Self> {
+        let mut path = cache_file.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut f) => {
+                track!(write!(f, "{}", std::process::id()).map_err(Error::from); path)?;
+                Ok(Self { path })
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => track_panic!(
+                ErrorKind::InvalidInput,
+                "The cache file {:?} is being used by another run (remove {:?} if no run is using it)",
+                cache_file,
+                path
+            ),
+            Err(e) => Err(track!(Error::from(e); path)),
+        }
+    }
+}
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problem::KurobakoProblemRecipe;
+    use crate::solver::KurobakoSolverRecipe;
+    use kurobako_problems::bo_standard::{BoStandardFunction, BoStandardProblemRecipe};
+    use trackable::result::TopLevelResult;
+
+    fn recipe(cache_file: &Path) -> Result<CacheProblemRecipe> {
+        let problem = KurobakoProblemRecipe::from(BoStandardProblemRecipe {
+            function: BoStandardFunction::Branin,
+        });
+        Ok(CacheProblemRecipe {
+            problem: track!(serde_json::to_value(&problem).map_err(Error::from))?,
+            cache_file: Some(cache_file.to_path_buf()),
+        })
+    }
+
+    fn evaluate(problem: &CacheProblem, x: f64) -> Result<(f64, Option<serde_json::Value>)> {
+        let mut evaluator = track!(problem.create_evaluator(Params::new(vec![x, 1.0])))?;
+        let (_, values) = track!(evaluator.evaluate(1))?;
+        Ok((values[0], evaluator.take_attrs().remove(CACHE_HITS_ATTR)))
+    }
+
+    #[test]
+    fn cache_file_works() -> TopLevelResult {
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let path = dir.path().join("cache.jsonl");
+        let recipe = track!(recipe(&path))?;
+        let registry = FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
+
+        let factory = track!(recipe.create_factory(&registry))?;
+        let problem = track!(factory.create_problem(ArcRng::new(0)))?;
+        let (v0, hits) = track!(evaluate(&problem, 0.0))?;
+        assert_eq!(hits, None);
+        let (v1, hits) = track!(evaluate(&problem, 0.0))?;
+        assert_eq!((v1, hits), (v0, Some(1.into())));
+
+        // Concurrent runs can't write the same file.
+        assert!(recipe.create_factory(&registry).is_err());
+        std::mem::drop(problem);
+        std::mem::drop(factory);
+
+        // The results are reused by another run.
+        let factory = track!(recipe.create_factory(&registry))?;
+        let problem = track!(factory.create_problem(ArcRng::new(1)))?;
+        let (v1, hits) = track!(evaluate(&problem, 0.0))?;
+        assert_eq!((v1, hits), (v0, Some(1.into())));
+        let (_, hits) = track!(evaluate(&problem, 2.0))?;
+        assert_eq!(hits, None);
+        std::mem::drop(problem);
+        std::mem::drop(factory);
+
+        let contents = track!(fs::read_to_string(&path).map_err(Error::from))?;
+        assert_eq!(contents.lines().count(), 2);
+        assert!(!dir.path().join("cache.jsonl.lock").exists());
+        Ok(())
+    }
+}
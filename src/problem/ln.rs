@@ -9,6 +9,7 @@ use kurobako_core::rng::ArcRng;
 use kurobako_core::trial::{Params, Values};
 use kurobako_core::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use structopt::StructOpt;
 
 /// Recipe to convert the distributions of continuous variables of a problem from uniform to log-uniform.
@@ -41,7 +42,7 @@ impl ProblemFactory for LnProblemFactory {
 
         let mut transformed_vars = Vec::new();
         for var in spec.params_domain.variables() {
-            if let (Range::Continuous { low, high }, Distribution::Uniform) =
+            if let (Range::Continuous { low, high, .. }, Distribution::Uniform) =
                 (var.range(), var.distribution())
             {
                 transformed_vars.push(
@@ -105,4 +106,16 @@ impl Evaluator for LnEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
         track!(self.evaluator.evaluate(next_step))
     }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        track!(self.evaluator.evaluate_with_fidelity(next_step, fidelity))
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        self.evaluator.take_attrs()
+    }
 }
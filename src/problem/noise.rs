@@ -0,0 +1,133 @@
+use kurobako_core::domain::{self, Domain};
+use kurobako_core::json::JsonRecipe;
+use kurobako_core::problem::{
+    BoxEvaluator, BoxProblem, BoxProblemFactory, Evaluator, Problem, ProblemFactory, ProblemRecipe,
+    ProblemSpec,
+};
+use kurobako_core::registry::FactoryRegistry;
+use kurobako_core::rng::{standard_normal, ArcRng};
+use kurobako_core::trial::{Params, Values};
+use kurobako_core::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use structopt::StructOpt;
+
+/// Recipe to add Gaussian noise to the objective values of a problem.
+///
+/// Unlike `GaussianNoiseFilterRecipe`, the noisy values are the evaluation results
+/// (i.e., they are recorded and reported as they are).
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct NoiseProblemRecipe {
+    /// Problem recipe JSON.
+    pub problem: JsonRecipe,
+
+    /// Standard deviation of the noise added to each objective value.
+    #[structopt(long)]
+    pub level: f64,
+}
+impl ProblemRecipe for NoiseProblemRecipe {
+    type Factory = NoiseProblemFactory;
+
+    fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
+        track_assert!(
+            self.level.is_finite() && self.level >= 0.0,
+            ErrorKind::InvalidInput; self.level
+        );
+        let problem = track!(registry.create_problem_factory_from_json(&self.problem))?;
+        Ok(NoiseProblemFactory {
+            problem,
+            level: self.level,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct NoiseProblemFactory {
+    problem: BoxProblemFactory,
+    level: f64,
+}
+impl ProblemFactory for NoiseProblemFactory {
+    type Problem = NoiseProblem;
+
+    fn specification(&self) -> Result<ProblemSpec> {
+        let mut spec = track!(self.problem.specification())?;
+        spec.name = format!("{} (noise={})", spec.name, self.level);
+
+        // Noisy values may be out of the ranges of the original objectives.
+        let values = spec
+            .values_domain
+            .variables()
+            .iter()
+            .map(|v| domain::var(v.name()))
+            .collect();
+        spec.values_domain = track!(Domain::new(values))?;
+        Ok(spec)
+    }
+
+    fn create_problem(&self, rng: ArcRng) -> Result<Self::Problem> {
+        let problem = track!(self.problem.create_problem(rng.clone()))?;
+        Ok(NoiseProblem {
+            problem,
+            level: self.level,
+            rng,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct NoiseProblem {
+    problem: BoxProblem,
+    level: f64,
+    rng: ArcRng,
+}
+impl Problem for NoiseProblem {
+    type Evaluator = NoiseEvaluator;
+
+    fn create_evaluator(&self, params: Params) -> Result<Self::Evaluator> {
+        let evaluator = track!(self.problem.create_evaluator(params))?;
+        Ok(NoiseEvaluator {
+            evaluator,
+            level: self.level,
+            rng: self.rng.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct NoiseEvaluator {
+    evaluator: BoxEvaluator,
+    level: f64,
+    rng: ArcRng,
+}
+impl NoiseEvaluator {
+    fn add_noise(&mut self, values: Values) -> Values {
+        let level = self.level;
+        let rng = &mut self.rng;
+        Values::new(
+            values
+                .iter()
+                .map(|v| v + level * standard_normal(rng))
+                .collect(),
+        )
+    }
+}
+impl Evaluator for NoiseEvaluator {
+    fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
+        let (step, values) = track!(self.evaluator.evaluate(next_step))?;
+        Ok((step, self.add_noise(values)))
+    }
+
+    fn evaluate_with_fidelity(
+        &mut self,
+        next_step: u64,
+        fidelity: &Params,
+    ) -> Result<(u64, Values)> {
+        let (step, values) = track!(self.evaluator.evaluate_with_fidelity(next_step, fidelity))?;
+        Ok((step, self.add_noise(values)))
+    }
+
+    fn take_attrs(&mut self) -> BTreeMap<String, serde_json::Value> {
+        self.evaluator.take_attrs()
+    }
+}
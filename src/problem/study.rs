@@ -2,7 +2,7 @@ use crate::runner::StudyRunner;
 use crate::study::StudyRecipe;
 use crate::variable::Var;
 use kurobako_core::domain::Range;
-use kurobako_core::json::{self, JsonRecipe};
+use kurobako_core::json::JsonRecipe;
 use kurobako_core::problem::{
     Evaluator, Problem, ProblemFactory, ProblemRecipe, ProblemSpec, ProblemSpecBuilder,
 };
@@ -22,7 +22,9 @@ pub struct StudyProblemRecipe {
     pub study: JsonRecipe,
 
     /// Variable JSONs.
-    #[structopt(long, parse(try_from_str = json::parse_json))]
+    ///
+    /// The name of each variable is the (dot-separated) path of the target value in the study recipe.
+    #[structopt(long, parse(try_from_str = Var::parse_json))]
     pub vars: Vec<Var>,
 }
 impl ProblemRecipe for StudyProblemRecipe {
@@ -127,9 +129,15 @@ impl StudyProblem {
                 let n = serde_json::Number::from(val as i64);
                 *json = serde_json::Value::Number(n);
             }
-            Range::Categorical { choices } => {
+            Range::Categorical { choices, .. } => {
                 *json = serde_json::Value::String(choices[val as usize].clone());
             }
+            Range::Ordinal { values } => {
+                let v = values[val as usize];
+                let n =
+                    track_assert_some!(serde_json::Number::from_f64(v), ErrorKind::InvalidInput; v);
+                *json = serde_json::Value::Number(n);
+            }
         }
         Ok(())
     }
@@ -155,19 +163,21 @@ impl Evaluator for StudyEvaluator {
     fn evaluate(&mut self, next_step: u64) -> Result<(u64, Values)> {
         loop {
             track!(self.runner.run_once())?;
-            if self.runner.current_step() < next_step {
+            let finished = self.runner.is_finished();
+            if self.runner.current_step() < next_step && !finished {
                 continue;
             }
 
             if let Some(values) = self.runner.best_values().cloned() {
-                let current_step = self.runner.current_step();
+                let mut current_step = self.runner.current_step();
+                if finished {
+                    // The budget may be exhausted before the last step (e.g., `BudgetUnit::Trials`).
+                    current_step = current_step.max(self.runner.max_step());
+                }
                 return Ok((current_step, values));
             }
 
-            track_assert!(
-                self.runner.current_step() < self.runner.max_step(),
-                ErrorKind::Other
-            );
+            track_assert!(!finished, ErrorKind::Other);
         }
     }
 }
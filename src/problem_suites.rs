@@ -1,6 +1,6 @@
 //! Built-in problem suites.
 use crate::problem::KurobakoProblemRecipe;
-use kurobako_problems::{hpobench, sigopt, surrogate, zdt};
+use kurobako_problems::{bo_standard, hpobench, sigopt, surrogate, zdt};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -13,6 +13,7 @@ pub enum ProblemSuite {
     Hpobench(HpobenchProblemSuite),
     Zdt(ZdtProblemSuite),
     Surrogate(SurrogateProblemSuite),
+    BoStandard(BoStandardProblemSuite),
 }
 impl ProblemSuite {
     /// Returns an iterator that iterates over the recipes included in the specified problem suite.
@@ -22,6 +23,7 @@ impl ProblemSuite {
             Self::Hpobench(s) => s.recipes(),
             Self::Zdt(s) => s.recipes(),
             Self::Surrogate(s) => s.recipes(),
+            Self::BoStandard(s) => s.recipes(),
         }
     }
 }
@@ -74,6 +76,32 @@ impl ZdtProblemSuite {
     }
 }
 
+/// Problem suite containing the standard test functions used in Bayesian optimization papers
+/// (Branin, Hartmann-3, Hartmann-6 and Shekel with `m=5,7,10`).
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct BoStandardProblemSuite {
+    /// If specified, Gaussian noise with this standard deviation is added to the objective values.
+    #[structopt(long, name = "LEVEL")]
+    pub noise: Option<f64>,
+}
+impl BoStandardProblemSuite {
+    fn recipes(&self) -> Box<dyn Iterator<Item = KurobakoProblemRecipe>> {
+        let noise = self.noise;
+        Box::new(
+            bo_standard::BoStandardFunction::all()
+                .iter()
+                .map(|&function| {
+                    KurobakoProblemRecipe::from(bo_standard::BoStandardProblemRecipe { function })
+                })
+                .map(move |recipe| match noise {
+                    None => recipe,
+                    Some(level) => recipe.with_noise(level).unwrap_or_else(|e| panic!("{}", e)),
+                }),
+        )
+    }
+}
+
 /// Problem suite defined in `https://github.com/sigopt/evalset`.
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
@@ -249,3 +277,62 @@ impl SurrogateProblemSuite {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::problem::{Evaluator as _, Problem as _, ProblemFactory, ProblemRecipe};
+    use kurobako_core::registry::FactoryRegistry;
+    use kurobako_core::rng::ArcRng;
+    use kurobako_core::trial::Params;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn bo_standard_suite_works() -> TopLevelResult {
+        let registry =
+            FactoryRegistry::new::<KurobakoProblemRecipe, crate::solver::KurobakoSolverRecipe>();
+
+        let suite = BoStandardProblemSuite { noise: None };
+        let specs = suite
+            .recipes()
+            .map(|r| track!(r.create_factory(&registry)?.specification()))
+            .collect::<kurobako_core::Result<Vec<_>>>()?;
+        let names = specs.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Branin",
+                "Hartmann3",
+                "Hartmann6",
+                "Shekel5",
+                "Shekel7",
+                "Shekel10"
+            ]
+        );
+
+        // Noisy variants wrap the same recipes.
+        let suite = BoStandardProblemSuite { noise: Some(0.5) };
+        for (recipe, spec) in suite.recipes().zip(specs.iter()) {
+            let noisy = track!(recipe.create_factory(&registry)?.specification())?;
+            assert_eq!(noisy.name, format!("{} (noise=0.5)", spec.name));
+            assert_eq!(noisy.params_domain, spec.params_domain);
+            assert_eq!(noisy.attrs.get("optimum"), spec.attrs.get("optimum"));
+        }
+
+        let evaluate = |recipe: &KurobakoProblemRecipe| -> kurobako_core::Result<f64> {
+            let problem = track!(recipe
+                .create_factory(&registry)?
+                .create_problem(ArcRng::new(0)))?;
+            let mut evaluator = track!(problem.create_evaluator(Params::new(vec![0.0, 0.0])))?;
+            Ok(track!(evaluator.evaluate(1))?.1[0])
+        };
+        let branin = KurobakoProblemRecipe::from(bo_standard::BoStandardProblemRecipe {
+            function: bo_standard::BoStandardFunction::Branin,
+        });
+        let value = track!(evaluate(&branin))?;
+        let noisy_value = track!(evaluate(&track!(branin.with_noise(0.5))?))?;
+        assert_ne!(value, noisy_value);
+        assert!((value - noisy_value).abs() < 5.0);
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 pub use self::problem::ProblemRecord;
+pub use self::provenance::ProvenanceRecord;
+pub use self::setup::SetupRecord;
 pub use self::solver::SolverRecord;
-pub use self::study::{StudyRecord, StudyRecordBuilder};
-pub use self::trial::{EvaluationRecord, TrialRecord, TrialRecordBuilder};
+pub use self::study::{StudyRecord, StudyRecordBuilder, Termination};
+pub use self::trial::{EvaluationRecord, IntermediateRecord, TrialRecord, TrialRecordBuilder};
 
 mod problem;
+mod provenance;
+mod setup;
 mod solver;
 mod study;
 mod trial;
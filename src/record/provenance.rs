@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::process::Command;
+
+/// Information about the environment that produced a study record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ProvenanceRecord {
+    /// Version of `kurobako`.
+    pub kurobako_version: String,
+
+    /// Version of `kurobako_core` (i.e., the protocol between kurobako, problems and solvers).
+    pub core_version: String,
+
+    /// Name of the host where the study was executed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+impl ProvenanceRecord {
+    /// Makes a `ProvenanceRecord` that describes the current process.
+    pub fn current(record_hostname: bool) -> Self {
+        Self {
+            kurobako_version: env!("CARGO_PKG_VERSION").to_owned(),
+            core_version: kurobako_core::VERSION.to_owned(),
+            hostname: if record_hostname { hostname() } else { None },
+        }
+    }
+}
+
+fn hostname() -> Option<String> {
+    let name = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("hostname").output().ok()?;
+            if output.status.success() {
+                String::from_utf8(output.stdout).ok()
+            } else {
+                None
+            }
+        })?;
+    let name = name.trim();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_works() {
+        let provenance = ProvenanceRecord::current(false);
+        assert_eq!(provenance.kurobako_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance.core_version, kurobako_core::VERSION);
+        assert_eq!(provenance.hostname, None);
+
+        // The hostname is omitted if it's unknown.
+        let json = serde_json::to_value(&provenance).unwrap_or_else(|e| panic!("{}", e));
+        assert!(json.get("hostname").is_none());
+        let hostname = ProvenanceRecord::current(true).hostname;
+        assert!(hostname.is_none_or(|name| !name.is_empty() && name.trim() == name));
+    }
+}
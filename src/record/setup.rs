@@ -0,0 +1,30 @@
+use crate::time::ElapsedSeconds;
+use serde::{Deserialize, Serialize};
+
+/// Time taken to set up a study and to make the first ask.
+///
+/// The factory creation times include the time taken to load the specifications
+/// (e.g., spawning the external programs of EPI problems and solvers).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct SetupRecord {
+    /// Time taken to create the problem factory and to get the problem specification.
+    pub problem_factory_elapsed: ElapsedSeconds,
+
+    /// Time taken to create the solver factory and to get the solver specification.
+    pub solver_factory_elapsed: ElapsedSeconds,
+
+    /// Time taken by `ProblemFactory::create_problem`.
+    pub create_problem_elapsed: ElapsedSeconds,
+
+    /// Time taken by `SolverFactory::create_solver`.
+    pub create_solver_elapsed: ElapsedSeconds,
+
+    /// Time taken by the first ask to the solver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_ask_elapsed: Option<ElapsedSeconds>,
+
+    /// Mean of the time taken by the asks after the first one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steady_ask_elapsed: Option<ElapsedSeconds>,
+}
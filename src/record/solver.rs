@@ -6,7 +6,9 @@ use sha2::{Digest, Sha256};
 use std::fmt::Write as _;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SolverRecord {
+    #[cfg_attr(feature = "json-schema", schemars(with = "serde_json::Value"))]
     pub recipe: KurobakoSolverRecipe,
     pub spec: SolverSpec,
 }
@@ -1,9 +1,12 @@
+use crate::filters::KurobakoFilterRecipe;
+use crate::problem::CACHE_HITS_ATTR;
 use crate::record::{
-    EvaluationRecord, ProblemRecord, SolverRecord, TrialRecord, TrialRecordBuilder,
+    EvaluationRecord, ProblemRecord, ProvenanceRecord, SetupRecord, SolverRecord, TrialRecord,
+    TrialRecordBuilder,
 };
-use crate::study::{Scheduling, StudyRecipe};
-use crate::time::DateTime;
-use chrono::Local;
+use crate::study::{BudgetUnit, Scheduling, StudyRecipe};
+use crate::time::{DateTime, ElapsedSeconds, StudyClock};
+use kurobako_core::domain::{Domain, VariableBuilder};
 use kurobako_core::hypervolume;
 use kurobako_core::num::OrderedFloat;
 use kurobako_core::problem::ProblemSpec;
@@ -14,6 +17,7 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
 use std::fmt::Write as _;
 use std::num::NonZeroUsize;
 use std::time::Duration;
@@ -23,7 +27,16 @@ pub struct StudyRecordBuilder {
     recipe: StudyRecipe,
     solver: SolverSpec,
     problem: ProblemSpec,
-    start_time: DateTime,
+    provenance: Option<ProvenanceRecord>,
+    setup: Option<SetupRecord>,
+    clock: StudyClock,
+    clamped_durations: u64,
+    out_of_range_values: u64,
+    asks: u64,
+    first_ask_elapsed: Option<ElapsedSeconds>,
+    steady_ask_elapsed: f64,
+    ask_cost_steps: u64,
+    solver_attrs: BTreeMap<String, String>,
     trials: BTreeMap<TrialId, TrialRecord>,
     pareto_frontier: BTreeMap<TrialId, (Params, Values)>,
 }
@@ -33,29 +46,90 @@ impl StudyRecordBuilder {
             recipe,
             solver,
             problem,
-            start_time: Local::now(),
+            provenance: None,
+            setup: None,
+            clock: StudyClock::start(),
+            clamped_durations: 0,
+            out_of_range_values: 0,
+            asks: 0,
+            first_ask_elapsed: None,
+            steady_ask_elapsed: 0.0,
+            ask_cost_steps: 0,
+            solver_attrs: BTreeMap::new(),
             trials: BTreeMap::new(),
             pareto_frontier: BTreeMap::new(),
         }
     }
 
+    pub fn provenance(mut self, provenance: ProvenanceRecord) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// Sets the time taken to set up the study.
+    ///
+    /// The ask latencies of the setup record are filled by `StudyRecordBuilder::add_ask`.
+    pub fn setup(mut self, setup: SetupRecord) -> Self {
+        self.setup = Some(setup);
+        self
+    }
+
+    pub fn solver_attrs(&mut self, attrs: BTreeMap<String, String>) {
+        self.solver_attrs = attrs;
+    }
+
+    /// Counts an ask made to the solver, and records the time taken by it.
+    pub fn add_ask(&mut self, elapsed: ElapsedSeconds) {
+        self.asks += 1;
+        if self.first_ask_elapsed.is_none() {
+            self.first_ask_elapsed = Some(elapsed);
+        } else {
+            self.steady_ask_elapsed += elapsed.get();
+        }
+    }
+
+    /// Counts the steps charged to the budget for the time taken by asks.
+    pub fn add_ask_cost_steps(&mut self, steps: u64) {
+        self.ask_cost_steps += steps;
+    }
+
+    /// Counts the values returned out of the values domain of the problem.
+    pub fn add_out_of_range_values(&mut self, n: u64) {
+        self.out_of_range_values += n;
+    }
+
+    /// Returns the number of the out-of-range values counted so far.
+    pub fn out_of_range_values(&self) -> u64 {
+        self.out_of_range_values
+    }
+
     pub fn add_trial(&mut self, trial: TrialRecordBuilder) {
+        let ask_elapsed = self.clamp(trial.ask_elapsed);
+        let tell_elapsed = self.clamp(trial.tell_elapsed);
+        let evaluate_elapsed = self.clamp(trial.evaluate_elapsed);
         let t = self.trials.entry(trial.id).or_insert_with(|| TrialRecord {
             thread_id: trial.thread_id,
             params: trial.params.clone(),
+            evaluated_params: trial.evaluated_params.clone(),
             evaluations: Vec::new(),
+            attrs: BTreeMap::new(),
+            merged: None,
         });
+        t.attrs.extend(trial.attrs.clone());
 
         t.evaluations.push(EvaluationRecord {
             values: trial.values.clone(),
+            fidelity: trial.fidelity.clone(),
+            status: trial.status,
+            intermediates: trial.intermediates.clone(),
             start_step: trial.start_step,
             end_step: trial.end_step,
-            ask_elapsed: trial.ask_elapsed,
-            tell_elapsed: trial.tell_elapsed,
-            evaluate_elapsed: trial.evaluate_elapsed,
+            ask_elapsed,
+            tell_elapsed,
+            evaluate_elapsed,
         });
 
-        if t.steps() == self.problem.steps.last() {
+        if trial.status.is_ok() && t.steps() == self.problem.steps.last() {
             let is_dominated = self
                 .pareto_frontier
                 .values()
@@ -77,6 +151,14 @@ impl StudyRecordBuilder {
         }
     }
 
+    fn clamp(&mut self, elapsed: ElapsedSeconds) -> ElapsedSeconds {
+        let (elapsed, clamped) = ElapsedSeconds::clamped(elapsed.get());
+        if clamped {
+            self.clamped_durations += 1;
+        }
+        elapsed
+    }
+
     pub fn pareto_frontier(&self) -> impl '_ + Iterator<Item = (TrialId, &Params, &Values)> {
         self.pareto_frontier
             .iter()
@@ -84,11 +166,25 @@ impl StudyRecordBuilder {
     }
 
     pub fn finish(self) -> StudyRecord {
-        StudyRecord {
-            start_time: self.start_time,
-            end_time: Local::now(),
+        let seed = self.recipe.seed.unwrap_or_else(|| unreachable!());
+        let first_ask_elapsed = self.first_ask_elapsed;
+        let steady_ask_elapsed = if self.asks > 1 {
+            Some(ElapsedSeconds::new(
+                self.steady_ask_elapsed / (self.asks - 1) as f64,
+            ))
+        } else {
+            None
+        };
+        let mut record = StudyRecord {
+            start_time: self.clock.anchor(),
+            end_time: self.clock.now(),
             budget: self.recipe.budget,
-            seed: self.recipe.seed.unwrap_or_else(|| unreachable!()),
+            budget_unit: self.recipe.budget_unit,
+            ask_cost_per_second: self.recipe.ask_cost_per_second,
+            ask_cost_steps: self.ask_cost_steps,
+            tell_delay: self.recipe.tell_delay,
+            seed,
+            problem_seed: Some(self.recipe.problem_seed(seed)),
             concurrency: self.recipe.concurrency,
             scheduling: self.recipe.scheduling,
             solver: SolverRecord {
@@ -100,28 +196,188 @@ impl StudyRecordBuilder {
                 spec: self.problem,
             },
             trials: self.trials.into_iter().map(|(_, v)| v).collect(),
+            filters: self.recipe.filters,
+            provenance: self.provenance,
+            setup: self.setup.map(|setup| SetupRecord {
+                first_ask_elapsed,
+                steady_ask_elapsed,
+                ..setup
+            }),
+            clamped_durations: self.clamped_durations,
+            out_of_range_values: self.out_of_range_values,
+            solver_attrs: self.solver_attrs,
+            labels: self.recipe.labels,
+            fingerprint: None,
+            termination: None,
+            budget_accounting: None,
+        };
+        record.budget_accounting = Some(BudgetAccounting {
+            asks: self.asks,
+            ..record.compute_budget_accounting()
+        });
+        record
+    }
+}
+
+/// Reason why a study terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Termination {
+    /// The budget (in steps) was consumed.
+    BudgetConsumed,
+
+    /// The number of the started trials reached the budget (see `BudgetUnit::Trials`).
+    TrialLimitReached,
+}
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BudgetConsumed => write!(f, "budget-consumed"),
+            Self::TrialLimitReached => write!(f, "trial-limit-reached"),
+        }
+    }
+}
+
+/// Breakdown of the budget consumed by a study.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct BudgetAccounting {
+    /// Number of the asks made to the solver.
+    pub asks: u64,
+
+    /// Total number of the evaluated steps.
+    pub evaluated_steps: u64,
+
+    /// Number of the steps belonging to the trials that reached the last step of the problem.
+    pub completed_steps: u64,
+
+    /// Number of the steps belonging to the trials that were abandoned (e.g., pruned) before the last step.
+    pub pruned_steps: u64,
+}
+impl BudgetAccounting {
+    /// Returns the fraction of the evaluated steps spent on the trials that were eventually completed.
+    ///
+    /// Returns `None` if no steps were evaluated.
+    pub fn pruning_efficiency(&self) -> Option<f64> {
+        if self.evaluated_steps == 0 {
+            None
+        } else {
+            Some(self.completed_steps as f64 / self.evaluated_steps as f64)
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct StudyRecord {
     pub start_time: DateTime,
     pub end_time: DateTime,
     pub seed: u64,
+
+    /// Seed of the RNG used to instantiate the problem.
+    ///
+    /// Studies having the same problem seed evaluated the same problem instance (see `ProblemInstance`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub problem_seed: Option<u64>,
+
     pub budget: u64,
+    #[serde(default, skip_serializing_if = "BudgetUnit::is_steps")]
+    pub budget_unit: BudgetUnit,
+
+    /// Number of steps charged to the budget per second taken by the solver to ask trials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ask_cost_per_second: Option<f64>,
+
+    /// Total number of the steps charged to the budget for the time taken by asks.
+    ///
+    /// These steps are included in the start and end steps of the trials,
+    /// so the budget axis of this study shows the total consumption.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub ask_cost_steps: u64,
+
+    /// Number of asks after which the result of an evaluation was told to the solver.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub tell_delay: u64,
+
     pub concurrency: NonZeroUsize,
     pub scheduling: Scheduling,
     pub solver: SolverRecord,
     pub problem: ProblemRecord,
     pub trials: Vec<TrialRecord>,
+
+    /// Filters applied between the solver and the problem (copied from `StudyRecipe::filters`).
+    ///
+    /// The study recipe itself isn't recorded, so this is the only record of the filters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Vec<serde_json::Value>"))]
+    pub filters: Vec<KurobakoFilterRecipe>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ProvenanceRecord>,
+
+    /// Time taken to set up this study (absent in the records produced by older versions).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setup: Option<SetupRecord>,
+
+    /// Number of out-of-range durations (e.g., negative ones) that were clamped while recording this study.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub clamped_durations: u64,
+
+    /// Number of the values returned out of the values domain of the problem (see `kurobako run --on-out-of-range`).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub out_of_range_values: u64,
+
+    /// Diagnostics reported by the solver at the end of this study.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub solver_attrs: BTreeMap<String, String>,
+
+    /// Metadata labels copied from the study recipe.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+
+    /// Fingerprint of the trial sequence (see `StudyRecord::compute_fingerprint`).
+    ///
+    /// This is recorded only if `kurobako run --fingerprint` is specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+
+    /// Reason why this study terminated (absent in the records produced by older versions).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub termination: Option<Termination>,
+
+    /// Breakdown of the consumed budget.
+    ///
+    /// This is backfilled from the trials when loading records that don't have it (see `StudyRecord::backfill`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_accounting: Option<BudgetAccounting>,
 }
 impl StudyRecord {
+    /// Group name of studies that don't have the label used for grouping.
+    pub const UNLABELED: &'static str = "(unlabeled)";
+
+    /// Returns the value of the given label, or `StudyRecord::UNLABELED` if this study doesn't have the label.
+    pub fn label(&self, key: &str) -> &str {
+        self.labels.get(key).map_or(Self::UNLABELED, |v| v.as_str())
+    }
+
     pub fn id(&self) -> Result<String> {
         let mut hasher = Sha256::new();
         hasher.update(&track!(
             serde_json::to_vec(&self.budget).map_err(Error::from)
         )?);
+        if !self.budget_unit.is_steps() {
+            hasher.update(&track!(
+                serde_json::to_vec(&self.budget_unit).map_err(Error::from)
+            )?);
+        }
+        if let Some(rate) = self.ask_cost_per_second {
+            hasher.update(&track!(serde_json::to_vec(&rate).map_err(Error::from))?);
+        }
+        if self.tell_delay > 0 {
+            hasher.update(&track!(
+                serde_json::to_vec(&self.tell_delay).map_err(Error::from)
+            )?);
+        }
         hasher.update(&track!(
             serde_json::to_vec(&self.concurrency).map_err(Error::from)
         )?);
@@ -134,6 +390,11 @@ impl StudyRecord {
         hasher.update(&track!(
             serde_json::to_vec(&self.problem).map_err(Error::from)
         )?);
+        if !self.filters.is_empty() {
+            hasher.update(&track!(
+                serde_json::to_vec(&self.filters).map_err(Error::from)
+            )?);
+        }
 
         let mut id = String::with_capacity(64);
         for b in hasher.finalize().as_slice() {
@@ -142,24 +403,239 @@ impl StudyRecord {
         Ok(id)
     }
 
+    /// Computes the fingerprint of the trial sequence of this study.
+    ///
+    /// The fingerprint is `"v1:"` followed by the lowercase hex SHA-256 digest of the concatenated
+    /// canonical encodings of the trials (see `TrialRecord::fingerprint_bytes`) in the order of their ids.
+    /// Timings, thread ids and attributes are not included, so two runs of the same solver, problem and seed
+    /// have the same fingerprint if and only if the solver proposed the same parameters in the same order
+    /// and the problem returned the same values at the same steps.
+    ///
+    /// Floats are parsed with serde_json's `float_roundtrip` feature, so the fingerprint of a record
+    /// loaded from a JSON file is the same as that computed when the study was run.
+    ///
+    /// Note that fingerprints are compared across kurobako versions (e.g., in CI),
+    /// so the encoding must not be changed without bumping the `v1` prefix.
+    pub fn compute_fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for trial in &self.trials {
+            hasher.update(trial.fingerprint_bytes());
+        }
+        format!("v1:{:x}", hasher.finalize())
+    }
+
+    /// Returns the length of the budget axis (in steps).
     pub fn study_steps(&self) -> u64 {
         self.problem.spec.steps.last() * self.budget
     }
 
-    pub fn best_values(&self) -> BTreeMap<u64, f64> {
-        let mut best_values = BTreeMap::new();
+    /// Returns the trials paired with the steps at which they ended on the budget axis.
+    ///
+    /// If the budget unit is `BudgetUnit::Steps`, the steps are the same as `TrialRecord::end_step()`.
+    /// If it's `BudgetUnit::Trials`, the `i`-th started trial is regarded as ending at `i * max_step`
+    /// regardless of how many steps it was evaluated, so the length of the axis is always `study_steps()`.
+    ///
+    /// Trials without evaluations are excluded.
+    pub fn trial_end_steps(&self) -> Vec<(&TrialRecord, u64)> {
+        self.trials
+            .iter()
+            .zip(self.trial_positions())
+            .filter_map(|(t, step)| step.map(|step| (t, step)))
+            .collect()
+    }
+
+    fn trial_positions(&self) -> Vec<Option<u64>> {
+        match self.budget_unit {
+            BudgetUnit::Steps => self.trials.iter().map(|t| t.end_step()).collect(),
+            BudgetUnit::Trials => {
+                let problem_steps = self.problem.spec.steps.last();
+                let mut order = self
+                    .trials
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, t)| t.start_step().map(|step| (i, step)))
+                    .collect::<Vec<_>>();
+                order.sort_by_key(|x| x.1);
+
+                let mut positions = vec![None; self.trials.len()];
+                for (n, (i, _)) in order.into_iter().enumerate() {
+                    positions[i] = Some((n as u64 + 1) * problem_steps);
+                }
+                positions
+            }
+        }
+    }
+
+    /// Makes this study single-objective by keeping only the `objective`-th (0-origin) objective values.
+    ///
+    /// The name of the objective is appended to the problem name if the study had multiple objectives.
+    /// Returns `false` (and leaves this study as is) if the problem doesn't have the objective.
+    pub fn select_objective(&mut self, objective: usize) -> Result<bool> {
+        let spec = &mut self.problem.spec;
+        let vars = spec.values_domain.variables();
+        let var = if let Some(var) = vars.get(objective) {
+            var.clone()
+        } else {
+            return Ok(false);
+        };
+        if vars.len() > 1 {
+            spec.name = format!("{} (Objective {}: {})", spec.name, objective, var.name());
+        }
+        spec.values_domain = track!(Domain::new(vec![VariableBuilder::from(var)]))?;
+        spec.reference_point = None;
+
+        let select = |values: &mut Values| {
+            if let Some(&v) = values.get(objective) {
+                *values = Values::new(vec![v]);
+            }
+        };
+        for trial in &mut self.trials {
+            for eval in &mut trial.evaluations {
+                select(&mut eval.values);
+                for intermediate in &mut eval.intermediates {
+                    select(&mut intermediate.values);
+                }
+            }
+        }
+        Ok(true)
+    }
 
+    /// Discards the evaluations that don't fit in the given budget.
+    ///
+    /// `budget` is measured in the same unit as `StudyRecord::budget`, i.e., in trials
+    /// (if the budget unit is `BudgetUnit::Steps`, a trial means `max_step` steps of the problem).
+    /// Trials that lose all their evaluations are removed, and the budget of this study becomes `budget`.
+    /// Studies whose budget is not larger than `budget` are left as is.
+    ///
+    /// Returns `true` if this study still has a trial evaluated up to the last step of the problem,
+    /// otherwise `false` (i.e., the study has no best value within the budget and should be discarded).
+    pub fn truncate_budget(&mut self, budget: u64) -> bool {
         let problem_steps = self.problem.spec.steps.last();
-        let mut trials = self
-            .trials
+        if budget < self.budget {
+            let max_step = budget * problem_steps;
+            match self.budget_unit {
+                BudgetUnit::Steps => {
+                    for trial in &mut self.trials {
+                        trial.evaluations.retain(|e| e.end_step <= max_step);
+                    }
+                }
+                BudgetUnit::Trials => {
+                    let positions = self.trial_positions();
+                    for (trial, position) in self.trials.iter_mut().zip(positions) {
+                        if position.is_none_or(|p| p > max_step) {
+                            trial.evaluations.clear();
+                        }
+                    }
+                }
+            }
+            self.trials.retain(|t| !t.evaluations.is_empty());
+            self.budget = budget;
+
+            // The recorded breakdown covers the whole budget.
+            self.budget_accounting = None;
+        }
+        self.trials
             .iter()
-            .filter_map(|t| {
-                if let (Some(step), Some(value)) = (t.end_step(), t.value(problem_steps)) {
-                    Some((step, value))
+            .any(|t| t.values(problem_steps).is_some())
+    }
+
+    /// Discards the trials that don't affect the best values, AUCs and hypervolumes.
+    ///
+    /// Kept are the first complete trial and the trials whose values were not dominated
+    /// by any preceding trial when they completed.
+    /// The other trials that ended between two kept trials are merged into a placeholder trial
+    /// (see `TrialRecord::merge`) placed at the position of the first of them,
+    /// so the consumed budget, the elapsed times, the number of the trials (and failures)
+    /// and the budget breakdown of this study stay the same.
+    ///
+    /// What is lost are the parameters and the values of the merged trials,
+    /// and the elapsed times at their end steps (i.e., `StudyRecord::elapsed_times` has fewer points).
+    ///
+    /// Studies whose budget is measured in trials are left as is,
+    /// because their budget axis depends on the number of the trials.
+    pub fn compact(&mut self) {
+        if self.budget_unit == BudgetUnit::Trials {
+            return;
+        }
+        let problem_steps = self.problem.spec.steps.last();
+        let first_complete = self
+            .first_complete_trial()
+            .and_then(|first| self.trials.iter().position(|t| std::ptr::eq(t, first)));
+
+        let mut order = (0..self.trials.len())
+            .filter(|&i| !self.trials[i].evaluations.is_empty())
+            .collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.trials[i].end_step());
+
+        let mut keep = vec![false; self.trials.len()];
+        let mut groups = Vec::new();
+        let mut group = Vec::new();
+        let mut front: Vec<Vec<f64>> = Vec::new();
+        for i in order {
+            let improved = match self.trials[i].values(problem_steps) {
+                Some(vs) if !vs.is_empty() => {
+                    let dominated = front
+                        .iter()
+                        .any(|f| f.iter().zip(vs.iter()).all(|(a, b)| a <= b));
+                    if !dominated {
+                        front.retain(|f| !f.iter().zip(vs.iter()).all(|(a, b)| b <= a));
+                        front.push(vs.to_vec());
+                    }
+                    !dominated
+                }
+                _ => false,
+            };
+            if improved || first_complete == Some(i) {
+                keep[i] = true;
+                if !group.is_empty() {
+                    groups.push(std::mem::take(&mut group));
+                }
+            } else {
+                group.push(i);
+            }
+        }
+        if !group.is_empty() {
+            groups.push(group);
+        }
+
+        let mut trials = std::mem::take(&mut self.trials)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let mut placeholders = BTreeMap::new();
+        for group in groups {
+            let position = group
+                .iter()
+                .copied()
+                .min()
+                .unwrap_or_else(|| unreachable!());
+            let merged = group
+                .into_iter()
+                .filter_map(|i| trials[i].take())
+                .collect::<Vec<_>>();
+            placeholders.insert(position, TrialRecord::merge(merged, problem_steps));
+        }
+        self.trials = trials
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, trial)| {
+                if keep[i] {
+                    trial
                 } else {
-                    None
+                    placeholders.remove(&i)
                 }
             })
+            .collect();
+    }
+
+    pub fn best_values(&self) -> BTreeMap<u64, f64> {
+        let mut best_values = BTreeMap::new();
+
+        let problem_steps = self.problem.spec.steps.last();
+        let mut trials = self
+            .trial_end_steps()
+            .into_iter()
+            .filter_map(|(t, step)| t.value(problem_steps).map(|value| (step, value)))
             .collect::<Vec<_>>();
         trials.sort_by_key(|t| t.0);
 
@@ -179,15 +655,9 @@ impl StudyRecord {
 
         let problem_steps = self.problem.spec.steps.last();
         let mut trials = self
-            .trials
-            .iter()
-            .filter_map(|t| {
-                if let (Some(step), Some(value)) = (t.end_step(), t.values(problem_steps)) {
-                    Some((step, value))
-                } else {
-                    None
-                }
-            })
+            .trial_end_steps()
+            .into_iter()
+            .filter_map(|(t, step)| t.values(problem_steps).map(|values| (step, values)))
             .collect::<Vec<_>>();
 
         trials.sort_by_key(|t| t.0);
@@ -208,14 +678,39 @@ impl StudyRecord {
     }
 
     pub fn elapsed_times(&self, include_evaluate_time: bool) -> BTreeMap<u64, f64> {
-        let mut times = BTreeMap::new();
-        let mut elapsed = 0.0;
-        for e in self.evaluations() {
-            elapsed += e.ask_elapsed.get() + e.tell_elapsed.get();
+        let elapsed = |e: &EvaluationRecord| {
+            let mut elapsed = e.ask_elapsed.get() + e.tell_elapsed.get();
             if include_evaluate_time {
                 elapsed += e.evaluate_elapsed.get();
             }
-            times.insert(e.end_step, elapsed);
+            elapsed
+        };
+
+        let mut times = BTreeMap::new();
+        let mut total = 0.0;
+        match self.budget_unit {
+            BudgetUnit::Steps => {
+                for e in self.evaluations() {
+                    total += elapsed(e);
+                    times.insert(e.end_step, total);
+                }
+            }
+            BudgetUnit::Trials => {
+                // The budget axis doesn't go back even if an earlier trial is resumed.
+                let mut evaluations = self
+                    .trial_end_steps()
+                    .into_iter()
+                    .flat_map(|(t, step)| t.evaluations.iter().map(move |e| (e, step)))
+                    .collect::<Vec<_>>();
+                evaluations.sort_by_key(|(e, _)| e.end_step);
+
+                let mut current = 0;
+                for (e, step) in evaluations {
+                    total += elapsed(e);
+                    current = current.max(step);
+                    times.insert(current, total);
+                }
+            }
         }
         times
     }
@@ -277,6 +772,37 @@ impl StudyRecord {
         Iter { queue }
     }
 
+    /// Returns the budget consumed until the best value reached `target` (i.e., became `<= target`) for the first time.
+    ///
+    /// The budget is measured in the same unit as `StudyRecord::budget`.
+    /// If the study never reached the target, this returns `None`.
+    pub fn budget_to_reach(&self, target: f64) -> Option<f64> {
+        let problem_steps = self.problem.spec.steps.last();
+        self.best_values()
+            .into_iter()
+            .find(|&(_, value)| value <= target)
+            .map(|(step, _)| step as f64 / problem_steps as f64)
+    }
+
+    /// Returns the budget consumed by this study (in the same unit as `StudyRecord::budget`).
+    pub fn consumed_budget(&self) -> f64 {
+        let problem_steps = self.problem.spec.steps.last();
+        let steps = self
+            .trial_end_steps()
+            .into_iter()
+            .map(|(_, step)| step)
+            .max()
+            .unwrap_or(0);
+        steps as f64 / problem_steps as f64
+    }
+
+    /// Returns the wall-clock time taken by this study.
+    pub fn duration(&self) -> Duration {
+        (self.end_time - self.start_time)
+            .to_std()
+            .unwrap_or_default()
+    }
+
     pub fn best_value(&self) -> Option<f64> {
         let problem_steps = self.problem.spec.steps.last();
         self.trials
@@ -300,15 +826,9 @@ impl StudyRecord {
 
         let problem_steps = self.problem.spec.steps.last();
         let mut trials = self
-            .trials
-            .iter()
-            .filter_map(|t| {
-                if let (Some(step), Some(value)) = (t.end_step(), t.value(problem_steps)) {
-                    Some((step, value))
-                } else {
-                    None
-                }
-            })
+            .trial_end_steps()
+            .into_iter()
+            .filter_map(|(t, step)| t.value(problem_steps).map(|value| (step, value)))
             .collect::<Vec<_>>();
         trials.sort_by_key(|t| t.0);
 
@@ -341,11 +861,375 @@ impl StudyRecord {
         self.trials.iter().map(|t| t.solver_elapsed()).sum()
     }
 
+    /// Returns the breakdown of the consumed budget (computed from the trials if it isn't recorded).
+    pub fn budget_accounting(&self) -> BudgetAccounting {
+        self.budget_accounting
+            .unwrap_or_else(|| self.compute_budget_accounting())
+    }
+
+    /// Computes the breakdown of the consumed budget from the trials.
+    ///
+    /// As asks that weren't evaluated are not recorded, the number of the asks is approximated
+    /// by the number of the evaluations.
+    pub fn compute_budget_accounting(&self) -> BudgetAccounting {
+        let problem_steps = self.problem.spec.steps.last();
+        let mut accounting = BudgetAccounting::default();
+        for trial in &self.trials {
+            if let Some(merged) = &trial.merged {
+                accounting.asks += merged.evaluations;
+                accounting.evaluated_steps += merged.completed_steps + merged.pruned_steps;
+                accounting.completed_steps += merged.completed_steps;
+                accounting.pruned_steps += merged.pruned_steps;
+                continue;
+            }
+            let steps = trial
+                .evaluations
+                .iter()
+                .map(|e| e.elapsed_steps())
+                .sum::<u64>();
+            accounting.asks += trial.evaluations.len() as u64;
+            accounting.evaluated_steps += steps;
+            if steps >= problem_steps {
+                accounting.completed_steps += steps;
+            } else {
+                accounting.pruned_steps += steps;
+            }
+        }
+        accounting
+    }
+
+    /// Fills the fields that are missing in the records written by older versions of kurobako.
+    pub fn backfill(&mut self) {
+        if self.budget_accounting.is_none() {
+            self.budget_accounting = Some(self.compute_budget_accounting());
+        }
+    }
+
+    /// Returns the number of the trials (including the ones merged into placeholders by `StudyRecord::compact`).
+    pub fn trial_count(&self) -> usize {
+        self.trials
+            .iter()
+            .map(|t| t.merged.as_ref().map_or(1, |m| m.trials as usize))
+            .sum()
+    }
+
+    pub fn failed_trials(&self) -> usize {
+        self.trials
+            .iter()
+            .map(|t| match &t.merged {
+                Some(m) => m.failed_trials as usize,
+                None => t.is_failed() as usize,
+            })
+            .sum()
+    }
+
+    /// Returns the number of the evaluations served from the cache of the `cache` problem.
+    pub fn cache_hits(&self) -> u64 {
+        self.trials
+            .iter()
+            .filter_map(|t| t.attrs.get(CACHE_HITS_ATTR).and_then(|v| v.as_u64()))
+            .sum()
+    }
+
+    /// Returns the step at which the first complete trial started on the budget axis.
+    pub fn first_complete_step(&self) -> Option<u64> {
+        let first = self.first_complete_trial()?;
+        match self.budget_unit {
+            BudgetUnit::Steps => first.start_step(),
+            BudgetUnit::Trials => {
+                let problem_steps = self.problem.spec.steps.last();
+                self.trial_end_steps()
+                    .into_iter()
+                    .find(|(t, _)| std::ptr::eq(*t, first))
+                    .map(|(_, step)| step - problem_steps)
+            }
+        }
+    }
+
     pub fn first_complete_trial(&self) -> Option<&TrialRecord> {
         let problem_steps = self.problem.spec.steps.last();
         self.trials
             .iter()
-            .filter(|t| t.steps() != problem_steps)
+            .filter(|t| t.steps() != problem_steps && !t.is_placeholder())
             .min_by_key(|t| t.start_step())
     }
 }
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    /// Makes the JSON of a trial that has a single evaluation.
+    fn trial(start_step: u64, end_step: u64, value: f64) -> serde_json::Value {
+        serde_json::json!({
+            "thread_id": 0,
+            "params": [value],
+            "evaluations": [{
+                "values": [value],
+                "start_step": start_step,
+                "end_step": end_step,
+                "ask_elapsed": 0.1,
+                "tell_elapsed": 0.2,
+                "evaluate_elapsed": 0.3
+            }]
+        })
+    }
+
+    /// Makes a study of a single-objective problem that has the given number of steps.
+    fn study(
+        budget: u64,
+        budget_unit: BudgetUnit,
+        steps: u64,
+        trials: Vec<serde_json::Value>,
+    ) -> Result<StudyRecord> {
+        let json = serde_json::json!({
+            "start_time": "2020-01-01T00:00:00+00:00",
+            "end_time": "2020-01-01T00:00:01+00:00",
+            "seed": 0,
+            "budget": budget,
+            "budget_unit": budget_unit,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": steps
+                }},
+                "spec": {
+                    "name": "Foo",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 10.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": steps
+                }
+            },
+            "trials": trials
+        });
+        track!(serde_json::from_value(json).map_err(Error::from))
+    }
+
+    /// Makes a study whose trials are listed out of the order of their end steps.
+    ///
+    /// The problem has two steps, and the trials ending at the steps 1 and 6 were pruned.
+    fn curve_study() -> Result<StudyRecord> {
+        let trials = vec![
+            trial(6, 8, 1.0),
+            trial(0, 1, 0.1),
+            trial(1, 3, 3.0),
+            trial(3, 5, 4.0),
+            trial(5, 6, 0.0),
+            trial(8, 10, 1.0),
+        ];
+        track!(study(5, BudgetUnit::Steps, 2, trials))
+    }
+
+    #[test]
+    fn best_values_are_keyed_by_end_steps() -> TopLevelResult {
+        let s = track!(curve_study())?;
+        let best_values = s.best_values();
+        assert_eq!(
+            best_values
+                .iter()
+                .map(|(&k, &v)| (k, v))
+                .collect::<Vec<_>>(),
+            [(3, 3.0), (8, 1.0)]
+        );
+
+        // The curve has no value until the first complete trial ends.
+        let curve = (1..=s.study_steps())
+            .map(|step| best_values.range(..=step).last().map(|(_, &v)| v))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            curve,
+            [
+                None,
+                None,
+                Some(3.0),
+                Some(3.0),
+                Some(3.0),
+                Some(3.0),
+                Some(3.0),
+                Some(1.0),
+                Some(1.0),
+                Some(1.0)
+            ]
+        );
+
+        assert_eq!(s.best_value(), Some(1.0));
+        assert_eq!(s.budget_to_reach(3.0), Some(1.5));
+        assert_eq!(s.budget_to_reach(1.0), Some(4.0));
+        assert_eq!(s.budget_to_reach(0.5), None);
+        assert_eq!(s.auc(3), Some(8.5));
+        Ok(())
+    }
+
+    #[test]
+    fn best_values_without_complete_trials() -> TopLevelResult {
+        let trials = vec![trial(0, 1, 1.0), trial(1, 2, 0.5)];
+        let s = track!(study(5, BudgetUnit::Steps, 2, trials))?;
+        assert!(s.best_values().is_empty());
+        assert_eq!(s.best_value(), None);
+        assert_eq!(s.budget_to_reach(f64::INFINITY), None);
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_budget_works() -> TopLevelResult {
+        // Five trials evaluated up to the last step (2) one after another.
+        let trials = (0..5)
+            .map(|i| trial(i * 2, i * 2 + 2, 5.0 - i as f64))
+            .collect();
+        let mut s = track!(study(5, BudgetUnit::Steps, 2, trials))?;
+        s.backfill();
+
+        assert!(s.truncate_budget(3));
+        assert_eq!(s.budget, 3);
+        assert_eq!(s.study_steps(), 6);
+        assert_eq!(s.trials.len(), 3);
+        assert_eq!(s.best_value(), Some(3.0));
+        assert_eq!(s.consumed_budget(), 3.0);
+        assert!(s.budget_accounting.is_none());
+        assert_eq!(s.budget_accounting().evaluated_steps, 6);
+
+        // The last trial is discarded unless it fits in the budget completely.
+        let trials = vec![trial(0, 2, 1.0), trial(2, 3, 0.5), trial(3, 5, 0.0)];
+        let mut s = track!(study(5, BudgetUnit::Steps, 2, trials))?;
+        assert!(s.truncate_budget(2));
+        assert_eq!(s.trials.len(), 2);
+        assert_eq!(s.best_value(), Some(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_budget_counts_trials() -> TopLevelResult {
+        // If the budget unit is trials, pruned trials consume a whole trial of the budget.
+        let trials = vec![
+            trial(0, 1, 3.0),
+            trial(1, 2, 2.0),
+            trial(2, 4, 1.0),
+            trial(4, 6, 0.0),
+        ];
+        let mut s = track!(study(4, BudgetUnit::Trials, 2, trials))?;
+        assert!(s.truncate_budget(3));
+        assert_eq!(s.trials.len(), 3);
+        assert_eq!(s.best_value(), Some(1.0));
+
+        assert!(!s.truncate_budget(2));
+        assert_eq!(s.trials.len(), 2);
+        assert_eq!(s.best_value(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_budget_without_complete_trials() -> TopLevelResult {
+        // Only the pruned trial fits in the budget, so the study has no best value.
+        let trials = vec![trial(0, 1, 2.0), trial(1, 3, 1.0)];
+        let mut s = track!(study(5, BudgetUnit::Steps, 2, trials))?;
+        assert!(!s.truncate_budget(1));
+        assert_eq!(s.trials.len(), 1);
+        assert_eq!(s.best_value(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_budget_keeps_shorter_studies() -> TopLevelResult {
+        let trials = vec![trial(0, 2, 2.0), trial(2, 4, 1.0)];
+        let mut s = track!(study(2, BudgetUnit::Steps, 2, trials))?;
+        assert!(s.truncate_budget(10));
+        assert_eq!(s.budget, 2);
+        assert_eq!(s.trials.len(), 2);
+        assert_eq!(s.best_value(), Some(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn compact_keeps_totals() -> TopLevelResult {
+        let mut failed = trial(3, 5, 9.0);
+        failed["evaluations"][0]["status"] = "FAILED".into();
+        let mut cached = trial(8, 10, 3.0);
+        cached["attrs"] = serde_json::json!({ CACHE_HITS_ATTR: 2 });
+        let trials = vec![
+            trial(6, 8, 1.0),
+            trial(0, 2, 2.0),
+            failed,
+            trial(2, 3, 0.5),
+            trial(5, 6, 0.0),
+            cached,
+            trial(10, 12, 0.5),
+        ];
+        let full = track!(study(6, BudgetUnit::Steps, 2, trials))?;
+        let mut compacted = full.clone();
+        compacted.compact();
+
+        // The improving trials (and the first pruned one, see `first_complete_trial`) are kept
+        // in the original order, and the others are merged.
+        let kept = compacted
+            .trials
+            .iter()
+            .map(|t| t.params.first().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            [Some(1.0), Some(2.0), None, Some(0.5), None, Some(0.5)]
+        );
+        let merged = compacted
+            .trials
+            .iter()
+            .filter_map(|t| t.merged.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(merged[0].trials, 2);
+        assert_eq!(merged[0].failed_trials, 1);
+        assert_eq!(merged[1].trials, 1);
+
+        assert_eq!(compacted.trial_count(), full.trial_count());
+        assert_eq!(compacted.failed_trials(), 1);
+        assert_eq!(compacted.cache_hits(), 2);
+        assert_eq!(
+            compacted.compute_budget_accounting(),
+            full.compute_budget_accounting()
+        );
+        let curve = |values: BTreeMap<u64, f64>| {
+            (1..=full.study_steps())
+                .map(|step| values.range(..=step).last().map(|(_, &v)| v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(compacted.best_values(), full.best_values());
+        assert_eq!(curve(compacted.hypervolumes()), curve(full.hypervolumes()));
+        assert_eq!(compacted.auc(2), full.auc(2));
+        assert_eq!(compacted.consumed_budget(), full.consumed_budget());
+        assert_eq!(compacted.solver_elapsed(), full.solver_elapsed());
+        assert_eq!(
+            compacted.elapsed_times(true).values().last(),
+            full.elapsed_times(true).values().last()
+        );
+
+        // Compacting twice doesn't change anything.
+        let twice = {
+            let mut s = compacted.clone();
+            s.compact();
+            s
+        };
+        assert_eq!(twice.trials.len(), compacted.trials.len());
+        assert_eq!(
+            twice.compute_budget_accounting(),
+            full.compute_budget_accounting()
+        );
+        Ok(())
+    }
+}
@@ -1,15 +1,24 @@
+use crate::problem::CACHE_HITS_ATTR;
 use crate::time::ElapsedSeconds;
-use kurobako_core::trial::{Params, TrialId, Values};
+use kurobako_core::trial::{Params, TrialId, TrialStatus, Values};
 use serde::{Deserialize, Serialize};
 use std::cmp;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
+const PLACEHOLDER_THREAD_ID: usize = usize::MAX;
+
 #[derive(Debug)]
 pub struct TrialRecordBuilder {
     pub id: TrialId,
     pub thread_id: usize,
     pub params: Params,
+    pub evaluated_params: Option<Params>,
+    pub fidelity: Params,
     pub values: Values,
+    pub status: TrialStatus,
+    pub attrs: BTreeMap<String, serde_json::Value>,
+    pub intermediates: Vec<IntermediateRecord>,
     pub start_step: u64,
     pub end_step: u64,
     pub ask_elapsed: ElapsedSeconds,
@@ -18,12 +27,125 @@ pub struct TrialRecordBuilder {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrialRecord {
     pub thread_id: usize,
     pub params: Params,
+
+    /// Parameters actually evaluated by the problem if they differ from the asked ones
+    /// (e.g., perturbed by the `param_noise` filter).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluated_params: Option<Params>,
+
     pub evaluations: Vec<EvaluationRecord>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: BTreeMap<String, serde_json::Value>,
+
+    /// Summary of the trials merged into this placeholder trial (see `StudyRecord::compact`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged: Option<MergedTrials>,
 }
 impl TrialRecord {
+    /// Merges the given trials into a placeholder trial.
+    ///
+    /// The placeholder holds a single evaluation that ends at the last end step of the trials
+    /// and has the total elapsed times of them.
+    /// The evaluation doesn't span any steps, so the placeholder never looks like a complete trial;
+    /// the numbers of the merged trials, failures and steps are kept in `TrialRecord::merged` instead.
+    /// The `cache_hits` attributes of the trials are summed up.
+    pub fn merge(trials: Vec<TrialRecord>, problem_steps: u64) -> Self {
+        let mut merged = MergedTrials::default();
+        let mut cache_hits = 0;
+        let mut end_step = 0;
+        let mut ask_elapsed = 0.0;
+        let mut tell_elapsed = 0.0;
+        let mut evaluate_elapsed = 0.0;
+        for trial in trials {
+            if let Some(m) = &trial.merged {
+                merged.add(m);
+            } else {
+                let steps = trial
+                    .evaluations
+                    .iter()
+                    .map(|e| e.elapsed_steps())
+                    .sum::<u64>();
+                merged.trials += 1;
+                merged.evaluations += trial.evaluations.len() as u64;
+                if trial.is_failed() {
+                    merged.failed_trials += 1;
+                }
+                if steps >= problem_steps {
+                    merged.completed_steps += steps;
+                } else {
+                    merged.pruned_steps += steps;
+                }
+            }
+            cache_hits += trial
+                .attrs
+                .get(CACHE_HITS_ATTR)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+            for e in &trial.evaluations {
+                end_step = end_step.max(e.end_step);
+                ask_elapsed += e.ask_elapsed.get();
+                tell_elapsed += e.tell_elapsed.get();
+                evaluate_elapsed += e.evaluate_elapsed.get();
+            }
+        }
+
+        let mut attrs = BTreeMap::new();
+        if cache_hits > 0 {
+            attrs.insert(CACHE_HITS_ATTR.to_owned(), cache_hits.into());
+        }
+        Self {
+            thread_id: PLACEHOLDER_THREAD_ID,
+            params: Params::new(Vec::new()),
+            evaluated_params: None,
+            evaluations: vec![EvaluationRecord {
+                values: Values::new(Vec::new()),
+                fidelity: Params::new(Vec::new()),
+                status: TrialStatus::Ok,
+                intermediates: Vec::new(),
+                start_step: end_step,
+                end_step,
+                ask_elapsed: ElapsedSeconds::new(ask_elapsed),
+                tell_elapsed: ElapsedSeconds::new(tell_elapsed),
+                evaluate_elapsed: ElapsedSeconds::new(evaluate_elapsed),
+            }],
+            attrs,
+            merged: Some(merged),
+        }
+    }
+
+    /// Returns the canonical encoding of this trial used by `StudyRecord::compute_fingerprint`.
+    ///
+    /// The encoding is a sequence of little-endian `u64` words:
+    /// the number of the parameters, the parameters, the number of the evaluations, and
+    /// then `start_step`, `end_step`, the number of the values and the values of each evaluation.
+    /// Floats are encoded as their bit patterns (all NaNs are mapped to the same pattern).
+    pub fn fingerprint_bytes(&self) -> Vec<u8> {
+        fn float(x: f64) -> u64 {
+            if x.is_nan() {
+                f64::NAN.to_bits()
+            } else {
+                x.to_bits()
+            }
+        }
+
+        let mut words = vec![self.params.len() as u64];
+        words.extend(self.params.iter().map(|&x| float(x)));
+        words.push(self.evaluations.len() as u64);
+        for eval in &self.evaluations {
+            words.extend(&[eval.start_step, eval.end_step, eval.values.len() as u64]);
+            words.extend(eval.values.iter().map(|&x| float(x)));
+        }
+        words.into_iter().flat_map(u64::to_le_bytes).collect()
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.thread_id == PLACEHOLDER_THREAD_ID
+    }
+
     pub fn value(&self, step: u64) -> Option<f64> {
         let mut current_step = 0;
         for eval in &self.evaluations {
@@ -85,11 +207,55 @@ impl TrialRecord {
     pub fn end_step(&self) -> Option<u64> {
         self.evaluations.last().map(|e| e.end_step)
     }
+
+    pub fn is_failed(&self) -> bool {
+        if let Some(e) = self.evaluations.last() {
+            e.status.is_failed()
+        } else {
+            false
+        }
+    }
+}
+
+/// Summary of the trials merged into a placeholder trial by `StudyRecord::compact`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct MergedTrials {
+    /// Number of the merged trials.
+    pub trials: u64,
+
+    /// Number of the merged trials whose last evaluations failed.
+    pub failed_trials: u64,
+
+    /// Number of the evaluations of the merged trials.
+    pub evaluations: u64,
+
+    /// Number of the steps of the merged trials that reached the last step of the problem.
+    pub completed_steps: u64,
+
+    /// Number of the steps of the other merged trials.
+    pub pruned_steps: u64,
+}
+impl MergedTrials {
+    fn add(&mut self, other: &Self) {
+        self.trials += other.trials;
+        self.failed_trials += other.failed_trials;
+        self.evaluations += other.evaluations;
+        self.completed_steps += other.completed_steps;
+        self.pruned_steps += other.pruned_steps;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct EvaluationRecord {
     pub values: Values,
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    pub fidelity: Params,
+    #[serde(default, skip_serializing_if = "TrialStatus::is_ok")]
+    pub status: TrialStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub intermediates: Vec<IntermediateRecord>,
     pub start_step: u64,
     pub end_step: u64,
     pub ask_elapsed: ElapsedSeconds,
@@ -101,3 +267,10 @@ impl EvaluationRecord {
         self.end_step - self.start_step
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct IntermediateRecord {
+    pub current_step: u64,
+    pub values: Values,
+}
@@ -1,20 +1,27 @@
 //! `kurobako report` command.
+use self::coverage::Coverage;
 use self::rankings::{Borda, Firsts};
+use crate::load::LoadOpt;
 use crate::markdown as md;
 use crate::markdown::MarkdownWriter;
-use crate::record::{ProblemRecord, SolverRecord, StudyRecord};
+use crate::record::{ProblemRecord, SolverRecord, StudyRecord, Termination};
+use crate::stats::{Normalization, Normalizations, TargetStats};
+use crate::target::TargetOpt;
 use kurobako_core::num::OrderedFloat;
+use kurobako_core::stats::{BasicStats, HeadToHead};
 use kurobako_core::{Error, ErrorKind, Result};
-use rustats::fundamental::{average, stddev};
 use rustats::hypothesis_testings::MannWhitneyU;
 use serde::Serialize;
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
-use std::io::Write;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 use structopt::StructOpt;
 
+mod coverage;
 mod rankings;
 
 /// Options of the `kurobako report` command.
@@ -27,6 +34,131 @@ pub struct ReportOpt {
         possible_values = Metric::POSSIBLE_VALUES
     )]
     pub metrics: Vec<Metric>,
+
+    /// Statistic shown in the main columns of the individual results.
+    ///
+    /// `--aggregate` is an alias for consistency with `kurobako plot curve`.
+    #[structopt(
+        long,
+        alias = "aggregate",
+        default_value = "mean",
+        possible_values = Stat::POSSIBLE_VALUES
+    )]
+    pub stats: Stat,
+
+    /// Quantiles shown in the extra columns (default: `0.25 0.75`).
+    #[structopt(long)]
+    pub quantiles: Vec<f64>,
+
+    /// If specified, the individual results have extra columns showing
+    /// the minimum, the quantiles and the maximum of the best values.
+    #[structopt(long)]
+    pub quantile_columns: bool,
+
+    /// Solver attributes (reported at the end of each study) shown in the extra columns of the individual results.
+    ///
+    /// Numeric attributes are summarized by `--stats`, and the others are shown as the set of distinct values.
+    #[structopt(long = "solver-attr")]
+    pub solver_attrs: Vec<String>,
+
+    /// If specified, the head-to-head win rate matrix is written to the given path as CSV.
+    #[structopt(long)]
+    pub win_rates_csv: Option<PathBuf>,
+
+    /// If specified, the head-to-head win rate matrix (including the win/loss/tie counts)
+    /// is written to the given path as JSON.
+    #[structopt(long)]
+    pub win_rates_json: Option<PathBuf>,
+
+    /// If specified, the report has a section summarizing the parameters asked by each solver.
+    ///
+    /// Numerical variables are shown as histograms of 10 bins over their ranges
+    /// (log-scaled for log-uniform variables), and categorical variables are shown as per-choice counts.
+    #[structopt(long)]
+    pub param_coverage: bool,
+
+    /// If specified, the report has a section showing the time taken to set up the studies
+    /// (i.e., creating the problems and the solvers) and the latencies of the first asks.
+    ///
+    /// Studies recorded by older versions don't have the setup times and are ignored.
+    #[structopt(long)]
+    pub setup_cost: bool,
+
+    /// If specified, the report has a section listing the studies of each solver on each problem
+    /// (i.e., their seeds, best values, AUCs, numbers of trials, durations and termination reasons)
+    /// sorted by their best values.
+    ///
+    /// This is useful to find outlier repetitions.
+    #[structopt(long)]
+    pub per_study_table: bool,
+
+    /// Maximum number of the studies of a solver on a problem shown in the per-study table.
+    ///
+    /// If a solver has more studies, only the best and the worst ones are shown (half and half).
+    #[structopt(long, default_value = "20")]
+    pub per_study_rows: usize,
+
+    /// If specified, all the rows of the per-study table are written to the given path as CSV.
+    #[structopt(long)]
+    pub per_study_csv: Option<PathBuf>,
+
+    /// Study label used to split the results of each solver into groups (e.g., `hardware`).
+    ///
+    /// Studies without the label fall into the `(unlabeled)` group.
+    #[structopt(long)]
+    pub group_by: Option<String>,
+
+    /// Target values for the extra columns of the individual results showing the budget
+    /// consumed until the best value reached each target (`-` if no study reached it).
+    ///
+    /// If only some of the studies reached a target, the column shows the statistic of
+    /// the successful studies followed by the number of them.
+    ///
+    /// If targets are specified, the report also has a section showing the success rates and
+    /// the expected running times (ERT) of the solvers for the targets.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub target: TargetOpt,
+
+    /// If specified, the success rates and the expected running times for the targets
+    /// are written to the given path as CSV.
+    #[structopt(long)]
+    pub ert_csv: Option<PathBuf>,
+
+    /// If specified, the overall results and the individual results have columns showing
+    /// the best values normalized into `[0, 1]` per problem.
+    ///
+    /// By default, the normalization base of a problem is the minimum and the maximum of the best values
+    /// of all the studies on the problem (see also `--normalization-file`).
+    #[structopt(long)]
+    pub normalize: bool,
+
+    /// JSON file containing the normalization constants of problems exported by `--export-normalization`.
+    ///
+    /// The constants are reused so that the normalized values are comparable with those of the previous reports.
+    /// Problems absent from the file are normalized by the local results (with a warning).
+    /// This implies `--normalize`.
+    #[structopt(long)]
+    pub normalization_file: Option<PathBuf>,
+
+    /// If specified, the normalization constants of the problems are written to the given path as JSON.
+    ///
+    /// If `--normalization-file` is also specified, the constants in the file
+    /// (including those of the problems not in this report) are exported as they are.
+    #[structopt(long)]
+    pub export_normalization: Option<PathBuf>,
+
+    /// Excludes the results of a solver on a problem from the report if some of its studies consumed
+    /// materially less budget than the others (e.g., because they were cut off).
+    ///
+    /// Such results are always listed in the "Comparability Warnings" section.
+    #[structopt(long)]
+    pub strict_comparability: bool,
+
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    #[allow(missing_docs)]
+    pub load: LoadOpt,
 }
 
 /// Evaluation metric.
@@ -57,6 +189,55 @@ impl FromStr for Metric {
     }
 }
 
+/// Statistic used to aggregate the results of studies.
+///
+/// This is shown in the main columns of a report and used as the central lines of curve plots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Stat {
+    /// Mean and standard deviation.
+    Mean,
+
+    /// Median.
+    Median,
+}
+impl Stat {
+    pub(crate) const POSSIBLE_VALUES: &'static [&'static str] = &["mean", "median"];
+
+    /// Returns the name of this statistic.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stat::Mean => "mean",
+            Stat::Median => "median",
+        }
+    }
+
+    fn header(self, name: &str) -> String {
+        match self {
+            Stat::Mean => format!("{} (avg +- sd)", name),
+            Stat::Median => format!("{} (median)", name),
+        }
+    }
+
+    fn format(self, values: impl Iterator<Item = f64>, precision: usize) -> String {
+        match (self, BasicStats::new(values, &[])) {
+            (_, None) => "-".to_owned(),
+            (Stat::Mean, Some(s)) => format!("{:.*} +- {:.*}", precision, s.mean, precision, s.sd),
+            (Stat::Median, Some(s)) => format!("{:.*}", precision, s.median),
+        }
+    }
+}
+impl FromStr for Stat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mean" => Ok(Stat::Mean),
+            "median" => Ok(Stat::Median),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown statistic name: {:?}", s),
+        }
+    }
+}
+
 /// Reporter that builds and prints a benchmark report.
 #[derive(Debug)]
 pub struct Reporter {
@@ -69,11 +250,18 @@ impl Reporter {
         if opt.metrics.is_empty() {
             opt.metrics = vec![Metric::BestValue, Metric::Auc];
         }
+        if opt.quantiles.is_empty() {
+            opt.quantiles = BasicStats::DEFAULT_QUANTILES.to_vec();
+        }
         Self { studies, opt }
     }
 
     /// Prints a full report.
     pub fn report_all(&self, mut writer: impl Write) -> Result<()> {
+        if self.opt.param_coverage {
+            track!(self.opt.load.require_all_trials("`--param-coverage`"))?;
+        }
+
         let mut writer = MarkdownWriter::new(&mut writer);
         let mut writer = track!(writer.heading("Benchmark Result Report"))?;
 
@@ -83,6 +271,62 @@ impl Reporter {
             env!("CARGO_PKG_VERSION"),
             env!("CARGO_PKG_VERSION"),
         )))?;
+        if let Some(versions) = self.version_mismatch() {
+            track!(list.item(&format!(
+                "**Warning**: The results were produced by different versions of kurobako: {}",
+                versions
+            )))?;
+        }
+        let clamped_durations = self
+            .studies
+            .iter()
+            .map(|s| s.clamped_durations)
+            .sum::<u64>();
+        if clamped_durations > 0 {
+            track!(list.item(&format!(
+                "**Warning**: {} out-of-range durations were clamped while running the studies",
+                clamped_durations
+            )))?;
+        }
+        if let Some(objective) = self.opt.load.objective {
+            track!(list.item(&format!(
+                "Objective: only the objective {} (0-origin) of each study is evaluated",
+                objective
+            )))?;
+        }
+        let ask_cost_steps = self.studies.iter().map(|s| s.ask_cost_steps).sum::<u64>();
+        if ask_cost_steps > 0 {
+            track!(list.item(&format!(
+                "Ask Cost: {} steps were charged to the budgets for the time taken by the solvers to ask trials",
+                ask_cost_steps
+            )))?;
+        }
+        let out_of_range_values = self
+            .studies
+            .iter()
+            .map(|s| s.out_of_range_values)
+            .sum::<u64>();
+        if out_of_range_values > 0 {
+            track!(list.item(&format!(
+                "**Warning**: {} values returned by the problems were out of their values domains",
+                out_of_range_values
+            )))?;
+        }
+        let incomparables = track!(self.incomparables())?;
+        if !incomparables.is_empty() {
+            track!(list.item(&format!(
+                "**Warning**: {} results of solvers on problems mix studies that consumed \
+                 materially different budgets (see [Comparability Warnings](#comparability-warnings))",
+                incomparables.len()
+            )))?;
+        }
+        let cache_hits = self.studies.iter().map(|s| s.cache_hits()).sum::<u64>();
+        if cache_hits > 0 {
+            track!(list.item(&format!(
+                "**Warning**: {} evaluations were served from the problem cache (i.e., reused results of previous runs)",
+                cache_hits
+            )))?;
+        }
         track!(list.item(&format!(
             "Number of Solvers: {}",
             track!(self.solvers())?.count()
@@ -91,6 +335,29 @@ impl Reporter {
             "Number of Problems: {}",
             track!(self.problems())?.count()
         )))?;
+        let normalizations = if self.normalizes() {
+            let (normalizations, fallbacks) = track!(self.normalizations())?;
+            if !fallbacks.is_empty() {
+                let problems = fallbacks
+                    .iter()
+                    .map(|p| format!("`{}`", p))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                eprintln!(
+                    "Warning: the following problems are not in the normalization file \
+                     and are normalized by the local results: {}",
+                    problems
+                );
+                track!(list.item(&format!(
+                    "**Warning**: The following problems are not in the normalization file \
+                     and are normalized by the local results: {}",
+                    problems
+                )))?;
+            }
+            Some(normalizations)
+        } else {
+            None
+        };
         let metrics = self
             .opt
             .metrics
@@ -116,16 +383,48 @@ impl Reporter {
         {
             let mut writer = track!(writer.heading("Table of Contents"))?;
             let mut list = writer.list().numbered();
+            if !incomparables.is_empty() {
+                track!(list.item("[Comparability Warnings](#comparability-warnings)"))?;
+            }
             track!(list.item("[Overall Results](#overall-results)"))?;
+            track!(list.item("[Head-to-Head Win Rates](#head-to-head-win-rates)"))?;
             track!(list.item("[Individual Results](#individual-results)"))?;
+            if self.opt.per_study_table {
+                track!(list.item("[Per-Study Results](#per-study-results)"))?;
+            }
+            if !self.opt.target.is_empty() {
+                track!(list.item("[Expected Running Time](#expected-running-time)"))?;
+            }
+            if self.opt.param_coverage {
+                track!(list.item("[Parameter Coverage](#parameter-coverage)"))?;
+            }
+            if self.opt.setup_cost {
+                track!(list.item("[Setup Cost](#setup-cost)"))?;
+            }
             track!(list.item("[Solvers](#solvers)"))?;
             track!(list.item("[Problems](#problems)"))?;
             track!(list.item("[Studies](#studies)"))?;
             track_writeln!(writer.inner_mut())?;
         }
 
-        track!(self.report_overall_results(&mut writer))?;
-        track!(self.report_individual_results(&mut writer))?;
+        if !incomparables.is_empty() {
+            track!(self.report_incomparables(&mut writer, &incomparables))?;
+        }
+        track!(self.report_overall_results(&mut writer, normalizations.as_ref()))?;
+        track!(self.report_win_rates(&mut writer))?;
+        track!(self.report_individual_results(&mut writer, normalizations.as_ref()))?;
+        if self.opt.per_study_table {
+            track!(self.report_per_study_results(&mut writer))?;
+        }
+        if !self.opt.target.is_empty() {
+            track!(self.report_expected_running_times(&mut writer))?;
+        }
+        if self.opt.param_coverage {
+            track!(self.report_param_coverage(&mut writer))?;
+        }
+        if self.opt.setup_cost {
+            track!(self.report_setup_costs(&mut writer))?;
+        }
         track!(self.report_solvers(&mut writer))?;
         track!(self.report_problems(&mut writer))?;
         track!(self.report_studies(&mut writer))?;
@@ -133,57 +432,156 @@ impl Reporter {
         Ok(())
     }
 
-    fn report_overall_results<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+    /// Returns a description of the versions that produced the results if they are not identical.
+    fn version_mismatch(&self) -> Option<String> {
+        let mut versions = BTreeMap::<_, usize>::new();
+        for study in &self.studies {
+            let version = study.provenance.as_ref().map_or_else(
+                || "unknown".to_owned(),
+                |p| format!("{} (core {})", p.kurobako_version, p.core_version),
+            );
+            *versions.entry(version).or_default() += 1;
+        }
+        if versions.len() < 2 {
+            return None;
+        }
+        let versions = versions
+            .into_iter()
+            .map(|(version, n)| format!("`{}` ({} studies)", version, n))
+            .collect::<Vec<_>>();
+        Some(versions.join(", "))
+    }
+
+    fn report_incomparables<W: Write>(
+        &self,
+        writer: &mut MarkdownWriter<W>,
+        incomparables: &[Incomparable],
+    ) -> Result<()> {
+        let mut writer = track!(writer.heading("Comparability Warnings"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "Some studies of the following results consumed less than {:.0}% of \
+             the budget consumed by the other studies of the same solver on the same problem.",
+            COMPARABLE_BUDGET_RATIO * 100.0
+        )?;
+        if self.opt.strict_comparability {
+            track_writeln!(
+                writer.inner_mut(),
+                "These results are excluded from this report (`--strict-comparability`)."
+            )?;
+        }
+        track_writeln!(writer.inner_mut())?;
+
+        let mut table = md::Table::new(
+            vec![
+                md::ColumnHeader::new("Problem", md::Align::Left),
+                md::ColumnHeader::new("Solver", md::Align::Left),
+                md::ColumnHeader::new("Short Studies", md::Align::Right),
+                md::ColumnHeader::new("Consumed Budget (min - max)", md::Align::Right),
+            ]
+            .into_iter(),
+        );
+        for x in incomparables {
+            table
+                .row()
+                .item(format!("[{}](#id-{})", x.problem.spec.name, x.problem_id))
+                .item(format!(
+                    "[{}](#id-{})",
+                    self.competitor_name(x.solver, x.label),
+                    track!(x.solver.id())?
+                ))
+                .item(format!("{}/{}", x.short_studies, x.studies))
+                .item(format!("{:.2} - {:.2}", x.min_budget, x.max_budget));
+        }
+        track!(writer.write_table(&table))?;
+        track!(writer.newline())?;
+        Ok(())
+    }
+
+    fn report_overall_results<W: Write>(
+        &self,
+        writer: &mut MarkdownWriter<W>,
+        normalizations: Option<&Normalizations>,
+    ) -> Result<()> {
         let mut writer = track!(writer.heading("Overall Results"))?;
         track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "\"Firsts\" is the number of the problems on which no other solver is better \
+             (all the solvers tied for the first place are counted).\n"
+        )?;
 
         let contests = track!(self.contests())?;
-        let (solver_ids, solvers): (Vec<_>, Vec<_>) = track!(self.solvers())?.unzip();
+        let competitors = track!(self.competitors())?;
+        let solver_ids = competitors.iter().map(|c| &c.key).collect::<Vec<_>>();
         let mut borda_ranking = Borda::new(solver_ids.iter());
         let mut firsts_ranking = Firsts::new(solver_ids.iter());
         let mut excluded_problems = Vec::new();
-        let alpha = self.alpha(solvers.len());
-        for (problem_id, contest) in contests {
+        let alpha = self.alpha(competitors.len());
+        for (problem_id, contest) in &contests {
             if !solver_ids
                 .iter()
-                .all(|s| contest.competitors.contains_key(s))
+                .all(|s| contest.competitors.contains_key(*s))
             {
                 excluded_problems.push((problem_id, contest.problem));
                 continue;
             }
 
             borda_ranking.compete(|&a, &b| {
-                let a = &contest.competitors[a];
-                let b = &contest.competitors[b];
+                let a = &contest.competitors[*a];
+                let b = &contest.competitors[*b];
                 self.compete(a, b, contest.auc_start_step, alpha)
             });
             firsts_ranking.compete(|&a, &b| {
-                let a = &contest.competitors[a];
-                let b = &contest.competitors[b];
+                let a = &contest.competitors[*a];
+                let b = &contest.competitors[*b];
                 self.compete(a, b, contest.auc_start_step, alpha)
             });
         }
 
-        let mut table = md::Table::new(
-            vec![
-                md::ColumnHeader::new("Solver", md::Align::Left),
-                md::ColumnHeader::new("Borda", md::Align::Right),
-                md::ColumnHeader::new("Firsts", md::Align::Right),
-            ]
-            .into_iter(),
-        );
+        let mut headers = vec![
+            md::ColumnHeader::new("Solver", md::Align::Left),
+            md::ColumnHeader::new("Borda", md::Align::Right),
+            md::ColumnHeader::new("Firsts", md::Align::Right),
+        ];
+        if normalizations.is_some() {
+            headers.push(md::ColumnHeader::new(
+                "Normalized Best (avg)",
+                md::Align::Right,
+            ));
+        }
+        let mut table = md::Table::new(headers.into_iter());
 
-        for (((solver_id, solver), borda), firsts) in solver_ids
+        for ((c, borda), firsts) in competitors
             .iter()
-            .zip(solvers.iter())
             .zip(borda_ranking.scores())
             .zip(firsts_ranking.scores())
         {
-            table
+            let row = table
                 .row()
-                .item(format!("[{}](#id-{})", solver.spec.name, solver_id))
+                .item(format!("[{}](#id-{})", c.name, c.solver_id))
                 .item(borda)
                 .item(firsts);
+            if let Some(normalizations) = normalizations {
+                // The average over the problems of the average normalized best values on each problem.
+                let averages = contests
+                    .iter()
+                    .filter_map(|(_, contest)| {
+                        let n = normalizations.get(&contest.problem.spec.name)?;
+                        let values = contest
+                            .competitors
+                            .get(&c.key)?
+                            .normalized_best_values(n)
+                            .collect::<Vec<_>>();
+                        BasicStats::new(values, &[]).map(|s| s.mean)
+                    })
+                    .collect::<Vec<_>>();
+                row.item(
+                    BasicStats::new(averages, &[])
+                        .map_or_else(|| "-".to_owned(), |s| format!("{:.3}", s.mean)),
+                );
+            }
         }
         track!(writer.write_table(&table))?;
         track!(writer.newline())?;
@@ -207,11 +605,107 @@ impl Reporter {
         Ok(())
     }
 
-    fn report_individual_results<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+    fn report_win_rates<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Head-to-Head Win Rates"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "Each cell is the fraction of the (problem, repetition) pairs on which the solver of the row \
+             achieved a better final best value than the solver of the column (ties count as half).\n\
+             Repetitions are paired by their random seeds if possible, otherwise by the order of their values.\n"
+        )?;
+
+        let win_rates = track!(self.win_rates())?;
+        let mut headers = vec![md::ColumnHeader::new("Solver", md::Align::Left)];
+        for solver in &win_rates.solvers {
+            headers.push(md::ColumnHeader::new(&solver.name, md::Align::Right));
+        }
+        let mut table = md::Table::new(headers.into_iter());
+        for (solver, row) in win_rates.solvers.iter().zip(win_rates.win_rates.iter()) {
+            let r = table.row();
+            r.item(format!("[{}](#id-{})", solver.name, solver.id));
+            for x in row {
+                r.item(x.map_or_else(|| "-".to_owned(), |x| format!("{:.3}", x)));
+            }
+        }
+        track!(writer.write_table(&table))?;
+        track!(writer.newline())?;
+        Ok(())
+    }
+
+    /// Writes the head-to-head win rate matrix to the files specified by `--win-rates-{csv,json}`.
+    pub fn export_win_rates(&self) -> Result<()> {
+        if self.opt.win_rates_csv.is_none() && self.opt.win_rates_json.is_none() {
+            return Ok(());
+        }
+
+        let win_rates = track!(self.win_rates())?;
+        if let Some(path) = &self.opt.win_rates_csv {
+            let file = track!(File::create(path).map_err(Error::from); path)?;
+            track!(win_rates.write_csv(BufWriter::new(file)); path)?;
+        }
+        if let Some(path) = &self.opt.win_rates_json {
+            let file = track!(File::create(path).map_err(Error::from); path)?;
+            track!(serde_json::to_writer_pretty(BufWriter::new(file), &win_rates)
+                .map_err(Error::from); path)?;
+        }
+        Ok(())
+    }
+
+    fn win_rates(&self) -> Result<WinRates<'_>> {
+        let contests = track!(self.contests())?;
+        let competitors = track!(self.competitors())?;
+
+        let n = competitors.len();
+        let mut matrix = vec![vec![HeadToHead::default(); n]; n];
+        for (_, contest) in &contests {
+            // Problems a solver doesn't participate in are skipped in its pairs.
+            let results = competitors
+                .iter()
+                .map(|c| {
+                    contest
+                        .competitors
+                        .get(&c.key)
+                        .map(|c| c.seeded_best_values())
+                })
+                .collect::<Vec<_>>();
+            for (i, a) in results.iter().enumerate() {
+                for (j, b) in results.iter().enumerate() {
+                    if let (true, Some(a), Some(b)) = (i != j, a, b) {
+                        matrix[i][j] += HeadToHead::compare(a, b);
+                    }
+                }
+            }
+        }
+        let win_rates = matrix
+            .iter()
+            .map(|row| row.iter().map(|h| h.win_rate()).collect())
+            .collect();
+        let solvers = competitors
+            .into_iter()
+            .map(|c| WinRateSolver {
+                id: c.solver_id,
+                name: c.name,
+                label: c.label,
+            })
+            .collect();
+        Ok(WinRates {
+            solvers,
+            win_rates,
+            matrix,
+        })
+    }
+
+    fn report_individual_results<W: Write>(
+        &self,
+        writer: &mut MarkdownWriter<W>,
+        normalizations: Option<&Normalizations>,
+    ) -> Result<()> {
         let mut writer = track!(writer.heading("Individual Results"))?;
         track_writeln!(writer.inner_mut())?;
 
         let contests = track!(self.contests())?;
+        let targets = track!(self.opt.target.load())?;
         for (problem_no, (problem_id, contest)) in contests.into_iter().enumerate() {
             let mut writer = track!(writer.heading(&format!(
                 "({}) Problem: [{}](#id-{})",
@@ -222,9 +716,9 @@ impl Reporter {
 
             // FIXME: Reduce redundant calculation.
             let auc_start_step = contest.auc_start_step;
-            let mut rankings = BTreeMap::new();
+            let mut rankings = Vec::new();
             let alpha = self.alpha(contest.competitors.len());
-            for (solver_id0, competitor0) in &contest.competitors {
+            for (solver_id0, competitor0) in contest.competitors_by_name() {
                 let mut ranking = 1;
                 for (solver_id1, competitor1) in &contest.competitors {
                     if solver_id0 == solver_id1 {
@@ -237,61 +731,106 @@ impl Reporter {
                         ranking += 1;
                     }
                 }
-                rankings.insert(solver_id0, ranking);
+                rankings.push((ranking, solver_id0));
             }
-            let mut rankings = rankings.into_iter().map(|x| (x.1, x.0)).collect::<Vec<_>>();
-            rankings.sort();
 
-            let mut table = md::Table::new(
-                vec![
-                    md::ColumnHeader::new("Ranking", md::Align::Right),
-                    md::ColumnHeader::new("Solver", md::Align::Left),
-                    md::ColumnHeader::new("Best (avg +- sd)", md::Align::Right),
-                    md::ColumnHeader::new("AUC (avg +- sd)", md::Align::Right),
-                    md::ColumnHeader::new("Elapsed (avg +- sd)", md::Align::Right),
-                ]
-                .into_iter(),
-            );
+            // Tied competitors share the same ranking and are ordered by their names.
+            rankings.sort_by_key(|x| x.0);
+
+            let stat = self.opt.stats;
+            let mut headers = vec![
+                md::ColumnHeader::new("Ranking", md::Align::Right),
+                md::ColumnHeader::new("Solver", md::Align::Left),
+                md::ColumnHeader::new(&stat.header("Best"), md::Align::Right),
+            ];
+            let normalization = normalizations.and_then(|n| n.get(&contest.problem.spec.name));
+            if normalizations.is_some() {
+                headers.push(md::ColumnHeader::new(
+                    &stat.header("Normalized Best"),
+                    md::Align::Right,
+                ));
+            }
+            if self.opt.quantile_columns {
+                let quantiles = self
+                    .opt
+                    .quantiles
+                    .iter()
+                    .map(|q| format!("q{}", q))
+                    .collect::<Vec<_>>();
+                headers.push(md::ColumnHeader::new(
+                    &format!("Best (min / {} / max)", quantiles.join(" / ")),
+                    md::Align::Right,
+                ));
+            }
+            headers.push(md::ColumnHeader::new(&stat.header("AUC"), md::Align::Right));
+            headers.push(md::ColumnHeader::new(
+                &stat.header("Elapsed"),
+                md::Align::Right,
+            ));
+            // Pruning is meaningless for single-step problems.
+            let multi_step = contest.problem.spec.steps.last() > 1;
+            if multi_step {
+                headers.push(md::ColumnHeader::new(
+                    &stat.header("Pruning Efficiency"),
+                    md::Align::Right,
+                ));
+            }
+            let problem_targets = targets.get(&contest.problem.spec);
+            for target in &problem_targets {
+                headers.push(md::ColumnHeader::new(
+                    &format!("Budget to Target ({})", target),
+                    md::Align::Right,
+                ));
+            }
+            for key in &self.opt.solver_attrs {
+                headers.push(md::ColumnHeader::new(key, md::Align::Right));
+            }
+            let mut table = md::Table::new(headers.into_iter());
             for (ranking, solver_id) in rankings {
                 let c = &contest.competitors[solver_id];
 
                 let solver = format!(
                     "[{}](#id-{}) ([study](#id-{}))",
-                    c.solver.spec.name,
-                    solver_id,
+                    self.competitor_name(c.solver, c.label),
+                    track!(c.solver.id())?,
                     track!(c.studies[0].id())?
                 );
 
-                let best_values = c.best_values().map(|x| x.0).collect::<Vec<_>>();
-                let best_value = format!(
-                    "{:.06} +- {:.06}",
-                    average(best_values.iter().copied()),
-                    stddev(best_values.iter().copied())
-                );
-
-                let aucs = c.aucs(auc_start_step).map(|x| x.0).collect::<Vec<_>>();
-                let auc = format!(
-                    "{:.03} +- {:.03}",
-                    average(aucs.iter().copied()),
-                    stddev(aucs.iter().copied())
-                );
+                let best_value = stat.format(c.best_values().map(|x| x.0), 6);
+                let auc = stat.format(c.aucs(auc_start_step).map(|x| x.0), 3);
+                let elapsed_time = stat.format(c.elapsed_times().map(|x| x.as_secs_f64()), 3);
 
-                let elapsed_times = c
-                    .elapsed_times()
-                    .map(|x| x.as_secs_f64())
-                    .collect::<Vec<_>>();
-                let elapsed_time = format!(
-                    "{:.03} +- {:.03}",
-                    average(elapsed_times.iter().copied()),
-                    stddev(elapsed_times.iter().copied())
-                );
-                table
-                    .row()
-                    .item(ranking)
-                    .item(solver)
-                    .item(best_value)
-                    .item(auc)
-                    .item(elapsed_time);
+                let row = table.row();
+                row.item(ranking).item(solver).item(best_value);
+                if normalizations.is_some() {
+                    row.item(normalization.map_or_else(
+                        || "-".to_owned(),
+                        |n| stat.format(c.normalized_best_values(n), 3),
+                    ));
+                }
+                if self.opt.quantile_columns {
+                    let quantiles =
+                        match BasicStats::new(c.best_values().map(|x| x.0), &self.opt.quantiles) {
+                            None => "-".to_owned(),
+                            Some(s) => std::iter::once(s.min)
+                                .chain(s.quantiles.iter().map(|x| x.1))
+                                .chain(std::iter::once(s.max))
+                                .map(|v| format!("{:.06}", v))
+                                .collect::<Vec<_>>()
+                                .join(" / "),
+                        };
+                    row.item(quantiles);
+                }
+                row.item(auc).item(elapsed_time);
+                if multi_step {
+                    row.item(stat.format(c.pruning_efficiencies(), 3));
+                }
+                for &target in &problem_targets {
+                    row.item(c.budget_to_reach(target, stat));
+                }
+                for key in &self.opt.solver_attrs {
+                    row.item(c.solver_attr(key, stat));
+                }
             }
 
             track!(writer.write_table(&table))?;
@@ -301,66 +840,527 @@ impl Reporter {
         Ok(())
     }
 
-    fn report_solvers<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
-        let mut writer = track!(writer.heading("Solvers"))?;
-        for (id, solver) in track!(self.solvers())? {
-            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
+    fn report_per_study_results<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Per-Study Results"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "The studies of each solver are sorted by their best values.\n\
+             If a solver has more than {} studies on a problem, only the best and the worst ones are shown.\n",
+            self.opt.per_study_rows
+        )?;
 
-            track_writeln!(writer.inner_mut(), "recipe:")?;
-            let json = track!(serde_json::to_string_pretty(&solver.recipe).map_err(Error::from))?;
-            track!(writer.code_block("json", &json))?;
-            track_writeln!(writer.inner_mut())?;
+        for (problem_no, p) in track!(self.per_study_results())?.into_iter().enumerate() {
+            let mut writer = track!(writer.heading(&format!(
+                "({}) Problem: [{}](#id-{})",
+                problem_no + 1,
+                p.problem.spec.name,
+                p.problem_id
+            )))?;
 
-            track_writeln!(writer.inner_mut(), "specification:")?;
-            let json = track!(serde_json::to_string_pretty(&solver.spec).map_err(Error::from))?;
-            track!(writer.code_block("json", &json))?;
+            let mut table = md::Table::new(
+                vec![
+                    md::ColumnHeader::new("Solver", md::Align::Left),
+                    md::ColumnHeader::new("Seed", md::Align::Right),
+                    md::ColumnHeader::new("Best", md::Align::Right),
+                    md::ColumnHeader::new("AUC", md::Align::Right),
+                    md::ColumnHeader::new("Trials", md::Align::Right),
+                    md::ColumnHeader::new("Duration (s)", md::Align::Right),
+                    md::ColumnHeader::new("Termination", md::Align::Left),
+                ]
+                .into_iter(),
+            );
+            for c in p.competitors {
+                let solver = format!("[{}](#id-{})", c.name, c.solver_id);
+                let rows = c.rows.len();
+                let limit = self.opt.per_study_rows;
+                for (i, row) in c.rows.iter().enumerate() {
+                    if rows > limit && i == limit.div_ceil(2) {
+                        table
+                            .row()
+                            .item(&solver)
+                            .item("...")
+                            .item(format!("({} studies omitted)", rows - limit))
+                            .item("")
+                            .item("")
+                            .item("")
+                            .item("");
+                    }
+                    if rows > limit && limit.div_ceil(2) <= i && i < rows - limit / 2 {
+                        continue;
+                    }
+                    table
+                        .row()
+                        .item(&solver)
+                        .item(row.seed)
+                        .item(
+                            row.best_value
+                                .map_or_else(|| "-".to_owned(), |v| format!("{:.6}", v)),
+                        )
+                        .item(
+                            row.auc
+                                .map_or_else(|| "-".to_owned(), |v| format!("{:.3}", v)),
+                        )
+                        .item(row.trials)
+                        .item(format!("{:.3}", row.duration.as_secs_f64()))
+                        .item(
+                            row.termination
+                                .map_or_else(|| "-".to_owned(), |t| t.to_string()),
+                        );
+                }
+            }
+            track!(writer.write_table(&table))?;
             track_writeln!(writer.inner_mut())?;
         }
         Ok(())
     }
 
-    fn report_problems<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
-        let mut writer = track!(writer.heading("Problems"))?;
-        for (id, problem) in track!(self.problems())? {
-            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
-
-            track_writeln!(writer.inner_mut(), "recipe:")?;
-            let json = track!(serde_json::to_string_pretty(&problem.recipe).map_err(Error::from))?;
-            track!(writer.code_block("json", &json))?;
-            track_writeln!(writer.inner_mut())?;
+    /// Writes the per-study results to the file specified by `--per-study-csv`.
+    pub fn export_per_study_results(&self) -> Result<()> {
+        let path = if let Some(path) = &self.opt.per_study_csv {
+            path
+        } else {
+            return Ok(());
+        };
 
-            track_writeln!(writer.inner_mut(), "specification:")?;
-            let json = track!(serde_json::to_string_pretty(&problem.spec).map_err(Error::from))?;
-            track!(writer.code_block("json", &json))?;
-            track_writeln!(writer.inner_mut())?;
+        let file = track!(File::create(path).map_err(Error::from); path)?;
+        let mut writer = BufWriter::new(file);
+        track_writeln!(
+            writer,
+            "problem,solver,seed,best_value,auc,trials,duration,termination"
+        )?;
+        for p in track!(self.per_study_results())? {
+            for c in p.competitors {
+                for row in c.rows {
+                    track_writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{},{}",
+                        csv_field(&p.problem.spec.name),
+                        csv_field(&c.name),
+                        row.seed,
+                        row.best_value.map_or_else(String::new, |x| x.to_string()),
+                        row.auc.map_or_else(String::new, |x| x.to_string()),
+                        row.trials,
+                        row.duration.as_secs_f64(),
+                        row.termination.map_or_else(String::new, |t| t.to_string())
+                    )?;
+                }
+            }
         }
-        Ok(())
+        track!(writer.flush().map_err(Error::from))
     }
 
-    fn report_studies<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
-        let mut writer = track!(writer.heading("Studies"))?;
-        let mut studies = BTreeMap::<_, Vec<_>>::new();
-        for study in &self.studies {
-            let id = track!(study.id())?;
-            studies
-                .entry((&study.problem.spec.name, &study.solver.spec.name, id))
-                .or_default()
-                .push(study);
+    /// Returns the studies of each (problem, competitor) pair sorted by their best values
+    /// (studies without best values come last).
+    fn per_study_results(&self) -> Result<Vec<ProblemStudyRows<'_>>> {
+        let mut result = Vec::new();
+        for (problem_id, contest) in track!(self.contests())? {
+            let mut competitors = Vec::new();
+            for (_, c) in contest.competitors_by_name() {
+                let mut rows = c
+                    .studies
+                    .iter()
+                    .map(|s| StudyRow {
+                        seed: s.seed,
+                        best_value: s.best_value(),
+                        auc: s.auc(contest.auc_start_step),
+                        trials: s.trial_count(),
+                        duration: s.duration(),
+                        termination: s.termination,
+                    })
+                    .collect::<Vec<_>>();
+                rows.sort_by_key(|r| {
+                    (
+                        r.best_value.is_none(),
+                        r.best_value.map(OrderedFloat),
+                        r.seed,
+                    )
+                });
+                competitors.push(CompetitorStudyRows {
+                    name: self.competitor_name(c.solver, c.label),
+                    solver_id: track!(c.solver.id())?,
+                    rows,
+                });
+            }
+            result.push(ProblemStudyRows {
+                problem_id,
+                problem: contest.problem,
+                competitors,
+            });
         }
-        for ((problem_name, solver_name, id), studies) in studies {
-            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
-            let mut list = writer.list();
-            track!(list.item(&format!(
-                "problem: [{}](#id-{})",
-                problem_name,
-                track!(studies[0].problem.id())?
-            )))?;
-            track!(list.item(&format!(
-                "solver: [{}](#id-{})",
-                solver_name,
+        Ok(result)
+    }
+
+    fn report_expected_running_times<W: Write>(
+        &self,
+        writer: &mut MarkdownWriter<W>,
+    ) -> Result<()> {
+        let mut writer = track!(writer.heading("Expected Running Time"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "\"Success\" is the number of the studies in which the best value reached the target.\n\
+             \"ERT\" (expected running time) is the total budget consumed by all the studies \
+             (until reaching the target, or the whole budget for unsuccessful studies) \
+             divided by the number of the successful studies (`-` if there are no such studies).\n"
+        )?;
+
+        let target_stats = track!(self.target_stats())?;
+        for (problem_no, p) in target_stats.into_iter().enumerate() {
+            let mut writer = track!(writer.heading(&format!(
+                "({}) Problem: [{}](#id-{})",
+                problem_no + 1,
+                p.problem.spec.name,
+                p.problem_id
+            )))?;
+
+            let mut table = md::Table::new(
+                vec![
+                    md::ColumnHeader::new("Solver", md::Align::Left),
+                    md::ColumnHeader::new("Target", md::Align::Right),
+                    md::ColumnHeader::new("Success", md::Align::Right),
+                    md::ColumnHeader::new("ERT", md::Align::Right),
+                ]
+                .into_iter(),
+            );
+            for c in p.competitors {
+                for s in c.stats {
+                    table
+                        .row()
+                        .item(format!("[{}](#id-{})", c.name, c.solver_id))
+                        .item(s.target)
+                        .item(format!("{}/{}", s.successes, s.runs))
+                        .item(
+                            s.ert
+                                .map_or_else(|| "-".to_owned(), |x| format!("{:.1}", x)),
+                        );
+                }
+            }
+            track!(writer.write_table(&table))?;
+            track_writeln!(writer.inner_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the success rates and the expected running times to the file specified by `--ert-csv`.
+    pub fn export_expected_running_times(&self) -> Result<()> {
+        let path = if let Some(path) = &self.opt.ert_csv {
+            path
+        } else {
+            return Ok(());
+        };
+
+        let file = track!(File::create(path).map_err(Error::from); path)?;
+        let mut writer = BufWriter::new(file);
+        track_writeln!(
+            writer,
+            "problem,solver,target,runs,successes,success_rate,ert"
+        )?;
+        for p in track!(self.target_stats())? {
+            for c in p.competitors {
+                for s in c.stats {
+                    track_writeln!(
+                        writer,
+                        "{},{},{},{},{},{},{}",
+                        csv_field(&p.problem.spec.name),
+                        csv_field(&c.name),
+                        s.target,
+                        s.runs,
+                        s.successes,
+                        s.success_rate(),
+                        s.ert.map_or_else(String::new, |x| x.to_string())
+                    )?;
+                }
+            }
+        }
+        track!(writer.flush().map_err(Error::from))
+    }
+
+    /// Writes the normalization constants to the file specified by `--export-normalization`.
+    pub fn export_normalizations(&self) -> Result<()> {
+        if let Some(path) = &self.opt.export_normalization {
+            let (normalizations, _) = track!(self.normalizations())?;
+            track!(normalizations.save(path))?;
+        }
+        Ok(())
+    }
+
+    fn normalizes(&self) -> bool {
+        self.opt.normalize || self.opt.normalization_file.is_some()
+    }
+
+    /// Returns the normalization constants of the problems and the names of the problems
+    /// that are absent from `--normalization-file` (i.e., normalized by the local results).
+    fn normalizations(&self) -> Result<(Normalizations, Vec<String>)> {
+        let mut normalizations = if let Some(path) = &self.opt.normalization_file {
+            track!(Normalizations::load(path))?
+        } else {
+            Normalizations::default()
+        };
+
+        let mut local = BTreeMap::<_, Vec<_>>::new();
+        for (_, contest) in track!(self.contests())? {
+            let name = &contest.problem.spec.name;
+            if normalizations.get(name).is_some() {
+                continue;
+            }
+            local
+                .entry(name.clone())
+                .or_default()
+                .extend(contest.competitors.values().flat_map(|c| c.best_values()));
+        }
+
+        let mut fallbacks = Vec::new();
+        for (name, values) in local {
+            if let Some(n) = Normalization::from_values(values.into_iter().map(|v| v.0)) {
+                normalizations.insert(&name, n);
+            }
+            if self.opt.normalization_file.is_some() {
+                fallbacks.push(name);
+            }
+        }
+        Ok((normalizations, fallbacks))
+    }
+
+    /// Returns the statistics of each (problem, competitor) pair for the target values of the problem.
+    fn target_stats(&self) -> Result<Vec<ProblemTargetStats<'_>>> {
+        let targets = track!(self.opt.target.load())?;
+        let mut result = Vec::new();
+        for (problem_id, contest) in track!(self.contests())? {
+            let problem_targets = targets.get(&contest.problem.spec);
+            let mut competitors = Vec::new();
+            for (_, c) in contest.competitors_by_name() {
+                competitors.push(CompetitorTargetStats {
+                    name: self.competitor_name(c.solver, c.label),
+                    solver_id: track!(c.solver.id())?,
+                    stats: problem_targets
+                        .iter()
+                        .map(|&t| TargetStats::from_studies(t, c.studies.iter().copied()))
+                        .collect(),
+                });
+            }
+            result.push(ProblemTargetStats {
+                problem_id,
+                problem: contest.problem,
+                competitors,
+            });
+        }
+        Ok(result)
+    }
+
+    fn report_param_coverage<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Parameter Coverage"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "Each histogram shows the distribution of the values asked by the solver \
+             over the range of the variable (`·` means an empty bin).\n\
+             \"Coverage\" is the fraction of the non-empty bins (or the asked choices).\n"
+        )?;
+
+        let contests = track!(self.contests())?;
+        for (problem_no, (problem_id, contest)) in contests.into_iter().enumerate() {
+            let mut writer = track!(writer.heading(&format!(
+                "({}) Problem: [{}](#id-{})",
+                problem_no + 1,
+                contest.problem.spec.name,
+                problem_id
+            )))?;
+
+            let mut table = md::Table::new(
+                vec![
+                    md::ColumnHeader::new("Solver", md::Align::Left),
+                    md::ColumnHeader::new("Variable", md::Align::Left),
+                    md::ColumnHeader::new("Histogram", md::Align::Left),
+                    md::ColumnHeader::new("Coverage", md::Align::Right),
+                ]
+                .into_iter(),
+            );
+            let vars = contest.problem.spec.params_domain.variables();
+            for (_, c) in contest.competitors_by_name() {
+                let solver = format!(
+                    "[{}](#id-{})",
+                    self.competitor_name(c.solver, c.label),
+                    track!(c.solver.id())?
+                );
+                for (i, var) in vars.iter().enumerate() {
+                    let coverage = Coverage::new(var, &c.asked_values(i, vars.len()));
+                    table
+                        .row()
+                        .item(&solver)
+                        .item(var.name())
+                        .item(coverage.summary())
+                        .item(format!("{:.0}%", coverage.fraction() * 100.0));
+                }
+            }
+            track!(writer.write_table(&table))?;
+            track_writeln!(writer.inner_mut())?;
+        }
+        Ok(())
+    }
+
+    fn report_setup_costs<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Setup Cost"))?;
+        track_writeln!(writer.inner_mut())?;
+        track_writeln!(
+            writer.inner_mut(),
+            "Factory times include loading the specifications (e.g., spawning external programs), \
+             and \"Steady Ask\" is the mean latency of the asks after the first one (in seconds).\n"
+        )?;
+
+        let mut solvers = BTreeMap::<_, Vec<_>>::new();
+        let mut problems = BTreeMap::<_, Vec<_>>::new();
+        for study in &self.studies {
+            if let Some(setup) = &study.setup {
+                let label = self.opt.group_by.as_ref().map(|k| study.label(k));
+                solvers
+                    .entry((
+                        self.competitor_name(&study.solver, label),
+                        track!(study.solver.id())?,
+                    ))
+                    .or_default()
+                    .push(setup);
+                problems
+                    .entry((study.problem.spec.name.clone(), track!(study.problem.id())?))
+                    .or_default()
+                    .push(setup);
+            }
+        }
+
+        let stats = self.opt.stats;
+        let mut table = md::Table::new(
+            vec![
+                md::ColumnHeader::new("Solver", md::Align::Left),
+                md::ColumnHeader::new(&stats.header("Factory"), md::Align::Right),
+                md::ColumnHeader::new(&stats.header("Create"), md::Align::Right),
+                md::ColumnHeader::new(&stats.header("First Ask"), md::Align::Right),
+                md::ColumnHeader::new(&stats.header("Steady Ask"), md::Align::Right),
+            ]
+            .into_iter(),
+        );
+        for ((name, id), setups) in &solvers {
+            table
+                .row()
+                .item(format!("[{}](#id-{})", name, id))
+                .item(stats.format(setups.iter().map(|s| s.solver_factory_elapsed.get()), 3))
+                .item(stats.format(setups.iter().map(|s| s.create_solver_elapsed.get()), 3))
+                .item(
+                    stats.format(
+                        setups
+                            .iter()
+                            .filter_map(|s| s.first_ask_elapsed)
+                            .map(|e| e.get()),
+                        3,
+                    ),
+                )
+                .item(
+                    stats.format(
+                        setups
+                            .iter()
+                            .filter_map(|s| s.steady_ask_elapsed)
+                            .map(|e| e.get()),
+                        3,
+                    ),
+                );
+        }
+        track!(writer.write_table(&table))?;
+        track!(writer.newline())?;
+
+        let mut table = md::Table::new(
+            vec![
+                md::ColumnHeader::new("Problem", md::Align::Left),
+                md::ColumnHeader::new(&stats.header("Factory"), md::Align::Right),
+                md::ColumnHeader::new(&stats.header("Create"), md::Align::Right),
+            ]
+            .into_iter(),
+        );
+        for ((name, id), setups) in &problems {
+            table
+                .row()
+                .item(format!("[{}](#id-{})", name, id))
+                .item(stats.format(setups.iter().map(|s| s.problem_factory_elapsed.get()), 3))
+                .item(stats.format(setups.iter().map(|s| s.create_problem_elapsed.get()), 3));
+        }
+        track!(writer.write_table(&table))?;
+        track!(writer.newline())?;
+        Ok(())
+    }
+
+    fn report_solvers<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Solvers"))?;
+        for (id, solver) in track!(self.solvers())? {
+            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
+
+            if let Some(max_params) = solver.spec.max_params {
+                track_writeln!(
+                    writer.inner_mut(),
+                    "maximum number of parameters: {}\n",
+                    max_params
+                )?;
+            }
+
+            track_writeln!(writer.inner_mut(), "recipe:")?;
+            let json = track!(serde_json::to_string_pretty(&solver.recipe).map_err(Error::from))?;
+            track!(writer.code_block("json", &json))?;
+            track_writeln!(writer.inner_mut())?;
+
+            track_writeln!(writer.inner_mut(), "specification:")?;
+            let json = track!(serde_json::to_string_pretty(&solver.spec).map_err(Error::from))?;
+            track!(writer.code_block("json", &json))?;
+            track_writeln!(writer.inner_mut())?;
+        }
+        Ok(())
+    }
+
+    fn report_problems<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Problems"))?;
+        for (id, problem) in track!(self.problems())? {
+            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
+
+            track_writeln!(writer.inner_mut(), "recipe:")?;
+            let json = track!(serde_json::to_string_pretty(&problem.recipe).map_err(Error::from))?;
+            track!(writer.code_block("json", &json))?;
+            track_writeln!(writer.inner_mut())?;
+
+            track_writeln!(writer.inner_mut(), "specification:")?;
+            let json = track!(serde_json::to_string_pretty(&problem.spec).map_err(Error::from))?;
+            track!(writer.code_block("json", &json))?;
+            track_writeln!(writer.inner_mut())?;
+        }
+        Ok(())
+    }
+
+    fn report_studies<W: Write>(&self, writer: &mut MarkdownWriter<W>) -> Result<()> {
+        let mut writer = track!(writer.heading("Studies"))?;
+        let mut studies = BTreeMap::<_, Vec<_>>::new();
+        for study in &self.studies {
+            let id = track!(study.id())?;
+            studies
+                .entry((&study.problem.spec.name, &study.solver.spec.name, id))
+                .or_default()
+                .push(study);
+        }
+        for ((problem_name, solver_name, id), studies) in studies {
+            let mut writer = track!(writer.heading(&format!("ID: {}", id)))?;
+            let mut list = writer.list();
+            track!(list.item(&format!(
+                "problem: [{}](#id-{})",
+                problem_name,
+                track!(studies[0].problem.id())?
+            )))?;
+            track!(list.item(&format!(
+                "solver: [{}](#id-{})",
+                solver_name,
                 track!(studies[0].solver.id())?
             )))?;
-            track!(list.item(&format!("budget: {}", studies[0].budget)))?;
+            if studies[0].budget_unit.is_steps() {
+                track!(list.item(&format!("budget: {}", studies[0].budget)))?;
+            } else {
+                track!(list.item(&format!(
+                    "budget: {} {}",
+                    studies[0].budget, studies[0].budget_unit
+                )))?;
+            }
             track!(list.item(&format!("repeats: {}", studies.len())))?;
             track!(list.item(&format!("concurrency: {}", studies[0].concurrency)))?;
             if studies[0].concurrency.get() > 1 {
@@ -389,17 +1389,55 @@ impl Reporter {
         Ok(map.into_iter().map(|(k, v)| (k.1, v)))
     }
 
+    /// Returns the competitors (i.e., solvers, or pairs of a solver and a label value if `--group-by` is specified).
+    fn competitors(&self) -> Result<Vec<CompetitorEntry<'_>>> {
+        let mut map = BTreeMap::new();
+        for study in &self.studies {
+            let solver_id = track!(study.solver.id())?;
+            let label = self.opt.group_by.as_ref().map(|k| study.label(k));
+            map.entry((&study.solver.spec.name, solver_id, label))
+                .or_insert(study);
+        }
+        map.into_iter()
+            .map(|((_, solver_id, label), study)| {
+                Ok(CompetitorEntry {
+                    key: track!(self.competitor_key(study))?,
+                    name: self.competitor_name(&study.solver, label),
+                    solver_id,
+                    label,
+                })
+            })
+            .collect()
+    }
+
+    fn competitor_key(&self, study: &StudyRecord) -> Result<String> {
+        let solver_id = track!(study.solver.id())?;
+        if let Some(key) = &self.opt.group_by {
+            Ok(format!("{}/{}", solver_id, study.label(key)))
+        } else {
+            Ok(solver_id)
+        }
+    }
+
+    fn competitor_name(&self, solver: &SolverRecord, label: Option<&str>) -> String {
+        match (&self.opt.group_by, label) {
+            (Some(key), Some(value)) => format!("{} ({}={})", solver.spec.name, key, value),
+            _ => solver.spec.name.clone(),
+        }
+    }
+
+    /// Compares two competitors by the metrics in precedence order.
+    ///
+    /// `Ordering::Equal` means that there is no statistically significant difference (i.e., a tie).
     fn compete(&self, a: &Competitor, b: &Competitor, auc_start_step: u64, alpha: f64) -> Ordering {
         for metric in &self.opt.metrics {
             let order = match metric {
-                Metric::BestValue => {
-                    MannWhitneyU::new(a.best_values(), b.best_values()).order(alpha)
-                }
+                Metric::BestValue => significance_order(a.best_values(), b.best_values(), alpha),
                 Metric::Auc => {
-                    MannWhitneyU::new(a.aucs(auc_start_step), b.aucs(auc_start_step)).order(alpha)
+                    significance_order(a.aucs(auc_start_step), b.aucs(auc_start_step), alpha)
                 }
                 Metric::ElapsedTime => {
-                    MannWhitneyU::new(a.elapsed_times(), b.elapsed_times()).order(alpha)
+                    significance_order(a.elapsed_times(), b.elapsed_times(), alpha)
                 }
             };
             if order != Ordering::Equal {
@@ -423,36 +1461,206 @@ impl Reporter {
         0.000_01
     }
 
-    fn contests(&self) -> Result<BTreeMap<String, Contest>> {
+    /// Returns the results of solvers on problems whose studies consumed materially different budgets.
+    fn incomparables(&self) -> Result<Vec<Incomparable<'_>>> {
+        let mut incomparables = Vec::new();
+        for (problem_id, contest) in track!(self.all_contests())? {
+            for (key, c) in contest.competitors_by_name() {
+                let budgets = c
+                    .studies
+                    .iter()
+                    .map(|s| s.consumed_budget())
+                    .collect::<Vec<_>>();
+                let min_budget = budgets.iter().copied().fold(f64::INFINITY, f64::min);
+                let max_budget = budgets.iter().copied().fold(0.0, f64::max);
+                let short_studies = budgets
+                    .iter()
+                    .filter(|&&b| b < max_budget * COMPARABLE_BUDGET_RATIO)
+                    .count();
+                if short_studies > 0 {
+                    incomparables.push(Incomparable {
+                        problem_id: problem_id.clone(),
+                        problem: contest.problem,
+                        competitor_key: key.clone(),
+                        solver: c.solver,
+                        label: c.label,
+                        studies: budgets.len(),
+                        short_studies,
+                        min_budget,
+                        max_budget,
+                    });
+                }
+            }
+        }
+        Ok(incomparables)
+    }
+
+    /// Returns the results of the studies grouped by problems (with their IDs).
+    ///
+    /// The contests are ordered by the problem names (and then by the problem IDs).
+    /// If `--strict-comparability` is specified, incomparable results are excluded.
+    fn contests(&self) -> Result<Vec<(String, Contest<'_>)>> {
+        let mut contests = track!(self.all_contests())?;
+        if self.opt.strict_comparability {
+            for x in track!(self.incomparables())? {
+                if let Some((_, contest)) = contests.iter_mut().find(|(id, _)| *id == x.problem_id)
+                {
+                    contest.competitors.remove(&x.competitor_key);
+                }
+            }
+            contests.retain(|(_, contest)| !contest.competitors.is_empty());
+        }
+        Ok(contests)
+    }
+
+    fn all_contests(&self) -> Result<Vec<(String, Contest<'_>)>> {
         let mut contests = BTreeMap::new();
         for study in &self.studies {
             let problem_id = track!(study.problem.id())?;
-            let contest = contests.entry(problem_id).or_insert_with(|| Contest {
+            let key = (&study.problem.spec.name, problem_id);
+            let contest = contests.entry(key).or_insert_with(|| Contest {
                 problem: &study.problem,
                 competitors: BTreeMap::new(),
                 auc_start_step: study.problem.spec.steps.last(),
             });
-            if let Some(trial) = study.first_complete_trial() {
-                if let Some(step) = trial.start_step() {
-                    if contest.auc_start_step < step {
-                        contest.auc_start_step = step;
-                    }
+            if let Some(step) = study.first_complete_step() {
+                if contest.auc_start_step < step {
+                    contest.auc_start_step = step;
                 }
             }
 
-            let solver_id = track!(study.solver.id())?;
+            let key = track!(self.competitor_key(study))?;
             contest
                 .competitors
-                .entry(solver_id)
+                .entry(key)
                 .or_insert_with(|| Competitor {
                     solver: &study.solver,
+                    label: self.opt.group_by.as_ref().map(|k| study.label(k)),
                     studies: Vec::new(),
                 })
                 .studies
                 .push(study)
         }
-        Ok(contests)
+        Ok(contests
+            .into_iter()
+            .map(|((_, problem_id), contest)| (problem_id, contest))
+            .collect())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WinRates<'a> {
+    solvers: Vec<WinRateSolver<'a>>,
+    win_rates: Vec<Vec<Option<f64>>>,
+    matrix: Vec<Vec<HeadToHead>>,
+}
+impl<'a> WinRates<'a> {
+    fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        track_write!(writer, "solver")?;
+        for solver in &self.solvers {
+            track_write!(writer, ",{}", csv_field(&solver.name))?;
+        }
+        track_writeln!(writer)?;
+
+        for (solver, row) in self.solvers.iter().zip(self.win_rates.iter()) {
+            track_write!(writer, "{}", csv_field(&solver.name))?;
+            for x in row {
+                match x {
+                    Some(x) => track_write!(writer, ",{}", x)?,
+                    None => track_write!(writer, ",")?,
+                }
+            }
+            track_writeln!(writer)?;
+        }
+        track!(writer.flush().map_err(Error::from))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WinRateSolver<'a> {
+    id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<&'a str>,
+}
+
+/// Compares two samples by the Mann-Whitney U test.
+///
+/// `MannWhitneyU` regards the samples as significantly different (in both directions)
+/// if all the values are the same because their variance is zero, so such samples are treated as a tie here.
+fn significance_order<T: Ord>(
+    xs: impl Iterator<Item = T>,
+    ys: impl Iterator<Item = T>,
+    alpha: f64,
+) -> Ordering {
+    let xs = xs.collect::<Vec<_>>();
+    let ys = ys.collect::<Vec<_>>();
+    let mut values = xs.iter().chain(ys.iter());
+    if let Some(first) = values.next() {
+        if values.all(|v| v == first) {
+            return Ordering::Equal;
+        }
     }
+    MannWhitneyU::new(xs.into_iter(), ys.into_iter()).order(alpha)
+}
+
+/// Quotes `s` if it contains characters that must be escaped in CSV.
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+struct ProblemTargetStats<'a> {
+    problem_id: String,
+    problem: &'a ProblemRecord,
+    competitors: Vec<CompetitorTargetStats>,
+}
+
+struct CompetitorTargetStats {
+    name: String,
+    solver_id: String,
+    stats: Vec<TargetStats>,
+}
+
+/// Studies of a solver are regarded as incomparable if some of them consumed less than
+/// this ratio of the budget consumed by the others.
+const COMPARABLE_BUDGET_RATIO: f64 = 0.9;
+
+/// Result of a solver on a problem whose studies consumed materially different budgets.
+struct Incomparable<'a> {
+    problem_id: String,
+    problem: &'a ProblemRecord,
+    competitor_key: String,
+    solver: &'a SolverRecord,
+    label: Option<&'a str>,
+    studies: usize,
+    short_studies: usize,
+    min_budget: f64,
+    max_budget: f64,
+}
+
+struct ProblemStudyRows<'a> {
+    problem_id: String,
+    problem: &'a ProblemRecord,
+    competitors: Vec<CompetitorStudyRows>,
+}
+
+struct CompetitorStudyRows {
+    name: String,
+    solver_id: String,
+    rows: Vec<StudyRow>,
+}
+
+struct StudyRow {
+    seed: u64,
+    best_value: Option<f64>,
+    auc: Option<f64>,
+    trials: usize,
+    duration: Duration,
+    termination: Option<Termination>,
 }
 
 struct Contest<'a> {
@@ -460,9 +1668,25 @@ struct Contest<'a> {
     competitors: BTreeMap<String, Competitor<'a>>,
     auc_start_step: u64,
 }
+impl<'a> Contest<'a> {
+    /// Returns the competitors (with their keys) ordered by the solver names (and then by the keys).
+    fn competitors_by_name(&self) -> Vec<(&String, &Competitor<'a>)> {
+        let mut competitors = self.competitors.iter().collect::<Vec<_>>();
+        competitors.sort_by_key(|(key, c)| (&c.solver.spec.name, *key));
+        competitors
+    }
+}
+
+struct CompetitorEntry<'a> {
+    key: String,
+    name: String,
+    solver_id: String,
+    label: Option<&'a str>,
+}
 
 struct Competitor<'a> {
     solver: &'a SolverRecord,
+    label: Option<&'a str>,
     studies: Vec<&'a StudyRecord>,
 }
 impl<'a> Competitor<'a> {
@@ -480,7 +1704,640 @@ impl<'a> Competitor<'a> {
             .map(OrderedFloat)
     }
 
+    fn normalized_best_values(
+        &self,
+        normalization: Normalization,
+    ) -> impl '_ + Iterator<Item = f64> {
+        self.best_values()
+            .map(move |v| normalization.normalize(v.0))
+    }
+
+    fn seeded_best_values(&self) -> Vec<(u64, Option<f64>)> {
+        self.studies
+            .iter()
+            .map(|s| (s.seed, s.best_value()))
+            .collect()
+    }
+
+    /// Returns the summary of the budgets consumed until the studies reached `target`.
+    fn budget_to_reach(&self, target: f64, stat: Stat) -> String {
+        let budgets = self
+            .studies
+            .iter()
+            .filter_map(|s| s.budget_to_reach(target))
+            .collect::<Vec<_>>();
+        if budgets.is_empty() {
+            "-".to_owned()
+        } else if budgets.len() < self.studies.len() {
+            format!(
+                "{} ({}/{})",
+                stat.format(budgets.iter().copied(), 1),
+                budgets.len(),
+                self.studies.len()
+            )
+        } else {
+            stat.format(budgets.into_iter(), 1)
+        }
+    }
+
+    /// Returns the fractions of the evaluated steps spent on the eventually completed trials.
+    fn pruning_efficiencies(&self) -> impl '_ + Iterator<Item = f64> {
+        self.studies
+            .iter()
+            .filter_map(|s| s.budget_accounting().pruning_efficiency())
+    }
+
     fn elapsed_times(&self) -> impl '_ + Iterator<Item = Duration> {
         self.studies.iter().map(|s| s.solver_elapsed())
     }
+
+    /// Returns the `i`-th parameter values of the trials (except placeholders) of the studies.
+    fn asked_values(&self, i: usize, params_len: usize) -> Vec<f64> {
+        self.studies
+            .iter()
+            .flat_map(|s| s.trials.iter())
+            .filter(|t| t.params.len() == params_len)
+            .map(|t| t.params[i])
+            .collect()
+    }
+
+    fn solver_attr(&self, key: &str, stat: Stat) -> String {
+        let values = self
+            .studies
+            .iter()
+            .filter_map(|s| s.solver_attrs.get(key))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return "-".to_owned();
+        }
+
+        let numbers = values
+            .iter()
+            .map(|v| v.parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>();
+        if let Ok(numbers) = numbers {
+            stat.format(numbers.into_iter(), 3)
+        } else {
+            let values = values.into_iter().collect::<BTreeSet<_>>();
+            values.into_iter().cloned().collect::<Vec<_>>().join(", ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::result::TopLevelResult;
+
+    fn study(
+        solver: &str,
+        seed: u64,
+        value: f64,
+        labels: serde_json::Value,
+    ) -> Result<StudyRecord> {
+        let json = serde_json::json!({
+            "start_time": "2020-01-01T00:00:00+00:00",
+            "end_time": "2020-01-01T00:00:01+00:00",
+            "seed": seed,
+            "budget": 1,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "solver": {
+                "recipe": {"random": {}},
+                "spec": {"name": solver, "attrs": {}, "capabilities": []}
+            },
+            "problem": {
+                "recipe": {"learning_curve": {
+                    "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+                }},
+                "spec": {
+                    "name": "Foo",
+                    "attrs": {},
+                    "params_domain": [{
+                        "name": "x",
+                        "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                        "distribution": "UNIFORM"
+                    }],
+                    "values_domain": [{
+                        "name": "Loss",
+                        "range": {"type": "CONTINUOUS"},
+                        "distribution": "UNIFORM"
+                    }],
+                    "steps": 1
+                }
+            },
+            "trials": [{
+                "thread_id": 0,
+                "params": [0.5],
+                "evaluations": [{
+                    "values": [value],
+                    "start_step": 0,
+                    "end_step": 1,
+                    "ask_elapsed": 0.0,
+                    "tell_elapsed": 0.0,
+                    "evaluate_elapsed": 0.0
+                }]
+            }],
+            "labels": labels
+        });
+        track!(serde_json::from_value(json).map_err(Error::from))
+    }
+
+    #[test]
+    fn group_by_works() -> TopLevelResult {
+        let studies = vec![
+            track!(study(
+                "Random",
+                0,
+                1.0,
+                serde_json::json!({"hardware": "gpu"})
+            ))?,
+            track!(study(
+                "Random",
+                1,
+                2.0,
+                serde_json::json!({"hardware": "gpu"})
+            ))?,
+            track!(study(
+                "Random",
+                0,
+                3.0,
+                serde_json::json!({"hardware": "cpu"})
+            ))?,
+            track!(study("Random", 0, 4.0, serde_json::json!({})))?,
+        ];
+        assert_eq!(studies[3].label("hardware"), StudyRecord::UNLABELED);
+
+        let opt = ReportOpt::from_iter(&["report", "--group-by", "hardware"]);
+        let reporter = Reporter::new(studies.clone(), opt);
+        let names = track!(reporter.competitors())?
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Random (hardware=(unlabeled))",
+                "Random (hardware=cpu)",
+                "Random (hardware=gpu)"
+            ]
+        );
+
+        let contests = track!(reporter.contests())?;
+        let contest = &contests[0].1;
+        assert_eq!(contest.competitors.len(), 3);
+        let gpu = contest
+            .competitors
+            .values()
+            .find(|c| c.label == Some("gpu"))
+            .unwrap();
+        assert_eq!(gpu.studies.len(), 2);
+
+        let mut buf = Vec::new();
+        track!(reporter.report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("Random (hardware=cpu)"));
+        assert!(report.contains("Random (hardware=(unlabeled))"));
+
+        // Without `--group-by`, all the studies of a solver are aggregated.
+        let reporter = Reporter::new(studies, ReportOpt::from_iter(&["report"]));
+        assert_eq!(track!(reporter.competitors())?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn report_is_deterministic() -> TopLevelResult {
+        let mut studies = Vec::new();
+        for problem in &["Foo", "Bar"] {
+            for solver in &["Zeta", "Alpha", "Mid"] {
+                for seed in 0..3 {
+                    // All the solvers tie on every problem.
+                    let mut s = track!(study(solver, seed, seed as f64, serde_json::json!({})))?;
+                    s.problem.spec.name = (*problem).to_owned();
+                    studies.push(s);
+                }
+            }
+        }
+
+        let report = |studies: Vec<StudyRecord>| -> Result<String> {
+            let reporter = Reporter::new(studies, ReportOpt::from_iter(&["report"]));
+            let mut buf = Vec::new();
+            track!(reporter.report_all(&mut buf))?;
+            Ok(String::from_utf8_lossy(&buf).into_owned())
+        };
+        let expected = track!(report(studies.clone()))?;
+        assert_eq!(track!(report(studies.clone()))?, expected);
+        studies.reverse();
+        assert_eq!(track!(report(studies))?, expected);
+
+        // Problems and solvers are ordered by their names.
+        let pos = |s: &str| expected.find(s).unwrap();
+        assert!(pos("Problem: [Bar]") < pos("Problem: [Foo]"));
+        let individual = &expected[pos("Problem: [Bar]")..pos("Problem: [Foo]")];
+        let ipos = |s: &str| individual.find(s).unwrap();
+        assert!(ipos("[Alpha]") < ipos("[Mid]") && ipos("[Mid]") < ipos("[Zeta]"));
+
+        // All the tied solvers are counted as firsts.
+        let overall = &expected[pos("## Overall Results")..pos("## Individual Results")];
+        let firsts = overall
+            .lines()
+            .filter(|l| l.starts_with("| [") && l.ends_with(" 2 |"))
+            .count();
+        assert_eq!(firsts, 3, "{}", overall);
+        Ok(())
+    }
+
+    #[test]
+    fn version_mismatch_works() -> TopLevelResult {
+        use crate::record::ProvenanceRecord;
+
+        let load = |versions: &[Option<&str>]| -> Result<Vec<StudyRecord>> {
+            let mut input = String::new();
+            for (seed, version) in versions.iter().enumerate() {
+                let mut study = track!(study("Random", seed as u64, 1.0, serde_json::json!({})))?;
+                study.provenance = version.map(|v| ProvenanceRecord {
+                    kurobako_version: v.to_owned(),
+                    core_version: "0.1.0".to_owned(),
+                    hostname: None,
+                });
+                input += &track!(serde_json::to_string(&study).map_err(Error::from))?;
+                input += "\n";
+            }
+            track!(LoadOpt::default().load(input.as_bytes()))
+        };
+        let mismatch = |studies: Vec<StudyRecord>| {
+            Reporter::new(studies, ReportOpt::from_iter(&["report"])).version_mismatch()
+        };
+
+        // Records produced by older versions don't have provenance.
+        let studies = track!(load(&[Some("0.2.0"), None]))?;
+        let provenance = studies[0].provenance.as_ref();
+        assert_eq!(
+            provenance.map(|p| p.kurobako_version.as_str()),
+            Some("0.2.0")
+        );
+        assert!(studies[1].provenance.is_none());
+        let json = track!(serde_json::to_value(&studies[1]).map_err(Error::from))?;
+        assert!(json.get("provenance").is_none());
+
+        assert_eq!(
+            mismatch(studies.clone()),
+            Some("`0.2.0 (core 0.1.0)` (1 studies), `unknown` (1 studies)".to_owned())
+        );
+        assert_eq!(mismatch(track!(load(&[None, None]))?), None);
+        assert_eq!(
+            mismatch(track!(load(&[Some("0.2.0"), Some("0.2.0")]))?),
+            None
+        );
+
+        let studies = track!(load(&[Some("0.2.0"), Some("0.3.0"), Some("0.3.0")]))?;
+        assert_eq!(
+            mismatch(studies.clone()),
+            Some("`0.2.0 (core 0.1.0)` (1 studies), `0.3.0 (core 0.1.0)` (2 studies)".to_owned())
+        );
+        let reporter = Reporter::new(studies, ReportOpt::from_iter(&["report"]));
+        let mut buf = Vec::new();
+        track!(reporter.report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("produced by different versions of kurobako"));
+        Ok(())
+    }
+
+    #[test]
+    fn strict_comparability_works() -> TopLevelResult {
+        let mut short = track!(study("Random", 2, 1.0, serde_json::json!({})))?;
+        short.trials.clear();
+        let studies = vec![
+            track!(study("Random", 0, 1.0, serde_json::json!({})))?,
+            track!(study("Random", 1, 2.0, serde_json::json!({})))?,
+            short,
+            track!(study("Other", 0, 3.0, serde_json::json!({})))?,
+        ];
+
+        let reporter = Reporter::new(studies.clone(), ReportOpt::from_iter(&["report"]));
+        let incomparables = track!(reporter.incomparables())?;
+        assert_eq!(incomparables.len(), 1);
+        assert_eq!(incomparables[0].solver.spec.name, "Random");
+        assert_eq!(
+            (incomparables[0].short_studies, incomparables[0].studies),
+            (1, 3)
+        );
+        let contests = track!(reporter.contests())?;
+        assert_eq!(contests[0].1.competitors.len(), 2);
+
+        let mut buf = Vec::new();
+        track!(reporter.report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("## Comparability Warnings"));
+        assert!(report.contains(" 1/3 |"));
+        assert!(report.contains(" 0.00 - 1.00 |"));
+
+        // The incomparable results are excluded.
+        let opt = ReportOpt::from_iter(&["report", "--strict-comparability"]);
+        let reporter = Reporter::new(studies, opt);
+        let contests = track!(reporter.contests())?;
+        let competitors = &contests[0].1.competitors;
+        assert_eq!(competitors.len(), 1);
+        assert!(competitors.values().all(|c| c.solver.spec.name == "Other"));
+        Ok(())
+    }
+
+    #[test]
+    fn param_coverage_works() -> TopLevelResult {
+        let studies = vec![
+            track!(study("Random", 0, 1.0, serde_json::json!({})))?,
+            track!(study("Random", 1, 2.0, serde_json::json!({})))?,
+        ];
+
+        let mut buf = Vec::new();
+        let reporter = Reporter::new(studies.clone(), ReportOpt::from_iter(&["report"]));
+        track!(reporter.report_all(&mut buf))?;
+        assert!(!String::from_utf8_lossy(&buf).contains("Parameter Coverage"));
+
+        let mut buf = Vec::new();
+        let opt = ReportOpt::from_iter(&["report", "--param-coverage"]);
+        track!(Reporter::new(studies, opt).report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("[Parameter Coverage](#parameter-coverage)"));
+        assert!(report.contains("| x        | ·····█···· |      10% |"));
+        Ok(())
+    }
+
+    #[test]
+    fn budget_to_target_works() -> TopLevelResult {
+        let studies = vec![
+            track!(study("Random", 0, 1.0, serde_json::json!({})))?,
+            track!(study("Random", 1, 2.0, serde_json::json!({})))?,
+        ];
+        assert_eq!(studies[0].budget_to_reach(1.5), Some(1.0));
+        assert_eq!(studies[1].budget_to_reach(1.5), None);
+
+        let mut buf = Vec::new();
+        let reporter = Reporter::new(studies.clone(), ReportOpt::from_iter(&["report"]));
+        track!(reporter.report_all(&mut buf))?;
+        assert!(!String::from_utf8_lossy(&buf).contains("Budget to Target"));
+
+        let mut buf = Vec::new();
+        let opt = ReportOpt::from_iter(&[
+            "report", "--target", "0.5", "--target", "1.5", "--target", "2.5",
+        ]);
+        track!(Reporter::new(studies, opt).report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("Budget to Target (2.5)"));
+        assert!(report.contains(
+            "|             1.0 +- 0.0 |       1.0 +- 0.0 (1/2) |                      - |"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn per_study_table_works() -> TopLevelResult {
+        let mut studies = Vec::new();
+        for (seed, value) in [3.0, 1.0, 5.0, 2.0, 4.0].iter().enumerate() {
+            let mut s = track!(study("Random", seed as u64, *value, serde_json::json!({})))?;
+            if seed == 1 {
+                s.termination = Some(Termination::BudgetConsumed);
+            }
+            studies.push(s);
+        }
+
+        let csv = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        let opt = ReportOpt::from_iter([
+            "report",
+            "--per-study-table",
+            "--per-study-rows",
+            "3",
+            "--per-study-csv",
+            csv.path().to_str().unwrap(),
+        ]);
+        let reporter = Reporter::new(studies, opt);
+        let mut buf = Vec::new();
+        track!(reporter.report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        let section = &report[report.find("## Per-Study Results").unwrap()..];
+        let rows = section
+            .lines()
+            .filter(|l| l.starts_with("| [Random]"))
+            .map(|l| {
+                l.split('|')
+                    .skip(2)
+                    .take(3)
+                    .map(|c| c.trim())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                vec!["1", "1.000000", "0.000"],
+                vec!["3", "2.000000", "0.000"],
+                vec!["...", "(2 studies omitted)", ""],
+                vec!["2", "5.000000", "0.000"],
+            ]
+        );
+        assert!(section.contains("budget-consumed"));
+
+        // The CSV has all the rows.
+        track!(reporter.export_per_study_results())?;
+        let csv = track!(std::fs::read_to_string(csv.path()).map_err(Error::from))?;
+        let seeds = csv
+            .lines()
+            .skip(1)
+            .map(|l| l.split(',').nth(2).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(seeds, ["1", "3", "0", "4", "2"]);
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with(",1,1,budget-consumed"));
+        Ok(())
+    }
+
+    #[test]
+    fn expected_running_time_works() -> TopLevelResult {
+        let studies = vec![
+            track!(study("Random", 0, 1.0, serde_json::json!({})))?,
+            track!(study("Random", 1, 2.0, serde_json::json!({})))?,
+        ];
+
+        let mut buf = Vec::new();
+        let reporter = Reporter::new(studies.clone(), ReportOpt::from_iter(&["report"]));
+        track!(reporter.report_all(&mut buf))?;
+        assert!(!String::from_utf8_lossy(&buf).contains("Expected Running Time"));
+
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let csv = dir.path().join("ert.csv");
+        let opt = ReportOpt::from_iter(&[
+            "report",
+            "--target",
+            "0.5",
+            "--target",
+            "1.5",
+            "--ert-csv",
+            csv.to_str().unwrap(),
+        ]);
+        let reporter = Reporter::new(studies, opt);
+        let mut buf = Vec::new();
+        track!(reporter.report_all(&mut buf))?;
+        let report = String::from_utf8_lossy(&buf);
+        assert!(report.contains("[Expected Running Time](#expected-running-time)"));
+        assert!(report.contains("|    1.5 |     1/2 | 2.0 |"));
+        assert!(report.contains("|    0.5 |     0/2 |   - |"));
+
+        track!(reporter.export_expected_running_times())?;
+        let csv = track!(std::fs::read_to_string(&csv).map_err(Error::from))?;
+        assert_eq!(
+            csv,
+            "problem,solver,target,runs,successes,success_rate,ert\n\
+             Foo,Random,1.5,2,1,0.5,2\n\
+             Foo,Random,0.5,2,0,0,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn normalization_works() -> TopLevelResult {
+        let mut studies = Vec::new();
+        for (problem, values) in &[("Foo", [1.0, 3.0]), ("Bar", [10.0, 20.0])] {
+            for (seed, &value) in values.iter().enumerate() {
+                let mut s = track!(study("Random", seed as u64, value, serde_json::json!({})))?;
+                s.problem.spec.name = (*problem).to_owned();
+                studies.push(s);
+            }
+        }
+        let report = |args: &[&str]| -> Result<String> {
+            let opt = ReportOpt::from_iter(std::iter::once(&"report").chain(args));
+            let reporter = Reporter::new(studies.clone(), opt);
+            let mut buf = Vec::new();
+            track!(reporter.report_all(&mut buf))?;
+            track!(reporter.export_normalizations())?;
+            Ok(String::from_utf8_lossy(&buf).into_owned())
+        };
+        let read_json = |path: &std::path::Path| -> Result<serde_json::Value> {
+            let file = track!(File::open(path).map_err(Error::from))?;
+            track!(serde_json::from_reader(file).map_err(Error::from))
+        };
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let exported = dir.path().join("exported.json");
+        let exported = exported.to_str().unwrap();
+
+        assert!(!track!(report(&[]))?.contains("Normalized"));
+
+        // Local normalization.
+        let result = track!(report(&["--normalize", "--export-normalization", exported]))?;
+        assert!(result.contains("Normalized Best (avg +- sd)"));
+        assert!(result.contains(" 0.500 +- 0.500 |"));
+        assert!(result.contains("|                 0.500 |"));
+        assert!(!result.contains("**Warning**"));
+        assert_eq!(
+            track!(read_json(exported.as_ref()))?,
+            serde_json::json!({
+                "Bar": {"best": 10.0, "worst": 20.0},
+                "Foo": {"best": 1.0, "worst": 3.0}
+            })
+        );
+
+        // `Foo` reuses the given constants, `Bar` falls back to the local normalization,
+        // and `Baz` (not in this report) is ignored but exported again.
+        let input = dir.path().join("input.json");
+        let mut file = track!(File::create(&input).map_err(Error::from))?;
+        track_write!(
+            file,
+            r#"{{"Foo": {{"best": 0.0, "worst": 4.0}}, "Baz": {{"best": 0.0, "worst": 1.0}}}}"#
+        )?;
+        let result = track!(report(&[
+            "--normalization-file",
+            input.to_str().unwrap(),
+            "--export-normalization",
+            exported
+        ]))?;
+        assert!(result.contains(" 0.500 +- 0.250 |"));
+        assert!(result.contains(
+            "**Warning**: The following problems are not in the normalization file \
+             and are normalized by the local results: `Bar`"
+        ));
+        assert_eq!(
+            track!(read_json(exported.as_ref()))?,
+            serde_json::json!({
+                "Bar": {"best": 10.0, "worst": 20.0},
+                "Baz": {"best": 0.0, "worst": 1.0},
+                "Foo": {"best": 0.0, "worst": 4.0}
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn low_memory_report_is_the_same() -> TopLevelResult {
+        use crate::runner::StudyRunner;
+        use crate::study::StudyRecipe;
+
+        // Studies with pruned trials (ASHA) on a problem that has multiple steps.
+        let mut json = String::new();
+        let solvers = [
+            serde_json::json!({"random": {}}),
+            serde_json::json!({"asha": {
+                "min_step_rate": 0.1,
+                "reduction_factor": 2,
+                "without_checkpoint": false,
+                "base_solver": {"random": {}}
+            }}),
+        ];
+        for solver in &solvers {
+            for seed in 0..3 {
+                let recipe = serde_json::json!({
+                    "solver": solver,
+                    "problem": {"learning_curve": {
+                        "dim": 2, "curve": "pow", "noise": 0.1, "crossing_probability": 0.2, "steps": 10
+                    }},
+                    "budget": 20,
+                    "concurrency": 2,
+                    "scheduling": "RANDOM",
+                    "seed": seed
+                });
+                let recipe: StudyRecipe =
+                    track!(serde_json::from_value(recipe).map_err(Error::from))?;
+                let study = track!(StudyRunner::new(&recipe).and_then(|r| r.run()))?;
+                json += &track!(serde_json::to_string(&study).map_err(Error::from))?;
+                json.push('\n');
+            }
+        }
+
+        let report = |args: &[&str]| -> Result<(String, Vec<StudyRecord>)> {
+            let opt = ReportOpt::from_iter(args);
+            let studies = track!(opt.load.load(json.as_bytes()))?;
+            let reporter = Reporter::new(studies.clone(), opt);
+            let mut buf = Vec::new();
+            track!(reporter.report_all(&mut buf))?;
+            Ok((String::from_utf8_lossy(&buf).into_owned(), studies))
+        };
+        let args = [
+            "report",
+            "--per-study-table",
+            "--metrics",
+            "best-value",
+            "auc",
+        ];
+        let (expected, full) = track!(report(&args))?;
+        let (actual, compacted) = track!(report(&[&args[..], &["--low-memory"]].concat()))?;
+        assert_eq!(actual, expected);
+
+        assert!(
+            compacted.iter().map(|s| s.trials.len()).sum::<usize>()
+                < full.iter().map(|s| s.trials.len()).sum::<usize>()
+        );
+        for (c, f) in compacted.iter().zip(full.iter()) {
+            assert_eq!(c.trial_count(), f.trial_count());
+            assert_eq!(c.compute_budget_accounting(), f.compute_budget_accounting());
+        }
+
+        // Sections that need all the trials are refused.
+        let e = track_assert_some!(
+            report(&["report", "--low-memory", "--param-coverage"]).err(),
+            ErrorKind::Bug
+        );
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        Ok(())
+    }
 }
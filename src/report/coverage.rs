@@ -0,0 +1,208 @@
+use kurobako_core::domain::{Distribution, Range, Variable};
+
+const BINS: usize = 10;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const EMPTY_BIN: char = '·';
+
+/// Summary of the values of a variable asked by a solver.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coverage {
+    /// Counts of the values in the bins over the range of a numerical variable.
+    Bins(Vec<u64>),
+
+    /// Counts of the values of a categorical (or ordinal) variable.
+    Choices(Vec<(String, u64)>),
+}
+impl Coverage {
+    /// Aggregates the given values of `var`.
+    ///
+    /// Non-finite values (e.g., parameters of inactive conditional variables) are ignored.
+    pub fn new(var: &Variable, values: &[f64]) -> Self {
+        let values = values
+            .iter()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect::<Vec<_>>();
+        match var.range() {
+            Range::Categorical { choices, .. } => Self::choices(choices.iter().cloned(), &values),
+            Range::Ordinal { values: xs } => {
+                Self::choices(xs.iter().map(|x| x.to_string()), &values)
+            }
+            Range::Discrete {
+                low,
+                high,
+                step,
+                inclusive_high,
+            } if levels(*low, *high, *step, *inclusive_high) <= BINS as i64 => {
+                // Each bin corresponds to a possible value.
+                let n = levels(*low, *high, *step, *inclusive_high) as usize;
+                let mut counts = vec![0; n];
+                for v in values {
+                    let i = ((v - *low as f64) / *step as f64).round();
+                    counts[(i.max(0.0) as usize).min(n - 1)] += 1;
+                }
+                Self::Bins(counts)
+            }
+            range => {
+                let (mut low, mut high) = (range.low(), range.high());
+                if !low.is_finite() {
+                    low = values.iter().copied().fold(f64::INFINITY, f64::min);
+                }
+                if !high.is_finite() {
+                    high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                }
+                let log = var.distribution() == Distribution::LogUniform && low > 0.0;
+                let scale = |x: f64| if log { x.ln() } else { x };
+                let (low, high) = (scale(low), scale(high));
+
+                let mut counts = vec![0; BINS];
+                for v in values {
+                    let i = if high > low {
+                        ((scale(v) - low) / (high - low) * BINS as f64).floor()
+                    } else {
+                        0.0
+                    };
+                    counts[(i.max(0.0) as usize).min(BINS - 1)] += 1;
+                }
+                Self::Bins(counts)
+            }
+        }
+    }
+
+    fn choices(choices: impl Iterator<Item = String>, values: &[f64]) -> Self {
+        let mut choices = choices.map(|c| (c, 0)).collect::<Vec<_>>();
+        for &v in values {
+            if let Some(c) = choices.get_mut(v as usize) {
+                c.1 += 1;
+            }
+        }
+        Self::Choices(choices)
+    }
+
+    /// Returns the fraction of the bins (or choices) that have at least one value.
+    pub fn fraction(&self) -> f64 {
+        let counts = match self {
+            Self::Bins(counts) => counts.clone(),
+            Self::Choices(choices) => choices.iter().map(|c| c.1).collect(),
+        };
+        if counts.is_empty() {
+            return 0.0;
+        }
+        counts.iter().filter(|&&n| n > 0).count() as f64 / counts.len() as f64
+    }
+
+    /// Returns a compact representation of the counts.
+    ///
+    /// Bins are rendered as a sparkline (empty bins are rendered as `·`),
+    /// and choices are rendered as a comma-separated list of `choice: count`.
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Bins(counts) => {
+                let max = counts.iter().copied().max().unwrap_or(0);
+                counts
+                    .iter()
+                    .map(|&n| {
+                        if n == 0 {
+                            EMPTY_BIN
+                        } else {
+                            let level = (n as f64 / max as f64 * BARS.len() as f64).ceil();
+                            BARS[level as usize - 1]
+                        }
+                    })
+                    .collect()
+            }
+            Self::Choices(choices) => choices
+                .iter()
+                .map(|(c, n)| format!("{}: {}", c, n))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+/// Returns the number of the possible values of a discrete range.
+fn levels(low: i64, high: i64, step: i64, inclusive_high: bool) -> i64 {
+    if inclusive_high {
+        (high - low) / step + 1
+    } else {
+        (high - low - 1) / step + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::Result;
+    use trackable::result::TopLevelResult;
+
+    fn coverage(var: kurobako_core::domain::VariableBuilder, values: &[f64]) -> Result<Coverage> {
+        let var = track!(var.finish())?;
+        Ok(Coverage::new(&var, values))
+    }
+
+    #[test]
+    fn continuous_coverage_works() -> TopLevelResult {
+        let values = (0..100).map(|i| i as f64 / 100.0).collect::<Vec<_>>();
+        let c = track!(coverage(var("x").continuous(0.0, 1.0), &values))?;
+        assert_eq!(c, Coverage::Bins(vec![10; 10]));
+        assert_eq!(c.summary(), "██████████");
+        assert_eq!(c.fraction(), 1.0);
+
+        // A solver that ignores the variable.
+        let c = track!(coverage(var("x").continuous(0.0, 10.0), &[3.5; 20]))?;
+        assert_eq!(c.summary(), "···█······");
+        assert_eq!(c.fraction(), 0.1);
+
+        let mut values = vec![0.15; 8];
+        values.extend(&[0.05, f64::NAN]);
+        let c = track!(coverage(var("x").continuous(0.0, 1.0), &values))?;
+        assert_eq!(c.summary(), "▁█········");
+        assert_eq!(c.fraction(), 0.2);
+
+        // The upper bound falls into the last bin.
+        let c = track!(coverage(var("x").continuous_inclusive(0.0, 1.0), &[1.0]))?;
+        assert_eq!(c.summary(), "·········█");
+        Ok(())
+    }
+
+    #[test]
+    fn log_uniform_coverage_works() -> TopLevelResult {
+        let values = [1.0, 10.0, 100.0, 1000.0, 9999.0];
+        let c = track!(coverage(
+            var("x").continuous(1.0, 10_000.0).log_uniform(),
+            &values
+        ))?;
+        assert_eq!(c, Coverage::Bins(vec![1, 0, 1, 0, 0, 1, 0, 1, 0, 1]));
+
+        let c = track!(coverage(var("x").continuous(1.0, 10_000.0), &values))?;
+        assert_eq!(c, Coverage::Bins(vec![4, 0, 0, 0, 0, 0, 0, 0, 0, 1]));
+        Ok(())
+    }
+
+    #[test]
+    fn discrete_coverage_works() -> TopLevelResult {
+        let c = track!(coverage(var("x").discrete(0, 4), &[0.0, 1.0, 1.0, 3.0]))?;
+        assert_eq!(c, Coverage::Bins(vec![1, 2, 0, 1]));
+        assert_eq!(c.fraction(), 0.75);
+
+        let values = (0..100).map(f64::from).collect::<Vec<_>>();
+        let c = track!(coverage(var("x").discrete(0, 100), &values))?;
+        assert_eq!(c, Coverage::Bins(vec![10; 10]));
+        Ok(())
+    }
+
+    #[test]
+    fn categorical_coverage_works() -> TopLevelResult {
+        let c = track!(coverage(
+            var("x").categorical(["a", "b", "c"]),
+            &[0.0, 2.0, 2.0]
+        ))?;
+        assert_eq!(c.summary(), "a: 1, b: 0, c: 2");
+        assert!((c.fraction() - 2.0 / 3.0).abs() < 1e-9);
+
+        let c = track!(coverage(var("x").ordinal(vec![0.5, 1.5]), &[1.0]))?;
+        assert_eq!(c.summary(), "0.5: 0, 1.5: 1");
+        Ok(())
+    }
+}
@@ -1,29 +1,47 @@
 //! `kurobako run` command.
+use crate::filters::KurobakoFilterRecipe;
 use crate::problem::KurobakoProblemRecipe;
-use crate::record::{StudyRecord, StudyRecordBuilder, TrialRecordBuilder};
+use crate::record::{
+    IntermediateRecord, ProvenanceRecord, SetupRecord, StudyRecord, StudyRecordBuilder,
+    Termination, TrialRecordBuilder,
+};
 use crate::solver::KurobakoSolverRecipe;
-use crate::study::{Scheduling, StudyRecipe};
+use crate::study::{BudgetUnit, Scheduling, StudyRecipe};
 use crate::time::ElapsedSeconds;
+use flate2::write::GzEncoder;
+use flate2::Compression as GzCompression;
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
-use kurobako_core::problem::ProblemRecipe as _;
+use kurobako_core::domain::{Domain, Range, VariableBuilder};
+use kurobako_core::filter::{self, BoxFilter, FilterRecipe as _, FilteredSolver};
+use kurobako_core::json::{self, Format, JsonRecipe};
 use kurobako_core::problem::{
-    BoxEvaluator, BoxProblem, Evaluator as _, Problem as _, ProblemFactory as _, ProblemSpec,
+    BoxEvaluator, BoxProblem, BoxProblemFactory, Evaluator as _, Problem as _, ProblemFactory as _,
+    ProblemSpec,
 };
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
-use kurobako_core::solver::{BoxSolver, Solver as _, SolverFactory as _, SolverRecipe as _};
+use kurobako_core::solver::{
+    BoxSolver, BoxSolverFactory, Capability, Solver as _, SolverFactory as _, SolverSpec,
+};
 use kurobako_core::trial::Values;
-use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, TrialId};
+use kurobako_core::trial::{EvaluatedTrial, IdGen, NextTrial, Params, TrialId, TrialStatus};
+use kurobako_core::yaml;
 use kurobako_core::{Error, ErrorKind, Result};
 use rand::seq::SliceRandom;
-use std::collections::{HashMap, VecDeque};
-use std::io::Write as _;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 use trackable::error::ErrorKindExt;
+use zstd::stream::write::Encoder as ZstdEncoder;
 
 /// Options of the `kurobako run` command.
 #[derive(Debug, Clone, StructOpt)]
@@ -36,6 +54,332 @@ pub struct RunnerOpt {
     /// Disables progress bar.
     #[structopt(long, short = "q")]
     pub quiet: bool,
+
+    /// Validates the parameters proposed by solvers before evaluating them.
+    #[structopt(long, default_value = "true", parse(try_from_str))]
+    pub validate_params: bool,
+
+    /// Which evaluation results are recorded when a trial proceeds over multiple steps at once.
+    ///
+    /// `all` evaluates the trial step-by-step and records the values at every evaluable step
+    /// (i.e., the full learning curve). Note that this may bloat output files.
+    #[structopt(long, default_value = "last", possible_values = &["all", "last"])]
+    pub record_intermediate: RecordIntermediate,
+
+    /// Only checks whether the given studies are runnable (e.g., solvers have the capabilities required by problems).
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Doesn't record the hostname of the machine in the results.
+    #[structopt(long)]
+    pub no_hostname: bool,
+
+    /// Requires solvers to have the `MULTI_STEP` capability (i.e., pruning support)
+    /// to solve problems that have more than one evaluable step.
+    ///
+    /// By default, solvers lacking the capability are allowed and evaluate every trial up to the last step,
+    /// so the results of pruning and non-pruning solvers may be compared in the same report.
+    #[structopt(long)]
+    pub require_multi_step: bool,
+
+    /// Records the fingerprint of the trial sequence of each study in the results.
+    ///
+    /// Fingerprints can be compared by the `kurobako compare-fingerprints` command
+    /// to detect nondeterminism of solvers and problems.
+    #[structopt(long)]
+    pub fingerprint: bool,
+
+    /// What to do when a problem returns a value out of the range declared in its values domain.
+    ///
+    /// `error` aborts the study, `clip` clips the value to the range, and `warn` keeps the value as it is
+    /// (a warning is shown at the first occurrence in each study).
+    /// In any case, the number of the out-of-range values is recorded in the study record.
+    /// Note that NaN values are not subject to this option; a trial that returns NaN is always regarded as failed.
+    #[structopt(long, default_value = "warn", possible_values = OutOfRangePolicy::POSSIBLE_VALUES)]
+    pub on_out_of_range: OutOfRangePolicy,
+
+    /// Stops launching new studies when the given wall-clock time (in hours) is about to run out.
+    ///
+    /// A study is not launched if the rolling mean duration of the completed studies exceeds the remaining time.
+    /// The studies already running are finished, and the skipped ones are written to the `--skipped-studies` file.
+    /// With this option, the studies of the same (problem, solver) pair are executed consecutively,
+    /// so that the pairs are completed one by one rather than all being left partially done.
+    #[structopt(long)]
+    pub max_run_duration: Option<f64>,
+
+    /// File to which the recipes of the studies skipped by `--max-run-duration` are written.
+    ///
+    /// The file is newline-delimited JSONs of study recipes, so it can be passed to `kurobako run` as is
+    /// to complete the benchmark later.
+    #[structopt(long, default_value = "skipped-studies.json")]
+    pub skipped_studies: PathBuf,
+
+    /// Compresses the output by gzip or zstd.
+    ///
+    /// The output is compressed as a stream, so it is typically redirected to a `*.json.gz` or `*.json.zst` file.
+    /// The commands loading study records (e.g., `report` and `plot`) decompress such files transparently.
+    #[structopt(long, possible_values = Compression::POSSIBLE_VALUES)]
+    pub compress: Option<Compression>,
+
+    /// Format of the study recipes given via the standard input.
+    ///
+    /// A YAML input is a stream of documents (separated by `---`), each of which is a study recipe.
+    /// The results are written as JSONs regardless of this option.
+    #[structopt(long, default_value = "json", possible_values = Format::POSSIBLE_VALUES)]
+    pub format: Format,
+}
+
+impl Default for RunnerOpt {
+    /// Returns the same options as the defaults of the `kurobako run` command.
+    fn default() -> Self {
+        Self {
+            parallelism: NonZeroUsize::new(1).unwrap_or_else(|| unreachable!()),
+            quiet: false,
+            validate_params: true,
+            record_intermediate: RecordIntermediate::default(),
+            dry_run: false,
+            no_hostname: false,
+            require_multi_step: false,
+            fingerprint: false,
+            on_out_of_range: OutOfRangePolicy::default(),
+            max_run_duration: None,
+            skipped_studies: PathBuf::from("skipped-studies.json"),
+            compress: None,
+            format: Format::default(),
+        }
+    }
+}
+
+/// Policy of handling values out of the values domain of a problem.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutOfRangePolicy {
+    /// Aborts the study.
+    Error,
+
+    /// Clips the values to the range.
+    Clip,
+
+    /// Keeps the values as they are (with a warning).
+    #[default]
+    Warn,
+}
+impl OutOfRangePolicy {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["error", "clip", "warn"];
+}
+impl FromStr for OutOfRangePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "error" => Ok(Self::Error),
+            "clip" => Ok(Self::Clip),
+            "warn" => Ok(Self::Warn),
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unknown out-of-range policy: {:?}",
+                s
+            ),
+        }
+    }
+}
+impl fmt::Display for OutOfRangePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Clip => write!(f, "clip"),
+            Self::Warn => write!(f, "warn"),
+        }
+    }
+}
+
+/// Policy of recording intermediate evaluation results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordIntermediate {
+    /// Records the results of all evaluable steps.
+    All,
+
+    /// Records only the result of the last step of each evaluation.
+    #[default]
+    Last,
+}
+impl FromStr for RecordIntermediate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "all" => Ok(Self::All),
+            "last" => Ok(Self::Last),
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unknown intermediate recording policy: {:?}",
+                s
+            ),
+        }
+    }
+}
+impl fmt::Display for RecordIntermediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Last => write!(f, "last"),
+        }
+    }
+}
+
+/// Compression format of the output of `kurobako run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Gzip.
+    Gzip,
+
+    /// Zstandard.
+    Zstd,
+}
+impl Compression {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["gzip", "zstd"];
+}
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown compression: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Gzip => write!(f, "gzip"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Writer that compresses data and writes it to the inner writer.
+enum CompressWriter<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(ZstdEncoder<'static, W>),
+}
+impl<W: Write> CompressWriter<W> {
+    fn new(compression: Compression, inner: W) -> Result<Self> {
+        match compression {
+            Compression::Gzip => Ok(Self::Gzip(GzEncoder::new(inner, GzCompression::default()))),
+            Compression::Zstd => {
+                let encoder = track!(ZstdEncoder::new(inner, 0).map_err(Error::from))?;
+                Ok(Self::Zstd(encoder))
+            }
+        }
+    }
+
+    /// Writes the trailer of the compressed data and returns the inner writer.
+    fn finish(self) -> Result<W> {
+        let mut inner = match self {
+            Self::Gzip(w) => track!(w.finish().map_err(Error::from))?,
+            Self::Zstd(w) => track!(w.finish().map_err(Error::from))?,
+        };
+        track!(inner.flush().map_err(Error::from))?;
+        Ok(inner)
+    }
+}
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(w) => w.write(buf),
+            Self::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Gzip(w) => w.flush(),
+            Self::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &StudyRecord) -> Result<()> {
+    track!(serde_json::to_writer(&mut *writer, record).map_err(Error::from))?;
+    track!(writeln!(writer).map_err(Error::from))?;
+    Ok(())
+}
+
+/// Number of the latest completed studies used to estimate the duration of a study.
+const STUDY_DURATION_WINDOW: usize = 10;
+
+/// Deadline of a benchmark run (see `RunnerOpt::max_run_duration`).
+#[derive(Debug)]
+struct Deadline {
+    deadline: Instant,
+    durations: Mutex<VecDeque<Duration>>,
+    skipped: Mutex<Vec<(usize, StudyRecipe)>>,
+}
+impl Deadline {
+    fn new(hours: f64) -> Result<Self> {
+        track_assert!(
+            hours.is_finite() && hours >= 0.0,
+            ErrorKind::InvalidInput; hours
+        );
+        Ok(Self {
+            deadline: Instant::now() + Duration::from_secs_f64(hours * 3600.0),
+            durations: Mutex::new(VecDeque::new()),
+            skipped: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Returns `true` if a new study is expected to finish before the deadline.
+    fn can_launch(&self) -> bool {
+        let durations = self.durations.lock().unwrap_or_else(|e| panic!("{}", e));
+        let estimate = if durations.is_empty() {
+            Duration::default()
+        } else {
+            durations.iter().sum::<Duration>() / durations.len() as u32
+        };
+        Instant::now() + estimate < self.deadline
+    }
+
+    fn add_duration(&self, duration: Duration) {
+        let mut durations = self.durations.lock().unwrap_or_else(|e| panic!("{}", e));
+        if durations.len() == STUDY_DURATION_WINDOW {
+            durations.pop_front();
+        }
+        durations.push_back(duration);
+    }
+
+    fn skip(&self, index: usize, recipe: StudyRecipe) {
+        let mut skipped = self.skipped.lock().unwrap_or_else(|e| panic!("{}", e));
+        skipped.push((index, recipe));
+    }
+
+    fn into_skipped(self) -> Vec<StudyRecipe> {
+        let mut skipped = self
+            .skipped
+            .into_inner()
+            .unwrap_or_else(|e| panic!("{}", e));
+        skipped.sort_by_key(|(i, _)| *i);
+        skipped.into_iter().map(|(_, recipe)| recipe).collect()
+    }
+}
+
+/// Reorders the studies so that the studies of the same (problem, solver) pair are consecutive.
+///
+/// The pairs are ordered by their first appearances.
+fn group_by_cell(studies: Vec<StudyRecipe>) -> Result<Vec<StudyRecipe>> {
+    let mut cells: Vec<(String, Vec<StudyRecipe>)> = Vec::new();
+    for study in studies {
+        let key =
+            track!(serde_json::to_string(&(&study.problem, &study.solver)).map_err(Error::from))?;
+        if let Some((_, cell)) = cells.iter_mut().find(|(k, _)| *k == key) {
+            cell.push(study);
+        } else {
+            cells.push((key, vec![study]));
+        }
+    }
+    Ok(cells.into_iter().flat_map(|(_, cell)| cell).collect())
 }
 
 #[derive(Debug, Clone)]
@@ -65,45 +409,182 @@ impl Cancel {
 }
 
 /// Runner of a benchmark.
+///
+/// This is the library counterpart of the `kurobako run` command.
+/// Except for [`Runner::run`], its methods don't touch the standard input and output
+/// (progress bars are drawn to the standard error unless `RunnerOpt::quiet` is `true`).
 #[derive(Debug)]
 pub struct Runner {
-    mpb: Arc<MultiProgress>,
     opt: RunnerOpt,
-    cancel: Cancel,
 }
 impl Runner {
     /// Makes a `Runner` instance.
     pub fn new(opt: RunnerOpt) -> Self {
-        let target = if opt.quiet {
+        Self { opt }
+    }
+
+    /// Runs the studies read from the standard input and writes the results to the standard output.
+    ///
+    /// This is the entry point of the `kurobako run` command.
+    pub fn run(self) -> Result<()> {
+        let stdin = std::io::stdin();
+        let recipes = track!(read_study_recipes(stdin.lock(), self.opt.format))?;
+        if self.opt.dry_run {
+            track!(self.run_all(recipes.clone(), |_| Ok(())))?;
+            eprintln!("All {} studies are runnable", recipes.len());
+            return Ok(());
+        }
+
+        if let Some(compression) = self.opt.compress {
+            let mut writer = track!(CompressWriter::new(compression, std::io::stdout()))?;
+            track!(self.run_all(recipes, |record| track!(write_record(&mut writer, &record))))?;
+            track!(writer.finish())?;
+        } else {
+            track!(self.run_all(recipes, |record| {
+                let stdout = std::io::stdout();
+                track!(write_record(&mut stdout.lock(), &record))
+            }))?;
+        }
+        eprintln!();
+        Ok(())
+    }
+
+    /// Runs a study with the given random seed.
+    ///
+    /// The seed overrides `study.seed`. No progress bar is drawn regardless of `RunnerOpt::quiet`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kurobako::runner::{Runner, RunnerOpt};
+    /// use kurobako::study::StudyRecipe;
+    ///
+    /// // Random search on the one-dimensional Ackley function (50 trials).
+    /// let study: StudyRecipe = serde_json::from_str(
+    ///     r#"{
+    ///         "solver": {"random": {}},
+    ///         "problem": {"sigopt": {"name": "ACKLEY", "dim": 1}},
+    ///         "budget": 50,
+    ///         "concurrency": 1,
+    ///         "scheduling": "RANDOM"
+    ///     }"#,
+    /// )?;
+    /// let record = Runner::new(RunnerOpt::default()).run_study(&study, 0)?;
+    /// assert_eq!(record.seed, 0);
+    /// assert!(record.best_value().unwrap() < 5.0);
+    /// # Ok::<(), kurobako_core::Error>(())
+    /// ```
+    pub fn run_study(&self, study: &StudyRecipe, seed: u64) -> Result<StudyRecord> {
+        let mut study = study.clone();
+        study.seed = Some(seed);
+        let mpb = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let registry = new_registry();
+        let runner = track!(StudyRunner::with_mpb(&study, &self.opt, &mpb, &registry))?;
+        track!(runner.run())
+    }
+
+    /// Runs the given studies and passes the resulting records to `sink`.
+    ///
+    /// The studies are executed by `RunnerOpt::parallelism` threads, so the order of the records
+    /// may differ from that of `studies`. If `RunnerOpt::dry_run` is `true`, this only checks
+    /// whether the studies are runnable and `sink` is never called.
+    ///
+    /// The first error (from a study or `sink`) cancels the remaining studies.
+    ///
+    /// If `RunnerOpt::max_run_duration` is specified, the studies that couldn't be launched within the duration
+    /// are written to `RunnerOpt::skipped_studies`.
+    pub fn run_all<F>(&self, studies: Vec<StudyRecipe>, sink: F) -> Result<()>
+    where
+        F: FnMut(StudyRecord) -> Result<()> + Send,
+    {
+        // The factories are shared by the studies with the same problem or solver recipes.
+        let registry = new_registry();
+        if self.opt.dry_run {
+            for study in &studies {
+                track!(check_study(study, &self.opt, &registry))?;
+            }
+            return Ok(());
+        }
+
+        let (studies, deadline) = if let Some(hours) = self.opt.max_run_duration {
+            (
+                track!(group_by_cell(studies))?,
+                Some(track!(Deadline::new(hours))?),
+            )
+        } else {
+            (studies, None)
+        };
+
+        let target = if self.opt.quiet {
             ProgressDrawTarget::hidden()
         } else {
             ProgressDrawTarget::stderr_with_hz(1)
         };
         let mpb = MultiProgress::with_draw_target(target);
-        Self {
-            mpb: Arc::new(mpb),
-            opt,
-            cancel: Cancel::new(),
-        }
-    }
+        let pb = create_pb(&mpb, &studies);
+        let cancel = Cancel::new();
+        let sink = Mutex::new(sink);
 
-    /// Runs the benchmark.
-    pub fn run(mut self) -> Result<()> {
-        let recipes = track!(self.read_study_recipes())?;
-        let pb = self.create_pb(&recipes);
+        // The runner threads release the external programs cached in their thread-local storages
+        // before finishing, and the ones cached in `registry` are terminated when it's dropped.
+        // So no external program survives after returning.
+        track!(thread::scope(|scope| {
+            self.spawn_runners(
+                scope,
+                studies,
+                &mpb,
+                pb,
+                &registry,
+                &cancel,
+                &sink,
+                deadline.as_ref(),
+            );
+            mpb.join().map_err(|e| ErrorKind::Other.cause(e))
+        }))?;
 
-        self.spawn_runners(recipes, pb);
-        track!(self.mpb.join().map_err(|e| ErrorKind::Other.cause(e)))?;
-        eprintln!();
+        if let Some(e) = cancel.take() {
+            return Err(e);
+        }
+        if let Some(deadline) = deadline {
+            let skipped = deadline.into_skipped();
+            track!(self.write_skipped_studies(&skipped))?;
+        }
+        Ok(())
+    }
 
-        if let Some(e) = self.cancel.take() {
-            Err(e)
-        } else {
-            Ok(())
+    fn write_skipped_studies(&self, skipped: &[StudyRecipe]) -> Result<()> {
+        let path = &self.opt.skipped_studies;
+        let file = track!(File::create(path).map_err(Error::from); path)?;
+        let mut writer = BufWriter::new(file);
+        for recipe in skipped {
+            track!(serde_json::to_writer(&mut writer, recipe).map_err(Error::from))?;
+            track!(writeln!(writer).map_err(Error::from))?;
         }
+        track!(writer.flush().map_err(Error::from))?;
+        if !skipped.is_empty() {
+            eprintln!(
+                "Warning: {} studies were skipped due to `--max-run-duration` (written to {:?})",
+                skipped.len(),
+                path
+            );
+        }
+        Ok(())
     }
 
-    fn spawn_runners(&self, recipes: Vec<StudyRecipe>, pb: ProgressBar) {
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_runners<'scope, F>(
+        &'scope self,
+        scope: &'scope thread::Scope<'scope, '_>,
+        recipes: Vec<StudyRecipe>,
+        mpb: &'scope MultiProgress,
+        pb: ProgressBar,
+        registry: &'scope FactoryRegistry,
+        cancel: &'scope Cancel,
+        sink: &'scope Mutex<F>,
+        deadline: Option<&'scope Deadline>,
+    ) where
+        F: FnMut(StudyRecord) -> Result<()> + Send,
+    {
         pb.tick();
 
         let pb_len = recipes.len() as u64;
@@ -116,10 +597,7 @@ impl Runner {
             let pb = pb.clone();
             let recipes = Arc::clone(&recipes);
             let next_index = Arc::clone(&next_index);
-            let cancel = self.cancel.clone();
-            let opt = self.opt.clone();
-            let mpb = Arc::clone(&self.mpb);
-            thread::spawn(move || {
+            scope.spawn(move || {
                 while !cancel.is_canceled() {
                     let i = next_index.fetch_add(1, atomic::Ordering::SeqCst);
                     let recipe = {
@@ -130,17 +608,25 @@ impl Runner {
                         recipes[i].take().unwrap_or_else(|| unreachable!())
                     };
 
-                    let result = track!(StudyRunner::with_mpb(&recipe, &opt, &mpb))
-                        .and_then(|runner| track!(runner.run()));
+                    if let Some(deadline) = deadline.filter(|d| !d.can_launch()) {
+                        deadline.skip(i, recipe);
+                        pb.inc(1);
+                        if pb.position() == pb_len {
+                            pb.finish_with_message("done");
+                        }
+                        continue;
+                    }
 
-                    fn output(record: StudyRecord) -> Result<()> {
-                        let stdout = std::io::stdout();
-                        let mut stdout = stdout.lock();
-                        track!(serde_json::to_writer(&mut stdout, &record).map_err(Error::from))?;
-                        track!(writeln!(stdout).map_err(Error::from))?;
-                        Ok(())
+                    let start = Instant::now();
+                    let result = track!(StudyRunner::with_mpb(&recipe, &self.opt, mpb, registry))
+                        .and_then(|runner| track!(runner.run()));
+                    if let Some(deadline) = deadline {
+                        deadline.add_duration(start.elapsed());
                     }
-                    let result = track!(result.and_then(output));
+                    let result = track!(result.and_then(|record| {
+                        let mut sink = track!(sink.lock().map_err(Error::from))?;
+                        track!((*sink)(record))
+                    }));
                     pb.inc(1);
 
                     if let Err(e) = result {
@@ -151,26 +637,38 @@ impl Runner {
                         pb.finish_with_message("done");
                     }
                 }
+
+                #[cfg(feature = "external-process")]
+                kurobako_core::epi::clear_thread_local_factories();
             });
         }
     }
+}
 
-    fn read_study_recipes(&mut self) -> Result<Vec<StudyRecipe>> {
-        let stdin = std::io::stdin();
-        serde_json::Deserializer::from_reader(stdin.lock())
-            .into_iter()
-            .map(|recipe| track!(recipe.map_err(Error::from)))
-            .collect()
-    }
+/// Reads study recipes (JSONs or YAML documents) from the given reader.
+///
+/// File references in the recipes are resolved relative to the current directory.
+pub fn read_study_recipes<R: Read>(reader: R, format: Format) -> Result<Vec<StudyRecipe>> {
+    let recipes: Vec<JsonRecipe> = match format {
+        Format::Json => track!(json::load(reader))?,
+        Format::Yaml => track!(yaml::load(reader))?,
+    };
+    recipes
+        .into_iter()
+        .map(|recipe| {
+            let recipe = track!(json::resolve_file_refs(recipe, Path::new(".")))?;
+            track!(serde_json::from_value(recipe).map_err(Error::from))
+        })
+        .collect()
+}
 
-    fn create_pb(&self, recipes: &[StudyRecipe]) -> ProgressBar {
-        let pb = self.mpb.add(ProgressBar::new(recipes.len() as u64));
-        let template =
-            "(ALL) [{elapsed_precise}] [STUDIES {pos:>6}/{len} {percent:>3}%] [ETA {eta:>3}] {msg}";
-        let style = ProgressStyle::default_bar().template(template);
-        pb.set_style(style);
-        pb
-    }
+fn create_pb(mpb: &MultiProgress, recipes: &[StudyRecipe]) -> ProgressBar {
+    let pb = mpb.add(ProgressBar::new(recipes.len() as u64));
+    let template =
+        "(ALL) [{elapsed_precise}] [STUDIES {pos:>6}/{len} {percent:>3}%] [ETA {eta:>3}] {msg}";
+    let style = ProgressStyle::default_bar().template(template);
+    pb.set_style(style);
+    pb
 }
 
 #[derive(Debug)]
@@ -184,41 +682,71 @@ pub(crate) struct StudyRunner {
     threads: EvaluationThreads,
     evaluators: HashMap<TrialId, EvaluatorState>,
     study_steps: u64,
+    trial_budget: Option<u64>,
+    ask_cost_per_second: Option<f64>,
+    ask_cost: f64,
+    started_trials: HashSet<TrialId>,
+    budget_exhausted: bool,
+    tell_delay: u64,
+    asks: u64,
+    delayed_tells: VecDeque<(u64, EvaluatedTrial, TrialRecordBuilder)>,
     opt: RunnerOpt,
     _mpb: Option<MultiProgress>,
 }
 impl StudyRunner {
     pub fn new(study: &StudyRecipe) -> Result<Self> {
         let opt = RunnerOpt {
-            parallelism: unsafe { NonZeroUsize::new_unchecked(1) },
             quiet: true,
+            ..RunnerOpt::default()
         };
         let mpb = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
-        let mut this = track!(Self::with_mpb(study, &opt, &mpb))?;
+        let mut this = track!(Self::with_mpb(study, &opt, &mpb, &new_registry()))?;
         this._mpb = Some(mpb);
         Ok(this)
     }
 
-    fn with_mpb(study: &StudyRecipe, opt: &RunnerOpt, mpb: &MultiProgress) -> Result<Self> {
-        let registry = FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
+    fn with_mpb(
+        study: &StudyRecipe,
+        opt: &RunnerOpt,
+        mpb: &MultiProgress,
+        registry: &FactoryRegistry,
+    ) -> Result<Self> {
+        track!(check_ask_cost(study))?;
 
         let random_seed = study.seed.unwrap_or_else(rand::random);
         let rng = ArcRng::new(random_seed);
 
-        let problem_factory = track!(study.problem.create_factory(&registry))?;
-        let problem_spec = track!(problem_factory.specification())?;
-        let problem = track!(problem_factory.create_problem(rng.clone()))?;
+        let ((problem_factory, problem_spec), problem_factory_elapsed) =
+            ElapsedSeconds::try_time(|| {
+                let factory = track!(create_problem_factory(registry, &study.problem))?;
+                let spec = track!(factory.specification())?;
+                Ok((factory, spec))
+            })?;
+        let problem_rng = ArcRng::new(study.problem_seed(random_seed));
+        let (problem, create_problem_elapsed) =
+            ElapsedSeconds::try_time(|| track!(problem_factory.create_problem(problem_rng)))?;
 
-        let solver_factory = track!(study.solver.create_factory(&registry))?;
-        let solver_spec = track!(solver_factory.specification())?;
+        let (filters, filtered_problem_spec) =
+            track!(create_filters(&study.filters, &rng, &problem_spec))?;
 
-        let incapables = solver_spec
-            .capabilities
-            .incapables(&problem_spec.requirements())
-            .collect::<Vec<_>>();
-        track_assert!(incapables.is_empty(), ErrorKind::Incapable; incapables);
+        let ((solver_factory, solver_spec), solver_factory_elapsed) =
+            ElapsedSeconds::try_time(|| {
+                let factory = track!(create_solver_factory(registry, &study.solver))?;
+                let spec = track!(factory.specification())?;
+                Ok((factory, spec))
+            })?;
+        let solver_problem_spec = track!(adapt_problem_spec(&solver_spec, &filtered_problem_spec))?;
 
-        let solver = track!(solver_factory.create_solver(rng.clone(), &problem_spec))?;
+        track!(check_capabilities(&solver_problem_spec, &solver_spec, opt))?;
+
+        let (solver, create_solver_elapsed) = ElapsedSeconds::try_time(|| {
+            track!(solver_factory.create_solver(rng.split(1), &solver_problem_spec))
+        })?;
+        let solver = if filters.is_empty() {
+            solver
+        } else {
+            BoxSolver::new(FilteredSolver::new(solver, filters))
+        };
 
         let study_steps = problem_spec.steps.last() * study.budget;
         let pb = mpb.add(ProgressBar::new(study_steps));
@@ -231,8 +759,16 @@ impl StudyRunner {
 
         let mut recipe = study.clone();
         recipe.seed = Some(random_seed);
-        let study_record = StudyRecordBuilder::new(recipe, solver_spec, problem_spec.clone());
-        let threads = EvaluationThreads::new(study, rng);
+        let study_record = StudyRecordBuilder::new(recipe, solver_spec, problem_spec.clone())
+            .provenance(ProvenanceRecord::current(!opt.no_hostname))
+            .setup(SetupRecord {
+                problem_factory_elapsed,
+                solver_factory_elapsed,
+                create_problem_elapsed,
+                create_solver_elapsed,
+                ..SetupRecord::default()
+            });
+        let threads = EvaluationThreads::new(study, rng.split(2));
         Ok(Self {
             solver,
             problem,
@@ -243,6 +779,17 @@ impl StudyRunner {
             threads,
             evaluators: HashMap::new(),
             study_steps,
+            trial_budget: match study.budget_unit {
+                BudgetUnit::Steps => None,
+                BudgetUnit::Trials => Some(study.budget),
+            },
+            ask_cost_per_second: study.ask_cost_per_second,
+            ask_cost: 0.0,
+            started_trials: HashSet::new(),
+            budget_exhausted: false,
+            tell_delay: study.tell_delay,
+            asks: 0,
+            delayed_tells: VecDeque::new(),
             opt: opt.clone(),
             _mpb: None,
         })
@@ -255,6 +802,9 @@ impl StudyRunner {
 
     pub fn run_once(&mut self) -> Result<()> {
         track!(self.fill_waiting_queue())?;
+        if self.budget_exhausted {
+            return Ok(());
+        }
 
         let start_step = self.pb.position();
         let thread = track!(self.threads.next())?;
@@ -267,28 +817,55 @@ impl StudyRunner {
 
         let problem_spec = &self.problem_spec;
         let evaluators = &mut self.evaluators;
-        let ((elapsed_steps, evaluated_trial), evaluate_elapsed) =
+        let record_intermediate = self.opt.record_intermediate;
+        let on_out_of_range = self.opt.on_out_of_range;
+        let ((elapsed_steps, evaluated_trial, intermediates), evaluate_elapsed) =
             ElapsedSeconds::try_time(|| {
-                track!(thread.evaluate(asked_trial.id, next_step, problem_spec, evaluators))
+                track!(thread.evaluate(
+                    &asked_trial,
+                    next_step,
+                    problem_spec,
+                    evaluators,
+                    record_intermediate,
+                    on_out_of_range
+                ))
             })?;
+        let out_of_range_values = std::mem::take(&mut thread.out_of_range_values);
+        if out_of_range_values > 0 {
+            if on_out_of_range == OutOfRangePolicy::Warn
+                && self.study_record.out_of_range_values() == 0
+            {
+                eprintln!(
+                    "Warning: problem {:?} returned values out of its values domain \
+                     (use `--on-out-of-range` to clip them or to abort the study)",
+                    self.problem_spec.name
+                );
+            }
+            self.study_record
+                .add_out_of_range_values(out_of_range_values);
+        }
         self.pb.inc(elapsed_steps);
         let end_step = self.pb.position();
 
         if end_step <= self.study_steps {
-            let ((), tell_elapsed) =
-                ElapsedSeconds::try_time(|| track!(self.solver.tell(evaluated_trial.clone())))?;
-
-            self.study_record.add_trial(TrialRecordBuilder {
+            let (params, evaluated_params) = recorded_params(&asked_trial);
+            let record = TrialRecordBuilder {
                 id: asked_trial.id,
                 thread_id,
-                params: asked_trial.params,
-                values: evaluated_trial.values,
+                params,
+                evaluated_params,
+                fidelity: asked_trial.fidelity,
+                values: evaluated_trial.values.clone(),
+                status: evaluated_trial.status,
+                attrs: evaluated_trial.attrs.clone(),
+                intermediates,
                 start_step,
                 end_step,
                 ask_elapsed,
-                tell_elapsed,
+                tell_elapsed: ElapsedSeconds::zero(),
                 evaluate_elapsed,
-            });
+            };
+            track!(self.tell(evaluated_trial, record))?;
         }
 
         Ok(())
@@ -298,6 +875,21 @@ impl StudyRunner {
         while self.threads.has_idle_thread() {
             let (asked_trial, ask_elapsed) =
                 ElapsedSeconds::try_time(|| track!(self.solver.ask(&mut self.idg)))?;
+            self.study_record.add_ask(ask_elapsed);
+            self.charge_ask_cost(ask_elapsed);
+            self.asks += 1;
+            track!(self.deliver_delayed_tells(false))?;
+
+            if let Some(max_trials) = self.trial_budget {
+                if !self.started_trials.contains(&asked_trial.id) {
+                    if self.started_trials.len() as u64 >= max_trials {
+                        // The trial beyond the budget is discarded without being evaluated.
+                        self.budget_exhausted = true;
+                        return Ok(());
+                    }
+                    self.started_trials.insert(asked_trial.id);
+                }
+            }
 
             if let Err(e) = track!(self.init_evaluator(&asked_trial)) {
                 if *e.kind() != ErrorKind::UnevaluableParams {
@@ -307,8 +899,28 @@ impl StudyRunner {
                         id: asked_trial.id,
                         values: Values::new(vec![]),
                         current_step: 0,
+                        status: TrialStatus::Failed,
+                        attrs: BTreeMap::new(),
                     };
-                    track!(self.solver.tell(unevaluable))?
+                    let step = self.pb.position();
+                    let (params, evaluated_params) = recorded_params(&asked_trial);
+                    let record = TrialRecordBuilder {
+                        id: asked_trial.id,
+                        thread_id: 0,
+                        params,
+                        evaluated_params,
+                        fidelity: asked_trial.fidelity,
+                        values: Values::new(vec![]),
+                        status: TrialStatus::Failed,
+                        attrs: BTreeMap::new(),
+                        intermediates: Vec::new(),
+                        start_step: step,
+                        end_step: step,
+                        ask_elapsed,
+                        tell_elapsed: ElapsedSeconds::zero(),
+                        evaluate_elapsed: ElapsedSeconds::zero(),
+                    };
+                    track!(self.tell(unevaluable, record))?;
                 }
             } else if asked_trial.next_step.is_some() {
                 track!(self.threads.assign(&asked_trial, ask_elapsed))?;
@@ -319,6 +931,62 @@ impl StudyRunner {
         Ok(())
     }
 
+    /// Tells the result of an evaluation to the solver and records the trial.
+    ///
+    /// If `tell_delay` is greater than zero, the result is buffered until the solver has been asked `tell_delay` more times.
+    fn tell(&mut self, trial: EvaluatedTrial, record: TrialRecordBuilder) -> Result<()> {
+        if self.tell_delay == 0 {
+            return track!(self.deliver_tell(trial, record));
+        }
+        self.delayed_tells
+            .push_back((self.asks + self.tell_delay, trial, record));
+        Ok(())
+    }
+
+    /// Tells the buffered results whose delay has elapsed (or all of them if `flush` is `true`) in the order of their evaluations.
+    fn deliver_delayed_tells(&mut self, flush: bool) -> Result<()> {
+        while let Some((due, trial, record)) = self.delayed_tells.pop_front() {
+            if !flush && due > self.asks {
+                self.delayed_tells.push_front((due, trial, record));
+                break;
+            }
+            track!(self.deliver_tell(trial, record))?;
+        }
+        Ok(())
+    }
+
+    fn deliver_tell(
+        &mut self,
+        trial: EvaluatedTrial,
+        mut record: TrialRecordBuilder,
+    ) -> Result<()> {
+        let ((), tell_elapsed) = ElapsedSeconds::try_time(|| track!(self.solver.tell(trial)))?;
+        record.tell_elapsed = tell_elapsed;
+        self.study_record.add_trial(record);
+        Ok(())
+    }
+
+    /// Converts the time taken by an ask to steps and charges them to the budget (if `ask_cost_per_second` is specified).
+    ///
+    /// Fractions of steps are carried over to the next ask.
+    fn charge_ask_cost(&mut self, ask_elapsed: ElapsedSeconds) {
+        let rate = if let Some(rate) = self.ask_cost_per_second {
+            rate
+        } else {
+            return;
+        };
+        self.ask_cost += ask_elapsed.get().max(0.0) * rate;
+        let steps = self.ask_cost.floor();
+        self.ask_cost -= steps;
+
+        let remaining = self.study_steps.saturating_sub(self.pb.position());
+        let steps = (steps as u64).min(remaining);
+        if steps > 0 {
+            self.pb.inc(steps);
+            self.study_record.add_ask_cost_steps(steps);
+        }
+    }
+
     pub fn current_step(&self) -> u64 {
         self.pb.position()
     }
@@ -327,16 +995,21 @@ impl StudyRunner {
         self.study_steps
     }
 
+    /// Returns `true` if the budget of the study has been consumed.
+    pub fn is_finished(&self) -> bool {
+        self.budget_exhausted || self.pb.position() >= self.study_steps
+    }
+
     pub fn best_values(&self) -> Option<&Values> {
         // Note that even if there are more than one trials on the pareto front,
         // the only last one will be returned.
         self.study_record.pareto_frontier().map(|x| x.2).last()
     }
 
-    fn run(mut self) -> Result<StudyRecord> {
+    pub fn run(mut self) -> Result<StudyRecord> {
         track!(self.run_init())?;
 
-        while self.pb.position() < self.study_steps {
+        while !self.is_finished() {
             if self.pb.is_hidden() && !self.opt.quiet {
                 eprintln!("DONE: {}/{}", self.pb.position(), self.study_steps);
             }
@@ -344,12 +1017,38 @@ impl StudyRunner {
         }
 
         self.pb.finish_and_clear();
-        Ok(self.study_record.finish())
+        track!(self.deliver_delayed_tells(true))?;
+        let solver_attrs = track!(self.solver.finalize())?;
+        self.study_record.solver_attrs(solver_attrs);
+        let mut record = self.study_record.finish();
+        record.termination = Some(if self.budget_exhausted {
+            Termination::TrialLimitReached
+        } else {
+            Termination::BudgetConsumed
+        });
+        if self.opt.fingerprint {
+            record.fingerprint = Some(record.compute_fingerprint());
+        }
+        Ok(record)
     }
 
     #[allow(clippy::map_entry)]
     fn init_evaluator(&mut self, trial: &NextTrial) -> Result<()> {
+        if self.opt.validate_params {
+            track!(
+                self.problem_spec.validate_fidelity(&trial.fidelity),
+                "The solver proposed invalid fidelity values: {:?}",
+                trial
+            )?;
+        }
         if !self.evaluators.contains_key(&trial.id) {
+            if self.opt.validate_params {
+                track!(
+                    self.problem_spec.validate_params(&trial.params),
+                    "The solver proposed invalid parameters: {:?}",
+                    trial
+                )?;
+            }
             let evaluator = track!(EvaluatorState::new(&self.problem, trial))?;
             self.evaluators.insert(trial.id, evaluator);
         }
@@ -362,6 +1061,155 @@ impl StudyRunner {
     }
 }
 
+fn check_study(study: &StudyRecipe, opt: &RunnerOpt, registry: &FactoryRegistry) -> Result<()> {
+    track!(check_ask_cost(study))?;
+
+    let problem_factory = track!(create_problem_factory(registry, &study.problem))?;
+    let problem_spec = track!(problem_factory.specification())?;
+    let (_, problem_spec) = track!(create_filters(
+        &study.filters,
+        &ArcRng::new(0),
+        &problem_spec
+    ))?;
+
+    let solver_factory = track!(create_solver_factory(registry, &study.solver))?;
+    let solver_spec = track!(solver_factory.specification())?;
+    let solver_problem_spec = track!(adapt_problem_spec(&solver_spec, &problem_spec))?;
+
+    track!(check_capabilities(&solver_problem_spec, &solver_spec, opt))
+}
+
+fn new_registry() -> FactoryRegistry {
+    FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>()
+}
+
+// The top-level factories are also created via the registry, so that they are shared by the studies.
+fn create_problem_factory(
+    registry: &FactoryRegistry,
+    recipe: &KurobakoProblemRecipe,
+) -> Result<BoxProblemFactory> {
+    let json = track!(serde_json::to_value(recipe).map_err(Error::from))?;
+    track!(registry.create_problem_factory_from_json(&json))
+}
+
+fn create_solver_factory(
+    registry: &FactoryRegistry,
+    recipe: &KurobakoSolverRecipe,
+) -> Result<BoxSolverFactory> {
+    let json = track!(serde_json::to_value(recipe).map_err(Error::from))?;
+    track!(registry.create_solver_factory_from_json(&json))
+}
+
+/// Returns the parameters asked by the solver and, if a filter perturbed them, the ones actually evaluated.
+fn recorded_params(trial: &NextTrial) -> (Params, Option<Params>) {
+    match &trial.asked_params {
+        Some(asked) => (asked.clone(), Some(trial.params.clone())),
+        None => (trial.params.clone(), None),
+    }
+}
+
+fn check_ask_cost(study: &StudyRecipe) -> Result<()> {
+    if let Some(rate) = study.ask_cost_per_second {
+        track_assert!(
+            rate.is_finite() && rate >= 0.0,
+            ErrorKind::InvalidInput,
+            "Ask cost must be a non-negative finite number: {}",
+            rate
+        );
+        track_assert!(
+            study.budget_unit.is_steps(),
+            ErrorKind::InvalidInput,
+            "Ask cost can only be used with the `steps` budget unit"
+        );
+    }
+    Ok(())
+}
+
+/// Checks whether the solver has all the capabilities required by the problem.
+///
+/// `Capability::MultiStep` is only checked if `RunnerOpt::require_multi_step` is `true`.
+/// The number of the parameters of the problem is also checked against `SolverSpec::max_params`.
+fn check_capabilities(
+    problem_spec: &ProblemSpec,
+    solver_spec: &SolverSpec,
+    opt: &RunnerOpt,
+) -> Result<()> {
+    if let Some(max_params) = solver_spec.max_params {
+        let params = problem_spec.params_domain.variables().len();
+        track_assert!(
+            params <= max_params,
+            ErrorKind::Incapable,
+            "Solver {:?} supports at most {} parameters, but problem {:?} has {}",
+            solver_spec.name,
+            max_params,
+            problem_spec.name,
+            params
+        );
+    }
+
+    if opt.require_multi_step {
+        return track!(problem_spec.check_capabilities(solver_spec));
+    }
+
+    let mut solver_spec = solver_spec.clone();
+    solver_spec
+        .capabilities
+        .add_capability(Capability::MultiStep);
+    track!(problem_spec.check_capabilities(&solver_spec))
+}
+
+/// Creates the filters of a study and applies them to the problem specification.
+fn create_filters(
+    recipes: &[KurobakoFilterRecipe],
+    rng: &ArcRng,
+    problem_spec: &ProblemSpec,
+) -> Result<(Vec<BoxFilter>, ProblemSpec)> {
+    let mut filters = recipes
+        .iter()
+        .enumerate()
+        .map(|(i, r)| track!(r.create_filter(rng.split(3 + i as u64))))
+        .collect::<Result<Vec<_>>>()?;
+    let mut spec = problem_spec.clone();
+    track!(filter::filter_problem_spec(&mut filters, &mut spec))?;
+    Ok((filters, spec))
+}
+
+/// Makes the problem specification passed to the solver.
+///
+/// If the solver lacks some capabilities required by the problem,
+/// the specification is degraded to the one that the solver can handle (if possible).
+/// The parameters proposed for the degraded specification are also valid for the original one.
+fn adapt_problem_spec(solver_spec: &SolverSpec, problem_spec: &ProblemSpec) -> Result<ProblemSpec> {
+    let capabilities = &solver_spec.capabilities;
+    if capabilities.is_capable(Capability::Ordinal)
+        || !capabilities.is_capable(Capability::UniformDiscrete)
+    {
+        return Ok(problem_spec.clone());
+    }
+
+    let mut spec = problem_spec.clone();
+    let vars = problem_spec
+        .params_domain
+        .variables()
+        .iter()
+        .map(|var| {
+            let builder = VariableBuilder::from(var.clone());
+            if let Range::Ordinal { values } = var.range() {
+                builder.uniform().range(Range::Discrete {
+                    low: 0,
+                    high: values.len() as i64,
+                    step: 1,
+                    inclusive_high: false,
+                })
+            } else {
+                builder
+            }
+        })
+        .collect();
+    spec.params_domain = track!(Domain::new(vars))?;
+    Ok(spec)
+}
+
 #[derive(Debug)]
 struct EvaluationThreads {
     threads: Vec<EvaluationThread>,
@@ -418,6 +1266,7 @@ struct EvaluationThread {
     thread_id: usize,
     waitings: VecDeque<WaitingTrial>,
     elapsed_steps: u64,
+    out_of_range_values: u64,
 }
 impl EvaluationThread {
     fn new(thread_id: usize) -> Self {
@@ -425,6 +1274,7 @@ impl EvaluationThread {
             thread_id,
             waitings: VecDeque::new(),
             elapsed_steps: 0,
+            out_of_range_values: 0,
         }
     }
 
@@ -439,36 +1289,138 @@ impl EvaluationThread {
 
     fn evaluate(
         &mut self,
-        trial_id: TrialId,
+        trial: &NextTrial,
         next_step: u64,
         problem_spec: &ProblemSpec,
         evaluators: &mut HashMap<TrialId, EvaluatorState>,
-    ) -> Result<(u64, EvaluatedTrial)> {
+        record_intermediate: RecordIntermediate,
+        on_out_of_range: OutOfRangePolicy,
+    ) -> Result<(u64, EvaluatedTrial, Vec<IntermediateRecord>)> {
+        let trial_id = trial.id;
         let mut state = track_assert_some!(evaluators.remove(&trial_id), ErrorKind::Bug);
 
         let next_step = track_assert_some!(
             problem_spec.steps.iter().find(|&s| s >= next_step),
             ErrorKind::Bug
         );
-        let (current_step, values) = track!(state.evaluator.evaluate(next_step))?;
-        track_assert!(state.current_step <= current_step, ErrorKind::Bug);
-        let elapsed_steps = current_step - state.current_step;
+        let start_step = state.current_step;
+        let mut target_steps = Vec::new();
+        if record_intermediate == RecordIntermediate::All {
+            target_steps.extend(
+                problem_spec
+                    .steps
+                    .iter()
+                    .filter(|&s| start_step < s && s < next_step),
+            );
+        }
+        target_steps.push(next_step);
+
+        let mut values = Values::new(Vec::new());
+        let mut attrs = BTreeMap::new();
+        let mut intermediates = Vec::new();
+        for target_step in target_steps {
+            if target_step != next_step && target_step <= state.current_step {
+                // The evaluator has already exceeded this step.
+                continue;
+            }
+
+            let result = track!(state
+                .evaluator
+                .evaluate_with_fidelity(target_step, &trial.fidelity));
+            let (current_step, step_values) = match result {
+                Ok(x) => x,
+                Err(e) if *e.kind() == ErrorKind::UnevaluableParams => {
+                    (state.current_step, Values::new(Vec::new()))
+                }
+                Err(e) => return Err(e),
+            };
+            attrs.extend(state.evaluator.take_attrs());
+            track_assert!(state.current_step <= current_step, ErrorKind::Bug);
+            state.current_step = current_step;
+            let (step_values, out_of_range) = track!(check_values(
+                step_values,
+                &problem_spec.values_domain,
+                on_out_of_range
+            ))?;
+            self.out_of_range_values += out_of_range;
+            values = step_values;
+
+            if record_intermediate == RecordIntermediate::All {
+                intermediates.push(IntermediateRecord {
+                    current_step,
+                    values: values.clone(),
+                });
+            }
+            if values.is_empty() {
+                break;
+            }
+        }
+
+        let current_step = state.current_step;
+        let elapsed_steps = current_step - start_step;
         self.elapsed_steps += elapsed_steps;
 
-        state.current_step = current_step;
         if state.current_step < problem_spec.steps.last() && !values.is_empty() {
             evaluators.insert(trial_id, state);
         }
 
+        let status = if values.is_empty() {
+            TrialStatus::Failed
+        } else {
+            TrialStatus::Ok
+        };
         let evaluated = EvaluatedTrial {
             id: trial_id,
             values,
             current_step,
+            status,
+            attrs,
         };
-        Ok((elapsed_steps, evaluated))
+        Ok((elapsed_steps, evaluated, intermediates))
     }
 }
 
+/// Checks the values returned by a problem against its values domain.
+///
+/// Returns the values processed in accordance with the policy and the number of the out-of-range values.
+/// Values containing NaN are replaced with empty ones (i.e., the evaluation is regarded as failed).
+fn check_values(
+    values: Values,
+    domain: &Domain,
+    policy: OutOfRangePolicy,
+) -> Result<(Values, u64)> {
+    if values.iter().any(|v| v.is_nan()) {
+        return Ok((Values::new(Vec::new()), 0));
+    }
+
+    let mut out_of_range = 0;
+    let mut checked = Vec::with_capacity(values.len());
+    for (&v, var) in values.iter().zip(domain.variables()) {
+        // The upper bound is regarded as inclusive.
+        let (low, high) = (var.range().low(), var.range().high());
+        if low <= v && v <= high {
+            checked.push(v);
+            continue;
+        }
+
+        out_of_range += 1;
+        match policy {
+            OutOfRangePolicy::Error => track_panic!(
+                ErrorKind::InvalidInput,
+                "The problem returned an out-of-range value for {:?}: value={}, range=[{}, {}]",
+                var.name(),
+                v,
+                low,
+                high
+            ),
+            OutOfRangePolicy::Clip => checked.push(v.max(low).min(high)),
+            OutOfRangePolicy::Warn => checked.push(v),
+        }
+    }
+    checked.extend(values.iter().skip(checked.len()));
+    Ok((Values::new(checked), out_of_range))
+}
+
 #[derive(Debug)]
 struct WaitingTrial {
     asked_trial: NextTrial,
@@ -489,3 +1441,636 @@ impl EvaluatorState {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::problem::{Evaluator, Problem};
+    use kurobako_core::solver::Solver;
+    use trackable::result::TopLevelResult;
+
+    /// Evaluates every trial until step 2, and then continues only the even ones until the last step.
+    struct PruningSolver {
+        resumed: VecDeque<NextTrial>,
+    }
+    impl Solver for PruningSolver {
+        fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+            if let Some(trial) = self.resumed.pop_front() {
+                return Ok(trial);
+            }
+            let id = idg.generate();
+            Ok(NextTrial {
+                id,
+                params: Params::new(vec![(id.get() % 10) as f64 / 10.0; 2]),
+                next_step: Some(2),
+                fidelity: Params::new(Vec::new()),
+                asked_params: None,
+            })
+        }
+
+        fn tell(&mut self, trial: EvaluatedTrial) -> Result<()> {
+            if trial.current_step == 2 && trial.id.get().is_multiple_of(2) {
+                let v = (trial.id.get() % 10) as f64 / 10.0;
+                self.resumed.push_back(NextTrial {
+                    id: trial.id,
+                    params: Params::new(vec![v; 2]),
+                    next_step: Some(8),
+                    fidelity: Params::new(Vec::new()),
+                    asked_params: None,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    fn run_study(budget_unit: &str) -> Result<StudyRecord> {
+        let recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"learning_curve": {
+                "dim": 2, "curve": "exp", "noise": 0.0, "crossing_probability": 0.0, "steps": 8
+            }},
+            "budget": 10,
+            "budget_unit": budget_unit,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 3
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        runner.solver = BoxSolver::new(PruningSolver {
+            resumed: VecDeque::new(),
+        });
+        track!(runner.run())
+    }
+
+    fn consumed_steps(study: &StudyRecord) -> u64 {
+        study
+            .trials
+            .iter()
+            .flat_map(|t| &t.evaluations)
+            .map(|e| e.elapsed_steps())
+            .sum()
+    }
+
+    #[test]
+    fn budget_units_work() -> TopLevelResult {
+        // Pruned trials consume only the steps they used, so more trials than the budget are run.
+        let steps = track!(run_study("steps"))?;
+        assert_eq!(steps.budget_unit, BudgetUnit::Steps);
+        assert_eq!(steps.trials.len(), 16);
+        assert_eq!(consumed_steps(&steps), 80);
+        assert_eq!(steps.termination, Some(Termination::BudgetConsumed));
+
+        // Exactly `budget` trials are run regardless of how many steps they used.
+        let trials = track!(run_study("trials"))?;
+        assert_eq!(trials.budget_unit, BudgetUnit::Trials);
+        assert_eq!(trials.trials.len(), 10);
+        assert_eq!(consumed_steps(&trials), 5 * 8 + 5 * 2);
+        assert_eq!(trials.termination, Some(Termination::TrialLimitReached));
+
+        // The budget axis is measured in trials.
+        let end_steps = trials
+            .trial_end_steps()
+            .into_iter()
+            .map(|(_, step)| step)
+            .collect::<Vec<_>>();
+        assert_eq!(end_steps, (1..=10).map(|i| i * 8).collect::<Vec<_>>());
+        assert!(trials
+            .best_values()
+            .keys()
+            .all(|&step| step <= trials.study_steps()));
+        assert_eq!(trials.elapsed_times(true).keys().last(), Some(&80));
+
+        // The unit is kept in the record.
+        let json = track!(serde_json::to_string(&trials).map_err(Error::from))?;
+        assert!(json.contains(r#""budget_unit":"trials""#));
+        let json = track!(serde_json::to_string(&steps).map_err(Error::from))?;
+        assert!(!json.contains("budget_unit"));
+        Ok(())
+    }
+
+    #[test]
+    fn budget_accounting_works() -> TopLevelResult {
+        // Odd trials are pruned at the step 2 and even ones are resumed until the last step.
+        let mut study = track!(run_study("steps"))?;
+        let accounting = track_assert_some!(study.budget_accounting, ErrorKind::Bug);
+        assert_eq!(accounting.asks, 16 + 8);
+        assert_eq!(accounting.evaluated_steps, 80);
+        assert_eq!(accounting.completed_steps, 8 * 8);
+        assert_eq!(accounting.pruned_steps, 8 * 2);
+        assert_eq!(accounting.pruning_efficiency(), Some(0.8));
+
+        // Records without the breakdown are backfilled from the trials.
+        study.budget_accounting = None;
+        study.backfill();
+        assert_eq!(study.budget_accounting, Some(accounting));
+        Ok(())
+    }
+
+    #[test]
+    fn require_multi_step_works() -> TopLevelResult {
+        let study = |solver: serde_json::Value| {
+            let recipe = serde_json::json!({
+                "solver": solver,
+                "problem": {"learning_curve": {
+                    "dim": 2, "curve": "exp", "noise": 0.0, "crossing_probability": 0.0, "steps": 8
+                }},
+                "budget": 2,
+                "concurrency": 1,
+                "scheduling": "RANDOM"
+            });
+            track!(serde_json::from_value::<StudyRecipe>(recipe).map_err(Error::from))
+        };
+        let lenient = RunnerOpt::default();
+        let strict = RunnerOpt {
+            require_multi_step: true,
+            ..RunnerOpt::default()
+        };
+
+        let random = track!(study(serde_json::json!({"random": {}})))?;
+        track!(check_study(&random, &lenient, &new_registry()))?;
+        let e = check_study(&random, &strict, &new_registry())
+            .err()
+            .map(|e| *e.kind());
+        assert_eq!(e, Some(ErrorKind::Incapable));
+
+        let pruning = track!(study(
+            serde_json::json!({"random": {"ask_all_steps": true}})
+        ))?;
+        track!(check_study(&pruning, &strict, &new_registry()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn max_params_works() -> TopLevelResult {
+        use kurobako_core::domain::var;
+        use kurobako_core::problem::ProblemSpecBuilder;
+        use kurobako_core::solver::{Capabilities, SolverSpecBuilder};
+
+        let problem = track!(ProblemSpecBuilder::new("foo")
+            .param(var("x").continuous(0.0, 1.0))
+            .param(var("y").continuous(0.0, 1.0))
+            .param(var("z").continuous(0.0, 1.0))
+            .value(var("v"))
+            .finish())?;
+        let solver = SolverSpecBuilder::new("bar").capabilities(Capabilities::all());
+        let opt = RunnerOpt::default();
+
+        // No limit.
+        track!(check_capabilities(&problem, &solver.finish(), &opt))?;
+
+        let solver = SolverSpecBuilder::new("bar").capabilities(Capabilities::all());
+        track!(check_capabilities(
+            &problem,
+            &solver.max_params(3).finish(),
+            &opt
+        ))?;
+
+        let solver = SolverSpecBuilder::new("bar").capabilities(Capabilities::all());
+        let e = check_capabilities(&problem, &solver.max_params(2).finish(), &opt)
+            .err()
+            .map(|e| *e.kind());
+        assert_eq!(e, Some(ErrorKind::Incapable));
+        Ok(())
+    }
+
+    /// Takes 20 milliseconds to ask the same parameters.
+    struct SlowSolver;
+    impl Solver for SlowSolver {
+        fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+            thread::sleep(std::time::Duration::from_millis(20));
+            track!(FixedSolver.ask(idg))
+        }
+
+        fn tell(&mut self, _trial: EvaluatedTrial) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_slow_study(extra: serde_json::Value) -> Result<StudyRecord> {
+        let mut recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"bo_standard": {"function": "BRANIN"}},
+            "budget": 10,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 0
+        });
+        if let (Some(recipe), Some(extra)) = (recipe.as_object_mut(), extra.as_object()) {
+            recipe.extend(extra.clone());
+        }
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        runner.solver = BoxSolver::new(SlowSolver);
+        track!(runner.run())
+    }
+
+    #[test]
+    fn ask_cost_works() -> TopLevelResult {
+        let free = track!(run_slow_study(serde_json::json!({})))?;
+        assert_eq!(free.trials.len(), 10);
+        assert_eq!(free.ask_cost_steps, 0);
+
+        // Each ask costs at least two steps (20 ms at 100 steps per second).
+        let charged = track!(run_slow_study(
+            serde_json::json!({"ask_cost_per_second": 100.0})
+        ))?;
+        assert_eq!(charged.ask_cost_per_second, Some(100.0));
+        assert!(charged.trials.len() <= 3);
+        assert!(charged.ask_cost_steps >= 2 * charged.trials.len() as u64);
+        assert!(charged.ask_cost_steps <= charged.study_steps());
+        assert!(charged.trials[0].start_step() >= Some(2));
+        assert!(charged.consumed_budget() >= 3.0 * charged.trials.len() as f64);
+        assert_ne!(track!(charged.id())?, track!(free.id())?);
+
+        // The ask cost is not supported in the `trials` budget unit.
+        let e = run_slow_study(serde_json::json!({
+            "ask_cost_per_second": 100.0, "budget_unit": "trials"
+        }))
+        .err()
+        .map(|e| *e.kind());
+        assert_eq!(e, Some(ErrorKind::InvalidInput));
+        Ok(())
+    }
+
+    #[test]
+    fn setup_is_recorded() -> TopLevelResult {
+        let study = track!(run_slow_study(serde_json::json!({})))?;
+        let setup = track_assert_some!(study.setup.as_ref(), ErrorKind::Bug);
+        let first_ask = track_assert_some!(setup.first_ask_elapsed, ErrorKind::Bug);
+        let steady_ask = track_assert_some!(setup.steady_ask_elapsed, ErrorKind::Bug);
+        assert!(first_ask.get() >= 0.02);
+        assert!(steady_ask.get() >= 0.02);
+
+        // Records without the setup times can be loaded.
+        let mut json = track!(serde_json::to_value(&study).map_err(Error::from))?;
+        track_assert_some!(json.as_object_mut(), ErrorKind::Bug).remove("setup");
+        let study: StudyRecord = track!(serde_json::from_value(json).map_err(Error::from))?;
+        assert_eq!(study.setup, None);
+        Ok(())
+    }
+
+    #[test]
+    fn tell_delay_works() -> TopLevelResult {
+        let recipe = serde_json::json!({
+            "solver": {"nsga2": {
+                "population": 4, "tournament": 2, "crossover": 0.5, "mutation": 0.3
+            }},
+            "problem": {"zdt": {"zdt": "1"}},
+            "budget": 20,
+            "budget_unit": "trials",
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 0,
+            "tell_delay": 3
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        track!(runner.run_init())?;
+        track!(runner.run_once())?;
+        track!(runner.run_once())?;
+        track!(runner.run_once())?;
+        // The results are not told (nor recorded) until three more asks have been made.
+        assert!(runner.best_values().is_none());
+        track!(runner.run_once())?;
+        assert!(runner.best_values().is_some());
+
+        // The pending results are told at the end of the study.
+        let delayed = track!(runner.run())?;
+        assert_eq!(delayed.trials.len(), 20);
+        assert_eq!(delayed.tell_delay, 3);
+
+        let mut recipe = recipe;
+        recipe.tell_delay = 0;
+        let immediate = track!(StudyRunner::new(&recipe).and_then(|r| r.run()))?;
+        assert_eq!(immediate.trials.len(), 20);
+        assert_ne!(track!(delayed.id())?, track!(immediate.id())?);
+        Ok(())
+    }
+
+    #[test]
+    fn max_run_duration_works() -> TopLevelResult {
+        let mut recipes = Vec::new();
+        for seed in 0..2 {
+            for function in &["BRANIN", "HARTMANN3"] {
+                let recipe = serde_json::json!({
+                    "solver": {"random": {}},
+                    "problem": {"bo_standard": {"function": function}},
+                    "budget": 2,
+                    "concurrency": 1,
+                    "scheduling": "RANDOM",
+                    "seed": seed
+                });
+                recipes.push(track!(
+                    serde_json::from_value::<StudyRecipe>(recipe).map_err(Error::from)
+                )?);
+            }
+        }
+        let seeds = |recipes: &[StudyRecipe]| recipes.iter().map(|r| r.seed).collect::<Vec<_>>();
+        let grouped = track!(group_by_cell(recipes.clone()))?;
+        assert_eq!(seeds(&grouped), [Some(0), Some(1), Some(0), Some(1)]);
+
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let skipped_studies = dir.path().join("skipped.json");
+        let run = |hours: f64| -> Result<(usize, Vec<StudyRecipe>)> {
+            let runner = Runner::new(RunnerOpt {
+                quiet: true,
+                max_run_duration: Some(hours),
+                skipped_studies: skipped_studies.clone(),
+                ..RunnerOpt::default()
+            });
+            let mut completed = 0;
+            track!(runner.run_all(recipes.clone(), |_| {
+                completed += 1;
+                Ok(())
+            }))?;
+            let file = track!(File::open(&skipped_studies).map_err(Error::from))?;
+            let skipped = track!(read_study_recipes(file, Format::Json))?;
+            Ok((completed, skipped))
+        };
+
+        // No study can be launched without remaining time.
+        let (completed, skipped) = track!(run(0.0))?;
+        assert_eq!(completed, 0);
+        assert_eq!(seeds(&skipped), seeds(&grouped));
+
+        let (completed, skipped) = track!(run(1.0))?;
+        assert_eq!(completed, 4);
+        assert!(skipped.is_empty());
+
+        assert!(track!(run(-1.0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn evaluated_params_are_recorded() -> TopLevelResult {
+        let recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"bo_standard": {"function": "BRANIN"}},
+            "budget": 5,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 0,
+            "filters": [{"param_noise": {"sigma_fraction": 0.1}}]
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        runner.solver = BoxSolver::new(FilteredSolver::new(
+            FixedSolver,
+            track!(create_filters(
+                &recipe.filters,
+                &ArcRng::new(0),
+                &runner.problem_spec
+            ))?
+            .0,
+        ));
+        let study = track!(runner.run())?;
+
+        // The asked parameters are recorded as they are, and the perturbed ones are recorded separately.
+        assert_eq!(study.trials.len(), 5);
+        for trial in &study.trials {
+            assert_eq!(trial.params, Params::new(vec![1.0, 2.0]));
+            let evaluated = track_assert_some!(trial.evaluated_params.as_ref(), ErrorKind::Bug);
+            assert_ne!(*evaluated, trial.params);
+        }
+        Ok(())
+    }
+
+    /// Always asks the same parameters.
+    struct FixedSolver;
+    impl Solver for FixedSolver {
+        fn ask(&mut self, idg: &mut IdGen) -> Result<NextTrial> {
+            Ok(NextTrial {
+                id: idg.generate(),
+                params: Params::new(vec![1.0, 2.0]),
+                next_step: Some(1),
+                fidelity: Params::new(Vec::new()),
+                asked_params: None,
+            })
+        }
+
+        fn tell(&mut self, _trial: EvaluatedTrial) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_repetition(instance: &str, repetition: u64) -> Result<StudyRecord> {
+        let recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"noise": {"problem": {"bo_standard": {"function": "BRANIN"}}, "level": 1.0}},
+            "budget": 5,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 7 + repetition,
+            "instance": instance,
+            "repetition": repetition
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        runner.solver = BoxSolver::new(FixedSolver);
+        track!(runner.run())
+    }
+
+    fn values(study: &StudyRecord) -> Vec<f64> {
+        study.trials.iter().filter_map(|t| t.value(1)).collect()
+    }
+
+    #[test]
+    fn problem_instances_work() -> TopLevelResult {
+        // The repetitions evaluate the same noisy problem instance.
+        let a = track!(run_repetition("shared", 0))?;
+        let b = track!(run_repetition("shared", 1))?;
+        assert_ne!(a.seed, b.seed);
+        assert_eq!(a.problem_seed, b.problem_seed);
+        assert_eq!(values(&a).len(), 5);
+        assert_eq!(values(&a), values(&b));
+
+        // Each repetition has its own instance.
+        let c = track!(run_repetition("per-repetition", 0))?;
+        let d = track!(run_repetition("per-repetition", 1))?;
+        assert_eq!(c.problem_seed, a.problem_seed);
+        assert_ne!(c.problem_seed, d.problem_seed);
+        assert_eq!(values(&a), values(&c));
+        assert_ne!(values(&c), values(&d));
+        Ok(())
+    }
+
+    /// Returns `0.5, 2.0, NaN, -1.0, 0.5, ...` in turn regardless of the parameters.
+    struct MisbehavingProblem {
+        count: Arc<AtomicUsize>,
+    }
+    impl Problem for MisbehavingProblem {
+        type Evaluator = MisbehavingEvaluator;
+
+        fn create_evaluator(&self, _params: Params) -> Result<Self::Evaluator> {
+            Ok(MisbehavingEvaluator {
+                count: Arc::clone(&self.count),
+            })
+        }
+    }
+
+    struct MisbehavingEvaluator {
+        count: Arc<AtomicUsize>,
+    }
+    impl Evaluator for MisbehavingEvaluator {
+        fn evaluate(&mut self, _next_step: u64) -> Result<(u64, Values)> {
+            let values = [0.5, 2.0, f64::NAN, -1.0];
+            let i = self.count.fetch_add(1, atomic::Ordering::SeqCst);
+            Ok((1, Values::new(vec![values[i % values.len()]])))
+        }
+    }
+
+    fn run_misbehaving_study(policy: &str) -> Result<StudyRecord> {
+        use kurobako_core::domain::var;
+
+        let recipe = serde_json::json!({
+            "solver": {"random": {}},
+            "problem": {"bo_standard": {"function": "BRANIN"}},
+            "budget": 5,
+            "concurrency": 1,
+            "scheduling": "RANDOM",
+            "seed": 0
+        });
+        let recipe: StudyRecipe = track!(serde_json::from_value(recipe).map_err(Error::from))?;
+        let mut runner = track!(StudyRunner::new(&recipe))?;
+        runner.problem = BoxProblem::new(MisbehavingProblem {
+            count: Arc::new(AtomicUsize::new(0)),
+        });
+        runner.problem_spec.values_domain =
+            track!(Domain::new(vec![var("v").continuous(0.0, 1.0)]))?;
+        runner.opt.on_out_of_range = track!(policy.parse())?;
+        track!(runner.run())
+    }
+
+    fn trial_values(study: &StudyRecord) -> Vec<Option<f64>> {
+        study.trials.iter().map(|t| t.value(1)).collect()
+    }
+
+    #[test]
+    fn out_of_range_policies_work() -> TopLevelResult {
+        // NaN values always make the trials failed.
+        let warn = track!(run_misbehaving_study("warn"))?;
+        assert_eq!(warn.out_of_range_values, 2);
+        assert_eq!(
+            trial_values(&warn),
+            [Some(0.5), Some(2.0), None, Some(-1.0), Some(0.5)]
+        );
+
+        let clip = track!(run_misbehaving_study("clip"))?;
+        assert_eq!(clip.out_of_range_values, 2);
+        assert_eq!(
+            trial_values(&clip),
+            [Some(0.5), Some(1.0), None, Some(0.0), Some(0.5)]
+        );
+
+        let e = run_misbehaving_study("error").err().map(|e| *e.kind());
+        assert_eq!(e, Some(ErrorKind::InvalidInput));
+
+        // The count is omitted from JSON if there are no out-of-range values.
+        let json = track!(serde_json::to_value(&warn).map_err(Error::from))?;
+        assert_eq!(json["out_of_range_values"], 2);
+        let mut study = warn;
+        study.out_of_range_values = 0;
+        let json = track!(serde_json::to_value(&study).map_err(Error::from))?;
+        assert!(json.get("out_of_range_values").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn yaml_recipes_work() -> TopLevelResult {
+        let yaml = r#"
+# Random search on the Branin function.
+solver:
+  random: {}
+problem:
+  bo_standard:
+    function: BRANIN
+budget: 5
+concurrency: 1
+scheduling: RANDOM
+seed: 0
+---
+solver: {random: {}}
+problem: {bo_standard: {function: HARTMANN3}}
+budget: 5  # In trials.
+concurrency: 2
+scheduling: FAIR
+"#;
+        let json = r#"
+{"solver": {"random": {}}, "problem": {"bo_standard": {"function": "BRANIN"}}, "budget": 5, "concurrency": 1, "scheduling": "RANDOM", "seed": 0}
+{"solver": {"random": {}}, "problem": {"bo_standard": {"function": "HARTMANN3"}}, "budget": 5, "concurrency": 2, "scheduling": "FAIR"}
+"#;
+        let to_values = |recipes: Vec<StudyRecipe>| -> Result<Vec<serde_json::Value>> {
+            recipes
+                .iter()
+                .map(|r| track!(serde_json::to_value(r).map_err(Error::from)))
+                .collect()
+        };
+        let from_yaml = track!(read_study_recipes(yaml.as_bytes(), Format::Yaml))?;
+        let from_json = track!(read_study_recipes(json.as_bytes(), Format::Json))?;
+        assert_eq!(from_yaml.len(), 2);
+        assert_eq!(track!(to_values(from_yaml))?, track!(to_values(from_json))?);
+
+        // Unlike the results, malformed recipes are errors.
+        let broken = format!("{}---\nsolver: {{random: {{}}}}\n", yaml);
+        assert!(read_study_recipes(broken.as_bytes(), Format::Yaml).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_output_can_be_loaded() -> TopLevelResult {
+        let recipes = (0..2)
+            .map(|seed| {
+                let recipe = serde_json::json!({
+                    "solver": {"random": {}},
+                    "problem": {"bo_standard": {"function": "BRANIN"}},
+                    "budget": 5,
+                    "concurrency": 1,
+                    "scheduling": "RANDOM",
+                    "seed": seed
+                });
+                track!(serde_json::from_value::<StudyRecipe>(recipe).map_err(Error::from))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let summary = |study: &StudyRecord| (study.seed, trial_values(study));
+
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        for (compression, name) in &[
+            (Compression::Gzip, "studies.json.gz"),
+            (Compression::Zstd, "studies.json.zst"),
+        ] {
+            let path = dir.path().join(name);
+            let file = track!(File::create(&path).map_err(Error::from))?;
+            let mut writer = track!(CompressWriter::new(*compression, file))?;
+            let mut expected = Vec::new();
+            let runner = Runner::new(RunnerOpt {
+                quiet: true,
+                ..RunnerOpt::default()
+            });
+            track!(runner.run_all(recipes.clone(), |record| {
+                expected.push(summary(&record));
+                track!(write_record(&mut writer, &record))
+            }))?;
+            track!(writer.finish())?;
+
+            let mut bytes = track!(std::fs::read(&path).map_err(Error::from))?;
+            assert!(serde_json::from_slice::<StudyRecord>(&bytes).is_err());
+
+            let opt = crate::load::LoadOpt {
+                files: vec![path.clone()],
+                ..Default::default()
+            };
+            let studies = track!(opt.load_inputs())?;
+            assert_eq!(studies.iter().map(summary).collect::<Vec<_>>(), expected);
+
+            // A truncated file is an error rather than a silently shortened result.
+            bytes.truncate(bytes.len() / 2);
+            track!(std::fs::write(&path, bytes).map_err(Error::from))?;
+            assert!(opt.load_inputs().is_err(), "{}", compression);
+        }
+
+        assert_eq!(track!("zstd".parse::<Compression>())?, Compression::Zstd);
+        assert!("xz".parse::<Compression>().is_err());
+        Ok(())
+    }
+}
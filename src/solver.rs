@@ -1,11 +1,14 @@
 //! The solver for `kurobako`.
+#[cfg(feature = "external-process")]
 use kurobako_core::epi;
 use kurobako_core::problem::ProblemSpec;
 use kurobako_core::registry::FactoryRegistry;
 use kurobako_core::rng::ArcRng;
 use kurobako_core::solver::{BoxSolver, BoxSolverFactory, SolverFactory, SolverRecipe, SolverSpec};
 use kurobako_core::Result;
-use kurobako_solvers::{asha, nsga2, optuna, random};
+#[cfg(feature = "external-process")]
+use kurobako_solvers::optuna;
+use kurobako_solvers::{asha, epsilon, flatten, nelder_mead, nsga2, random, restart, scalarize};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
@@ -38,10 +41,17 @@ impl SolverRecipe for KurobakoSolverRecipe {
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "snake_case")]
 enum InnerRecipe {
+    #[cfg(feature = "external-process")]
     Command(epi::solver::ExternalProgramSolverRecipe),
     Random(random::RandomSolverRecipe),
     Asha(asha::AshaSolverRecipe),
     Nsga2(nsga2::Nsga2SolverRecipe),
+    NelderMead(nelder_mead::NelderMeadSolverRecipe),
+    Restart(restart::RestartSolverRecipe),
+    Epsilon(epsilon::EpsilonSolverRecipe),
+    Flatten(flatten::FlattenSolverRecipe),
+    Scalarize(scalarize::ScalarizeSolverRecipe),
+    #[cfg(feature = "external-process")]
     Optuna(optuna::OptunaSolverRecipe),
 }
 impl SolverRecipe for InnerRecipe {
@@ -50,9 +60,16 @@ impl SolverRecipe for InnerRecipe {
     fn create_factory(&self, registry: &FactoryRegistry) -> Result<Self::Factory> {
         match self {
             Self::Random(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            #[cfg(feature = "external-process")]
             Self::Optuna(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
             Self::Asha(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
             Self::Nsga2(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            Self::NelderMead(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            Self::Restart(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            Self::Epsilon(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            Self::Flatten(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            Self::Scalarize(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
+            #[cfg(feature = "external-process")]
             Self::Command(r) => track!(r.create_factory(registry)).map(BoxSolverFactory::new),
         }
     }
@@ -1,18 +1,28 @@
 //! `kurobako spec` command.
 use crate::problem::KurobakoProblemRecipe;
 use crate::solver::KurobakoSolverRecipe;
+use kurobako_core::domain::var;
 use kurobako_core::json;
-use kurobako_core::problem::{ProblemFactory as _, ProblemRecipe as _, ProblemSpec};
+use kurobako_core::problem::{
+    ProblemFactory as _, ProblemRecipe as _, ProblemSpec, ProblemSpecBuilder,
+};
 use kurobako_core::registry::FactoryRegistry;
-use kurobako_core::solver::{SolverFactory as _, SolverRecipe as _, SolverSpec};
-use kurobako_core::Result;
+use kurobako_core::rng::ArcRng;
+use kurobako_core::solver::{
+    Capabilities, IncapableError, Solver as _, SolverFactory, SolverRecipe as _, SolverSpec,
+};
+use kurobako_core::trial::IdGen;
+use kurobako_core::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
-/// Options of the `kurobako spec` command.
+/// Subcommands of the `kurobako spec` command.
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
-pub enum SpecOpt {
+#[allow(clippy::large_enum_variant)]
+pub enum SpecCommand {
     /// Show the specification of the given problem.
     Problem {
         /// Problem recipe (JSON).
@@ -25,10 +35,66 @@ pub enum SpecOpt {
         /// Solver recipe (JSON).
         #[structopt(parse(try_from_str = json::parse_json))]
         solver: KurobakoSolverRecipe,
+
+        /// Problem recipe (JSON).
+        ///
+        /// If specified, the capabilities that the solver lacks to solve the problem are also shown.
+        #[structopt(long, parse(try_from_str = json::parse_json))]
+        problem: Option<KurobakoProblemRecipe>,
+
+        /// If specified, a solver is also created and asked for a trial to check that it works.
+        ///
+        /// The solver is created for the problem given by `--problem`,
+        /// or for a dummy problem that has a continuous parameter and an objective if it's omitted.
+        /// For external solvers, this exercises the whole handshake without running a study
+        /// (the errors include the last stderr lines of the program).
+        #[structopt(long)]
+        instantiate: bool,
     },
 }
 
+/// Options of the `kurobako spec` command.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case", setting = AppSettings::ArgsNegateSubcommands)]
+pub struct SpecOpt {
+    /// Show the schemas of the given message or record formats in this format, instead of a specification.
+    ///
+    /// This is useful to implement external problems and solvers in other languages.
+    /// The schemas are only available if `kurobako` is built with the `json-schema` feature.
+    #[structopt(long, possible_values = SchemaFormat::POSSIBLE_VALUES, requires = "targets")]
+    pub format: Option<SchemaFormat>,
+
+    /// Message or record formats of which the schemas are shown (used with `--format`).
+    #[structopt(possible_values = SchemaTarget::POSSIBLE_VALUES, requires = "format")]
+    pub targets: Vec<SchemaTarget>,
+
+    /// Target problem or solver.
+    #[structopt(subcommand)]
+    pub command: Option<SpecCommand>,
+}
+
 impl SpecOpt {
+    /// Returns the specification of the given problem or solver (or the schemas of the given formats).
+    pub fn get_spec(&self) -> Result<Spec> {
+        match (self.format, &self.command) {
+            (Some(SchemaFormat::JsonSchema), None) => {
+                let schemas = self.targets.iter().map(|t| track!(t.json_schema()));
+                Ok(Spec::Schemas(track!(schemas.collect::<Result<_>>())?))
+            }
+            (None, Some(command)) => track!(command.get_spec()),
+            (Some(_), Some(_)) => track_panic!(
+                ErrorKind::InvalidInput,
+                "`--format` cannot be used with a subcommand"
+            ),
+            (None, None) => track_panic!(
+                ErrorKind::InvalidInput,
+                "Either a subcommand or `--format` must be specified"
+            ),
+        }
+    }
+}
+
+impl SpecCommand {
     /// Returns the specification of the given problem or solver.
     pub fn get_spec(&self) -> Result<Spec> {
         let registry = FactoryRegistry::new::<KurobakoProblemRecipe, KurobakoSolverRecipe>();
@@ -38,15 +104,157 @@ impl SpecOpt {
                 let problem_spec = track!(problem_factory.specification())?;
                 Ok(Spec::Problem(problem_spec))
             }
-            Self::Solver { solver } => {
+            Self::Solver {
+                solver,
+                problem,
+                instantiate,
+            } => {
                 let solver_factory = track!(solver.create_factory(&registry))?;
                 let solver_spec = track!(solver_factory.specification())?;
-                Ok(Spec::Solver(solver_spec))
+                let problem = if let Some(problem) = problem {
+                    problem
+                } else {
+                    if *instantiate {
+                        let problem_spec = track!(dummy_problem_spec())?;
+                        track!(try_solver(&solver_factory, &problem_spec))?;
+                    }
+                    return Ok(Spec::Solver(solver_spec));
+                };
+
+                let problem_factory = track!(problem.create_factory(&registry))?;
+                let problem_spec = track!(problem_factory.specification())?;
+                if *instantiate {
+                    track!(try_solver(&solver_factory, &problem_spec))?;
+                }
+                let (missing, message) = match problem_spec.check_capabilities(&solver_spec) {
+                    Ok(()) => (Capabilities::empty(), None),
+                    Err(e) => {
+                        let cause = track_assert_some!(
+                            e.concrete_cause::<IncapableError>(),
+                            ErrorKind::Bug
+                        );
+                        (cause.missing().clone(), Some(cause.to_string()))
+                    }
+                };
+                Ok(Spec::SolverForProblem {
+                    problem: problem_spec.name,
+                    solver: solver_spec,
+                    missing_capabilities: missing,
+                    message,
+                })
             }
         }
     }
 }
 
+fn try_solver<F: SolverFactory>(factory: &F, problem: &ProblemSpec) -> Result<()> {
+    let mut solver = track!(factory.create_solver(ArcRng::new(0), problem))?;
+    track!(solver.ask(&mut IdGen::new()))?;
+    Ok(())
+}
+
+fn dummy_problem_spec() -> Result<ProblemSpec> {
+    track!(ProblemSpecBuilder::new("Dummy")
+        .param(var("x").continuous(0.0, 1.0))
+        .value(var("y"))
+        .finish())
+}
+
+/// Format of the schemas shown by `kurobako spec --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+    /// [JSON Schema](https://json-schema.org/) (draft 7).
+    JsonSchema,
+}
+impl SchemaFormat {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["json-schema"];
+}
+impl FromStr for SchemaFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json-schema" => Ok(Self::JsonSchema),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown schema format: {:?}", s),
+        }
+    }
+}
+
+/// Message or record format of which `kurobako spec --format` shows the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaTarget {
+    /// Messages exchanged with external problems (`ProblemMessage`).
+    ProblemMessage,
+
+    /// Messages exchanged with external solvers (`SolverMessage`).
+    SolverMessage,
+
+    /// Problem specification (`ProblemSpec`).
+    ProblemSpec,
+
+    /// Solver specification (`SolverSpec`).
+    SolverSpec,
+
+    /// Study record (`StudyRecord`).
+    ///
+    /// The recipes in a record are arbitrary JSON values in the schema.
+    StudyRecord,
+}
+impl SchemaTarget {
+    const POSSIBLE_VALUES: &'static [&'static str] = &[
+        "problem-message",
+        "solver-message",
+        "problem-spec",
+        "solver-spec",
+        "study-record",
+    ];
+
+    /// Returns the JSON Schema of this target.
+    ///
+    /// This fails if `kurobako` is built without the `json-schema` feature.
+    #[cfg(feature = "json-schema")]
+    pub fn json_schema(self) -> Result<serde_json::Value> {
+        use crate::record::StudyRecord;
+        use kurobako_core::epi::problem::ProblemMessage;
+        use kurobako_core::epi::solver::SolverMessage;
+        use schemars::schema_for;
+
+        let schema = match self {
+            Self::ProblemMessage => schema_for!(ProblemMessage),
+            Self::SolverMessage => schema_for!(SolverMessage),
+            Self::ProblemSpec => schema_for!(ProblemSpec),
+            Self::SolverSpec => schema_for!(SolverSpec),
+            Self::StudyRecord => schema_for!(StudyRecord),
+        };
+        track!(serde_json::to_value(schema).map_err(Error::from))
+    }
+
+    /// Returns the JSON Schema of this target.
+    ///
+    /// This fails if `kurobako` is built without the `json-schema` feature.
+    #[cfg(not(feature = "json-schema"))]
+    pub fn json_schema(self) -> Result<serde_json::Value> {
+        track_panic!(
+            ErrorKind::Other,
+            "`kurobako` is built without the `json-schema` feature"
+        )
+    }
+}
+impl FromStr for SchemaTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "problem-message" => Ok(Self::ProblemMessage),
+            "solver-message" => Ok(Self::SolverMessage),
+            "problem-spec" => Ok(Self::ProblemSpec),
+            "solver-spec" => Ok(Self::SolverSpec),
+            "study-record" => Ok(Self::StudyRecord),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown schema target: {:?}", s),
+        }
+    }
+}
+
 /// Specification.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -56,4 +264,139 @@ pub enum Spec {
 
     /// Solver specification.
     Solver(SolverSpec),
+
+    /// Solver specification and the capabilities it lacks to solve a problem.
+    SolverForProblem {
+        /// Solver specification.
+        solver: SolverSpec,
+
+        /// Problem name.
+        problem: String,
+
+        /// Capabilities required by the problem but not supported by the solver.
+        missing_capabilities: Capabilities,
+
+        /// Description of the missing capabilities (e.g., which variables require them).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+
+    /// Schemas of message or record formats.
+    Schemas(Vec<serde_json::Value>),
+}
+
+#[cfg(all(test, target_os = "linux", feature = "external-process"))]
+mod tests {
+    use super::*;
+    use kurobako_core::epi::solver::ExternalProgramSolverRecipe;
+    use kurobako_core::Error;
+    use std::io::Write as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn instantiate_works() -> TopLevelResult {
+        let random = track!(json::parse_json(r#"{"random": {}}"#))?;
+        let command = SpecCommand::Solver {
+            solver: random,
+            problem: None,
+            instantiate: true,
+        };
+        match track!(command.get_spec())? {
+            Spec::Solver(spec) => assert_eq!(spec.name, "Random"),
+            spec => panic!("{:?}", spec),
+        }
+
+        // A solver that completes the handshake but crashes when it's asked.
+        let mut script = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(writeln!(
+            script,
+            "#!/bin/sh\n\
+             echo '{{\"type\": \"SOLVER_SPEC_CAST\", \"spec\": {{\"name\": \"Broken\"}}}}'\n\
+             read create\n\
+             read ask\n\
+             echo 'ModuleNotFoundError: foo' >&2\n\
+             exit 1"
+        )
+        .map_err(Error::from))?;
+        let script = script.into_temp_path();
+        {
+            use std::fs;
+            use std::os::unix::fs::PermissionsExt as _;
+            track!(
+                fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+                    .map_err(Error::from)
+            )?;
+        }
+        let broken = ExternalProgramSolverRecipe {
+            path: script.to_path_buf(),
+            args: Vec::new(),
+            timeout: Some(10),
+            shutdown_grace_period: None,
+            passthrough_stderr: false,
+            python: None,
+            venv: None,
+        };
+        let broken: KurobakoSolverRecipe = track!(serde_json::from_value(
+            serde_json::json!({ "command": broken })
+        )
+        .map_err(Error::from))?;
+
+        let command = SpecCommand::Solver {
+            solver: broken.clone(),
+            problem: None,
+            instantiate: false,
+        };
+        assert!(command.get_spec().is_ok());
+
+        let command = SpecCommand::Solver {
+            solver: broken,
+            problem: None,
+            instantiate: true,
+        };
+        let e = track_assert_some!(command.get_spec().err(), ErrorKind::Bug);
+        assert!(e.to_string().contains("ModuleNotFoundError"), "{}", e);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "json-schema"))]
+mod json_schema_tests {
+    use super::*;
+    use trackable::error::ErrorKindExt as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn format_option_works() -> TopLevelResult {
+        let opt = track!(SpecOpt::from_iter_safe(&[
+            "spec",
+            "--format",
+            "json-schema",
+            "problem-message",
+            "study-record",
+        ])
+        .map_err(|e| ErrorKind::InvalidInput.cause(e.to_string())))?;
+        match track!(opt.get_spec())? {
+            Spec::Schemas(schemas) => {
+                assert_eq!(schemas.len(), 2);
+                assert_eq!(
+                    schemas[0],
+                    track!(SchemaTarget::ProblemMessage.json_schema())?
+                );
+                assert_eq!(schemas[1], track!(SchemaTarget::StudyRecord.json_schema())?);
+            }
+            spec => panic!("{:?}", spec),
+        }
+
+        assert!(SpecOpt::from_iter_safe(&["spec", "--format", "json-schema"]).is_err());
+        assert!(SpecOpt::from_iter_safe(&["spec", "problem-message"]).is_err());
+        assert!(SpecOpt::from_iter_safe(&[
+            "spec",
+            "--format",
+            "json-schema",
+            "solver",
+            r#"{"random": {}}"#
+        ])
+        .is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,291 @@
+//! Statistics used to compare the results of solvers across problems.
+//!
+//! # References
+//!
+//! - [COCO: Performance Assessment](https://arxiv.org/abs/1605.03560)
+use crate::record::StudyRecord;
+use kurobako_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Outcome of a run (i.e., a study) with respect to a target value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    /// The run reached the target after consuming the given budget.
+    Reached(f64),
+
+    /// The run didn't reach the target until the end (the value is the total budget consumed by the run).
+    Failed(f64),
+}
+impl RunOutcome {
+    /// Makes the outcome of the given study.
+    pub fn new(study: &StudyRecord, target: f64) -> Self {
+        match study.budget_to_reach(target) {
+            Some(budget) => RunOutcome::Reached(budget),
+            None => RunOutcome::Failed(study.consumed_budget()),
+        }
+    }
+
+    /// Returns `true` if the run reached the target.
+    pub fn is_success(self) -> bool {
+        matches!(self, RunOutcome::Reached(_))
+    }
+
+    /// Returns the budget consumed by the run (until it reached the target if succeeded).
+    pub fn budget(self) -> f64 {
+        match self {
+            RunOutcome::Reached(b) | RunOutcome::Failed(b) => b,
+        }
+    }
+}
+
+/// Success rate and expected running time (ERT) of a set of runs for a target value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TargetStats {
+    /// Target value.
+    pub target: f64,
+
+    /// Number of the runs.
+    pub runs: usize,
+
+    /// Number of the runs that reached the target.
+    pub successes: usize,
+
+    /// Expected running time (`None` if no run reached the target).
+    pub ert: Option<f64>,
+}
+impl TargetStats {
+    /// Computes the statistics of the given run outcomes.
+    pub fn new(target: f64, outcomes: &[RunOutcome]) -> Self {
+        Self {
+            target,
+            runs: outcomes.len(),
+            successes: outcomes.iter().filter(|o| o.is_success()).count(),
+            ert: expected_running_time(outcomes),
+        }
+    }
+
+    /// Computes the statistics of the given studies.
+    pub fn from_studies<'a, I>(target: f64, studies: I) -> Self
+    where
+        I: IntoIterator<Item = &'a StudyRecord>,
+    {
+        let outcomes = studies
+            .into_iter()
+            .map(|s| RunOutcome::new(s, target))
+            .collect::<Vec<_>>();
+        Self::new(target, &outcomes)
+    }
+
+    /// Returns the fraction of the runs that reached the target.
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.runs as f64
+        }
+    }
+}
+
+/// Computes the expected running time (ERT) of the given runs.
+///
+/// ERT is the sum of the budgets consumed by all the runs (successful runs count the budget
+/// until they reached the target, and unsuccessful ones count their whole budget) divided by
+/// the number of the successful runs.
+/// If there are no successful runs, ERT is infinite and this returns `None`.
+pub fn expected_running_time(outcomes: &[RunOutcome]) -> Option<f64> {
+    let successes = outcomes.iter().filter(|o| o.is_success()).count();
+    if successes == 0 {
+        return None;
+    }
+    let total = outcomes.iter().map(|o| o.budget()).sum::<f64>();
+    Some(total / successes as f64)
+}
+
+/// Constants of the min-max normalization of the values of a problem.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Normalization {
+    /// Value mapped to `0.0`.
+    pub best: f64,
+
+    /// Value mapped to `1.0`.
+    pub worst: f64,
+}
+impl Normalization {
+    /// Makes the normalization whose base is the minimum and the maximum of the given values.
+    ///
+    /// Non-finite values are ignored, and `None` is returned if there are no finite values.
+    pub fn from_values<I>(values: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        let mut this: Option<Self> = None;
+        for v in values.into_iter().filter(|v| v.is_finite()) {
+            this = Some(match this {
+                None => Self { best: v, worst: v },
+                Some(n) => Self {
+                    best: n.best.min(v),
+                    worst: n.worst.max(v),
+                },
+            });
+        }
+        this
+    }
+
+    /// Normalizes the given value.
+    ///
+    /// The result can be out of `[0, 1]` if the value is out of the base range
+    /// (e.g., when the constants were computed from another benchmark result).
+    /// If `best` and `worst` are the same, values not worse than `best` are mapped to `0.0`
+    /// and the others are mapped to `1.0`.
+    pub fn normalize(&self, value: f64) -> f64 {
+        let width = self.worst - self.best;
+        if width > 0.0 {
+            (value - self.best) / width
+        } else if value <= self.best {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Normalization constants of problems (keyed by the problem names).
+///
+/// This is serialized as a JSON object like `{"Branin": {"best": 0.39, "worst": 2.5}}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Normalizations {
+    constants: BTreeMap<String, Normalization>,
+}
+impl Normalizations {
+    /// Loads the constants from the given JSON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = track!(File::open(path).map_err(Error::from); path)?;
+        track!(serde_json::from_reader(file).map_err(Error::from); path)
+    }
+
+    /// Saves the constants to the given JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = track!(File::create(path).map_err(Error::from); path)?;
+        track!(serde_json::to_writer_pretty(BufWriter::new(file), self).map_err(Error::from); path)
+    }
+
+    /// Returns the constants of the given problem.
+    pub fn get(&self, problem: &str) -> Option<Normalization> {
+        self.constants.get(problem).copied()
+    }
+
+    /// Sets the constants of the given problem.
+    pub fn insert(&mut self, problem: &str, normalization: Normalization) {
+        self.constants.insert(problem.to_owned(), normalization);
+    }
+
+    /// Returns the number of the problems.
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    /// Returns `true` if there are no problems.
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RunOutcome::{Failed, Reached};
+
+    #[test]
+    fn expected_running_time_works() {
+        // All the runs succeeded: ERT is the mean of the running times.
+        assert_eq!(
+            expected_running_time(&[Reached(10.0), Reached(20.0), Reached(30.0)]),
+            Some(20.0)
+        );
+
+        // (10 + 30 + 100 + 100) / 2 = 120
+        assert_eq!(
+            expected_running_time(&[Reached(10.0), Failed(100.0), Reached(30.0), Failed(100.0)]),
+            Some(120.0)
+        );
+
+        // (5 + 50 + 80) / 1 = 135 (unsuccessful runs may have different budgets)
+        assert_eq!(
+            expected_running_time(&[Failed(50.0), Reached(5.0), Failed(80.0)]),
+            Some(135.0)
+        );
+
+        assert_eq!(expected_running_time(&[Failed(100.0), Failed(100.0)]), None);
+        assert_eq!(expected_running_time(&[]), None);
+    }
+
+    #[test]
+    fn target_stats_works() {
+        let stats = TargetStats::new(0.5, &[Reached(10.0), Failed(100.0), Failed(100.0)]);
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.successes, 1);
+        assert_eq!(stats.ert, Some(210.0));
+        assert_eq!(stats.success_rate(), 1.0 / 3.0);
+
+        let stats = TargetStats::new(0.5, &[]);
+        assert_eq!(stats.success_rate(), 0.0);
+        assert_eq!(stats.ert, None);
+    }
+
+    #[test]
+    fn normalization_works() {
+        let n = Normalization::from_values(vec![3.0, 1.0, f64::NAN, 2.0]).unwrap();
+        assert_eq!(
+            n,
+            Normalization {
+                best: 1.0,
+                worst: 3.0
+            }
+        );
+        assert_eq!(n.normalize(1.0), 0.0);
+        assert_eq!(n.normalize(2.5), 0.75);
+        assert_eq!(n.normalize(5.0), 2.0);
+        assert_eq!(n.normalize(0.0), -0.5);
+
+        let n = Normalization::from_values(vec![1.0, 1.0]).unwrap();
+        assert_eq!(n.normalize(1.0), 0.0);
+        assert_eq!(n.normalize(0.5), 0.0);
+        assert_eq!(n.normalize(2.0), 1.0);
+
+        assert_eq!(Normalization::from_values(vec![f64::INFINITY]), None);
+        assert_eq!(Normalization::from_values(vec![]), None);
+    }
+
+    #[test]
+    fn normalizations_round_trip() -> trackable::result::TopLevelResult {
+        let mut normalizations = Normalizations::default();
+        normalizations.insert(
+            "foo",
+            Normalization {
+                best: -1.0,
+                worst: 2.0,
+            },
+        );
+        let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+        let path = dir.path().join("normalization.json");
+        track!(normalizations.save(&path))?;
+
+        let json: serde_json::Value = track!(serde_json::from_reader(track!(
+            File::open(&path).map_err(Error::from)
+        )?)
+        .map_err(Error::from))?;
+        assert_eq!(
+            json,
+            serde_json::json!({"foo": {"best": -1.0, "worst": 2.0}})
+        );
+        assert_eq!(track!(Normalizations::load(&path))?, normalizations);
+        Ok(())
+    }
+}
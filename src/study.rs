@@ -1,9 +1,12 @@
 //! Study.
+use crate::filters::KurobakoFilterRecipe;
 use crate::problem::KurobakoProblemRecipe;
 use crate::solver::KurobakoSolverRecipe;
-use kurobako_core::json;
+use kurobako_core::json::{self, JsonRecipe};
+use kurobako_core::rng::ArcRng;
 use kurobako_core::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::num::NonZeroUsize;
 use std::str::FromStr;
@@ -23,6 +26,21 @@ pub struct StudyRecipe {
     #[structopt(long, default_value = "20")]
     pub budget: u64,
 
+    /// Unit of `budget` (see `BudgetUnit`).
+    #[structopt(long, default_value = "steps", possible_values = BudgetUnit::POSSIBLE_VALUES)]
+    #[serde(default, skip_serializing_if = "BudgetUnit::is_steps")]
+    pub budget_unit: BudgetUnit,
+
+    /// Number of steps charged to the budget per second taken by the solver to ask trials (see `StudiesRecipe`).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ask_cost_per_second: Option<f64>,
+
+    /// Number of asks after which the result of an evaluation is told to the solver (see `StudiesRecipe`).
+    #[structopt(long, default_value = "0")]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub tell_delay: u64,
+
     #[structopt(long, default_value = "1")]
     pub concurrency: NonZeroUsize,
 
@@ -32,10 +50,151 @@ pub struct StudyRecipe {
     /// Random seed.
     #[structopt(long)]
     pub seed: Option<u64>,
+
+    /// Whether the repetitions of this study share the same problem instance (see `ProblemInstance`).
+    #[structopt(long, default_value = "per-repetition", possible_values = ProblemInstance::POSSIBLE_VALUES)]
+    #[serde(default, skip_serializing_if = "ProblemInstance::is_per_repetition")]
+    pub instance: ProblemInstance,
+
+    /// Index of the repetition of this study (set by `kurobako studies`).
+    ///
+    /// The seed of the first repetition is regarded as `seed - repetition`.
+    #[structopt(long, default_value = "0")]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub repetition: u64,
+
+    /// Filter recipe JSONs applied between the solver and the problem.
+    ///
+    /// The first filter is the closest to the problem.
+    #[structopt(long, parse(try_from_str = json::parse_json))]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<KurobakoFilterRecipe>,
+
+    /// Metadata labels copied verbatim into the study record.
+    ///
+    /// They don't affect the execution, but can be used to group results (see `--group-by` of `kurobako report`).
+    #[structopt(skip)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl StudyRecipe {
+    /// Returns the seed of the RNG given to the problem factory when the study seed is `seed`.
+    pub fn problem_seed(&self, seed: u64) -> u64 {
+        let seed = match self.instance {
+            ProblemInstance::Shared => seed.wrapping_sub(self.repetition),
+            ProblemInstance::PerRepetition => seed,
+        };
+        ArcRng::new(seed).split(0).seed()
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// How problem instances are made for the repetitions of a study.
+///
+/// This matters only for problems that use the given RNG to instantiate themselves
+/// (e.g., randomly generated problems or the `noise` problem).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemInstance {
+    /// All the repetitions share the same problem instance.
+    ///
+    /// The problem RNG is derived from the seed of the first repetition.
+    Shared,
+
+    /// Each repetition has its own problem instance.
+    ///
+    /// The problem RNG is derived from the seed of the repetition.
+    #[default]
+    PerRepetition,
+}
+impl ProblemInstance {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["shared", "per-repetition"];
+
+    /// Returns `true` if this is `ProblemInstance::PerRepetition`.
+    pub fn is_per_repetition(&self) -> bool {
+        *self == Self::PerRepetition
+    }
+}
+impl FromStr for ProblemInstance {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "shared" => Ok(Self::Shared),
+            "per-repetition" => Ok(Self::PerRepetition),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown problem instance: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for ProblemInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Shared => write!(f, "shared"),
+            Self::PerRepetition => write!(f, "per-repetition"),
+        }
+    }
+}
+
+/// Unit of the budget of a study.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetUnit {
+    /// The budget is measured in evaluation steps.
+    ///
+    /// A study can evaluate `budget * max_step` steps in total (where `max_step` is the last step of the problem),
+    /// and a partially evaluated (e.g., pruned) trial consumes only the steps it actually used.
+    /// So, the number of trials depends on how aggressively the solver prunes them.
+    #[default]
+    Steps,
+
+    /// The budget is measured in trials.
+    ///
+    /// A study runs `budget` trials regardless of how many steps each of them is evaluated.
+    Trials,
+}
+impl BudgetUnit {
+    /// Possible values.
+    pub const POSSIBLE_VALUES: &'static [&'static str] = &["steps", "trials"];
+
+    /// Returns `true` if this is `BudgetUnit::Steps`.
+    pub fn is_steps(&self) -> bool {
+        *self == Self::Steps
+    }
+}
+impl FromStr for BudgetUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "steps" => Ok(Self::Steps),
+            "trials" => Ok(Self::Trials),
+            _ => track_panic!(ErrorKind::InvalidInput, "Unknown budget unit: {:?}", s),
+        }
+    }
+}
+impl fmt::Display for BudgetUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Steps => write!(f, "steps"),
+            Self::Trials => write!(f, "trials"),
+        }
+    }
 }
 
 /// Logical threads scheduling policy for executing a study.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, StructOpt, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[allow(missing_docs)]
@@ -69,16 +228,19 @@ impl fmt::Display for Scheduling {
 }
 
 /// Recipe of multiple studies.
+///
+/// Solver, problem and filter recipes can contain placeholders such as `${dim}` in their string values.
+/// They are replaced with the values of the variables specified by `--var`.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 pub struct StudiesRecipe {
     /// Solver recipe JSONs.
     #[structopt(long, parse(try_from_str = json::parse_json))]
-    pub solvers: Vec<KurobakoSolverRecipe>,
+    pub solvers: Vec<JsonRecipe>,
 
     /// Problem recipe JSONs.
     #[structopt(long, parse(try_from_str = json::parse_json))]
-    pub problems: Vec<KurobakoProblemRecipe>,
+    pub problems: Vec<JsonRecipe>,
 
     /// Number of execution times of each study.
     #[structopt(long, default_value = "10")]
@@ -88,6 +250,34 @@ pub struct StudiesRecipe {
     #[structopt(long, default_value = "20")]
     pub budget: u64,
 
+    /// Unit of the budget (`steps` or `trials`).
+    ///
+    /// `steps` means that each study can evaluate `budget * max_step` steps in total,
+    /// so pruned trials consume only the steps they used.
+    /// `trials` means that each study runs exactly `budget` trials.
+    #[structopt(long, default_value = "steps", possible_values = BudgetUnit::POSSIBLE_VALUES)]
+    #[serde(default, skip_serializing_if = "BudgetUnit::is_steps")]
+    pub budget_unit: BudgetUnit,
+
+    /// Number of steps charged to the budget per second taken by the solver to ask trials.
+    ///
+    /// If specified, the time spent in asks is converted to the equivalent evaluation steps at this rate,
+    /// and they consume the budget in addition to the evaluated steps.
+    /// This penalizes slow solvers in a way that doesn't depend on the evaluation time of the problem.
+    /// It can only be used with the `steps` budget unit.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ask_cost_per_second: Option<f64>,
+
+    /// Number of the further asks made to the solver before the result of an evaluation is told to it.
+    ///
+    /// This simulates the stale feedback of asynchronous optimization systems.
+    /// The pending results are told at the end of each study.
+    /// If this is greater than zero, the studies have the `tell_delay` label (see `kurobako report --group-by`).
+    #[structopt(long, default_value = "0")]
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub tell_delay: u64,
+
     /// Concurrency of a study execution.
     #[structopt(long, default_value = "1")]
     pub concurrency: NonZeroUsize,
@@ -101,27 +291,603 @@ pub struct StudiesRecipe {
     /// Random seed.
     #[structopt(long)]
     pub seed: Option<u64>,
+
+    /// Whether the repetitions of each study share the same problem instance (`shared` or `per-repetition`).
+    ///
+    /// If `shared` is specified without `--seed`, a random seed is chosen for the studies.
+    #[structopt(long, default_value = "per-repetition", possible_values = ProblemInstance::POSSIBLE_VALUES)]
+    #[serde(default, skip_serializing_if = "ProblemInstance::is_per_repetition")]
+    pub instance: ProblemInstance,
+
+    /// Filter recipe JSONs applied between the solver and the problem of each study.
+    ///
+    /// The first filter is the closest to the problem.
+    #[structopt(long, parse(try_from_str = json::parse_json))]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<JsonRecipe>,
+
+    /// Template variables (`NAME=VALUE`) substituted for the `${NAME}` placeholders in the recipes.
+    ///
+    /// `VALUE` is parsed as JSON (or taken as a string if it isn't valid JSON).
+    /// If `VALUE` is an array (e.g., `dim=[2,8,32]`), a variant of the recipe is generated for each element,
+    /// and the variants of multiple variables are combined as a cross product.
+    #[structopt(long = "var")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vars: Vec<TemplateVar>,
+
+    /// Metadata labels (`KEY=VALUE`) attached to every generated study (e.g., `hardware=gpu`).
+    #[structopt(long = "label", parse(try_from_str = parse_label))]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<(String, String)>,
+
+    /// Budgets scaled by template variables (`FACTOR*NAME`, e.g., `20*dim`).
+    ///
+    /// If specified, `--budget` is ignored and each rule generates studies whose budget is
+    /// `FACTOR` times the value of the variable `NAME` (which must be a non-negative integer).
+    /// The generated studies are labeled with `budget_rule=FACTOR*NAME` and `NAME=VALUE`,
+    /// so they can be grouped by `--group-by` of `kurobako report` and `kurobako plot`.
+    #[structopt(long = "budget-rule")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub budget_rules: Vec<BudgetRule>,
+
+    /// If this flag is set, the values of the template variables are attached to the generated studies as labels.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub label_vars: bool,
 }
 impl StudiesRecipe {
-    /// Returns a iterator that iterates over the study recipes specified by this recipe.
-    pub fn studies(&self) -> impl Iterator<Item = StudyRecipe> {
+    /// Returns the study recipes specified by this recipe.
+    ///
+    /// The placeholders in the recipes are expanded before the recipes are deserialized.
+    pub fn studies(&self) -> Result<Vec<StudyRecipe>> {
+        let mut vars = BTreeMap::new();
+        for var in &self.vars {
+            let values = match &var.value {
+                JsonRecipe::Array(values) => values.clone(),
+                value => vec![value.clone()],
+            };
+            track_assert!(
+                !values.is_empty(),
+                ErrorKind::InvalidInput,
+                "Variable {:?} has no values",
+                var.name
+            );
+            track_assert!(
+                vars.insert(var.name.clone(), values).is_none(),
+                ErrorKind::InvalidInput,
+                "Duplicate variable: {:?}",
+                var.name
+            );
+        }
+
+        let mut names = BTreeSet::new();
+        for recipe in self
+            .solvers
+            .iter()
+            .chain(&self.problems)
+            .chain(&self.filters)
+        {
+            names.extend(track!(json::placeholders(recipe))?);
+        }
+        for rule in &self.budget_rules {
+            track_assert!(
+                vars.contains_key(&rule.var),
+                ErrorKind::InvalidInput,
+                "Undefined variable in budget rule: {:?}",
+                rule.to_string()
+            );
+            names.insert(rule.var.clone());
+        }
+        let mut assignments = vec![BTreeMap::new()];
+        for name in names {
+            let values = if let Some(values) = vars.get(&name) {
+                values
+            } else {
+                // Reported as an undefined placeholder by `json::substitute_placeholders()`.
+                continue;
+            };
+            let mut expanded = Vec::new();
+            for assignment in assignments {
+                for value in values {
+                    let mut assignment = assignment.clone();
+                    assignment.insert(name.clone(), value.clone());
+                    expanded.push(assignment);
+                }
+            }
+            assignments = expanded;
+        }
+
+        // Variants are grouped by problem so that the ordering of the generated studies is the same as
+        // when no placeholders are used. Variants that don't depend on some variables are deduplicated.
+        let mut variants: Vec<(JsonRecipe, Vec<SolverVariant>)> = Vec::new();
+        let mut seen = HashSet::new();
+        for (i, problem) in self.problems.iter().enumerate() {
+            for assignment in &assignments {
+                let problem = track!(
+                    json::substitute_placeholders(problem, assignment),
+                    "problems[{}]",
+                    i
+                )?;
+                let budgets = track!(self.budgets(assignment))?;
+                for (j, solver) in self.solvers.iter().enumerate() {
+                    let solver = track!(
+                        json::substitute_placeholders(solver, assignment),
+                        "solvers[{}]",
+                        j
+                    )?;
+                    let filters = track!(self
+                        .filters
+                        .iter()
+                        .enumerate()
+                        .map(|(k, f)| track!(
+                            json::substitute_placeholders(f, assignment),
+                            "filters[{}]",
+                            k
+                        ))
+                        .collect::<Result<Vec<_>>>())?;
+                    for (budget, labels) in &budgets {
+                        let key = serde_json::json!([problem, solver, filters, budget, labels])
+                            .to_string();
+                        if !seen.insert(key) {
+                            continue;
+                        }
+                        let variant = SolverVariant {
+                            solver: solver.clone(),
+                            filters: filters.clone(),
+                            budget: *budget,
+                            labels: labels.clone(),
+                        };
+                        if let Some(i) = variants.iter().position(|(p, _)| *p == problem) {
+                            variants[i].1.push(variant);
+                        } else {
+                            variants.push((problem.clone(), vec![variant]));
+                        }
+                    }
+                }
+            }
+        }
+
+        let base_seed = match (self.seed, self.instance) {
+            (None, ProblemInstance::Shared) => Some(u64::from(rand::random::<u32>())),
+            (seed, _) => seed,
+        };
         let mut studies = Vec::new();
-        for problem in &self.problems {
+        for (problem, solvers) in variants {
+            let problem: KurobakoProblemRecipe =
+                track!(serde_json::from_value(problem.clone()).map_err(Error::from); problem)?;
+            let solvers = solvers
+                .into_iter()
+                .map(|v| {
+                    let s: KurobakoSolverRecipe = track!(
+                        serde_json::from_value(v.solver.clone()).map_err(Error::from);
+                        v.solver
+                    )?;
+                    let filters = v
+                        .filters
+                        .into_iter()
+                        .map(|f| track!(serde_json::from_value(f.clone()).map_err(Error::from); f))
+                        .collect::<Result<Vec<KurobakoFilterRecipe>>>()?;
+                    Ok((s, filters, v.budget, v.labels))
+                })
+                .collect::<Result<Vec<_>>>()?;
             for i in 0..self.repeats {
-                for solver in &self.solvers {
-                    let seed = self.seed.map(|s| s + i as u64);
+                for (solver, filters, budget, labels) in &solvers {
+                    let seed = base_seed.map(|s| s + i as u64);
+                    let mut labels = labels.clone();
+                    if self.tell_delay > 0 {
+                        labels.insert("tell_delay".to_owned(), self.tell_delay.to_string());
+                    }
                     let study = StudyRecipe {
                         solver: solver.clone(),
                         problem: problem.clone(),
-                        budget: self.budget,
+                        budget: *budget,
+                        budget_unit: self.budget_unit,
+                        ask_cost_per_second: self.ask_cost_per_second,
+                        tell_delay: self.tell_delay,
                         concurrency: self.concurrency,
                         scheduling: self.scheduling,
                         seed,
+                        instance: self.instance,
+                        repetition: i as u64,
+                        filters: filters.clone(),
+                        labels,
                     };
                     studies.push(study);
                 }
             }
         }
-        studies.into_iter()
+        Ok(studies)
+    }
+
+    /// Returns the budgets and the labels of the studies generated for the given variable assignment.
+    fn budgets(
+        &self,
+        assignment: &BTreeMap<String, JsonRecipe>,
+    ) -> Result<Vec<(u64, BTreeMap<String, String>)>> {
+        let mut labels = BTreeMap::new();
+        if self.label_vars {
+            for (name, value) in assignment {
+                labels.insert(name.clone(), label_value(value));
+            }
+        }
+        labels.extend(self.labels.iter().cloned());
+        if self.budget_rules.is_empty() {
+            return Ok(vec![(self.budget, labels)]);
+        }
+
+        let mut budgets = Vec::new();
+        for rule in &self.budget_rules {
+            let value = track_assert_some!(
+                assignment.get(&rule.var),
+                ErrorKind::InvalidInput,
+                "Undefined variable in budget rule: {:?}",
+                rule.to_string()
+            );
+            let n = track_assert_some!(
+                value.as_u64(),
+                ErrorKind::InvalidInput,
+                "Variable {:?} of a budget rule must be a non-negative integer: {}",
+                rule.var,
+                value
+            );
+            let budget = track_assert_some!(
+                rule.factor.checked_mul(n),
+                ErrorKind::InvalidInput,
+                "Budget overflow: {}",
+                rule
+            );
+            track_assert!(
+                budget > 0,
+                ErrorKind::InvalidInput,
+                "Budget rule {} yields zero budget",
+                rule
+            );
+
+            let mut labels = labels.clone();
+            labels.insert(rule.var.clone(), label_value(value));
+            labels.insert("budget_rule".to_owned(), rule.to_string());
+            budgets.push((budget, labels));
+        }
+        Ok(budgets)
+    }
+}
+
+fn label_value(value: &JsonRecipe) -> String {
+    match value {
+        JsonRecipe::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn parse_label(s: &str) -> Result<(String, String)> {
+    let mut tokens = s.splitn(2, '=');
+    let key = tokens.next().unwrap_or_else(|| unreachable!());
+    let value = track_assert_some!(
+        tokens.next(),
+        ErrorKind::InvalidInput,
+        "Expected `KEY=VALUE`: {:?}",
+        s
+    );
+    track_assert!(
+        !key.is_empty(),
+        ErrorKind::InvalidInput,
+        "Empty label key: {:?}",
+        s
+    );
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Solver recipe, filter recipes, budget and labels of a variant of studies.
+#[derive(Debug)]
+struct SolverVariant {
+    solver: JsonRecipe,
+    filters: Vec<JsonRecipe>,
+    budget: u64,
+    labels: BTreeMap<String, String>,
+}
+
+/// Budget of studies scaled by a template variable of `StudiesRecipe` (`FACTOR*NAME`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetRule {
+    /// Factor multiplied to the variable value.
+    pub factor: u64,
+
+    /// Variable name.
+    pub var: String,
+}
+impl FromStr for BudgetRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = s.splitn(2, '*');
+        let first = tokens.next().unwrap_or_else(|| unreachable!()).trim();
+        let (factor, var) = if let Some(var) = tokens.next() {
+            let factor = track!(first.parse::<u64>().map_err(Error::from); s)?;
+            (factor, var.trim())
+        } else {
+            (1, first)
+        };
+        track_assert!(
+            !var.is_empty(),
+            ErrorKind::InvalidInput,
+            "Expected `FACTOR*NAME`: {:?}",
+            s
+        );
+        Ok(Self {
+            factor,
+            var: var.to_owned(),
+        })
+    }
+}
+impl fmt::Display for BudgetRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}*{}", self.factor, self.var)
+    }
+}
+
+/// Template variable of `StudiesRecipe`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateVar {
+    /// Variable name.
+    pub name: String,
+
+    /// Variable value (an array means multiple values).
+    pub value: JsonRecipe,
+}
+impl FromStr for TemplateVar {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = s.splitn(2, '=');
+        let name = tokens.next().unwrap_or_else(|| unreachable!());
+        let value = track_assert_some!(
+            tokens.next(),
+            ErrorKind::InvalidInput,
+            "Expected `NAME=VALUE`: {:?}",
+            s
+        );
+        track_assert!(
+            !name.is_empty(),
+            ErrorKind::InvalidInput,
+            "Empty variable name: {:?}",
+            s
+        );
+        let value =
+            serde_json::from_str(value).unwrap_or_else(|_| JsonRecipe::String(value.to_owned()));
+        Ok(Self {
+            name: name.to_owned(),
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trackable::error::ErrorKindExt as _;
+    use trackable::result::TopLevelResult;
+
+    #[test]
+    fn labels_round_trip() -> TopLevelResult {
+        let recipe = StudiesRecipe::from_iter_safe(&[
+            "studies",
+            "--solvers",
+            r#"{"random": {}}"#,
+            "--problems",
+            r#"{"learning_curve": {"dim": 2, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 3}}"#,
+            "--repeats",
+            "2",
+            "--label",
+            "hardware=gpu",
+            "--label",
+            "note=a=b",
+        ])
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+
+        let studies = track!(recipe.studies())?;
+        assert_eq!(studies.len(), 2);
+        for study in studies {
+            assert_eq!(study.labels["hardware"], "gpu");
+            assert_eq!(study.labels["note"], "a=b");
+
+            let json = track!(serde_json::to_string(&study).map_err(Error::from))?;
+            let study: StudyRecipe = track!(serde_json::from_str(&json).map_err(Error::from))?;
+            assert_eq!(study.labels.len(), 2);
+            assert_eq!(study.labels["hardware"], "gpu");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn budget_rules_work() -> TopLevelResult {
+        let recipe = StudiesRecipe::from_iter_safe(&[
+            "studies",
+            "--solvers",
+            r#"{"random": {}}"#,
+            "--problems",
+            r#"{"learning_curve": {"dim": "${dim}", "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 3}}"#,
+            "--repeats",
+            "1",
+            "--var",
+            "dim=[2,4]",
+            "--budget-rule",
+            "20*dim",
+            "--budget-rule",
+            "dim",
+            "--label",
+            "hardware=gpu",
+        ])
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+
+        let studies = track!(recipe.studies())?;
+        assert_eq!(
+            studies
+                .iter()
+                .map(|s| (
+                    s.budget,
+                    s.labels["dim"].as_str(),
+                    s.labels["budget_rule"].as_str()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (40, "2", "20*dim"),
+                (2, "2", "1*dim"),
+                (80, "4", "20*dim"),
+                (4, "4", "1*dim")
+            ]
+        );
+        assert!(studies.iter().all(|s| s.labels["hardware"] == "gpu"));
+
+        // The variable of a budget rule must be defined.
+        let mut invalid = recipe.clone();
+        invalid.budget_rules = vec![track!("10*foo".parse())?];
+        assert!(invalid.studies().is_err());
+        assert!("x*dim".parse::<BudgetRule>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn label_vars_works() -> TopLevelResult {
+        let recipe = StudiesRecipe::from_iter_safe(&[
+            "studies",
+            "--solvers",
+            r#"{"random": {}}"#,
+            "--problems",
+            r#"{"learning_curve": {"dim": "${dim}", "curve": "${curve}", "noise": 0.0, "crossing_probability": 0.0, "steps": 3}}"#,
+            "--repeats",
+            "1",
+            "--var",
+            "dim=[2,4]",
+            "--var",
+            "curve=pow",
+            "--label-vars",
+        ])
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+
+        let studies = track!(recipe.studies())?;
+        assert_eq!(studies.len(), 2);
+        assert_eq!(studies[1].labels["dim"], "4");
+        assert_eq!(studies[1].labels["curve"], "pow");
+        assert_eq!(studies[1].budget, 20);
+        Ok(())
+    }
+
+    #[test]
+    fn filters_option_works() -> TopLevelResult {
+        let solver = r#"{"random": {}}"#;
+        let problem = r#"{"bo_standard": {"function": "BRANIN"}}"#;
+        let filter = r#"{"param_noise": {"sigma_fraction": 0.1}}"#;
+        let to_json = |filters: &[KurobakoFilterRecipe]| -> Result<serde_json::Value> {
+            track!(serde_json::to_value(filters).map_err(Error::from))
+        };
+
+        // `kurobako study` and `kurobako studies` share the option name.
+        let study = StudyRecipe::from_iter_safe(&[
+            "study",
+            "--solver",
+            solver,
+            "--problem",
+            problem,
+            "--filters",
+            filter,
+        ])
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+        let studies = StudiesRecipe::from_iter_safe(&[
+            "studies",
+            "--solvers",
+            solver,
+            "--problems",
+            problem,
+            "--filters",
+            filter,
+        ])
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+        let studies = track!(studies.studies())?;
+        assert_eq!(studies.len(), 10);
+
+        let expected = track!(to_json(&study.filters))?;
+        assert_eq!(
+            expected,
+            serde_json::json!([{"param_noise": {"sigma_fraction": 0.1}}])
+        );
+        for s in &studies {
+            assert_eq!(track!(to_json(&s.filters))?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_label_works() {
+        assert_eq!(
+            parse_label("hardware=gpu").ok(),
+            Some(("hardware".to_owned(), "gpu".to_owned()))
+        );
+        assert_eq!(
+            parse_label("empty=").ok(),
+            Some(("empty".to_owned(), "".to_owned()))
+        );
+        assert!(parse_label("hardware").is_err());
+        assert!(parse_label("=gpu").is_err());
+    }
+
+    #[test]
+    fn problem_instances_work() -> TopLevelResult {
+        let studies = |instance: &str, seed: Option<&str>| -> Result<Vec<StudyRecipe>> {
+            let mut args = vec![
+                "studies",
+                "--solvers",
+                r#"{"random": {}}"#,
+                "--problems",
+                r#"{"noise": {"problem": {"bo_standard": {"function": "BRANIN"}}, "level": 1.0}}"#,
+                "--repeats",
+                "3",
+                "--instance",
+                instance,
+            ];
+            if let Some(seed) = seed {
+                args.extend(&["--seed", seed]);
+            }
+            let recipe = StudiesRecipe::from_iter_safe(&args)
+                .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e.to_string())))?;
+            track!(recipe.studies())
+        };
+        let problem_seeds = |studies: &[StudyRecipe]| {
+            studies
+                .iter()
+                .map(|s| s.problem_seed(s.seed.unwrap_or_else(|| unreachable!())))
+                .collect::<BTreeSet<_>>()
+        };
+
+        let shared = track!(studies("shared", Some("10")))?;
+        assert_eq!(
+            shared.iter().map(|s| s.repetition).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            shared.iter().map(|s| s.seed).collect::<Vec<_>>(),
+            [Some(10), Some(11), Some(12)]
+        );
+        assert_eq!(problem_seeds(&shared).len(), 1);
+
+        // A seed is chosen if omitted.
+        let shared = track!(studies("shared", None))?;
+        assert!(shared.iter().all(|s| s.seed.is_some()));
+        assert_eq!(problem_seeds(&shared).len(), 1);
+
+        let per_repetition = track!(studies("per-repetition", Some("10")))?;
+        assert_eq!(problem_seeds(&per_repetition).len(), 3);
+        assert_eq!(
+            per_repetition[1].problem_seed(11),
+            ArcRng::new(11).split(0).seed()
+        );
+
+        // `per-repetition` is the default and omitted from JSON.
+        let json = track!(serde_json::to_value(&per_repetition[0]).map_err(Error::from))?;
+        assert!(json.get("instance").is_none());
+        assert!(json.get("repetition").is_none());
+        let json = track!(serde_json::to_value(&shared[1]).map_err(Error::from))?;
+        assert_eq!(json["instance"], "shared");
+        assert_eq!(json["repetition"], 1);
+        Ok(())
     }
 }
@@ -0,0 +1,142 @@
+//! Target values of problems (thresholds that solvers are expected to reach).
+use kurobako_core::num::OrderedFloat;
+use kurobako_core::problem::ProblemSpec;
+use kurobako_core::{Error, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Key of the problem attribute that holds the known optimum (global minimum) of a problem.
+pub const OPTIMUM_ATTR: &str = "optimum";
+
+/// Returns the known optimum of the given problem if the `optimum` attribute is set.
+pub fn optimum(spec: &ProblemSpec) -> Option<f64> {
+    spec.attrs
+        .get(OPTIMUM_ATTR)
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &f64| v.is_finite())
+}
+
+/// Options for specifying target values.
+#[derive(Debug, Clone, Default, StructOpt, Serialize)]
+#[structopt(rename_all = "kebab-case")]
+pub struct TargetOpt {
+    /// Target value applied to all the problems (can be specified multiple times).
+    #[structopt(long = "target", number_of_values = 1)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<f64>,
+
+    /// JSON file that maps problem names to their target values (e.g., `{"Branin": [1.0, 0.5]}`).
+    #[structopt(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets_file: Option<PathBuf>,
+
+    /// Tolerance from the known optimum of a problem (`optimum + tolerance` is used as a target value).
+    ///
+    /// This can be specified multiple times, and is ignored for problems without the `optimum` attribute.
+    #[structopt(long = "optimum-tolerance", number_of_values = 1)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub optimum_tolerances: Vec<f64>,
+}
+impl TargetOpt {
+    /// Returns `true` if no target values are specified.
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty() && self.targets_file.is_none() && self.optimum_tolerances.is_empty()
+    }
+
+    /// Loads the target values.
+    pub fn load(&self) -> Result<Targets> {
+        let per_problem = if let Some(path) = &self.targets_file {
+            let file = track!(File::open(path).map_err(Error::from); path)?;
+            track!(serde_json::from_reader(file).map_err(Error::from); path)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Targets {
+            global: self.targets.clone(),
+            per_problem,
+            optimum_tolerances: self.optimum_tolerances.clone(),
+        })
+    }
+}
+
+/// Target values.
+#[derive(Debug, Clone, Default)]
+pub struct Targets {
+    global: Vec<f64>,
+    per_problem: BTreeMap<String, Vec<f64>>,
+    optimum_tolerances: Vec<f64>,
+}
+impl Targets {
+    /// Returns the target values of the given problem in descending order (i.e., from the easiest one).
+    pub fn get(&self, problem: &ProblemSpec) -> Vec<f64> {
+        let near_optimum = optimum(problem).into_iter().flat_map(|optimum| {
+            self.optimum_tolerances
+                .iter()
+                .map(move |tolerance| optimum + tolerance)
+        });
+        let mut targets = self
+            .global
+            .iter()
+            .chain(self.per_problem.get(&problem.name).into_iter().flatten())
+            .copied()
+            .chain(near_optimum)
+            .filter(|t| !t.is_nan())
+            .map(OrderedFloat)
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| b.cmp(a));
+        targets.dedup();
+        targets.into_iter().map(|t| t.0).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurobako_core::domain::var;
+    use kurobako_core::problem::ProblemSpecBuilder;
+    use std::io::Write as _;
+    use trackable::result::TopLevelResult;
+
+    fn spec(name: &str, optimum: Option<&str>) -> Result<ProblemSpec> {
+        let mut spec = ProblemSpecBuilder::new(name)
+            .param(var("x").continuous(0.0, 1.0))
+            .value(var("y"));
+        if let Some(v) = optimum {
+            spec = spec.attr(OPTIMUM_ATTR, v);
+        }
+        track!(spec.finish())
+    }
+
+    #[test]
+    fn targets_work() -> TopLevelResult {
+        let mut file = track!(tempfile::NamedTempFile::new().map_err(Error::from))?;
+        track!(write!(file, r#"{{"foo": [0.5, 2.0], "bar": []}}"#).map_err(Error::from))?;
+
+        let opt = TargetOpt {
+            targets: vec![1.0, 2.0],
+            targets_file: Some(file.path().to_path_buf()),
+            optimum_tolerances: vec![0.25, 1.0],
+        };
+        let targets = track!(opt.load())?;
+        assert_eq!(targets.get(&track!(spec("foo", None))?), [2.0, 1.0, 0.5]);
+        assert_eq!(targets.get(&track!(spec("bar", None))?), [2.0, 1.0]);
+        assert_eq!(
+            targets.get(&track!(spec("bar", Some("-1.0")))?),
+            [2.0, 1.0, 0.0, -0.75]
+        );
+        let targets = track!(TargetOpt::default().load())?;
+        assert_eq!(targets.get(&track!(spec("foo", None))?), [] as [f64; 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn optimum_works() -> TopLevelResult {
+        assert_eq!(optimum(&track!(spec("foo", Some("-3.5")))?), Some(-3.5));
+        assert_eq!(optimum(&track!(spec("foo", None))?), None);
+        assert_eq!(optimum(&track!(spec("foo", Some("unknown")))?), None);
+        Ok(())
+    }
+}
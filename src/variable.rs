@@ -1,12 +1,138 @@
 //! `kurobako var` command.
-use kurobako_core::domain::{Range, VariableBuilder};
-use kurobako_core::{Error, Result};
+use kurobako_core::domain::{self, Condition, Distribution, Range, Variable, VariableBuilder};
+use kurobako_core::{Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
 use structopt::StructOpt;
 
-/// Variable.
+/// Options of the `kurobako var` command.
+///
+/// Exactly one of `--continuous`, `--discrete`, `--categorical`, `--ordinal` and `--boolean`
+/// must be specified.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct VariableOpt {
+    /// Variable name.
+    #[structopt(long)]
+    pub name: String,
+
+    /// Continuous numerical range `[LOW, HIGH)`.
+    #[structopt(
+        long,
+        number_of_values = 2,
+        value_names = &["LOW", "HIGH"],
+        allow_hyphen_values = true
+    )]
+    pub continuous: Option<Vec<f64>>,
+
+    /// Discrete numerical range `[LOW, HIGH)`.
+    #[structopt(
+        long,
+        number_of_values = 2,
+        value_names = &["LOW", "HIGH"],
+        allow_hyphen_values = true
+    )]
+    pub discrete: Option<Vec<i64>>,
+
+    /// Categorical range.
+    #[structopt(long, min_values = 1)]
+    pub categorical: Option<Vec<String>>,
+
+    /// Ordinal range (the values must be sorted in strictly ascending order).
+    #[structopt(long, min_values = 1, allow_hyphen_values = true)]
+    pub ordinal: Option<Vec<f64>>,
+
+    /// Boolean range (i.e., a categorical range of `false` and `true`).
+    #[structopt(long)]
+    pub boolean: bool,
+
+    /// Includes the upper bound of a numerical range.
+    #[structopt(long)]
+    pub inclusive_high: bool,
+
+    /// Quantization step of a numerical range.
+    #[structopt(long)]
+    pub step: Option<f64>,
+
+    /// Makes the distribution of the variable log scale.
+    #[structopt(long)]
+    pub log_uniform: bool,
+
+    /// Activation condition (e.g., `optimizer == 0`, `depth > 2` or `kernel in [1, 2]`).
+    ///
+    /// Categorical values are specified by the indices of the choices.
+    #[structopt(long)]
+    pub condition: Option<Condition>,
+
+    /// Human-readable description of the variable.
+    #[structopt(long)]
+    pub description: Option<String>,
+}
+impl VariableOpt {
+    /// Builds the variable specified by this options.
+    pub fn to_variable(&self) -> Result<Variable> {
+        let mut builder = domain::var(&self.name);
+        let mut ranges = 0;
+        if let Some(v) = &self.continuous {
+            builder = if self.inclusive_high {
+                builder.continuous_inclusive(v[0], v[1])
+            } else {
+                builder.continuous(v[0], v[1])
+            };
+            ranges += 1;
+        }
+        if let Some(v) = &self.discrete {
+            builder = builder.range(Range::Discrete {
+                low: v[0],
+                high: v[1],
+                step: 1,
+                inclusive_high: self.inclusive_high,
+            });
+            ranges += 1;
+        }
+        if let Some(choices) = &self.categorical {
+            builder = builder.categorical(choices);
+            ranges += 1;
+        }
+        if let Some(values) = &self.ordinal {
+            builder = builder.ordinal(values.iter().copied());
+            ranges += 1;
+        }
+        if self.boolean {
+            builder = builder.boolean();
+            ranges += 1;
+        }
+        track_assert!(
+            ranges == 1,
+            ErrorKind::InvalidInput,
+            "Exactly one of `--continuous`, `--discrete`, `--categorical`, `--ordinal` and `--boolean` must be specified"
+        );
+        if self.inclusive_high {
+            track_assert!(
+                self.continuous.is_some() || self.discrete.is_some(),
+                ErrorKind::InvalidInput,
+                "`--inclusive-high` is only applicable to numerical ranges"
+            );
+        }
+
+        if let Some(step) = self.step {
+            builder = builder.step(step);
+        }
+        if self.log_uniform {
+            builder = builder.log_uniform();
+        }
+        if let Some(condition) = &self.condition {
+            builder = builder.condition(condition.clone());
+        }
+        if let Some(description) = &self.description {
+            builder = builder.description(description);
+        }
+        track!(builder.finish())
+    }
+}
+
+/// Variable of a study problem (i.e., a parameter of the recipe of a study).
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 pub struct Var {
@@ -23,6 +149,27 @@ pub struct Var {
     pub range: Range,
 }
 impl Var {
+    /// Parses a JSON of either a `Var` or a `Variable` (i.e., an output of `kurobako var`).
+    ///
+    /// In the latter case, the variable name is regarded as a path.
+    pub fn parse_json(s: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum VarOrVariable {
+            Var(Var),
+            Variable(Variable),
+        }
+
+        match track!(serde_json::from_str(s).map_err(Error::from))? {
+            VarOrVariable::Var(v) => Ok(v),
+            VarOrVariable::Variable(v) => Ok(Self {
+                path: track!(v.name().parse())?,
+                log_uniform: v.distribution() == Distribution::LogUniform,
+                range: v.range().clone(),
+            }),
+        }
+    }
+
     /// Converts to `VariableBuilder`.
     pub fn to_domain_var(&self) -> VariableBuilder {
         let builder = VariableBuilder::new(&self.path.to_string()).range(self.range.clone());
@@ -0,0 +1,115 @@
+#![cfg(all(feature = "external-process", target_os = "linux"))]
+use kurobako::runner::{Runner, RunnerOpt};
+use kurobako::study::StudyRecipe;
+use kurobako_core::domain::var;
+use kurobako_core::epi::problem::ProblemMessage;
+use kurobako_core::problem::ProblemSpecBuilder;
+use kurobako_core::{Error, Result};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use trackable::result::TopLevelResult;
+use trackable::track;
+
+// Appends the PID of the process to the file given as the second argument.
+const PROLOGUE: &str = r#"echo $$ >> "$2"
+printf '%s\n' "$1""#;
+
+const WELL_BEHAVED: &str = r#"
+while read -r line; do
+  case "$line" in
+    *CREATE_EVALUATOR_CALL*) echo '{"type":"CREATE_EVALUATOR_REPLY"}';;
+    *EVALUATE_CALL*) echo '{"type":"EVALUATE_REPLY","current_step":1,"values":[0.5]}';;
+    *SHUTDOWN_CAST*) exit 0;;
+  esac
+done"#;
+
+const IGNORES_SHUTDOWN: &str = r#"
+while read -r line; do
+  case "$line" in
+    *CREATE_EVALUATOR_CALL*) echo '{"type":"CREATE_EVALUATOR_REPLY"}';;
+    *EVALUATE_CALL*) echo '{"type":"EVALUATE_REPLY","current_step":1,"values":[0.5]}';;
+  esac
+done
+while true; do sleep 1; done"#;
+
+const FAILS_TO_EVALUATE: &str = r#"
+while read -r line; do
+  case "$line" in
+    *CREATE_EVALUATOR_CALL*) echo '{"type":"CREATE_EVALUATOR_REPLY"}';;
+    *EVALUATE_CALL*) exit 1;;
+  esac
+done"#;
+
+fn study(script: &str, pid_file: &Path) -> Result<StudyRecipe> {
+    let spec = track!(ProblemSpecBuilder::new("child")
+        .param(var("x").continuous(0.0, 1.0))
+        .value(var("y"))
+        .finish())?;
+    let spec = track!(
+        serde_json::to_string(&ProblemMessage::ProblemSpecCast { spec }).map_err(Error::from)
+    )?;
+    let recipe = serde_json::json!({
+        "solver": {"random": {}},
+        "problem": {"command": {
+            "path": "sh",
+            "args": ["-c", format!("{}{}", PROLOGUE, script), "sh", spec, pid_file],
+            "shutdown_grace_period": 0
+        }},
+        "budget": 3,
+        "concurrency": 1,
+        "scheduling": "RANDOM"
+    });
+    track!(serde_json::from_value(recipe).map_err(Error::from))
+}
+
+fn run(studies: Vec<StudyRecipe>) -> Result<()> {
+    let opt = RunnerOpt {
+        parallelism: NonZeroUsize::new(2).unwrap_or_else(|| unreachable!()),
+        quiet: true,
+        ..RunnerOpt::default()
+    };
+    track!(Runner::new(opt).run_all(studies, |_| Ok(())))
+}
+
+// Zombies also have `/proc/<pid>`, so this checks that the children have been reaped.
+fn surviving_children(pid_file: &Path) -> Result<Vec<String>> {
+    let pids = track!(fs::read_to_string(pid_file).map_err(Error::from))?;
+    Ok(pids
+        .lines()
+        .filter(|pid| Path::new(&format!("/proc/{}", pid)).exists())
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+#[test]
+fn no_child_survives_after_success() -> TopLevelResult {
+    let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+    let pid_file = dir.path().join("pids");
+    let studies = vec![
+        track!(study(WELL_BEHAVED, &pid_file))?,
+        track!(study(IGNORES_SHUTDOWN, &pid_file))?,
+    ];
+    track!(run(studies))?;
+
+    let pids = track!(fs::read_to_string(&pid_file).map_err(Error::from))?;
+    assert_eq!(pids.lines().count(), 2);
+    assert_eq!(track!(surviving_children(&pid_file))?, Vec::<String>::new());
+    Ok(())
+}
+
+#[test]
+fn no_child_survives_after_failure() -> TopLevelResult {
+    let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+    let pid_file = dir.path().join("pids");
+    let studies = vec![
+        track!(study(IGNORES_SHUTDOWN, &pid_file))?,
+        track!(study(FAILS_TO_EVALUATE, &pid_file))?,
+    ];
+    assert!(run(studies).is_err());
+
+    let pids = track!(fs::read_to_string(&pid_file).map_err(Error::from))?;
+    assert!(pids.lines().count() >= 1);
+    assert_eq!(track!(surviving_children(&pid_file))?, Vec::<String>::new());
+    Ok(())
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "external-process")]
+use kurobako::solver::KurobakoSolverRecipe;
+use kurobako_core::Error;
+use std::fs;
+use std::process::Command;
+use trackable::result::TopLevelResult;
+use trackable::track;
+
+fn kurobako() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_kurobako"))
+}
+
+#[test]
+fn init_works() -> TopLevelResult {
+    let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+    let status = track!(kurobako()
+        .arg("init")
+        .arg(dir.path())
+        .arg("--with-external-problem")
+        .status()
+        .map_err(Error::from))?;
+    assert!(status.success());
+
+    let json =
+        track!(fs::read_to_string(dir.path().join("solvers/optuna.json")).map_err(Error::from))?;
+    let recipe: KurobakoSolverRecipe = track!(serde_json::from_str(&json).map_err(Error::from))?;
+    let recipe = track!(serde_json::to_value(&recipe).map_err(Error::from))?;
+    assert!(recipe.get("optuna").is_some());
+
+    // Existing files are not overwritten without `--force`.
+    let status = track!(kurobako()
+        .arg("init")
+        .arg(dir.path())
+        .status()
+        .map_err(Error::from))?;
+    assert!(!status.success());
+
+    // Optuna is not necessarily installed, so only the random solver is used here.
+    let status = track!(Command::new("sh")
+        .arg(dir.path().join("run.sh"))
+        .env("KUROBAKO", env!("CARGO_BIN_EXE_kurobako"))
+        .env("SOLVERS", "random")
+        .env("BUDGET", "2")
+        .env("REPEATS", "1")
+        .status()
+        .map_err(Error::from))?;
+    assert!(status.success());
+
+    let results = track!(fs::read_to_string(dir.path().join("results.json")).map_err(Error::from))?;
+    assert_eq!(results.lines().count(), 3);
+
+    let report = track!(fs::read_to_string(dir.path().join("report.md")).map_err(Error::from))?;
+    assert!(report.contains("Quadratic"));
+    Ok(())
+}
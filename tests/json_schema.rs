@@ -0,0 +1,157 @@
+#![cfg(all(
+    feature = "json-schema",
+    feature = "external-process",
+    target_os = "linux"
+))]
+#[macro_use]
+extern crate trackable;
+
+use jsonschema::JSONSchema;
+use kurobako::runner::{Runner, RunnerOpt};
+use kurobako::spec::SchemaTarget;
+use kurobako::study::StudyRecipe;
+use kurobako_core::epi::problem::ProblemMessage;
+use kurobako_core::{Error, ErrorKind, Result};
+use std::fs;
+use std::io::Write as _;
+use std::path::PathBuf;
+use trackable::result::TopLevelResult;
+
+/// Returns the path of the `random_solver` example (built by `cargo test` together with this test).
+fn example_path() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap_or_else(|e| panic!("{}", e));
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push("examples");
+    path.push(format!("random_solver{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{:?} is not found (run `cargo build --examples`)",
+        path
+    );
+    path
+}
+
+fn compile(target: SchemaTarget) -> Result<JSONSchema> {
+    let schema = track!(target.json_schema())?;
+    match JSONSchema::compile(&schema) {
+        Ok(schema) => Ok(schema),
+        Err(e) => track_panic!(ErrorKind::Other, "{:?}: {}", target, e),
+    }
+}
+
+fn validate(schema: &JSONSchema, instance: &serde_json::Value) -> Result<()> {
+    if let Err(errors) = schema.validate(instance) {
+        let errors = errors.map(|e| e.to_string()).collect::<Vec<_>>();
+        track_panic!(
+            ErrorKind::InvalidInput,
+            "{}: {}",
+            instance,
+            errors.join("; ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns the complete lines written to the given log file.
+fn logged_messages(path: &std::path::Path) -> Result<Vec<serde_json::Value>> {
+    let log = track!(fs::read_to_string(path).map_err(Error::from))?;
+    let mut lines = log.split('\n').collect::<Vec<_>>();
+    lines.pop(); // The last one may be written partially.
+    lines
+        .into_iter()
+        .map(|line| track!(serde_json::from_str(line).map_err(Error::from); line))
+        .collect()
+}
+
+#[test]
+fn recorded_messages_conform_to_schemas() -> TopLevelResult {
+    // An external solver whose messages are recorded in both directions.
+    let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+    let to_solver = dir.path().join("to_solver.jsonl");
+    let from_solver = dir.path().join("from_solver.jsonl");
+    let script = dir.path().join("solver.sh");
+    {
+        let mut file = track!(fs::File::create(&script).map_err(Error::from))?;
+        track!(writeln!(
+            file,
+            "#!/bin/sh\ntee -a {:?} | {:?} | tee -a {:?}",
+            to_solver,
+            example_path(),
+            from_solver
+        )
+        .map_err(Error::from))?;
+    }
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        track!(
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).map_err(Error::from)
+        )?;
+    }
+
+    let recipe: StudyRecipe = track!(serde_json::from_value(serde_json::json!({
+        "solver": {"command": {"path": script, "args": [], "timeout": 10}},
+        "problem": {"learning_curve": {
+            "dim": 3, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 10
+        }},
+        "budget": 20,
+        "concurrency": 2,
+        "scheduling": "RANDOM"
+    }))
+    .map_err(Error::from))?;
+    let runner = Runner::new(RunnerOpt {
+        quiet: true,
+        ..RunnerOpt::default()
+    });
+    let record = track!(runner.run_study(&recipe, 0))?;
+
+    let schema = track!(compile(SchemaTarget::SolverMessage))?;
+    let mut types = Vec::new();
+    for path in &[&to_solver, &from_solver] {
+        for message in track!(logged_messages(path))? {
+            track!(validate(&schema, &message))?;
+            types.push(message["type"].as_str().unwrap_or_default().to_owned());
+        }
+    }
+    for t in &[
+        "SOLVER_SPEC_CAST",
+        "CREATE_SOLVER_CAST",
+        "ASK_CALL",
+        "ASK_REPLY",
+        "TELL_CALL",
+    ] {
+        track_assert!(types.iter().any(|x| x == t), ErrorKind::Other; t, types);
+    }
+
+    let problem_spec = track!(serde_json::to_value(&record.problem.spec).map_err(Error::from))?;
+    track!(validate(
+        &track!(compile(SchemaTarget::ProblemSpec))?,
+        &problem_spec
+    ))?;
+    let solver_spec = track!(serde_json::to_value(&record.solver.spec).map_err(Error::from))?;
+    track!(validate(
+        &track!(compile(SchemaTarget::SolverSpec))?,
+        &solver_spec
+    ))?;
+    let cast = ProblemMessage::ProblemSpecCast {
+        spec: record.problem.spec.clone(),
+    };
+    let cast = track!(serde_json::to_value(&cast).map_err(Error::from))?;
+    track!(validate(
+        &track!(compile(SchemaTarget::ProblemMessage))?,
+        &cast
+    ))?;
+
+    let record = track!(serde_json::to_value(&record).map_err(Error::from))?;
+    track!(validate(
+        &track!(compile(SchemaTarget::StudyRecord))?,
+        &record
+    ))?;
+
+    // Messages that violate the schema are rejected.
+    let broken = serde_json::json!({"type": "ASK_REPLY", "trial": {"id": "foo"}});
+    assert!(validate(&schema, &broken).is_err());
+    Ok(())
+}
@@ -0,0 +1,143 @@
+#[macro_use]
+extern crate trackable;
+
+use kurobako_core::{Error, ErrorKind, Result};
+use std::fs;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use trackable::result::TopLevelResult;
+
+/// Returns the JSON of a study that has a single trial.
+fn study_json(seed: u64) -> String {
+    serde_json::json!({
+        "start_time": "2020-01-01T00:00:00+00:00",
+        "end_time": "2020-01-01T00:00:01+00:00",
+        "seed": seed,
+        "budget": 1,
+        "concurrency": 1,
+        "scheduling": "RANDOM",
+        "solver": {
+            "recipe": {"random": {}},
+            "spec": {"name": "Random", "attrs": {}, "capabilities": []}
+        },
+        "problem": {
+            "recipe": {"learning_curve": {
+                "dim": 1, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 1
+            }},
+            "spec": {
+                "name": "Foo",
+                "attrs": {},
+                "params_domain": [{
+                    "name": "x",
+                    "range": {"type": "CONTINUOUS", "low": 0.0, "high": 1.0},
+                    "distribution": "UNIFORM"
+                }],
+                "values_domain": [{
+                    "name": "Loss",
+                    "range": {"type": "CONTINUOUS"},
+                    "distribution": "UNIFORM"
+                }],
+                "steps": 1
+            }
+        },
+        "trials": [{
+            "thread_id": 0,
+            "params": [0.5],
+            "evaluations": [{
+                "values": [seed as f64],
+                "start_step": 0,
+                "end_step": 1,
+                "ask_elapsed": 0.0,
+                "tell_elapsed": 0.0,
+                "evaluate_elapsed": 0.0
+            }]
+        }]
+    })
+    .to_string()
+}
+
+/// Runs `command` with the given standard input and returns the output.
+fn run(command: &mut Command, stdin: &[u8]) -> Result<Output> {
+    let mut child = track!(command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::from))?;
+    {
+        let mut input = track_assert_some!(child.stdin.take(), ErrorKind::Bug);
+        track!(input.write_all(stdin).map_err(Error::from))?;
+    }
+    track!(child.wait_with_output().map_err(Error::from))
+}
+
+/// Writes the given content to `path` compressed by `compression` (`gzip` or `zstd`).
+fn write_compressed(path: &Path, compression: &str, content: &str) -> Result<()> {
+    let file = track!(fs::File::create(path).map_err(Error::from))?;
+    if compression == "gzip" {
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        track!(encoder.write_all(content.as_bytes()).map_err(Error::from))?;
+        track!(encoder.finish().map_err(Error::from))?;
+    } else {
+        track!(zstd::stream::copy_encode(content.as_bytes(), file, 0).map_err(Error::from))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn report_reads_files_and_stdin() -> TopLevelResult {
+    let dir = track!(tempfile::tempdir().map_err(Error::from))?;
+    let plain = dir.path().join("plain.json");
+    let gzip = dir.path().join("gzip.json.gz");
+    let zstd = dir.path().join("zstd.json.zst");
+    let broken = dir.path().join("broken.json");
+    track!(fs::write(&plain, study_json(0)).map_err(Error::from))?;
+    track!(write_compressed(&gzip, "gzip", &study_json(2)))?;
+    track!(write_compressed(&zstd, "zstd", &study_json(3)))?;
+
+    // The second record is malformed, and it starts right after the first line.
+    let valid = study_json(4);
+    track!(fs::write(&broken, format!("{}\n{{\"seed\": oops}}\n", valid)).map_err(Error::from))?;
+
+    let output = track!(run(
+        Command::new(env!("CARGO_BIN_EXE_kurobako"))
+            .arg("report")
+            .arg(&plain)
+            .arg("-")
+            .arg(&gzip)
+            .arg(&zstd)
+            .arg(&broken),
+        study_json(1).as_bytes()
+    ))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stdout.contains("- repeats: 5\n"), "{}", stdout);
+
+    // The warning names the file and the byte offset of the malformed record.
+    let warning = track_assert_some!(
+        stderr
+            .lines()
+            .find(|l| l.starts_with("Warning: skipped a malformed record")),
+        ErrorKind::Other; stderr
+    );
+    assert!(warning.contains(&format!("{:?}", broken)), "{}", warning);
+    // The error is detected right after reading the unexpected `o`.
+    let offset = valid.len() + 1 + r#"{"seed": o"#.len();
+    assert!(
+        warning.contains(&format!("at byte offset {})", offset)),
+        "{}",
+        warning
+    );
+    assert!(stderr.contains("skipped 1 malformed records in total"));
+
+    // Without files, the standard input is read.
+    let output = track!(run(
+        Command::new(env!("CARGO_BIN_EXE_kurobako")).arg("report"),
+        format!("{}\n{}", study_json(0), study_json(1)).as_bytes()
+    ))?;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("- repeats: 2\n"));
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! Checks that `--low-memory` bounds the memory used to load large study records.
+use kurobako::load::LoadOpt;
+use kurobako_core::Result;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use trackable::result::TopLevelResult;
+use trackable::track;
+
+/// Allocator that keeps track of the peak of the allocated bytes.
+struct PeakAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+/// Returns a newline-delimited JSON of `studies` synthetic studies having `trials` trials each.
+///
+/// The values of the trials are pseudo-random, so only a few trials improve the best value.
+fn synthetic_studies(studies: usize, trials: usize) -> String {
+    let mut json = String::new();
+    for seed in 0..studies {
+        let mut line = String::new();
+        for i in 0..trials {
+            if i > 0 {
+                line.push(',');
+            }
+            let value = ((i * 7919 + seed * 104_729) % 10_007) as f64 / 10_007.0;
+            write!(
+                line,
+                r#"{{"thread_id":0,"params":[{value}],"evaluations":[{{"values":[{value}],"start_step":{i},"end_step":{j},"ask_elapsed":0.001,"tell_elapsed":0.001,"evaluate_elapsed":0.01}}]}}"#,
+                value = value,
+                i = i,
+                j = i + 1
+            )
+            .unwrap_or_else(|e| panic!("{}", e));
+        }
+        write!(
+            json,
+            r#"{{"start_time":"2020-01-01T00:00:00+00:00","end_time":"2020-01-01T00:01:00+00:00","seed":{seed},"budget":{trials},"concurrency":1,"scheduling":"RANDOM","solver":{{"recipe":{{"random":{{}}}},"spec":{{"name":"Random","attrs":{{}},"capabilities":[]}}}},"problem":{{"recipe":{{"learning_curve":{{"dim":1,"curve":"pow","noise":0.0,"crossing_probability":0.0,"steps":1}}}},"spec":{{"name":"Synthetic","attrs":{{}},"params_domain":[{{"name":"x","range":{{"type":"CONTINUOUS","low":0.0,"high":1.0}},"distribution":"UNIFORM"}}],"values_domain":[{{"name":"Loss","range":{{"type":"CONTINUOUS"}},"distribution":"UNIFORM"}}],"steps":1}}}},"trials":[{line}]}}"#,
+            seed = seed,
+            trials = trials,
+            line = line
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        json.push('\n');
+    }
+    json
+}
+
+/// Memory allocated while loading study records.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    /// Peak of the allocated bytes.
+    peak: usize,
+
+    /// Bytes held by the loaded records.
+    retained: usize,
+}
+
+/// Loads the given JSON and measures the memory allocated while loading it.
+fn measure(json: &str, low_memory: bool) -> Result<Usage> {
+    let opt = LoadOpt {
+        low_memory,
+        ..LoadOpt::default()
+    };
+    let base = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(base, Ordering::SeqCst);
+    let studies = track!(opt.load(json.as_bytes()))?;
+    let usage = Usage {
+        peak: PEAK.load(Ordering::SeqCst) - base,
+        retained: ALLOCATED.load(Ordering::SeqCst) - base,
+    };
+    assert_eq!(studies.len(), json.lines().count());
+    Ok(usage)
+}
+
+#[test]
+fn low_memory_bounds_memory_usage() -> TopLevelResult {
+    const TRIALS: usize = 5_000;
+    let one = synthetic_studies(1, TRIALS);
+    let many = synthetic_studies(10, TRIALS);
+
+    // Only one study is held in full at a time, so the peak barely depends on the number of studies.
+    let one_study = track!(measure(&one, true))?;
+    let low_memory = track!(measure(&many, true))?;
+    assert!(
+        low_memory.peak < one_study.peak * 3 / 2,
+        "{:?}, {:?}",
+        one_study,
+        low_memory
+    );
+
+    // Without the option, all the trials are kept.
+    let full = track!(measure(&many, false))?;
+    assert!(
+        low_memory.retained * 20 < full.retained,
+        "{:?}, {:?}",
+        low_memory,
+        full
+    );
+    assert!(full.peak > low_memory.peak + full.retained / 2);
+    Ok(())
+}
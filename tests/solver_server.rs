@@ -0,0 +1,70 @@
+#![cfg(feature = "external-process")]
+use kurobako::runner::{Runner, RunnerOpt};
+use kurobako::study::StudyRecipe;
+use std::path::PathBuf;
+use trackable::result::TopLevelResult;
+use trackable::track;
+
+/// Returns the path of the `random_solver` example (built by `cargo test` together with this test).
+fn example_path() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap_or_else(|e| panic!("{}", e));
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push("examples");
+    path.push(format!("random_solver{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{:?} is not found (run `cargo build --examples`)",
+        path
+    );
+    path
+}
+
+fn study(solver: serde_json::Value) -> serde_json::Result<StudyRecipe> {
+    serde_json::from_value(serde_json::json!({
+        "solver": solver,
+        "problem": {"learning_curve": {
+            "dim": 3, "curve": "pow", "noise": 0.0, "crossing_probability": 0.0, "steps": 10
+        }},
+        "budget": 20,
+        "concurrency": 2,
+        "scheduling": "RANDOM"
+    }))
+}
+
+#[test]
+fn random_solver_server_works() -> TopLevelResult {
+    let in_process =
+        track!(study(serde_json::json!({"random": {}})).map_err(kurobako_core::Error::from))?;
+    let external = track!(study(serde_json::json!({"command": {
+        "path": example_path(),
+        "args": [],
+        "timeout": 10
+    }}))
+    .map_err(kurobako_core::Error::from))?;
+
+    let runner = Runner::new(RunnerOpt {
+        quiet: true,
+        ..RunnerOpt::default()
+    });
+    let expected = track!(runner.run_study(&in_process, 0))?;
+    let actual = track!(runner.run_study(&external, 0))?;
+
+    assert_eq!(actual.solver.spec.name, expected.solver.spec.name);
+    assert_eq!(
+        actual.solver.spec.capabilities,
+        expected.solver.spec.capabilities
+    );
+    assert_eq!(actual.trials.len(), expected.trials.len());
+    assert_eq!(
+        actual.best_value().is_some(),
+        expected.best_value().is_some()
+    );
+    for trial in &actual.trials {
+        track!(actual.problem.spec.validate_params(&trial.params))?;
+        assert_eq!(trial.evaluations.len(), 1);
+    }
+    Ok(())
+}